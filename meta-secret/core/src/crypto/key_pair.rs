@@ -178,6 +178,18 @@ pub mod test {
         assert_ne!(signature.to_string(), another_signature.to_string());
     }
 
+    #[test]
+    fn test_dsa_key_pair_verify() {
+        let key_pair = DsaKeyPair::generate();
+        let text = "Test message to sign";
+
+        let signature = key_pair.sign(text.to_string());
+
+        assert!(key_pair.pk().verify(text, &signature).is_ok());
+        assert!(key_pair.pk().verify("Tampered message", &signature).is_err());
+        assert!(DsaKeyPair::generate().pk().verify(text, &signature).is_err());
+    }
+
     #[test]
     fn test_dsa_key_pair_encode() {
         let key_pair = DsaKeyPair::generate();
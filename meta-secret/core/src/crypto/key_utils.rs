@@ -1,39 +1,16 @@
 use anyhow::Result;
-use crate::crypto::key_pair::{KeyPair, TransportDsaKeyPair};
 use crate::crypto::keys::TransportSk;
-use std::fs;
-use std::path::Path;
-use tracing::info;
-use serde_json;
+use crate::crypto::master_key::{MasterKeyProvider, PlainKeyFile};
 
 /// Loads a master key from the specified file path or creates a new one if the file doesn't exist
 pub fn load_or_create_master_key(key_file_path: &str) -> Result<TransportSk> {
-    if Path::new(key_file_path).exists() {
-        // Read the key from file
-        info!("Reading master key from file: {}", key_file_path);
-        let file = fs::File::open(key_file_path)?;
-        
-        // Deserialize the key using serde_json
-        let master_key: TransportSk = serde_json::from_reader(file)?;
-        
-        Ok(master_key)
-    } else {
-        // Generate a new key
-        info!("Generating new master key and saving to: {}", key_file_path);
-        let key_pair = TransportDsaKeyPair::generate();
-        let master_key = key_pair.sk();
-        
-        // Serialize and save the key using serde_json
-        let file = fs::File::create(key_file_path)?;
-        serde_json::to_writer_pretty(file, &master_key)?;
-        
-        Ok(master_key)
-    }
+    PlainKeyFile::new(key_file_path).master_key()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::crypto::key_pair::{KeyPair, TransportDsaKeyPair};
     use std::fs;
     use std::path::Path;
 
@@ -1,12 +1,16 @@
 use crate::crypto::encoding::base64::Base64Text;
-use crate::crypto::key_pair::{DsaKeyPair, KeyPair, TransportDsaKeyPair};
+use crate::crypto::key_pair::{
+    DalekPublicKey, DalekSignature, DsaKeyPair, KeyPair, TransportDsaKeyPair,
+};
 use crate::crypto::utils::U64IdUrlEnc;
 use crate::node::common::model::crypto::aead::{AeadCipherText, AeadPlainText, EncryptedMessage};
 use crate::node::common::model::crypto::channel::{CommunicationChannel, LoopbackChannel};
 use crate::node::common::model::device::common::DeviceId;
 use crate::secret::shared_secret::PlainText;
+use crate::CoreResult;
 use age::x25519::{Identity, Recipient};
 use anyhow::{anyhow, bail, Result};
+use sha2::{Digest, Sha256};
 use std::str::FromStr;
 use wasm_bindgen::prelude::wasm_bindgen;
 
@@ -28,6 +32,16 @@ pub struct OpenBox {
 #[wasm_bindgen(getter_with_clone)]
 pub struct DsaPk(pub Base64Text);
 
+impl DsaPk {
+    /// Verify a signature produced by [`DsaKeyPair::sign`] over the same text
+    pub fn verify(&self, text: &str, signature: &Base64Text) -> CoreResult<()> {
+        let pk = DalekPublicKey::try_from(&self.0)?;
+        let signature = DalekSignature::try_from(signature)?;
+        pk.verify_strict(text.as_bytes(), &signature)?;
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[wasm_bindgen(getter_with_clone)]
@@ -104,6 +118,14 @@ impl OpenBox {
     pub fn transport_pk(&self) -> &TransportPk {
         &self.transport_pk
     }
+
+    /// Short hex digest of the public keys, people compare it to make sure they see the same device
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.dsa_pk.0.base64_str().as_bytes());
+        hasher.update(self.transport_pk.0.base64_str().as_bytes());
+        hex::encode(&hasher.finalize()[..16])
+    }
 }
 
 impl From<&SecretBox> for OpenBox {
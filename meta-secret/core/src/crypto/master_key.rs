@@ -0,0 +1,549 @@
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::key_pair::{KeyPair, TransportDsaKeyPair};
+use crate::crypto::keys::TransportSk;
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::TryRngCore;
+use rand::rngs::OsRng;
+use secrecy::{ExposeSecret, ExposeSecretMut, SecretBox, SecretString};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use tracing::info;
+
+const ENCRYPTED_KEY_FILE_VERSION: u32 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+
+/// Gives access to the master key that unlocks the device credentials
+pub trait MasterKeyProvider {
+    /// Returns the master key, providers that store the key create it on the first run
+    fn master_key(&self) -> Result<TransportSk>;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MasterKeyError {
+    #[error("Wrong passphrase for the master key file {0:?}")]
+    WrongPassphrase(PathBuf),
+    #[error("The master key file {0:?} is not encrypted")]
+    NotEncrypted(PathBuf),
+    #[error(
+        "Invalid master key source: {0:?}, expected file, passphrase-file, env:<VAR>, stdin or command:<CMD>"
+    )]
+    InvalidSource(String),
+}
+
+/// Where the master key comes from
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum MasterKeySource {
+    /// Key file next to the database, encrypted if a passphrase has been set
+    #[default]
+    File,
+    /// Key file encrypted with a passphrase, a new key is encrypted right away
+    PassphraseFile,
+    /// Environment variable holding the key, for CI
+    Env { var: String },
+    /// The key is read from stdin, for CI
+    Stdin,
+    /// Shell command that prints the key, e.g. a call to a secret manager
+    Command { command: String },
+}
+
+impl MasterKeySource {
+    /// Builds the provider, the passphrase is only asked for if the key file is encrypted
+    pub fn provider<F>(&self, key_file: &Path, passphrase: F) -> Result<Box<dyn MasterKeyProvider>>
+    where
+        F: FnOnce() -> Result<SecretString>,
+    {
+        let provider: Box<dyn MasterKeyProvider> = match self {
+            MasterKeySource::File => match KeyFileKind::of(key_file)? {
+                Some(KeyFileKind::Encrypted) => {
+                    Box::new(PassphraseKeyFile::new(key_file, passphrase()?))
+                }
+                _ => Box::new(PlainKeyFile::new(key_file)),
+            },
+            MasterKeySource::PassphraseFile => {
+                if let Some(KeyFileKind::Plain) = KeyFileKind::of(key_file)? {
+                    bail!(MasterKeyError::NotEncrypted(key_file.to_path_buf()));
+                }
+                Box::new(PassphraseKeyFile::new(key_file, passphrase()?))
+            }
+            MasterKeySource::Env { var } => Box::new(EnvMasterKey { var: var.clone() }),
+            MasterKeySource::Stdin => Box::new(StdinMasterKey),
+            MasterKeySource::Command { command } => Box::new(CommandMasterKey {
+                command: command.clone(),
+            }),
+        };
+        Ok(provider)
+    }
+}
+
+impl FromStr for MasterKeySource {
+    type Err = MasterKeyError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let invalid = || MasterKeyError::InvalidSource(source.to_string());
+
+        match source.split_once(':') {
+            None => match source {
+                "file" => Ok(MasterKeySource::File),
+                "passphrase-file" => Ok(MasterKeySource::PassphraseFile),
+                "stdin" => Ok(MasterKeySource::Stdin),
+                _ => Err(invalid()),
+            },
+            Some((_, "")) => Err(invalid()),
+            Some(("env", var)) => Ok(MasterKeySource::Env {
+                var: var.to_string(),
+            }),
+            Some(("command", command)) => Ok(MasterKeySource::Command {
+                command: command.to_string(),
+            }),
+            Some(_) => Err(invalid()),
+        }
+    }
+}
+
+impl Display for MasterKeySource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MasterKeySource::File => write!(f, "file"),
+            MasterKeySource::PassphraseFile => write!(f, "passphrase-file"),
+            MasterKeySource::Env { var } => write!(f, "env:{}", var),
+            MasterKeySource::Stdin => write!(f, "stdin"),
+            MasterKeySource::Command { command } => write!(f, "command:{}", command),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyFileKind {
+    Plain,
+    Encrypted,
+}
+
+impl KeyFileKind {
+    /// Detects the format of the key file, `None` if there is no file yet
+    pub fn of(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the master key file {:?}", path))?;
+        if serde_json::from_str::<EncryptedKeyFile>(&content).is_ok() {
+            return Ok(Some(KeyFileKind::Encrypted));
+        }
+        if serde_json::from_str::<TransportSk>(&content).is_ok() {
+            return Ok(Some(KeyFileKind::Plain));
+        }
+        bail!("Unknown format of the master key file {:?}", path)
+    }
+}
+
+/// Unencrypted key file, the key is stored as json
+pub struct PlainKeyFile {
+    path: PathBuf,
+}
+
+impl PlainKeyFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn save(&self, master_key: &TransportSk) -> Result<()> {
+        let json = serde_json::to_vec_pretty(master_key)?;
+        write_key_file(&self.path, &json)
+    }
+}
+
+impl MasterKeyProvider for PlainKeyFile {
+    fn master_key(&self) -> Result<TransportSk> {
+        if self.path.exists() {
+            info!("Reading master key from file: {:?}", self.path);
+            let file = fs::File::open(&self.path)?;
+            let master_key: TransportSk = serde_json::from_reader(file)?;
+            Ok(master_key)
+        } else {
+            info!("Generating new master key and saving to: {:?}", self.path);
+            let master_key = TransportDsaKeyPair::generate().sk();
+            self.save(&master_key)?;
+            Ok(master_key)
+        }
+    }
+}
+
+/// Argon2id settings, stored in the key file so that they can be raised later
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// OWASP recommendation for Argon2id
+    fn default() -> Self {
+        Self {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive_key(
+        &self,
+        passphrase: &SecretString,
+        salt: &[u8],
+    ) -> Result<SecretBox<[u8; KEY_LENGTH]>> {
+        let params = Params::new(
+            self.memory_kib,
+            self.iterations,
+            self.parallelism,
+            Some(KEY_LENGTH),
+        )
+        .map_err(|e| anyhow!("Invalid Argon2id parameters: {}", e))?;
+
+        let mut key = SecretBox::new(Box::new([0u8; KEY_LENGTH]));
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(
+                passphrase.expose_secret().as_bytes(),
+                salt,
+                key.expose_secret_mut(),
+            )
+            .map_err(|e| anyhow!("Failed to derive the key from the passphrase: {}", e))?;
+        Ok(key)
+    }
+}
+
+/// The master key encrypted with XChaCha20-Poly1305,
+/// the encryption key is derived from a passphrase with Argon2id
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct EncryptedKeyFile {
+    version: u32,
+    kdf: KdfParams,
+    salt: Base64Text,
+    nonce: Base64Text,
+    cipher_text: Base64Text,
+}
+
+/// Key file protected with a passphrase
+pub struct PassphraseKeyFile {
+    path: PathBuf,
+    passphrase: SecretString,
+    kdf: KdfParams,
+}
+
+impl PassphraseKeyFile {
+    pub fn new(path: impl Into<PathBuf>, passphrase: SecretString) -> Self {
+        Self {
+            path: path.into(),
+            passphrase,
+            kdf: KdfParams::default(),
+        }
+    }
+
+    /// Argon2id settings for newly written files, existing files keep their own
+    pub fn with_kdf_params(mut self, kdf: KdfParams) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    pub fn save(&self, master_key: &TransportSk) -> Result<()> {
+        let mut salt = [0u8; SALT_LENGTH];
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.try_fill_bytes(&mut salt)?;
+        OsRng.try_fill_bytes(&mut nonce)?;
+
+        let key = self.kdf.derive_key(&self.passphrase, &salt)?;
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(key.expose_secret()));
+        let plain_text = serde_json::to_vec(master_key)?;
+        let cipher_text = cipher
+            .encrypt(XNonce::from_slice(&nonce), plain_text.as_slice())
+            .map_err(|_| anyhow!("Failed to encrypt the master key"))?;
+
+        let key_file = EncryptedKeyFile {
+            version: ENCRYPTED_KEY_FILE_VERSION,
+            kdf: self.kdf,
+            salt: Base64Text::from(salt.as_slice()),
+            nonce: Base64Text::from(nonce.as_slice()),
+            cipher_text: Base64Text::from(cipher_text),
+        };
+        write_key_file(&self.path, &serde_json::to_vec_pretty(&key_file)?)
+    }
+
+    /// Re-encrypts the key file with a new passphrase
+    pub fn change_passphrase(self, new_passphrase: SecretString) -> Result<Self> {
+        let master_key = self.master_key()?;
+        let key_file = Self {
+            passphrase: new_passphrase,
+            ..self
+        };
+        key_file.save(&master_key)?;
+        Ok(key_file)
+    }
+
+    fn decrypt(&self, key_file: EncryptedKeyFile) -> Result<TransportSk> {
+        if key_file.version != ENCRYPTED_KEY_FILE_VERSION {
+            bail!(
+                "Unsupported version of the master key file {:?}: {}",
+                self.path,
+                key_file.version
+            );
+        }
+
+        let salt = Vec::<u8>::try_from(&key_file.salt)?;
+        let nonce = Vec::<u8>::try_from(&key_file.nonce)?;
+        let cipher_text = Vec::<u8>::try_from(&key_file.cipher_text)?;
+        if nonce.len() != NONCE_LENGTH {
+            bail!("Invalid nonce in the master key file {:?}", self.path);
+        }
+
+        let key = key_file.kdf.derive_key(&self.passphrase, &salt)?;
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(key.expose_secret()));
+        let plain_text = cipher
+            .decrypt(XNonce::from_slice(&nonce), cipher_text.as_slice())
+            .map_err(|_| MasterKeyError::WrongPassphrase(self.path.clone()))?;
+
+        Ok(serde_json::from_slice(&plain_text)?)
+    }
+}
+
+impl MasterKeyProvider for PassphraseKeyFile {
+    fn master_key(&self) -> Result<TransportSk> {
+        if !self.path.exists() {
+            info!("Generating new encrypted master key: {:?}", self.path);
+            let master_key = TransportDsaKeyPair::generate().sk();
+            self.save(&master_key)?;
+            return Ok(master_key);
+        }
+
+        let content = fs::read_to_string(&self.path)?;
+        let Ok(key_file) = serde_json::from_str::<EncryptedKeyFile>(&content) else {
+            bail!(MasterKeyError::NotEncrypted(self.path.clone()));
+        };
+        self.decrypt(key_file)
+    }
+}
+
+/// The key is taken from an environment variable
+pub struct EnvMasterKey {
+    pub var: String,
+}
+
+impl MasterKeyProvider for EnvMasterKey {
+    fn master_key(&self) -> Result<TransportSk> {
+        let Ok(value) = std::env::var(&self.var) else {
+            bail!(
+                "Environment variable {} with the master key is not set",
+                self.var
+            );
+        };
+        parse_master_key(&value)
+    }
+}
+
+/// The key is read from stdin until EOF
+pub struct StdinMasterKey;
+
+impl MasterKeyProvider for StdinMasterKey {
+    fn master_key(&self) -> Result<TransportSk> {
+        let mut value = String::new();
+        std::io::stdin().read_to_string(&mut value)?;
+        parse_master_key(&value)
+    }
+}
+
+/// The key is printed to stdout by a shell command
+pub struct CommandMasterKey {
+    pub command: String,
+}
+
+impl MasterKeyProvider for CommandMasterKey {
+    fn master_key(&self) -> Result<TransportSk> {
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .output()
+            .with_context(|| format!("Failed to run master key command: {}", self.command))?;
+
+        if !output.status.success() {
+            bail!(
+                "Master key command failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        parse_master_key(&String::from_utf8(output.stdout)?)
+    }
+}
+
+/// Accepts the content of a plain key file or an age secret key
+pub fn parse_master_key(value: &str) -> Result<TransportSk> {
+    let value = value.trim();
+    let master_key = match serde_json::from_str::<TransportSk>(value) {
+        Ok(master_key) => master_key,
+        Err(_) => TransportSk(Base64Text::from(value)),
+    };
+
+    master_key.as_age().context("Invalid master key")?;
+    Ok(master_key)
+}
+
+/// Key files are readable by the owner only
+fn write_key_file(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&tmp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fast_kdf() -> KdfParams {
+        KdfParams {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    fn passphrase(value: &str) -> SecretString {
+        SecretString::from(value.to_string())
+    }
+
+    #[test]
+    fn test_passphrase_key_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("master.key.json");
+
+        let key_file =
+            PassphraseKeyFile::new(&path, passphrase("correct horse")).with_kdf_params(fast_kdf());
+        let master_key = key_file.master_key()?;
+        assert_eq!(KeyFileKind::of(&path)?, Some(KeyFileKind::Encrypted));
+        assert!(!fs::read_to_string(&path)?.contains(&master_key.0.to_string()));
+
+        assert_eq!(key_file.master_key()?, master_key);
+
+        let wrong = PassphraseKeyFile::new(&path, passphrase("battery staple"));
+        let err = wrong.master_key().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MasterKeyError>(),
+            Some(MasterKeyError::WrongPassphrase(_))
+        ));
+
+        let key_file = key_file.change_passphrase(passphrase("battery staple"))?;
+        assert_eq!(key_file.master_key()?, master_key);
+        assert!(
+            PassphraseKeyFile::new(&path, passphrase("correct horse"))
+                .master_key()
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_source_detects_encrypted_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("master.key.json");
+
+        let plain = MasterKeySource::File.provider(&path, || bail!("No passphrase expected"))?;
+        let master_key = plain.master_key()?;
+        assert_eq!(KeyFileKind::of(&path)?, Some(KeyFileKind::Plain));
+
+        let err = MasterKeySource::PassphraseFile
+            .provider(&path, || Ok(passphrase("secret")))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<MasterKeyError>(),
+            Some(MasterKeyError::NotEncrypted(_))
+        ));
+
+        PassphraseKeyFile::new(&path, passphrase("secret"))
+            .with_kdf_params(fast_kdf())
+            .save(&master_key)?;
+        let encrypted = MasterKeySource::File.provider(&path, || Ok(passphrase("secret")))?;
+        assert_eq!(encrypted.master_key()?, master_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_and_env_sources() -> Result<()> {
+        let master_key = TransportDsaKeyPair::generate().sk();
+        let age_key = String::try_from(&master_key.0)?;
+
+        let command = CommandMasterKey {
+            command: format!("echo {}", age_key),
+        };
+        assert_eq!(command.master_key()?, master_key);
+
+        let failing = CommandMasterKey {
+            command: String::from("exit 3"),
+        };
+        assert!(failing.master_key().is_err());
+
+        let missing = EnvMasterKey {
+            var: String::from("META_SECRET_TEST_MISSING_MASTER_KEY"),
+        };
+        assert!(missing.master_key().is_err());
+
+        assert_eq!(
+            parse_master_key(&serde_json::to_string(&master_key)?)?,
+            master_key
+        );
+        assert!(parse_master_key("not a key").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_master_key_source_from_str() {
+        let sources = [
+            MasterKeySource::File,
+            MasterKeySource::PassphraseFile,
+            MasterKeySource::Stdin,
+            MasterKeySource::Env {
+                var: String::from("MASTER_KEY"),
+            },
+            MasterKeySource::Command {
+                command: String::from("pass show meta-secret"),
+            },
+        ];
+        for source in sources {
+            assert_eq!(
+                MasterKeySource::from_str(&source.to_string()).unwrap(),
+                source
+            );
+        }
+
+        assert!(MasterKeySource::from_str("env:").is_err());
+        assert!(MasterKeySource::from_str("vault").is_err());
+    }
+}
@@ -3,3 +3,4 @@ pub mod key_pair;
 pub mod keys;
 pub mod utils;
 pub mod key_utils;
+pub mod master_key;
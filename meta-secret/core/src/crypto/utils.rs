@@ -81,6 +81,16 @@ pub struct U64IdUrlEnc {
 }
 
 impl U64IdUrlEnc {
+    pub fn generate() -> U64IdUrlEnc {
+        let mut rng_bytes = [0u8; 8];
+        OsRng
+            .try_fill_bytes(&mut rng_bytes)
+            .expect("Failed to get random bytes from OS");
+
+        let text = Base64Text::from(rng_bytes.as_slice());
+        U64IdUrlEnc { text }
+    }
+
     pub fn take(&self, n: usize) -> String {
         self.text.base64_str().chars().take(n).collect::<String>()
     }
@@ -3,6 +3,7 @@ use std::string::FromUtf8Error;
 
 use crate::crypto::keys::TransportPk;
 use crate::node::common::model::crypto::channel::CommunicationChannel;
+use crate::node::common::model::device::common::DeviceId;
 use crate::secret::data_block::common::DataBlockParserError;
 use shamirsecretsharing::SSSError;
 
@@ -94,6 +95,16 @@ pub enum RecoveryError {
     EmptyInput(String),
     #[error("Invalid share")]
     InvalidShare(String),
+    #[error("Not enough valid shares: {valid} of {threshold}, invalid shares came from: {devices:?}")]
+    NotEnoughValidShares {
+        valid: usize,
+        threshold: usize,
+        devices: Vec<DeviceId>,
+    },
+    #[error("Shares belong to different splits of the secret, invalid shares can't be identified")]
+    AmbiguousShares,
+    #[error("The commitments of the shares are not signed by a member of the vault")]
+    UnauthenticatedShares,
 
     #[error(transparent)]
     ShamirCombineSharesError {
@@ -107,6 +118,12 @@ pub enum RecoveryError {
     },
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum InviteError {
+    #[error("No member of the vault has the key fingerprint of the invite: {fingerprint}")]
+    UnknownInviter { fingerprint: String },
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum SplitError {
     #[error("Secrets directory can't be created")]
@@ -115,8 +115,8 @@ pub fn split(secret: String, config: SharedSecretConfig) -> CoreResult<()> {
         return Err(CoreError::from(SplitError::from(dir_err)));
     }
 
-    for share_index in 0..config.number_of_shares {
-        let share: UserShareDto = shared_secret.get_share(share_index);
+    let shares = shared_secret.get_shares(config.number_of_shares)?;
+    for (share_index, share) in shares.into_iter().enumerate() {
         let share_json = serde_json::to_string_pretty(&share)?;
 
         // Save the JSON structure into the output file
@@ -324,6 +324,8 @@ mod tests {
         let share1 = UserShareDto {
             share_id: 0,
             share_blocks: vec![],
+            commitments: vec![],
+            dealer: None,
         };
 
         // For this test, we'll focus on the error case when shares list is empty
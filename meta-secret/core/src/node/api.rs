@@ -1,9 +1,13 @@
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::key_pair::DsaKeyPair;
+use crate::crypto::keys::DsaPk;
 use crate::node::common::model::secret::{SsDistributionStatus, SsRecoveryId};
 use crate::node::common::model::user::common::UserData;
 use crate::node::common::model::vault::vault::VaultName;
 use crate::node::db::events::generic_log_event::GenericKvLogEvent;
 use crate::node::db::events::object_id::ArtifactId;
 use crate::node::db::objects::persistent_vault::VaultTail;
+use crate::CRATE_PKG_VERSION;
 use anyhow::{anyhow, Result};
 use derive_more::From;
 use serde::{Deserialize, Serialize};
@@ -15,12 +19,79 @@ pub enum ReadSyncRequest {
     SsRequest(SsRequest),
     SsRecoveryCompletion(SsRecoveryCompletion),
     ServerTail(ServerTailRequest),
+    Handshake(HandshakeRequest),
+}
+
+impl ReadSyncRequest {
+    /// Position in the log a replication read starts from
+    pub fn cursor(&self) -> Option<PageCursor> {
+        match self {
+            ReadSyncRequest::Vault(request) => Some(PageCursor::Vault(request.tail.clone())),
+            ReadSyncRequest::SsRequest(request) => Some(PageCursor::SsLog(request.ss_log.clone())),
+            _ => None,
+        }
+    }
+
+    /// The same replication read continued from the cursor of the next page
+    pub fn with_cursor(self, cursor: PageCursor) -> Result<Self> {
+        match (self, cursor) {
+            (ReadSyncRequest::Vault(mut request), PageCursor::Vault(tail)) => {
+                request.tail = tail;
+                Ok(ReadSyncRequest::Vault(request))
+            }
+            (ReadSyncRequest::SsRequest(mut request), PageCursor::SsLog(ss_log)) => {
+                request.ss_log = ss_log;
+                Ok(ReadSyncRequest::SsRequest(request))
+            }
+            (request, cursor) => Err(anyhow!(
+                "Page cursor {:?} doesn't match the request {:?}",
+                cursor,
+                request
+            )),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, From, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum WriteSyncRequest {
-    Event(GenericKvLogEvent),
+    Event(SignedEvent),
+}
+
+/// An event written by a client, signed by the device that wrote it.
+/// The signature covers the event exactly as serialized by the device,
+/// the event is always taken from these bytes, never re-serialized.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedEvent {
+    event_json: String,
+    signature: Base64Text,
+}
+
+impl SignedEvent {
+    pub fn sign(event: &GenericKvLogEvent, dsa: &DsaKeyPair) -> Result<Self> {
+        let event_json = serde_json::to_string(event)?;
+        let signature = dsa.sign(event_json.clone());
+        Ok(Self {
+            event_json,
+            signature,
+        })
+    }
+
+    pub fn event(&self) -> Result<GenericKvLogEvent> {
+        Ok(serde_json::from_str(&self.event_json)?)
+    }
+
+    /// Size of the serialized event
+    pub fn size(&self) -> usize {
+        self.event_json.len()
+    }
+
+    /// Checks that the event has been signed by the owner of the key
+    pub fn verify(&self, dsa_pk: &DsaPk) -> Result<()> {
+        dsa_pk.verify(&self.event_json, &self.signature)?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, From, Serialize, Deserialize)]
@@ -30,6 +101,14 @@ pub enum SyncRequest {
     Write(Box<WriteSyncRequest>),
 }
 
+impl SyncRequest {
+    /// Write request with the event signed by the device key
+    pub fn signed_write(event: GenericKvLogEvent, dsa: &DsaKeyPair) -> Result<Self> {
+        let signed_event = SignedEvent::sign(&event, dsa)?;
+        Ok(SyncRequest::Write(Box::from(WriteSyncRequest::Event(signed_event))))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SsRecoveryCompletion {
@@ -49,6 +128,8 @@ fn default_receiver_status() -> SsDistributionStatus {
 pub struct VaultRequest {
     pub sender: UserData,
     pub tail: VaultTail,
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -56,6 +137,17 @@ pub struct VaultRequest {
 pub struct SsRequest {
     pub sender: UserData,
     pub ss_log: ArtifactId,
+    #[serde(default = "default_page_size")]
+    pub page_size: usize,
+}
+
+/// Number of events the client asks for in a single replication response
+pub const DEFAULT_PAGE_SIZE: usize = 256;
+/// The server never returns more events than that in a single replication response
+pub const MAX_PAGE_SIZE: usize = 1024;
+
+fn default_page_size() -> usize {
+    DEFAULT_PAGE_SIZE
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -70,12 +162,36 @@ pub enum DataSyncResponse {
     Empty,
     Data(DataEventsResponse),
     ServerTailResponse(ServerTailResponse),
+    Handshake(HandshakeResponse),
+    UpgradeRequired(UpgradeRequired),
     Error { msg: String },
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct DataEventsResponse(pub Vec<GenericKvLogEvent>);
+pub struct DataEventsResponse {
+    pub events: Vec<GenericKvLogEvent>,
+    /// Where the next page starts, `None` if the client has been caught up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_page: Option<PageCursor>,
+}
+
+impl From<Vec<GenericKvLogEvent>> for DataEventsResponse {
+    fn from(events: Vec<GenericKvLogEvent>) -> Self {
+        Self {
+            events,
+            next_page: None,
+        }
+    }
+}
+
+/// Continuation cursor of a paginated replication response
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PageCursor {
+    Vault(VaultTail),
+    SsLog(ArtifactId),
+}
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -90,6 +206,7 @@ impl DataSyncResponse {
     pub fn to_data(&self) -> Result<DataEventsResponse> {
         match self {
             DataSyncResponse::Data(data) => Ok(data.clone()),
+            DataSyncResponse::UpgradeRequired(upgrade) => Err(upgrade.clone().into()),
             _ => Err(anyhow!("Invalid response type")),
         }
     }
@@ -97,7 +214,304 @@ impl DataSyncResponse {
     pub fn to_server_tail(&self) -> Result<ServerTailResponse> {
         match self {
             DataSyncResponse::ServerTailResponse(server_tail) => Ok(server_tail.clone()),
+            DataSyncResponse::UpgradeRequired(upgrade) => Err(upgrade.clone().into()),
             _ => Err(anyhow!("Invalid response type")),
         }
     }
+
+    pub fn to_handshake(&self) -> Result<HandshakeResponse> {
+        match self {
+            DataSyncResponse::Handshake(handshake) => Ok(handshake.clone()),
+            DataSyncResponse::UpgradeRequired(upgrade) => Err(upgrade.clone().into()),
+            _ => Err(anyhow!("Invalid response type")),
+        }
+    }
+}
+
+/// Version of the sync wire format, bumped on breaking changes of `SyncRequest`/`DataSyncResponse`
+pub const PROTOCOL_VERSION: u32 = 3;
+/// The oldest peer protocol version this build is able to talk to,
+/// version 3 has made the signatures of written events mandatory
+pub const MIN_PROTOCOL_VERSION: u32 = 3;
+/// Http header with the sender's `PROTOCOL_VERSION`, tells an outdated client from an outdated
+/// server when a request can't be read
+pub const PROTOCOL_VERSION_HEADER: &str = "x-meta-secret-protocol";
+/// Http header with the sender's package version
+pub const PKG_VERSION_HEADER: &str = "x-meta-secret-version";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ProtocolFeature {
+    JsonCodec,
+    /// Replication responses come in pages with a continuation cursor
+    PagedSync,
+    /// Every write carries the signature of the device, the server rejects unsigned writes
+    SignedRequests,
+    /// A feature introduced by a newer peer
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProtocolInfo {
+    pub version: u32,
+    pub min_version: u32,
+    pub pkg_version: String,
+    #[serde(default)]
+    pub features: Vec<ProtocolFeature>,
+    /// Features the peer must support to talk to this side
+    #[serde(default)]
+    pub required_features: Vec<ProtocolFeature>,
+}
+
+impl ProtocolInfo {
+    pub fn current() -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            min_version: MIN_PROTOCOL_VERSION,
+            pkg_version: CRATE_PKG_VERSION.to_string(),
+            features: vec![
+                ProtocolFeature::JsonCodec,
+                ProtocolFeature::PagedSync,
+                ProtocolFeature::SignedRequests,
+            ],
+            required_features: vec![ProtocolFeature::JsonCodec, ProtocolFeature::SignedRequests],
+        }
+    }
+
+    pub fn supports(&self, feature: ProtocolFeature) -> bool {
+        feature != ProtocolFeature::Unknown && self.features.contains(&feature)
+    }
+
+    /// Features required by the peer which are not supported by this side
+    fn missing_features(&self, peer: &ProtocolInfo) -> Vec<ProtocolFeature> {
+        peer.required_features
+            .iter()
+            .filter(|feature| !self.supports(**feature))
+            .copied()
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandshakeRequest {
+    pub client: ProtocolInfo,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HandshakeResponse {
+    pub server: ProtocolInfo,
+    /// Features supported by both sides
+    pub features: Vec<ProtocolFeature>,
+    /// The key the server signs the audit logs with, pinned by the client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_key: Option<DsaPk>,
+}
+
+impl HandshakeResponse {
+    /// Check that the client and the server are able to talk to each other.
+    /// Runs on the server for an incoming handshake and on the client for the server's answer.
+    pub fn negotiate(
+        client: &ProtocolInfo,
+        server: &ProtocolInfo,
+    ) -> std::result::Result<Self, UpgradeRequired> {
+        let upgrade_required = |component, missing_features| UpgradeRequired {
+            component,
+            client_version: client.pkg_version.clone(),
+            server_version: server.pkg_version.clone(),
+            missing_features,
+        };
+
+        let client_missing = client.missing_features(server);
+        if client.version < server.min_version || !client_missing.is_empty() {
+            return Err(upgrade_required(UpgradeComponent::Client, client_missing));
+        }
+
+        let server_missing = server.missing_features(client);
+        if server.version < client.min_version || !server_missing.is_empty() {
+            return Err(upgrade_required(UpgradeComponent::Server, server_missing));
+        }
+
+        let features = client
+            .features
+            .iter()
+            .filter(|feature| server.supports(**feature))
+            .copied()
+            .collect();
+
+        Ok(Self {
+            server: server.clone(),
+            features,
+            audit_key: None,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpgradeComponent {
+    Client,
+    Server,
+}
+
+/// The client and the server protocols are incompatible, one of them has to be upgraded
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[error(
+    "Upgrade required: {component:?} is outdated (client: {client_version}, server: {server_version}, missing features: {missing_features:?})"
+)]
+pub struct UpgradeRequired {
+    pub component: UpgradeComponent,
+    pub client_version: String,
+    pub server_version: String,
+    pub missing_features: Vec<ProtocolFeature>,
+}
+
+impl UpgradeRequired {
+    /// The server failed to read a request, the client speaks another protocol.
+    /// A client that doesn't send its protocol version predates the handshake.
+    pub fn unreadable_request(
+        client_protocol: Option<u32>,
+        client_version: Option<String>,
+    ) -> Self {
+        let component = match client_protocol {
+            Some(client_protocol) if client_protocol > PROTOCOL_VERSION => UpgradeComponent::Server,
+            _ => UpgradeComponent::Client,
+        };
+
+        Self {
+            component,
+            client_version: client_version.unwrap_or_else(|| String::from("unknown")),
+            server_version: CRATE_PKG_VERSION.to_string(),
+            missing_features: vec![],
+        }
+    }
+
+    /// The client failed to read a response of the server, the server speaks a newer protocol
+    pub fn unreadable_response(server_version: Option<String>) -> Self {
+        Self {
+            component: UpgradeComponent::Client,
+            client_version: CRATE_PKG_VERSION.to_string(),
+            server_version: server_version.unwrap_or_else(|| String::from("unknown")),
+            missing_features: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_protocol_negotiation() {
+        let handshake =
+            HandshakeResponse::negotiate(&ProtocolInfo::current(), &ProtocolInfo::current())
+                .unwrap();
+        assert_eq!(
+            handshake.features,
+            vec![
+                ProtocolFeature::JsonCodec,
+                ProtocolFeature::PagedSync,
+                ProtocolFeature::SignedRequests
+            ]
+        );
+    }
+
+    #[test]
+    fn test_client_without_signed_requests_gets_missing_features() {
+        // a client of the version 2 protocol, before the writes were signed
+        let json = r#"{"version":2,"minVersion":2,"pkgVersion":"1.0.0","features":["jsonCodec"]}"#;
+        let client: ProtocolInfo = serde_json::from_str(json).unwrap();
+
+        let upgrade = HandshakeResponse::negotiate(&client, &ProtocolInfo::current()).unwrap_err();
+        assert_eq!(upgrade.component, UpgradeComponent::Client);
+        assert_eq!(
+            upgrade.missing_features,
+            vec![ProtocolFeature::SignedRequests]
+        );
+    }
+
+    #[test]
+    fn test_outdated_client_requires_upgrade() {
+        let client = ProtocolInfo {
+            version: 0,
+            ..ProtocolInfo::current()
+        };
+        let server = ProtocolInfo {
+            required_features: vec![ProtocolFeature::Unknown],
+            ..ProtocolInfo::current()
+        };
+
+        let upgrade = HandshakeResponse::negotiate(&client, &server).unwrap_err();
+        assert_eq!(upgrade.component, UpgradeComponent::Client);
+        assert_eq!(upgrade.missing_features, vec![ProtocolFeature::Unknown]);
+    }
+
+    #[test]
+    fn test_outdated_server_requires_upgrade() {
+        let client = ProtocolInfo {
+            version: PROTOCOL_VERSION + 1,
+            min_version: PROTOCOL_VERSION + 1,
+            ..ProtocolInfo::current()
+        };
+
+        let upgrade =
+            HandshakeResponse::negotiate(&client, &ProtocolInfo::current()).unwrap_err();
+        assert_eq!(upgrade.component, UpgradeComponent::Server);
+    }
+
+    #[test]
+    fn test_unknown_features_are_ignored() {
+        let json = r#"{"version":3,"minVersion":2,"pkgVersion":"9.0.0","features":["jsonCodec","signedRequests","quantumCodec"]}"#;
+        let newer_client: ProtocolInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            newer_client.features,
+            vec![
+                ProtocolFeature::JsonCodec,
+                ProtocolFeature::SignedRequests,
+                ProtocolFeature::Unknown
+            ]
+        );
+
+        let handshake =
+            HandshakeResponse::negotiate(&newer_client, &ProtocolInfo::current()).unwrap();
+        assert_eq!(
+            handshake.features,
+            vec![ProtocolFeature::JsonCodec, ProtocolFeature::SignedRequests]
+        );
+    }
+
+    #[test]
+    fn test_unreadable_request_blames_the_outdated_side() {
+        let legacy = UpgradeRequired::unreadable_request(None, None);
+        assert_eq!(legacy.component, UpgradeComponent::Client);
+        assert_eq!(legacy.client_version, "unknown");
+
+        let newer = UpgradeRequired::unreadable_request(
+            Some(PROTOCOL_VERSION + 1),
+            Some(String::from("9.0.0")),
+        );
+        assert_eq!(newer.component, UpgradeComponent::Server);
+        assert_eq!(newer.client_version, "9.0.0");
+    }
+
+    #[test]
+    fn test_upgrade_required_response_is_typed_error() {
+        let upgrade = HandshakeResponse::negotiate(
+            &ProtocolInfo {
+                version: 0,
+                ..ProtocolInfo::current()
+            },
+            &ProtocolInfo::current(),
+        )
+        .unwrap_err();
+
+        let err = DataSyncResponse::UpgradeRequired(upgrade.clone())
+            .to_server_tail()
+            .unwrap_err();
+        assert_eq!(err.downcast_ref::<UpgradeRequired>(), Some(&upgrade));
+    }
 }
@@ -16,10 +16,12 @@ use crate::node::common::model::user::user_creds::UserCreds;
 use crate::node::common::model::vault::vault::VaultName;
 use crate::node::common::model::{ApplicationState, VaultFullInfo};
 use crate::node::db::actions::recover::RecoveryHandler;
+use crate::node::db::events::audit_event::AuditEntry;
+use crate::node::db::objects::persistent_audit::{AuditFilter, PersistentAudit};
 use crate::node::db::objects::persistent_object::PersistentObject;
 use crate::node::db::repo::generic_db::KvLogEventRepo;
 use crate::node::db::repo::persistent_credentials::PersistentCredentials;
-use crate::secret::shared_secret::PlainText;
+use crate::secret::share_verification::VerifiedRecovery;
 
 pub fn resolve_signup_vault_name(state: &ApplicationState) -> Result<VaultName> {
     match state {
@@ -55,12 +57,13 @@ pub fn find_recovery_claim_id_from_state(
     member.ss_claims.find_recovery_claim_id(pass_id)
 }
 
-pub async fn recover_plain_text<Repo: KvLogEventRepo, SyncP: SyncProtocol>(
+/// Recovers the secret along with the devices that have sent invalid shares
+pub async fn recover_secret<Repo: KvLogEventRepo, SyncP: SyncProtocol>(
     sync_gateway: &SyncGateway<Repo, SyncP>,
     user_creds: UserCreds,
     claim_id: ClaimId,
     pass_id: MetaPasswordId,
-) -> Result<PlainText> {
+) -> Result<VerifiedRecovery> {
     let recovery_handler = RecoveryHandler {
         p_obj: sync_gateway.p_obj.clone(),
     };
@@ -70,6 +73,71 @@ pub async fn recover_plain_text<Repo: KvLogEventRepo, SyncP: SyncProtocol>(
         .await
 }
 
+/// Audit log query as the user types it: devices are given by ids or names,
+/// secrets by names
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditQuery {
+    /// Unix time in milliseconds
+    #[serde(default)]
+    pub since: Option<u64>,
+    #[serde(default)]
+    pub device: Option<String>,
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+/// Audit entries of the vault of a member, the passwords get their names from the vault
+pub async fn find_audit_entries<Repo: KvLogEventRepo>(
+    p_obj: Arc<PersistentObject<Repo>>,
+    state: &ApplicationState,
+    query: &AuditQuery,
+) -> Result<Vec<AuditEntry>> {
+    let ApplicationState::Vault(VaultFullInfo::Member(member)) = state else {
+        bail!("Audit log is available to vault members only");
+    };
+    let vault = &member.member.vault;
+
+    let device = match &query.device {
+        None => None,
+        Some(device) => {
+            let maybe_device_id = vault.users.values().find_map(|membership| {
+                let device_data = membership.user_data().device;
+                let is_device = device_data.device_id.to_string().eq(device)
+                    || device_data.device_name.as_str().eq(device);
+                is_device.then_some(device_data.device_id)
+            });
+            let Some(device_id) = maybe_device_id else {
+                bail!("Unknown device: {}", device);
+            };
+            Some(device_id)
+        }
+    };
+
+    let filter = AuditFilter {
+        since: query.since,
+        device,
+        secret: query
+            .secret
+            .as_deref()
+            .map(|secret| vault.find_secret_by_name(secret))
+            .transpose()?,
+    };
+
+    let p_audit = PersistentAudit::from(p_obj);
+    let mut entries = p_audit.find(vault.vault_name.clone(), &filter).await?;
+
+    for entry in entries.iter_mut() {
+        if let Some(pass_id) = entry.action.pass_id_mut()
+            && let Some(secret) = vault.secrets.get(pass_id)
+        {
+            *pass_id = secret.clone();
+        }
+    }
+
+    Ok(entries)
+}
+
 pub async fn build_client_components<Repo: KvLogEventRepo>(
     client_repo: Arc<Repo>,
     sync_protocol: Arc<HttpSyncProtocol>,
@@ -92,12 +160,12 @@ pub async fn build_client_components<Repo: KvLogEventRepo>(
             .await?,
     );
 
-    let sync_gateway = Arc::new(SyncGateway {
-        id: String::from("client-gateway"),
-        p_obj: p_obj.clone(),
-        sync: sync_protocol,
-        master_key: master_key.clone(),
-    });
+    let sync_gateway = Arc::new(SyncGateway::new(
+        String::from("client-gateway"),
+        p_obj.clone(),
+        sync_protocol,
+        master_key.clone(),
+    ));
 
     let state_provider = Arc::new(MetaClientStateProvider::new());
 
@@ -0,0 +1,612 @@
+//! Rules an automated device (the virtual device) follows when it answers the requests of a vault
+//! on its own. A request the rules don't allow is left to the other members, it's never declined.
+//! The default policy approves everything, the way the virtual device has always worked.
+//! A device loads its policy at startup from the [`AutoApprovalSource`] the host that starts
+//! the device hands over (see `VirtualDevice::init`).
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::info;
+
+use crate::node::common::model::IdString;
+use crate::node::common::model::device::common::{DeviceId, DeviceName, DeviceType};
+use crate::node::common::model::secret::SsClaim;
+use crate::node::common::model::user::common::UserData;
+
+#[derive(Error, Debug)]
+pub enum AutoApprovalConfigError {
+    #[error("Invalid auto-approval policy JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid auto-approval policy:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+    #[error("Auto-approval policy file {path:?} can't be read: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// Where the auto-approval policy of a device comes from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum AutoApprovalSource {
+    /// The built-in policy that approves everything
+    #[default]
+    Default,
+    /// JSON handed over by the host application
+    Json(String),
+    /// JSON file on disk
+    File(PathBuf),
+}
+
+impl AutoApprovalSource {
+    /// Reads the policy and checks it, a device doesn't start with a broken policy
+    pub fn load(&self) -> Result<AutoApprovalPolicy, AutoApprovalConfigError> {
+        match self {
+            Self::Default => Ok(AutoApprovalPolicy::default()),
+            Self::Json(json) => AutoApprovalPolicy::from_json(json),
+            Self::File(path) => {
+                let json = fs::read_to_string(path).map_err(|source| {
+                    AutoApprovalConfigError::Io {
+                        path: path.clone(),
+                        source,
+                    }
+                })?;
+                AutoApprovalPolicy::from_json(&json)
+            }
+        }
+    }
+}
+
+impl Display for AutoApprovalSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => write!(f, "built-in policy"),
+            Self::Json(_) => write!(f, "policy of the host application"),
+            Self::File(path) => write!(f, "file {}", path.display()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct AutoApprovalPolicy {
+    pub join: JoinRule,
+    pub recovery: RecoveryRule,
+    /// Requests are answered at any time if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub business_hours: Option<BusinessHours>,
+    /// Approvals per requester are not limited if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Candidates the device lets in: a candidate has to match one of the device types or names,
+/// any candidate if both lists are empty
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct JoinRule {
+    pub enabled: bool,
+    pub device_types: Vec<DeviceType>,
+    pub device_names: Vec<DeviceName>,
+}
+
+/// Secrets the device releases its shares of, by name or id, any secret if the list is empty
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct RecoveryRule {
+    pub enabled: bool,
+    pub secrets: Vec<String>,
+}
+
+/// Hours of the days the device answers requests in, `[start_hour, end_hour)` in the time zone
+/// of the offset
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct BusinessHours {
+    pub start_hour: u8,
+    pub end_hour: u8,
+    pub days: Vec<Weekday>,
+    pub utc_offset_minutes: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+/// At most `max_approvals` approved requests of the same requester in `window_secs`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct RateLimit {
+    pub max_approvals: usize,
+    pub window_secs: u64,
+}
+
+impl Default for JoinRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            device_types: vec![],
+            device_names: vec![],
+        }
+    }
+}
+
+impl Default for RecoveryRule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            secrets: vec![],
+        }
+    }
+}
+
+impl Default for BusinessHours {
+    fn default() -> Self {
+        Self {
+            start_hour: 9,
+            end_hour: 18,
+            days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+impl AutoApprovalPolicy {
+    pub fn from_json(json: &str) -> Result<Self, AutoApprovalConfigError> {
+        let policy: Self = serde_json::from_str(json)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn validate(&self) -> Result<(), AutoApprovalConfigError> {
+        let mut errors = vec![];
+
+        if let Some(hours) = &self.business_hours {
+            if hours.start_hour >= hours.end_hour || hours.end_hour > 24 {
+                errors.push(format!(
+                    "businessHours: startHour {} must be before endHour {} (at most 24)",
+                    hours.start_hour, hours.end_hour
+                ));
+            }
+            if hours.days.is_empty() {
+                errors.push(String::from("businessHours: days can't be empty"));
+            }
+            if hours.utc_offset_minutes.abs() >= 24 * 60 {
+                errors.push(format!(
+                    "businessHours: utcOffsetMinutes {} is out of range",
+                    hours.utc_offset_minutes
+                ));
+            }
+        }
+
+        if let Some(rate_limit) = &self.rate_limit
+            && (rate_limit.max_approvals == 0 || rate_limit.window_secs == 0)
+        {
+            errors.push(String::from(
+                "rateLimit: maxApprovals and windowSecs must be greater than zero",
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AutoApprovalConfigError::Invalid(errors))
+        }
+    }
+}
+
+impl JoinRule {
+    fn allows(&self, candidate: &UserData) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if self.device_types.is_empty() && self.device_names.is_empty() {
+            return true;
+        }
+        self.device_types.contains(&candidate.device.device_type)
+            || self.device_names.contains(&candidate.device.device_name)
+    }
+}
+
+impl RecoveryRule {
+    fn allows(&self, pass_name: &str, pass_id: &str) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        self.secrets.is_empty()
+            || self
+                .secrets
+                .iter()
+                .any(|secret| secret == pass_name || secret == pass_id)
+    }
+}
+
+impl BusinessHours {
+    /// `now` is the unix time in milliseconds
+    pub fn contains(&self, now: u64) -> bool {
+        let now_secs = i64::try_from(now / 1000).unwrap_or(i64::MAX);
+        let local_secs = now_secs.saturating_add(i64::from(self.utc_offset_minutes) * 60);
+        let days = local_secs.div_euclid(24 * 60 * 60);
+        let hour = local_secs.rem_euclid(24 * 60 * 60) / (60 * 60);
+
+        let weekday = Weekday::from_days_since_epoch(days);
+        self.days.contains(&weekday)
+            && hour >= i64::from(self.start_hour)
+            && hour < i64::from(self.end_hour)
+    }
+}
+
+impl Weekday {
+    const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    fn from_days_since_epoch(days: i64) -> Self {
+        // 1970-01-01 was a Thursday
+        let index = (days + 3).rem_euclid(7);
+        Self::ALL[usize::try_from(index).unwrap_or_default()]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AutoApprovalDecision {
+    Approved,
+    /// The request is left to the other members of the vault
+    Skipped(SkipReason),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    DeviceNotAllowed,
+    SecretNotAllowed,
+    OutsideBusinessHours,
+    RateLimited,
+}
+
+impl AutoApprovalDecision {
+    pub fn is_approved(&self) -> bool {
+        matches!(self, AutoApprovalDecision::Approved)
+    }
+}
+
+/// Applies the policy to the requests of a vault. The approver remembers its decisions:
+/// a request is counted against the rate limit once, and a decision is logged when it changes
+#[derive(Default)]
+pub struct AutoApprover {
+    policy: AutoApprovalPolicy,
+    state: Mutex<ApproverState>,
+}
+
+#[derive(Default)]
+struct ApproverState {
+    decisions: HashMap<String, AutoApprovalDecision>,
+    /// Unix times in milliseconds of the approved requests of every requester
+    approvals: HashMap<DeviceId, Vec<u64>>,
+}
+
+impl AutoApprover {
+    pub fn new(policy: AutoApprovalPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(ApproverState::default()),
+        }
+    }
+
+    pub fn policy(&self) -> &AutoApprovalPolicy {
+        &self.policy
+    }
+
+    pub fn decide_join(&self, candidate: &UserData, now: u64) -> AutoApprovalDecision {
+        let requester = &candidate.device.device_id;
+        let request = format!("join:{}", requester);
+        let is_allowed = self.policy.join.allows(candidate);
+        self.decide(
+            request,
+            requester,
+            is_allowed,
+            SkipReason::DeviceNotAllowed,
+            now,
+        )
+    }
+
+    /// `pass_name` is the unsealed name of the secret of the claim, empty if it's unknown
+    pub fn decide_recovery(
+        &self,
+        claim: &SsClaim,
+        pass_name: &str,
+        now: u64,
+    ) -> AutoApprovalDecision {
+        let request = format!("recovery:{}", claim.id.0.clone().id_str());
+        let pass_id = claim.dist_claim_id.pass_id.id.clone().id_str();
+        let is_allowed = self.policy.recovery.allows(pass_name, &pass_id);
+        self.decide(
+            request,
+            &claim.sender,
+            is_allowed,
+            SkipReason::SecretNotAllowed,
+            now,
+        )
+    }
+
+    fn decide(
+        &self,
+        request: String,
+        requester: &DeviceId,
+        is_allowed: bool,
+        not_allowed: SkipReason,
+        now: u64,
+    ) -> AutoApprovalDecision {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // the device keeps answering an approved request until it's done with it
+        if let Some(decision @ AutoApprovalDecision::Approved) = state.decisions.get(&request) {
+            return decision.clone();
+        }
+
+        let decision = if !is_allowed {
+            AutoApprovalDecision::Skipped(not_allowed)
+        } else if !self.is_business_time(now) {
+            AutoApprovalDecision::Skipped(SkipReason::OutsideBusinessHours)
+        } else if self.is_rate_limited(&mut state, requester, now) {
+            AutoApprovalDecision::Skipped(SkipReason::RateLimited)
+        } else {
+            state
+                .approvals
+                .entry(requester.clone())
+                .or_default()
+                .push(now);
+            AutoApprovalDecision::Approved
+        };
+
+        let previous = state.decisions.insert(request.clone(), decision.clone());
+        if previous.as_ref() != Some(&decision) {
+            info!(
+                request = %request,
+                requester = %requester,
+                decision = ?decision,
+                "Auto-approval decision"
+            );
+        }
+
+        decision
+    }
+
+    fn is_business_time(&self, now: u64) -> bool {
+        self.policy
+            .business_hours
+            .as_ref()
+            .is_none_or(|hours| hours.contains(now))
+    }
+
+    fn is_rate_limited(&self, state: &mut ApproverState, requester: &DeviceId, now: u64) -> bool {
+        let Some(rate_limit) = &self.policy.rate_limit else {
+            return false;
+        };
+
+        let window_start = now.saturating_sub(rate_limit.window_secs.saturating_mul(1000));
+        let approvals = state.approvals.entry(requester.clone()).or_default();
+        approvals.retain(|approved_at| *approved_at > window_start);
+        approvals.len() >= rate_limit.max_approvals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+    use crate::node::common::model::meta_pass::MetaPasswordId;
+
+    /// Monday, 2024-01-01 10:00:00 UTC
+    const MONDAY_10AM: u64 = 1_704_103_200_000;
+    const HOUR: u64 = 60 * 60 * 1000;
+
+    #[test]
+    fn test_join_rule_matches_device_type_or_name() {
+        let registry = FixtureRegistry::empty();
+        let client = registry.state.user_creds.client.user();
+        let vd = registry.state.user_creds.vd.user();
+
+        let approver = AutoApprover::new(AutoApprovalPolicy {
+            join: JoinRule {
+                device_names: vec![DeviceName::virtual_device()],
+                device_types: vec![DeviceType::from("HardwareKey")],
+                ..JoinRule::default()
+            },
+            ..AutoApprovalPolicy::default()
+        });
+
+        assert!(approver.decide_join(&vd, MONDAY_10AM).is_approved());
+        assert_eq!(
+            approver.decide_join(&client, MONDAY_10AM),
+            AutoApprovalDecision::Skipped(SkipReason::DeviceNotAllowed)
+        );
+
+        let default_approver = AutoApprover::default();
+        assert!(
+            default_approver
+                .decide_join(&client, MONDAY_10AM)
+                .is_approved()
+        );
+    }
+
+    #[test]
+    fn test_recovery_rule_allows_listed_secrets() {
+        let registry = FixtureRegistry::empty();
+        let vault_member = registry.state.vault_data.client_vault_member;
+        let wifi_claim = vault_member.create_recovery_claim(MetaPasswordId::build_from_str("wifi"));
+        let db_claim =
+            vault_member.create_recovery_claim(MetaPasswordId::build_from_str("prod_db"));
+
+        let approver = AutoApprover::new(AutoApprovalPolicy {
+            recovery: RecoveryRule {
+                secrets: vec![String::from("wifi")],
+                ..RecoveryRule::default()
+            },
+            ..AutoApprovalPolicy::default()
+        });
+
+        assert!(
+            approver
+                .decide_recovery(&wifi_claim, "wifi", MONDAY_10AM)
+                .is_approved()
+        );
+        assert_eq!(
+            approver.decide_recovery(&db_claim, "prod_db", MONDAY_10AM),
+            AutoApprovalDecision::Skipped(SkipReason::SecretNotAllowed)
+        );
+
+        let no_recovery = AutoApprover::new(AutoApprovalPolicy {
+            recovery: RecoveryRule {
+                enabled: false,
+                secrets: vec![],
+            },
+            ..AutoApprovalPolicy::default()
+        });
+        assert!(
+            !no_recovery
+                .decide_recovery(&wifi_claim, "wifi", MONDAY_10AM)
+                .is_approved()
+        );
+    }
+
+    #[test]
+    fn test_business_hours() {
+        let hours = BusinessHours::default();
+        assert!(hours.contains(MONDAY_10AM));
+        assert!(!hours.contains(MONDAY_10AM - 2 * HOUR));
+        assert!(!hours.contains(MONDAY_10AM + 8 * HOUR));
+        // Sunday
+        assert!(!hours.contains(MONDAY_10AM - 24 * HOUR));
+
+        // 10:00 UTC is 19:00 in Tokyo
+        let tokyo_hours = BusinessHours {
+            utc_offset_minutes: 9 * 60,
+            ..BusinessHours::default()
+        };
+        assert!(!tokyo_hours.contains(MONDAY_10AM));
+        assert!(tokyo_hours.contains(MONDAY_10AM - 2 * HOUR));
+    }
+
+    #[test]
+    fn test_rate_limit_counts_approved_requests_once() {
+        let registry = FixtureRegistry::empty();
+        let vault_member = registry.state.vault_data.client_vault_member;
+        let approver = AutoApprover::new(AutoApprovalPolicy {
+            rate_limit: Some(RateLimit {
+                max_approvals: 1,
+                window_secs: 60 * 60,
+            }),
+            ..AutoApprovalPolicy::default()
+        });
+
+        let first = vault_member.create_recovery_claim(MetaPasswordId::build_from_str("first"));
+        let second = vault_member.create_recovery_claim(MetaPasswordId::build_from_str("second"));
+
+        assert!(
+            approver
+                .decide_recovery(&first, "first", MONDAY_10AM)
+                .is_approved()
+        );
+        // the approved request is answered again until the claim is done
+        assert!(
+            approver
+                .decide_recovery(&first, "first", MONDAY_10AM + 1)
+                .is_approved()
+        );
+        assert_eq!(
+            approver.decide_recovery(&second, "second", MONDAY_10AM + 1),
+            AutoApprovalDecision::Skipped(SkipReason::RateLimited)
+        );
+        assert!(
+            approver
+                .decide_recovery(&second, "second", MONDAY_10AM + HOUR)
+                .is_approved()
+        );
+    }
+
+    #[test]
+    fn test_policy_from_json() -> anyhow::Result<()> {
+        let policy = AutoApprovalPolicy::from_json(
+            r#"{
+                "join": {"deviceTypes": ["CLI"]},
+                "recovery": {"secrets": ["wifi"]},
+                "businessHours": {"startHour": 8, "endHour": 20, "days": ["mon", "sat"]},
+                "rateLimit": {"maxApprovals": 3, "windowSecs": 3600}
+            }"#,
+        )?;
+        assert_eq!(policy.join.device_types, vec![DeviceType::cli()]);
+        assert!(policy.join.enabled);
+        assert_eq!(
+            policy.business_hours.map(|hours| hours.days),
+            Some(vec![Weekday::Mon, Weekday::Sat])
+        );
+        assert_eq!(
+            AutoApprovalPolicy::from_json("{}")?,
+            AutoApprovalPolicy::default()
+        );
+
+        let invalid = AutoApprovalPolicy::from_json(
+            r#"{"businessHours": {"startHour": 18, "endHour": 9}, "rateLimit": {"maxApprovals": 0, "windowSecs": 60}}"#,
+        );
+        let Err(AutoApprovalConfigError::Invalid(errors)) = invalid else {
+            panic!("Invalid policy must be rejected");
+        };
+        assert_eq!(errors.len(), 2);
+
+        assert!(matches!(
+            AutoApprovalPolicy::from_json(r#"{"joins": {}}"#),
+            Err(AutoApprovalConfigError::Json(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_is_loaded_from_file() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("auto_approval.json");
+        fs::write(&path, r#"{"join": {"deviceTypes": ["CLI"]}}"#)?;
+
+        let policy = AutoApprovalSource::File(path.clone()).load()?;
+        assert_eq!(policy.join.device_types, vec![DeviceType::cli()]);
+        assert_eq!(AutoApprovalSource::Default.load()?, AutoApprovalPolicy::default());
+
+        fs::write(&path, r#"{"rateLimit": {"maxApprovals": 0, "windowSecs": 60}}"#)?;
+        assert!(matches!(
+            AutoApprovalSource::File(path).load(),
+            Err(AutoApprovalConfigError::Invalid(_))
+        ));
+
+        let missing = AutoApprovalSource::File(dir.path().join("missing.json"));
+        assert!(matches!(
+            missing.load(),
+            Err(AutoApprovalConfigError::Io { .. })
+        ));
+        Ok(())
+    }
+}
@@ -1,4 +1,6 @@
 use crate::node::common::model::meta_pass::{MetaPasswordId, PlainPassInfo};
+use crate::node::common::model::secret::{ClaimId, RecoveryTargets};
+use crate::node::common::model::vault::invite::VaultInvite;
 use crate::node::common::model::vault::vault::VaultName;
 use crate::node::common::model::ApplicationState;
 
@@ -7,8 +9,13 @@ pub enum GenericAppStateRequest {
     GetState,
     GenerateUserCreds(VaultName),
     SignUp(VaultName),
+    /// Joins the vault of the invite
+    JoinWithInvite(VaultInvite),
     ClusterDistribution(PlainPassInfo),
-    Recover(MetaPasswordId),
+    Recover(MetaPasswordId, RecoveryTargets),
+    CancelRecovery(ClaimId),
+    VetoRecovery(ClaimId),
+    RotateDeviceKeys,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
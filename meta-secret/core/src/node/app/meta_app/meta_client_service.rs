@@ -14,12 +14,18 @@ use crate::node::common::model::meta_pass::SecurePassInfo;
 use crate::node::common::model::secret::ClaimId;
 use crate::node::common::model::user::common::{UserData, UserDataOutsiderStatus};
 use crate::node::common::model::user::user_creds::UserCreds;
+use crate::node::common::model::vault::invite::VaultInvite;
 use crate::node::common::model::vault::vault::{VaultMember, VaultStatus};
 use crate::node::common::model::{ApplicationState, UserMemberFullInfo, VaultFullInfo};
+use crate::node::db::actions::invite::InviteAction;
 use crate::node::db::actions::recover::RecoveryAction;
+use crate::node::db::actions::rotate_keys::KeyRotationAction;
 use crate::node::db::actions::sign_up::claim::SignUpClaim;
 use crate::node::db::actions::sign_up::join::JoinActionUpdate;
-use crate::node::db::events::vault::vault_log_event::{JoinClusterEvent, VaultActionEvents};
+use crate::node::db::events::vault::vault_log_event::{
+    JoinClusterEvent, UpdateVaultSettingsEvent, VaultActionEvents,
+};
+use crate::node::db::objects::persistent_device_log::PersistentDeviceLog;
 use crate::node::db::objects::persistent_object::PersistentObject;
 use crate::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
 use crate::node::db::objects::persistent_vault::PersistentVault;
@@ -52,9 +58,14 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
         let mut service_state = self.build_service_state().await?;
 
         loop {
-            let request = self.data_transfer.dt.service_receive().await?;
+            let envelope = self.data_transfer.dt.service_receive().await?;
+            if envelope.is_cancelled() {
+                debug!("Skip cancelled request: {}", envelope.id);
+                continue;
+            }
+
             let new_app_state_result = self
-                .handle_client_request(service_state.app_state, request)
+                .handle_client_request(service_state.app_state, envelope.request.clone())
                 .await;
 
             let new_app_state = match new_app_state_result {
@@ -68,7 +79,9 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
             service_state.app_state = new_app_state;
             //self.state_provider.push(&service_state.app_state).await?;
             let response_state = GenericAppStateResponse::AppState(service_state.app_state.clone());
-            self.data_transfer.dt.send_to_client(response_state).await;
+            if let Err(err) = envelope.reply(response_state).await {
+                debug!("Response is not delivered: {}", err);
+            }
 
             async_std::task::sleep(Duration::from_millis(100)).await;
         }
@@ -133,6 +146,21 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
                 self.sync_gateway.sync(user_creds.user()).await?;
             }
 
+            GenericAppStateRequest::JoinWithInvite(invite) => {
+                info!("Handle join request with an invite");
+
+                let user_creds = self.get_user_creds(&request).await?;
+
+                self.sync_gateway.sync(user_creds.user()).await?;
+                self.sync_gateway.sync(user_creds.user()).await?;
+
+                let invite_action = InviteAction::from(self.p_obj.clone());
+                invite_action.join(&user_creds, invite).await?;
+
+                self.sync_gateway.sync(user_creds.user()).await?;
+                self.sync_gateway.sync(user_creds.user()).await?;
+            }
+
             GenericAppStateRequest::ClusterDistribution(plain_request) => {
                 let user_creds = self.get_user_creds(&request).await?;
 
@@ -173,7 +201,7 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
                 self.sync_gateway.sync(user_creds.user()).await?;
             }
 
-            GenericAppStateRequest::Recover(meta_pass_id) => {
+            GenericAppStateRequest::Recover(meta_pass_id, targets) => {
                 let user_creds = self.get_user_creds(&request).await?;
 
                 self.sync_gateway.sync(user_creds.user()).await?;
@@ -181,12 +209,57 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
 
                 let recovery_action = RecoveryAction::from(self.p_obj.clone());
                 recovery_action
-                    .recovery_request(user_creds.clone(), meta_pass_id.clone())
+                    .recovery_request(user_creds.clone(), meta_pass_id.clone(), targets.clone())
+                    .await?;
+
+                self.sync_gateway.sync(user_creds.user()).await?;
+                self.sync_gateway.sync(user_creds.user()).await?;
+            }
+
+            GenericAppStateRequest::CancelRecovery(claim_id) => {
+                let user_creds = self.get_user_creds(&request).await?;
+
+                self.sync_gateway.sync(user_creds.user()).await?;
+
+                let recovery_action = RecoveryAction::from(self.p_obj.clone());
+                recovery_action
+                    .cancel_recovery_request(user_creds.clone(), claim_id.clone())
+                    .await?;
+
+                self.sync_gateway.sync(user_creds.user()).await?;
+                self.sync_gateway.sync(user_creds.user()).await?;
+            }
+
+            GenericAppStateRequest::VetoRecovery(claim_id) => {
+                let user_creds = self.get_user_creds(&request).await?;
+
+                self.sync_gateway.sync(user_creds.user()).await?;
+
+                let recovery_action = RecoveryAction::from(self.p_obj.clone());
+                recovery_action
+                    .veto_recovery_request(user_creds.clone(), claim_id.clone())
                     .await?;
 
                 self.sync_gateway.sync(user_creds.user()).await?;
                 self.sync_gateway.sync(user_creds.user()).await?;
             }
+
+            GenericAppStateRequest::RotateDeviceKeys => {
+                let user_creds = self.get_user_creds(&request).await?;
+
+                self.sync_gateway.sync(user_creds.user()).await?;
+                self.sync_gateway.sync(user_creds.user()).await?;
+
+                let creds_repo = PersistentCredentials {
+                    p_obj: self.p_obj.clone(),
+                    master_key: self.master_key.clone(),
+                };
+                let rotation_action = KeyRotationAction::from(self.p_obj.clone());
+                let rotated_creds = rotation_action.rotate(&creds_repo, user_creds).await?;
+
+                self.sync_gateway.sync(rotated_creds.user()).await?;
+                self.sync_gateway.sync(rotated_creds.user()).await?;
+            }
         }
 
         //Update app state
@@ -220,18 +293,32 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
                 let device_name = self.device_data.device_name.clone();
                 let device_type = self.device_data.device_type.clone();
                 creds_repo
-                    .get_or_generate_user_creds_with_type(device_name, device_type, vault_name.clone())
+                    .get_or_generate_labeled_user_creds(device_name, device_type, vault_name.clone())
                     .await?
             }
             GenericAppStateRequest::SignUp(vault_name) => {
                 let device_name = self.device_data.device_name.clone();
                 let device_type = self.device_data.device_type.clone();
                 creds_repo
-                    .get_or_generate_user_creds_with_type(device_name, device_type, vault_name.clone())
+                    .get_or_generate_labeled_user_creds(device_name, device_type, vault_name.clone())
+                    .await?
+            }
+            GenericAppStateRequest::JoinWithInvite(invite) => {
+                let device_name = self.device_data.device_name.clone();
+                let device_type = self.device_data.device_type.clone();
+                creds_repo
+                    .get_or_generate_labeled_user_creds(
+                        device_name,
+                        device_type,
+                        invite.vault_name.clone(),
+                    )
                     .await?
             }
             GenericAppStateRequest::ClusterDistribution(_) => self.find_user_creds().await?,
-            GenericAppStateRequest::Recover(_) => self.find_user_creds().await?,
+            GenericAppStateRequest::Recover(..) => self.find_user_creds().await?,
+            GenericAppStateRequest::CancelRecovery(_) => self.find_user_creds().await?,
+            GenericAppStateRequest::VetoRecovery(_) => self.find_user_creds().await?,
+            GenericAppStateRequest::RotateDeviceKeys => self.find_user_creds().await?,
         };
         Ok(user_creds)
     }
@@ -284,14 +371,30 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
                     VaultStatus::Outsider(outsider) => {
                         ApplicationState::Vault(VaultFullInfo::Outsider(outsider))
                     }
-                    VaultStatus::Member(member_user) => {
+                    VaultStatus::Member(mut member_user) => {
+                        // the approvals of the other members have arrived with the sync,
+                        // the break-glass delays of the claims may be over,
+                        // and invited candidates may be waiting for a pre-approval
+                        let orchestrator = MetaOrchestrator {
+                            p_obj: self.p_obj(),
+                            user_creds: user_creds.clone(),
+                        };
+                        orchestrator.release_approved_shares().await?;
+                        orchestrator.release_break_glass_shares().await?;
+                        orchestrator.accept_invited_joins().await?;
+
+                        // Password and device names are sealed to the vault, open them for display
                         let vault = p_vault
                             .get_vault(member_user.user_data.vault_name())
-                            .await?;
+                            .await?
+                            .to_data()
+                            .unseal_names(&user_creds);
 
                         let ss_claims = {
                             let p_ss = PersistentSharedSecret::from(self.p_obj());
-                            p_ss.get_ss_log_obj(user_creds.vault_name).await?
+                            p_ss.get_ss_log_obj(user_creds.vault_name.clone())
+                                .await?
+                                .with_pass_names(&vault)
                         };
 
                         let maybe_vault_log_event = {
@@ -299,14 +402,18 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
                             p_vault.vault_log(vault_name).await?
                         };
 
-                        let vault_action_events = maybe_vault_log_event
+                        let mut vault_action_events = maybe_vault_log_event
                             .map(|obj| obj.0.value)
                             .unwrap_or(VaultActionEvents::default());
+                        if let Some(label) = &user_creds.vault_label {
+                            vault_action_events = vault_action_events.open_device_names(label);
+                            member_user.user_data = label.open_user(member_user.user_data);
+                        }
 
                         let user_full_info = UserMemberFullInfo {
                             member: VaultMember {
                                 member: member_user,
-                                vault: vault.to_data(),
+                                vault,
                             },
                             ss_claims,
                             vault_events: vault_action_events,
@@ -416,6 +523,51 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> MetaClientService<Repo, Sync> {
         }
     }
 
+    /// Makes an invite to the vault, valid for `ttl` milliseconds
+    pub async fn create_invite(&self, ttl: u64, pre_approved: bool) -> Result<VaultInvite> {
+        let user_creds = self.find_user_creds().await?;
+
+        self.sync_gateway.sync(user_creds.user()).await?;
+
+        let invite_action = InviteAction::from(self.p_obj.clone());
+        let invite = invite_action.create(&user_creds, ttl, pre_approved).await?;
+
+        self.sync_gateway.sync(user_creds.user()).await?;
+        self.sync_gateway.sync(user_creds.user()).await?;
+
+        Ok(invite)
+    }
+
+    /// Changes the settings of the vault, the settings left out stay as they are.
+    /// Loosening the settings takes as many members as the join quorum of the vault
+    pub async fn update_vault_settings(
+        &self,
+        join_quorum: Option<usize>,
+        require_invite: Option<bool>,
+    ) -> Result<()> {
+        let user_creds = self.find_user_creds().await?;
+
+        self.sync_gateway.sync(user_creds.user()).await?;
+
+        let p_vault = PersistentVault::from(self.p_obj());
+        let VaultStatus::Member(member) = p_vault.find(user_creds.user()).await? else {
+            bail!("Only a vault member can change the settings of the vault");
+        };
+
+        let settings = UpdateVaultSettingsEvent {
+            sender: member,
+            join_quorum,
+            require_invite,
+        };
+        let p_device_log = PersistentDeviceLog::from(self.p_obj());
+        p_device_log.save_update_settings_request(settings).await?;
+
+        self.sync_gateway.sync(user_creds.user()).await?;
+        self.sync_gateway.sync(user_creds.user()).await?;
+
+        Ok(())
+    }
+
     fn p_obj(&self) -> Arc<PersistentObject<Repo>> {
         self.sync_gateway.p_obj.clone()
     }
@@ -1,4 +1,5 @@
 pub mod app_manager_shared;
+pub mod auto_approval;
 pub mod meta_app;
 pub mod orchestrator;
 pub mod sync;
@@ -1,8 +1,11 @@
+use crate::crypto::keys::TransportPk;
+use crate::node::app::auto_approval::AutoApprover;
+use crate::node::common::clock::unix_time_millis;
 use crate::node::common::model::crypto::aead::EncryptedMessage;
-use crate::node::common::model::meta_pass::{PlainPassInfo, SecurePassInfo};
+use crate::node::common::model::meta_pass::{MetaPasswordId, PlainPassInfo, SecurePassInfo};
 use crate::node::common::model::secret::{
-    ClaimId, SecretDistributionData, SecretDistributionType, SsClaim, SsDeclineData,
-    SsDistributionId, SsDistributionStatus, SsLogData,
+    ClaimId, RecoveryPolicy, SecretDistributionData, SecretDistributionType, SsApprovalData,
+    SsClaim, SsDeclineData, SsDistributionId, SsDistributionStatus, SsLogData,
 };
 use crate::node::common::model::user::common::{UserDataMember, UserMembership};
 use crate::node::common::model::user::user_creds::UserCreds;
@@ -14,18 +17,19 @@ use crate::node::db::descriptors::shared_secret_descriptor::SsWorkflowDescriptor
 use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
 use crate::node::db::events::shared_secret_event::{SsDeviceLogObject, SsWorkflowObject};
 use crate::node::db::events::vault::vault_log_event::{
-    JoinClusterEvent, VaultActionRequestEvent, VaultLogObject,
+    AddMetaPassEvent, JoinClusterEvent, VaultActionRequestEvent, VaultLogObject,
 };
+use crate::node::db::objects::persistent_device_log::PersistentDeviceLog;
 use crate::node::db::objects::persistent_object::PersistentObject;
 use crate::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
 use crate::node::db::objects::persistent_vault::PersistentVault;
 use crate::node::db::repo::generic_db::KvLogEventRepo;
-use crate::recover_from_shares;
 use crate::secret::split2;
+use crate::secret::share_verification::{HeldShare, ShareVerifier};
 use crate::secret::shared_secret::{PlainText, UserShareDto};
 use anyhow::bail;
 use anyhow::Result;
-use log::debug;
+use log::{debug, warn};
 use std::collections::HashSet;
 use std::sync::Arc;
 
@@ -42,6 +46,11 @@ pub struct MetaOrchestrator<Repo: KvLogEventRepo> {
 impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
     /// Accept all requests automatically
     pub async fn orchestrate(&self) -> Result<()> {
+        self.orchestrate_with(&AutoApprover::default()).await
+    }
+
+    /// Accept the requests the auto-approval policy allows, the rest is left to the other members
+    pub async fn orchestrate_with(&self, approver: &AutoApprover) -> Result<()> {
         let member = self.get_member().await?;
         let maybe_vault_log_event = self.get_vault_log_event(&member).await?;
 
@@ -50,14 +59,24 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
         };
 
         let vault_actions = action_event.value;
+        let current_vault = self.get_vault(member.clone()).await?;
 
         for request in vault_actions.requests {
             match request {
                 VaultActionRequestEvent::JoinCluster(join_request) => {
-                    self.update_membership(join_request, JoinActionUpdate::Accept)
-                        .await?;
+                    let decision =
+                        approver.decide_join(&join_request.candidate, unix_time_millis());
+                    if decision.is_approved()
+                        || self.has_pre_approved_invite(&current_vault, &join_request)
+                    {
+                        self.update_membership(join_request, JoinActionUpdate::Accept)
+                            .await?;
+                    }
                 }
-                VaultActionRequestEvent::AddMetaPass(_) => {
+                VaultActionRequestEvent::AddMetaPass(_)
+                | VaultActionRequestEvent::RotateDeviceKeys(_)
+                | VaultActionRequestEvent::AddInvite(_)
+                | VaultActionRequestEvent::UpdateSettings(_) => {
                     //skip
                 }
             }
@@ -65,12 +84,41 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
 
         // shared secret actions
         let ss_log_data = self.get_ss_log_data().await?;
+        let now = unix_time_millis();
+        let local_device_id = self.user_creds.device_id().clone();
+        let vault = self
+            .get_vault(member)
+            .await?
+            .unseal_names(&self.user_creds);
 
         for (_, claim) in ss_log_data.claims {
+            // stale claims can't be approved anymore
+            if claim.is_expired(now) {
+                continue;
+            }
+            // the escrow waits for the members to veto the claim, see release_break_glass_shares
+            if self.is_escrow_of(&claim) {
+                continue;
+            }
+
+            // a claim waiting for this device is a new request, the rest is already answered
+            let is_pending = claim.distribution_type == SecretDistributionType::Recover
+                && matches!(
+                    claim.status.get(&local_device_id),
+                    Some(SsDistributionStatus::Pending)
+                );
+            if is_pending {
+                let pass = vault.find_secret(&claim.dist_claim_id.pass_id);
+                let decision = approver.decide_recovery(&claim, &pass.name, now);
+                if !decision.is_approved() {
+                    continue;
+                }
+            }
+
             self.accept_recover(claim.id).await?;
         }
 
-        Ok(())
+        self.release_break_glass_shares().await
     }
 
     pub async fn accept_recover(&self, claim_id: ClaimId) -> Result<()> {
@@ -87,7 +135,13 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                 }
                 SecretDistributionType::Recover => {
                     if claim_id.eq(&claim.id) {
-                        self.handle_recover(vault.clone(), claim).await?;
+                        if claim.is_expired(unix_time_millis()) {
+                            bail!("Recovery claim has expired: {:?}", claim_id);
+                        }
+                        match vault.recovery_policy(&claim.dist_claim_id.pass_id) {
+                            None => self.handle_recover(vault.clone(), claim).await?,
+                            Some(policy) => self.approve_recover(&vault, policy, claim).await?,
+                        }
                     }
                 }
             }
@@ -96,6 +150,126 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
         Ok(())
     }
 
+    /// Releases the shares of the claims this device has approved
+    /// as soon as the other approvers required by the recovery policies have approved them too
+    pub async fn release_approved_shares(&self) -> Result<()> {
+        let member = self.get_member().await?;
+        let vault = self.get_vault(member).await?;
+        let ss_log_data = self.get_ss_log_data().await?;
+        let now = unix_time_millis();
+
+        for (_, claim) in ss_log_data.claims {
+            if claim.distribution_type != SecretDistributionType::Recover || claim.is_expired(now) {
+                continue;
+            }
+
+            let Some(policy) = vault.recovery_policy(&claim.dist_claim_id.pass_id) else {
+                continue;
+            };
+            self.release_approved_share(&vault, policy, claim).await?;
+        }
+
+        Ok(())
+    }
+
+    /// The escrow device releases its share of the break-glass claims
+    /// that nobody has vetoed or declined during the delay of the break-glass policy
+    pub async fn release_break_glass_shares(&self) -> Result<()> {
+        let member = self.get_member().await?;
+        let vault = self.get_vault(member).await?;
+        let ss_log_data = self.get_ss_log_data().await?;
+        let now = unix_time_millis();
+        let local_device_id = self.user_creds.device_id().clone();
+        let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+
+        for (_, claim) in ss_log_data.claims {
+            if claim.distribution_type != SecretDistributionType::Recover
+                || !self.is_escrow_of(&claim)
+                || !claim.is_break_glass_due(now)
+            {
+                continue;
+            }
+
+            // the escrow and the delay of the claim are the ones the vault has set for the secret,
+            // the sender can't shorten the delay the members have to veto the claim
+            let follows_policy = claim.break_glass.as_ref().is_some_and(|break_glass| {
+                vault
+                    .break_glass_policy(&claim.dist_claim_id.pass_id)
+                    .is_some_and(|policy| break_glass.follows(policy))
+            });
+            if !follows_policy {
+                warn!(
+                    "Break-glass claim {:?} doesn't match the policy of the secret",
+                    claim.id
+                );
+                continue;
+            }
+
+            let is_waiting = matches!(
+                claim.status.get(&local_device_id),
+                Some(SsDistributionStatus::Pending | SsDistributionStatus::Approved)
+            );
+            if !is_waiting {
+                continue;
+            }
+
+            // the share has been released already and waits for the next sync
+            let is_released = !p_ss.get_recoveries(claim.clone()).await?.is_empty();
+            if !is_released {
+                debug!("Break-glass recovery of claim {:?} is due", claim.id);
+                self.handle_recover(vault.clone(), claim.clone()).await?;
+            }
+
+            let mut released_claim = claim;
+            released_claim.status = released_claim.status.sent(local_device_id.clone());
+            p_ss.save_local_ss_log_event(released_claim).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Accepts the candidates that have presented a pre-approved invite of this device
+    pub async fn accept_invited_joins(&self) -> Result<()> {
+        let member = self.get_member().await?;
+        let vault = self.get_vault(member.clone()).await?;
+        let maybe_vault_log_event = self.get_vault_log_event(&member).await?;
+
+        let Some(VaultLogObject(action_event)) = maybe_vault_log_event else {
+            return Ok(());
+        };
+
+        for request in action_event.value.requests {
+            if let VaultActionRequestEvent::JoinCluster(join_request) = request
+                && self.has_pre_approved_invite(&vault, &join_request)
+            {
+                self.update_membership(join_request, JoinActionUpdate::Accept)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn has_pre_approved_invite(&self, vault: &VaultData, join_request: &JoinClusterEvent) -> bool {
+        let candidate = &join_request.candidate.device.device_id;
+        join_request
+            .invite
+            .as_ref()
+            .and_then(|ticket| vault.find_invite(ticket))
+            .is_some_and(|invite| {
+                invite.pre_approved
+                    && invite.created_by.eq(self.user_creds.device_id())
+                    && invite.used_by.as_ref() == Some(candidate)
+            })
+    }
+
+    fn is_escrow_of(&self, claim: &SsClaim) -> bool {
+        claim
+            .break_glass
+            .as_ref()
+            .is_some_and(|break_glass| break_glass.escrow.eq(self.user_creds.device_id()))
+    }
+
     pub async fn decline_recover(&self, claim_id: ClaimId) -> Result<()> {
         println!("🦀 Orchestrator: decline claim_id: {:?}", claim_id);
         let local_device_id = self.user_creds.device_id().clone();
@@ -112,7 +286,7 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
         println!("🦀 Orchestrator: updated_claim status: {:?}", updated_claim.status);
 
         let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
-        p_ss.save_ss_log_event(updated_claim).await?;
+        p_ss.save_local_ss_log_event(updated_claim).await?;
 
         for recovery_db_id in claim.recovery_db_ids() {
             if recovery_db_id.distribution_id.receiver.eq(&local_device_id) {
@@ -123,10 +297,7 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                 };
                 let key =
                     KvKey::from(SsWorkflowDescriptor::Decline(recovery_db_id.clone()));
-                let decline_wf = SsWorkflowObject::Decline(KvLogEvent {
-                    key,
-                    value: decline_data,
-                });
+                let decline_wf = SsWorkflowObject::Decline(KvLogEvent::new(key, decline_data));
                 self.p_obj.repo.save(decline_wf).await?;
                 break;
             }
@@ -151,6 +322,24 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
         };
 
         let vault_actions = action_event.value;
+        // the request in the log carries the invite the candidate has presented
+        let Some(join_request) = vault_actions.find_join_request(&join_request.candidate) else {
+            debug!("No pending join request of {:?}", join_request.candidate);
+            return Ok(());
+        };
+
+        let local_device_id = self.user_creds.device_id().clone();
+        let mut approvals = vault_actions.join_approvals(&join_request).to_vec();
+        let is_approved = approvals
+            .iter()
+            .any(|approval| approval.sender_id().eq(&local_device_id));
+        if upd == JoinActionUpdate::Accept && is_approved {
+            debug!(
+                "Join request of {:?} is approved already",
+                join_request.candidate
+            );
+            return Ok(());
+        }
 
         for request in vault_actions.requests {
             match request {
@@ -164,9 +353,15 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                             },
                         };
 
-                        join_action.update(db_join_request, upd.clone()).await?;
+                        let dsa = self.user_creds.device_creds.key_manager()?.dsa;
+                        let approval = join_action
+                            .update(db_join_request, upd.clone(), &dsa)
+                            .await?;
 
-                        if let JoinActionUpdate::Accept = upd {
+                        // the candidate gets the shares once this approval completes the join quorum,
+                        // the candidate stays pending until then
+                        approvals.push(approval);
+                        if upd == JoinActionUpdate::Accept && vault.has_join_quorum(&approvals) {
                             let redistribution_vault = vault.clone().update_membership(
                                 UserMembership::Member(UserDataMember {
                                     user_data: join_request.candidate.clone(),
@@ -180,7 +375,10 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                         }
                     }
                 }
-                VaultActionRequestEvent::AddMetaPass(_) => {
+                VaultActionRequestEvent::AddMetaPass(_)
+                | VaultActionRequestEvent::RotateDeviceKeys(_)
+                | VaultActionRequestEvent::AddInvite(_)
+                | VaultActionRequestEvent::UpdateSettings(_) => {
                     //Ignore server side events (no need approval)
                 }
             }
@@ -219,7 +417,15 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
         let mut members = vault.members();
         members.sort_by_key(|member| member.user().device.device_id.to_string());
 
-        for pass_id in vault.secrets.iter() {
+        // the joined device gets every password name, also of the passwords whose shares can't be
+        // redistributed by this device
+        for vault_pass_id in vault.secrets.iter() {
+            self.reseal_pass_name(vault_pass_id, join_request, &members)
+                .await?;
+        }
+
+        for vault_pass_id in vault.secrets.iter() {
+            let pass_id = &vault_pass_id.opaque();
             let mut split_claim = ss_log_data
                 .claims
                 .values()
@@ -269,7 +475,7 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
             let Some(source_event) = self.p_obj.find_tail_event(source_desc).await? else {
                 debug!(
                     "Skip redistribution for pass {}, source share for local device not found",
-                    pass_id.name
+                    pass_id.id_str()
                 );
                 continue;
             };
@@ -292,7 +498,10 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                 .unwrap_or(1)
                 .max(1);
 
-            let mut shares_for_recovery: Vec<UserShareDto> = vec![source_share.clone()];
+            let mut shares_for_recovery: Vec<HeldShare> = vec![HeldShare {
+                holder: local_device_id.clone(),
+                share: source_share.clone(),
+            }];
             let mut collected_share_ids: HashSet<usize> = HashSet::from([source_share.share_id]);
 
             let mut candidate_devices: Vec<_> = members
@@ -302,17 +511,15 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
             candidate_devices.sort_by_key(|device_id| device_id.to_string());
             candidate_devices.dedup();
 
+            // all the available shares are collected, so that invalid ones can be discarded
             for receiver in candidate_devices {
-                if shares_for_recovery.len() >= shares_needed {
-                    break;
-                }
                 if receiver.eq(&local_device_id) {
                     continue;
                 }
 
                 let dist_desc = SsWorkflowDescriptor::Distribution(SsDistributionId {
                     pass_id: pass_id.clone(),
-                    receiver,
+                    receiver: receiver.clone(),
                 });
                 let Some(dist_event) = self.p_obj.find_tail_event(dist_desc).await? else {
                     continue;
@@ -338,26 +545,36 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                 };
 
                 if collected_share_ids.insert(share.share_id) {
-                    shares_for_recovery.push(share);
+                    shares_for_recovery.push(HeldShare {
+                        holder: receiver,
+                        share,
+                    });
                 }
             }
 
             if shares_for_recovery.len() < shares_needed {
                 debug!(
                     "Skip redistribution for pass {}: not enough shares to recover (need {}, got {})",
-                    pass_id.name,
+                    pass_id.id_str(),
                     shares_needed,
                     shares_for_recovery.len()
                 );
                 continue;
             }
 
-            let plain_secret = recover_from_shares(shares_for_recovery)?;
+            let verifier = ShareVerifier::new(local_device_id.clone(), &members);
+            let plain_secret = verifier.recover(shares_for_recovery)?.secret;
             let secure_pass = SecurePassInfo::from(PlainPassInfo {
                 pass_id: pass_id.clone(),
                 pass: plain_secret.text,
+                recovery_policy: None,
+                break_glass: None,
             });
-            let re_split = split2(secure_pass, vault.sss_cfg())?;
+            let mut re_split = split2(secure_pass, vault.sss_cfg())?;
+            re_split.sign_commitments(
+                &local_device_id,
+                &self.user_creds.device_creds.key_manager()?.dsa,
+            )?;
 
             if re_split.shares.len() != members.len() {
                 bail!("Invalid state: shares count does not match vault members count");
@@ -378,14 +595,14 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                     receiver: receiver.user().device.device_id.clone(),
                 };
 
-                let wf = SsWorkflowObject::Distribution(KvLogEvent {
-                    key: KvKey::from(SsWorkflowDescriptor::Distribution(dist_id)),
-                    value: SecretDistributionData {
+                let wf = SsWorkflowObject::Distribution(KvLogEvent::new(
+                    KvKey::from(SsWorkflowDescriptor::Distribution(dist_id)),
+                    SecretDistributionData {
                         vault_name: source_dist.value.vault_name.clone(),
                         claim_id: split_claim.dist_claim_id.clone(),
                         secret_message: EncryptedMessage::CipherShare { share: encrypted },
                     },
-                });
+                ));
                 self.p_obj.repo.save(wf).await?;
             }
 
@@ -396,13 +613,59 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                 .status
                 .statuses
                 .insert(joined_device_id.clone(), SsDistributionStatus::Pending);
-            p_ss.save_ss_log_event(split_claim.clone()).await?;
+            p_ss.save_local_ss_log_event(split_claim.clone()).await?;
             ss_log_data.claims.insert(split_claim.id.clone(), split_claim);
         }
 
         Ok(())
     }
 
+    /// The name of the password is sealed to the members it was created for,
+    /// re-seal it so that the joined device can read it as well
+    async fn reseal_pass_name(
+        &self,
+        vault_pass_id: &MetaPasswordId,
+        join_request: &JoinClusterEvent,
+        members: &[UserDataMember],
+    ) -> Result<()> {
+        // a clear name (of an older vault) is readable anyway
+        let Some(sealed_name) = &vault_pass_id.sealed_name else {
+            return Ok(());
+        };
+        if sealed_name.is_sealed_for(join_request.candidate.device.keys.transport_pk()) {
+            return Ok(());
+        }
+
+        let key_manager = self.user_creds.device_creds.key_manager()?;
+        let pass_id = vault_pass_id.clone().unseal(&key_manager.transport.sk());
+        if !pass_id.has_clear_name() {
+            return Ok(());
+        }
+
+        let local_device_id = self.user_creds.device_id();
+        let Some(sender) = members
+            .iter()
+            .find(|member| member.user().device.device_id.eq(local_device_id))
+        else {
+            return Ok(());
+        };
+
+        let receivers: Vec<TransportPk> = members
+            .iter()
+            .map(|member| member.user().device.keys.transport_pk().clone())
+            .collect();
+
+        let add_meta_pass = AddMetaPassEvent {
+            sender: sender.clone(),
+            meta_pass_id: pass_id.seal(&key_manager.transport, &receivers)?,
+            recovery_policy: None,
+            break_glass: None,
+        };
+
+        let p_device_log = PersistentDeviceLog::from(self.p_obj.clone());
+        p_device_log.save_add_meta_pass_request(add_meta_pass).await
+    }
+
     async fn get_vault_log_event(&self, member: &UserDataMember) -> Result<Option<VaultLogObject>> {
         let p_vault = PersistentVault::from(self.p_obj());
         let maybe_vault_log_event = {
@@ -432,6 +695,84 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
         Ok(ss_log_data)
     }
 
+    /// The receiver approves a claim of a secret with a recovery policy.
+    /// The approval is synced to the other members, the share is released once the claim has
+    /// all the approvals the policy requires
+    async fn approve_recover(
+        &self,
+        vault: &VaultData,
+        policy: &RecoveryPolicy,
+        claim: SsClaim,
+    ) -> Result<()> {
+        let local_device_id = self.user_creds.device_id().clone();
+
+        let mut approved_claim = claim.clone();
+        if let Some(SsDistributionStatus::Pending) = claim.status.get(&local_device_id) {
+            let dsa = self.user_creds.device_creds.key_manager()?.dsa;
+            let approval_data = SsApprovalData::sign(&claim, local_device_id.clone(), &dsa)?;
+            approved_claim.approve(approval_data.clone());
+
+            let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+            p_ss.save_local_ss_log_event(approved_claim.clone()).await?;
+
+            for recovery_db_id in claim.recovery_db_ids() {
+                if recovery_db_id.distribution_id.receiver.eq(&local_device_id) {
+                    let key = KvKey::from(SsWorkflowDescriptor::Approval(recovery_db_id));
+                    let approval_wf =
+                        SsWorkflowObject::Approval(KvLogEvent::new(key, approval_data));
+                    self.p_obj.repo.save(approval_wf).await?;
+                    break;
+                }
+            }
+        }
+
+        self.release_approved_share(vault, policy, approved_claim)
+            .await
+    }
+
+    async fn release_approved_share(
+        &self,
+        vault: &VaultData,
+        policy: &RecoveryPolicy,
+        claim: SsClaim,
+    ) -> Result<()> {
+        let local_device_id = self.user_creds.device_id().clone();
+
+        let is_approved = matches!(
+            claim.status.get(&local_device_id),
+            Some(SsDistributionStatus::Approved)
+        );
+        if !is_approved {
+            return Ok(());
+        }
+
+        if !policy.is_satisfied(&claim, vault) {
+            debug!(
+                "Claim {:?} has {} of {} approvals, the share is not released yet",
+                claim.id,
+                policy.approvals_of(&claim, vault),
+                policy.approvals
+            );
+            return Ok(());
+        }
+
+        let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+        // the share has been released already and waits for the next sync
+        let is_released = !p_ss.get_recoveries(claim.clone()).await?.is_empty();
+        if is_released {
+            return Ok(());
+        }
+
+        self.handle_recover(vault.clone(), claim.clone()).await?;
+
+        // keeps the share from being released again until the server confirms it
+        let mut released_claim = claim;
+        released_claim.status = released_claim.status.sent(local_device_id);
+        p_ss.save_local_ss_log_event(released_claim).await?;
+
+        Ok(())
+    }
+
     /// When the receiver accepts, re-encrypts its share for the claim sender and saves
     /// the recovery workflow locally. Sync will push it to the server so the sender can pull and decrypt.
     async fn handle_recover(&self, vault: VaultData, claim: SsClaim) -> Result<()> {
@@ -484,14 +825,14 @@ impl<Repo: KvLogEventRepo> MetaOrchestrator<Repo> {
                             let key =
                                 KvKey::from(SsWorkflowDescriptor::Recovery(claim_db_id.clone()));
 
-                            let new_wf_event = SsWorkflowObject::Recovery(KvLogEvent {
+                            let new_wf_event = SsWorkflowObject::Recovery(KvLogEvent::new(
                                 key,
-                                value: SecretDistributionData {
+                                SecretDistributionData {
                                     vault_name: self.user_creds.vault_name.clone(),
                                     claim_id: claim_db_id.claim_id,
                                     secret_message: msg,
                                 },
-                            });
+                            ));
 
                             p_ss.p_obj.repo.save(new_wf_event).await?;
                         }
@@ -515,7 +856,10 @@ mod tests {
     use crate::meta_tests::fixture_util::fixture::states::EmptyState;
     use crate::node::common::model::secret::SsDistributionId;
     use crate::node::common::model::vault::vault_data::VaultData;
+    use crate::crypto::key_pair::KeyPair;
+    use crate::node::db::descriptors::vault_descriptor::DeviceLogDescriptor;
     use crate::node::db::events::shared_secret_event::SsWorkflowObject;
+    use crate::node::db::events::vault::vault_log_event::VaultActionEvent;
     use crate::node::db::in_mem_db::InMemKvLogEventRepo;
     use crate::secret::MetaDistributor;
     use anyhow::Result;
@@ -572,9 +916,7 @@ mod tests {
             .update_membership(UserMembership::Member(joined_member.clone()))
             .add_secret(pass_id.clone());
 
-        let join_request = JoinClusterEvent {
-            candidate: joined_member.user().clone(),
-        };
+        let join_request = JoinClusterEvent::from(joined_member.user().clone());
 
         orchestrator
             .redistribute_existing_secrets(&updated_vault, &join_request)
@@ -621,9 +963,7 @@ mod tests {
         let updated_vault = single_member_vault
             .update_membership(UserMembership::Member(joined_member.clone()))
             .add_secret(pass_id.clone());
-        let join_request = JoinClusterEvent {
-            candidate: joined_member.user().clone(),
-        };
+        let join_request = JoinClusterEvent::from(joined_member.user().clone());
 
         orchestrator
             .redistribute_existing_secrets(&updated_vault, &join_request)
@@ -642,4 +982,48 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_pass_name_is_resealed_without_redistribution() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let client_creds = registry.state.user_creds.client.clone();
+        let client_member = registry.state.vault_data.client_membership.user_data_member();
+        let joined_member = registry.state.vault_data.vd_membership.user_data_member();
+
+        // the device holds no shares of the password, so there is nothing to redistribute
+        let key_manager = client_creds.device_creds.key_manager()?;
+        let pass_id = PlainPassInfo::new("prod-db-root".to_string(), "2bee|~".to_string())
+            .pass_id
+            .seal(&key_manager.transport, [&key_manager.transport.pk()])?;
+        let vault = VaultData::from(client_member)
+            .update_membership(UserMembership::Member(joined_member.clone()))
+            .add_secret(pass_id);
+
+        let orchestrator = MetaOrchestrator {
+            p_obj: registry.state.p_obj.client.clone(),
+            user_creds: client_creds.clone(),
+        };
+        let join_request = JoinClusterEvent::from(joined_member.user().clone());
+        orchestrator
+            .redistribute_existing_secrets(&vault, &join_request)
+            .await?;
+
+        let resealed = orchestrator
+            .p_obj
+            .get_object_events_from_beginning(DeviceLogDescriptor::from(client_creds.user_id()))
+            .await?
+            .into_iter()
+            .find_map(|event| match event.0.value {
+                VaultActionEvent::Request(VaultActionRequestEvent::AddMetaPass(add_meta_pass)) => {
+                    Some(add_meta_pass.meta_pass_id)
+                }
+                _ => None,
+            })
+            .expect("The name must be re-sealed to the joined device");
+
+        let joined_sk = &registry.state.user_creds.vd.device_creds.secret_box.transport.sk;
+        assert_eq!(resealed.unseal(joined_sk).name, "prod-db-root");
+
+        Ok(())
+    }
 }
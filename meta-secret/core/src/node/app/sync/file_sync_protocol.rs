@@ -0,0 +1,790 @@
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::keys::TransportPk;
+use crate::node::api::{
+    DataEventsResponse, DataSyncResponse, HandshakeResponse, ProtocolInfo, ReadSyncRequest,
+    ServerTailResponse, SyncRequest,
+};
+use crate::node::app::sync::sync_protocol::SyncProtocol;
+use crate::node::common::model::crypto::aead::AeadCipherText;
+use crate::node::common::model::device::common::{DeviceData, DeviceId};
+use crate::node::common::model::device::device_creds::DeviceCreds;
+use crate::secret::shared_secret::PlainText;
+use anyhow::{bail, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SyncBundleError {
+    /// The request has been queued and will be answered by the next response bundle
+    #[error("Sync request is waiting to be relayed to the server")]
+    AwaitingRelay,
+
+    #[error("Unexpected bundle kind: expected {expected:?}, got {actual:?}")]
+    UnexpectedKind {
+        expected: SyncBundleKind,
+        actual: SyncBundleKind,
+    },
+
+    #[error("Bundle sender {sender:?} doesn't match its keys")]
+    SenderMismatch { sender: DeviceId },
+
+    #[error("Invalid bundle signature from {sender:?}")]
+    InvalidSignature { sender: DeviceId },
+
+    /// The sender is not the device the bundle is expected from
+    #[error("Bundle sender {sender:?} is not trusted")]
+    UntrustedSender { sender: DeviceId },
+
+    #[error("No request bundle has been exported, there is no relay to take responses from")]
+    NoRelay,
+}
+
+impl SyncBundleError {
+    pub fn is_awaiting_relay(err: &anyhow::Error) -> bool {
+        matches!(
+            err.downcast_ref::<SyncBundleError>(),
+            Some(SyncBundleError::AwaitingRelay)
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncBundleKind {
+    /// Requests exported by an offline device
+    Request,
+    /// Server responses collected by the relay device
+    Response,
+}
+
+/// A request together with the server's answer (if it has been relayed already)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncExchange {
+    pub request: SyncRequest,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<DataSyncResponse>,
+}
+
+/// Encrypted content of a [`SyncBundle`]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncBundleContent {
+    /// Pending writes and reads, answered by the server in a response bundle.
+    /// Replication reads carry the tail cursors of the local logs,
+    /// the relay answers each of them with all the pages after the cursor.
+    pub exchanges: Vec<SyncExchange>,
+}
+
+impl From<Vec<SyncExchange>> for SyncBundleContent {
+    fn from(exchanges: Vec<SyncExchange>) -> Self {
+        Self { exchanges }
+    }
+}
+
+/// Signed and encrypted file carried between an offline device and a relay device.
+/// The payload is a [`SyncBundleContent`] encrypted for the sender and the receiver only.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncBundle {
+    pub kind: SyncBundleKind,
+    pub sender: DeviceData,
+    pub payload: AeadCipherText,
+    pub signature: Base64Text,
+}
+
+impl SyncBundle {
+    pub fn seal(
+        kind: SyncBundleKind,
+        creds: &DeviceCreds,
+        receiver: &TransportPk,
+        content: SyncBundleContent,
+    ) -> Result<Self> {
+        let key_manager = creds.key_manager()?;
+
+        let payload = {
+            let content_json = serde_json::to_string(&content)?;
+            key_manager
+                .transport
+                .encrypt_string(PlainText::from(content_json), receiver)?
+        };
+
+        let signature = key_manager.dsa.sign(Self::signed_text(kind, &payload)?);
+
+        Ok(Self {
+            kind,
+            sender: creds.device.clone(),
+            payload,
+            signature,
+        })
+    }
+
+    /// Verify the sender's signature and decrypt the bundle content. The sender has to be one of
+    /// the `trusted` devices, the keys a bundle declares prove nothing on their own
+    pub fn open(
+        &self,
+        kind: SyncBundleKind,
+        creds: &DeviceCreds,
+        trusted: &[DeviceData],
+    ) -> Result<SyncBundleContent> {
+        if self.kind != kind {
+            bail!(SyncBundleError::UnexpectedKind {
+                expected: kind,
+                actual: self.kind,
+            });
+        }
+
+        let sender_keys = &self.sender.keys;
+        let sender_id = self.sender.device_id.clone();
+        let is_trusted = trusted
+            .iter()
+            .any(|device| device.device_id == sender_id && device.keys.eq(sender_keys));
+        if !is_trusted {
+            bail!(SyncBundleError::UntrustedSender { sender: sender_id });
+        }
+
+        // the id of a device is not derived from its current keys after a key rotation
+        let is_sender_channel = self.payload.channel.contains(sender_keys.transport_pk());
+        if !is_sender_channel {
+            bail!(SyncBundleError::SenderMismatch { sender: sender_id });
+        }
+
+        let signed_text = Self::signed_text(self.kind, &self.payload)?;
+        if sender_keys
+            .dsa_pk
+            .verify(&signed_text, &self.signature)
+            .is_err()
+        {
+            bail!(SyncBundleError::InvalidSignature { sender: sender_id });
+        }
+
+        let key_manager = creds.key_manager()?;
+        let plain_text = self.payload.decrypt(&key_manager.transport.sk())?;
+        let content_json = String::try_from(&plain_text.msg)?;
+        Ok(serde_json::from_str(&content_json)?)
+    }
+
+    fn signed_text(kind: SyncBundleKind, payload: &AeadCipherText) -> Result<String> {
+        Ok(serde_json::to_string(&(kind, payload))?)
+    }
+}
+
+/// Local state of the file based sync: requests to be exported and responses to be consumed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSyncState {
+    pub outbox: Vec<SyncRequest>,
+    pub inbox: Vec<SyncExchange>,
+    /// Exported requests waiting for their responses, exported again until they are answered
+    #[serde(default)]
+    pub in_flight: Vec<SyncRequest>,
+    /// The relay device of the exported bundles, responses are accepted from it only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay: Option<DeviceData>,
+    /// Server tail of the last response bundle, the writes after it are already relayed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_tail: Option<ServerTailResponse>,
+}
+
+/// Sync protocol for air-gapped devices. Instead of talking to the server it keeps requests in
+/// a local state file, the requests are exported as a [`SyncBundle`] and relayed to the server
+/// by a connected device. Responses come back in another bundle and are served from the inbox.
+///
+/// Writes are accepted immediately. Reads that have no imported response yet are queued and
+/// answered with what the device already knows about the server: replication reads get no
+/// new events and the server tail is the one of the last response bundle. So a sync round
+/// runs to the end and collects every pending read. Only the very first server tail read,
+/// with nothing known about the server, fails with [`SyncBundleError::AwaitingRelay`].
+pub struct FileSyncProtocol {
+    pub state_path: PathBuf,
+}
+
+impl FileSyncProtocol {
+    pub fn load_state(&self) -> Result<FileSyncState> {
+        if !self.state_path.exists() {
+            return Ok(FileSyncState::default());
+        }
+
+        let state_json = fs::read_to_string(&self.state_path)?;
+        Ok(serde_json::from_str(&state_json)?)
+    }
+
+    fn save_state(&self, state: &FileSyncState) -> Result<()> {
+        fs::write(&self.state_path, serde_json::to_string_pretty(state)?)?;
+        Ok(())
+    }
+
+    /// Write all pending requests into a request bundle file addressed to the relay device.
+    /// Requests of earlier bundles that haven't been answered yet are exported again,
+    /// the requests leave the outbox only after the bundle file has been written.
+    pub fn export(
+        &self,
+        creds: &DeviceCreds,
+        relay: &DeviceData,
+        bundle_path: &Path,
+    ) -> Result<SyncBundle> {
+        let mut state = self.load_state()?;
+        let exchanges: Vec<SyncExchange> = state
+            .in_flight
+            .iter()
+            .chain(state.outbox.iter())
+            .map(|request| SyncExchange {
+                request: request.clone(),
+                response: None,
+            })
+            .collect();
+
+        let content = SyncBundleContent::from(exchanges);
+        let relay_pk = relay.keys.transport_pk();
+        let bundle = SyncBundle::seal(SyncBundleKind::Request, creds, relay_pk, content)?;
+        fs::write(bundle_path, serde_json::to_string_pretty(&bundle)?)?;
+
+        state.in_flight.append(&mut state.outbox);
+        state.relay = Some(relay.clone());
+        self.save_state(&state)?;
+
+        Ok(bundle)
+    }
+
+    /// Replace the inbox with the responses from the relay device,
+    /// the requests without a response stay in flight
+    pub fn import(&self, creds: &DeviceCreds, bundle: &SyncBundle) -> Result<()> {
+        let mut state = self.load_state()?;
+        let Some(relay) = state.relay.clone() else {
+            bail!(SyncBundleError::NoRelay);
+        };
+
+        let content = bundle.open(SyncBundleKind::Response, creds, &[relay])?;
+        info!("Import {} sync responses", content.exchanges.len());
+
+        state.inbox = content
+            .exchanges
+            .into_iter()
+            .filter(|exchange| exchange.response.is_some())
+            .collect();
+        let inbox = &state.inbox;
+        state
+            .in_flight
+            .retain(|request| !inbox.iter().any(|exchange| exchange.request.eq(request)));
+
+        let maybe_server_tail = state.inbox.iter().find_map(|exchange| match &exchange.response {
+            Some(DataSyncResponse::ServerTailResponse(server_tail)) => Some(server_tail.clone()),
+            _ => None,
+        });
+        if let Some(server_tail) = maybe_server_tail {
+            state.server_tail = Some(server_tail);
+        }
+
+        self.save_state(&state)
+    }
+
+    /// The answer to a read that is still waiting for the relay
+    fn pending_read_response(
+        state: &FileSyncState,
+        read_request: &ReadSyncRequest,
+    ) -> Result<DataSyncResponse> {
+        match read_request {
+            ReadSyncRequest::Vault(_) | ReadSyncRequest::SsRequest(_) => {
+                Ok(DataSyncResponse::Data(DataEventsResponse::from(vec![])))
+            }
+            ReadSyncRequest::ServerTail(_) => match &state.server_tail {
+                Some(server_tail) => Ok(DataSyncResponse::ServerTailResponse(server_tail.clone())),
+                None => bail!(SyncBundleError::AwaitingRelay),
+            },
+            ReadSyncRequest::SsRecoveryCompletion(_) | ReadSyncRequest::Handshake(_) => {
+                bail!(SyncBundleError::AwaitingRelay)
+            }
+        }
+    }
+}
+
+impl SyncProtocol for FileSyncProtocol {
+    async fn send(&self, request: SyncRequest) -> Result<DataSyncResponse> {
+        // the device speaks to the bundle file, the relay negotiates with the server on its own
+        if let SyncRequest::Read(read_request) = &request
+            && let ReadSyncRequest::Handshake(handshake) = read_request.as_ref()
+        {
+            let client = &handshake.client;
+            let handshake = HandshakeResponse::negotiate(client, &ProtocolInfo::current())?;
+            return Ok(DataSyncResponse::Handshake(handshake));
+        }
+
+        let mut state = self.load_state()?;
+
+        let maybe_exchange_index = state
+            .inbox
+            .iter()
+            .position(|exchange| exchange.request == request);
+
+        if let Some(exchange_index) = maybe_exchange_index {
+            let exchange = state.inbox.remove(exchange_index);
+            self.save_state(&state)?;
+
+            if let Some(response) = exchange.response {
+                return Ok(response);
+            }
+        }
+
+        let is_queued = state.outbox.contains(&request) || state.in_flight.contains(&request);
+        if !is_queued {
+            debug!("Queue sync request: {:?}", request);
+            state.outbox.push(request.clone());
+            self.save_state(&state)?;
+        }
+
+        match &request {
+            SyncRequest::Read(read_request) => Self::pending_read_response(&state, read_request),
+            SyncRequest::Write(_) => Ok(DataSyncResponse::Empty),
+        }
+    }
+}
+
+/// Relays a request bundle of an offline device to the server and builds the response bundle
+pub struct SyncBundleRelay<Sync: SyncProtocol> {
+    pub sync: Sync,
+    /// Devices of the vault members, the relay serves no one else
+    pub members: Vec<DeviceData>,
+}
+
+impl<Sync: SyncProtocol> SyncBundleRelay<Sync> {
+    /// Writes go to the server first, so the answers to the reads already include them
+    pub async fn relay(&self, creds: &DeviceCreds, bundle: &SyncBundle) -> Result<SyncBundle> {
+        let content = bundle.open(SyncBundleKind::Request, creds, &self.members)?;
+        info!("Relay {} sync requests", content.exchanges.len());
+
+        let (writes, reads): (Vec<_>, Vec<_>) = content
+            .exchanges
+            .into_iter()
+            .partition(|exchange| matches!(exchange.request, SyncRequest::Write(_)));
+
+        let mut exchanges = vec![];
+        for SyncExchange { request, .. } in writes.into_iter().chain(reads) {
+            let response = self.answer(&request).await?;
+            exchanges.push(SyncExchange {
+                request,
+                response: Some(response),
+            });
+        }
+
+        let receiver = bundle.sender.keys.transport_pk();
+        let content = SyncBundleContent::from(exchanges);
+        SyncBundle::seal(SyncBundleKind::Response, creds, receiver, content)
+    }
+
+    /// Replication reads are answered with every page after their cursor,
+    /// an offline device can't ask for the next page without another round trip
+    async fn answer(&self, request: &SyncRequest) -> Result<DataSyncResponse> {
+        let mut response = self.sync.send(request.clone()).await?;
+
+        let SyncRequest::Read(read_request) = request else {
+            return Ok(response);
+        };
+
+        let mut read_request = read_request.as_ref().clone();
+        while let DataSyncResponse::Data(data) = &mut response {
+            let Some(cursor) = data.next_page.take() else {
+                break;
+            };
+
+            if read_request.cursor().as_ref() == Some(&cursor) {
+                bail!("Invalid page cursor: {:?}", cursor);
+            }
+
+            read_request = read_request.with_cursor(cursor)?;
+            let page_request = SyncRequest::Read(Box::new(read_request.clone()));
+            let page = self.sync.send(page_request).await?.to_data()?;
+
+            data.events.extend(page.events);
+            data.next_page = page.next_page;
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::keys::fixture::KeyManagerFixture;
+    use crate::node::api::{
+        HandshakeRequest, PageCursor, ServerTailRequest, SsRequest, DEFAULT_PAGE_SIZE,
+    };
+    use crate::node::common::model::device::device_creds::fixture::DeviceCredentialsFixture;
+    use crate::node::common::model::device::device_creds::SecureDeviceCreds;
+    use crate::node::common::model::user::common::UserData;
+    use crate::node::common::model::vault::vault::VaultName;
+    use crate::node::db::descriptors::shared_secret_descriptor::SsLogDescriptor;
+    use crate::node::db::events::generic_log_event::GenericKvLogEvent;
+    use crate::node::db::events::local_event::DeviceCredsObject;
+    use crate::node::db::events::object_id::{ArtifactId, Next};
+    use tempfile::tempdir;
+
+    struct ServerTailSync;
+
+    impl SyncProtocol for ServerTailSync {
+        async fn send(&self, request: SyncRequest) -> Result<DataSyncResponse> {
+            match request {
+                SyncRequest::Read(_) => {
+                    Ok(DataSyncResponse::ServerTailResponse(ServerTailResponse {
+                        device_log_tail: None,
+                        ss_device_log_tail: None,
+                    }))
+                }
+                SyncRequest::Write(_) => Ok(DataSyncResponse::Empty),
+            }
+        }
+    }
+
+    /// Serves the ss log in two pages
+    struct PagedSsLogSync {
+        event: GenericKvLogEvent,
+    }
+
+    impl PagedSsLogSync {
+        fn first_page() -> ArtifactId {
+            ArtifactId::from(SsLogDescriptor::from(VaultName::test()))
+        }
+    }
+
+    impl SyncProtocol for PagedSsLogSync {
+        async fn send(&self, request: SyncRequest) -> Result<DataSyncResponse> {
+            let SyncRequest::Read(read_request) = request else {
+                return Ok(DataSyncResponse::Empty);
+            };
+
+            match *read_request {
+                ReadSyncRequest::SsRequest(ss_request) => {
+                    let first_page = Self::first_page();
+                    let next_page = if ss_request.ss_log == first_page {
+                        Some(PageCursor::SsLog(first_page.next()))
+                    } else {
+                        None
+                    };
+
+                    Ok(DataSyncResponse::Data(DataEventsResponse {
+                        events: vec![self.event.clone()],
+                        next_page,
+                    }))
+                }
+                _ => Ok(DataSyncResponse::ServerTailResponse(ServerTailResponse {
+                    device_log_tail: None,
+                    ss_device_log_tail: None,
+                })),
+            }
+        }
+    }
+
+    fn ss_log_request(creds: &DeviceCreds) -> SyncRequest {
+        let sender = UserData {
+            vault_name: VaultName::test(),
+            device: creds.device.clone(),
+        };
+        SyncRequest::Read(Box::new(ReadSyncRequest::SsRequest(SsRequest {
+            sender,
+            ss_log: PagedSsLogSync::first_page(),
+            page_size: DEFAULT_PAGE_SIZE,
+        })))
+    }
+
+    fn server_tail_request(creds: &DeviceCreds) -> SyncRequest {
+        let sender = UserData {
+            vault_name: VaultName::test(),
+            device: creds.device.clone(),
+        };
+        SyncRequest::Read(Box::new(ReadSyncRequest::ServerTail(ServerTailRequest {
+            sender,
+        })))
+    }
+
+    fn creds_event(creds: &DeviceCredentialsFixture) -> Result<GenericKvLogEvent> {
+        let secure_creds =
+            SecureDeviceCreds::build(creds.client.clone(), creds.client_master_key.pk()?)?;
+        Ok(GenericKvLogEvent::DeviceCreds(DeviceCredsObject::from(
+            secure_creds,
+        )))
+    }
+
+    fn write_request(creds: &DeviceCredentialsFixture) -> Result<SyncRequest> {
+        let event = creds_event(creds)?;
+        SyncRequest::signed_write(event, &creds.client.key_manager()?.dsa)
+    }
+
+    fn read_bundle(path: &Path) -> Result<SyncBundle> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    #[tokio::test]
+    async fn test_offline_round_trip() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let dir = tempdir()?;
+        let bundle_path = dir.path().join("request.bundle.json");
+        let offline_sync = FileSyncProtocol {
+            state_path: dir.path().join("sync-state.json"),
+        };
+
+        let tail_request = server_tail_request(&creds.client);
+        let write_request = write_request(&creds)?;
+
+        let write_response = offline_sync.send(write_request.clone()).await?;
+        assert_eq!(write_response, DataSyncResponse::Empty);
+
+        // nothing is known about the server before the first response bundle
+        let read_err = offline_sync.send(tail_request.clone()).await.unwrap_err();
+        assert!(SyncBundleError::is_awaiting_relay(&read_err));
+
+        let relay_device = creds.client_b.device.clone();
+        let request_bundle = offline_sync.export(&creds.client, &relay_device, &bundle_path)?;
+        assert_eq!(read_bundle(&bundle_path)?, request_bundle);
+        assert!(offline_sync.load_state()?.outbox.is_empty());
+
+        // exported writes are not queued again while the bundle is on its way
+        offline_sync.send(write_request).await?;
+        assert!(offline_sync.load_state()?.outbox.is_empty());
+
+        let relay = SyncBundleRelay {
+            sync: ServerTailSync,
+            members: vec![creds.client.device.clone()],
+        };
+        let response_bundle = relay.relay(&creds.client_b, &request_bundle).await?;
+        offline_sync.import(&creds.client, &response_bundle)?;
+        assert!(offline_sync.load_state()?.in_flight.is_empty());
+
+        let tail = offline_sync
+            .send(tail_request.clone())
+            .await?
+            .to_server_tail()?;
+        assert_eq!(tail.device_log_tail, None);
+
+        // Responses are consumed once, the next read is relayed again
+        // and meanwhile answered with the last known server tail
+        let known_tail = offline_sync.send(tail_request.clone()).await?.to_server_tail()?;
+        assert_eq!(known_tail, tail);
+        assert_eq!(offline_sync.load_state()?.outbox, vec![tail_request]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_all_pending_reads_are_relayed_in_one_round_trip() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let dir = tempdir()?;
+        let offline_sync = FileSyncProtocol {
+            state_path: dir.path().join("sync-state.json"),
+        };
+
+        let tail_request = server_tail_request(&creds.client);
+        let ss_request = ss_log_request(&creds.client);
+
+        let err = offline_sync.send(tail_request.clone()).await.unwrap_err();
+        assert!(SyncBundleError::is_awaiting_relay(&err));
+        // a replication read waiting for the relay has no new events yet
+        let pending_ss_log = offline_sync.send(ss_request.clone()).await?.to_data()?;
+        assert!(pending_ss_log.events.is_empty());
+        assert_eq!(pending_ss_log.next_page, None);
+
+        let relay_device = creds.client_b.device.clone();
+        let request_bundle =
+            offline_sync.export(&creds.client, &relay_device, &dir.path().join("request.json"))?;
+        let members = vec![creds.client.device.clone()];
+        let request_content =
+            request_bundle.open(SyncBundleKind::Request, &creds.client_b, &members)?;
+        assert_eq!(request_content.exchanges.len(), 2);
+
+        let relay = SyncBundleRelay {
+            sync: PagedSsLogSync {
+                event: creds_event(&creds)?,
+            },
+            members,
+        };
+        let response_bundle = relay.relay(&creds.client_b, &request_bundle).await?;
+        offline_sync.import(&creds.client, &response_bundle)?;
+
+        offline_sync.send(tail_request).await?.to_server_tail()?;
+        // both pages of the ss log come back in a single response
+        let ss_log = offline_sync.send(ss_request).await?.to_data()?;
+        assert_eq!(ss_log.events.len(), 2);
+        assert_eq!(ss_log.next_page, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_export_keeps_the_outbox() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let dir = tempdir()?;
+        let offline_sync = FileSyncProtocol {
+            state_path: dir.path().join("sync-state.json"),
+        };
+
+        let write_request = write_request(&creds)?;
+        offline_sync.send(write_request.clone()).await?;
+
+        let relay_device = creds.client_b.device.clone();
+        let missing_dir_path = dir.path().join("missing").join("request.json");
+        let export = offline_sync.export(&creds.client, &relay_device, &missing_dir_path);
+        assert!(export.is_err());
+
+        let state = offline_sync.load_state()?;
+        assert_eq!(state.outbox, vec![write_request]);
+        assert!(state.in_flight.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handshake_is_answered_locally() -> Result<()> {
+        let dir = tempdir()?;
+        let offline_sync = FileSyncProtocol {
+            state_path: dir.path().join("sync-state.json"),
+        };
+
+        let request = SyncRequest::Read(Box::new(ReadSyncRequest::Handshake(HandshakeRequest {
+            client: ProtocolInfo::current(),
+        })));
+        offline_sync.send(request).await?.to_handshake()?;
+        assert!(offline_sync.load_state()?.outbox.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tampered_bundle_is_rejected() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let relay_pk = creds.client_b.device.keys.transport_pk().clone();
+        let exchanges = vec![SyncExchange {
+            request: server_tail_request(&creds.client),
+            response: None,
+        }];
+
+        let bundle =
+            SyncBundle::seal(SyncBundleKind::Request, &creds.client, &relay_pk, exchanges.into())?;
+        let trusted = vec![creds.client.device.clone(), creds.vd.device.clone()];
+        assert!(bundle
+            .open(SyncBundleKind::Request, &creds.client_b, &trusted)
+            .is_ok());
+        assert!(bundle
+            .open(SyncBundleKind::Response, &creds.client_b, &trusted)
+            .is_err());
+        // Only the sender and the relay are able to read the bundle
+        assert!(bundle
+            .open(SyncBundleKind::Request, &creds.vd, &trusted)
+            .is_err());
+
+        let mut forged = bundle.clone();
+        forged.sender = creds.vd.device.clone();
+        let err = forged
+            .open(SyncBundleKind::Request, &creds.client_b, &trusted)
+            .unwrap_err();
+        assert!(err.downcast_ref::<SyncBundleError>().is_some());
+
+        let mut resigned = bundle;
+        resigned.signature = creds.vd.key_manager()?.dsa.sign("forged".to_string());
+        let err = resigned
+            .open(SyncBundleKind::Request, &creds.client_b, &trusted)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SyncBundleError>(),
+            Some(SyncBundleError::InvalidSignature { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bundles_of_untrusted_devices_are_rejected() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let dir = tempdir()?;
+        let offline_sync = FileSyncProtocol {
+            state_path: dir.path().join("sync-state.json"),
+        };
+
+        offline_sync.send(write_request(&creds)?).await?;
+        let request_bundle = offline_sync.export(
+            &creds.client,
+            &creds.client_b.device,
+            &dir.path().join("request.json"),
+        )?;
+
+        // the relay serves the members of its vault only
+        let foreign_relay = SyncBundleRelay {
+            sync: ServerTailSync,
+            members: vec![creds.vd.device.clone()],
+        };
+        let err = foreign_relay
+            .relay(&creds.client_b, &request_bundle)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SyncBundleError>(),
+            Some(SyncBundleError::UntrustedSender { .. })
+        ));
+
+        // anyone can seal a response to the offline device, it comes from the relay only
+        let forged_response = SyncBundle::seal(
+            SyncBundleKind::Response,
+            &creds.vd,
+            creds.client.device.keys.transport_pk(),
+            SyncBundleContent::from(vec![SyncExchange {
+                request: server_tail_request(&creds.client),
+                response: Some(DataSyncResponse::ServerTailResponse(ServerTailResponse {
+                    device_log_tail: None,
+                    ss_device_log_tail: None,
+                })),
+            }]),
+        )?;
+        let err = offline_sync
+            .import(&creds.client, &forged_response)
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SyncBundleError>(),
+            Some(SyncBundleError::UntrustedSender { .. })
+        ));
+        assert_eq!(offline_sync.load_state()?.in_flight.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_requests_are_exported_again() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let dir = tempdir()?;
+        let offline_sync = FileSyncProtocol {
+            state_path: dir.path().join("sync-state.json"),
+        };
+        let relay_device = creds.client_b.device.clone();
+        let members = vec![creds.client.device.clone()];
+
+        let write_request = write_request(&creds)?;
+        offline_sync.send(write_request.clone()).await?;
+        // the first bundle gets lost on its way to the relay
+        offline_sync.export(&creds.client, &relay_device, &dir.path().join("lost.json"))?;
+
+        let tail_request = server_tail_request(&creds.client);
+        assert!(offline_sync.send(tail_request.clone()).await.is_err());
+        let request_bundle =
+            offline_sync.export(&creds.client, &relay_device, &dir.path().join("request.json"))?;
+        let content = request_bundle.open(SyncBundleKind::Request, &creds.client_b, &members)?;
+        let requests: Vec<SyncRequest> = content
+            .exchanges
+            .into_iter()
+            .map(|exchange| exchange.request)
+            .collect();
+        assert_eq!(requests, vec![write_request.clone(), tail_request.clone()]);
+
+        // only the write has been answered, the read stays in flight
+        let partial_response = SyncBundle::seal(
+            SyncBundleKind::Response,
+            &creds.client_b,
+            creds.client.device.keys.transport_pk(),
+            SyncBundleContent::from(vec![SyncExchange {
+                request: write_request,
+                response: Some(DataSyncResponse::Empty),
+            }]),
+        )?;
+        offline_sync.import(&creds.client, &partial_response)?;
+        assert_eq!(offline_sync.load_state()?.in_flight, vec![tail_request]);
+
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
 pub mod api_url;
+pub mod file_sync_protocol;
 pub mod sync_gateway;
 pub mod sync_protocol;
@@ -1,11 +1,12 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::time::Duration;
 
 use tracing::{debug, error, info, instrument};
 
 use crate::node::api::{
-    DataEventsResponse, ReadSyncRequest, ServerTailRequest, ServerTailResponse, SsRequest,
-    SyncRequest, VaultRequest, WriteSyncRequest,
+    HandshakeRequest, HandshakeResponse, PageCursor, ProtocolInfo, ReadSyncRequest,
+    ServerTailRequest, ServerTailResponse, SsRequest, SyncRequest, UpgradeRequired, VaultRequest,
+    DEFAULT_PAGE_SIZE,
 };
 use crate::node::app::sync::sync_protocol::SyncProtocol;
 use crate::node::common::model::device::common::DeviceId;
@@ -21,11 +22,14 @@ use crate::node::db::events::object_id::ArtifactId;
 use crate::node::db::events::shared_secret_event::SsDeviceLogObject;
 use crate::node::db::events::vault::device_log_event::DeviceLogObject;
 use crate::node::db::objects::persistent_object::PersistentObject;
+use crate::node::db::actions::invite::InviteAction;
+use crate::node::db::objects::persistent_audit::PersistentAudit;
 use crate::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
 use crate::node::db::objects::persistent_vault::PersistentVault;
 use crate::node::db::repo::generic_db::KvLogEventRepo;
 use crate::node::db::repo::persistent_credentials::PersistentCredentials;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use crate::crypto::key_pair::DsaKeyPair;
 use crate::crypto::keys::TransportSk;
 
 pub struct SyncGateway<Repo: KvLogEventRepo, Sync: SyncProtocol> {
@@ -33,9 +37,26 @@ pub struct SyncGateway<Repo: KvLogEventRepo, Sync: SyncProtocol> {
     pub p_obj: Arc<PersistentObject<Repo>>,
     pub sync: Arc<Sync>,
     pub master_key: TransportSk,
+    /// Protocol negotiated with the server, kept until the server asks for an upgrade
+    handshake: Mutex<Option<HandshakeResponse>>,
 }
 
 impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
+    pub fn new(
+        id: String,
+        p_obj: Arc<PersistentObject<Repo>>,
+        sync: Arc<Sync>,
+        master_key: TransportSk,
+    ) -> Self {
+        Self {
+            id,
+            p_obj,
+            sync,
+            master_key,
+            handshake: Mutex::new(None),
+        }
+    }
+
     #[instrument(skip_all)]
     pub async fn run(&self) {
         info!("Run sync gateway");
@@ -66,18 +87,90 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
     ///  - vault, shared secret... - user has been registered, we can sync vault related events
     #[instrument(skip_all)]
     pub async fn sync(&self, user: UserData) -> Result<()> {
+        let result = self.sync_round(user).await;
+
+        let is_upgrade_required = result
+            .as_ref()
+            .is_err_and(|err| err.downcast_ref::<UpgradeRequired>().is_some());
+        if is_upgrade_required {
+            // the server has been replaced, negotiate again in the next round
+            self.reset_handshake();
+        }
+
+        result
+    }
+
+    async fn sync_round(&self, user: UserData) -> Result<()> {
+        self.negotiated_handshake().await?;
+
         let server_tail = self.get_server_tail(user.clone()).await?;
 
-        self.sync_device_log(&server_tail, user.user_id()).await?;
+        // every event written to the server is signed by the device
+        let dsa = self.device_dsa().await?;
+
+        self.sync_device_log(&server_tail, user.user_id(), &dsa).await?;
 
         let vault_sync_request = self.get_vault_request(user.clone()).await?;
         self.sync_vault(vault_sync_request).await?;
 
-        self.sync_shared_secrets(&server_tail, user).await?;
+        self.sync_shared_secrets(&server_tail, user, &dsa).await?;
 
         Ok(())
     }
 
+    /// Exchange protocol versions and features with the server,
+    /// fails with [`crate::node::api::UpgradeRequired`] if the client or the server is outdated
+    pub async fn handshake(&self) -> Result<HandshakeResponse> {
+        let client = ProtocolInfo::current();
+        let request = SyncRequest::Read(Box::from(ReadSyncRequest::Handshake(HandshakeRequest {
+            client: client.clone(),
+        })));
+
+        let server_handshake = self.sync.send(request).await?.to_handshake()?;
+        let mut handshake = HandshakeResponse::negotiate(&client, &server_handshake.server)?;
+        handshake.audit_key = server_handshake.audit_key;
+
+        if let Some(audit_key) = handshake.audit_key.clone() {
+            // audit logs signed by another key are rejected on read, the sync goes on
+            let p_audit = PersistentAudit::from(self.p_obj.clone());
+            if let Err(err) = p_audit.pin_key(audit_key).await {
+                error!("Server audit key is not accepted: {:?}", err);
+            }
+        }
+
+        *self.handshake.lock().unwrap_or_else(PoisonError::into_inner) = Some(handshake.clone());
+        Ok(handshake)
+    }
+
+    /// The cached handshake, the server is asked only once per gateway
+    async fn negotiated_handshake(&self) -> Result<HandshakeResponse> {
+        let maybe_handshake = self
+            .handshake
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        match maybe_handshake {
+            Some(handshake) => Ok(handshake),
+            None => self.handshake().await,
+        }
+    }
+
+    async fn device_dsa(&self) -> Result<DsaKeyPair> {
+        let creds_repo = PersistentCredentials {
+            p_obj: self.p_obj.clone(),
+            master_key: self.master_key.clone(),
+        };
+        let Some(device_creds) = creds_repo.get_device_creds().await? else {
+            bail!("Device credentials not found");
+        };
+        Ok(device_creds.key_manager()?.dsa)
+    }
+
+    fn reset_handshake(&self) {
+        *self.handshake.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
     async fn get_server_tail(&self, user_data: UserData) -> Result<ServerTailResponse> {
         let server_tail = {
             let server_tail_sync_request = self.get_server_tail_request(user_data).await?;
@@ -104,39 +197,49 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
         Ok(sync_request)
     }
 
+    /// Pull vault events page by page until the client is caught up with the server
     #[instrument(skip(self))]
-    async fn sync_vault(&self, vault_sync_request: SyncRequest) -> Result<()> {
-        let DataEventsResponse(data_sync_events) =
-            self.sync.send(vault_sync_request).await?.to_data()?;
+    async fn sync_vault(&self, vault_request: VaultRequest) -> Result<()> {
+        let mut vault_request = vault_request;
 
-        for new_event in data_sync_events {
-            debug!(
-                "id: {:?}. Sync gateway. New event from server: {:?}",
-                self.id, new_event
-            );
-            self.p_obj.repo.save(new_event).await?;
+        loop {
+            let request = SyncRequest::Read(Box::from(ReadSyncRequest::Vault(vault_request.clone())));
+            let data_sync_response = self.sync.send(request).await?.to_data()?;
+
+            for new_event in data_sync_response.events {
+                debug!(
+                    "id: {:?}. Sync gateway. New event from server: {:?}",
+                    self.id, new_event
+                );
+                self.p_obj.save_verified(new_event).await?;
+            }
+
+            match data_sync_response.next_page {
+                None => return Ok(()),
+                Some(PageCursor::Vault(next_tail)) if next_tail != vault_request.tail => {
+                    vault_request.tail = next_tail;
+                }
+                Some(cursor) => bail!("Invalid vault page cursor: {:?}", cursor),
+            }
         }
-        Ok(())
     }
 
-    async fn get_vault_request(&self, user: UserData) -> Result<SyncRequest> {
-        let vault_sync_request = {
-            let sender = user.clone();
-            let p_vault = PersistentVault::from(self.p_obj.clone());
-            let tail = p_vault.vault_tail(user).await?;
+    async fn get_vault_request(&self, user: UserData) -> Result<VaultRequest> {
+        let p_vault = PersistentVault::from(self.p_obj.clone());
+        let tail = p_vault.vault_tail(user.clone()).await?;
 
-            SyncRequest::Read(Box::from(ReadSyncRequest::Vault(VaultRequest {
-                sender,
-                tail,
-            })))
-        };
-        Ok(vault_sync_request)
+        Ok(VaultRequest {
+            sender: user,
+            tail,
+            page_size: DEFAULT_PAGE_SIZE,
+        })
     }
 
     async fn sync_ss_device_log(
         &self,
         server_tail: &ServerTailResponse,
         device_id: DeviceId,
+        dsa: &DsaKeyPair,
     ) -> Result<()> {
         let server_ss_device_log_tail_id = {
             let unit_id = || ArtifactId::from(SsDeviceLogDescriptor::from(device_id));
@@ -152,42 +255,51 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
             .await?;
 
         for ss_device_log_event in ss_device_log_events_to_sync {
-            let sync_request = SyncRequest::Write(Box::from(WriteSyncRequest::Event(
-                ss_device_log_event.to_generic(),
-            )));
+            let sync_request = SyncRequest::signed_write(ss_device_log_event.to_generic(), dsa)?;
             self.sync.send(sync_request).await?;
         }
 
         Ok(())
     }
 
-    async fn sync_ss_log(&self, user: UserData) -> Result<()> {
+    async fn sync_ss_log(&self, user: UserData, dsa: &DsaKeyPair) -> Result<()> {
         let vault_name = user.vault_name.clone();
-        let ss_sync_request = {
-            let ss_log_free_id = {
-                let obj_desc = SsLogDescriptor::from(vault_name.clone());
-                self.p_obj.find_free_id_by_obj_desc(obj_desc).await?
-            };
+        let mut ss_request = {
+            let ss_log_sync_id = PersistentSharedSecret::from(self.p_obj.clone())
+                .find_ss_log_sync_id(vault_name.clone())
+                .await?;
 
-            SyncRequest::Read(Box::from(ReadSyncRequest::SsRequest(SsRequest {
+            SsRequest {
                 sender: user.clone(),
-                ss_log: ss_log_free_id,
-            })))
+                ss_log: ss_log_sync_id,
+                page_size: DEFAULT_PAGE_SIZE,
+            }
         };
 
-        let DataEventsResponse(data_sync_events) =
-            self.sync.send(ss_sync_request).await?.to_data()?;
+        loop {
+            let ss_sync_request =
+                SyncRequest::Read(Box::from(ReadSyncRequest::SsRequest(ss_request.clone())));
+            let data_sync_response = self.sync.send(ss_sync_request).await?.to_data()?;
 
-        debug!(
-            ss_events_count = data_sync_events.len(),
-            "sync_ss_log: events received from server"
-        );
-        for new_event in data_sync_events {
             debug!(
-                "id: {:?}. Sync gateway. New ss event from server: {:?}",
-                self.id, new_event
+                ss_events_count = data_sync_response.events.len(),
+                "sync_ss_log: events received from server"
             );
-            self.p_obj.repo.save(new_event).await?;
+            for new_event in data_sync_response.events {
+                debug!(
+                    "id: {:?}. Sync gateway. New ss event from server: {:?}",
+                    self.id, new_event
+                );
+                self.p_obj.save_verified(new_event).await?;
+            }
+
+            match data_sync_response.next_page {
+                None => break,
+                Some(PageCursor::SsLog(next_ss_log)) if next_ss_log != ss_request.ss_log => {
+                    ss_request.ss_log = next_ss_log;
+                }
+                Some(cursor) => bail!("Invalid ss log page cursor: {:?}", cursor),
+            }
         }
 
         // Send claims
@@ -213,26 +325,60 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
                                 // Keep local split distributions so sender can perform future
                                 // redistributions for new members under current K=2 policy.
                                 // TODO(security): revisit retention strategy when migrating to K=N-1 resharing.
-                                let request = {
-                                    let event = WriteSyncRequest::Event(wf_event.to_generic());
-                                    SyncRequest::Write(Box::from(event))
-                                };
+                                let request = SyncRequest::signed_write(wf_event.to_generic(), dsa)?;
                                 self.sync.send(request).await?;
                             }
                         }
                     }
                     SecretDistributionType::Recover => {
                         if claim.sender.eq(&user.device.device_id) {
+                            let maybe_cancellation = p_ss.get_cancellation(claim.clone()).await?;
+                            if let Some(wf_event) = maybe_cancellation {
+                                let obj_id = wf_event.obj_id();
+                                let request = SyncRequest::signed_write(wf_event.to_generic(), dsa)?;
+                                match self.sync.send(request).await {
+                                    Ok(_) => {
+                                        self.p_obj.repo.delete(obj_id).await;
+                                    }
+                                    Err(e) => {
+                                        debug!(
+                                            "Failed to push claim cancellation, will retry: {:?}",
+                                            e
+                                        );
+                                    }
+                                }
+                            }
                             continue;
                         }
 
+                        let maybe_veto = p_ss.get_veto(claim.clone()).await?;
+                        if let Some(wf_event) = maybe_veto {
+                            let obj_id = wf_event.obj_id();
+                            let request = SyncRequest::signed_write(wf_event.to_generic(), dsa)?;
+                            match self.sync.send(request).await {
+                                Ok(_) => {
+                                    self.p_obj.repo.delete(obj_id).await;
+                                }
+                                Err(e) => {
+                                    debug!("Failed to push claim veto, will retry: {:?}", e);
+                                }
+                            }
+                        }
+
+                        // approvals go first, the server releases no share of a claim
+                        // that lacks the approvals of its recovery policy
+                        let approval_events = p_ss.get_approvals(claim.clone()).await?;
+                        for wf_event in approval_events {
+                            let obj_id = wf_event.obj_id();
+                            let request = SyncRequest::signed_write(wf_event.to_generic(), dsa)?;
+                            self.sync.send(request).await?;
+                            self.p_obj.repo.delete(obj_id).await;
+                        }
+
                         let wf_events = p_ss.get_recoveries(claim.clone()).await?;
                         for wf_event in wf_events {
                             let obj_id = wf_event.obj_id();
-                            let request = {
-                                let event = WriteSyncRequest::Event(wf_event.to_generic());
-                                SyncRequest::Write(Box::from(event))
-                            };
+                            let request = SyncRequest::signed_write(wf_event.to_generic(), dsa)?;
                             self.sync.send(request).await?;
                             self.p_obj.repo.delete(obj_id).await;
                         }
@@ -241,10 +387,7 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
                             let decline_events = p_ss.get_declines(claim.clone()).await?;
                             for wf_event in decline_events {
                                 let obj_id = wf_event.obj_id();
-                                let request = {
-                                    let event = WriteSyncRequest::Event(wf_event.to_generic());
-                                    SyncRequest::Write(Box::from(event))
-                                };
+                                let request = SyncRequest::signed_write(wf_event.to_generic(), dsa)?;
                                 match self.sync.send(request).await {
                                     Ok(_) => {
                                         self.p_obj.repo.delete(obj_id).await;
@@ -263,13 +406,14 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
         Ok(())
     }
 
-    #[instrument(skip(self))]
+    #[instrument(skip(self, dsa))]
     async fn sync_device_log(
         &self,
         server_tail: &ServerTailResponse,
         user_id: UserId,
+        dsa: &DsaKeyPair,
     ) -> Result<()> {
-        let device_log_events_to_sync = self.device_log_sync_request(server_tail, user_id).await?;
+        let device_log_events_to_sync = self.device_log_sync_request(server_tail, user_id, dsa).await?;
         for device_log_event in device_log_events_to_sync {
             self.sync.send(device_log_event).await?;
         }
@@ -281,31 +425,27 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
         &self,
         server_tail: &ServerTailResponse,
         user_id: UserId,
+        dsa: &DsaKeyPair,
     ) -> Result<Vec<SyncRequest>> {
         let tail_to_sync = match &server_tail.device_log_tail {
             None => ArtifactId::from(DeviceLogDescriptor::from(user_id)),
             Some(server_tail_id) => server_tail_id.clone(),
         };
 
-        let device_log_events_to_sync: Vec<SyncRequest> = self
-            .p_obj
+        self.p_obj
             .find_object_events::<DeviceLogObject>(tail_to_sync)
             .await?
             .into_iter()
-            .map(|device_log_event| {
-                SyncRequest::Write(Box::from(WriteSyncRequest::Event(
-                    device_log_event.to_generic(),
-                )))
-            })
-            .collect();
-        Ok(device_log_events_to_sync)
+            .map(|device_log_event| SyncRequest::signed_write(device_log_event.to_generic(), dsa))
+            .collect()
     }
 
-    #[instrument(skip(self))]
+    #[instrument(skip(self, dsa))]
     async fn sync_shared_secrets(
         &self,
         server_tail: &ServerTailResponse,
         user: UserData,
+        dsa: &DsaKeyPair,
     ) -> Result<()> {
         let vault_status = {
             let p_vault = PersistentVault {
@@ -319,15 +459,141 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> SyncGateway<Repo, Sync> {
             return Ok(());
         };
 
+        // a device that has joined with an invite takes no shares until its inviter is a member
+        let vault = PersistentVault::from(self.p_obj.clone())
+            .get_vault(user.vault_name())
+            .await?;
+        InviteAction::from(self.p_obj.clone())
+            .verify_join_invite(&vault.to_data())
+            .await?;
+
         //sync ss_device_log and ss_log
-        self.sync_ss_device_log(server_tail, user.device.device_id.clone())
+        self.sync_ss_device_log(server_tail, user.device.device_id.clone(), dsa)
             .await?;
-        self.sync_ss_log(user).await?;
+        self.sync_ss_log(user, dsa).await?;
 
         Ok(())
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key_pair::KeyPair;
+    use crate::crypto::keys::DsaPk;
+    use crate::crypto::keys::fixture::KeyManagerFixture;
+    use crate::node::api::{
+        DataEventsResponse, DataSyncResponse, HandshakeResponse, UpgradeComponent,
+    };
+    use crate::node::common::model::device::device_creds::fixture::DeviceCredentialsFixture;
+    use crate::node::common::model::vault::vault::VaultName;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct HandshakeCountingSync {
+        handshakes: AtomicUsize,
+        upgrade_required: AtomicBool,
+        audit_key: Option<DsaPk>,
+    }
+
+    impl SyncProtocol for HandshakeCountingSync {
+        async fn send(&self, request: SyncRequest) -> Result<DataSyncResponse> {
+            let SyncRequest::Read(read_request) = request else {
+                return Ok(DataSyncResponse::Empty);
+            };
+
+            let response = match *read_request {
+                ReadSyncRequest::Handshake(_) => {
+                    self.handshakes.fetch_add(1, Ordering::SeqCst);
+                    DataSyncResponse::Handshake(HandshakeResponse {
+                        server: ProtocolInfo::current(),
+                        features: vec![],
+                        audit_key: self.audit_key.clone(),
+                    })
+                }
+                ReadSyncRequest::ServerTail(_)
+                    if self.upgrade_required.swap(false, Ordering::SeqCst) =>
+                {
+                    DataSyncResponse::UpgradeRequired(UpgradeRequired {
+                        component: UpgradeComponent::Client,
+                        client_version: "1.0.0".to_string(),
+                        server_version: "2.0.0".to_string(),
+                        missing_features: vec![],
+                    })
+                }
+                ReadSyncRequest::ServerTail(_) => {
+                    DataSyncResponse::ServerTailResponse(ServerTailResponse {
+                        device_log_tail: None,
+                        ss_device_log_tail: None,
+                    })
+                }
+                _ => DataSyncResponse::Data(DataEventsResponse::from(vec![])),
+            };
+
+            Ok(response)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_is_cached_until_upgrade_required() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let sync = Arc::new(HandshakeCountingSync::default());
+        let gateway = SyncGateway::new(
+            "test_gw".to_string(),
+            Arc::new(PersistentObject::in_mem()),
+            sync.clone(),
+            creds.client_master_key.clone(),
+        );
+        let user = UserData {
+            vault_name: VaultName::test(),
+            device: creds.client.device.clone(),
+        };
+
+        gateway.negotiated_handshake().await?;
+        gateway.negotiated_handshake().await?;
+        assert_eq!(sync.handshakes.load(Ordering::SeqCst), 1);
+
+        sync.upgrade_required.store(true, Ordering::SeqCst);
+        let err = gateway.sync(user).await.unwrap_err();
+        assert!(err.downcast_ref::<UpgradeRequired>().is_some());
+        // the round with the upgrade error still used the cached handshake
+        assert_eq!(sync.handshakes.load(Ordering::SeqCst), 1);
+
+        gateway.negotiated_handshake().await?;
+        assert_eq!(sync.handshakes.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handshake_pins_the_first_audit_key() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let server_key = creds.server.key_manager()?.dsa.pk();
+        let other_key = creds.client.key_manager()?.dsa.pk();
+        let p_obj = Arc::new(PersistentObject::in_mem());
+
+        for audit_key in [server_key.clone(), other_key] {
+            let sync = Arc::new(HandshakeCountingSync {
+                audit_key: Some(audit_key.clone()),
+                ..HandshakeCountingSync::default()
+            });
+            let gateway = SyncGateway::new(
+                "test_gw".to_string(),
+                p_obj.clone(),
+                sync,
+                creds.client_master_key.clone(),
+            );
+
+            let handshake = gateway.handshake().await?;
+            assert_eq!(handshake.audit_key, Some(audit_key));
+        }
+
+        let pinned_key = PersistentAudit::from(p_obj).find_pinned_key().await?;
+        assert_eq!(pinned_key, Some(server_key));
+        Ok(())
+    }
+}
+
 #[cfg(any(test, feature = "test-framework"))]
 pub mod fixture {
     use crate::meta_tests::fixture_util::fixture::states::EmptyState;
@@ -343,19 +609,19 @@ pub mod fixture {
 
     impl<Sync: SyncProtocol> SyncGatewayFixture<Sync> {
         pub fn from(state: &EmptyState, server_sync: Arc<Sync>) -> Self {
-            let client_gw = Arc::new(SyncGateway {
-                id: "client_gw".to_string(),
-                p_obj: state.p_obj.client.clone(),
-                sync: server_sync.clone(),
-                master_key: state.device_creds.client_master_key.clone(),
-            });
-
-            let vd_gw = Arc::new(SyncGateway {
-                id: "vd_gw".to_string(),
-                p_obj: state.p_obj.vd.clone(),
-                sync: server_sync,
-                master_key: state.device_creds.vd_master_key.clone(),
-            });
+            let client_gw = Arc::new(SyncGateway::new(
+                "client_gw".to_string(),
+                state.p_obj.client.clone(),
+                server_sync.clone(),
+                state.device_creds.client_master_key.clone(),
+            ));
+
+            let vd_gw = Arc::new(SyncGateway::new(
+                "vd_gw".to_string(),
+                state.p_obj.vd.clone(),
+                server_sync,
+                state.device_creds.vd_master_key.clone(),
+            ));
 
             Self { client_gw, vd_gw }
         }
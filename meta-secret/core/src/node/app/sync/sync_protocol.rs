@@ -1,7 +1,13 @@
-use crate::node::api::{DataSyncResponse, SyncRequest};
+use crate::node::api::{
+    DataSyncResponse, SyncRequest, UpgradeRequired, PKG_VERSION_HEADER, PROTOCOL_VERSION,
+    PROTOCOL_VERSION_HEADER,
+};
 use crate::node::app::sync::api_url::ApiUrl;
-use anyhow::Result;
+use crate::CRATE_PKG_VERSION;
+use anyhow::{bail, Result};
 use reqwest::Client;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::Certificate;
 use std::time::Duration;
 
 pub trait SyncProtocol {
@@ -10,23 +16,64 @@ pub trait SyncProtocol {
 
 pub struct HttpSyncProtocol {
     pub api_url: ApiUrl,
+    client: Client,
+}
+
+impl HttpSyncProtocol {
+    pub fn new(api_url: ApiUrl) -> Self {
+        Self {
+            api_url,
+            client: Client::new(),
+        }
+    }
+
+    /// Trusts the PEM encoded certificate in addition to the system roots,
+    /// e.g. a self-signed certificate of a private meta-server
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_trusted_cert(api_url: ApiUrl, cert_pem: &[u8]) -> Result<Self> {
+        let cert = Certificate::from_pem(cert_pem)?;
+        let client = Client::builder().tls_certs_merge([cert]).build()?;
+        Ok(Self { api_url, client })
+    }
 }
 
 impl SyncProtocol for HttpSyncProtocol {
+    /// Fails with [`UpgradeRequired`] if the server doesn't speak the protocol of this client,
+    /// whichever request finds it out first
     async fn send(&self, request: SyncRequest) -> Result<DataSyncResponse> {
-        let client = Client::new();
         let url = self.api_url.get_url() + "/meta_request";
 
-        let response = client
+        let response = self
+            .client
             .post(url.clone())
             .timeout(Duration::from_secs(15))
             .header("Content-Type", "application/json")
             .header("Access-Control-Allow-Origin", url)
+            .header(PROTOCOL_VERSION_HEADER, PROTOCOL_VERSION.to_string())
+            .header(PKG_VERSION_HEADER, CRATE_PKG_VERSION)
             .json(&request)
             .send()
             .await?;
 
-        let result: DataSyncResponse = response.json().await?;
-        Ok(result)
+        let status = response.status();
+        let server_version = response
+            .headers()
+            .get(PKG_VERSION_HEADER)
+            .and_then(|version| version.to_str().ok())
+            .map(String::from);
+        let body = response.bytes().await?;
+
+        match serde_json::from_slice::<DataSyncResponse>(&body) {
+            Ok(DataSyncResponse::UpgradeRequired(upgrade)) => Err(upgrade.into()),
+            Ok(result) => Ok(result),
+            Err(_) if status.is_success() => {
+                Err(UpgradeRequired::unreadable_response(server_version).into())
+            }
+            Err(_) => bail!(
+                "Server error {}: {}",
+                status,
+                String::from_utf8_lossy(&body)
+            ),
+        }
     }
 }
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 use tracing::{info, instrument};
 
+use crate::node::app::auto_approval::{AutoApprovalSource, AutoApprover};
 use crate::node::app::meta_app::meta_client_service::MetaClientAccessProxy;
 use crate::node::app::orchestrator::MetaOrchestrator;
 use crate::node::app::sync::sync_gateway::SyncGateway;
@@ -19,6 +20,7 @@ pub struct VirtualDevice<Repo: KvLogEventRepo, Sync: SyncProtocol> {
     pub meta_client_proxy: Arc<MetaClientAccessProxy>,
     gateway: Arc<SyncGateway<Repo, Sync>>,
     master_key: TransportSk,
+    approver: AutoApprover,
 }
 
 impl<Repo: KvLogEventRepo, Sync: SyncProtocol> VirtualDevice<Repo, Sync> {
@@ -27,15 +29,19 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> VirtualDevice<Repo, Sync> {
         persistent_object: Arc<PersistentObject<Repo>>,
         meta_client_access_proxy: Arc<MetaClientAccessProxy>,
         gateway: Arc<SyncGateway<Repo, Sync>>,
-        master_key: TransportSk
+        master_key: TransportSk,
+        policy_source: AutoApprovalSource,
     ) -> Result<VirtualDevice<Repo, Sync>> {
         info!("Initialize virtual device event handler");
+        let policy = policy_source.load()?;
+        info!(source = %policy_source, ?policy, "Auto-approval policy");
 
         let virtual_device = Self {
             p_obj: persistent_object,
             meta_client_proxy: meta_client_access_proxy.clone(),
             gateway,
-            master_key
+            master_key,
+            approver: AutoApprover::new(policy),
         };
 
         Ok(virtual_device)
@@ -72,7 +78,7 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> VirtualDevice<Repo, Sync> {
             user_creds: user_creds.clone(),
         };
 
-        orchestrator.orchestrate().await?;
+        orchestrator.orchestrate_with(&self.approver).await?;
 
         self.gateway.sync(user_creds.user()).await?;
         Ok(())
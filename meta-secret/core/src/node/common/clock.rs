@@ -0,0 +1,232 @@
+//! Timestamps of the events. Every event gets the wall-clock time of its writer and
+//! a hybrid logical clock (HLC) timestamp: the HLC never goes backwards and it's always ahead of
+//! the events the node has seen, so the events can be ordered even if the clocks of the devices differ.
+
+use std::sync::{Mutex, PoisonError};
+use tracing::warn;
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// The clock of this node, shared by all the writers of the process
+static CLOCK: HybridClock = HybridClock::new();
+
+/// How far ahead of the local clock (in milliseconds) a remote timestamp can be.
+/// A timestamp further in the future would drag the clock of this node along with it
+pub const MAX_CLOCK_DRIFT: u64 = 60 * 60 * 1000;
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct HlcTimestamp {
+    /// Unix time in milliseconds, the greatest one the node has seen
+    pub wall: u64,
+    /// Orders the events that have the same wall time
+    pub logical: u32,
+}
+
+impl HlcTimestamp {
+    /// The timestamp right after this one, the wall time moves on once the logical counter runs out
+    fn successor(&self) -> Self {
+        match self.logical.checked_add(1) {
+            Some(logical) => Self {
+                wall: self.wall,
+                logical,
+            },
+            None => Self {
+                wall: self.wall.saturating_add(1),
+                logical: 0,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventTime {
+    /// Unix time in milliseconds by the clock of the writer
+    pub created_at: u64,
+    pub hlc: HlcTimestamp,
+    /// Unix time in milliseconds when the server received the event.
+    /// It's added after the event has been written, so it isn't a part of the event chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub received_at: Option<u64>,
+}
+
+impl EventTime {
+    /// Timestamps a new event by the clock of this node
+    pub fn now() -> Self {
+        let created_at = unix_time_millis();
+        Self {
+            created_at,
+            hlc: CLOCK.tick(created_at),
+            received_at: None,
+        }
+    }
+
+    /// Moves the clock of this node ahead of an event written by another node
+    pub fn observe(&self) {
+        CLOCK.observe(&self.hlc, unix_time_millis());
+    }
+}
+
+pub fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| u64::try_from(time.as_millis()).unwrap_or(u64::MAX))
+        .unwrap_or_default()
+}
+
+pub struct HybridClock {
+    last: Mutex<HlcTimestamp>,
+}
+
+impl HybridClock {
+    pub const fn new() -> Self {
+        Self {
+            last: Mutex::new(HlcTimestamp {
+                wall: 0,
+                logical: 0,
+            }),
+        }
+    }
+
+    /// Timestamp of a local event, `now` is the unix time in milliseconds
+    pub fn tick(&self, now: u64) -> HlcTimestamp {
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        *last = if now > last.wall {
+            HlcTimestamp {
+                wall: now,
+                logical: 0,
+            }
+        } else {
+            last.successor()
+        };
+        *last
+    }
+
+    /// Merges the timestamp of a remote event into the clock.
+    /// A timestamp too far in the future is ignored, the clock stays as it is
+    pub fn observe(&self, remote: &HlcTimestamp, now: u64) -> HlcTimestamp {
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        if remote.wall > now.saturating_add(MAX_CLOCK_DRIFT) {
+            warn!(
+                "Remote timestamp {} is too far ahead of the local clock {}, ignored",
+                remote.wall, now
+            );
+            return *last;
+        }
+
+        let wall = now.max(last.wall).max(remote.wall);
+
+        *last = if wall == last.wall && wall == remote.wall {
+            HlcTimestamp {
+                wall,
+                logical: last.logical.max(remote.logical),
+            }
+            .successor()
+        } else if wall == last.wall {
+            last.successor()
+        } else if wall == remote.wall {
+            remote.successor()
+        } else {
+            HlcTimestamp { wall, logical: 0 }
+        };
+        *last
+    }
+}
+
+impl Default for HybridClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_never_goes_backwards() {
+        let clock = HybridClock::new();
+
+        let first = clock.tick(1_000);
+        let same_time = clock.tick(1_000);
+        let clock_skew = clock.tick(900);
+        let later = clock.tick(1_100);
+
+        assert_eq!(
+            first,
+            HlcTimestamp {
+                wall: 1_000,
+                logical: 0
+            }
+        );
+        assert!(first < same_time);
+        assert!(same_time < clock_skew);
+        assert_eq!(clock_skew.wall, 1_000);
+        assert!(clock_skew < later);
+        assert_eq!(
+            later,
+            HlcTimestamp {
+                wall: 1_100,
+                logical: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_clock_moves_ahead_of_remote_events() {
+        let clock = HybridClock::new();
+        clock.tick(1_000);
+
+        let remote = HlcTimestamp {
+            wall: 5_000,
+            logical: 3,
+        };
+        let observed = clock.observe(&remote, 1_001);
+        assert!(remote < observed);
+
+        // the local clock is behind, the next local events are still ordered after the remote one
+        let next = clock.tick(1_002);
+        assert!(remote < next);
+        assert!(observed < next);
+    }
+
+    #[test]
+    fn test_clock_ignores_hostile_remote_timestamps() {
+        let clock = HybridClock::new();
+        let now = 1_000_000;
+        let local = clock.tick(now);
+
+        // a wall time near the end of time would pin the clock forever
+        let far_future = HlcTimestamp {
+            wall: u64::MAX,
+            logical: u32::MAX,
+        };
+        assert_eq!(local, clock.observe(&far_future, now));
+
+        let beyond_drift = HlcTimestamp {
+            wall: now + MAX_CLOCK_DRIFT + 1,
+            logical: 0,
+        };
+        assert_eq!(local, clock.observe(&beyond_drift, now));
+        // the clock keeps following the local time
+        assert_eq!(now + 1, clock.tick(now + 1).wall);
+
+        // the logical counter of a remote timestamp can't overflow the clock
+        let exhausted = HlcTimestamp {
+            wall: now + MAX_CLOCK_DRIFT,
+            logical: u32::MAX,
+        };
+        let observed = clock.observe(&exhausted, now);
+        assert!(exhausted < observed);
+        assert_eq!(
+            observed,
+            HlcTimestamp {
+                wall: now + MAX_CLOCK_DRIFT + 1,
+                logical: 0
+            }
+        );
+        assert!(observed < clock.tick(now));
+    }
+}
@@ -1,10 +1,74 @@
+use derive_more::{Display, From};
 use flume::{Drain, Receiver, RecvError, Sender};
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use thiserror::Error;
 use tracing::{instrument, Instrument};
 
+/// Identifies a request and its response, unique within one data transfer
+#[derive(Clone, Copy, Debug, Display, From, PartialEq, Eq, Hash)]
+pub struct CorrelationId(u64);
+
+#[derive(Debug, Error)]
+pub enum DataTransferError {
+    #[error("The service is not running")]
+    ServiceStopped,
+    #[error("The service dropped request {0} without a response")]
+    NoResponse(CorrelationId),
+    #[error("Request {id} timed out after {timeout:?}")]
+    Timeout {
+        id: CorrelationId,
+        timeout: Duration,
+    },
+    #[error("Request {0} was cancelled by the client")]
+    Cancelled(CorrelationId),
+}
+
+/// A request on its way to the service.
+/// The response goes back through a channel owned by this request only,
+/// so concurrent clients never receive each other's responses.
+#[derive(Debug)]
+pub struct MpscEnvelope<Request, Response> {
+    pub id: CorrelationId,
+    pub request: Request,
+    reply: Option<Sender<Response>>,
+}
+
+impl<Request, Response> MpscEnvelope<Request, Response> {
+    pub fn new(id: CorrelationId, request: Request) -> (Self, Receiver<Response>) {
+        let (reply, response) = flume::bounded(1);
+        let envelope = Self {
+            id,
+            request,
+            reply: Some(reply),
+        };
+        (envelope, response)
+    }
+
+    /// The client has stopped waiting for the response (timed out or dropped the request)
+    pub fn is_cancelled(&self) -> bool {
+        self.reply
+            .as_ref()
+            .is_some_and(|reply| reply.is_disconnected())
+    }
+
+    pub async fn reply(self, response: Response) -> Result<(), DataTransferError> {
+        let Some(reply) = self.reply else {
+            // fire-and-forget request, nobody waits for the response
+            return Ok(());
+        };
+
+        reply
+            .send_async(response)
+            .await
+            .map_err(|_| DataTransferError::Cancelled(self.id))
+    }
+}
+
 pub struct MpscDataTransfer<Request, Response> {
-    pub service_channel: MpscServiceChannel<Request>,
-    pub client_channel: MpscClientChannel<Response>,
+    pub service_channel: MpscServiceChannel<MpscEnvelope<Request, Response>>,
+    next_id: AtomicU64,
 }
 
 pub struct MpscServiceChannel<Request> {
@@ -13,8 +77,8 @@ pub struct MpscServiceChannel<Request> {
 }
 
 impl<Request> MpscServiceChannel<Request> {
-    fn new() -> MpscServiceChannel<Request> {
-        let (server_sender, server_receiver) = flume::bounded(10);
+    fn new(capacity: usize) -> MpscServiceChannel<Request> {
+        let (server_sender, server_receiver) = flume::bounded(capacity);
         MpscServiceChannel {
             sender: server_sender,
             receiver: server_receiver,
@@ -22,21 +86,6 @@ impl<Request> MpscServiceChannel<Request> {
     }
 }
 
-pub struct MpscClientChannel<Response> {
-    sender: Sender<Response>,
-    receiver: Receiver<Response>,
-}
-
-impl<Response> MpscClientChannel<Response> {
-    fn new() -> MpscClientChannel<Response> {
-        let (client_sender, client_receiver) = flume::bounded(10);
-        MpscClientChannel {
-            sender: client_sender,
-            receiver: client_receiver,
-        }
-    }
-}
-
 impl<Request, Response> Default for MpscDataTransfer<Request, Response> {
     fn default() -> Self {
         Self::new()
@@ -45,31 +94,47 @@ impl<Request, Response> Default for MpscDataTransfer<Request, Response> {
 
 impl<Request, Response> MpscDataTransfer<Request, Response> {
     pub fn new() -> MpscDataTransfer<Request, Response> {
+        Self::with_capacity(10)
+    }
+
+    /// `capacity` is the number of requests waiting for the service
+    pub fn with_capacity(capacity: usize) -> MpscDataTransfer<Request, Response> {
         MpscDataTransfer {
-            service_channel: MpscServiceChannel::new(),
-            client_channel: MpscClientChannel::new(),
+            service_channel: MpscServiceChannel::new(capacity),
+            next_id: AtomicU64::new(0),
         }
     }
+
+    fn next_id(&self) -> CorrelationId {
+        CorrelationId(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
 }
 
 impl<Request: Debug, Response: Debug> MpscDataTransfer<Request, Response> {
+    /// Sends a request without waiting for the response
     #[instrument(skip(self))]
     pub async fn send_to_service(&self, message: Request) {
+        let envelope = MpscEnvelope {
+            id: self.next_id(),
+            request: message,
+            reply: None,
+        };
+
         let _ = self
             .service_channel
             .sender
-            .send_async(message)
+            .send_async(envelope)
             .in_current_span()
             .await;
     }
 
     #[instrument(skip(self))]
-    pub fn service_drain(&self) -> Drain<Request> {
+    pub fn service_drain(&self) -> Drain<'_, MpscEnvelope<Request, Response>> {
         self.service_channel.receiver.drain()
     }
 
     #[instrument(skip(self))]
-    pub async fn service_receive(&self) -> Result<Request, RecvError> {
+    pub async fn service_receive(&self) -> Result<MpscEnvelope<Request, Response>, RecvError> {
         self.service_channel
             .receiver
             .recv_async()
@@ -77,29 +142,113 @@ impl<Request: Debug, Response: Debug> MpscDataTransfer<Request, Response> {
             .await
     }
 
+    /// Sends a request and waits for its own response.
+    /// Dropping the returned future cancels the request.
     #[instrument(skip(self))]
-    pub async fn send_to_service_and_get(&self, message: Request) -> Result<Response, RecvError> {
-        let _ = self
-            .service_channel
+    pub async fn send_to_service_and_get(
+        &self,
+        message: Request,
+    ) -> Result<Response, DataTransferError> {
+        self.exchange(self.next_id(), message).await
+    }
+
+    /// Same as [`Self::send_to_service_and_get`], the request is cancelled if there is
+    /// no response within the timeout
+    #[instrument(skip(self))]
+    pub async fn send_to_service_and_get_timeout(
+        &self,
+        message: Request,
+        timeout: Duration,
+    ) -> Result<Response, DataTransferError> {
+        let id = self.next_id();
+        async_std::future::timeout(timeout, self.exchange(id, message))
+            .await
+            .map_err(|_| DataTransferError::Timeout { id, timeout })?
+    }
+
+    async fn exchange(
+        &self,
+        id: CorrelationId,
+        message: Request,
+    ) -> Result<Response, DataTransferError> {
+        let (envelope, response) = MpscEnvelope::new(id, message);
+
+        self.service_channel
             .sender
-            .send_async(message)
+            .send_async(envelope)
             .in_current_span()
-            .await;
-        //receive a message from the service via client channel
-        self.client_channel
-            .receiver
+            .await
+            .map_err(|_| DataTransferError::ServiceStopped)?;
+
+        response
             .recv_async()
             .in_current_span()
             .await
+            .map_err(|_| DataTransferError::NoResponse(id))
     }
+}
 
-    #[instrument(skip(self))]
-    pub async fn send_to_client(&self, events: Response) {
-        let _ = self
-            .client_channel
-            .sender
-            .send_async(events)
-            .in_current_span()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_concurrent_clients_get_own_responses() {
+        let dt = MpscDataTransfer::<u64, u64>::new();
+
+        let service = async {
+            // answer in reverse order to make sure responses are matched by id, not by order
+            let mut envelopes = vec![];
+            for _ in 0..3 {
+                envelopes.push(dt.service_receive().await.unwrap());
+            }
+            for envelope in envelopes.into_iter().rev() {
+                let response = envelope.request * 10;
+                envelope.reply(response).await.unwrap();
+            }
+        };
+
+        let clients = async {
+            tokio::join!(
+                dt.send_to_service_and_get(1),
+                dt.send_to_service_and_get(2),
+                dt.send_to_service_and_get(3),
+            )
+        };
+
+        let (_, (first, second, third)) = tokio::join!(service, clients);
+        assert_eq!(first.unwrap(), 10);
+        assert_eq!(second.unwrap(), 20);
+        assert_eq!(third.unwrap(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_cancels_request() {
+        let dt = MpscDataTransfer::<u64, u64>::new();
+
+        let result = dt
+            .send_to_service_and_get_timeout(1, Duration::from_millis(10))
             .await;
+        assert!(matches!(result, Err(DataTransferError::Timeout { .. })));
+
+        let envelope = dt.service_receive().await.unwrap();
+        assert!(envelope.is_cancelled());
+        assert!(matches!(
+            envelope.reply(10).await,
+            Err(DataTransferError::Cancelled(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_envelope_is_no_response() {
+        let dt = MpscDataTransfer::<u64, u64>::new();
+
+        let service = async {
+            let envelope = dt.service_receive().await.unwrap();
+            drop(envelope);
+        };
+
+        let (_, result) = tokio::join!(service, dt.send_to_service_and_get(1));
+        assert!(matches!(result, Err(DataTransferError::NoResponse(_))));
     }
 }
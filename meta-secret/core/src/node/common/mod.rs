@@ -1,4 +1,5 @@
 pub mod actor;
+pub mod clock;
 pub mod data_transfer;
 pub mod meta_tracing;
 pub mod model;
@@ -2,7 +2,7 @@ use age::armor::{ArmoredWriter, Format};
 use std::io::Write;
 
 use crate::crypto::encoding::base64::Base64Text;
-use crate::crypto::keys::TransportSk;
+use crate::crypto::keys::{TransportPk, TransportSk};
 use crate::node::common::model::crypto::channel::CommunicationChannel;
 use anyhow::{bail, Result};
 
@@ -32,6 +32,16 @@ impl AeadCipherText {
 
         Ok(plain_text)
     }
+
+    /// Re-encrypt the message after a party of the channel has replaced its transport key
+    pub fn rotate(&self, old_sk: &TransportSk, new_pk: &TransportPk) -> Result<AeadCipherText> {
+        let AeadPlainText { msg, channel } = self.decrypt(old_sk)?;
+        let plain_text = AeadPlainText {
+            msg,
+            channel: channel.rotate(&old_sk.pk()?, new_pk),
+        };
+        plain_text.encrypt()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
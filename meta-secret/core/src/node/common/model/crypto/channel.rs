@@ -116,6 +116,18 @@ impl CommunicationChannel {
         }
     }
 
+    /// The same channel after one of the parties has replaced its key
+    pub fn rotate(&self, old_pk: &TransportPk, new_pk: &TransportPk) -> CommunicationChannel {
+        let rotate = |pk: &TransportPk| {
+            if pk.eq(old_pk) {
+                new_pk.clone()
+            } else {
+                pk.clone()
+            }
+        };
+        CommunicationChannel::build(rotate(self.sender()), rotate(self.receiver()))
+    }
+
     pub fn contains(&self, pk: &TransportPk) -> bool {
         match self {
             CommunicationChannel::End2End(channel) => {
@@ -1,2 +1,3 @@
 pub mod aead;
 pub mod channel;
+pub mod sealed;
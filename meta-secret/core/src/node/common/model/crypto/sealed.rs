@@ -0,0 +1,88 @@
+use crate::crypto::key_pair::{KeyPair, TransportDsaKeyPair};
+use crate::crypto::keys::{TransportPk, TransportSk};
+use crate::node::common::model::crypto::aead::AeadCipherText;
+use crate::secret::shared_secret::PlainText;
+use anyhow::{bail, Result};
+
+/// A human-readable name encrypted to a set of devices, one age message per device.
+/// The server stores the sealed name and never sees the name itself.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SealedName {
+    pub ciphers: Vec<AeadCipherText>,
+}
+
+impl SealedName {
+    /// Encrypts the name for every receiver. Each message goes through a channel
+    /// between the sender and a receiver, so the sender is always able to open it as well
+    pub fn seal<'a>(
+        name: &str,
+        sender: &TransportDsaKeyPair,
+        receivers: impl IntoIterator<Item = &'a TransportPk>,
+    ) -> Result<Self> {
+        let mut ciphers: Vec<AeadCipherText> = vec![];
+        let mut receivers: Vec<TransportPk> = receivers.into_iter().cloned().collect();
+        if receivers.is_empty() {
+            receivers.push(sender.pk());
+        }
+
+        for receiver in receivers {
+            let already_sealed = ciphers
+                .iter()
+                .any(|cipher| cipher.channel.receiver() == &receiver);
+            if already_sealed {
+                continue;
+            }
+
+            ciphers.push(sender.encrypt_string(PlainText::from(name), &receiver)?);
+        }
+
+        Ok(Self { ciphers })
+    }
+
+    pub fn is_sealed_for(&self, pk: &TransportPk) -> bool {
+        self.ciphers
+            .iter()
+            .any(|cipher| cipher.channel.contains(pk))
+    }
+
+    pub fn open(&self, sk: &TransportSk) -> Result<String> {
+        let pk = sk.pk()?;
+        let Some(cipher) = self
+            .ciphers
+            .iter()
+            .find(|cipher| cipher.channel.contains(&pk))
+        else {
+            bail!("The name is not sealed for the device");
+        };
+
+        let plain_text = cipher.decrypt(sk)?;
+        let name = String::try_from(&plain_text.msg)?;
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::keys::fixture::KeyManagerFixture;
+
+    #[test]
+    fn test_sealed_name_opens_only_for_receivers() -> Result<()> {
+        let fixture = KeyManagerFixture::generate();
+        let receivers = [fixture.vd.transport.pk()];
+
+        let sealed = SealedName::seal("prod-db-root", &fixture.client.transport, &receivers)?;
+
+        assert_eq!(sealed.ciphers.len(), 1);
+        assert_eq!(sealed.open(&fixture.vd.transport.sk())?, "prod-db-root");
+        assert_eq!(sealed.open(&fixture.client.transport.sk())?, "prod-db-root");
+        assert!(!sealed.is_sealed_for(&fixture.client_b.transport.pk()));
+        assert!(sealed.open(&fixture.client_b.transport.sk()).is_err());
+
+        let json = serde_json::to_string(&sealed)?;
+        assert!(!json.contains("prod-db-root"));
+
+        Ok(())
+    }
+}
@@ -1,12 +1,16 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
 use wasm_bindgen::prelude::wasm_bindgen;
 
+use crate::crypto::encoding::base64::Base64Text;
 use crate::crypto::keys::OpenBox;
 use crate::crypto::utils::{U64IdUrlEnc, UuidUrlEnc};
 use crate::node::common::model::device::device_link::LoopbackDeviceLink;
 use crate::node::common::model::IdString;
 
+/// Stable identity of a device. It's derived from the first transport key of the device
+/// and doesn't change when the device rotates its keys
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(transparent)]
@@ -44,7 +48,7 @@ impl From<&OpenBox> for DeviceId {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[wasm_bindgen(getter_with_clone)]
 pub struct DeviceName(String);
@@ -89,6 +93,10 @@ impl DeviceName {
         let uuid = UuidUrlEnc::generate();
         DeviceName(uuid.id_str())
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -190,14 +198,40 @@ pub fn device_ui_category(device_type: &DeviceType) -> DeviceUiCategory {
     DeviceUiCategory::Other
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// The name of the device is only for display, two copies of the device are the same device
+/// whether the name has been decrypted or not, see `VaultLabel`
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[wasm_bindgen(getter_with_clone)]
 pub struct DeviceData {
     pub device_id: DeviceId,
+    /// Name given to the device, empty until it's decrypted on a device of the vault
+    #[serde(default)]
     pub device_name: DeviceName,
     pub device_type: DeviceType,
     pub keys: OpenBox,
+    /// The name encrypted with the key of the vault label, the server never sees the name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[wasm_bindgen(skip)]
+    pub sealed_name: Option<Base64Text>,
+}
+
+impl PartialEq for DeviceData {
+    fn eq(&self, other: &Self) -> bool {
+        self.device_id == other.device_id
+            && self.device_type == other.device_type
+            && self.keys == other.keys
+    }
+}
+
+impl Eq for DeviceData {}
+
+impl Hash for DeviceData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.device_id.hash(state);
+        self.device_type.hash(state);
+        self.keys.hash(state);
+    }
 }
 
 /// Contains only public information about device
@@ -208,12 +242,21 @@ impl DeviceData {
             device_type,
             device_id: DeviceId::from(&open_box),
             keys: open_box,
+            sealed_name: None,
         }
     }
 
     pub fn from(device_name: DeviceName, open_box: OpenBox) -> Self {
         Self::from_with_type(device_name, DeviceType::other(), open_box)
     }
+
+    /// The same device with rotated keys, the device id stays the same
+    pub fn with_keys(&self, keys: OpenBox) -> Self {
+        Self {
+            keys,
+            ..self.clone()
+        }
+    }
 }
 
 #[wasm_bindgen]
@@ -96,6 +96,13 @@ impl DeviceCreds {
         let key_manager = KeyManager::try_from(&self.secret_box)?;
         Ok(key_manager)
     }
+
+    /// Generates new keys for the same device, the device id, name and type stay the same
+    pub fn rotate_keys(&self) -> DeviceCreds {
+        let secret_box = KeyManager::generate_secret_box();
+        let device = self.device.with_keys(OpenBox::from(&secret_box));
+        DeviceCreds { secret_box, device }
+    }
 }
 
 #[cfg(any(test, feature = "test-framework"))]
@@ -1,17 +1,82 @@
+use crate::crypto::key_pair::TransportDsaKeyPair;
+use crate::crypto::keys::{TransportPk, TransportSk};
 use crate::crypto::utils::U64IdUrlEnc;
+use crate::node::common::model::crypto::sealed::SealedName;
+use crate::node::common::model::secret::{BreakGlassPolicy, RecoveryPolicy};
+use anyhow::Result;
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
 use wasm_bindgen::prelude::wasm_bindgen;
 
 pub const SALT_LENGTH: usize = 8;
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+/// A password is identified by its id, the name is only for display.
+/// The name never leaves the device in clear text: the vault keeps the name sealed
+/// to the vault members, and claims and workflow events carry the id only.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[wasm_bindgen(getter_with_clone)]
 pub struct MetaPasswordId {
     pub id: U64IdUrlEnc,
-    /// Human-readable name given to the password
+    /// Human-readable name given to the password, empty until it's decrypted on the device
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[wasm_bindgen(skip)]
+    pub sealed_name: Option<SealedName>,
+}
+
+impl PartialEq for MetaPasswordId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for MetaPasswordId {}
+
+impl Hash for MetaPasswordId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl MetaPasswordId {
+    /// The id without the name, the way claims and workflow events reference the password
+    pub fn opaque(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            name: String::new(),
+            sealed_name: None,
+        }
+    }
+
+    /// The id with the name sealed to the receivers (and the sender), the clear name is dropped
+    pub fn seal<'a>(
+        &self,
+        sender: &TransportDsaKeyPair,
+        receivers: impl IntoIterator<Item = &'a TransportPk>,
+    ) -> Result<Self> {
+        let sealed_name = SealedName::seal(&self.name, sender, receivers)?;
+        Ok(Self {
+            id: self.id.clone(),
+            name: String::new(),
+            sealed_name: Some(sealed_name),
+        })
+    }
+
+    /// Decrypts the sealed name, the name stays empty if the device can't open it
+    pub fn unseal(mut self, sk: &TransportSk) -> Self {
+        let maybe_name = self.sealed_name.as_ref().map(|sealed| sealed.open(sk));
+        if let Some(Ok(name)) = maybe_name {
+            self.name = name;
+        }
+        self
+    }
+
+    pub fn has_clear_name(&self) -> bool {
+        !self.name.is_empty()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -19,12 +84,32 @@ pub struct MetaPasswordId {
 pub struct PlainPassInfo {
     pub pass_id: MetaPasswordId,
     pub pass: String,
+    #[wasm_bindgen(skip)]
+    pub recovery_policy: Option<RecoveryPolicy>,
+    #[wasm_bindgen(skip)]
+    pub break_glass: Option<BreakGlassPolicy>,
+}
+
+impl PlainPassInfo {
+    pub fn with_recovery_policy(mut self, recovery_policy: RecoveryPolicy) -> Self {
+        self.recovery_policy = Some(recovery_policy);
+        self
+    }
+
+    pub fn with_break_glass(mut self, break_glass: BreakGlassPolicy) -> Self {
+        self.break_glass = Some(break_glass);
+        self
+    }
 }
 
 #[derive(Debug)]
 pub struct SecurePassInfo {
     pub pass_id: MetaPasswordId,
     pub pass: SecretString,
+    /// Approvals the recovery of the secret requires
+    pub recovery_policy: Option<RecoveryPolicy>,
+    /// Escrow device of the break-glass recovery of the secret
+    pub break_glass: Option<BreakGlassPolicy>,
 }
 
 impl From<PlainPassInfo> for SecurePassInfo {
@@ -32,6 +117,8 @@ impl From<PlainPassInfo> for SecurePassInfo {
         Self {
             pass_id: plain.pass_id,
             pass: SecretString::new(plain.pass.into()),
+            recovery_policy: plain.recovery_policy,
+            break_glass: plain.break_glass,
         }
     }
 }
@@ -39,13 +126,20 @@ impl From<PlainPassInfo> for SecurePassInfo {
 impl SecurePassInfo {
     pub fn new(pass: SecretString, pass_name: String) -> Self {
         let pass_id = MetaPasswordId::build(pass_name);
-        Self { pass_id, pass }
+        Self {
+            pass_id,
+            pass,
+            recovery_policy: None,
+            break_glass: None,
+        }
     }
 
     pub fn to_plain(&self) -> PlainPassInfo {
         PlainPassInfo {
             pass_id: self.pass_id.clone(),
             pass: ExposeSecret::expose_secret(&self.pass).to_string(),
+            recovery_policy: self.recovery_policy.clone(),
+            break_glass: self.break_glass.clone(),
         }
     }
 }
@@ -61,10 +155,14 @@ impl MetaPasswordId {
         Self::build(name.to_string())
     }
 
+    /// A new password gets a random id: a hash of the name would let anyone
+    /// who sees the id confirm a guessed name. Passwords are found by name
+    /// on the device, where the names are unsealed, see `VaultData::find_secret_by_name`
     pub fn build(name: String) -> Self {
         Self {
-            id: U64IdUrlEnc::from(name.clone()),
+            id: U64IdUrlEnc::generate(),
             name,
+            sealed_name: None,
         }
     }
 }
@@ -77,6 +175,8 @@ impl PlainPassInfo {
         Self {
             pass_id,
             pass: pass.to_string(),
+            recovery_policy: None,
+            break_glass: None,
         }
     }
 }
@@ -96,9 +196,9 @@ mod tests {
         // Verify an id was generated
         assert!(!password_id.id_str().is_empty());
 
-        // Verify the id is deterministic (same name produces same id)
+        // Verify the id doesn't depend on the name
         let password_id2 = MetaPasswordId::build(name);
-        assert_eq!(password_id.id_str(), password_id2.id_str());
+        assert_ne!(password_id.id_str(), password_id2.id_str());
     }
 
     #[test]
@@ -125,12 +225,37 @@ mod tests {
     fn test_meta_password_id_equality() {
         let password1 = MetaPasswordId::build(String::from("Test Password"));
         let password2 = MetaPasswordId::build(String::from("Test Password"));
-        let password3 = MetaPasswordId::build(String::from("Different Password"));
 
-        // Same name should create equal objects
-        assert_eq!(password1, password2);
+        // Passwords are equal by id, the name doesn't matter
+        assert_ne!(password1, password2);
+
+        let mut renamed = password1.clone();
+        renamed.name = String::from("Different Password");
+        assert_eq!(password1, renamed);
+        assert_eq!(password1, password1.opaque());
+    }
+
+    #[test]
+    fn test_sealed_meta_password_id() -> anyhow::Result<()> {
+        use crate::crypto::key_pair::KeyPair;
+        use crate::crypto::keys::fixture::KeyManagerFixture;
+
+        let fixture = KeyManagerFixture::generate();
+        let pass_id = MetaPasswordId::build_from_str("prod-db-root");
+
+        let sealed = pass_id.seal(&fixture.client.transport, [&fixture.vd.transport.pk()])?;
+        assert!(!sealed.has_clear_name());
+        assert_eq!(sealed, pass_id);
+        assert!(!serde_json::to_string(&sealed)?.contains("prod-db-root"));
+
+        let opened = sealed.clone().unseal(&fixture.vd.transport.sk());
+        assert_eq!(opened.name, "prod-db-root");
+
+        let not_opened = sealed.unseal(&fixture.client_b.transport.sk());
+        assert!(!not_opened.has_clear_name());
 
-        // Different names should create non-equal objects
-        assert_ne!(password1, password3);
+        assert!(pass_id.opaque().sealed_name.is_none());
+        assert!(!pass_id.opaque().has_clear_name());
+        Ok(())
     }
 }
@@ -1,9 +1,16 @@
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::key_pair::DsaKeyPair;
+use crate::crypto::keys::DsaPk;
 use crate::crypto::utils::Id48bit;
+use crate::node::common::clock::EventTime;
 use crate::node::common::model::crypto::aead::EncryptedMessage;
-use crate::node::common::model::device::common::DeviceId;
+use crate::node::common::model::device::common::{DeviceData, DeviceId, DeviceType};
 use crate::node::common::model::meta_pass::MetaPasswordId;
+use crate::node::common::model::user::common::UserMembership;
 use crate::node::common::model::vault::vault::VaultName;
+use crate::node::common::model::vault::vault_data::VaultData;
 use crate::node::common::model::IdString;
+use anyhow::{bail, Result};
 use derive_more::From;
 use std::collections::HashMap;
 use wasm_bindgen::prelude::wasm_bindgen;
@@ -65,6 +72,122 @@ impl IdString for SsRecoveryId {
     }
 }
 
+/// How long the members can answer a recovery claim, in milliseconds
+pub const RECOVERY_CLAIM_TTL: u64 = 24 * 60 * 60 * 1000;
+
+/// Vault members that are asked to release their shares for a recovery
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RecoveryTargets {
+    /// Every other member of the vault
+    #[default]
+    All,
+    /// Only the given devices
+    Devices(Vec<DeviceId>),
+    /// The given devices first, the rest of the members are asked
+    /// if none of the devices has released its share within `fallback_after` milliseconds
+    Preferred {
+        devices: Vec<DeviceId>,
+        fallback_after: u64,
+    },
+}
+
+/// Approvals a recovery claim of a secret needs before the receivers release their shares.
+/// The policy is set when the secret is added to the vault and can't be changed afterwards
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryPolicy {
+    /// Number of distinct devices that have to approve a claim
+    pub approvals: usize,
+    /// Only the approvals of these device types count, any type if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub device_types: Vec<DeviceType>,
+    /// Only the approvals of these devices count (the role of the approvers), any member if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvers: Vec<DeviceId>,
+}
+
+impl RecoveryPolicy {
+    pub fn is_approver(&self, device: &DeviceData) -> bool {
+        let is_allowed_type =
+            self.device_types.is_empty() || self.device_types.contains(&device.device_type);
+        let is_allowed_device =
+            self.approvers.is_empty() || self.approvers.contains(&device.device_id);
+        is_allowed_type && is_allowed_device
+    }
+
+    /// Approvals of the claim made by the vault members the policy accepts.
+    /// An approval counts only if it is signed by the key the vault holds for the approver
+    pub fn approvals_of(&self, claim: &SsClaim, vault: &VaultData) -> usize {
+        claim
+            .approvers()
+            .iter()
+            .filter_map(|device_id| match vault.find_user(device_id) {
+                Some(UserMembership::Member(member)) => Some(member.user().device.clone()),
+                _ => None,
+            })
+            .filter(|device| self.is_approver(device))
+            .filter(|device| claim.is_signed_approver(device))
+            .count()
+    }
+
+    pub fn is_satisfied(&self, claim: &SsClaim, vault: &VaultData) -> bool {
+        self.approvals_of(claim, vault) >= self.approvals
+    }
+}
+
+/// How long the members can veto a break-glass recovery by default, in milliseconds
+pub const DEFAULT_BREAK_GLASS_DELAY: u64 = 72 * 60 * 60 * 1000;
+
+/// Opt-in break-glass recovery of a secret: the escrow device releases its share of a recovery
+/// claim by itself if no member has vetoed (or declined) the claim during the delay.
+/// The policy is set when the secret is added to the vault and can't be changed afterwards
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakGlassPolicy {
+    pub escrow: DeviceId,
+    /// Waiting period in milliseconds
+    pub delay: u64,
+}
+
+/// Break-glass state of a recovery claim
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakGlass {
+    pub escrow: DeviceId,
+    /// Waiting period in milliseconds
+    pub delay: u64,
+    /// The member that has stopped the escrow from releasing its share
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vetoed_by: Option<DeviceId>,
+}
+
+impl From<BreakGlassPolicy> for BreakGlass {
+    fn from(policy: BreakGlassPolicy) -> Self {
+        Self {
+            escrow: policy.escrow,
+            delay: policy.delay,
+            vetoed_by: None,
+        }
+    }
+}
+
+impl BreakGlass {
+    /// The escrow and the delay are the ones of the policy of the secret
+    pub fn follows(&self, policy: &BreakGlassPolicy) -> bool {
+        self.escrow == policy.escrow && self.delay == policy.delay
+    }
+}
+
+/// Members of the vault that join a recovery claim if its receivers don't release a share in time
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryFallback {
+    pub receivers: Vec<DeviceId>,
+    /// Milliseconds since the claim has been made
+    pub after: u64,
+}
+
 /// SsDistributionClaim represents a specific distribution of a secret across multiple devices.
 ///
 /// This struct allows to easily represent a claim, and enables distribution logic to operate on it.
@@ -83,6 +206,26 @@ pub struct SsClaim {
     // All receivers of secret shares excluding the sender (the sender already has a share).
     pub receivers: Vec<DeviceId>,
     pub status: SsDistributionCompositeStatus,
+    /// Unix time in milliseconds when the server garbage collector first saw the claim,
+    /// only the claims without `time` (made by older clients) are aged by it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
+    /// When the sender made the claim (and when the server received it)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<EventTime>,
+    /// How long the receivers can answer the claim, in milliseconds.
+    /// Claims without a ttl never expire
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
+    /// Devices asked next if the receivers of a recovery claim don't answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<RecoveryFallback>,
+    /// The escrow device that releases its share if nobody vetoes the recovery in time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub break_glass: Option<BreakGlass>,
+    /// Approvals signed by the receivers, the status of a receiver alone is not a proof
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvals: Vec<SsApprovalData>,
 }
 
 impl SsClaim {
@@ -113,6 +256,173 @@ impl SsClaim {
 
         ids
     }
+
+    /// Unix time in milliseconds after which the claim can't be approved.
+    /// Counted from the time the server received the claim, if it's known
+    pub fn expires_at(&self) -> Option<u64> {
+        let ttl = self.ttl?;
+        Some(self.started_at()?.saturating_add(ttl))
+    }
+
+    /// Unix time in milliseconds when the fallback receivers are asked
+    pub fn fallback_at(&self) -> Option<u64> {
+        let fallback = self.fallback.as_ref()?;
+        Some(self.started_at()?.saturating_add(fallback.after))
+    }
+
+    /// Unix time in milliseconds when the escrow device releases its share unless the claim is vetoed
+    pub fn break_glass_at(&self) -> Option<u64> {
+        let break_glass = self.break_glass.as_ref()?;
+        Some(self.started_at()?.saturating_add(break_glass.delay))
+    }
+
+    /// Any member can stop a break-glass recovery: by a veto, or by declining the claim
+    pub fn is_vetoed(&self) -> bool {
+        let is_vetoed = self
+            .break_glass
+            .as_ref()
+            .is_some_and(|break_glass| break_glass.vetoed_by.is_some());
+        let is_declined = self
+            .status
+            .statuses
+            .values()
+            .any(|status| matches!(status, SsDistributionStatus::Declined));
+        is_vetoed || is_declined
+    }
+
+    /// The waiting period is over and nobody has vetoed the claim, the escrow releases its share
+    pub fn is_break_glass_due(&self, now: u64) -> bool {
+        let is_due = self
+            .break_glass_at()
+            .is_some_and(|break_glass_at| now >= break_glass_at);
+        is_due && !self.is_vetoed() && !self.is_expired(now)
+    }
+
+    /// Returns true if the veto has stopped the break-glass recovery
+    pub fn veto(&mut self, device_id: DeviceId) -> bool {
+        match self.break_glass.as_mut() {
+            Some(break_glass) if break_glass.vetoed_by.is_none() => {
+                break_glass.vetoed_by = Some(device_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// When the server received the claim, or when the sender made it
+    pub fn started_at(&self) -> Option<u64> {
+        let time = self.time.as_ref()?;
+        Some(time.received_at.unwrap_or(time.created_at))
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        let is_timed_out = self
+            .expires_at()
+            .is_some_and(|expires_at| now >= expires_at);
+        is_timed_out || self.status.status() == SsDistributionStatus::Expired
+    }
+
+    /// Receivers that haven't answered a timed out claim get the `Expired` status.
+    /// Returns true if any status has changed
+    pub fn expire(&mut self, now: u64) -> bool {
+        let is_timed_out = self
+            .expires_at()
+            .is_some_and(|expires_at| now >= expires_at);
+        if !is_timed_out {
+            return false;
+        }
+
+        let status = self.status.clone().expire_remaining_pending();
+        let changed = status != self.status;
+        self.status = status;
+        changed
+    }
+
+    /// The receiver approves the claim, its signed approval is kept with the claim
+    pub fn approve(&mut self, approval: SsApprovalData) {
+        if approval.claim_id != self.id {
+            return;
+        }
+
+        self.status = self.status.clone().approve(approval.receiver_id.clone());
+        let is_new = !self
+            .approvals
+            .iter()
+            .any(|known| known.receiver_id == approval.receiver_id);
+        if is_new {
+            self.approvals.push(approval);
+        }
+    }
+
+    /// The device has approved the claim and the approval is signed by the device
+    fn is_signed_approver(&self, device: &DeviceData) -> bool {
+        self.approvals.iter().any(|approval| {
+            approval.receiver_id == device.device_id
+                && approval.verify(self, &device.keys.dsa_pk).is_ok()
+        })
+    }
+
+    /// Receivers that have approved the claim, the ones that have released a share included
+    pub fn approvers(&self) -> Vec<DeviceId> {
+        let mut approvers: Vec<DeviceId> = self
+            .status
+            .statuses
+            .iter()
+            .filter(|(_, status)| {
+                matches!(
+                    status,
+                    SsDistributionStatus::Approved
+                        | SsDistributionStatus::Sent
+                        | SsDistributionStatus::Delivered
+                )
+            })
+            .map(|(device_id, _)| device_id.clone())
+            .collect();
+        approvers.sort_by_key(|device_id| device_id.to_string());
+        approvers
+    }
+
+    /// Asks the fallback receivers once the delay has passed (or every receiver has declined)
+    /// and none of the receivers has released its share.
+    /// Returns true if the receivers have changed
+    pub fn fall_back(&mut self, now: u64) -> bool {
+        let Some(fallback_at) = self.fallback_at() else {
+            return false;
+        };
+
+        let is_released = self.status.statuses.values().any(|status| {
+            matches!(
+                status,
+                SsDistributionStatus::Sent | SsDistributionStatus::Delivered
+            )
+        });
+        if is_released || self.is_expired(now) {
+            return false;
+        }
+
+        let is_declined = self
+            .status
+            .statuses
+            .values()
+            .all(|status| matches!(status, SsDistributionStatus::Declined));
+        if now < fallback_at && !is_declined {
+            return false;
+        }
+
+        let Some(fallback) = self.fallback.take() else {
+            return false;
+        };
+        for receiver in fallback.receivers {
+            if receiver.eq(&self.sender) || self.receivers.contains(&receiver) {
+                continue;
+            }
+            self.status
+                .statuses
+                .insert(receiver.clone(), SsDistributionStatus::Pending);
+            self.receivers.push(receiver);
+        }
+        true
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -126,6 +436,11 @@ pub enum SsDistributionStatus {
     Delivered,
     /// The receiver device has declined the recovery request
     Declined,
+    /// The receiver device hasn't answered the claim before its ttl
+    Expired,
+    /// The receiver device has approved the recovery claim,
+    /// it releases the share when the claim has the approvals the recovery policy requires
+    Approved,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -153,15 +468,26 @@ impl SsDistributionCompositeStatus {
         self
     }
 
+    /// Pending receivers approve the claim, the ones that have answered already keep their status
+    pub fn approve(mut self, device_id: DeviceId) -> Self {
+        if let Some(status @ SsDistributionStatus::Pending) = self.statuses.get_mut(&device_id) {
+            *status = SsDistributionStatus::Approved;
+        }
+        self
+    }
+
     pub fn get(&self, device_id: &DeviceId) -> Option<&SsDistributionStatus> {
         self.statuses.get(device_id)
     }
 
     pub fn status(&self) -> SsDistributionStatus {
-        let is_pending = self
-            .statuses
-            .values()
-            .any(|dist_status| matches!(dist_status, SsDistributionStatus::Pending));
+        // an approved share is still waiting for the other approvals
+        let is_pending = self.statuses.values().any(|dist_status| {
+            matches!(
+                dist_status,
+                SsDistributionStatus::Pending | SsDistributionStatus::Approved
+            )
+        });
 
         let is_sent = self
             .statuses
@@ -173,11 +499,18 @@ impl SsDistributionCompositeStatus {
             .values()
             .any(|dist_status| matches!(dist_status, SsDistributionStatus::Declined));
 
+        let is_expired = self
+            .statuses
+            .values()
+            .any(|dist_status| matches!(dist_status, SsDistributionStatus::Expired));
+
         // TODO: k-of-N — replace with count(Sent) + count(Delivered) >= threshold
         if is_sent {
             SsDistributionStatus::Sent
         } else if is_pending {
             SsDistributionStatus::Pending
+        } else if is_expired {
+            SsDistributionStatus::Expired
         } else if is_declined {
             SsDistributionStatus::Declined
         } else {
@@ -188,12 +521,27 @@ impl SsDistributionCompositeStatus {
     pub fn decline_remaining_pending(mut self) -> Self {
         // TODO: k-of-N — call only when count(Sent) >= threshold instead of any(Sent)
         for status in self.statuses.values_mut() {
-            if matches!(status, SsDistributionStatus::Pending) {
+            if matches!(
+                status,
+                SsDistributionStatus::Pending | SsDistributionStatus::Approved
+            ) {
                 *status = SsDistributionStatus::Declined;
             }
         }
         self
     }
+
+    pub fn expire_remaining_pending(mut self) -> Self {
+        for status in self.statuses.values_mut() {
+            if matches!(
+                status,
+                SsDistributionStatus::Pending | SsDistributionStatus::Approved
+            ) {
+                *status = SsDistributionStatus::Expired;
+            }
+        }
+        self
+    }
 }
 
 impl From<Vec<DeviceId>> for SsDistributionCompositeStatus {
@@ -231,6 +579,62 @@ pub struct SsDeclineData {
     pub receiver_id: DeviceId,
 }
 
+/// The receiver approves a recovery claim of a secret that requires several approvals
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsApprovalData {
+    pub vault_name: VaultName,
+    pub claim_id: ClaimId,
+    pub receiver_id: DeviceId,
+    /// Signature of the receiver over the claim, the password and the receiver id
+    pub signature: Base64Text,
+}
+
+impl SsApprovalData {
+    pub fn sign(claim: &SsClaim, receiver_id: DeviceId, dsa: &DsaKeyPair) -> Result<Self> {
+        let signature = dsa.sign(Self::signed_text(&claim.dist_claim_id, &receiver_id)?);
+        Ok(Self {
+            vault_name: claim.vault_name.clone(),
+            claim_id: claim.id.clone(),
+            receiver_id,
+            signature,
+        })
+    }
+
+    /// Checks that the approval of the claim is signed by the given key of the receiver
+    pub fn verify(&self, claim: &SsClaim, receiver_dsa_pk: &DsaPk) -> Result<()> {
+        if self.claim_id != claim.id || self.claim_id != claim.dist_claim_id.id {
+            bail!("The approval belongs to another claim");
+        }
+
+        let signed_text = Self::signed_text(&claim.dist_claim_id, &self.receiver_id)?;
+        receiver_dsa_pk.verify(&signed_text, &self.signature)?;
+        Ok(())
+    }
+
+    fn signed_text(claim_id: &SsClaimId, receiver_id: &DeviceId) -> Result<String> {
+        Ok(serde_json::to_string(&(claim_id, receiver_id))?)
+    }
+}
+
+/// A member stops the escrow device from releasing its share of a break-glass recovery
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsVetoData {
+    pub vault_name: VaultName,
+    pub claim_id: SsClaimId,
+    pub device_id: DeviceId,
+}
+
+/// The sender withdraws its recovery claim
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SsCancelClaimData {
+    pub vault_name: VaultName,
+    pub claim_id: SsClaimId,
+    pub sender: DeviceId,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SsLogData {
@@ -238,6 +642,14 @@ pub struct SsLogData {
 }
 
 impl SsLogData {
+    /// Claims carry opaque password ids, take the names from the (unsealed) vault for display
+    pub fn with_pass_names(mut self, vault: &VaultData) -> Self {
+        for claim in self.claims.values_mut() {
+            claim.dist_claim_id.pass_id = vault.find_secret(&claim.dist_claim_id.pass_id);
+        }
+        self
+    }
+
     pub fn find_recovery_claim_id(&self, pass_id: &MetaPasswordId) -> Option<ClaimId> {
         let mut claim_id = None;
         for (_, claim) in self.claims.iter() {
@@ -252,7 +664,7 @@ impl SsLogData {
             }
 
             match claim.status.status() {
-                SsDistributionStatus::Pending => {
+                SsDistributionStatus::Pending | SsDistributionStatus::Approved => {
                     println!("🦀 Founded claim status: Pending");
                     continue;
                 }
@@ -270,6 +682,10 @@ impl SsLogData {
                     println!("🦀 Founded claim status: Declined");
                     continue;
                 }
+                SsDistributionStatus::Expired => {
+                    println!("🦀 Founded claim status: Expired");
+                    continue;
+                }
             }
         }
 
@@ -290,7 +706,7 @@ impl SsLogData {
             }
 
             match claim.status.status() {
-                SsDistributionStatus::Pending => {
+                SsDistributionStatus::Pending | SsDistributionStatus::Approved => {
                     println!("🦀 Founded claim status: Pending");
                     result_claim = Some(claim.clone());
                     break;
@@ -310,6 +726,11 @@ impl SsLogData {
                     result_claim = Some(claim.clone());
                     break;
                 }
+                SsDistributionStatus::Expired => {
+                    println!("🦀 Founded claim status: Expired");
+                    result_claim = Some(claim.clone());
+                    break;
+                }
             }
         }
 
@@ -392,6 +813,26 @@ impl SsLogData {
 
         self
     }
+
+    pub fn approve(mut self, approval: SsApprovalData) -> Self {
+        if let Some(claim) = self.claims.get_mut(&approval.claim_id) {
+            claim.approve(approval);
+        }
+        self
+    }
+
+    pub fn veto(mut self, claim_id: &ClaimId, device_id: DeviceId) -> Self {
+        if let Some(claim) = self.claims.get_mut(claim_id) {
+            claim.veto(device_id);
+        }
+        self
+    }
+
+    /// The claim has been withdrawn by its sender, nobody needs it anymore
+    pub fn cancel(mut self, claim_id: &ClaimId) -> Self {
+        self.claims.remove(claim_id);
+        self
+    }
 }
 
 impl SsLogData {
@@ -427,10 +868,12 @@ impl From<SsClaim> for WasmSsDistributionClaim {
 mod test {
     use crate::crypto::utils::{Id48bit, U64IdUrlEnc};
     use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
-    use crate::node::common::model::device::common::DeviceId;
+    use crate::node::common::clock::{EventTime, HlcTimestamp};
+    use crate::node::common::model::device::common::{DeviceId, DeviceType};
     use crate::node::common::model::meta_pass::MetaPasswordId;
     use crate::node::common::model::secret::{
-        ClaimId, SecretDistributionType, SsClaim, SsClaimId, SsDistributionCompositeStatus,
+        BreakGlass, BreakGlassPolicy, ClaimId, RECOVERY_CLAIM_TTL, RecoveryPolicy, RecoveryTargets,
+        SecretDistributionType, SsApprovalData, SsClaim, SsClaimId, SsDistributionCompositeStatus,
         SsDistributionStatus, SsLogData,
     };
     use crate::node::common::model::vault::vault::VaultName;
@@ -454,6 +897,7 @@ mod test {
                 pass_id: MetaPasswordId {
                     id: U64IdUrlEnc::from("pass_id".to_string()),
                     name: "test_pass".to_string(),
+                    sealed_name: None,
                 },
             },
             vault_name: VaultName::test(),
@@ -461,6 +905,12 @@ mod test {
             distribution_type: SecretDistributionType::Split,
             receivers: receivers.clone(),
             status: SsDistributionCompositeStatus::from(receivers),
+            created_at: None,
+            time: None,
+            ttl: None,
+            fallback: None,
+            break_glass: None,
+            approvals: vec![],
         };
 
         let dist_ids = claim.distribution_ids();
@@ -484,6 +934,7 @@ mod test {
         let pass_id = MetaPasswordId {
             id: U64IdUrlEnc::from("test_pass_id".to_string()),
             name: "test_pass".to_string(),
+            sealed_name: None,
         };
         let ss_claim_id = SsClaimId {
             id: claim_id.clone(),
@@ -499,6 +950,12 @@ mod test {
             distribution_type: SecretDistributionType::Split,
             receivers: receivers.clone(),
             status: SsDistributionCompositeStatus::from(receivers.clone()),
+            created_at: None,
+            time: None,
+            ttl: None,
+            fallback: None,
+            break_glass: None,
+            approvals: vec![],
         };
 
         // Generate recovery IDs
@@ -689,6 +1146,7 @@ mod test {
                 pass_id: MetaPasswordId {
                     id: U64IdUrlEnc::from("pass_id".to_string()),
                     name: "test_pass".to_string(),
+                    sealed_name: None,
                 },
             },
             vault_name: VaultName::test(),
@@ -696,6 +1154,12 @@ mod test {
             distribution_type: SecretDistributionType::Split,
             receivers: receivers.clone(),
             status: SsDistributionCompositeStatus::from(receivers.clone()),
+            created_at: None,
+            time: None,
+            ttl: None,
+            fallback: None,
+            break_glass: None,
+            approvals: vec![],
         };
 
         // Create log data with the claim
@@ -789,4 +1253,316 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_recovery_claim_expiry() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+
+        let client_device_id = registry.state.device_creds.client.device.device_id;
+        let client_b_device_id = registry.state.device_creds.client_b.device.device_id;
+        let vd_device_id = registry.state.device_creds.vd.device.device_id;
+
+        let claim_id = ClaimId::from(Id48bit::generate());
+        let receivers = vec![client_b_device_id.clone(), vd_device_id.clone()];
+        let mut claim = SsClaim {
+            id: claim_id.clone(),
+            dist_claim_id: SsClaimId {
+                id: claim_id,
+                pass_id: MetaPasswordId::build_from_str("expiring_pass"),
+            },
+            vault_name: VaultName::test(),
+            sender: client_device_id,
+            distribution_type: SecretDistributionType::Recover,
+            receivers: receivers.clone(),
+            status: SsDistributionCompositeStatus::from(receivers).decline(vd_device_id.clone()),
+            created_at: None,
+            time: Some(EventTime {
+                created_at: 1_000,
+                hlc: HlcTimestamp::default(),
+                received_at: Some(2_000),
+            }),
+            ttl: Some(500),
+            fallback: None,
+            break_glass: None,
+            approvals: vec![],
+        };
+
+        // the ttl is counted from the time the server has received the claim
+        assert_eq!(claim.expires_at(), Some(2_500));
+        assert!(!claim.is_expired(2_499));
+        assert!(claim.is_expired(2_500));
+
+        assert!(!claim.expire(2_499));
+        assert!(claim.expire(2_500));
+        assert!(!claim.expire(3_000), "Claim is expired only once");
+
+        assert_eq!(
+            claim.status.get(&client_b_device_id),
+            Some(&SsDistributionStatus::Expired)
+        );
+        assert_eq!(
+            claim.status.get(&vd_device_id),
+            Some(&SsDistributionStatus::Declined)
+        );
+        assert_eq!(claim.status.status(), SsDistributionStatus::Expired);
+        // the local clock may be behind the server one, the status still counts
+        assert!(claim.is_expired(0));
+
+        let mut endless_claim = claim.clone();
+        endless_claim.ttl = None;
+        endless_claim.status = SsDistributionCompositeStatus::from(endless_claim.receivers.clone());
+        assert_eq!(endless_claim.expires_at(), None);
+        assert!(!endless_claim.expire(u64::MAX));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_removes_claim() {
+        let registry = FixtureRegistry::empty();
+        let claim = registry
+            .state
+            .vault_data
+            .client_vault_member
+            .create_recovery_claim(MetaPasswordId::build_from_str("cancelled_pass"));
+        assert_eq!(claim.ttl, Some(RECOVERY_CLAIM_TTL));
+
+        let log_data = SsLogData::new(claim.clone()).cancel(&claim.id);
+        assert!(log_data.claims.is_empty());
+    }
+
+    #[test]
+    fn test_recovery_claim_falls_back_to_other_members() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let client_b_device_id = registry.state.device_creds.client_b.device.device_id;
+        let vd_device_id = registry.state.device_creds.vd.device.device_id;
+
+        let mut claim = registry
+            .state
+            .vault_data
+            .client_vault_member
+            .create_targeted_recovery_claim(
+                MetaPasswordId::build_from_str("preferred_pass"),
+                RecoveryTargets::Preferred {
+                    devices: vec![client_b_device_id.clone()],
+                    fallback_after: 500,
+                },
+            )?;
+        claim.time = Some(EventTime {
+            created_at: 1_000,
+            hlc: HlcTimestamp::default(),
+            received_at: None,
+        });
+        let preferred_claim = claim.clone();
+
+        assert_eq!(claim.fallback_at(), Some(1_500));
+        assert!(!claim.fall_back(1_499));
+        assert_eq!(claim.receivers, vec![client_b_device_id.clone()]);
+
+        assert!(claim.fall_back(1_500));
+        assert_eq!(
+            claim.receivers,
+            vec![client_b_device_id.clone(), vd_device_id.clone()]
+        );
+        assert_eq!(
+            claim.status.get(&vd_device_id),
+            Some(&SsDistributionStatus::Pending)
+        );
+        assert!(claim.fallback.is_none());
+        assert!(!claim.fall_back(2_000), "Claim falls back only once");
+
+        // the preferred device has released its share, nobody else is asked
+        let mut released_claim = preferred_claim.clone();
+        released_claim.status = released_claim.status.sent(client_b_device_id.clone());
+        assert!(!released_claim.fall_back(1_500));
+        assert_eq!(released_claim.receivers, vec![client_b_device_id.clone()]);
+
+        // no need to wait if the preferred device has declined
+        let mut declined_claim = preferred_claim;
+        declined_claim.status = declined_claim.status.decline(client_b_device_id);
+        assert!(declined_claim.fall_back(1_000));
+        assert!(declined_claim.receivers.contains(&vd_device_id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_break_glass_is_due_unless_vetoed() {
+        let registry = FixtureRegistry::empty();
+        let client_b_device_id = registry.state.device_creds.client_b.device.device_id;
+        let vd_device_id = registry.state.device_creds.vd.device.device_id;
+
+        let mut claim = registry
+            .state
+            .vault_data
+            .client_vault_member
+            .create_recovery_claim(MetaPasswordId::build_from_str("escrowed_pass"));
+        claim.time = Some(EventTime {
+            created_at: 1_000,
+            hlc: HlcTimestamp::default(),
+            received_at: Some(2_000),
+        });
+        claim.ttl = Some(10_000);
+        assert!(!claim.is_break_glass_due(u64::MAX));
+
+        claim.break_glass = Some(BreakGlass::from(BreakGlassPolicy {
+            escrow: vd_device_id.clone(),
+            delay: 5_000,
+        }));
+        assert_eq!(claim.break_glass_at(), Some(7_000));
+        assert!(!claim.is_break_glass_due(6_999));
+        assert!(claim.is_break_glass_due(7_000));
+        assert!(
+            !claim.is_break_glass_due(12_000),
+            "Expired claim can't be recovered"
+        );
+
+        // a member declining the claim stops the escrow as well
+        let mut declined_claim = claim.clone();
+        declined_claim.status = declined_claim.status.decline(client_b_device_id.clone());
+        assert!(declined_claim.is_vetoed());
+        assert!(!declined_claim.is_break_glass_due(7_000));
+
+        let ss_log = SsLogData::new(claim.clone()).veto(&claim.id, client_b_device_id.clone());
+        let vetoed_claim = &ss_log.claims[&claim.id];
+        assert!(vetoed_claim.is_vetoed());
+        assert!(!vetoed_claim.is_break_glass_due(7_000));
+
+        // the first veto is kept
+        let mut vetoed_claim = vetoed_claim.clone();
+        assert!(!vetoed_claim.veto(vd_device_id));
+        assert_eq!(
+            vetoed_claim
+                .break_glass
+                .and_then(|break_glass| break_glass.vetoed_by),
+            Some(client_b_device_id)
+        );
+    }
+
+    #[test]
+    fn test_recovery_policy_requires_distinct_approvals() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let vault_member = registry.state.vault_data.client_vault_member;
+        let vault = vault_member.vault.clone();
+        let client_b_dsa = registry.state.device_creds.client_b.key_manager()?.dsa;
+        let vd_dsa = registry.state.device_creds.vd.key_manager()?.dsa;
+        let client_b_device = registry.state.device_creds.client_b.device;
+        let vd_device = registry.state.device_creds.vd.device;
+
+        let claim = vault_member.create_recovery_claim(MetaPasswordId::build_from_str("prod_db"));
+        let mut ss_log = SsLogData::new(claim.clone());
+        let policy = RecoveryPolicy {
+            approvals: 2,
+            device_types: vec![],
+            approvers: vec![],
+        };
+        assert_eq!(policy.approvals_of(&claim, &vault), 0);
+
+        // the same device approving twice is one approval
+        for _ in 0..2 {
+            let approval =
+                SsApprovalData::sign(&claim, client_b_device.device_id.clone(), &client_b_dsa)?;
+            ss_log = ss_log.approve(approval);
+        }
+        let approved_claim = ss_log.claims[&claim.id].clone();
+        assert_eq!(policy.approvals_of(&approved_claim, &vault), 1);
+        assert!(!policy.is_satisfied(&approved_claim, &vault));
+        assert_eq!(
+            approved_claim.status.status(),
+            SsDistributionStatus::Pending
+        );
+
+        ss_log = ss_log.approve(SsApprovalData::sign(
+            &claim,
+            vd_device.device_id.clone(),
+            &vd_dsa,
+        )?);
+        let approved_claim = ss_log.claims[&claim.id].clone();
+        assert!(policy.is_satisfied(&approved_claim, &vault));
+
+        // a released share still counts as an approval
+        let mut released_claim = approved_claim.clone();
+        released_claim.status = released_claim.status.sent(vd_device.device_id.clone());
+        assert_eq!(policy.approvals_of(&released_claim, &vault), 2);
+
+        // only the approvals of the chosen devices or device types count
+        let vd_only = RecoveryPolicy {
+            approvals: 1,
+            device_types: vec![],
+            approvers: vec![vd_device.device_id.clone()],
+        };
+        assert_eq!(vd_only.approvals_of(&approved_claim, &vault), 1);
+
+        let hardware_only = RecoveryPolicy {
+            approvals: 1,
+            device_types: vec![DeviceType::from("HardwareKey")],
+            approvers: vec![],
+        };
+        assert_eq!(hardware_only.approvals_of(&approved_claim, &vault), 0);
+
+        let vd_type = RecoveryPolicy {
+            approvals: 1,
+            device_types: vec![vd_device.device_type.clone()],
+            approvers: vec![],
+        };
+        assert!(vd_type.is_satisfied(&approved_claim, &vault));
+
+        // a declined receiver can't approve anymore
+        let mut declined_claim = claim;
+        declined_claim.status = declined_claim
+            .status
+            .decline(client_b_device.device_id.clone())
+            .approve(client_b_device.device_id.clone());
+        assert_eq!(
+            declined_claim.status.get(&client_b_device.device_id),
+            Some(&SsDistributionStatus::Declined)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_forged_approvals_are_not_counted() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let vault_member = registry.state.vault_data.client_vault_member;
+        let vault = vault_member.vault.clone();
+        let client_dsa = registry.state.device_creds.client.key_manager()?.dsa;
+        let vd_dsa = registry.state.device_creds.vd.key_manager()?.dsa;
+        let vd_device_id = registry.state.device_creds.vd.device.device_id;
+
+        let claim = vault_member.create_recovery_claim(MetaPasswordId::build_from_str("prod_db"));
+        let vd_only = RecoveryPolicy {
+            approvals: 1,
+            device_types: vec![],
+            approvers: vec![vd_device_id.clone()],
+        };
+
+        // the sender approves its claim on behalf of the virtual device
+        let forged = SsApprovalData::sign(&claim, vd_device_id.clone(), &client_dsa)?;
+        let forged_claim = SsLogData::new(claim.clone()).approve(forged).claims[&claim.id].clone();
+        assert_eq!(
+            forged_claim.status.get(&vd_device_id),
+            Some(&SsDistributionStatus::Approved)
+        );
+        assert_eq!(vd_only.approvals_of(&forged_claim, &vault), 0);
+
+        // a status without an approval doesn't count either
+        let mut unsigned_claim = claim.clone();
+        unsigned_claim.status = unsigned_claim.status.approve(vd_device_id.clone());
+        assert_eq!(vd_only.approvals_of(&unsigned_claim, &vault), 0);
+
+        // the approval of another claim can't be replayed
+        let other_claim =
+            vault_member.create_recovery_claim(MetaPasswordId::build_from_str("prod_db"));
+        let mut replayed = SsApprovalData::sign(&other_claim, vd_device_id.clone(), &vd_dsa)?;
+        replayed.claim_id = claim.id.clone();
+        unsigned_claim.approvals.push(replayed);
+        assert_eq!(vd_only.approvals_of(&unsigned_claim, &vault), 0);
+
+        let approval = SsApprovalData::sign(&claim, vd_device_id.clone(), &vd_dsa)?;
+        let approved_claim = SsLogData::new(claim.clone()).approve(approval).claims[&claim.id].clone();
+        assert_eq!(vd_only.approvals_of(&approved_claim, &vault), 1);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,23 @@
+//! Hash sets don't keep the order of their items, a set of an event is serialized sorted
+//! (by the json of the items), so that the same event always has the same json and hash.
+//! Use it with `#[serde(serialize_with = "sorted_set::serialize")]`
+
+use serde::ser::Error;
+use serde::{Serialize, Serializer};
+use std::collections::HashSet;
+
+pub fn serialize<T, S>(set: &HashSet<T>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Serialize,
+    S: Serializer,
+{
+    // the keys of json objects are sorted, unlike the keys of hash maps
+    let mut items = set
+        .iter()
+        .map(|item| serde_json::to_value(item).map(|json| (json.to_string(), item)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(S::Error::custom)?;
+    items.sort_by(|(json, _), (other_json, _)| json.cmp(other_json));
+
+    serializer.collect_seq(items.into_iter().map(|(_, item)| item))
+}
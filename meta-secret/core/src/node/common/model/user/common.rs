@@ -190,6 +190,7 @@ mod tests {
             device_name,
             device_type: DeviceType::other(),
             keys: open_box,
+            sealed_name: None,
         }
     }
 
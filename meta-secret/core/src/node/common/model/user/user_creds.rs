@@ -1,7 +1,11 @@
-use crate::crypto::keys::TransportPk;
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::keys::{TransportPk, TransportSk};
+use crate::node::common::model::crypto::aead::{AeadCipherText, AeadPlainText};
+use crate::node::common::model::crypto::channel::CommunicationChannel;
 use crate::node::common::model::device::common::{DeviceData, DeviceId};
 use crate::node::common::model::device::device_creds::{DeviceCreds, SecureDeviceCreds};
 use crate::node::common::model::user::common::{UserData, UserId};
+use crate::node::common::model::vault::label::VaultLabel;
 use crate::node::common::model::vault::vault::VaultName;
 use anyhow::Result;
 
@@ -10,17 +14,48 @@ use anyhow::Result;
 pub struct SecureUserCreds {
     pub vault_name: VaultName,
     pub device_creds: SecureDeviceCreds,
+    /// The vault label encrypted with the master key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault_label: Option<AeadCipherText>,
 }
 
 impl SecureUserCreds {
     
     pub fn build(user_creds: UserCreds, master_pk: TransportPk) -> Result<Self> {
+        let vault_label = match &user_creds.vault_label {
+            None => None,
+            Some(label) => {
+                let plain_label = AeadPlainText {
+                    msg: Base64Text::from(serde_json::to_string(label)?),
+                    channel: CommunicationChannel::single_device(master_pk.clone()).to_channel(),
+                };
+                Some(plain_label.encrypt()?)
+            }
+        };
+
         let secure_device_creds = SecureDeviceCreds::build(user_creds.device_creds, master_pk)?;
 
         // Create secure user credentials with the secure device credentials
         Ok(SecureUserCreds {
             vault_name: user_creds.vault_name.clone(),
             device_creds: secure_device_creds,
+            vault_label,
+        })
+    }
+
+    pub fn decrypt(self, master_key: &TransportSk) -> Result<UserCreds> {
+        let vault_label = match self.vault_label {
+            None => None,
+            Some(vault_label) => {
+                let plain_label = vault_label.decrypt(master_key)?;
+                Some(serde_json::from_str(&String::try_from(&plain_label.msg)?)?)
+            }
+        };
+
+        Ok(UserCreds {
+            vault_name: self.vault_name,
+            device_creds: self.device_creds.decrypt(master_key)?,
+            vault_label,
         })
     }
 }
@@ -28,8 +63,13 @@ impl SecureUserCreds {
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserCreds {
+    /// The opaque name of the vault on the server
     pub vault_name: VaultName,
     pub device_creds: DeviceCreds,
+    /// The name the user has given the vault, credentials made before vault labels
+    /// have the name in clear text in `vault_name` and no label
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault_label: Option<VaultLabel>,
 }
 
 impl UserCreds {
@@ -37,6 +77,30 @@ impl UserCreds {
         self.device_creds.device.clone()
     }
 
+    /// The name of the vault for display
+    pub fn vault_label_name(&self) -> VaultName {
+        match &self.vault_label {
+            Some(label) => VaultName::from(label.name.clone()),
+            None => self.vault_name.clone(),
+        }
+    }
+
+    /// The device with its name decrypted, for display
+    pub fn open_device(&self) -> DeviceData {
+        match &self.vault_label {
+            Some(label) => label.open_device(self.device()),
+            None => self.device(),
+        }
+    }
+
+    /// Decrypts the name of another device of the vault
+    pub fn open_device_name(&self, device: DeviceData) -> DeviceData {
+        match &self.vault_label {
+            Some(label) => label.open_device(device),
+            None => device,
+        }
+    }
+
     pub fn user(&self) -> UserData {
         UserData {
             vault_name: self.vault_name.clone(),
@@ -69,9 +133,22 @@ impl UserCredsBuilder<DeviceCreds> {
         let user_creds = UserCreds {
             vault_name,
             device_creds: self.creds,
+            vault_label: None,
         };
         UserCredsBuilder { creds: user_creds }
     }
+
+    /// The vault is stored under the opaque name of the label, and the name of the device
+    /// is sealed with the label, so that only the devices of the vault can read them
+    pub fn build_with_label(mut self, label: VaultLabel) -> Result<UserCredsBuilder<UserCreds>> {
+        self.creds.device = label.seal_device(&self.creds.device)?;
+        let user_creds = UserCreds {
+            vault_name: label.vault_name.clone(),
+            device_creds: self.creds,
+            vault_label: Some(label),
+        };
+        Ok(UserCredsBuilder { creds: user_creds })
+    }
 }
 
 #[cfg(any(test, feature = "test-framework"))]
@@ -0,0 +1,160 @@
+use crate::crypto::utils::generate_hash;
+use crate::node::common::model::device::common::DeviceId;
+use crate::node::common::model::user::common::UserDataMember;
+use crate::node::common::model::vault::vault::VaultName;
+use anyhow::{Result, bail};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use sha2::{Digest, Sha256};
+
+/// How long an invite can be used by default, in milliseconds
+pub const DEFAULT_INVITE_TTL: u64 = 7 * 24 * 60 * 60 * 1000;
+
+const INVITE_TEXT_PREFIX: &str = "meta-secret-invite:";
+
+/// An invitation to join a vault. A member makes it and shares it with the candidate
+/// as text or a QR code, the vault only keeps the hash of the one-time token
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultInvite {
+    pub vault_name: VaultName,
+    pub token: String,
+    /// Fingerprint of the device keys of the inviting member
+    pub key_fingerprint: String,
+    /// Unix time in milliseconds
+    pub expires_at: u64,
+    /// The inviting member accepts the candidate without asking
+    pub pre_approved: bool,
+}
+
+impl VaultInvite {
+    pub fn generate(inviter: &UserDataMember, ttl: u64, pre_approved: bool, now: u64) -> Self {
+        Self {
+            vault_name: inviter.user_data.vault_name(),
+            token: generate_hash(),
+            key_fingerprint: inviter.user().device.keys.fingerprint(),
+            expires_at: now.saturating_add(ttl),
+            pre_approved,
+        }
+    }
+
+    /// The part of the invite the vault keeps
+    pub fn record(&self, created_by: DeviceId) -> InviteRecord {
+        InviteRecord {
+            token_hash: self.ticket().token_hash(),
+            key_fingerprint: self.key_fingerprint.clone(),
+            created_by,
+            expires_at: self.expires_at,
+            pre_approved: self.pre_approved,
+            used_by: None,
+        }
+    }
+
+    /// The part of the invite the candidate presents in its join request
+    pub fn ticket(&self) -> InviteTicket {
+        InviteTicket {
+            token: self.token.clone(),
+            key_fingerprint: self.key_fingerprint.clone(),
+        }
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Text form of the invite, it fits into a QR code
+    pub fn to_text(&self) -> Result<String> {
+        let json = serde_json::to_string(self)?;
+        Ok(format!(
+            "{}{}",
+            INVITE_TEXT_PREFIX,
+            URL_SAFE_NO_PAD.encode(json)
+        ))
+    }
+
+    pub fn from_text(text: &str) -> Result<Self> {
+        let Some(encoded) = text.trim().strip_prefix(INVITE_TEXT_PREFIX) else {
+            bail!("Not a vault invite");
+        };
+        let json = URL_SAFE_NO_PAD.decode(encoded)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+/// Invite token presented by the candidate along with the key fingerprint it expects
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteTicket {
+    pub token: String,
+    pub key_fingerprint: String,
+}
+
+impl InviteTicket {
+    pub fn token_hash(&self) -> String {
+        hex::encode(Sha256::digest(self.token.as_bytes()))
+    }
+}
+
+/// An invite of the vault, it can be used by a single candidate
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteRecord {
+    pub token_hash: String,
+    pub key_fingerprint: String,
+    pub created_by: DeviceId,
+    /// Unix time in milliseconds
+    pub expires_at: u64,
+    pub pre_approved: bool,
+    /// The candidate that has presented the invite
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub used_by: Option<DeviceId>,
+}
+
+impl InviteRecord {
+    pub fn is_expired(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+
+    pub fn matches(&self, ticket: &InviteTicket) -> bool {
+        self.token_hash == ticket.token_hash() && self.key_fingerprint == ticket.key_fingerprint
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+    use crate::node::common::model::user::common::UserDataMember;
+    use crate::node::common::model::vault::invite::{DEFAULT_INVITE_TTL, VaultInvite};
+    use anyhow::Result;
+
+    #[test]
+    fn test_invite_text_round_trip() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let inviter = UserDataMember::from(fixture.state.user_creds.client.user());
+
+        let invite = VaultInvite::generate(&inviter, DEFAULT_INVITE_TTL, true, 1_000);
+        let text = invite.to_text()?;
+
+        assert_eq!(VaultInvite::from_text(&text)?, invite);
+        assert!(VaultInvite::from_text("meta-secret-invite:not json").is_err());
+        assert!(VaultInvite::from_text(&invite.token).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_invite_record_keeps_token_hash() {
+        let fixture = FixtureRegistry::empty();
+        let inviter = UserDataMember::from(fixture.state.user_creds.client.user());
+        let device_id = inviter.user().device.device_id.clone();
+
+        let invite = VaultInvite::generate(&inviter, 10, false, 1_000);
+        let record = invite.record(device_id);
+
+        assert_ne!(record.token_hash, invite.token);
+        assert!(record.matches(&invite.ticket()));
+        assert!(!record.is_expired(1_009));
+        assert!(record.is_expired(1_010));
+
+        let other = VaultInvite::generate(&inviter, 10, false, 1_000);
+        assert!(!record.matches(&other.ticket()));
+    }
+}
@@ -0,0 +1,182 @@
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::master_key::KdfParams;
+use crate::node::common::model::device::common::{DeviceData, DeviceName};
+use crate::node::common::model::user::common::{
+    UserData, UserDataMember, UserDataOutsider, UserMembership,
+};
+use crate::node::common::model::vault::vault::VaultName;
+use crate::node::common::model::IdString;
+use anyhow::{anyhow, bail, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use sha2::{Digest, Sha256};
+
+const LABEL_SALT: &[u8] = b"meta-secret/vault-label";
+const VAULT_ID_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 24;
+
+/// The name the users know the vault by. The server never sees it: the vault is stored
+/// under an opaque vault name derived from the label, and the names of the devices
+/// are encrypted with a key derived from it. Every device that joins has typed the label
+/// (or got it with an invite), the server has to run Argon2id for every guess
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultLabel {
+    pub name: String,
+    /// Opaque name of the vault on the server
+    pub vault_name: VaultName,
+    key: Base64Text,
+}
+
+impl VaultLabel {
+    /// Derives the vault name and the key, it takes a while on purpose
+    pub fn derive(name: &str) -> Result<Self> {
+        let kdf = KdfParams::default();
+        let params = Params::new(
+            kdf.memory_kib,
+            kdf.iterations,
+            kdf.parallelism,
+            Some(VAULT_ID_LENGTH + KEY_LENGTH),
+        )
+        .map_err(|e| anyhow!("Invalid Argon2id parameters: {}", e))?;
+
+        let mut output = [0u8; VAULT_ID_LENGTH + KEY_LENGTH];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(name.as_bytes(), LABEL_SALT, &mut output)
+            .map_err(|e| anyhow!("Failed to derive the vault name: {}", e))?;
+
+        let (vault_id, key) = output.split_at(VAULT_ID_LENGTH);
+        Ok(Self {
+            name: name.to_string(),
+            vault_name: VaultName::from(hex::encode(vault_id)),
+            key: Base64Text::from(key),
+        })
+    }
+
+    /// The device with its name encrypted, the same name always gives the same cipher text,
+    /// so that the device stays the same in the events of the vault
+    pub fn seal_device(&self, device: &DeviceData) -> Result<DeviceData> {
+        if device.device_name.is_empty() {
+            return Ok(device.clone());
+        }
+
+        let name = device.device_name.as_str();
+        let device_id = device.device_id.clone().id_str();
+        let key = Vec::<u8>::try_from(&self.key)?;
+
+        let nonce = {
+            let mut hasher = Sha256::new();
+            hasher.update(&key);
+            hasher.update(device_id.as_bytes());
+            hasher.update(name.as_bytes());
+            hasher.finalize()[..NONCE_LENGTH].to_vec()
+        };
+
+        let payload = Payload {
+            msg: name.as_bytes(),
+            aad: device_id.as_bytes(),
+        };
+        let cipher_text = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(XNonce::from_slice(&nonce), payload)
+            .map_err(|_| anyhow!("Failed to encrypt the device name"))?;
+
+        let sealed_name = [nonce, cipher_text].concat();
+        Ok(DeviceData {
+            device_name: DeviceName::default(),
+            sealed_name: Some(Base64Text::from(sealed_name)),
+            ..device.clone()
+        })
+    }
+
+    /// Decrypts the name of the device, the name stays as it is if the device can't be opened
+    pub fn open_device(&self, mut device: DeviceData) -> DeviceData {
+        if let Ok(name) = self.open_name(&device) {
+            device.device_name = name;
+        }
+        device
+    }
+
+    pub fn open_user(&self, mut user: UserData) -> UserData {
+        user.device = self.open_device(user.device);
+        user
+    }
+
+    pub fn open_membership(&self, membership: UserMembership) -> UserMembership {
+        match membership {
+            UserMembership::Member(member) => UserMembership::Member(UserDataMember {
+                user_data: self.open_user(member.user_data),
+            }),
+            UserMembership::Outsider(outsider) => UserMembership::Outsider(UserDataOutsider {
+                user_data: self.open_user(outsider.user_data),
+                ..outsider
+            }),
+        }
+    }
+
+    fn open_name(&self, device: &DeviceData) -> Result<DeviceName> {
+        let Some(sealed_name) = &device.sealed_name else {
+            bail!("The device name is not sealed");
+        };
+
+        let sealed_name = Vec::<u8>::try_from(sealed_name)?;
+        if sealed_name.len() < NONCE_LENGTH {
+            bail!("Invalid sealed device name");
+        }
+        let (nonce, cipher_text) = sealed_name.split_at(NONCE_LENGTH);
+
+        let key = Vec::<u8>::try_from(&self.key)?;
+        let device_id = device.device_id.clone().id_str();
+        let payload = Payload {
+            msg: cipher_text,
+            aad: device_id.as_bytes(),
+        };
+        let name = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| anyhow!("The device name is sealed with another vault label"))?;
+
+        Ok(DeviceName::from(String::from_utf8(name)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+
+    #[test]
+    fn test_label_derives_an_opaque_vault_name() -> Result<()> {
+        let label = VaultLabel::derive("family-vault")?;
+
+        assert_eq!(label, VaultLabel::derive("family-vault")?);
+        assert!(!label.vault_name.0.contains("family-vault"));
+        assert_eq!(label.vault_name.0.len(), 2 * VAULT_ID_LENGTH);
+        assert_ne!(label.vault_name, VaultLabel::derive("family-vault2")?.vault_name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_device_name_opens_only_with_the_label() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let device = registry.state.device_creds.client.device.clone();
+        let label = VaultLabel::derive("family-vault")?;
+
+        let sealed = label.seal_device(&device)?;
+        assert!(sealed.device_name.is_empty());
+        assert_eq!(sealed, device);
+        assert_eq!(label.seal_device(&device)?.sealed_name, sealed.sealed_name);
+
+        let json = serde_json::to_string(&sealed)?;
+        assert!(!json.contains(device.device_name.as_str().as_str()));
+
+        let opened = label.open_device(sealed.clone());
+        assert_eq!(opened.device_name, device.device_name);
+
+        let other_label = VaultLabel::derive("other-vault")?;
+        assert!(other_label.open_device(sealed).device_name.is_empty());
+
+        Ok(())
+    }
+}
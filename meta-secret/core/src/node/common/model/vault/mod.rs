@@ -1,2 +1,4 @@
+pub mod invite;
+pub mod label;
 pub mod vault;
 pub mod vault_data;
@@ -1,14 +1,17 @@
 use crate::crypto::utils::Id48bit;
+use crate::node::common::clock::EventTime;
 use crate::node::common::model::device::common::DeviceId;
 use crate::node::common::model::meta_pass::MetaPasswordId;
 use crate::node::common::model::secret::{
-    ClaimId, SecretDistributionType, SsClaim, SsClaimId, SsDistributionCompositeStatus,
-    WasmSsLogData,
+    BreakGlass, ClaimId, RecoveryFallback, RecoveryTargets, SecretDistributionType, SsClaim,
+    SsClaimId, SsDistributionCompositeStatus, SsDistributionStatus, WasmSsLogData,
+    RECOVERY_CLAIM_TTL,
 };
 use crate::node::common::model::user::common::{
     UserData, UserDataMember, UserDataOutsider, UserMembership,
 };
 use crate::node::common::model::vault::vault_data::{VaultData, WasmVaultData};
+use anyhow::{bail, Result};
 use derive_more::From;
 use std::fmt::Display;
 use wasm_bindgen::prelude::wasm_bindgen;
@@ -108,47 +111,148 @@ pub struct VaultMember {
 
 impl VaultMember {
     pub fn create_split_claim(&self, pass_id: MetaPasswordId) -> SsClaim {
-        self.create_distribution_claim(pass_id, SecretDistributionType::Split)
+        let receivers = self.other_devices();
+        self.create_distribution_claim(pass_id, SecretDistributionType::Split, receivers)
     }
 
     pub fn create_recovery_claim(&self, pass_id: MetaPasswordId) -> SsClaim {
-        self.create_distribution_claim(pass_id, SecretDistributionType::Recover)
+        let receivers = self.other_devices();
+        self.create_distribution_claim(pass_id, SecretDistributionType::Recover, receivers)
+    }
+
+    /// Recovery claim that asks only the chosen members of the vault for their shares
+    pub fn create_targeted_recovery_claim(
+        &self,
+        pass_id: MetaPasswordId,
+        targets: RecoveryTargets,
+    ) -> Result<SsClaim> {
+        let other_devices = self.other_devices();
+
+        let (devices, fallback_after) = match targets {
+            RecoveryTargets::All => {
+                let claim = self.create_recovery_claim(pass_id);
+                return Ok(self.with_break_glass(claim));
+            }
+            RecoveryTargets::Devices(devices) => (devices, None),
+            RecoveryTargets::Preferred {
+                devices,
+                fallback_after,
+            } => (devices, Some(fallback_after)),
+        };
+
+        let mut receivers: Vec<DeviceId> = vec![];
+        for device_id in devices {
+            if !other_devices.contains(&device_id) {
+                bail!("Device {} is not another member of the vault", device_id);
+            }
+            if !receivers.contains(&device_id) {
+                receivers.push(device_id);
+            }
+        }
+
+        if receivers.is_empty() {
+            bail!("No devices to ask for the shares");
+        }
+
+        let fallback = fallback_after.and_then(|after| {
+            let rest: Vec<DeviceId> = other_devices
+                .into_iter()
+                .filter(|device_id| !receivers.contains(device_id))
+                .collect();
+            (!rest.is_empty()).then_some(RecoveryFallback {
+                receivers: rest,
+                after,
+            })
+        });
+
+        let mut claim =
+            self.create_distribution_claim(pass_id, SecretDistributionType::Recover, receivers);
+        claim.fallback = fallback;
+        Ok(self.with_break_glass(claim))
+    }
+
+    /// Claims of the secrets that have opted in to break-glass recovery always ask the escrow
+    /// device, and they live long enough for the escrow to release its share after the delay
+    fn with_break_glass(&self, mut claim: SsClaim) -> SsClaim {
+        let Some(policy) = self.vault.break_glass_policy(&claim.dist_claim_id.pass_id) else {
+            return claim;
+        };
+        if policy.escrow.eq(&claim.sender) {
+            return claim;
+        }
+
+        if !claim.receivers.contains(&policy.escrow) {
+            claim.receivers.push(policy.escrow.clone());
+            claim
+                .status
+                .statuses
+                .insert(policy.escrow.clone(), SsDistributionStatus::Pending);
+        }
+        if let Some(fallback) = claim.fallback.as_mut() {
+            fallback
+                .receivers
+                .retain(|device_id| !device_id.eq(&policy.escrow));
+        }
+        if claim
+            .fallback
+            .as_ref()
+            .is_some_and(|fallback| fallback.receivers.is_empty())
+        {
+            claim.fallback = None;
+        }
+
+        claim.ttl = Some(policy.delay.saturating_add(RECOVERY_CLAIM_TTL));
+        claim.break_glass = Some(BreakGlass::from(policy.clone()));
+        claim
     }
 
     fn create_distribution_claim(
         &self,
         pass_id: MetaPasswordId,
         distribution_type: SecretDistributionType,
+        receivers: Vec<DeviceId>,
     ) -> SsClaim {
-        let links: Vec<DeviceId> = self
-            .vault
-            .members()
-            .iter()
-            .filter_map(|vault_member| {
-                if vault_member.eq(&self.member) {
-                    None
-                } else {
-                    Some(vault_member.user_data.device.device_id.clone())
-                }
-            })
-            .collect();
-
         let claim_id = ClaimId::from(Id48bit::generate());
+        let ttl = match distribution_type {
+            SecretDistributionType::Split => None,
+            SecretDistributionType::Recover => Some(RECOVERY_CLAIM_TTL),
+        };
 
         SsClaim {
             id: claim_id.clone(),
             dist_claim_id: SsClaimId {
                 id: claim_id,
-                pass_id,
+                pass_id: pass_id.opaque(),
             },
             vault_name: self.vault.vault_name.clone(),
             sender: self.user_device(),
             distribution_type,
-            receivers: links.clone(),
-            status: SsDistributionCompositeStatus::from(links),
+            receivers: receivers.clone(),
+            status: SsDistributionCompositeStatus::from(receivers),
+            created_at: None,
+            time: Some(EventTime::now()),
+            ttl,
+            fallback: None,
+            break_glass: None,
+            approvals: vec![],
         }
     }
 
+    /// Devices of the vault members except the current one
+    fn other_devices(&self) -> Vec<DeviceId> {
+        self.vault
+            .members()
+            .iter()
+            .filter_map(|vault_member| {
+                if vault_member.eq(&self.member) {
+                    None
+                } else {
+                    Some(vault_member.user_data.device.device_id.clone())
+                }
+            })
+            .collect()
+    }
+
     fn user_device(&self) -> DeviceId {
         self.member.user().device.device_id.clone()
     }
@@ -189,7 +293,9 @@ impl VaultStatus {
 mod test {
     use super::*;
     use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
-    use crate::node::common::model::secret::{SecretDistributionType, SsDistributionStatus};
+    use crate::node::common::model::secret::{
+        BreakGlassPolicy, RecoveryTargets, SecretDistributionType, SsDistributionStatus,
+    };
     use anyhow::Result;
 
     #[test]
@@ -249,4 +355,91 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_create_targeted_recovery_claim() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let vault_data_fixture = fixture.state.vault_data;
+        let vault_member = vault_data_fixture.client_vault_member;
+        let client_b = vault_data_fixture.client_b_membership.device_id();
+        let vd = vault_data_fixture.vd_membership.device_id();
+        let pass_id = MetaPasswordId::build_from_str("test_password");
+
+        let claim = vault_member.create_targeted_recovery_claim(
+            pass_id.clone(),
+            RecoveryTargets::Devices(vec![client_b.clone()]),
+        )?;
+        assert_eq!(claim.distribution_type, SecretDistributionType::Recover);
+        assert_eq!(claim.receivers, vec![client_b.clone()]);
+        assert_eq!(claim.status.statuses.len(), 1);
+        assert!(claim.fallback.is_none());
+
+        let claim = vault_member.create_targeted_recovery_claim(
+            pass_id.clone(),
+            RecoveryTargets::Preferred {
+                devices: vec![vd.clone()],
+                fallback_after: 1_000,
+            },
+        )?;
+        assert_eq!(claim.receivers, vec![vd.clone()]);
+        let fallback = claim.fallback.expect("fallback receivers");
+        assert_eq!(fallback.receivers, vec![client_b.clone()]);
+        assert_eq!(fallback.after, 1_000);
+
+        // the sender can't ask itself
+        let own_device = vault_member.user_device();
+        let result = vault_member
+            .create_targeted_recovery_claim(pass_id, RecoveryTargets::Devices(vec![own_device]));
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_break_glass_claim_asks_escrow() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let vault_data_fixture = fixture.state.vault_data;
+        let client_b = vault_data_fixture.client_b_membership.device_id();
+        let vd = vault_data_fixture.vd_membership.device_id();
+        let pass_id = MetaPasswordId::build_from_str("break_glass_pass");
+
+        let policy = BreakGlassPolicy {
+            escrow: vd.clone(),
+            delay: 10_000,
+        };
+        let mut vault_member = vault_data_fixture.client_vault_member;
+        vault_member.vault = vault_member
+            .vault
+            .add_break_glass_policy(&pass_id, policy.clone());
+
+        // the escrow is asked even if the sender has chosen other devices
+        let claim = vault_member.create_targeted_recovery_claim(
+            pass_id.clone(),
+            RecoveryTargets::Devices(vec![client_b.clone()]),
+        )?;
+        assert_eq!(claim.receivers, vec![client_b.clone(), vd.clone()]);
+        assert_eq!(claim.status.get(&vd), Some(&SsDistributionStatus::Pending));
+        assert_eq!(claim.ttl, Some(policy.delay + RECOVERY_CLAIM_TTL));
+        let break_glass = claim.break_glass.expect("break-glass claim");
+        assert_eq!(break_glass.escrow, vd);
+        assert!(break_glass.vetoed_by.is_none());
+
+        let claim = vault_member.create_targeted_recovery_claim(
+            pass_id,
+            RecoveryTargets::Preferred {
+                devices: vec![client_b.clone()],
+                fallback_after: 1_000,
+            },
+        )?;
+        assert_eq!(claim.receivers, vec![client_b, vd]);
+        assert!(claim.fallback.is_none());
+
+        // other secrets are recovered as usual
+        let claim =
+            vault_member.create_recovery_claim(MetaPasswordId::build_from_str("everyday_pass"));
+        assert!(claim.break_glass.is_none());
+        assert_eq!(claim.ttl, Some(RECOVERY_CLAIM_TTL));
+
+        Ok(())
+    }
 }
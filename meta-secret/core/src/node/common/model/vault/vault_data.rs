@@ -1,11 +1,18 @@
+use crate::crypto::keys::TransportPk;
+use crate::node::common::model::IdString;
 use crate::node::common::model::device::common::DeviceId;
 use crate::node::common::model::meta_pass::MetaPasswordId;
+use crate::node::common::model::secret::{BreakGlassPolicy, RecoveryPolicy};
+use crate::node::common::model::sorted_set;
+use crate::node::common::model::user::user_creds::UserCreds;
 use crate::node::common::model::user::common::{
     UserData, UserDataMember, UserDataOutsider, UserMembership, WasmUserMembership,
 };
+use crate::node::common::model::vault::invite::{InviteRecord, InviteTicket};
 use crate::node::common::model::vault::vault::{VaultMember, VaultName, VaultStatus};
 use crate::node::db::events::vault::vault_log_event::{
-    AddMetaPassEvent, VaultActionEvents, VaultActionUpdateEvent,
+    AddInviteEvent, AddMetaPassEvent, JoinClusterEvent, UpdateMembershipEvent,
+    UpdateVaultSettingsEvent, VaultActionEvents, VaultActionRequestEvent, VaultActionUpdateEvent,
 };
 use crate::secret::data_block::common::SharedSecretConfig;
 use anyhow::{bail, Result};
@@ -17,7 +24,35 @@ use wasm_bindgen::prelude::wasm_bindgen;
 pub struct VaultData {
     pub vault_name: VaultName,
     pub users: HashMap<DeviceId, UserMembership>,
+    #[serde(serialize_with = "sorted_set::serialize")]
     pub secrets: HashSet<MetaPasswordId>,
+    /// Recovery policies of the secrets by password id
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub recovery_policies: HashMap<String, RecoveryPolicy>,
+    /// Break-glass recovery of the secrets that have opted in, by password id
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub break_glass_policies: HashMap<String, BreakGlassPolicy>,
+    /// Approvals of the members a join request needs, the server sets it for a new vault
+    /// and the members change it with the settings of the vault
+    #[serde(default = "default_join_quorum")]
+    pub join_quorum: usize,
+    /// Devices join the vault with an invite of a member only, the server sets it for a new vault
+    /// and the members change it with the settings of the vault
+    #[serde(default)]
+    pub require_invite: bool,
+    /// Open invites of the members, an invite is removed once its candidate is accepted or declined
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invites: Vec<InviteRecord>,
+    /// The name the users know the vault by, it's only known on the devices, see `VaultLabel`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// A single member is enough to accept a new device
+pub const DEFAULT_JOIN_QUORUM: usize = 1;
+
+fn default_join_quorum() -> usize {
+    DEFAULT_JOIN_QUORUM
 }
 
 #[wasm_bindgen(getter_with_clone)]
@@ -29,6 +64,11 @@ impl WasmVaultData {
         self.0.vault_name.clone()
     }
 
+    /// The name of the vault for display
+    pub fn label(&self) -> String {
+        self.0.label.clone().unwrap_or_else(|| self.0.vault_name.to_string())
+    }
+
     pub fn users(&self) -> Vec<WasmUserMembership> {
         self.0
             .users
@@ -59,7 +99,7 @@ impl From<VaultData> for WasmVaultData {
 impl From<UserDataMember> for VaultData {
     fn from(member: UserDataMember) -> Self {
         let vault_name = member.user_data.vault_name();
-        let device_id = DeviceId::from(&member.user_data.device.keys);
+        let device_id = member.user_data.device.device_id.clone();
 
         let member = UserMembership::Member(member);
 
@@ -70,6 +110,12 @@ impl From<UserDataMember> for VaultData {
             vault_name,
             users,
             secrets: HashSet::new(),
+            recovery_policies: HashMap::new(),
+            break_glass_policies: HashMap::new(),
+            join_quorum: DEFAULT_JOIN_QUORUM,
+            require_invite: false,
+            invites: vec![],
+            label: None,
         }
     }
 }
@@ -102,11 +148,213 @@ impl VaultData {
         outsiders
     }
 
+    /// Adds the password, or replaces it if the name got sealed to a different set of members
     pub fn add_secret(mut self, meta_password_id: MetaPasswordId) -> Self {
-        self.secrets.insert(meta_password_id);
+        self.secrets.replace(meta_password_id);
+        self
+    }
+
+    /// Finds the password by its name or id, the names are known once they're unsealed on the device
+    pub fn find_secret_by_name(&self, name_or_id: &str) -> Result<MetaPasswordId> {
+        let found: Vec<&MetaPasswordId> = self
+            .secrets
+            .iter()
+            .filter(|secret| secret.name == name_or_id || secret.id_str() == name_or_id)
+            .collect();
+
+        match found.as_slice() {
+            [secret] => Ok((*secret).clone()),
+            [] => bail!("Secret '{}' is not found in the vault", name_or_id),
+            _ => bail!("Secret name '{}' is ambiguous, use the secret id", name_or_id),
+        }
+    }
+
+    pub fn add_recovery_policy(mut self, pass_id: &MetaPasswordId, policy: RecoveryPolicy) -> Self {
+        self.recovery_policies
+            .insert(pass_id.id.clone().id_str(), policy);
+        self
+    }
+
+    pub fn recovery_policy(&self, pass_id: &MetaPasswordId) -> Option<&RecoveryPolicy> {
+        self.recovery_policies.get(&pass_id.id.clone().id_str())
+    }
+
+    pub fn add_break_glass_policy(
+        mut self,
+        pass_id: &MetaPasswordId,
+        policy: BreakGlassPolicy,
+    ) -> Self {
+        self.break_glass_policies
+            .insert(pass_id.id.clone().id_str(), policy);
+        self
+    }
+
+    pub fn break_glass_policy(&self, pass_id: &MetaPasswordId) -> Option<&BreakGlassPolicy> {
+        self.break_glass_policies.get(&pass_id.id.clone().id_str())
+    }
+
+    pub fn with_join_quorum(mut self, join_quorum: usize) -> Self {
+        self.join_quorum = join_quorum;
+        self
+    }
+
+    /// Settings that let no more devices in than the current ones, a single member can apply them
+    pub fn is_stricter(&self, settings: &UpdateVaultSettingsEvent) -> bool {
+        let is_stricter_quorum = settings
+            .join_quorum
+            .is_none_or(|join_quorum| join_quorum >= self.join_quorum);
+        let is_stricter_invite = settings
+            .require_invite
+            .is_none_or(|require_invite| require_invite || !self.require_invite);
+        is_stricter_quorum && is_stricter_invite
+    }
+
+    pub fn update_settings(mut self, settings: &UpdateVaultSettingsEvent) -> Self {
+        if let Some(join_quorum) = settings.join_quorum {
+            self.join_quorum = join_quorum;
+        }
+        if let Some(require_invite) = settings.require_invite {
+            self.require_invite = require_invite;
+        }
+        self
+    }
+
+    pub fn with_require_invite(mut self, require_invite: bool) -> Self {
+        self.require_invite = require_invite;
+        self
+    }
+
+    /// The join quorum a vault with fewer members can reach, all members have to approve then
+    pub fn required_join_approvals(&self) -> usize {
+        self.join_quorum.clamp(1, self.members().len().max(1))
+    }
+
+    /// Whether the signed approvals of the members are enough to accept the candidate
+    pub fn has_join_quorum(&self, approvals: &[UpdateMembershipEvent]) -> bool {
+        let member_approvals: HashSet<&DeviceId> = approvals
+            .iter()
+            .filter(|approval| self.is_signed_by_member(approval))
+            .map(|approval| approval.sender_id())
+            .collect();
+        member_approvals.len() >= self.required_join_approvals()
+    }
+
+    /// The update is signed with the key the vault holds for its sender,
+    /// a device can't speak for another member by just putting it into the sender field
+    pub fn is_signed_by_member(&self, update: &UpdateMembershipEvent) -> bool {
+        match self.find_user(update.sender_id()) {
+            Some(UserMembership::Member(member)) => update
+                .verify(&member.user_data.device.keys.dsa_pk)
+                .is_ok(),
+            _ => false,
+        }
+    }
+
+    /// Adds an invite made by a member with the current keys of its device
+    pub fn add_invite(mut self, invite: InviteRecord) -> Self {
+        let is_new = !self
+            .invites
+            .iter()
+            .any(|record| record.token_hash == invite.token_hash);
+        if is_new && self.is_inviter(&invite) {
+            self.invites.push(invite);
+        }
+        self
+    }
+
+    /// The invite is made by a member that still has the same keys
+    fn is_inviter(&self, invite: &InviteRecord) -> bool {
+        matches!(
+            self.find_user(&invite.created_by),
+            Some(UserMembership::Member(member))
+                if member.user().device.keys.fingerprint() == invite.key_fingerprint
+        )
+    }
+
+    pub fn find_invite(&self, ticket: &InviteTicket) -> Option<&InviteRecord> {
+        self.invites.iter().find(|record| record.matches(ticket))
+    }
+
+    /// Checks that the ticket is a valid invite for the candidate: the invite has not expired,
+    /// nobody else has used it, and the inviter is still a member with the same keys
+    pub fn check_invite(
+        &self,
+        ticket: &InviteTicket,
+        candidate: &DeviceId,
+        now: u64,
+    ) -> Result<&InviteRecord> {
+        let Some(invite) = self.find_invite(ticket) else {
+            bail!("the vault has no such invite");
+        };
+        if invite.is_expired(now) {
+            bail!("the invite has expired");
+        }
+        if invite
+            .used_by
+            .as_ref()
+            .is_some_and(|used_by| used_by != candidate)
+        {
+            bail!("the invite has already been used");
+        }
+        if !self.is_inviter(invite) {
+            bail!("the inviter is no longer a member with the same keys");
+        }
+        Ok(invite)
+    }
+
+    /// Binds the invite to the candidate that has presented it
+    pub fn use_invite(mut self, ticket: &InviteTicket, candidate: &DeviceId) -> Self {
+        if let Some(invite) = self
+            .invites
+            .iter_mut()
+            .find(|record| record.matches(ticket) && record.used_by.is_none())
+        {
+            invite.used_by = Some(candidate.clone());
+        }
+        self
+    }
+
+    pub fn remove_invite(mut self, ticket: &InviteTicket) -> Self {
+        self.invites.retain(|record| !record.matches(ticket));
         self
     }
 
+    /// Drops the expired invites nobody has used
+    pub fn without_expired_invites(mut self, now: u64) -> Self {
+        self.invites
+            .retain(|record| record.used_by.is_some() || !record.is_expired(now));
+        self
+    }
+
+    /// Decrypts the names for display on the device: the password names sealed to the members,
+    /// and the name of the vault and the device names that only the label can open
+    pub fn unseal_names(mut self, user_creds: &UserCreds) -> Self {
+        let sk = &user_creds.device_creds.secret_box.transport.sk;
+        self.secrets = self
+            .secrets
+            .into_iter()
+            .map(|pass_id| pass_id.unseal(sk))
+            .collect();
+
+        if let Some(label) = &user_creds.vault_label {
+            self.label = Some(label.name.clone());
+            self.users = self
+                .users
+                .into_iter()
+                .map(|(device_id, membership)| (device_id, label.open_membership(membership)))
+                .collect();
+        }
+        self
+    }
+
+    /// Resolves an opaque password id (from a claim or a workflow event) to the vault's version
+    pub fn find_secret(&self, pass_id: &MetaPasswordId) -> MetaPasswordId {
+        self.secrets
+            .get(pass_id)
+            .cloned()
+            .unwrap_or_else(|| pass_id.clone())
+    }
+
     pub fn update_membership(mut self, membership: UserMembership) -> Self {
         self.users.insert(membership.device_id(), membership);
         self
@@ -141,6 +389,14 @@ impl VaultData {
         self.users.get(device_id)
     }
 
+    /// Finds the member that currently owns the transport key.
+    /// Device ids survive key rotations, so they can't be derived from the keys
+    pub fn find_member_by_key(&self, transport_pk: &TransportPk) -> Option<UserDataMember> {
+        self.members()
+            .into_iter()
+            .find(|member| member.user().device.keys.transport_pk().eq(transport_pk))
+    }
+
     pub fn to_vault_member(self, member: UserDataMember) -> Result<VaultMember> {
         let is_member = self.is_member(&member.user_data.device.device_id.clone());
 
@@ -178,22 +434,107 @@ impl VaultAggregate {
         for update in updates {
             match &update {
                 VaultActionUpdateEvent::UpdateMembership(membership) => {
-                    if self
-                        .vault
-                        .is_member(&membership.sender.user().device.device_id)
-                    {
-                        self.vault = self.vault.update_membership(membership.update.clone());
+                    if !self.vault.is_signed_by_member(membership) {
+                        continue;
+                    }
+
+                    let request = &membership.request;
+                    // the request may have been closed by another update of this batch
+                    let join_request = VaultActionRequestEvent::JoinCluster(request.clone());
+                    if !self.events.requests.contains(&join_request) {
+                        continue;
+                    }
+
+                    match &membership.update {
+                        UserMembership::Member(candidate)
+                            if candidate.user_data.eq(&request.candidate) =>
+                        {
+                            // the candidate stays pending until enough members have accepted it
+                            self.events = self.events.approve_join(membership.as_ref().clone());
+                            let approvals = self.events.join_approvals(request);
+                            if self.vault.has_join_quorum(approvals) {
+                                self.vault =
+                                    self.vault.update_membership(membership.update.clone());
+                                self = self.close_join_request(request);
+                            }
+                        }
+                        UserMembership::Member(_) => {
+                            // accepting somebody else than the candidate of the request
+                        }
+                        UserMembership::Outsider(_) => {
+                            // a single member is enough to turn the candidate down
+                            self.vault = self.vault.update_membership(membership.update.clone());
+                            self = self.close_join_request(request);
+                        }
                     }
                 }
                 VaultActionUpdateEvent::AddMetaPass(AddMetaPassEvent {
                     sender,
                     meta_pass_id,
+                    recovery_policy,
+                    break_glass,
                 }) => {
                     if self.vault.is_member(&sender.user().device.device_id) {
+                        // the policies are set once, a member can't weaken them by re-adding the secret
+                        let is_new_secret = !self.vault.secrets.contains(meta_pass_id);
                         self.vault = self.vault.add_secret(meta_pass_id.clone());
+                        if let (true, Some(policy)) = (is_new_secret, recovery_policy) {
+                            self.vault =
+                                self.vault.add_recovery_policy(meta_pass_id, policy.clone());
+                        }
+                        if let (true, Some(policy)) = (is_new_secret, break_glass) {
+                            // only a member of the vault can hold the escrow share
+                            if self.vault.is_member(&policy.escrow) {
+                                self.vault = self
+                                    .vault
+                                    .add_break_glass_policy(meta_pass_id, policy.clone());
+                            }
+                        }
+                    }
+                }
+                VaultActionUpdateEvent::RotateDeviceKeys(rotation) => {
+                    // only the owner of the current keys is able to replace them
+                    let device_id = &rotation.member.user().device.device_id;
+                    let has_current_keys = matches!(
+                        self.vault.find_user(device_id),
+                        Some(UserMembership::Member(member)) if member.eq(&rotation.member)
+                    );
+                    if has_current_keys && rotation.verify().is_ok() {
+                        let rotated = UserMembership::Member(rotation.rotated_member());
+                        self.vault = self.vault.update_membership(rotated);
+                    }
+                }
+                VaultActionUpdateEvent::AddInvite(AddInviteEvent { sender, invite }) => {
+                    // a member invites on its own behalf only
+                    if invite.created_by.eq(&sender.user().device.device_id) {
+                        self.vault = self.vault.add_invite(invite.clone());
                     }
                 }
-                VaultActionUpdateEvent::AddToPending { candidate } => {
+                VaultActionUpdateEvent::UpdateSettings(settings) => {
+                    let is_valid = settings.join_quorum != Some(0);
+                    if !is_valid || !self.vault.is_member(settings.sender_id()) {
+                        continue;
+                    }
+
+                    // a single member makes the vault stricter, loosening it takes the join quorum
+                    let is_accepted = self.vault.is_stricter(settings) || {
+                        let (events, proposers) = self.events.propose_settings(settings.clone());
+                        self.events = events;
+                        let members = proposers
+                            .iter()
+                            .filter(|device_id| self.vault.is_member(device_id))
+                            .count();
+                        members >= self.vault.required_join_approvals()
+                    };
+                    if is_accepted {
+                        self.vault = self.vault.update_settings(settings);
+                        self.events = self.events.close_settings_proposals();
+                    }
+                }
+                VaultActionUpdateEvent::AddToPending { candidate, invite } => {
+                    if let Some(ticket) = invite {
+                        self.vault = self.vault.use_invite(ticket, &candidate.device.device_id);
+                    }
                     let pending =
                         UserMembership::Outsider(UserDataOutsider::pending(candidate.clone()));
                     self.vault = self.vault.update_membership(pending);
@@ -205,6 +546,15 @@ impl VaultAggregate {
         self.complete()
     }
 
+    /// The invite of the candidate is spent together with its join request
+    fn close_join_request(mut self, request: &JoinClusterEvent) -> Self {
+        if let Some(ticket) = &request.invite {
+            self.vault = self.vault.remove_invite(ticket);
+        }
+        self.events = self.events.close_join_request(request);
+        self
+    }
+
     fn complete(mut self) -> Self {
         // This method is now just a fallback - all processing happens in synchronize
         self.events = self.events.complete();
@@ -289,13 +639,21 @@ pub mod fixture {
 mod test {
     use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
     use crate::node::common::model::meta_pass::MetaPasswordId;
+    use crate::node::common::model::secret::{
+        BreakGlassPolicy, DEFAULT_BREAK_GLASS_DELAY, RecoveryPolicy,
+    };
     use crate::node::common::model::user::common::{
-        UserDataMember, UserDataOutsider, UserMembership,
+        UserDataMember, UserDataOutsider, UserDataOutsiderStatus, UserMembership,
+    };
+    use crate::node::common::model::user::user_creds::UserCreds;
+    use crate::node::common::model::vault::invite::{DEFAULT_INVITE_TTL, VaultInvite};
+    use crate::node::common::model::vault::vault_data::{
+        DEFAULT_JOIN_QUORUM, VaultAggregate, VaultData,
     };
-    use crate::node::common::model::vault::vault_data::{VaultAggregate, VaultData};
     use crate::node::db::events::vault::vault_log_event::{
-        AddMetaPassEvent, JoinClusterEvent, UpdateMembershipEvent, VaultActionEvent,
-        VaultActionEvents, VaultActionRequestEvent, VaultActionUpdateEvent,
+        AddInviteEvent, AddMetaPassEvent, JoinClusterEvent, UpdateMembershipEvent,
+        UpdateVaultSettingsEvent, VaultActionEvent, VaultActionEvents, VaultActionRequestEvent,
+        VaultActionUpdateEvent,
     };
     use anyhow::Result;
 
@@ -344,15 +702,13 @@ mod test {
             VaultActionEvent::Request(VaultActionRequestEvent::JoinCluster(join_request.clone()))
         };
 
-        let update_membership = VaultActionUpdateEvent::UpdateMembership(UpdateMembershipEvent {
-            request: join_request.clone(),
-            sender: UserDataMember {
-                user_data: client_creds.user(),
-            },
-            update: UserMembership::Member(UserDataMember {
+        let update_membership = join_update(
+            &join_request,
+            &client_creds,
+            UserMembership::Member(UserDataMember {
                 user_data: join_request.candidate.clone(),
             }),
-        });
+        )?;
 
         let update_membership_event = VaultActionEvent::Update(update_membership);
 
@@ -399,6 +755,8 @@ mod test {
         let add_meta_pass = AddMetaPassEvent {
             sender: client_member.clone(),
             meta_pass_id: meta_pass_id.clone(),
+            recovery_policy: None,
+            break_glass: None,
         };
 
         // First, create a request event
@@ -422,6 +780,121 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_secret_is_found_by_name_or_id() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client_member = UserDataMember::from(fixture.state.user_creds.client.user());
+
+        let prod_db = MetaPasswordId::build_from_str("prod_db");
+        let wifi = MetaPasswordId::build_from_str("wifi");
+        let another_wifi = MetaPasswordId::build_from_str("wifi");
+        let vault_data = VaultData::from(client_member)
+            .add_secret(prod_db.clone())
+            .add_secret(wifi.clone())
+            .add_secret(another_wifi);
+
+        assert_eq!(prod_db, vault_data.find_secret_by_name("prod_db")?);
+        assert_eq!(wifi, vault_data.find_secret_by_name(&wifi.id_str())?);
+        assert!(vault_data.find_secret_by_name("wifi").is_err());
+        assert!(vault_data.find_secret_by_name("unknown").is_err());
+
+        // the id is random, it doesn't give the name away
+        assert_ne!(prod_db, MetaPasswordId::build_from_str("prod_db"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recovery_policy_is_set_once() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client_creds = fixture.state.user_creds.client;
+        let client_member = UserDataMember::from(client_creds.user());
+        let vault_data = VaultData::from(client_member.clone());
+
+        let meta_pass_id = MetaPasswordId::build_from_str("prod_db");
+        let two_person_rule = RecoveryPolicy {
+            approvals: 2,
+            device_types: vec![],
+            approvers: vec![],
+        };
+        let add_meta_pass = AddMetaPassEvent {
+            sender: client_member.clone(),
+            meta_pass_id: meta_pass_id.clone(),
+            recovery_policy: Some(two_person_rule.clone()),
+            break_glass: None,
+        };
+        // re-adding the secret doesn't weaken its policy
+        let weaker_add_meta_pass = AddMetaPassEvent {
+            recovery_policy: Some(RecoveryPolicy {
+                approvals: 1,
+                ..two_person_rule.clone()
+            }),
+            ..add_meta_pass.clone()
+        };
+
+        let mut vault = vault_data;
+        for add_event in [add_meta_pass, weaker_add_meta_pass] {
+            let events = VaultActionEvents::default()
+                .request(VaultActionRequestEvent::AddMetaPass(add_event.clone()))
+                .apply(VaultActionUpdateEvent::AddMetaPass(add_event));
+            vault = VaultAggregate::build_from(events, vault).vault;
+        }
+
+        assert_eq!(vault.recovery_policy(&meta_pass_id), Some(&two_person_rule));
+        let everyday_pass = MetaPasswordId::build_from_str("wifi");
+        assert!(vault.recovery_policy(&everyday_pass).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_break_glass_escrow_must_be_member() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client_member = UserDataMember::from(fixture.state.user_creds.client.user());
+        let vault_data = VaultData::from(client_member.clone());
+        let outsider = fixture.state.user_creds.client_b.device_id().clone();
+        let escrow = fixture.state.user_creds.client.device_id().clone();
+
+        let outsider_escrow = AddMetaPassEvent {
+            sender: client_member.clone(),
+            meta_pass_id: MetaPasswordId::build_from_str("outsider_escrow"),
+            recovery_policy: None,
+            break_glass: Some(BreakGlassPolicy {
+                escrow: outsider,
+                delay: DEFAULT_BREAK_GLASS_DELAY,
+            }),
+        };
+        let member_escrow = AddMetaPassEvent {
+            sender: client_member,
+            meta_pass_id: MetaPasswordId::build_from_str("member_escrow"),
+            recovery_policy: None,
+            break_glass: Some(BreakGlassPolicy {
+                escrow,
+                delay: DEFAULT_BREAK_GLASS_DELAY,
+            }),
+        };
+
+        let mut vault = vault_data;
+        for add_event in [outsider_escrow.clone(), member_escrow.clone()] {
+            let events = VaultActionEvents::default()
+                .request(VaultActionRequestEvent::AddMetaPass(add_event.clone()))
+                .apply(VaultActionUpdateEvent::AddMetaPass(add_event));
+            vault = VaultAggregate::build_from(events, vault).vault;
+        }
+
+        assert!(
+            vault
+                .break_glass_policy(&outsider_escrow.meta_pass_id)
+                .is_none()
+        );
+        assert_eq!(
+            vault.break_glass_policy(&member_escrow.meta_pass_id),
+            member_escrow.break_glass.as_ref()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_vault_aggregate_sender_not_member() -> Result<()> {
         // Setup
@@ -438,6 +911,8 @@ mod test {
         let add_meta_pass = AddMetaPassEvent {
             sender: non_member_sender,
             meta_pass_id: meta_pass_id.clone(),
+            recovery_policy: None,
+            break_glass: None,
         };
 
         // Create update event
@@ -471,11 +946,11 @@ mod test {
         let request_event = VaultActionRequestEvent::JoinCluster(join_request.clone());
 
         // Create member update
-        let update_membership = VaultActionUpdateEvent::UpdateMembership(UpdateMembershipEvent {
-            request: join_request.clone(),
-            sender: UserDataMember::from(client_creds.user()), // Valid member as sender
-            update: UserMembership::Member(UserDataMember::from(client_b_creds.user())),
-        });
+        let update_membership = join_update(
+            &join_request,
+            &client_creds, // Valid member as sender
+            UserMembership::Member(UserDataMember::from(client_b_creds.user())),
+        )?;
 
         // Create events with the request and update
         let events = VaultActionEvents::default()
@@ -509,21 +984,20 @@ mod test {
         // Create first join request and member update
         let join_request_b = JoinClusterEvent::from(client_b_creds.user());
         let request_event_b = VaultActionRequestEvent::JoinCluster(join_request_b.clone());
-        let update_membership_b = VaultActionUpdateEvent::UpdateMembership(UpdateMembershipEvent {
-            request: join_request_b.clone(),
-            sender: UserDataMember::from(client_creds.user()),
-            update: UserMembership::Member(UserDataMember::from(client_b_creds.user())),
-        });
+        let update_membership_b = join_update(
+            &join_request_b,
+            &client_creds,
+            UserMembership::Member(UserDataMember::from(client_b_creds.user())),
+        )?;
 
         // Create second join request and member update
         let join_request_vd = JoinClusterEvent::from(vd_creds.user());
         let request_event_vd = VaultActionRequestEvent::JoinCluster(join_request_vd.clone());
-        let update_membership_vd =
-            VaultActionUpdateEvent::UpdateMembership(UpdateMembershipEvent {
-                request: join_request_vd.clone(),
-                sender: UserDataMember::from(client_creds.user()),
-                update: UserMembership::Member(UserDataMember::from(vd_creds.user())),
-            });
+        let update_membership_vd = join_update(
+            &join_request_vd,
+            &client_creds,
+            UserMembership::Member(UserDataMember::from(vd_creds.user())),
+        )?;
 
         // Create events with both requests and updates
         let events = VaultActionEvents::default()
@@ -547,4 +1021,343 @@ mod test {
 
         Ok(())
     }
+
+    fn join_update(
+        request: &JoinClusterEvent,
+        sender: &UserCreds,
+        update: UserMembership,
+    ) -> Result<VaultActionUpdateEvent> {
+        let update = UpdateMembershipEvent::sign(
+            request.clone(),
+            UserDataMember::from(sender.user()),
+            update,
+            &sender.device_creds.key_manager()?.dsa,
+        )?;
+        Ok(VaultActionUpdateEvent::UpdateMembership(Box::new(update)))
+    }
+
+    #[test]
+    fn test_join_quorum() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client_creds = fixture.state.user_creds.client;
+        let vd_creds = fixture.state.user_creds.vd;
+        let client = client_creds.user();
+        let vd = vd_creds.user();
+        let client_b = fixture.state.user_creds.client_b.user();
+
+        let vault_data = VaultData::from(UserDataMember::from(client.clone()))
+            .update_membership(UserMembership::Member(UserDataMember::from(vd.clone())))
+            .with_join_quorum(2);
+        assert_eq!(2, vault_data.required_join_approvals());
+
+        let join_request = JoinClusterEvent::from(client_b.clone());
+        let accept = UserMembership::Member(UserDataMember::from(client_b.clone()));
+
+        let events = VaultActionEvents::default()
+            .request(VaultActionRequestEvent::JoinCluster(join_request.clone()))
+            .apply(join_update(&join_request, &client_creds, accept.clone())?);
+        let aggregate = VaultAggregate::build_from(events, vault_data);
+
+        // one approval out of two, the candidate keeps waiting
+        assert!(!aggregate.vault.is_member(&client_b.device.device_id));
+        assert_eq!(1, aggregate.events.requests.len());
+        assert_eq!(
+            aggregate.events.join_approvers(&join_request),
+            vec![client.device.device_id.clone()]
+        );
+
+        // the same member approving again doesn't count twice
+        let events = aggregate
+            .events
+            .apply(join_update(&join_request, &client_creds, accept.clone())?);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert!(!aggregate.vault.is_member(&client_b.device.device_id));
+
+        let events = aggregate
+            .events
+            .apply(join_update(&join_request, &vd_creds, accept)?);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+
+        assert!(aggregate.vault.is_member(&client_b.device.device_id));
+        assert!(aggregate.events.requests.is_empty());
+        assert!(aggregate.events.join_approvals.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_quorum_cannot_be_forged_by_one_device() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client_creds = fixture.state.user_creds.client;
+        let client = client_creds.user();
+        let vd = fixture.state.user_creds.vd.user();
+        let client_b = fixture.state.user_creds.client_b.user();
+
+        let vault_data = VaultData::from(UserDataMember::from(client.clone()))
+            .update_membership(UserMembership::Member(UserDataMember::from(vd.clone())))
+            .with_join_quorum(2);
+
+        let join_request = JoinClusterEvent::from(client_b.clone());
+        let accept = UserMembership::Member(UserDataMember::from(client_b.clone()));
+
+        // the client approves for itself and then again on behalf of vd, signing with its own key
+        let forged_approval = UpdateMembershipEvent::sign(
+            join_request.clone(),
+            UserDataMember::from(vd.clone()),
+            accept.clone(),
+            &client_creds.device_creds.key_manager()?.dsa,
+        )?;
+        let events = VaultActionEvents::default()
+            .request(VaultActionRequestEvent::JoinCluster(join_request.clone()))
+            .apply(join_update(&join_request, &client_creds, accept)?)
+            .apply(VaultActionUpdateEvent::UpdateMembership(Box::new(forged_approval.clone())));
+        let aggregate = VaultAggregate::build_from(events, vault_data);
+
+        assert!(!aggregate.vault.is_member(&client_b.device.device_id));
+        assert_eq!(1, aggregate.events.requests.len());
+        assert_eq!(
+            aggregate.events.join_approvers(&join_request),
+            vec![client.device.device_id.clone()]
+        );
+
+        // the forged approval doesn't count even if it ends up among the recorded approvals
+        let approvals = aggregate
+            .events
+            .approve_join(forged_approval)
+            .join_approvals(&join_request)
+            .to_vec();
+        assert_eq!(2, approvals.len());
+        assert!(!aggregate.vault.has_join_quorum(&approvals));
+
+        // a forged decline doesn't turn the candidate down either
+        let forged_decline = UpdateMembershipEvent::sign(
+            join_request.clone(),
+            UserDataMember::from(vd.clone()),
+            UserMembership::Outsider(UserDataOutsider {
+                user_data: client_b.clone(),
+                status: UserDataOutsiderStatus::Declined,
+            }),
+            &client_creds.device_creds.key_manager()?.dsa,
+        )?;
+        let events = VaultActionEvents::default()
+            .request(VaultActionRequestEvent::JoinCluster(join_request.clone()))
+            .apply(VaultActionUpdateEvent::UpdateMembership(Box::new(forged_decline)));
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert_eq!(1, aggregate.events.requests.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_join_quorum_is_capped_and_declined_by_one_member() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client_creds = fixture.state.user_creds.client;
+        let vd_creds = fixture.state.user_creds.vd;
+        let client = client_creds.user();
+        let vd = vd_creds.user();
+        let client_b = fixture.state.user_creds.client_b.user();
+        // a single member vault can't collect three approvals
+        let single_member_vault =
+            VaultData::from(UserDataMember::from(client.clone())).with_join_quorum(3);
+        assert_eq!(1, single_member_vault.required_join_approvals());
+
+        let join_request = JoinClusterEvent::from(vd.clone());
+        let events = VaultActionEvents::default()
+            .request(VaultActionRequestEvent::JoinCluster(join_request.clone()))
+            .apply(join_update(
+                &join_request,
+                &client_creds,
+                UserMembership::Member(UserDataMember::from(vd.clone())),
+            )?);
+        let aggregate = VaultAggregate::build_from(events, single_member_vault);
+        assert!(aggregate.vault.is_member(&vd.device.device_id));
+
+        let vault_data = aggregate.vault.with_join_quorum(2);
+        let join_request = JoinClusterEvent::from(client_b.clone());
+        let events = VaultActionEvents::default()
+            .request(VaultActionRequestEvent::JoinCluster(join_request.clone()))
+            .apply(join_update(
+                &join_request,
+                &client_creds,
+                UserMembership::Member(UserDataMember::from(client_b.clone())),
+            )?)
+            .apply(join_update(
+                &join_request,
+                &vd_creds,
+                UserMembership::Outsider(UserDataOutsider {
+                    user_data: client_b.clone(),
+                    status: UserDataOutsiderStatus::Declined,
+                }),
+            )?);
+        let aggregate = VaultAggregate::build_from(events, vault_data);
+
+        assert!(!aggregate.vault.is_member(&client_b.device.device_id));
+        assert!(aggregate.events.requests.is_empty());
+        assert!(aggregate.events.join_approvals.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_members_change_join_quorum() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client = UserDataMember::from(fixture.state.user_creds.client.user());
+        let vd = UserDataMember::from(fixture.state.user_creds.vd.user());
+        let client_b = UserDataMember::from(fixture.state.user_creds.client_b.user());
+        let vault_data = VaultData::from(client.clone())
+            .update_membership(UserMembership::Member(vd.clone()))
+            .update_membership(UserMembership::Member(client_b.clone()));
+
+        let settings = |events: VaultActionEvents, sender: &UserDataMember, join_quorum: usize| {
+            let settings = UpdateVaultSettingsEvent {
+                sender: sender.clone(),
+                join_quorum: Some(join_quorum),
+                require_invite: None,
+            };
+            events
+                .request(VaultActionRequestEvent::UpdateSettings(settings.clone()))
+                .apply(VaultActionUpdateEvent::UpdateSettings(settings))
+        };
+
+        // a single member raises the quorum
+        let events = settings(VaultActionEvents::default(), &client, 3);
+        let aggregate = VaultAggregate::build_from(events, vault_data);
+        assert_eq!(3, aggregate.vault.join_quorum);
+
+        // lowering it takes as many members as the current quorum
+        let events = settings(aggregate.events, &client, 1);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert_eq!(3, aggregate.vault.join_quorum);
+
+        let events = settings(aggregate.events, &vd, 1);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert_eq!(3, aggregate.vault.join_quorum);
+        assert_eq!(2, aggregate.events.settings_proposals.len());
+
+        let events = settings(aggregate.events, &client_b, 1);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert_eq!(1, aggregate.vault.join_quorum);
+        assert!(aggregate.events.settings_proposals.is_empty());
+
+        // an outsider and a zero quorum change nothing
+        let vault_data = VaultData::from(client.clone());
+        let events = settings(VaultActionEvents::default(), &client_b, 2);
+        let aggregate = VaultAggregate::build_from(events, vault_data);
+        assert_eq!(DEFAULT_JOIN_QUORUM, aggregate.vault.join_quorum);
+
+        let events = settings(aggregate.events, &client, 0);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert_eq!(DEFAULT_JOIN_QUORUM, aggregate.vault.join_quorum);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_members_require_invites() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client = UserDataMember::from(fixture.state.user_creds.client.user());
+        let vd = UserDataMember::from(fixture.state.user_creds.vd.user());
+        let vault_data = VaultData::from(client.clone())
+            .update_membership(UserMembership::Member(vd.clone()))
+            .with_join_quorum(2);
+
+        let settings = |events: VaultActionEvents, sender: &UserDataMember, require_invite: bool| {
+            let settings = UpdateVaultSettingsEvent {
+                sender: sender.clone(),
+                join_quorum: None,
+                require_invite: Some(require_invite),
+            };
+            events
+                .request(VaultActionRequestEvent::UpdateSettings(settings.clone()))
+                .apply(VaultActionUpdateEvent::UpdateSettings(settings))
+        };
+
+        // a single member requires invites
+        let events = settings(VaultActionEvents::default(), &client, true);
+        let aggregate = VaultAggregate::build_from(events, vault_data);
+        assert!(aggregate.vault.require_invite);
+        assert_eq!(2, aggregate.vault.join_quorum);
+
+        // dropping the requirement takes the join quorum
+        let events = settings(aggregate.events, &client, false);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert!(aggregate.vault.require_invite);
+
+        let events = settings(aggregate.events, &vd, false);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert!(!aggregate.vault.require_invite);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invite_is_spent_with_join_request() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let client = UserDataMember::from(fixture.state.user_creds.client.user());
+        let vd = fixture.state.user_creds.vd.user();
+        let client_b = fixture.state.user_creds.client_b.user();
+        let vault_data = VaultData::from(client.clone());
+
+        let invite = VaultInvite::generate(&client, DEFAULT_INVITE_TTL, true, 1_000);
+        let client_id = client.user().device.device_id.clone();
+
+        // only the inviter can add its own invite
+        let forged_invite = AddInviteEvent {
+            sender: UserDataMember::from(client_b.clone()),
+            invite: invite.record(client_b.device.device_id.clone()),
+        };
+        let add_invite = AddInviteEvent {
+            sender: client.clone(),
+            invite: invite.record(client_id),
+        };
+        let events = VaultActionEvents::default()
+            .request(VaultActionRequestEvent::AddInvite(forged_invite.clone()))
+            .apply(VaultActionUpdateEvent::AddInvite(forged_invite))
+            .request(VaultActionRequestEvent::AddInvite(add_invite.clone()))
+            .apply(VaultActionUpdateEvent::AddInvite(add_invite));
+        let aggregate = VaultAggregate::build_from(events, vault_data);
+        assert_eq!(1, aggregate.vault.invites.len());
+
+        let ticket = invite.ticket();
+        let vd_id = vd.device.device_id.clone();
+        assert!(aggregate.vault.check_invite(&ticket, &vd_id, 1_001).is_ok());
+        assert!(
+            aggregate
+                .vault
+                .check_invite(&ticket, &vd_id, invite.expires_at)
+                .is_err()
+        );
+
+        // the invite is bound to the first candidate that presents it
+        let join_request = JoinClusterEvent {
+            candidate: vd.clone(),
+            invite: Some(ticket.clone()),
+        };
+        let events = VaultActionEvents::default()
+            .request(VaultActionRequestEvent::JoinCluster(join_request.clone()))
+            .apply(VaultActionUpdateEvent::AddToPending {
+                candidate: vd.clone(),
+                invite: Some(ticket.clone()),
+            });
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        let client_b_id = client_b.device.device_id.clone();
+        assert!(aggregate.vault.check_invite(&ticket, &vd_id, 1_001).is_ok());
+        assert!(
+            aggregate
+                .vault
+                .check_invite(&ticket, &client_b_id, 1_001)
+                .is_err()
+        );
+
+        let events = aggregate.events.apply(join_update(
+            &join_request,
+            &fixture.state.user_creds.client,
+            UserMembership::Member(UserDataMember::from(vd.clone())),
+        )?);
+        let aggregate = VaultAggregate::build_from(events, aggregate.vault);
+        assert!(aggregate.vault.is_member(&vd_id));
+        assert!(aggregate.vault.invites.is_empty());
+
+        Ok(())
+    }
 }
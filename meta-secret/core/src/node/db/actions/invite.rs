@@ -0,0 +1,225 @@
+use crate::errors::InviteError;
+use crate::node::common::clock::unix_time_millis;
+use crate::node::common::model::user::common::UserDataOutsiderStatus;
+use crate::node::common::model::user::user_creds::UserCreds;
+use crate::node::common::model::vault::invite::{InviteTicket, VaultInvite};
+use crate::node::common::model::vault::vault::VaultStatus;
+use crate::node::common::model::vault::vault_data::VaultData;
+use crate::node::db::descriptors::vault_descriptor::JoinInviteDescriptor;
+use crate::node::db::events::local_event::JoinInviteObject;
+use crate::node::db::events::object_id::ArtifactId;
+use crate::node::db::events::vault::vault_log_event::{AddInviteEvent, JoinClusterEvent};
+use crate::node::db::objects::persistent_device_log::PersistentDeviceLog;
+use crate::node::db::objects::persistent_object::PersistentObject;
+use crate::node::db::objects::persistent_vault::PersistentVault;
+use crate::node::db::repo::generic_db::KvLogEventRepo;
+use anyhow::{Result, bail};
+use derive_more::From;
+use std::sync::Arc;
+use tracing::info;
+use tracing_attributes::instrument;
+
+/// Invitations to join a vault. A member makes an invite and shares it out of band,
+/// the candidate presents its token in the join request. The candidate doesn't see the vault
+/// until it has been accepted, so it keeps the invite and takes no shares until the member
+/// with the key fingerprint of the invite shows up in the vault
+#[derive(From)]
+pub struct InviteAction<Repo: KvLogEventRepo> {
+    pub p_obj: Arc<PersistentObject<Repo>>,
+}
+
+impl<Repo: KvLogEventRepo> InviteAction<Repo> {
+    /// Makes an invite valid for `ttl` milliseconds, the vault learns about it on the next sync
+    #[instrument(skip(self, user_creds))]
+    pub async fn create(
+        &self,
+        user_creds: &UserCreds,
+        ttl: u64,
+        pre_approved: bool,
+    ) -> Result<VaultInvite> {
+        let p_vault = PersistentVault::from(self.p_obj.clone());
+        let VaultStatus::Member(member) = p_vault.find(user_creds.user()).await? else {
+            bail!("Only a vault member can invite new devices");
+        };
+
+        // the candidate gets the label of the vault, the vault name the server knows is derived from it
+        let invite = VaultInvite {
+            vault_name: user_creds.vault_label_name(),
+            ..VaultInvite::generate(&member, ttl, pre_approved, unix_time_millis())
+        };
+        let add_invite = AddInviteEvent {
+            invite: invite.record(member.user().device.device_id.clone()),
+            sender: member,
+        };
+
+        let p_device_log = PersistentDeviceLog::from(self.p_obj.clone());
+        p_device_log.save_add_invite_request(add_invite).await?;
+
+        Ok(invite)
+    }
+
+    /// Sends a join request with the token of the invite
+    #[instrument(skip_all)]
+    pub async fn join(&self, user_creds: &UserCreds, invite: &VaultInvite) -> Result<VaultStatus> {
+        if user_creds.vault_label_name() != invite.vault_name {
+            bail!(
+                "The invite is for the vault {}, the device belongs to {}",
+                invite.vault_name,
+                user_creds.vault_label_name()
+            );
+        }
+        if invite.is_expired(unix_time_millis()) {
+            bail!("The invite has expired");
+        }
+
+        let p_vault = PersistentVault::from(self.p_obj.clone());
+        let vault_status = p_vault.find(user_creds.user()).await?;
+
+        match &vault_status {
+            VaultStatus::NotExists(_) => {
+                bail!("Vault {} doesn't exist", invite.vault_name);
+            }
+            VaultStatus::Outsider(outsider) => match outsider.status {
+                UserDataOutsiderStatus::NonMember => {
+                    info!("Save Join request with an invite");
+                    let join_invite_id = ArtifactId::from(JoinInviteDescriptor);
+                    self.p_obj.repo.delete(join_invite_id).await;
+                    self.p_obj
+                        .repo
+                        .save(JoinInviteObject::from(invite.ticket()))
+                        .await?;
+
+                    let join_request = JoinClusterEvent {
+                        candidate: outsider.user_data.clone(),
+                        invite: Some(invite.ticket()),
+                    };
+                    let p_device_log = PersistentDeviceLog::from(self.p_obj.clone());
+                    p_device_log.save_join_request(join_request).await?;
+                }
+                UserDataOutsiderStatus::Pending => {
+                    info!("Device is pending")
+                }
+                UserDataOutsiderStatus::Declined => {
+                    bail!("Device has been declined")
+                }
+            },
+            VaultStatus::Member(_) => {
+                info!("Device is already a vault member");
+                let vault = p_vault.get_vault(user_creds.vault_name.clone()).await?;
+                verify_inviter(&vault.to_data(), &invite.ticket())?;
+            }
+        }
+
+        Ok(vault_status)
+    }
+
+    /// Checks the invite the device has joined with against the members of the vault,
+    /// the invite is dropped once its member is found
+    #[instrument(skip_all)]
+    pub async fn verify_join_invite(&self, vault: &VaultData) -> Result<()> {
+        let maybe_join_invite = self.p_obj.find_tail_event(JoinInviteDescriptor).await?;
+        let Some(join_invite) = maybe_join_invite else {
+            return Ok(());
+        };
+
+        verify_inviter(vault, &join_invite.0.value)?;
+        self.p_obj
+            .repo
+            .delete(ArtifactId::from(JoinInviteDescriptor))
+            .await;
+        Ok(())
+    }
+}
+
+/// The invite has to be made by a member of the vault, otherwise whoever handed it out
+/// is not the device the candidate was told about
+fn verify_inviter(vault: &VaultData, ticket: &InviteTicket) -> Result<()> {
+    let is_inviter_member = vault
+        .members()
+        .iter()
+        .any(|member| member.user().device.keys.fingerprint() == ticket.key_fingerprint);
+
+    if !is_inviter_member {
+        bail!(InviteError::UnknownInviter {
+            fingerprint: ticket.key_fingerprint.clone(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+    use crate::node::common::model::user::common::{UserDataMember, UserDataOutsider};
+    use crate::node::common::model::vault::invite::DEFAULT_INVITE_TTL;
+    use crate::node::db::descriptors::vault_descriptor::VaultStatusDescriptor;
+    use crate::node::db::events::vault::vault_event::VaultObject;
+    use crate::node::db::events::vault::vault_status::VaultStatusObject;
+    use crate::node::db::repo::generic_db::SaveCommand;
+
+    #[tokio::test]
+    async fn test_invite_of_another_key_blocks_the_shares() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let candidate = registry.state.user_creds.client.clone();
+        let vd_member = UserDataMember::from(registry.state.user_creds.vd.user());
+        // client_b is not in the vault, its invite carries a fingerprint no member has
+        let stranger = UserDataMember::from(registry.state.user_creds.client_b.user());
+        let p_obj = registry.state.p_obj.client.clone();
+
+        let status_desc = VaultStatusDescriptor::from(candidate.user_id());
+        let status = VaultStatus::Outsider(UserDataOutsider::non_member(candidate.user()));
+        let status_obj = VaultStatusObject::new(status, ArtifactId::from(status_desc));
+        p_obj.repo.save(status_obj).await?;
+
+        let vault = VaultObject::sign_up(candidate.vault_name.clone(), vd_member.clone()).to_data();
+        let invite_action = InviteAction::from(p_obj.clone());
+
+        let forged = VaultInvite::generate(&stranger, DEFAULT_INVITE_TTL, true, unix_time_millis());
+        invite_action.join(&candidate, &forged).await?;
+
+        let err = invite_action.verify_join_invite(&vault).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InviteError>(),
+            Some(InviteError::UnknownInviter { .. })
+        ));
+        let kept_invite = p_obj.find_tail_event(JoinInviteDescriptor).await?;
+        assert_eq!(kept_invite.map(|invite| invite.0.value), Some(forged.ticket()));
+
+        let invite = VaultInvite::generate(&vd_member, DEFAULT_INVITE_TTL, true, unix_time_millis());
+        invite_action.join(&candidate, &invite).await?;
+        invite_action.verify_join_invite(&vault).await?;
+        assert!(p_obj.find_tail_event(JoinInviteDescriptor).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_member_rejects_invite_of_another_key() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let user_creds = registry.state.user_creds.client.clone();
+        let member = UserDataMember::from(user_creds.user());
+        let stranger = UserDataMember::from(registry.state.user_creds.client_b.user());
+        let p_obj = registry.state.p_obj.client.clone();
+
+        p_obj
+            .repo
+            .save(VaultObject::sign_up(user_creds.vault_name.clone(), member.clone()))
+            .await?;
+        let status_desc = VaultStatusDescriptor::from(user_creds.user_id());
+        let status_obj =
+            VaultStatusObject::new(VaultStatus::Member(member), ArtifactId::from(status_desc));
+        p_obj.repo.save(status_obj).await?;
+
+        let forged = VaultInvite::generate(&stranger, DEFAULT_INVITE_TTL, true, unix_time_millis());
+        let err = InviteAction::from(p_obj)
+            .join(&user_creds, &forged)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<InviteError>(),
+            Some(InviteError::UnknownInviter { .. })
+        ));
+        Ok(())
+    }
+}
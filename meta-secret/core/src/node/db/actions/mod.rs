@@ -1,3 +1,5 @@
+pub mod invite;
 pub mod recover;
+pub mod rotate_keys;
 pub mod sign_up;
 pub mod vault;
@@ -1,5 +1,8 @@
 use crate::node::common::model::meta_pass::MetaPasswordId;
-use crate::node::common::model::secret::{ClaimId, SecretDistributionData, SsDistributionId};
+use crate::node::common::model::secret::{
+    ClaimId, RecoveryTargets, SecretDistributionType, SsCancelClaimData, SsDistributionId,
+    SsVetoData,
+};
 use crate::node::common::model::user::user_creds::UserCreds;
 use crate::node::common::model::vault::vault::VaultStatus;
 use crate::node::db::descriptors::shared_secret_descriptor::SsWorkflowDescriptor;
@@ -7,12 +10,16 @@ use crate::node::db::objects::persistent_object::PersistentObject;
 use crate::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
 use crate::node::db::objects::persistent_vault::PersistentVault;
 use crate::node::db::repo::generic_db::KvLogEventRepo;
-use crate::recover_from_shares;
+use crate::node::db::descriptors::object_descriptor::ObjectDescriptor;
+use crate::node::db::events::generic_log_event::KeyExtractor;
+use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
+use crate::node::db::events::shared_secret_event::SsWorkflowObject;
+use crate::secret::share_verification::{HeldShare, ShareVerifier, VerifiedRecovery};
 use crate::secret::shared_secret::UserShareDto;
-use crate::PlainText;
 use anyhow::bail;
 use derive_more::From;
 use std::sync::Arc;
+use tracing::warn;
 use tracing_attributes::instrument;
 
 #[derive(From)]
@@ -21,12 +28,13 @@ pub struct RecoveryAction<Repo: KvLogEventRepo> {
 }
 
 impl<Repo: KvLogEventRepo> RecoveryAction<Repo> {
-    /// Send recover request to all vault members except current user
+    /// Send recover request to the target devices, by default to all vault members except current user
     #[instrument(skip_all)]
     pub async fn recovery_request(
         &self,
         user_creds: UserCreds,
         pass_id: MetaPasswordId,
+        targets: RecoveryTargets,
     ) -> anyhow::Result<()> {
         let vault_repo = PersistentVault::from(self.p_obj.clone());
 
@@ -45,7 +53,7 @@ impl<Repo: KvLogEventRepo> RecoveryAction<Repo> {
                     .await?
                     .to_data()
                     .to_vault_member(member)?;
-                let claim = vault_member.create_recovery_claim(pass_id);
+                let claim = vault_member.create_targeted_recovery_claim(pass_id, targets)?;
 
                 let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
                 p_ss.save_claim_in_ss_device_log(claim).await?;
@@ -54,6 +62,89 @@ impl<Repo: KvLogEventRepo> RecoveryAction<Repo> {
 
         Ok(())
     }
+
+    /// Withdraw a recovery request of the current user, sync will push the cancellation to the server
+    #[instrument(skip_all)]
+    pub async fn cancel_recovery_request(
+        &self,
+        user_creds: UserCreds,
+        claim_id: ClaimId,
+    ) -> anyhow::Result<()> {
+        let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+        let ss_log_data = p_ss.get_ss_log_obj(user_creds.vault_name.clone()).await?;
+
+        let Some(claim) = ss_log_data.claims.get(&claim_id) else {
+            bail!("Claim not found: {:?}", claim_id);
+        };
+
+        if claim.distribution_type != SecretDistributionType::Recover {
+            bail!("Only recovery claims can be cancelled: {:?}", claim_id);
+        }
+
+        if !claim.sender.eq(user_creds.device_id()) {
+            bail!("Only the sender can cancel the claim: {:?}", claim_id);
+        }
+
+        let cancel_data = SsCancelClaimData {
+            vault_name: claim.vault_name.clone(),
+            claim_id: claim.dist_claim_id.clone(),
+            sender: claim.sender.clone(),
+        };
+        let key = KvKey::from(SsWorkflowDescriptor::CancelClaim(
+            claim.dist_claim_id.clone(),
+        ));
+        let cancel_wf = SsWorkflowObject::CancelClaim(KvLogEvent::new(key, cancel_data));
+        self.p_obj.repo.save(cancel_wf).await?;
+
+        Ok(())
+    }
+
+    /// Stop the escrow device from releasing its share of a break-glass recovery,
+    /// sync will push the veto to the server
+    #[instrument(skip_all)]
+    pub async fn veto_recovery_request(
+        &self,
+        user_creds: UserCreds,
+        claim_id: ClaimId,
+    ) -> anyhow::Result<()> {
+        let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+        let ss_log_data = p_ss.get_ss_log_obj(user_creds.vault_name.clone()).await?;
+
+        let Some(claim) = ss_log_data.claims.get(&claim_id) else {
+            bail!("Claim not found: {:?}", claim_id);
+        };
+
+        if claim.distribution_type != SecretDistributionType::Recover {
+            bail!("Only recovery claims can be vetoed: {:?}", claim_id);
+        }
+
+        if claim.break_glass.is_none() {
+            bail!("The claim is not a break-glass recovery: {:?}", claim_id);
+        }
+
+        if claim.sender.eq(user_creds.device_id()) {
+            bail!(
+                "The sender can't veto its own claim, cancel it instead: {:?}",
+                claim_id
+            );
+        }
+
+        let veto_data = SsVetoData {
+            vault_name: claim.vault_name.clone(),
+            claim_id: claim.dist_claim_id.clone(),
+            device_id: user_creds.device_id().clone(),
+        };
+        let key = KvKey::from(SsWorkflowDescriptor::Veto(claim.dist_claim_id.clone()));
+        let veto_wf = SsWorkflowObject::Veto(KvLogEvent::new(key, veto_data));
+        self.p_obj.repo.save(veto_wf).await?;
+
+        // the members see the veto right away, the server's log replaces this view on the next sync
+        let mut local_view = claim.clone();
+        local_view.veto(user_creds.device_id().clone());
+        p_ss.save_local_ss_log_event(local_view).await?;
+
+        Ok(())
+    }
 }
 
 /// Recovers secret from local shares on the client side
@@ -69,13 +160,13 @@ impl<Repo: KvLogEventRepo> RecoveryHandler<Repo> {
         user_creds: UserCreds,
         claim_id: ClaimId,
         pass_id: MetaPasswordId,
-    ) -> anyhow::Result<PlainText> {
+    ) -> anyhow::Result<VerifiedRecovery> {
         // Create PersistentSharedSecret to access shared secret data
         let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
 
         // 2. Get the SS log to find the claim
         let vault_name = user_creds.vault_name.clone();
-        let ss_log_data = p_ss.get_ss_log_obj(vault_name).await?;
+        let ss_log_data = p_ss.get_ss_log_obj(vault_name.clone()).await?;
 
         // Find the claim using the ID in the recovery_id
         let claim = ss_log_data
@@ -93,59 +184,75 @@ impl<Repo: KvLogEventRepo> RecoveryHandler<Repo> {
         });
         let maybe_dist = self.p_obj.find_tail_event(desc).await?;
 
-        // Extract all SecretDistributionData objects from recoveries and dists
-        let recovery_data: Vec<SecretDistributionData> = recoveries
-            .into_iter()
-            .map(|r| r.to_distribution_data())
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let distribution_data: Vec<SecretDistributionData> = maybe_dist
-            .map(|dist| dist.to_distribution_data())
-            .transpose()?
-            .into_iter()
-            .collect();
-
-        if recovery_data.is_empty() && distribution_data.is_empty() {
+        if recoveries.is_empty() && maybe_dist.is_none() {
             bail!("No recovery shares found for selected claim");
         }
 
         // Decrypt the secret shares using the transport key
         let transport_sk = &user_creds.device_creds.secret_box.transport.sk;
 
-        // Prepare vectors to collect all shares
-        let mut user_shares = Vec::new();
+        // Prepare vectors to collect all shares along with the devices that hold them
+        let mut held_shares = Vec::new();
 
         // Process recovery shares
-        for data in recovery_data {
+        for recovery in recoveries {
+            let ObjectDescriptor::SharedSecret(SsWorkflowDescriptor::Recovery(recovery_id)) =
+                recovery.key().obj_desc
+            else {
+                bail!("Invalid recovery event: {:?}", recovery.key());
+            };
+            let data = recovery.to_distribution_data()?;
             let decrypted = data.secret_message.cipher_text().decrypt(transport_sk)?;
-            let share = UserShareDto::try_from(&decrypted.msg)?;
-            user_shares.push(share);
+            held_shares.push(HeldShare {
+                holder: recovery_id.distribution_id.receiver,
+                share: UserShareDto::try_from(&decrypted.msg)?,
+            });
         }
 
         // Process distribution shares
-        for data in distribution_data {
+        if let Some(dist) = maybe_dist {
+            let data = dist.to_distribution_data()?;
             let decrypted = data.secret_message.cipher_text().decrypt(transport_sk)?;
-            let share = UserShareDto::try_from(&decrypted.msg)?;
-            user_shares.push(share);
+            held_shares.push(HeldShare {
+                holder: user_creds.device_id().clone(),
+                share: UserShareDto::try_from(&decrypted.msg)?,
+            });
         }
 
-        // Recover the secret using the collected shares
-        let plain_text = recover_from_shares(user_shares)?;
+        // Recover the secret using the valid shares only,
+        // the devices that have sent invalid shares are reported to the caller
+        let vault = PersistentVault::from(self.p_obj.clone())
+            .get_vault(vault_name)
+            .await?
+            .to_data();
+        let verifier = ShareVerifier::new(user_creds.device_id().clone(), &vault.members());
+        let recovery = verifier.recover(held_shares)?;
+        if !recovery.invalid.is_empty() {
+            warn!(
+                "Invalid shares have been discarded, sent by: {:?}",
+                recovery.invalid
+            );
+        }
 
-        Ok(plain_text)
+        Ok(recovery)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::RecoveryHandler;
+    use super::{RecoveryAction, RecoveryHandler};
     use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
     use crate::node::common::model::crypto::aead::EncryptedMessage;
     use crate::node::common::model::meta_pass::MetaPasswordId;
-    use crate::node::common::model::secret::{SecretDistributionData, SsDistributionId};
+    use crate::node::common::model::secret::{
+        BreakGlass, BreakGlassPolicy, DEFAULT_BREAK_GLASS_DELAY, SecretDistributionData,
+        SsDistributionId,
+    };
+    use crate::node::common::model::user::common::UserDataMember;
     use crate::node::db::descriptors::shared_secret_descriptor::SsWorkflowDescriptor;
     use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
     use crate::node::db::events::shared_secret_event::SsWorkflowObject;
+    use crate::node::db::events::vault::vault_event::VaultObject;
     use crate::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
     use crate::node::db::repo::generic_db::SaveCommand;
     use crate::secret::data_block::common::SharedSecretConfig;
@@ -182,30 +289,41 @@ mod tests {
         let sender_pk = user_creds.device_creds.device.keys.transport_pk();
         let sender_km = user_creds.device_creds.key_manager()?;
 
-        for (share_index, recovery_id) in claim.recovery_db_ids().into_iter().enumerate() {
-            let share_json = shared_secret.get_share(share_index).as_json()?;
+        // the shares are verified against the commitments signed by the member
+        let member = UserDataMember::from(user_creds.user());
+        p_obj
+            .repo
+            .save(VaultObject::sign_up(user_creds.vault_name.clone(), member))
+            .await?;
+        let mut shares = shared_secret.get_shares(cfg.number_of_shares)?;
+        for share in shares.iter_mut() {
+            share.sign_commitments(user_creds.device_id().clone(), &sender_km.dsa)?;
+        }
+
+        for (share, recovery_id) in shares.iter().zip(claim.recovery_db_ids()) {
+            let share_json = share.as_json()?;
             let encrypted = sender_km
                 .transport
                 .encrypt_string(PlainText::from(share_json), &sender_pk)?;
 
-            let wf_event = SsWorkflowObject::Recovery(KvLogEvent {
-                key: KvKey::from(SsWorkflowDescriptor::Recovery(recovery_id.clone())),
-                value: SecretDistributionData {
+            let wf_event = SsWorkflowObject::Recovery(KvLogEvent::new(
+                KvKey::from(SsWorkflowDescriptor::Recovery(recovery_id.clone())),
+                SecretDistributionData {
                     vault_name: user_creds.vault_name.clone(),
                     claim_id: recovery_id.claim_id,
                     secret_message: EncryptedMessage::CipherShare { share: encrypted },
                 },
-            });
+            ));
 
             p_obj.repo.save(wf_event).await?;
         }
 
         let recovery = RecoveryHandler { p_obj };
-        let plain = recovery
+        let recovered = recovery
             .recover(user_creds, claim.id, pass_id)
             .await?;
 
-        assert_eq!(plain.text, "2bee|~");
+        assert_eq!(recovered.secret.text, "2bee|~");
         Ok(())
     }
 
@@ -244,4 +362,103 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn cancel_recovery_request_saves_cancellation_of_own_claim() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let user_creds = fixture.state.user_creds.client.clone();
+        let p_obj = fixture.state.p_obj.client.clone();
+        let p_ss = PersistentSharedSecret::from(p_obj.clone());
+        let vault_member = fixture.state.vault_data.client_vault_member.clone();
+
+        let pass_id = MetaPasswordId::build_from_str("cancel_own_claim");
+        let claim = vault_member.create_recovery_claim(pass_id);
+        p_ss.save_ss_log_event(claim.clone()).await?;
+
+        let action = RecoveryAction::from(p_obj.clone());
+        action
+            .cancel_recovery_request(user_creds, claim.id.clone())
+            .await?;
+
+        let Some(SsWorkflowObject::CancelClaim(cancel_event)) =
+            p_ss.get_cancellation(claim.clone()).await?
+        else {
+            panic!("Cancellation must be saved");
+        };
+        assert_eq!(cancel_event.value.claim_id, claim.dist_claim_id);
+        assert_eq!(cancel_event.value.sender, claim.sender);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cancel_recovery_request_rejects_claims_of_other_devices() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let p_obj = fixture.state.p_obj.client.clone();
+        let p_ss = PersistentSharedSecret::from(p_obj.clone());
+        let vd_member = fixture.state.vault_data.vd_vault_member.clone();
+
+        let claim = vd_member.create_recovery_claim(MetaPasswordId::build_from_str("foreign"));
+        p_ss.save_ss_log_event(claim.clone()).await?;
+
+        let action = RecoveryAction::from(p_obj);
+        let err = action
+            .cancel_recovery_request(fixture.state.user_creds.client.clone(), claim.id.clone())
+            .await
+            .expect_err("A device can't cancel someone else's claim");
+        assert!(
+            err.to_string()
+                .contains("Only the sender can cancel the claim")
+        );
+
+        assert!(p_ss.get_cancellation(claim).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn veto_recovery_request_stops_break_glass_claims_only() -> Result<()> {
+        let fixture = FixtureRegistry::empty();
+        let user_creds = fixture.state.user_creds.client.clone();
+        let p_obj = fixture.state.p_obj.client.clone();
+        let p_ss = PersistentSharedSecret::from(p_obj.clone());
+        let vd_member = fixture.state.vault_data.vd_vault_member.clone();
+
+        let regular_claim =
+            vd_member.create_recovery_claim(MetaPasswordId::build_from_str("regular"));
+        p_ss.save_ss_log_event(regular_claim.clone()).await?;
+
+        let mut break_glass_claim =
+            vd_member.create_recovery_claim(MetaPasswordId::build_from_str("escrowed"));
+        break_glass_claim.break_glass = Some(BreakGlass::from(BreakGlassPolicy {
+            escrow: fixture.state.user_creds.client_b.device_id().clone(),
+            delay: DEFAULT_BREAK_GLASS_DELAY,
+        }));
+        p_ss.save_ss_log_event(break_glass_claim.clone()).await?;
+
+        let action = RecoveryAction::from(p_obj);
+        let err = action
+            .veto_recovery_request(user_creds.clone(), regular_claim.id.clone())
+            .await
+            .expect_err("Only break-glass claims can be vetoed");
+        assert!(err.to_string().contains("not a break-glass recovery"));
+        assert!(p_ss.get_veto(regular_claim).await?.is_none());
+
+        action
+            .veto_recovery_request(user_creds.clone(), break_glass_claim.id.clone())
+            .await?;
+
+        let Some(SsWorkflowObject::Veto(veto_event)) =
+            p_ss.get_veto(break_glass_claim.clone()).await?
+        else {
+            panic!("Veto must be saved");
+        };
+        assert_eq!(veto_event.value.claim_id, break_glass_claim.dist_claim_id);
+        assert_eq!(&veto_event.value.device_id, user_creds.device_id());
+
+        // the member sees its veto before the next sync
+        let ss_log = p_ss.get_ss_log_obj(user_creds.vault_name.clone()).await?;
+        assert!(ss_log.claims[&break_glass_claim.id].is_vetoed());
+
+        Ok(())
+    }
 }
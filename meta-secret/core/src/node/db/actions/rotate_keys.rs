@@ -0,0 +1,452 @@
+use crate::crypto::keys::{TransportPk, TransportSk};
+use crate::node::common::model::crypto::aead::EncryptedMessage;
+use crate::node::common::clock::unix_time_millis;
+use crate::node::common::model::IdString;
+use crate::node::common::model::secret::{
+    ClaimId, SecretDistributionData, SecretDistributionType, SsDistributionId,
+    SsDistributionStatus,
+};
+use crate::node::common::model::user::user_creds::UserCreds;
+use crate::node::common::model::vault::vault::VaultStatus;
+use crate::node::common::model::vault::vault_data::VaultData;
+use crate::node::db::descriptors::shared_secret_descriptor::SsWorkflowDescriptor;
+use crate::node::db::events::generic_log_event::ObjIdExtractor;
+use crate::node::db::events::kv_log_event::KvLogEvent;
+use crate::node::db::events::shared_secret_event::SsWorkflowObject;
+use crate::node::db::events::vault::vault_log_event::RotateDeviceKeysEvent;
+use crate::node::db::objects::persistent_device_log::PersistentDeviceLog;
+use crate::node::db::objects::persistent_object::PersistentObject;
+use crate::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
+use crate::node::db::objects::persistent_vault::PersistentVault;
+use crate::node::db::repo::persistent_credentials::PersistentCredentials;
+use crate::node::db::repo::generic_db::KvLogEventRepo;
+use anyhow::{Result, bail};
+use derive_more::From;
+use std::sync::Arc;
+use tracing::info;
+use tracing_attributes::instrument;
+
+/// Replaces the keys of the device. The vault members learn the new keys from
+/// a [`RotateDeviceKeysEvent`] signed with the old dsa key, and the shares kept on the device
+/// are re-encrypted to the new transport key, the old keys are gone after the rotation.
+/// The shares still on the way to the device are encrypted to the old key, so the keys
+/// can't be rotated until they have arrived (the device syncs before the rotation).
+/// The new keys are saved before anything else happens, an interrupted rotation
+/// is finished with the same keys the next time
+#[derive(From)]
+pub struct KeyRotationAction<Repo: KvLogEventRepo> {
+    pub p_obj: Arc<PersistentObject<Repo>>,
+}
+
+impl<Repo: KvLogEventRepo> KeyRotationAction<Repo> {
+    /// Returns the credentials with the new keys, they are the current credentials of the device
+    #[instrument(skip_all)]
+    pub async fn rotate(
+        &self,
+        creds_repo: &PersistentCredentials<Repo>,
+        user_creds: UserCreds,
+    ) -> Result<UserCreds> {
+        let p_vault = PersistentVault::from(self.p_obj.clone());
+        let VaultStatus::Member(member) = p_vault.find(user_creds.user()).await? else {
+            bail!("Only a vault member can rotate the keys of the device");
+        };
+        let vault = p_vault
+            .get_vault(member.user().vault_name())
+            .await?
+            .to_data();
+
+        let rotated_creds = match creds_repo.get_pending_user_creds().await? {
+            Some(pending) if pending.device_id() == user_creds.device_id() => {
+                info!("Resuming an interrupted key rotation");
+                pending
+            }
+            _ => {
+                self.check_no_pending_shares(&user_creds).await?;
+
+                let rotated_creds = UserCreds {
+                    vault_name: user_creds.vault_name.clone(),
+                    device_creds: user_creds.device_creds.rotate_keys(),
+                    vault_label: user_creds.vault_label.clone(),
+                };
+                creds_repo
+                    .save_pending_user_creds(rotated_creds.clone())
+                    .await?;
+                rotated_creds
+            }
+        };
+
+        // the swap has been interrupted after the current credentials got the new keys
+        if rotated_creds.device().keys == user_creds.device().keys {
+            creds_repo.replace_user_creds(rotated_creds.clone()).await?;
+            return Ok(rotated_creds);
+        }
+
+        let old_key_manager = user_creds.device_creds.key_manager()?;
+        let rotation = RotateDeviceKeysEvent::sign(
+            member,
+            rotated_creds.device().keys,
+            &old_key_manager.dsa,
+        )?;
+        let p_device_log = PersistentDeviceLog::from(self.p_obj.clone());
+        p_device_log
+            .save_rotate_device_keys_request(rotation)
+            .await?;
+
+        let old_sk = old_key_manager.transport.sk();
+        let new_keys = rotated_creds.device().keys;
+        self.re_encrypt_shares(&user_creds, &vault, &old_sk, new_keys.transport_pk())
+            .await?;
+
+        creds_repo.replace_user_creds(rotated_creds.clone()).await?;
+        Ok(rotated_creds)
+    }
+
+    /// Split shares sent to the device and the shares of its own recovery claims
+    /// that haven't been delivered yet would be lost with the old key
+    async fn check_no_pending_shares(&self, user_creds: &UserCreds) -> Result<()> {
+        let device_id = user_creds.device_id();
+        let now = unix_time_millis();
+        let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+        let ss_log = p_ss.get_ss_log_obj(user_creds.vault_name.clone()).await?;
+
+        let mut pending_claims: Vec<ClaimId> = ss_log
+            .claims
+            .into_values()
+            .filter(|claim| !claim.is_expired(now))
+            .filter(|claim| {
+                let is_on_the_way = |status: &SsDistributionStatus| {
+                    matches!(
+                        status,
+                        SsDistributionStatus::Pending
+                            | SsDistributionStatus::Sent
+                            | SsDistributionStatus::Approved
+                    )
+                };
+
+                match claim.distribution_type {
+                    SecretDistributionType::Split => {
+                        claim.status.get(device_id).is_some_and(is_on_the_way)
+                    }
+                    SecretDistributionType::Recover => {
+                        claim.sender.eq(device_id)
+                            && claim.status.statuses.values().any(is_on_the_way)
+                    }
+                }
+            })
+            .map(|claim| claim.id)
+            .collect();
+
+        if !pending_claims.is_empty() {
+            pending_claims.sort_by_key(|claim_id| claim_id.0.clone().id_str());
+            bail!(
+                "Shares of the claims {:?} are still on the way to the device, \
+                rotate the keys when they have arrived",
+                pending_claims
+            );
+        }
+        Ok(())
+    }
+
+    async fn re_encrypt_shares(
+        &self,
+        user_creds: &UserCreds,
+        vault: &VaultData,
+        old_sk: &TransportSk,
+        new_pk: &TransportPk,
+    ) -> Result<()> {
+        let mut shares: Vec<SsWorkflowObject> = vec![];
+
+        // the shares of the device and the shares the device has sent to the other members
+        for pass_id in vault.secrets.iter() {
+            for member in vault.members() {
+                let desc = SsWorkflowDescriptor::Distribution(SsDistributionId {
+                    pass_id: pass_id.opaque(),
+                    receiver: member.user().device.device_id.clone(),
+                });
+                if let Some(share) = self.p_obj.find_tail_event(desc).await? {
+                    shares.push(share);
+                }
+            }
+        }
+
+        // the shares received for the recovery claims of the device
+        let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+        let ss_log = p_ss.get_ss_log_obj(user_creds.vault_name.clone()).await?;
+        for claim in ss_log.claims.into_values() {
+            let is_own_recovery = claim.distribution_type == SecretDistributionType::Recover
+                && claim.sender.eq(user_creds.device_id());
+            if is_own_recovery {
+                shares.extend(p_ss.get_recoveries(claim).await?);
+            }
+        }
+
+        let mut re_encrypted = 0;
+        for share in shares {
+            let maybe_rotated = match share {
+                SsWorkflowObject::Distribution(event) => {
+                    rotate_share(event, old_sk, new_pk)?.map(SsWorkflowObject::Distribution)
+                }
+                SsWorkflowObject::Recovery(event) => {
+                    rotate_share(event, old_sk, new_pk)?.map(SsWorkflowObject::Recovery)
+                }
+                SsWorkflowObject::Decline(_)
+                | SsWorkflowObject::Approval(_)
+                | SsWorkflowObject::CancelClaim(_)
+                | SsWorkflowObject::Veto(_) => None,
+            };
+
+            if let Some(rotated) = maybe_rotated {
+                // workflow events have fixed keys, the share is replaced in place
+                self.p_obj.repo.delete(rotated.obj_id()).await;
+                self.p_obj.repo.save(rotated).await?;
+                re_encrypted += 1;
+            }
+        }
+
+        info!(
+            "{} shares have been re-encrypted to the new key",
+            re_encrypted
+        );
+        Ok(())
+    }
+}
+
+/// Re-encrypts the share if it's encrypted to the old key
+fn rotate_share(
+    mut event: KvLogEvent<SecretDistributionData>,
+    old_sk: &TransportSk,
+    new_pk: &TransportPk,
+) -> Result<Option<KvLogEvent<SecretDistributionData>>> {
+    let cipher_text = event.value.secret_message.cipher_text();
+    if !cipher_text.channel.contains(&old_sk.pk()?) {
+        return Ok(None);
+    }
+
+    let share = cipher_text.rotate(old_sk, new_pk)?;
+    event.value.secret_message = EncryptedMessage::CipherShare { share };
+    Ok(Some(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+    use crate::node::common::model::meta_pass::{MetaPasswordId, PlainPassInfo, SecurePassInfo};
+    use crate::node::common::model::user::common::{UserDataMember, UserMembership};
+    use crate::node::common::model::vault::vault::VaultMember;
+    use crate::node::db::descriptors::creds::DeviceCredsDescriptor;
+    use crate::node::db::descriptors::vault_descriptor::{
+        DeviceLogDescriptor, VaultStatusDescriptor,
+    };
+    use crate::node::db::events::object_id::ArtifactId;
+    use crate::node::db::events::vault::vault_event::VaultObject;
+    use crate::node::db::events::vault::vault_log_event::{
+        VaultActionEvent, VaultActionRequestEvent,
+    };
+    use crate::node::db::events::vault::vault_status::VaultStatusObject;
+    use crate::node::db::repo::generic_db::{DeleteCommand, SaveCommand};
+    use crate::secret::MetaDistributor;
+
+    #[tokio::test]
+    async fn test_rotation_keeps_device_id_and_re_encrypts_shares() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let user_creds = registry.state.user_creds.client.clone();
+        let p_obj = registry.state.p_obj.client.clone();
+        let user = user_creds.user();
+        let member = UserDataMember::from(user.clone());
+
+        let pass_id = MetaPasswordId::build_from_str("rotated_secret");
+        let mut vault_obj = VaultObject::sign_up(user.vault_name(), member.clone());
+        vault_obj.0.value = vault_obj.0.value.add_secret(pass_id.clone());
+        let vault = vault_obj.clone().to_data();
+        p_obj.repo.save(vault_obj).await?;
+
+        let status_desc = VaultStatusDescriptor::from(user.user_id());
+        let status = VaultStatus::Member(member.clone());
+        let status_obj = VaultStatusObject::new(status, ArtifactId::from(status_desc));
+        p_obj.repo.save(status_obj).await?;
+
+        let vault_member = VaultMember { member, vault };
+        let distributor = MetaDistributor {
+            p_obj: p_obj.clone(),
+            user_creds: Arc::new(user_creds.clone()),
+            vault_member: vault_member.clone(),
+        };
+        let pass_info = PlainPassInfo {
+            pass_id: pass_id.clone(),
+            pass: "2bee|~".to_string(),
+            recovery_policy: None,
+            break_glass: None,
+        };
+        distributor
+            .distribute(vault_member, SecurePassInfo::from(pass_info))
+            .await?;
+
+        let share_desc = SsWorkflowDescriptor::Distribution(SsDistributionId {
+            pass_id,
+            receiver: user_creds.device_id().clone(),
+        });
+        let old_share: SsWorkflowObject = p_obj.find_tail_event(share_desc.clone()).await?.unwrap();
+
+        let creds_repo = PersistentCredentials {
+            p_obj: p_obj.clone(),
+            master_key: registry.state.device_creds.client_master_key.clone(),
+        };
+        creds_repo.save_device_creds(user_creds.device_creds.clone()).await?;
+        creds_repo.save_user_creds(user_creds.clone()).await?;
+
+        let action = KeyRotationAction::from(p_obj.clone());
+        let rotated_creds = action.rotate(&creds_repo, user_creds.clone()).await?;
+
+        assert_eq!(rotated_creds.device_id(), user_creds.device_id());
+        assert_ne!(rotated_creds.device().keys, user_creds.device().keys);
+        assert_eq!(Some(rotated_creds.clone()), creds_repo.get_user_creds().await?);
+        assert_eq!(
+            Some(rotated_creds.device_creds.clone()),
+            creds_repo.get_device_creds().await?
+        );
+        assert!(creds_repo.get_pending_user_creds().await?.is_none());
+
+        let new_share: SsWorkflowObject = p_obj.find_tail_event(share_desc).await?.unwrap();
+        assert_eq!(old_share.obj_id(), new_share.obj_id());
+
+        let new_sk = rotated_creds.device_creds.key_manager()?.transport.sk();
+        let old_sk = user_creds.device_creds.key_manager()?.transport.sk();
+        let share = new_share.to_distribution_data()?.secret_message;
+        assert!(share.cipher_text().decrypt(&new_sk).is_ok());
+        assert!(share.cipher_text().decrypt(&old_sk).is_err());
+
+        // the members receive the new keys signed by the old key
+        let rotation = p_obj
+            .get_object_events_from_beginning(DeviceLogDescriptor::from(user_creds.user_id()))
+            .await?
+            .into_iter()
+            .find_map(|event| match event.0.value {
+                VaultActionEvent::Request(VaultActionRequestEvent::RotateDeviceKeys(rotation)) => {
+                    Some(rotation)
+                }
+                _ => None,
+            })
+            .expect("Rotation request must be saved to the device log");
+        rotation.verify()?;
+        assert_eq!(rotation.rotated_member().user_data, rotated_creds.user());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_rotation_keeps_the_keys() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let user_creds = registry.state.user_creds.client.clone();
+        let p_obj = registry.state.p_obj.client.clone();
+        let user = user_creds.user();
+        let member = UserDataMember::from(user.clone());
+
+        p_obj
+            .repo
+            .save(VaultObject::sign_up(user.vault_name(), member.clone()))
+            .await?;
+        let status_desc = VaultStatusDescriptor::from(user.user_id());
+        let status_obj =
+            VaultStatusObject::new(VaultStatus::Member(member), ArtifactId::from(status_desc));
+        p_obj.repo.save(status_obj).await?;
+
+        let creds_repo = PersistentCredentials {
+            p_obj: p_obj.clone(),
+            master_key: registry.state.device_creds.client_master_key.clone(),
+        };
+        creds_repo.save_device_creds(user_creds.device_creds.clone()).await?;
+        creds_repo.save_user_creds(user_creds.clone()).await?;
+
+        // the device stopped right after the new keys had been saved
+        let pending_creds = UserCreds {
+            device_creds: user_creds.device_creds.rotate_keys(),
+            ..user_creds.clone()
+        };
+        creds_repo
+            .save_pending_user_creds(pending_creds.clone())
+            .await?;
+        assert_eq!(Some(user_creds.clone()), creds_repo.get_user_creds().await?);
+        assert_eq!(
+            Some(user_creds.device_creds.clone()),
+            creds_repo.get_device_creds().await?
+        );
+
+        // the current credentials are gone in the middle of a swap, the pending ones are used
+        p_obj
+            .repo
+            .delete(ArtifactId::from(DeviceCredsDescriptor))
+            .await;
+        assert_eq!(
+            Some(pending_creds.device_creds.clone()),
+            creds_repo.get_device_creds().await?
+        );
+        creds_repo.save_device_creds(user_creds.device_creds.clone()).await?;
+
+        // the next rotation finishes the interrupted one with the same keys
+        let action = KeyRotationAction::from(p_obj.clone());
+        let rotated_creds = action.rotate(&creds_repo, user_creds.clone()).await?;
+        assert_eq!(rotated_creds, pending_creds);
+        assert_eq!(Some(pending_creds), creds_repo.get_user_creds().await?);
+        assert!(creds_repo.get_pending_user_creds().await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rotation_waits_for_the_shares_on_the_way() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let user_creds = registry.state.user_creds.client.clone();
+        let p_obj = registry.state.p_obj.client.clone();
+        let user = user_creds.user();
+        let member = UserDataMember::from(user.clone());
+        let vd_member = registry.state.vault_data.vd_membership.user_data_member();
+
+        let pass_id = MetaPasswordId::build_from_str("pending_secret");
+        let mut vault_obj = VaultObject::sign_up(user.vault_name(), member.clone());
+        vault_obj.0.value = vault_obj
+            .0
+            .value
+            .update_membership(UserMembership::Member(vd_member.clone()))
+            .add_secret(pass_id.clone());
+        let vault = vault_obj.clone().to_data();
+        p_obj.repo.save(vault_obj).await?;
+
+        let status_desc = VaultStatusDescriptor::from(user.user_id());
+        let status_obj =
+            VaultStatusObject::new(VaultStatus::Member(member), ArtifactId::from(status_desc));
+        p_obj.repo.save(status_obj).await?;
+
+        let creds_repo = PersistentCredentials {
+            p_obj: p_obj.clone(),
+            master_key: registry.state.device_creds.client_master_key.clone(),
+        };
+        creds_repo.save_device_creds(user_creds.device_creds.clone()).await?;
+        creds_repo.save_user_creds(user_creds.clone()).await?;
+
+        // the virtual device has split a password, the share of the client is on the server
+        let vd_vault_member = VaultMember {
+            member: vd_member,
+            vault,
+        };
+        let mut claim = vd_vault_member.create_split_claim(pass_id);
+        claim.status = claim.status.sent(user_creds.device_id().clone());
+        let p_ss = PersistentSharedSecret::from(p_obj.clone());
+        p_ss.save_ss_log_event(claim.clone()).await?;
+
+        let action = KeyRotationAction::from(p_obj.clone());
+        let err = action.rotate(&creds_repo, user_creds.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("still on the way"));
+        assert_eq!(Some(user_creds.clone()), creds_repo.get_user_creds().await?);
+        assert!(creds_repo.get_pending_user_creds().await?.is_none());
+
+        // the share has arrived
+        claim.status = claim.status.complete(user_creds.device_id().clone());
+        p_ss.save_ss_log_event(claim).await?;
+
+        let rotated_creds = action.rotate(&creds_repo, user_creds.clone()).await?;
+        assert_ne!(rotated_creds.device().keys, user_creds.device().keys);
+
+        Ok(())
+    }
+}
@@ -1,11 +1,25 @@
 use crate::node::common::model::user::common::UserDataMember;
+use crate::node::common::model::vault::vault_data::DEFAULT_JOIN_QUORUM;
 use crate::node::db::events::generic_log_event::{GenericKvLogEvent, ToGenericEvent};
 use crate::node::db::events::vault::vault_event::VaultObject;
 use crate::node::db::events::vault::vault_log_event::VaultLogObject;
 use tracing::info;
 use tracing_attributes::instrument;
 
-pub struct SignUpAction;
+/// Creates a vault with the settings it starts with
+pub struct SignUpAction {
+    pub join_quorum: usize,
+    pub require_invite: bool,
+}
+
+impl Default for SignUpAction {
+    fn default() -> Self {
+        Self {
+            join_quorum: DEFAULT_JOIN_QUORUM,
+            require_invite: false,
+        }
+    }
+}
 
 impl SignUpAction {
     #[instrument(skip(self))]
@@ -17,7 +31,12 @@ impl SignUpAction {
         let vault_log_event = VaultLogObject::create(candidate.clone()).to_generic();
 
         let vault_event = {
-            let vault_event = VaultObject::sign_up(vault_name.clone(), candidate);
+            let mut vault_event = VaultObject::sign_up(vault_name.clone(), candidate);
+            vault_event.0.value = vault_event
+                .0
+                .value
+                .with_join_quorum(self.join_quorum)
+                .with_require_invite(self.require_invite);
             vault_event.to_generic()
         };
 
@@ -44,7 +63,7 @@ mod test {
         let device_creds = &DeviceCredentialsFixture::from_km(km);
         let user_creds_fixture = UserCredentialsFixture::from(device_creds);
 
-        let sign_up_action = SignUpAction;
+        let sign_up_action = SignUpAction::default();
         let user_data_member = UserDataMember::from(user_creds_fixture.client.user());
         let events = sign_up_action.accept(user_data_member.clone());
 
@@ -1,9 +1,10 @@
 use std::sync::Arc;
 
-use crate::node::common::model::device::common::DeviceName;
+use crate::node::common::model::device::common::{DeviceName, DeviceType};
 use crate::node::common::model::user::common::{UserData, UserDataOutsiderStatus};
 use crate::node::common::model::user::user_creds::UserCreds;
 use crate::node::common::model::vault::vault::VaultName;
+use crate::node::db::events::vault::vault_log_event::JoinClusterEvent;
 use crate::node::db::repo::persistent_credentials::PersistentCredentials;
 use crate::node::{
     common::model::vault::vault::VaultStatus,
@@ -38,7 +39,7 @@ impl<Repo: KvLogEventRepo> SignUpClaim<Repo> {
             master_key
         };
         creds_repo
-            .get_or_generate_user_creds(device_name, vault_name)
+            .get_or_generate_labeled_user_creds(device_name, DeviceType::other(), vault_name)
             .await
     }
 
@@ -59,7 +60,8 @@ impl<Repo: KvLogEventRepo> SignUpClaim<Repo> {
             VaultStatus::Outsider(outsider) => match outsider.status {
                 UserDataOutsiderStatus::NonMember => {
                     info!("Save Join request");
-                    p_device_log.save_join_request(&outsider.user_data).await?;
+                    let join_request = JoinClusterEvent::from(outsider.user_data.clone());
+                    p_device_log.save_join_request(join_request).await?;
                 }
                 UserDataOutsiderStatus::Pending => {
                     info!("Device is pending")
@@ -1,3 +1,4 @@
+use crate::crypto::key_pair::DsaKeyPair;
 use crate::node::common::model::user::common::{
     UserDataMember, UserDataOutsider, UserDataOutsiderStatus, UserMembership,
 };
@@ -29,7 +30,8 @@ impl<Repo: KvLogEventRepo> JoinAction<Repo> {
         &self,
         join_request: JoinClusterEvent,
         upd: JoinActionUpdate,
-    ) -> Result<()> {
+        dsa: &DsaKeyPair,
+    ) -> Result<UpdateMembershipEvent> {
         let candidate_membership = self.member.vault.membership(join_request.candidate.clone());
         let p_device_log = PersistentDeviceLog::from(self.p_obj.clone());
 
@@ -46,15 +48,17 @@ impl<Repo: KvLogEventRepo> JoinAction<Repo> {
                         }),
                     };
 
-                    let update_event = UpdateMembershipEvent {
-                        request: join_request,
-                        sender: self.member.member.clone(),
+                    let update_event = UpdateMembershipEvent::sign(
+                        join_request,
+                        self.member.member.clone(),
                         update,
-                    };
+                        dsa,
+                    )?;
 
                     p_device_log
-                        .save_updated_membership_event(update_event)
-                        .await
+                        .save_updated_membership_event(update_event.clone())
+                        .await?;
+                    Ok(update_event)
                 }
                 UserDataOutsiderStatus::Declined => {
                     bail!("User request already declined")
@@ -104,12 +108,11 @@ mod tests {
         };
 
         // Create the join request
-        let join_request = JoinClusterEvent {
-            candidate: candidate_user.clone(),
-        };
+        let join_request = JoinClusterEvent::from(candidate_user.clone());
+        let dsa = registry.state.device_creds.client.key_manager()?.dsa;
 
         // Execute the function
-        let result = action.update(join_request, JoinActionUpdate::Accept).await;
+        let result = action.update(join_request, JoinActionUpdate::Accept, &dsa).await;
 
         // Verify result is successful
         assert!(result.is_ok(), "Accept join request should succeed");
@@ -158,12 +161,11 @@ mod tests {
         };
 
         // Create the join request
-        let join_request = JoinClusterEvent {
-            candidate: candidate_user.clone(),
-        };
+        let join_request = JoinClusterEvent::from(candidate_user.clone());
+        let dsa = registry.state.device_creds.client.key_manager()?.dsa;
 
         // Execute the function
-        let result = action.update(join_request, JoinActionUpdate::Accept).await;
+        let result = action.update(join_request, JoinActionUpdate::Accept, &dsa).await;
 
         // Verify result
         assert!(
@@ -200,12 +202,11 @@ mod tests {
         };
 
         // Create the join request
-        let join_request = JoinClusterEvent {
-            candidate: candidate_user.clone(),
-        };
+        let join_request = JoinClusterEvent::from(candidate_user.clone());
+        let dsa = registry.state.device_creds.client.key_manager()?.dsa;
 
         // Execute the function
-        let result = action.update(join_request, JoinActionUpdate::Accept).await;
+        let result = action.update(join_request, JoinActionUpdate::Accept, &dsa).await;
 
         // Verify result
         assert!(
@@ -1,3 +1,4 @@
+use crate::node::common::clock::{EventTime, unix_time_millis};
 use crate::node::common::model::device::common::DeviceData;
 use crate::node::common::model::user::common::{UserDataMember, UserDataOutsider, UserMembership};
 use crate::node::common::model::vault::vault_data::VaultAggregate;
@@ -23,10 +24,19 @@ use tracing::info;
 pub struct ServerVaultAction<Repo: KvLogEventRepo> {
     pub p_obj: Arc<PersistentObject<Repo>>,
     pub server_device: DeviceData,
+    /// The join quorum a new vault starts with, the members change it later
+    pub join_quorum: usize,
+    /// Whether a new vault accepts invited devices only, the members change it later
+    pub require_invite: bool,
 }
 
 impl<Repo: KvLogEventRepo> ServerVaultAction<Repo> {
-    pub async fn do_processing(&self, action_event: VaultActionEvent) -> Result<()> {
+    /// Applies the action of a device log event, `time` is the time of the event
+    pub async fn do_processing(
+        &self,
+        action_event: VaultActionEvent,
+        time: Option<EventTime>,
+    ) -> Result<()> {
         let p_vault = PersistentVault::from(self.p_obj.clone());
 
         //saving messages from device_log to vault_log guarantees ordering between events
@@ -36,39 +46,45 @@ impl<Repo: KvLogEventRepo> ServerVaultAction<Repo> {
                 let action = CreateVaultAction {
                     p_obj: self.p_obj.clone(),
                     server_device: self.server_device.clone(),
+                    join_quorum: self.join_quorum,
+                    require_invite: self.require_invite,
                 };
                 action.create(create_vault_event.owner.clone()).await?;
             }
 
             VaultActionEvent::Request(action_request) => {
                 p_vault
-                    .save_vault_log_request_event(action_request.clone())
+                    .save_vault_log_request_event(action_request.clone(), time)
                     .await?;
 
                 match action_request {
                     VaultActionRequestEvent::JoinCluster(join_event) => {
+                        //the invite has been checked by the server before the request got saved
                         let upd = VaultActionUpdateEvent::AddToPending {
                             candidate: join_event.candidate.clone(),
+                            invite: join_event.invite.clone(),
                         };
                         self.handle_update(&upd).await?;
                     }
                     VaultActionRequestEvent::AddMetaPass(add_meta_pass_event) => {
                         //server is a handler for add meta pass requests
-                        let vault_name = action_request.vault_name();
                         let upd = VaultActionUpdateEvent::AddMetaPass(add_meta_pass_event.clone());
-
-                        let vault_action_events = p_vault
-                            .get_vault_log_artifact(action_event.vault_name())
-                            .await?
-                            .0
-                            .value
-                            .apply(upd.clone());
-
-                        p_vault
-                            .save_vault_log_events(vault_action_events, vault_name)
-                            .await?;
-
-                        self.handle_update(&upd).await?;
+                        self.accept_request(&action_event, upd).await?;
+                    }
+                    VaultActionRequestEvent::RotateDeviceKeys(rotation) => {
+                        //the signature of the rotation is verified by VaultAggregate
+                        let upd = VaultActionUpdateEvent::RotateDeviceKeys(rotation.clone());
+                        self.accept_request(&action_event, upd).await?;
+                    }
+                    VaultActionRequestEvent::AddInvite(add_invite) => {
+                        //the inviter is checked by VaultAggregate
+                        let upd = VaultActionUpdateEvent::AddInvite(add_invite.clone());
+                        self.accept_request(&action_event, upd).await?;
+                    }
+                    VaultActionRequestEvent::UpdateSettings(settings) => {
+                        //the sender and the quorum to loosen the settings are checked by VaultAggregate
+                        let upd = VaultActionUpdateEvent::UpdateSettings(settings.clone());
+                        self.accept_request(&action_event, upd).await?;
                     }
                 }
             }
@@ -80,6 +96,29 @@ impl<Repo: KvLogEventRepo> ServerVaultAction<Repo> {
         Ok(())
     }
 
+    /// The server is a handler of the requests that don't need an approval of the members
+    async fn accept_request(
+        &self,
+        action_event: &VaultActionEvent,
+        upd: VaultActionUpdateEvent,
+    ) -> Result<()> {
+        let p_vault = PersistentVault::from(self.p_obj.clone());
+        let vault_name = action_event.vault_name();
+
+        let vault_action_events = p_vault
+            .get_vault_log_artifact(vault_name.clone())
+            .await?
+            .0
+            .value
+            .apply(upd.clone());
+
+        p_vault
+            .save_vault_log_events(vault_action_events, vault_name)
+            .await?;
+
+        self.handle_update(&upd).await
+    }
+
     async fn handle_update(&self, action_update: &VaultActionUpdateEvent) -> Result<()> {
         let p_vault = PersistentVault::from(self.p_obj.clone());
         let vault_name = action_update.vault_name();
@@ -93,20 +132,21 @@ impl<Repo: KvLogEventRepo> ServerVaultAction<Repo> {
             .value
             .apply(action_update.clone());
 
-        let agg = VaultAggregate::build_from(vault_action_events, vault.clone().to_data());
+        let vault_data = vault
+            .clone()
+            .to_data()
+            .without_expired_invites(unix_time_millis());
+        let agg = VaultAggregate::build_from(vault_action_events, vault_data);
 
         let vault_event = {
-            let key = KvKey {
-                obj_id: vault.obj_id().next(),
-                obj_desc: VaultDescriptor::from(vault_name.clone()).to_obj_desc(),
-            };
-            VaultObject(KvLogEvent {
-                key,
-                value: agg.vault,
-            })
+            let key = KvKey::artifact(
+                VaultDescriptor::from(vault_name.clone()).to_obj_desc(),
+                vault.obj_id().next(),
+            );
+            VaultObject(KvLogEvent::new(key, agg.vault))
         };
 
-        self.p_obj.repo.save(vault_event.clone()).await?;
+        self.p_obj.append(vault_event.clone()).await?;
 
         p_vault
             .save_vault_log_events(agg.events, vault_name)
@@ -120,7 +160,15 @@ impl<Repo: KvLogEventRepo> ServerVaultAction<Repo> {
             VaultActionUpdateEvent::AddMetaPass(AddMetaPassEvent { .. }) => {
                 // no extra steps required (vault  is already updated by VaultAggregate)
             }
-            VaultActionUpdateEvent::AddToPending { candidate } => {
+            VaultActionUpdateEvent::RotateDeviceKeys(rotation) => {
+                // the status of the device keeps a copy of the device keys
+                let update = UserMembership::Member(rotation.rotated_member());
+                self.update_vault_status(vault_event, update).await?;
+            }
+            VaultActionUpdateEvent::AddInvite(_) | VaultActionUpdateEvent::UpdateSettings(_) => {
+                // kept by the vault, the membership doesn't change
+            }
+            VaultActionUpdateEvent::AddToPending { candidate, .. } => {
                 let update = UserMembership::Outsider(UserDataOutsider::pending(candidate.clone()));
                 self.update_vault_status(vault_event, update).await?;
             }
@@ -149,7 +197,7 @@ impl<Repo: KvLogEventRepo> ServerVaultAction<Repo> {
             status_obj.to_generic()
         };
 
-        self.p_obj.repo.save(event).await?;
+        self.p_obj.append(event).await?;
         Ok(())
     }
 }
@@ -157,6 +205,8 @@ impl<Repo: KvLogEventRepo> ServerVaultAction<Repo> {
 pub struct CreateVaultAction<Repo: KvLogEventRepo> {
     pub p_obj: Arc<PersistentObject<Repo>>,
     pub server_device: DeviceData,
+    pub join_quorum: usize,
+    pub require_invite: bool,
 }
 
 impl<Repo: KvLogEventRepo> CreateVaultAction<Repo> {
@@ -182,11 +232,14 @@ impl<Repo: KvLogEventRepo> CreateVaultAction<Repo> {
             candidate.user_data.vault_name()
         );
 
-        let sign_up_action = SignUpAction;
+        let sign_up_action = SignUpAction {
+            join_quorum: self.join_quorum,
+            require_invite: self.require_invite,
+        };
         let sign_up_events = sign_up_action.accept(candidate.clone());
 
         for sign_up_event in sign_up_events {
-            self.p_obj.repo.save(sign_up_event).await?;
+            self.p_obj.append(sign_up_event).await?;
         }
         anyhow::Ok(())
     }
@@ -197,6 +250,7 @@ impl<Repo: KvLogEventRepo> CreateVaultAction<Repo> {
 pub mod fixture {
     use super::*;
     use crate::meta_tests::fixture_util::fixture::states::EmptyState;
+    use crate::node::common::model::vault::vault_data::DEFAULT_JOIN_QUORUM;
     use crate::node::db::in_mem_db::InMemKvLogEventRepo;
 
     pub struct ServerVaultActionFixture {
@@ -209,6 +263,8 @@ pub mod fixture {
                 server: ServerVaultAction {
                     p_obj: state.p_obj.server.clone(),
                     server_device: state.device_creds.server.device.clone(),
+                    join_quorum: DEFAULT_JOIN_QUORUM,
+                    require_invite: false,
                 },
             }
         }
@@ -224,7 +280,7 @@ mod tests {
     use crate::node::common::model::user::common::{UserDataMember, UserMembership};
     use crate::node::common::model::vault::vault::VaultStatus;
     use crate::node::db::events::vault::vault_log_event::{
-        AddMetaPassEvent, UpdateMembershipEvent,
+        AddMetaPassEvent, RotateDeviceKeysEvent, UpdateMembershipEvent,
     };
     use crate::node::db::events::vault::vault_log_event::{
         CreateVaultEvent, JoinClusterEvent, VaultActionInitEvent, VaultActionRequestEvent,
@@ -251,7 +307,9 @@ mod tests {
         let vault_action_event = VaultActionEvent::Init(create_vault_event);
 
         // Act
-        let result = server_vault_action.do_processing(vault_action_event).await;
+        let result = server_vault_action
+            .do_processing(vault_action_event, None)
+            .await;
 
         // Assert
         assert!(result.is_ok());
@@ -277,13 +335,17 @@ mod tests {
         let meta_pass_event = AddMetaPassEvent {
             sender: owner.clone(),
             meta_pass_id: MetaPasswordId::build_from_str("Test Password"),
+            recovery_policy: None,
+            break_glass: None,
         };
 
         let request_event = VaultActionRequestEvent::AddMetaPass(meta_pass_event);
         let vault_action_event = VaultActionEvent::Request(request_event);
 
         // Act
-        let result = server_vault_action.do_processing(vault_action_event).await;
+        let result = server_vault_action
+            .do_processing(vault_action_event, None)
+            .await;
 
         // Assert
         assert!(result.is_ok());
@@ -311,27 +373,48 @@ mod tests {
         // First, we need to add a join request to the vault log
         let join_request = JoinClusterEvent::from(new_member.user_data.clone());
         let request_event = VaultActionRequestEvent::JoinCluster(join_request.clone());
-        let vault_action_request = VaultActionEvent::Request(request_event);
+        let vault_action_request = VaultActionEvent::Request(request_event.clone());
 
         // Process the join request
+        let request_time = EventTime::now();
         server_vault_action
-            .do_processing(vault_action_request)
+            .do_processing(vault_action_request, Some(request_time.clone()))
             .await?;
 
+        // The pending request keeps its time
+        let p_vault = PersistentVault::from(server_vault_action.p_obj.clone());
+        let vault_log = p_vault
+            .get_vault_log_artifact(owner.user_data.vault_name())
+            .await?;
+        assert_eq!(
+            vault_log.0.value.request_time(&request_event),
+            Some(&request_time)
+        );
+
         // Now create the membership update event - it needs to match the request
-        let update_event = VaultActionUpdateEvent::UpdateMembership(UpdateMembershipEvent {
-            sender: owner.clone(),
-            update: UserMembership::Member(new_member.clone()),
-            request: join_request, // Use the same join request as above
-        });
+        let owner_dsa = registry.state.empty.user_creds.client.device_creds.key_manager()?.dsa;
+        let update_membership = UpdateMembershipEvent::sign(
+            join_request, // Use the same join request as above
+            owner.clone(),
+            UserMembership::Member(new_member.clone()),
+            &owner_dsa,
+        )?;
+        let update_event = VaultActionUpdateEvent::UpdateMembership(Box::new(update_membership));
 
         // Process the update
         let vault_action_event = VaultActionEvent::Update(update_event);
-        let result = server_vault_action.do_processing(vault_action_event).await;
+        let result = server_vault_action
+            .do_processing(vault_action_event, None)
+            .await;
         assert!(result.is_ok(), "Membership update should succeed");
 
+        // The request isn't pending anymore
+        let vault_log = p_vault
+            .get_vault_log_artifact(owner.user_data.vault_name())
+            .await?;
+        assert_eq!(vault_log.0.value.request_time(&request_event), None);
+
         // Now the new member should be properly added to the vault
-        let p_vault = PersistentVault::from(server_vault_action.p_obj.clone());
         let vault = p_vault.get_vault(owner.user_data.vault_name()).await?;
 
         // Check if the new member was added to the vault
@@ -350,4 +433,62 @@ mod tests {
             _ => panic!("Expected VaultStatus::Member, got {:?}", status),
         }
     }
+
+    #[tokio::test]
+    async fn test_rotate_device_keys() -> Result<()> {
+        // Setup
+        let registry = FixtureRegistry::base().await?;
+        let server_vault_action = &registry.state.server_vault_action.server;
+        let user_creds = &registry.state.empty.user_creds.client;
+        let owner = UserDataMember::from(user_creds.user());
+
+        create_vault(&registry).await?;
+
+        let old_key_manager = user_creds.device_creds.key_manager()?;
+        let rotated_creds = user_creds.device_creds.rotate_keys();
+        let new_keys = rotated_creds.device.keys.clone();
+
+        // A rotation signed with a foreign key is ignored
+        let foreign_key_manager = registry.state.empty.user_creds.vd.device_creds.key_manager()?;
+        let forged = RotateDeviceKeysEvent::sign(
+            owner.clone(),
+            new_keys.clone(),
+            &foreign_key_manager.dsa,
+        )?;
+        let request_event = VaultActionRequestEvent::RotateDeviceKeys(forged);
+        server_vault_action
+            .do_processing(VaultActionEvent::Request(request_event), None)
+            .await?;
+
+        let p_vault = PersistentVault::from(server_vault_action.p_obj.clone());
+        let vault = p_vault.get_vault(owner.user_data.vault_name()).await?.to_data();
+        assert_eq!(
+            Some(&UserMembership::Member(owner.clone())),
+            vault.find_user(&owner.user().device.device_id)
+        );
+
+        // Act
+        let rotation = RotateDeviceKeysEvent::sign(owner.clone(), new_keys, &old_key_manager.dsa)?;
+        let request_event = VaultActionRequestEvent::RotateDeviceKeys(rotation.clone());
+        server_vault_action
+            .do_processing(VaultActionEvent::Request(request_event), None)
+            .await?;
+
+        // Assert: the device keeps its id and the vault knows the new keys
+        let vault = p_vault.get_vault(owner.user_data.vault_name()).await?.to_data();
+        let rotated_member = rotation.rotated_member();
+        assert_eq!(
+            rotated_member.user().device.device_id,
+            owner.user().device.device_id
+        );
+        assert_eq!(
+            Some(&UserMembership::Member(rotated_member.clone())),
+            vault.find_user(&owner.user().device.device_id)
+        );
+
+        let status = p_vault.find(rotated_member.user_data.clone()).await?;
+        assert_eq!(VaultStatus::Member(rotated_member), status);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,57 @@
+use crate::node::common::model::vault::vault::VaultName;
+use crate::node::db::descriptors::object_descriptor::{
+    ObjectDescriptor, ObjectName, ObjectType, ToObjectDescriptor,
+};
+use crate::node::db::events::audit_event::{AuditKeyObject, AuditLogObject};
+use derive_more::From;
+
+/// The audit trail of a vault, written by the server only
+#[derive(Clone, Debug, PartialEq, From, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogDescriptor(VaultName);
+
+impl ToObjectDescriptor for AuditLogDescriptor {
+    type EventType = AuditLogObject;
+
+    fn to_obj_desc(self) -> ObjectDescriptor {
+        ObjectDescriptor::AuditLog(self)
+    }
+}
+
+impl ObjectType for AuditLogDescriptor {
+    fn object_type(&self) -> String {
+        String::from("AuditLog")
+    }
+}
+
+impl ObjectName for AuditLogDescriptor {
+    fn object_name(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+/// The key of the server the audit logs are signed with, pinned by the client
+/// the first time it talks to the server. Kept on the client only
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditKeyDescriptor;
+
+impl ToObjectDescriptor for AuditKeyDescriptor {
+    type EventType = AuditKeyObject;
+
+    fn to_obj_desc(self) -> ObjectDescriptor {
+        ObjectDescriptor::AuditKey(self)
+    }
+}
+
+impl ObjectType for AuditKeyDescriptor {
+    fn object_type(&self) -> String {
+        String::from("AuditKey")
+    }
+}
+
+impl ObjectName for AuditKeyDescriptor {
+    fn object_name(&self) -> String {
+        String::from("index")
+    }
+}
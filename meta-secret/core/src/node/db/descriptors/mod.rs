@@ -1,3 +1,4 @@
+pub mod audit_descriptor;
 pub mod creds;
 pub mod object_descriptor;
 pub mod shared_secret_descriptor;
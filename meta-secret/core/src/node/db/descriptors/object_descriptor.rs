@@ -1,10 +1,12 @@
 use crate::node::common::model::IdString;
+use crate::node::db::descriptors::audit_descriptor::{AuditKeyDescriptor, AuditLogDescriptor};
 use crate::node::db::descriptors::creds::{DeviceCredsDescriptor, UserCredsDescriptor};
 use crate::node::db::descriptors::shared_secret_descriptor::{
     SsDeviceLogDescriptor, SsLogDescriptor, SsWorkflowDescriptor,
 };
 use crate::node::db::descriptors::vault_descriptor::{
-    DeviceLogDescriptor, VaultDescriptor, VaultLogDescriptor, VaultStatusDescriptor,
+    DeviceLogDescriptor, JoinInviteDescriptor, VaultDescriptor, VaultLogDescriptor,
+    VaultStatusDescriptor,
 };
 use crate::node::db::events::generic_log_event::GenericKvLogEventConvertible;
 use crate::node::db::events::object_id::Next;
@@ -21,11 +23,18 @@ pub enum ObjectDescriptor {
     VaultLog(VaultLogDescriptor),
     Vault(VaultDescriptor),
     VaultStatus(VaultStatusDescriptor),
+    /// The invite of a pending join, kept on the candidate only
+    JoinInvite(JoinInviteDescriptor),
 
     /// Secret distribution (split, recover, recovery request and so on)
     SsLog(SsLogDescriptor),
     SsDeviceLog(SsDeviceLogDescriptor),
     SharedSecret(SsWorkflowDescriptor),
+
+    /// Signed records of the actions taken in a vault
+    AuditLog(AuditLogDescriptor),
+    /// The pinned key of the audit logs
+    AuditKey(AuditKeyDescriptor),
 }
 
 pub trait ToObjectDescriptor: Clone {
@@ -87,6 +96,14 @@ impl SeqId {
     pub fn first() -> Self {
         GenesisId.next()
     }
+
+    /// Id of the previous event, the first id has no predecessor
+    pub fn prev_id(&self) -> Option<SeqId> {
+        (self.curr > 1).then(|| SeqId {
+            curr: self.curr - 1,
+            prev: self.prev - 1,
+        })
+    }
 }
 
 impl Next<SeqId> for SeqId {
@@ -121,6 +138,10 @@ impl ObjectName for ObjectDescriptor {
             ObjectDescriptor::SharedSecret(s_s_descriptor) => s_s_descriptor.clone().id_str(),
             ObjectDescriptor::SsLog(desc) => desc.clone().id_str(),
             ObjectDescriptor::SsDeviceLog(desc) => desc.clone().id_str(),
+
+            ObjectDescriptor::AuditLog(desc) => desc.object_name(),
+            ObjectDescriptor::AuditKey(desc) => desc.object_name(),
+            ObjectDescriptor::JoinInvite(desc) => desc.object_name(),
         }
     }
 }
@@ -137,6 +158,9 @@ impl ObjectType for ObjectDescriptor {
             ObjectDescriptor::VaultStatus(mem) => mem.object_type(),
             ObjectDescriptor::SsLog(desc) => desc.object_type(),
             ObjectDescriptor::SsDeviceLog(desc) => desc.object_type(),
+            ObjectDescriptor::AuditLog(desc) => desc.object_type(),
+            ObjectDescriptor::AuditKey(desc) => desc.object_type(),
+            ObjectDescriptor::JoinInvite(desc) => desc.object_type(),
         }
     }
 }
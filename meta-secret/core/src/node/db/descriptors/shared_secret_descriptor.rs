@@ -1,5 +1,5 @@
 use crate::node::common::model::device::common::DeviceId;
-use crate::node::common::model::secret::{SsDistributionId, SsRecoveryId};
+use crate::node::common::model::secret::{SsClaimId, SsDistributionId, SsRecoveryId};
 use crate::node::common::model::vault::vault::VaultName;
 use crate::node::common::model::IdString;
 use crate::node::db::descriptors::object_descriptor::{
@@ -17,6 +17,12 @@ pub enum SsWorkflowDescriptor {
     /// Allows devices distributing their shares (split operation)
     Distribution(SsDistributionId),
     Decline(SsRecoveryId),
+    /// The receiver approves a claim of a secret that has a recovery policy
+    Approval(SsRecoveryId),
+    /// The sender withdraws its claim
+    CancelClaim(SsClaimId),
+    /// A member stops the break-glass recovery of a claim
+    Veto(SsClaimId),
 }
 
 #[derive(Clone, Debug, PartialEq, From, Serialize, Deserialize)]
@@ -33,6 +39,9 @@ impl ObjectType for SsWorkflowDescriptor {
             SsWorkflowDescriptor::Distribution(_) => "SsDistribution",
             SsWorkflowDescriptor::Recovery(_) => "SsRecovery",
             SsWorkflowDescriptor::Decline(_) => "SsDecline",
+            SsWorkflowDescriptor::Approval(_) => "SsApproval",
+            SsWorkflowDescriptor::CancelClaim(_) => "SsCancelClaim",
+            SsWorkflowDescriptor::Veto(_) => "SsVeto",
         };
 
         String::from(obj_type)
@@ -63,6 +72,9 @@ impl IdString for SsWorkflowDescriptor {
             SsWorkflowDescriptor::Distribution(event_id) => event_id.id_str(),
             SsWorkflowDescriptor::Recovery(db_id) => db_id.id_str(),
             SsWorkflowDescriptor::Decline(db_id) => db_id.id_str(),
+            SsWorkflowDescriptor::Approval(db_id) => db_id.id_str(),
+            SsWorkflowDescriptor::CancelClaim(claim_id) => claim_id.id_str(),
+            SsWorkflowDescriptor::Veto(claim_id) => claim_id.id_str(),
         }
     }
 }
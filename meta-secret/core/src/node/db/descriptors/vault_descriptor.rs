@@ -3,6 +3,7 @@ use crate::node::common::model::vault::vault::VaultName;
 use crate::node::db::descriptors::object_descriptor::{
     ObjectDescriptor, ObjectName, ObjectType, ToObjectDescriptor,
 };
+use crate::node::db::events::local_event::JoinInviteObject;
 use crate::node::db::events::vault::device_log_event::DeviceLogObject;
 use crate::node::db::events::vault::vault_event::VaultObject;
 use crate::node::db::events::vault::vault_log_event::VaultLogObject;
@@ -25,6 +26,12 @@ pub struct VaultDescriptor(VaultName);
 #[serde(rename_all = "camelCase")]
 pub struct VaultStatusDescriptor(UserId);
 
+/// The invite the device has joined the vault with, kept locally until
+/// the inviting member shows up in the vault
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinInviteDescriptor;
+
 impl ToObjectDescriptor for DeviceLogDescriptor {
     type EventType = DeviceLogObject;
 
@@ -105,6 +112,26 @@ impl ObjectName for VaultStatusDescriptor {
     }
 }
 
+impl ToObjectDescriptor for JoinInviteDescriptor {
+    type EventType = JoinInviteObject;
+
+    fn to_obj_desc(self) -> ObjectDescriptor {
+        ObjectDescriptor::JoinInvite(self)
+    }
+}
+
+impl ObjectType for JoinInviteDescriptor {
+    fn object_type(&self) -> String {
+        String::from("JoinInvite")
+    }
+}
+
+impl ObjectName for JoinInviteDescriptor {
+    fn object_name(&self) -> String {
+        String::from("index")
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use serde_json::json;
@@ -0,0 +1,312 @@
+//! The audit trail of a vault: who did what and when. Entries are derived by the server
+//! from the events it receives, timestamped by the server clock and signed with the server key,
+//! the trail gets replicated to the vault members along with the vault.
+//!
+//! Clients verify the trail against the server key pinned on the first handshake
+//! ([`AuditKeyObject`]), never against a key taken from the trail itself.
+//! The device of an entry is authenticated by the server: write entries take it from
+//! events whose device signature the server has checked, delivery entries take it from
+//! the key of the delivered share, never from the sender of the request.
+
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::key_pair::{DsaKeyPair, KeyPair};
+use crate::crypto::keys::DsaPk;
+use crate::node::common::model::device::common::DeviceId;
+use crate::node::common::model::meta_pass::MetaPasswordId;
+use crate::node::common::model::secret::{SecretDistributionType, SsClaimId};
+use crate::node::db::descriptors::audit_descriptor::AuditKeyDescriptor;
+use crate::node::db::events::error::LogEventCastError;
+use crate::node::db::events::generic_log_event::{
+    GenericKvLogEvent, KeyExtractor, ObjIdExtractor, ToGenericEvent,
+};
+use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
+use crate::node::db::events::object_id::ArtifactId;
+use anyhow::{Result, bail};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AuditError {
+    #[error("Audit entry of {device} has an invalid signature")]
+    InvalidSignature { device: String },
+    #[error("Audit entry of {device} is signed by an unknown key")]
+    UnknownSigner { device: String },
+    #[error("The audit key of the server is not pinned")]
+    KeyNotPinned,
+    #[error("The server presents an audit key that differs from the pinned one")]
+    KeyMismatch,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogObject(pub KvLogEvent<SignedAuditEntry>);
+
+impl AuditLogObject {
+    pub fn to_entry(self) -> SignedAuditEntry {
+        self.0.value
+    }
+}
+
+impl TryFrom<GenericKvLogEvent> for AuditLogObject {
+    type Error = anyhow::Error;
+
+    fn try_from(event: GenericKvLogEvent) -> Result<Self, Self::Error> {
+        if let GenericKvLogEvent::AuditLog(audit_log) = event {
+            Ok(audit_log)
+        } else {
+            bail!(LogEventCastError::InvalidAuditLog(event))
+        }
+    }
+}
+
+impl ToGenericEvent for AuditLogObject {
+    fn to_generic(self) -> GenericKvLogEvent {
+        GenericKvLogEvent::AuditLog(self)
+    }
+}
+
+impl KeyExtractor for AuditLogObject {
+    fn key(&self) -> KvKey {
+        self.0.key.clone()
+    }
+}
+
+impl ObjIdExtractor for AuditLogObject {
+    fn obj_id(&self) -> ArtifactId {
+        self.0.key.obj_id.clone()
+    }
+}
+
+/// The audit key of the server, pinned by the client on the first handshake
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditKeyObject(pub KvLogEvent<DsaPk>);
+
+impl From<DsaPk> for AuditKeyObject {
+    fn from(audit_key: DsaPk) -> Self {
+        AuditKeyObject(KvLogEvent::new(KvKey::from(AuditKeyDescriptor), audit_key))
+    }
+}
+
+impl TryFrom<GenericKvLogEvent> for AuditKeyObject {
+    type Error = anyhow::Error;
+
+    fn try_from(event: GenericKvLogEvent) -> Result<Self, Self::Error> {
+        if let GenericKvLogEvent::AuditKey(audit_key) = event {
+            Ok(audit_key)
+        } else {
+            bail!(LogEventCastError::InvalidAuditKey(event))
+        }
+    }
+}
+
+impl ToGenericEvent for AuditKeyObject {
+    fn to_generic(self) -> GenericKvLogEvent {
+        GenericKvLogEvent::AuditKey(self)
+    }
+}
+
+impl KeyExtractor for AuditKeyObject {
+    fn key(&self) -> KvKey {
+        self.0.key.clone()
+    }
+}
+
+impl ObjIdExtractor for AuditKeyObject {
+    fn obj_id(&self) -> ArtifactId {
+        self.0.key.obj_id.clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// Unix time in milliseconds, by the server clock
+    pub time: u64,
+    /// The device that took the action
+    pub device: DeviceId,
+    pub action: AuditAction,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditAction {
+    VaultCreated,
+    JoinRequested,
+    JoinAccepted {
+        candidate: DeviceId,
+    },
+    JoinDeclined {
+        candidate: DeviceId,
+    },
+    /// Passwords are referenced by their opaque ids, the server never sees the names
+    SecretAdded {
+        pass_id: MetaPasswordId,
+    },
+    KeysRotated,
+    /// A member has invited a new device into the vault
+    InviteCreated,
+    /// A member has changed or proposed to change the settings of the vault
+    SettingsUpdated,
+    RecoveryRequested {
+        claim: SsClaimId,
+    },
+    RecoveryApproved {
+        claim: SsClaimId,
+    },
+    /// The device has approved a claim of a secret with a recovery policy,
+    /// its share is released once the claim has all the approvals the policy requires
+    RecoveryApprovalGranted {
+        claim: SsClaimId,
+    },
+    RecoveryDeclined {
+        claim: SsClaimId,
+    },
+    /// The sender has withdrawn its recovery claim
+    RecoveryCancelled {
+        claim: SsClaimId,
+    },
+    /// The device has stopped the escrow from releasing its share of a break-glass recovery
+    RecoveryVetoed {
+        claim: SsClaimId,
+    },
+    /// The server delivered a share to the device
+    ShareDelivered {
+        claim: SsClaimId,
+        distribution_type: SecretDistributionType,
+    },
+}
+
+impl AuditAction {
+    /// The password the action is about
+    pub fn pass_id(&self) -> Option<&MetaPasswordId> {
+        match self {
+            AuditAction::SecretAdded { pass_id } => Some(pass_id),
+            AuditAction::RecoveryRequested { claim }
+            | AuditAction::RecoveryApproved { claim }
+            | AuditAction::RecoveryApprovalGranted { claim }
+            | AuditAction::RecoveryDeclined { claim }
+            | AuditAction::RecoveryCancelled { claim }
+            | AuditAction::RecoveryVetoed { claim }
+            | AuditAction::ShareDelivered { claim, .. } => Some(&claim.pass_id),
+            AuditAction::VaultCreated
+            | AuditAction::JoinRequested
+            | AuditAction::JoinAccepted { .. }
+            | AuditAction::JoinDeclined { .. }
+            | AuditAction::KeysRotated
+            | AuditAction::InviteCreated
+            | AuditAction::SettingsUpdated => None,
+        }
+    }
+
+    pub fn pass_id_mut(&mut self) -> Option<&mut MetaPasswordId> {
+        match self {
+            AuditAction::SecretAdded { pass_id } => Some(pass_id),
+            AuditAction::RecoveryRequested { claim }
+            | AuditAction::RecoveryApproved { claim }
+            | AuditAction::RecoveryApprovalGranted { claim }
+            | AuditAction::RecoveryDeclined { claim }
+            | AuditAction::RecoveryCancelled { claim }
+            | AuditAction::RecoveryVetoed { claim }
+            | AuditAction::ShareDelivered { claim, .. } => Some(&mut claim.pass_id),
+            AuditAction::VaultCreated
+            | AuditAction::JoinRequested
+            | AuditAction::JoinAccepted { .. }
+            | AuditAction::JoinDeclined { .. }
+            | AuditAction::KeysRotated
+            | AuditAction::InviteCreated
+            | AuditAction::SettingsUpdated => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedAuditEntry {
+    pub entry: AuditEntry,
+    pub signer: DsaPk,
+    pub signature: Base64Text,
+}
+
+impl SignedAuditEntry {
+    pub fn sign(entry: AuditEntry, dsa: &DsaKeyPair) -> Result<Self> {
+        let signature = dsa.sign(serde_json::to_string(&entry)?);
+        Ok(Self {
+            entry,
+            signer: dsa.pk(),
+            signature,
+        })
+    }
+
+    /// Checks that the entry is signed by the given key
+    pub fn verify(&self, signer: &DsaPk) -> Result<()> {
+        if !self.signer.eq(signer) {
+            bail!(AuditError::UnknownSigner {
+                device: self.entry.device.to_string(),
+            });
+        }
+
+        let signed_text = serde_json::to_string(&self.entry)?;
+        if self.signer.verify(&signed_text, &self.signature).is_err() {
+            bail!(AuditError::InvalidSignature {
+                device: self.entry.device.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+    use crate::meta_tests::fixture_util::fixture::states::EmptyState;
+
+    fn entry(registry: &FixtureRegistry<EmptyState>) -> AuditEntry {
+        AuditEntry {
+            time: 1_700_000_000_000,
+            device: registry.state.user_creds.client.device_id().clone(),
+            action: AuditAction::KeysRotated,
+        }
+    }
+
+    #[test]
+    fn test_signed_entry_is_verified() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_dsa = registry.state.device_creds.server.key_manager()?.dsa;
+
+        let signed = SignedAuditEntry::sign(entry(&registry), &server_dsa)?;
+        signed.verify(&server_dsa.pk())
+    }
+
+    #[test]
+    fn test_tampered_entry_is_rejected() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_dsa = registry.state.device_creds.server.key_manager()?.dsa;
+
+        let mut signed = SignedAuditEntry::sign(entry(&registry), &server_dsa)?;
+        signed.entry.action = AuditAction::VaultCreated;
+
+        let err = signed.verify(&server_dsa.pk()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AuditError>(),
+            Some(AuditError::InvalidSignature { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_of_another_signer_is_rejected() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_dsa = registry.state.device_creds.server.key_manager()?.dsa;
+        let client_dsa = registry.state.device_creds.client.key_manager()?.dsa;
+
+        let signed = SignedAuditEntry::sign(entry(&registry), &client_dsa)?;
+
+        let err = signed.verify(&server_dsa.pk()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AuditError>(),
+            Some(AuditError::UnknownSigner { .. })
+        ));
+        Ok(())
+    }
+}
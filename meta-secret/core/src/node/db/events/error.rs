@@ -32,6 +32,10 @@ pub enum LogEventCastError {
     InvalidSsDeviceLog(GenericKvLogEvent),
     #[error("InvalidSsLog: Invalid event")]
     InvalidSsLog(GenericKvLogEvent),
+    #[error("InvalidAuditLog: Invalid event")]
+    InvalidAuditLog(GenericKvLogEvent),
+    #[error("InvalidAuditKey: Invalid event")]
+    InvalidAuditKey(GenericKvLogEvent),
     #[error("WrongSsLog: wrong event")]
     WrongSsLog(SsLogObject),
     #[error("WrongSsLogId: wrong event")]
@@ -0,0 +1,216 @@
+//! Every event of an object keeps the hash of the previous event of the same object
+//! (in [`KvKey::prev_hash`](crate::node::db::events::kv_log_event::KvKey)),
+//! so a past event can't be rewritten without breaking the links of all the events after it.
+//!
+//! The first event of a chain is linked to the [`genesis`] marker. Events written before
+//! the chains were introduced have no links, a chain may start with such events,
+//! but once an event of the object is linked (or marked), all the next ones have to be linked too.
+
+use crate::crypto::encoding::base64::Base64Text;
+use crate::node::common::model::IdString;
+use crate::node::db::events::generic_log_event::{GenericKvLogEvent, KeyExtractor, ObjIdExtractor};
+use anyhow::{Result, bail};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum EventChainError {
+    #[error("Event {obj_id} is not linked to the previous event of the object")]
+    BrokenLink { obj_id: String },
+    #[error("Event {obj_id} diverges from the locally stored history of the object")]
+    Divergence { obj_id: String },
+}
+
+/// Sha256 of the canonical json of the event. Object keys are sorted, arrays keep their order:
+/// the order of a list is a part of the event (hash sets are serialized sorted, see
+/// [`sorted_set`](crate::node::common::model::sorted_set)).
+/// The receive time is added by the server after the event was linked, it isn't hashed
+pub fn event_hash(event: &GenericKvLogEvent) -> Result<Base64Text> {
+    let mut event = event.clone();
+    if let Some(time) = event.time_mut() {
+        time.received_at = None;
+    }
+
+    let canonical = canonical_json(serde_json::to_value(&event)?);
+    let hash = Sha256::digest(serde_json::to_vec(&canonical)?);
+    Ok(Base64Text::from(hash.as_slice()))
+}
+
+/// Checks that the event follows the previous event of the object
+pub fn verify_link(prev: &GenericKvLogEvent, event: &GenericKvLogEvent) -> Result<()> {
+    let is_linked = match &event.key().prev_hash {
+        Some(prev_hash) => prev_hash.eq(&event_hash(prev)?),
+        // events that precede the chains
+        None => prev.key().prev_hash.is_none(),
+    };
+
+    if !is_linked {
+        bail!(EventChainError::BrokenLink {
+            obj_id: event.obj_id().id_str(),
+        });
+    }
+    Ok(())
+}
+
+/// The link of the first event of an object, it tells the next events that the object is chained
+pub fn genesis() -> Base64Text {
+    Base64Text::from([0u8; 32].as_slice())
+}
+
+/// Marks the event as the first event of the chain of the object
+pub fn start(mut event: GenericKvLogEvent) -> GenericKvLogEvent {
+    event.key_mut().prev_hash = Some(genesis());
+    event
+}
+
+/// Links the event to the previous event of the object
+pub fn link(prev: &GenericKvLogEvent, mut event: GenericKvLogEvent) -> Result<GenericKvLogEvent> {
+    event.key_mut().prev_hash = Some(event_hash(prev)?);
+    Ok(event)
+}
+
+fn canonical_json(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(canonical_json).collect()),
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, value)| (key, canonical_json(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+    use crate::node::common::model::device::common::DeviceId;
+    use crate::node::common::model::meta_pass::MetaPasswordId;
+    use crate::node::common::model::secret::RecoveryPolicy;
+    use crate::node::common::model::user::common::UserDataMember;
+    use crate::node::db::descriptors::vault_descriptor::VaultDescriptor;
+    use crate::node::db::events::generic_log_event::ToGenericEvent;
+    use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
+    use crate::node::db::events::object_id::Next;
+    use crate::node::db::events::vault::vault_event::VaultObject;
+
+    fn vault_events() -> (GenericKvLogEvent, GenericKvLogEvent) {
+        let registry = FixtureRegistry::empty();
+        let user = registry.state.user_creds.client.user();
+        let first = VaultObject::sign_up(user.vault_name(), UserDataMember::from(user.clone()));
+
+        let second_key = KvKey::from(VaultDescriptor::from(user.vault_name())).next();
+        let second = VaultObject(KvLogEvent::new(second_key, first.clone().to_data()));
+        (first.to_generic(), second.to_generic())
+    }
+
+    #[test]
+    fn test_linked_events() -> Result<()> {
+        let (first, second) = vault_events();
+        let second = link(&first, second)?;
+        verify_link(&first, &second)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewritten_event_breaks_the_link() -> Result<()> {
+        let (first, second) = vault_events();
+        let second = link(&first, second)?;
+
+        let GenericKvLogEvent::Vault(mut rewritten) = first else {
+            panic!("Vault event expected");
+        };
+        rewritten.0.value.secrets.clear();
+        rewritten.0.value.users.clear();
+        let rewritten = rewritten.to_generic();
+
+        let err = verify_link(&rewritten, &second).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<EventChainError>(),
+            Some(EventChainError::BrokenLink { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unlinked_event_after_linked_one() -> Result<()> {
+        let (first, second) = vault_events();
+        let second = link(&first, second)?;
+
+        let mut third = second.clone();
+        third.key_mut().obj_id = third.obj_id().next();
+        third.key_mut().prev_hash = None;
+
+        assert!(verify_link(&second, &third).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unlinked_second_event_after_genesis() -> Result<()> {
+        let (first, second) = vault_events();
+        let first = start(first);
+
+        // the second event is rewritten and its link is dropped
+        let mut rewritten = link(&first, second)?;
+        rewritten.key_mut().prev_hash = None;
+
+        let err = verify_link(&first, &rewritten).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<EventChainError>(),
+            Some(EventChainError::BrokenLink { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_depends_on_list_order() -> Result<()> {
+        let (first, _) = vault_events();
+        let GenericKvLogEvent::Vault(mut vault_event) = first else {
+            panic!("Vault event expected");
+        };
+        let registry = FixtureRegistry::empty();
+        let approvers = vec![
+            registry.state.user_creds.client.device_id().clone(),
+            registry.state.user_creds.vd.device_id().clone(),
+        ];
+
+        let mut with_policy = |approvers: Vec<DeviceId>| {
+            let policy = RecoveryPolicy {
+                approvals: 1,
+                device_types: vec![],
+                approvers,
+            };
+            vault_event
+                .0
+                .value
+                .recovery_policies
+                .insert(String::from("pass"), policy);
+            event_hash(&vault_event.clone().to_generic())
+        };
+
+        let hash = with_policy(approvers.clone())?;
+        let reordered_hash = with_policy(approvers.into_iter().rev().collect())?;
+        assert_ne!(hash, reordered_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_does_not_depend_on_set_order() -> Result<()> {
+        let (first, _) = vault_events();
+        let GenericKvLogEvent::Vault(mut vault_event) = first else {
+            panic!("Vault event expected");
+        };
+        for i in 0..16 {
+            let pass_id = MetaPasswordId::build_from_str(&format!("pass_{}", i));
+            vault_event.0.value.secrets.insert(pass_id);
+        }
+        let first = vault_event.to_generic();
+
+        let json = serde_json::to_string(&first)?;
+        let restored: GenericKvLogEvent = serde_json::from_str(&json)?;
+        assert_eq!(event_hash(&first)?, event_hash(&restored)?);
+        Ok(())
+    }
+}
@@ -1,7 +1,9 @@
 use super::shared_secret_event::SsLogObject;
+use crate::node::common::clock::EventTime;
+use crate::node::db::events::audit_event::{AuditKeyObject, AuditLogObject};
 use crate::node::db::events::error::ErrorMessage;
 use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
-use crate::node::db::events::local_event::{DeviceCredsObject, UserCredsObject};
+use crate::node::db::events::local_event::{DeviceCredsObject, JoinInviteObject, UserCredsObject};
 use crate::node::db::events::object_id::ArtifactId;
 use crate::node::db::events::shared_secret_event::{SsDeviceLogObject, SsWorkflowObject};
 use crate::node::db::events::vault::device_log_event::DeviceLogObject;
@@ -14,6 +16,7 @@ use crate::node::db::events::vault::vault_status::VaultStatusObject;
 pub enum GenericKvLogEvent {
     DeviceCreds(DeviceCredsObject),
     UserCreds(UserCredsObject),
+    JoinInvite(JoinInviteObject),
 
     DeviceLog(DeviceLogObject),
     VaultLog(VaultLogObject),
@@ -24,6 +27,9 @@ pub enum GenericKvLogEvent {
     SsLog(SsLogObject),
     SsWorkflow(SsWorkflowObject),
 
+    AuditLog(AuditLogObject),
+    AuditKey(AuditKeyObject),
+
     DbError(KvLogEvent<ErrorMessage>),
 }
 
@@ -63,6 +69,88 @@ impl GenericKvLogEvent {
     pub fn ss_log(self) -> anyhow::Result<SsLogObject> {
         SsLogObject::try_from(self)
     }
+
+    pub fn audit_log(self) -> anyhow::Result<AuditLogObject> {
+        AuditLogObject::try_from(self)
+    }
+
+    pub fn audit_key(self) -> anyhow::Result<AuditKeyObject> {
+        AuditKeyObject::try_from(self)
+    }
+
+    pub fn key_mut(&mut self) -> &mut KvKey {
+        match self {
+            GenericKvLogEvent::DeviceCreds(obj) => &mut obj.0.key,
+            GenericKvLogEvent::UserCreds(obj) => &mut obj.0.key,
+            GenericKvLogEvent::JoinInvite(obj) => &mut obj.0.key,
+            GenericKvLogEvent::DeviceLog(obj) => &mut obj.0.key,
+            GenericKvLogEvent::VaultLog(obj) => &mut obj.0.key,
+            GenericKvLogEvent::Vault(obj) => &mut obj.0.key,
+            GenericKvLogEvent::VaultStatus(obj) => &mut obj.0.key,
+            GenericKvLogEvent::SsDeviceLog(obj) => &mut obj.0.key,
+            GenericKvLogEvent::SsLog(obj) => &mut obj.0.key,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Distribution(event)) => &mut event.key,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Recovery(event)) => &mut event.key,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Decline(event)) => &mut event.key,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Approval(event)) => &mut event.key,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::CancelClaim(event)) => &mut event.key,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Veto(event)) => &mut event.key,
+            GenericKvLogEvent::AuditLog(obj) => &mut obj.0.key,
+            GenericKvLogEvent::AuditKey(obj) => &mut obj.0.key,
+            GenericKvLogEvent::DbError(event) => &mut event.key,
+        }
+    }
+
+    /// When the event was written, see [`EventTime`]
+    pub fn time(&self) -> Option<&EventTime> {
+        match self {
+            GenericKvLogEvent::DeviceCreds(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::UserCreds(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::JoinInvite(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::DeviceLog(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::VaultLog(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::Vault(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::VaultStatus(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::SsDeviceLog(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::SsLog(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Distribution(event)) => {
+                event.time.as_ref()
+            }
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Recovery(event)) => event.time.as_ref(),
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Decline(event)) => event.time.as_ref(),
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Approval(event)) => event.time.as_ref(),
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::CancelClaim(event)) => {
+                event.time.as_ref()
+            }
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Veto(event)) => event.time.as_ref(),
+            GenericKvLogEvent::AuditLog(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::AuditKey(obj) => obj.0.time.as_ref(),
+            GenericKvLogEvent::DbError(event) => event.time.as_ref(),
+        }
+    }
+
+    pub fn time_mut(&mut self) -> &mut Option<EventTime> {
+        match self {
+            GenericKvLogEvent::DeviceCreds(obj) => &mut obj.0.time,
+            GenericKvLogEvent::UserCreds(obj) => &mut obj.0.time,
+            GenericKvLogEvent::JoinInvite(obj) => &mut obj.0.time,
+            GenericKvLogEvent::DeviceLog(obj) => &mut obj.0.time,
+            GenericKvLogEvent::VaultLog(obj) => &mut obj.0.time,
+            GenericKvLogEvent::Vault(obj) => &mut obj.0.time,
+            GenericKvLogEvent::VaultStatus(obj) => &mut obj.0.time,
+            GenericKvLogEvent::SsDeviceLog(obj) => &mut obj.0.time,
+            GenericKvLogEvent::SsLog(obj) => &mut obj.0.time,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Distribution(event)) => &mut event.time,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Recovery(event)) => &mut event.time,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Decline(event)) => &mut event.time,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Approval(event)) => &mut event.time,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::CancelClaim(event)) => &mut event.time,
+            GenericKvLogEvent::SsWorkflow(SsWorkflowObject::Veto(event)) => &mut event.time,
+            GenericKvLogEvent::AuditLog(obj) => &mut obj.0.time,
+            GenericKvLogEvent::AuditKey(obj) => &mut obj.0.time,
+            GenericKvLogEvent::DbError(event) => &mut event.time,
+        }
+    }
 }
 
 pub trait GenericKvLogEventConvertible: Sized {
@@ -109,12 +197,15 @@ impl ObjIdExtractor for GenericKvLogEvent {
             GenericKvLogEvent::SsWorkflow(obj) => obj.obj_id(),
             GenericKvLogEvent::DeviceCreds(obj) => obj.obj_id(),
             GenericKvLogEvent::UserCreds(obj) => obj.obj_id(),
+            GenericKvLogEvent::JoinInvite(obj) => obj.obj_id(),
             GenericKvLogEvent::DbError(event) => event.key.obj_id.clone(),
             GenericKvLogEvent::DeviceLog(obj) => obj.obj_id(),
             GenericKvLogEvent::VaultLog(obj) => obj.obj_id(),
             GenericKvLogEvent::VaultStatus(obj) => obj.obj_id(),
             GenericKvLogEvent::SsDeviceLog(obj) => obj.obj_id(),
             GenericKvLogEvent::SsLog(obj) => obj.obj_id(),
+            GenericKvLogEvent::AuditLog(obj) => obj.obj_id(),
+            GenericKvLogEvent::AuditKey(obj) => obj.obj_id(),
         }
     }
 }
@@ -126,12 +217,15 @@ impl KeyExtractor for GenericKvLogEvent {
             GenericKvLogEvent::SsWorkflow(obj) => obj.key(),
             GenericKvLogEvent::DeviceCreds(obj) => obj.key(),
             GenericKvLogEvent::UserCreds(obj) => obj.key(),
+            GenericKvLogEvent::JoinInvite(obj) => obj.key(),
             GenericKvLogEvent::DbError(event) => event.key.clone(),
             GenericKvLogEvent::DeviceLog(obj) => obj.key(),
             GenericKvLogEvent::VaultLog(obj) => obj.key(),
             GenericKvLogEvent::VaultStatus(obj) => obj.key(),
             GenericKvLogEvent::SsDeviceLog(obj) => obj.key(),
             GenericKvLogEvent::SsLog(obj) => obj.key(),
+            GenericKvLogEvent::AuditLog(obj) => obj.key(),
+            GenericKvLogEvent::AuditKey(obj) => obj.key(),
         }
     }
 }
@@ -1,3 +1,5 @@
+use crate::crypto::encoding::base64::Base64Text;
+use crate::node::common::clock::EventTime;
 use crate::node::db::descriptors::object_descriptor::{ObjectDescriptor, ToObjectDescriptor};
 use crate::node::db::events::object_id::{ArtifactId, Next};
 
@@ -6,6 +8,20 @@ use crate::node::db::events::object_id::{ArtifactId, Next};
 pub struct KvLogEvent<T> {
     pub key: KvKey,
     pub value: T,
+    /// When the event was written, events written before the timestamps were introduced have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<EventTime>,
+}
+
+impl<T> KvLogEvent<T> {
+    /// A new event timestamped by the clock of this node
+    pub fn new(key: KvKey, value: T) -> Self {
+        Self {
+            key,
+            value,
+            time: Some(EventTime::now()),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -20,6 +36,9 @@ pub struct KvEvent<T> {
 pub struct KvKey {
     pub obj_id: ArtifactId,
     pub obj_desc: ObjectDescriptor,
+    /// Hash of the previous event of the object, see [`crate::node::db::events::event_chain`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_hash: Option<Base64Text>,
 }
 
 impl<T: ToObjectDescriptor> From<T> for KvKey {
@@ -27,6 +46,7 @@ impl<T: ToObjectDescriptor> From<T> for KvKey {
         Self {
             obj_id: ArtifactId::from(obj_desc.clone()),
             obj_desc: obj_desc.to_obj_desc(),
+            prev_hash: None,
         }
     }
 }
@@ -34,12 +54,42 @@ impl<T: ToObjectDescriptor> From<T> for KvKey {
 impl Next<KvKey> for KvKey {
     fn next(mut self) -> Self {
         self.obj_id = self.obj_id.next();
+        self.prev_hash = None;
         self
     }
 }
 
 impl KvKey {
     pub fn artifact(obj_desc: ObjectDescriptor, obj_id: ArtifactId) -> Self {
-        Self { obj_id, obj_desc }
+        Self {
+            obj_id,
+            obj_desc,
+            prev_hash: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::common::model::vault::vault::VaultName;
+    use crate::node::db::descriptors::vault_descriptor::VaultLogDescriptor;
+    use anyhow::Result;
+
+    #[test]
+    fn test_events_without_time_are_read() -> Result<()> {
+        let event = KvLogEvent::new(
+            KvKey::from(VaultLogDescriptor::from(VaultName::test())),
+            String::from("value"),
+        );
+        assert!(event.time.is_some());
+
+        let mut json = serde_json::to_value(&event)?;
+        json.as_object_mut().unwrap().remove("time");
+
+        let old_event: KvLogEvent<String> = serde_json::from_value(json)?;
+        assert_eq!(old_event.time, None);
+        assert_eq!(old_event.key, event.key);
+        Ok(())
     }
 }
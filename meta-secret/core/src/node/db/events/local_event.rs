@@ -1,7 +1,9 @@
 use crate::node::common::model::device::common::DeviceData;
 use crate::node::common::model::device::device_creds::{SecureDeviceCreds};
 use crate::node::common::model::user::user_creds::{SecureUserCreds};
+use crate::node::common::model::vault::invite::InviteTicket;
 use crate::node::db::descriptors::creds::{DeviceCredsDescriptor, UserCredsDescriptor};
+use crate::node::db::descriptors::vault_descriptor::JoinInviteDescriptor;
 use crate::node::db::events::generic_log_event::{
     GenericKvLogEvent, KeyExtractor, ObjIdExtractor, ToGenericEvent,
 };
@@ -19,19 +21,13 @@ pub struct UserCredsObject(pub KvLogEvent<SecureUserCreds>);
 
 impl From<SecureDeviceCreds> for DeviceCredsObject {
     fn from(creds: SecureDeviceCreds) -> Self {
-        DeviceCredsObject(KvLogEvent {
-            key: KvKey::from(DeviceCredsDescriptor),
-            value: creds,
-        })
+        DeviceCredsObject(KvLogEvent::new(KvKey::from(DeviceCredsDescriptor), creds))
     }
 }
 
 impl From<SecureUserCreds> for UserCredsObject {
     fn from(creds: SecureUserCreds) -> Self {
-        UserCredsObject(KvLogEvent {
-            key: KvKey::from(UserCredsDescriptor),
-            value: creds,
-        })
+        UserCredsObject(KvLogEvent::new(KvKey::from(UserCredsDescriptor), creds))
     }
 }
 
@@ -240,3 +236,44 @@ use crate::node::common::model::device::device_creds::DeviceCreds;
         Ok(())
     }
 }
+
+/// The ticket of the invite the device has joined with
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinInviteObject(pub KvLogEvent<InviteTicket>);
+
+impl From<InviteTicket> for JoinInviteObject {
+    fn from(ticket: InviteTicket) -> Self {
+        JoinInviteObject(KvLogEvent::new(KvKey::from(JoinInviteDescriptor), ticket))
+    }
+}
+
+impl ObjIdExtractor for JoinInviteObject {
+    fn obj_id(&self) -> ArtifactId {
+        self.0.key.obj_id.clone()
+    }
+}
+
+impl KeyExtractor for JoinInviteObject {
+    fn key(&self) -> KvKey {
+        self.0.key.clone()
+    }
+}
+
+impl ToGenericEvent for JoinInviteObject {
+    fn to_generic(self) -> GenericKvLogEvent {
+        GenericKvLogEvent::JoinInvite(self)
+    }
+}
+
+impl TryFrom<GenericKvLogEvent> for JoinInviteObject {
+    type Error = Error;
+
+    fn try_from(event: GenericKvLogEvent) -> Result<Self, Self::Error> {
+        if let GenericKvLogEvent::JoinInvite(join_invite) = event {
+            Ok(join_invite)
+        } else {
+            Err(anyhow!("Invalid join invite event type"))
+        }
+    }
+}
@@ -1,4 +1,6 @@
+pub mod audit_event;
 pub mod error;
+pub mod event_chain;
 pub mod generic_log_event;
 pub mod kv_log_event;
 pub mod local_event;
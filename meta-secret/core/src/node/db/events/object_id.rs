@@ -28,6 +28,14 @@ impl ArtifactId {
     pub fn first(self) -> ArtifactId {
         ArtifactId::from(self.fqdn)
     }
+
+    /// Id of the previous event of the object
+    pub fn prev_id(&self) -> Option<ArtifactId> {
+        self.id.prev_id().map(|id| ArtifactId {
+            fqdn: self.fqdn.clone(),
+            id,
+        })
+    }
 }
 
 impl From<ObjectFqdn> for ArtifactId {
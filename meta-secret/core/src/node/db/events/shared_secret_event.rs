@@ -1,4 +1,7 @@
-use crate::node::common::model::secret::{SecretDistributionData, SsClaim, SsDeclineData, SsLogData};
+use crate::node::common::model::secret::{
+    SecretDistributionData, SsApprovalData, SsCancelClaimData, SsClaim, SsDeclineData, SsLogData,
+    SsVetoData,
+};
 use crate::node::db::events::error::LogEventCastError;
 use crate::node::db::events::generic_log_event::{
     GenericKvLogEvent, KeyExtractor, ObjIdExtractor, ToGenericEvent,
@@ -13,6 +16,9 @@ pub enum SsWorkflowObject {
     Recovery(KvLogEvent<SecretDistributionData>),
     Distribution(KvLogEvent<SecretDistributionData>),
     Decline(KvLogEvent<SsDeclineData>),
+    Approval(KvLogEvent<SsApprovalData>),
+    CancelClaim(KvLogEvent<SsCancelClaimData>),
+    Veto(KvLogEvent<SsVetoData>),
 }
 
 impl KeyExtractor for SsWorkflowObject {
@@ -21,6 +27,9 @@ impl KeyExtractor for SsWorkflowObject {
             SsWorkflowObject::Distribution(event) => event.key.clone(),
             SsWorkflowObject::Recovery(event) => event.key.clone(),
             SsWorkflowObject::Decline(event) => event.key.clone(),
+            SsWorkflowObject::Approval(event) => event.key.clone(),
+            SsWorkflowObject::CancelClaim(event) => event.key.clone(),
+            SsWorkflowObject::Veto(event) => event.key.clone(),
         }
     }
 }
@@ -31,6 +40,9 @@ impl SsWorkflowObject {
             SsWorkflowObject::Recovery(claim) => Ok(claim.value),
             SsWorkflowObject::Distribution(dist) => Ok(dist.value),
             SsWorkflowObject::Decline(_) => bail!("Decline has no distribution data"),
+            SsWorkflowObject::Approval(_) => bail!("Approval has no distribution data"),
+            SsWorkflowObject::CancelClaim(_) => bail!("Cancellation has no distribution data"),
+            SsWorkflowObject::Veto(_) => bail!("Veto has no distribution data"),
         }
     }
 }
@@ -93,6 +105,9 @@ impl ObjIdExtractor for SsWorkflowObject {
             SsWorkflowObject::Distribution(event) => event.key.obj_id.clone(),
             SsWorkflowObject::Recovery(event) => event.key.obj_id.clone(),
             SsWorkflowObject::Decline(event) => event.key.obj_id.clone(),
+            SsWorkflowObject::Approval(event) => event.key.obj_id.clone(),
+            SsWorkflowObject::CancelClaim(event) => event.key.obj_id.clone(),
+            SsWorkflowObject::Veto(event) => event.key.obj_id.clone(),
         }
     }
 }
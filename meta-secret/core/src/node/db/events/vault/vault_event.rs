@@ -18,10 +18,7 @@ impl VaultObject {
         let desc = VaultDescriptor::from(vault_name.clone());
         let vault_data = VaultData::from(candidate);
 
-        let sign_up_event = KvLogEvent {
-            key: KvKey::from(desc),
-            value: vault_data,
-        };
+        let sign_up_event = KvLogEvent::new(KvKey::from(desc), vault_data);
         VaultObject(sign_up_event)
     }
 
@@ -1,5 +1,14 @@
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::key_pair::DsaKeyPair;
+use crate::crypto::keys::{DsaPk, OpenBox};
+use crate::node::common::clock::EventTime;
+use crate::node::common::model::device::common::DeviceId;
 use crate::node::common::model::meta_pass::MetaPasswordId;
+use crate::node::common::model::secret::{BreakGlassPolicy, RecoveryPolicy};
+use crate::node::common::model::sorted_set;
 use crate::node::common::model::user::common::{UserData, UserDataMember, UserMembership};
+use crate::node::common::model::vault::invite::{InviteRecord, InviteTicket};
+use crate::node::common::model::vault::label::VaultLabel;
 use crate::node::common::model::vault::vault::VaultName;
 use crate::node::db::descriptors::vault_descriptor::VaultLogDescriptor;
 use crate::node::db::events::error::LogEventCastError;
@@ -23,10 +32,10 @@ pub struct VaultLogObject(pub KvLogEvent<VaultActionEvents>);
 
 impl VaultLogObject {
     pub fn create(owner: UserDataMember) -> Self {
-        Self(KvLogEvent {
-            key: KvKey::from(VaultLogDescriptor::from(owner.user_data.vault_name())),
-            value: VaultActionEvents::default(),
-        })
+        Self(KvLogEvent::new(
+            KvKey::from(VaultLogDescriptor::from(owner.user_data.vault_name())),
+            VaultActionEvents::default(),
+        ))
     }
 }
 
@@ -64,8 +73,37 @@ impl ObjIdExtractor for VaultLogObject {
 #[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VaultActionEvents {
+    #[serde(serialize_with = "sorted_set::serialize")]
     pub requests: HashSet<VaultActionRequestEvent>,
+    #[serde(serialize_with = "sorted_set::serialize")]
     pub updates: HashSet<VaultActionUpdateEvent>,
+    /// When the pending requests were made, requests of older devices have no time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub request_times: Vec<VaultRequestTime>,
+    /// Members that have accepted the pending join requests, a candidate joins the vault
+    /// once the approvals reach the join quorum of the vault
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub join_approvals: Vec<JoinApproval>,
+    /// Settings the members have proposed to loosen, they are applied once
+    /// as many members as the join quorum have proposed the same settings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub settings_proposals: Vec<UpdateVaultSettingsEvent>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultRequestTime {
+    pub request: VaultActionRequestEvent,
+    pub time: EventTime,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JoinApproval {
+    pub request: JoinClusterEvent,
+    /// The signed updates of the members, the vault checks every signature against
+    /// the keys it holds before counting the approval
+    pub approvals: Vec<UpdateMembershipEvent>,
 }
 
 impl VaultActionEvents {
@@ -101,13 +139,53 @@ impl VaultActionEvents {
         self
     }
 
+    /// Same as [`Self::request`] but keeps the time of the request
+    pub fn timed_request(
+        mut self,
+        request: VaultActionRequestEvent,
+        time: Option<EventTime>,
+    ) -> Self {
+        if let Some(time) = time {
+            self.request_times
+                .retain(|request_time| request_time.request != request);
+            self.request_times.push(VaultRequestTime {
+                request: request.clone(),
+                time,
+            });
+        }
+        self.request(request)
+    }
+
+    /// Decrypts the device names of the join candidates for display, see `VaultLabel`
+    pub fn open_device_names(mut self, label: &VaultLabel) -> Self {
+        self.requests = self
+            .requests
+            .into_iter()
+            .map(|request| match request {
+                VaultActionRequestEvent::JoinCluster(mut join_request) => {
+                    join_request.candidate = label.open_user(join_request.candidate);
+                    VaultActionRequestEvent::JoinCluster(join_request)
+                }
+                request => request,
+            })
+            .collect();
+        self
+    }
+
+    pub fn request_time(&self, request: &VaultActionRequestEvent) -> Option<&EventTime> {
+        self.request_times
+            .iter()
+            .find(|request_time| request_time.request.eq(request))
+            .map(|request_time| &request_time.time)
+    }
+
     pub fn apply(mut self, upd_event: VaultActionUpdateEvent) -> Self {
         match &upd_event {
             VaultActionUpdateEvent::UpdateMembership(update) => {
                 let request = VaultActionRequestEvent::JoinCluster(update.request.clone());
-                let removed = self.requests.remove(&request);
-                // if corresponding request exists we can apply the update
-                if removed {
+                // the request stays in the log until the vault has enough approvals
+                // to take it in or a member declines it, see VaultAggregate
+                if self.requests.contains(&request) {
                     self.updates.insert(upd_event);
                 } else {
                     info!(
@@ -129,11 +207,131 @@ impl VaultActionEvents {
                     );
                 }
             }
+            VaultActionUpdateEvent::RotateDeviceKeys(event) => {
+                let request = VaultActionRequestEvent::RotateDeviceKeys(event.clone());
+                let removed = self.requests.remove(&request);
+                if removed {
+                    self.updates.insert(upd_event);
+                } else {
+                    info!(
+                        "Corresponding request not found: {:?}, update won't be applied",
+                        request
+                    );
+                }
+            }
+            VaultActionUpdateEvent::AddInvite(event) => {
+                let request = VaultActionRequestEvent::AddInvite(event.clone());
+                let removed = self.requests.remove(&request);
+                if removed {
+                    self.updates.insert(upd_event);
+                } else {
+                    info!(
+                        "Corresponding request not found: {:?}, update won't be applied",
+                        request
+                    );
+                }
+            }
+            VaultActionUpdateEvent::UpdateSettings(event) => {
+                let request = VaultActionRequestEvent::UpdateSettings(event.clone());
+                let removed = self.requests.remove(&request);
+                if removed {
+                    self.updates.insert(upd_event);
+                } else {
+                    info!(
+                        "Corresponding request not found: {:?}, update won't be applied",
+                        request
+                    );
+                }
+            }
             VaultActionUpdateEvent::AddToPending { .. } => {
                 self.updates.insert(upd_event);
             }
         };
 
+        let requests = &self.requests;
+        self.request_times
+            .retain(|request_time| requests.contains(&request_time.request));
+
+        self
+    }
+
+    /// The pending join request of the candidate, with the invite it has presented
+    pub fn find_join_request(&self, candidate: &UserData) -> Option<JoinClusterEvent> {
+        self.requests.iter().find_map(|request| match request {
+            VaultActionRequestEvent::JoinCluster(join) if join.candidate.eq(candidate) => {
+                Some(join.clone())
+            }
+            _ => None,
+        })
+    }
+
+    pub fn join_approvals(&self, request: &JoinClusterEvent) -> &[UpdateMembershipEvent] {
+        self.join_approvals
+            .iter()
+            .find(|approval| approval.request.eq(request))
+            .map(|approval| approval.approvals.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn join_approvers(&self, request: &JoinClusterEvent) -> Vec<DeviceId> {
+        self.join_approvals(request)
+            .iter()
+            .map(|approval| approval.sender_id().clone())
+            .collect()
+    }
+
+    pub fn approve_join(mut self, update: UpdateMembershipEvent) -> Self {
+        match self
+            .join_approvals
+            .iter_mut()
+            .find(|approval| approval.request.eq(&update.request))
+        {
+            Some(approval) => {
+                let is_new = !approval
+                    .approvals
+                    .iter()
+                    .any(|existing| existing.sender_id().eq(update.sender_id()));
+                if is_new {
+                    approval.approvals.push(update);
+                }
+            }
+            None => self.join_approvals.push(JoinApproval {
+                request: update.request.clone(),
+                approvals: vec![update],
+            }),
+        }
+        self
+    }
+
+    /// Keeps the latest proposal of every member, returns the members that have proposed the same settings
+    pub fn propose_settings(mut self, proposal: UpdateVaultSettingsEvent) -> (Self, Vec<DeviceId>) {
+        self.settings_proposals
+            .retain(|existing| existing.sender_id() != proposal.sender_id());
+        self.settings_proposals.push(proposal.clone());
+
+        let proposers = self
+            .settings_proposals
+            .iter()
+            .filter(|existing| existing.has_same_settings(&proposal))
+            .map(|existing| existing.sender_id().clone())
+            .collect();
+        (self, proposers)
+    }
+
+    /// The proposals are dropped once the settings of the vault have changed
+    pub fn close_settings_proposals(mut self) -> Self {
+        self.settings_proposals.clear();
+        self
+    }
+
+    /// Removes the join request along with its approvals once the candidate is accepted or declined
+    pub fn close_join_request(mut self, request: &JoinClusterEvent) -> Self {
+        let join_request = VaultActionRequestEvent::JoinCluster(request.clone());
+        self.requests.remove(&join_request);
+        self.request_times
+            .retain(|request_time| request_time.request != join_request);
+        self.join_approvals
+            .retain(|approval| !approval.request.eq(request));
         self
     }
 
@@ -164,6 +362,9 @@ pub enum VaultActionInitEvent {
 pub enum VaultActionRequestEvent {
     JoinCluster(JoinClusterEvent),
     AddMetaPass(AddMetaPassEvent),
+    RotateDeviceKeys(RotateDeviceKeysEvent),
+    AddInvite(AddInviteEvent),
+    UpdateSettings(UpdateVaultSettingsEvent),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, From, Serialize, Deserialize)]
@@ -172,11 +373,55 @@ pub struct CreateVaultEvent {
     pub owner: UserDataMember,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, From, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[wasm_bindgen(getter_with_clone)]
 pub struct JoinClusterEvent {
     pub candidate: UserData,
+    /// The invite the candidate has got from a member
+    #[wasm_bindgen(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite: Option<InviteTicket>,
+}
+
+impl From<UserData> for JoinClusterEvent {
+    fn from(candidate: UserData) -> Self {
+        Self {
+            candidate,
+            invite: None,
+        }
+    }
+}
+
+/// A member makes an invite, the vault keeps it until the candidate is accepted or declined
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddInviteEvent {
+    pub sender: UserDataMember,
+    pub invite: InviteRecord,
+}
+
+/// A member changes the rules of the vault, the settings left out stay as they are
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateVaultSettingsEvent {
+    pub sender: UserDataMember,
+    /// Approvals of the members a join request needs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub join_quorum: Option<usize>,
+    /// Devices join the vault with an invite of a member only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub require_invite: Option<bool>,
+}
+
+impl UpdateVaultSettingsEvent {
+    pub fn sender_id(&self) -> &DeviceId {
+        &self.sender.user_data.device.device_id
+    }
+
+    pub fn has_same_settings(&self, other: &UpdateVaultSettingsEvent) -> bool {
+        self.join_quorum == other.join_quorum && self.require_invite == other.require_invite
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -184,6 +429,61 @@ pub struct JoinClusterEvent {
 pub struct AddMetaPassEvent {
     pub sender: UserDataMember,
     pub meta_pass_id: MetaPasswordId,
+    /// Approvals the recovery of a new secret requires, ignored for the secrets the vault has
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recovery_policy: Option<RecoveryPolicy>,
+    /// Opts a new secret in to break-glass recovery, ignored for the secrets the vault has
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub break_glass: Option<BreakGlassPolicy>,
+}
+
+/// A member replaces the keys of its device. The event is signed with the old dsa key,
+/// so only the owner of the current keys is able to rotate them
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotateDeviceKeysEvent {
+    /// The member with the keys being replaced
+    pub member: UserDataMember,
+    pub new_keys: OpenBox,
+    pub signature: Base64Text,
+}
+
+impl RotateDeviceKeysEvent {
+    pub fn sign(member: UserDataMember, new_keys: OpenBox, old_dsa: &DsaKeyPair) -> Result<Self> {
+        let signature = old_dsa.sign(Self::signed_text(&member, &new_keys)?);
+        Ok(Self {
+            member,
+            new_keys,
+            signature,
+        })
+    }
+
+    /// Checks that the new keys are signed by the old dsa key of the device
+    pub fn verify(&self) -> Result<()> {
+        let signed_text = Self::signed_text(&self.member, &self.new_keys)?;
+        self.member
+            .user()
+            .device
+            .keys
+            .dsa_pk
+            .verify(&signed_text, &self.signature)?;
+        Ok(())
+    }
+
+    /// The same member with the new keys
+    pub fn rotated_member(&self) -> UserDataMember {
+        let user = self.member.user();
+        UserDataMember {
+            user_data: UserData {
+                vault_name: user.vault_name(),
+                device: user.device.with_keys(self.new_keys.clone()),
+            },
+        }
+    }
+
+    fn signed_text(member: &UserDataMember, new_keys: &OpenBox) -> Result<String> {
+        Ok(serde_json::to_string(&(member, new_keys))?)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -192,6 +492,45 @@ pub struct UpdateMembershipEvent {
     pub request: JoinClusterEvent,
     pub sender: UserDataMember,
     pub update: UserMembership,
+    /// Made by the dsa key of the sender, the sender field alone is just a claim
+    pub signature: Base64Text,
+}
+
+impl UpdateMembershipEvent {
+    pub fn sign(
+        request: JoinClusterEvent,
+        sender: UserDataMember,
+        update: UserMembership,
+        dsa: &DsaKeyPair,
+    ) -> Result<Self> {
+        let signature = dsa.sign(Self::signed_text(&request, &sender, &update)?);
+        Ok(Self {
+            request,
+            sender,
+            update,
+            signature,
+        })
+    }
+
+    /// Checks the signature against the key the vault holds for the sender,
+    /// not against the keys the event carries
+    pub fn verify(&self, sender_dsa_pk: &DsaPk) -> Result<()> {
+        let signed_text = Self::signed_text(&self.request, &self.sender, &self.update)?;
+        sender_dsa_pk.verify(&signed_text, &self.signature)?;
+        Ok(())
+    }
+
+    pub fn sender_id(&self) -> &DeviceId {
+        &self.sender.user_data.device.device_id
+    }
+
+    fn signed_text(
+        request: &JoinClusterEvent,
+        sender: &UserDataMember,
+        update: &UserMembership,
+    ) -> Result<String> {
+        Ok(serde_json::to_string(&(request, sender, update))?)
+    }
 }
 
 impl VaultActionRequestEvent {
@@ -199,6 +538,9 @@ impl VaultActionRequestEvent {
         let name = match self {
             VaultActionRequestEvent::JoinCluster { .. } => "JoinRequest",
             VaultActionRequestEvent::AddMetaPass { .. } => "AddMetaPasswordRequest",
+            VaultActionRequestEvent::RotateDeviceKeys { .. } => "RotateDeviceKeysRequest",
+            VaultActionRequestEvent::AddInvite { .. } => "AddInviteRequest",
+            VaultActionRequestEvent::UpdateSettings { .. } => "UpdateSettingsRequest",
         };
 
         String::from(name)
@@ -209,11 +551,22 @@ impl VaultActionRequestEvent {
 #[serde(rename_all = "camelCase")]
 pub enum VaultActionUpdateEvent {
     /// There is no corresponding request for this event (server prematurely adds candidate to pending list)
-    AddToPending { candidate: UserData },
+    AddToPending {
+        candidate: UserData,
+        /// The invite the candidate has presented
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        invite: Option<InviteTicket>,
+    },
     /// When the device becomes a member of the vault, it can change membership of other members
-    UpdateMembership(UpdateMembershipEvent),
+    UpdateMembership(Box<UpdateMembershipEvent>),
     /// A member can add a new meta password into the vault
     AddMetaPass(AddMetaPassEvent),
+    /// A member replaces the keys of its device
+    RotateDeviceKeys(RotateDeviceKeysEvent),
+    /// A member invites a new device into the vault
+    AddInvite(AddInviteEvent),
+    /// A member changes the settings of the vault
+    UpdateSettings(UpdateVaultSettingsEvent),
 }
 
 impl VaultActionUpdateEvent {
@@ -225,7 +578,16 @@ impl VaultActionUpdateEvent {
             VaultActionUpdateEvent::AddMetaPass(AddMetaPassEvent { sender, .. }) => {
                 sender.user_data.vault_name()
             }
-            VaultActionUpdateEvent::AddToPending { candidate } => candidate.vault_name(),
+            VaultActionUpdateEvent::RotateDeviceKeys(RotateDeviceKeysEvent { member, .. }) => {
+                member.user_data.vault_name()
+            }
+            VaultActionUpdateEvent::AddInvite(AddInviteEvent { sender, .. }) => {
+                sender.user_data.vault_name()
+            }
+            VaultActionUpdateEvent::UpdateSettings(UpdateVaultSettingsEvent { sender, .. }) => {
+                sender.user_data.vault_name()
+            }
+            VaultActionUpdateEvent::AddToPending { candidate, .. } => candidate.vault_name(),
         }
     }
 }
@@ -235,6 +597,13 @@ impl VaultActionRequestEvent {
         match self {
             VaultActionRequestEvent::JoinCluster(request) => request.candidate.vault_name(),
             VaultActionRequestEvent::AddMetaPass(request) => request.sender.user_data.vault_name(),
+            VaultActionRequestEvent::RotateDeviceKeys(request) => {
+                request.member.user_data.vault_name()
+            }
+            VaultActionRequestEvent::AddInvite(request) => request.sender.user_data.vault_name(),
+            VaultActionRequestEvent::UpdateSettings(request) => {
+                request.sender.user_data.vault_name()
+            }
         }
     }
 }
@@ -244,6 +613,9 @@ impl VaultActionUpdateEvent {
         let name = match self {
             VaultActionUpdateEvent::UpdateMembership { .. } => "UpdateMembership",
             VaultActionUpdateEvent::AddMetaPass { .. } => "AddMetaPassword",
+            VaultActionUpdateEvent::RotateDeviceKeys { .. } => "RotateDeviceKeys",
+            VaultActionUpdateEvent::AddInvite { .. } => "AddInvite",
+            VaultActionUpdateEvent::UpdateSettings { .. } => "UpdateSettings",
             VaultActionUpdateEvent::AddToPending { .. } => "AddToPending",
         };
 
@@ -285,6 +657,9 @@ impl VaultActionEvent {
                 let user = match request {
                     VaultActionRequestEvent::JoinCluster(event) => &event.candidate,
                     VaultActionRequestEvent::AddMetaPass(event) => &event.sender.user_data,
+                    VaultActionRequestEvent::RotateDeviceKeys(event) => &event.member.user_data,
+                    VaultActionRequestEvent::AddInvite(event) => &event.sender.user_data,
+                    VaultActionRequestEvent::UpdateSettings(event) => &event.sender.user_data,
                 };
                 user.vault_name()
             }
@@ -313,27 +688,28 @@ mod test {
         let client_creds = fixture.state.user_creds.client;
         let client_b_creds = fixture.state.user_creds.client_b;
 
-        let join_request = JoinClusterEvent {
-            candidate: client_creds.user(),
-        };
+        let join_request = JoinClusterEvent::from(client_creds.user());
         let event =
             VaultActionEvent::Request(VaultActionRequestEvent::JoinCluster(join_request.clone()));
 
         let actions = VaultActionEvents::default().apply_event(event);
         assert_eq!(actions.requests.len(), 1);
 
-        let update = VaultActionUpdateEvent::UpdateMembership(UpdateMembershipEvent {
-            request: join_request,
-            sender: UserDataMember {
+        let update = UpdateMembershipEvent::sign(
+            join_request,
+            UserDataMember {
                 user_data: client_creds.user(),
             },
-            update: UserMembership::Member(UserDataMember {
+            UserMembership::Member(UserDataMember {
                 user_data: client_b_creds.user(),
             }),
-        });
+            &client_creds.device_creds.key_manager()?.dsa,
+        )?;
+        let update = VaultActionUpdateEvent::UpdateMembership(Box::new(update));
         let event = VaultActionEvent::Update(update);
         let with_update_vault_request = actions.apply_event(event);
-        assert_eq!(with_update_vault_request.requests.len(), 0);
+        // the request is closed by the vault aggregate once the join quorum is reached
+        assert_eq!(with_update_vault_request.requests.len(), 1);
         assert_eq!(with_update_vault_request.updates.len(), 1);
 
         Ok(())
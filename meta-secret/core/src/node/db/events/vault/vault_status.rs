@@ -18,13 +18,7 @@ impl VaultStatusObject {
         let user_id = status.user().user_id();
         let desc = VaultStatusDescriptor::from(user_id).to_obj_desc();
 
-        VaultStatusObject(KvLogEvent {
-            key: KvKey {
-                obj_id: event_id,
-                obj_desc: desc,
-            },
-            value: status,
-        })
+        VaultStatusObject(KvLogEvent::new(KvKey::artifact(desc, event_id), status))
     }
 
     pub fn status(self) -> VaultStatus {
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::Arc;
 
 use async_mutex::Mutex;
@@ -9,7 +9,7 @@ use crate::node::db::events::generic_log_event::{
 };
 use crate::node::db::events::object_id::ArtifactId;
 use crate::node::db::repo::generic_db::{
-    DbCleanUpCommand, DeleteCommand, FindOneQuery, KvLogEventRepo, SaveCommand,
+    DbCleanUpCommand, DeleteCommand, FindObjectsQuery, FindOneQuery, KvLogEventRepo, SaveCommand,
 };
 use anyhow::Result;
 use tracing::instrument;
@@ -75,6 +75,19 @@ impl DbCleanUpCommand for InMemKvLogEventRepo {
 
 impl KvLogEventRepo for InMemKvLogEventRepo {}
 
+#[async_trait(? Send)]
+impl FindObjectsQuery for InMemKvLogEventRepo {
+    async fn find_object_names(&self, obj_type: &str) -> Result<Vec<String>> {
+        let db = self.db.lock().await;
+        let names: BTreeSet<String> = db
+            .keys()
+            .filter(|key| key.fqdn.obj_type == obj_type)
+            .map(|key| key.fqdn.obj_instance.clone())
+            .collect();
+        Ok(names.into_iter().collect())
+    }
+}
+
 impl InMemKvLogEventRepo {
     pub async fn get_db(&self) -> HashMap<ArtifactId, GenericKvLogEvent> {
         let db = self.db.lock().await;
@@ -157,10 +170,10 @@ use crate::crypto::key_pair::TransportDsaKeyPair;
 
         // Insert multiple events
         for i in 1..=5 {
-            let kv_event = KvLogEvent {
-                key: KvKey::artifact(creds_desc.clone().to_obj_desc(), id.clone()),
-                value: secure_device_creds.clone(),
-            };
+            let kv_event = KvLogEvent::new(
+                KvKey::artifact(creds_desc.clone().to_obj_desc(), id.clone()),
+                secure_device_creds.clone(),
+            );
             
             let creds_obj = DeviceCredsObject(kv_event);
             let test_event = creds_obj.to_generic();
@@ -1,3 +1,4 @@
+pub mod persistent_audit;
 pub mod persistent_device_log;
 pub mod persistent_object;
 pub mod persistent_shared_secret;
@@ -0,0 +1,319 @@
+use std::sync::Arc;
+
+use crate::crypto::key_pair::DsaKeyPair;
+use crate::crypto::keys::DsaPk;
+use crate::node::common::model::device::common::DeviceId;
+use crate::node::common::model::meta_pass::MetaPasswordId;
+use crate::node::common::model::vault::vault::VaultName;
+use crate::node::db::descriptors::audit_descriptor::{AuditKeyDescriptor, AuditLogDescriptor};
+use crate::node::db::descriptors::object_descriptor::ToObjectDescriptor;
+use crate::node::db::events::audit_event::{
+    AuditEntry, AuditError, AuditKeyObject, AuditLogObject, SignedAuditEntry,
+};
+use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
+use crate::node::db::objects::persistent_object::PersistentObject;
+use crate::node::db::repo::generic_db::KvLogEventRepo;
+use anyhow::{Result, bail};
+use derive_more::From;
+use tracing_attributes::instrument;
+
+/// Selects audit entries, an empty filter selects all of them
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditFilter {
+    /// Unix time in milliseconds, entries older than that are skipped
+    #[serde(default)]
+    pub since: Option<u64>,
+    #[serde(default)]
+    pub device: Option<DeviceId>,
+    #[serde(default)]
+    pub secret: Option<MetaPasswordId>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        let is_recent = self.since.is_none_or(|since| entry.time >= since);
+        let is_device = self
+            .device
+            .as_ref()
+            .is_none_or(|device| entry.device.eq(device));
+        let is_secret = self.secret.as_ref().is_none_or(|secret| {
+            entry
+                .action
+                .pass_id()
+                .is_some_and(|pass_id| pass_id.eq(secret))
+        });
+
+        is_recent && is_device && is_secret
+    }
+}
+
+#[derive(From)]
+pub struct PersistentAudit<Repo: KvLogEventRepo> {
+    pub p_obj: Arc<PersistentObject<Repo>>,
+}
+
+impl<Repo: KvLogEventRepo> PersistentAudit<Repo> {
+    /// Signs the entry and appends it to the audit log of the vault
+    #[instrument(skip(self, dsa))]
+    pub async fn record(
+        &self,
+        vault_name: VaultName,
+        entry: AuditEntry,
+        dsa: &DsaKeyPair,
+    ) -> Result<()> {
+        let obj_desc = AuditLogDescriptor::from(vault_name);
+        let free_id = self
+            .p_obj
+            .find_free_id_by_obj_desc(obj_desc.clone())
+            .await?;
+
+        let audit_event = AuditLogObject(KvLogEvent::new(
+            KvKey::artifact(obj_desc.to_obj_desc(), free_id),
+            SignedAuditEntry::sign(entry, dsa)?,
+        ));
+        self.p_obj.append(audit_event).await?;
+        Ok(())
+    }
+
+    /// Pins the audit key of the server. The first key is trusted, a different key
+    /// later on means the server (or whoever answers in its name) has changed
+    #[instrument(skip_all)]
+    pub async fn pin_key(&self, audit_key: DsaPk) -> Result<()> {
+        match self.find_pinned_key().await? {
+            None => {
+                self.p_obj.repo.save(AuditKeyObject::from(audit_key)).await?;
+                Ok(())
+            }
+            Some(pinned_key) if pinned_key.eq(&audit_key) => Ok(()),
+            Some(_) => bail!(AuditError::KeyMismatch),
+        }
+    }
+
+    pub async fn find_pinned_key(&self) -> Result<Option<DsaPk>> {
+        let maybe_key_event = self.p_obj.find_tail_event(AuditKeyDescriptor).await?;
+        Ok(maybe_key_event.map(|key_event| key_event.0.value))
+    }
+
+    /// Audit entries of the vault in the order they were recorded. All the entries
+    /// have to be signed by the pinned audit key of the server
+    pub async fn find(
+        &self,
+        vault_name: VaultName,
+        filter: &AuditFilter,
+    ) -> Result<Vec<AuditEntry>> {
+        let events: Vec<AuditLogObject> = self
+            .p_obj
+            .get_object_events_from_beginning(AuditLogDescriptor::from(vault_name))
+            .await?;
+
+        if events.is_empty() {
+            return Ok(vec![]);
+        }
+        let Some(signer) = self.find_pinned_key().await? else {
+            bail!(AuditError::KeyNotPinned);
+        };
+
+        let mut entries = vec![];
+        for event in events {
+            let signed_entry = event.to_entry();
+            signed_entry.verify(&signer)?;
+            if filter.matches(&signed_entry.entry) {
+                entries.push(signed_entry.entry);
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::key_pair::KeyPair;
+    use crate::crypto::utils::Id48bit;
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+    use crate::node::common::model::secret::{ClaimId, SsClaimId};
+    use crate::node::db::events::audit_event::{AuditAction, AuditError};
+
+    #[tokio::test]
+    async fn test_recorded_entries_are_filtered() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_dsa = registry.state.device_creds.server.key_manager()?.dsa;
+        let vault_name = registry
+            .state
+            .vault_data
+            .client_vault_member
+            .vault
+            .vault_name
+            .clone();
+        let client = registry.state.device_creds.client.device.device_id.clone();
+        let client_b = registry
+            .state
+            .device_creds
+            .client_b
+            .device
+            .device_id
+            .clone();
+        let pass_id = MetaPasswordId::build_from_str("audited").opaque();
+
+        let p_audit = PersistentAudit::from(registry.state.p_obj.server.clone());
+        p_audit.pin_key(server_dsa.pk()).await?;
+        let entries = vec![
+            AuditEntry {
+                time: 100,
+                device: client.clone(),
+                action: AuditAction::VaultCreated,
+            },
+            AuditEntry {
+                time: 200,
+                device: client_b.clone(),
+                action: AuditAction::JoinRequested,
+            },
+            AuditEntry {
+                time: 300,
+                device: client.clone(),
+                action: AuditAction::RecoveryRequested {
+                    claim: SsClaimId {
+                        id: ClaimId(Id48bit::generate()),
+                        pass_id: pass_id.clone(),
+                    },
+                },
+            },
+        ];
+        for entry in entries.clone() {
+            p_audit
+                .record(vault_name.clone(), entry, &server_dsa)
+                .await?;
+        }
+
+        let all = p_audit
+            .find(vault_name.clone(), &AuditFilter::default())
+            .await?;
+        assert_eq!(all, entries);
+
+        let since = AuditFilter {
+            since: Some(200),
+            ..AuditFilter::default()
+        };
+        assert_eq!(
+            p_audit.find(vault_name.clone(), &since).await?,
+            entries[1..]
+        );
+
+        let of_device = AuditFilter {
+            device: Some(client_b),
+            ..AuditFilter::default()
+        };
+        assert_eq!(
+            p_audit.find(vault_name.clone(), &of_device).await?,
+            entries[1..2]
+        );
+
+        let of_secret = AuditFilter {
+            secret: Some(pass_id),
+            ..AuditFilter::default()
+        };
+        assert_eq!(p_audit.find(vault_name, &of_secret).await?, entries[2..]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_entries_of_another_signer_are_rejected() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_dsa = registry.state.device_creds.server.key_manager()?.dsa;
+        let client_dsa = registry.state.device_creds.client.key_manager()?.dsa;
+        let vault_name = registry
+            .state
+            .vault_data
+            .client_vault_member
+            .vault
+            .vault_name
+            .clone();
+        let client = registry.state.device_creds.client.device.device_id.clone();
+
+        let p_audit = PersistentAudit::from(registry.state.p_obj.server.clone());
+        p_audit.pin_key(server_dsa.pk()).await?;
+        for dsa in [&server_dsa, &client_dsa] {
+            let entry = AuditEntry {
+                time: 100,
+                device: client.clone(),
+                action: AuditAction::KeysRotated,
+            };
+            p_audit.record(vault_name.clone(), entry, dsa).await?;
+        }
+
+        let err = p_audit
+            .find(vault_name, &AuditFilter::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AuditError>(),
+            Some(AuditError::UnknownSigner { .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_log_started_by_another_signer_is_rejected() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_dsa = registry.state.device_creds.server.key_manager()?.dsa;
+        let client_dsa = registry.state.device_creds.client.key_manager()?.dsa;
+        let vault_name = registry
+            .state
+            .vault_data
+            .client_vault_member
+            .vault
+            .vault_name
+            .clone();
+        let client = registry.state.device_creds.client.device.device_id.clone();
+
+        let p_audit = PersistentAudit::from(registry.state.p_obj.client.clone());
+        let entry = AuditEntry {
+            time: 100,
+            device: client,
+            action: AuditAction::KeysRotated,
+        };
+        p_audit.record(vault_name.clone(), entry, &client_dsa).await?;
+
+        let err = p_audit
+            .find(vault_name.clone(), &AuditFilter::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AuditError>(),
+            Some(AuditError::KeyNotPinned)
+        ));
+
+        p_audit.pin_key(server_dsa.pk()).await?;
+        let err = p_audit
+            .find(vault_name, &AuditFilter::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AuditError>(),
+            Some(AuditError::UnknownSigner { .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pinned_key_is_not_replaced() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_dsa = registry.state.device_creds.server.key_manager()?.dsa;
+        let client_dsa = registry.state.device_creds.client.key_manager()?.dsa;
+
+        let p_audit = PersistentAudit::from(registry.state.p_obj.client.clone());
+        p_audit.pin_key(server_dsa.pk()).await?;
+        p_audit.pin_key(server_dsa.pk()).await?;
+
+        let err = p_audit.pin_key(client_dsa.pk()).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<AuditError>(),
+            Some(AuditError::KeyMismatch)
+        ));
+        assert_eq!(p_audit.find_pinned_key().await?, Some(server_dsa.pk()));
+        Ok(())
+    }
+}
@@ -7,8 +7,9 @@ use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
 use crate::node::db::events::object_id::ArtifactId;
 use crate::node::db::events::vault::device_log_event::DeviceLogObject;
 use crate::node::db::events::vault::vault_log_event::{
-    AddMetaPassEvent, CreateVaultEvent, JoinClusterEvent, UpdateMembershipEvent, VaultActionEvent,
-    VaultActionInitEvent, VaultActionRequestEvent, VaultActionUpdateEvent,
+    AddInviteEvent, AddMetaPassEvent, CreateVaultEvent, JoinClusterEvent, RotateDeviceKeysEvent,
+    UpdateMembershipEvent, UpdateVaultSettingsEvent, VaultActionEvent, VaultActionInitEvent,
+    VaultActionRequestEvent, VaultActionUpdateEvent,
 };
 use crate::node::db::objects::persistent_object::PersistentObject;
 use crate::node::db::repo::generic_db::KvLogEventRepo;
@@ -38,12 +39,12 @@ impl<Repo: KvLogEventRepo> PersistentDeviceLog<Repo> {
 
         let free_key = self.get_device_log_free_key(member_user).await?;
 
-        let join_request = DeviceLogObject(KvLogEvent {
-            key: free_key,
-            value: VaultActionEvent::Update(VaultActionUpdateEvent::UpdateMembership(update)),
-        });
+        let join_request = DeviceLogObject(KvLogEvent::new(
+            free_key,
+            VaultActionEvent::Update(VaultActionUpdateEvent::UpdateMembership(Box::new(update))),
+        ));
 
-        self.p_obj.repo.save(join_request).await?;
+        self.p_obj.append(join_request).await?;
 
         Ok(())
     }
@@ -57,12 +58,12 @@ impl<Repo: KvLogEventRepo> PersistentDeviceLog<Repo> {
                 owner: UserDataMember::from(user.clone()),
             });
             let upd = VaultActionEvent::Init(create_action);
-            DeviceLogObject(KvLogEvent {
-                key: self.get_device_log_free_key(user).await?,
-                value: upd,
-            })
+            DeviceLogObject(KvLogEvent::new(
+                self.get_device_log_free_key(user).await?,
+                upd,
+            ))
         };
-        self.p_obj.repo.save(create_request).await?;
+        self.p_obj.append(create_request).await?;
 
         Ok(())
     }
@@ -75,28 +76,67 @@ impl<Repo: KvLogEventRepo> PersistentDeviceLog<Repo> {
         let meta_pass = {
             let add_meta_pass = VaultActionRequestEvent::AddMetaPass(meta_pass_event.clone());
 
-            DeviceLogObject(KvLogEvent {
-                key: self
-                    .get_device_log_free_key(meta_pass_event.sender.user())
+            DeviceLogObject(KvLogEvent::new(
+                self.get_device_log_free_key(meta_pass_event.sender.user())
                     .await?,
-                value: VaultActionEvent::Request(add_meta_pass),
-            })
+                VaultActionEvent::Request(add_meta_pass),
+            ))
         };
 
-        self.p_obj.repo.save(meta_pass).await?;
+        self.p_obj.append(meta_pass).await?;
 
         Ok(())
     }
 
     #[instrument(skip_all)]
-    pub async fn save_join_request(&self, user: &UserData) -> Result<()> {
+    pub async fn save_add_invite_request(&self, add_invite: AddInviteEvent) -> Result<()> {
+        let key = self
+            .get_device_log_free_key(add_invite.sender.user())
+            .await?;
+        let request = VaultActionRequestEvent::AddInvite(add_invite);
+
+        let invite = DeviceLogObject(KvLogEvent::new(key, VaultActionEvent::Request(request)));
+        self.p_obj.append(invite).await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    pub async fn save_update_settings_request(
+        &self,
+        settings: UpdateVaultSettingsEvent,
+    ) -> Result<()> {
+        let key = self.get_device_log_free_key(settings.sender.user()).await?;
+        let request = VaultActionRequestEvent::UpdateSettings(settings);
+
+        let update = DeviceLogObject(KvLogEvent::new(key, VaultActionEvent::Request(request)));
+        self.p_obj.append(update).await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    pub async fn save_rotate_device_keys_request(
+        &self,
+        rotation: RotateDeviceKeysEvent,
+    ) -> Result<()> {
+        let key = self.get_device_log_free_key(rotation.member.user()).await?;
+        let request = VaultActionRequestEvent::RotateDeviceKeys(rotation);
+
+        let rotate_keys = DeviceLogObject(KvLogEvent::new(key, VaultActionEvent::Request(request)));
+        self.p_obj.append(rotate_keys).await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    pub async fn save_join_request(&self, join_event: JoinClusterEvent) -> Result<()> {
         info!("Save event: Join request");
-        let request = VaultActionRequestEvent::JoinCluster(JoinClusterEvent::from(user.clone()));
-        let join_request = DeviceLogObject(KvLogEvent {
-            key: self.get_device_log_free_key(user).await?,
-            value: VaultActionEvent::Request(request),
-        });
-        self.p_obj.repo.save(join_request).await?;
+        let key = self.get_device_log_free_key(&join_event.candidate).await?;
+        let request = VaultActionRequestEvent::JoinCluster(join_event);
+        let join_request =
+            DeviceLogObject(KvLogEvent::new(key, VaultActionEvent::Request(request)));
+        self.p_obj.append(join_request).await?;
 
         Ok(())
     }
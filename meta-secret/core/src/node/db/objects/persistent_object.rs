@@ -1,13 +1,18 @@
 use std::sync::Arc;
 
-use tracing::instrument;
+use tracing::{error, instrument};
 
+use crate::node::common::model::IdString;
 use crate::node::db::descriptors::object_descriptor::ToObjectDescriptor;
-use crate::node::db::events::generic_log_event::{GenericKvLogEventConvertible, ObjIdExtractor};
+use crate::node::db::events::event_chain;
+use crate::node::db::events::event_chain::EventChainError;
+use crate::node::db::events::generic_log_event::{
+    GenericKvLogEvent, GenericKvLogEventConvertible, ObjIdExtractor, ToGenericEvent,
+};
 use crate::node::db::events::object_id::{ArtifactId, Next};
 use crate::node::db::in_mem_db::InMemKvLogEventRepo;
 use crate::node::db::repo::generic_db::KvLogEventRepo;
-use anyhow::Result;
+use anyhow::{Result, bail};
 
 pub struct PersistentObject<Repo: KvLogEventRepo> {
     pub repo: Arc<Repo>,
@@ -28,16 +33,37 @@ impl<Repo: KvLogEventRepo> PersistentObject<Repo> {
     pub async fn find_object_events<T: GenericKvLogEventConvertible>(
         &self,
         tail_id: ArtifactId,
+    ) -> Result<Vec<T>> {
+        self.find_object_events_page(tail_id, usize::MAX).await
+    }
+
+    /// Same as [`Self::find_object_events`] but returns at most `limit` events
+    #[instrument(skip_all)]
+    pub async fn find_object_events_page<T: GenericKvLogEventConvertible>(
+        &self,
+        tail_id: ArtifactId,
+        limit: usize,
     ) -> Result<Vec<T>> {
         let mut commit_log: Vec<T> = vec![];
 
+        // the chain is verified from the event preceding the page
+        let mut maybe_prev_event = match tail_id.prev_id() {
+            Some(prev_id) => self.repo.find_one(prev_id).await?,
+            None => None,
+        };
+
         let mut curr_tail_id = tail_id.clone();
-        loop {
-            let maybe_curr_db_event = self.repo.find_one_obj(curr_tail_id.clone()).await?;
+        while commit_log.len() < limit {
+            let maybe_curr_db_event = self.repo.find_one(curr_tail_id.clone()).await?;
 
             if let Some(curr_db_event) = maybe_curr_db_event {
+                if let Some(prev_event) = &maybe_prev_event {
+                    event_chain::verify_link(prev_event, &curr_db_event)?;
+                }
+
                 curr_tail_id = curr_tail_id.next();
-                commit_log.push(curr_db_event);
+                maybe_prev_event = Some(curr_db_event.clone());
+                commit_log.push(T::try_from_event(curr_db_event)?);
             } else {
                 break;
             }
@@ -46,6 +72,50 @@ impl<Repo: KvLogEventRepo> PersistentObject<Repo> {
         Ok(commit_log)
     }
 
+    /// Saves a new event of an object linked to the previous event of the object,
+    /// returns the event as it was saved
+    #[instrument(skip_all)]
+    pub async fn append<T: ToGenericEvent>(&self, event: T) -> Result<GenericKvLogEvent> {
+        let event = self.link(event).await?;
+        self.repo.save(event.clone()).await?;
+        Ok(event)
+    }
+
+    /// Links the event to the previous event of the object,
+    /// the first event of the object starts the chain
+    #[instrument(skip_all)]
+    pub async fn link<T: ToGenericEvent>(&self, event: T) -> Result<GenericKvLogEvent> {
+        let event = event.to_generic();
+        let Some(prev_id) = event.obj_id().prev_id() else {
+            return Ok(event_chain::start(event));
+        };
+
+        match self.repo.find_one(prev_id).await? {
+            Some(prev_event) => event_chain::link(&prev_event, event),
+            None => Ok(event),
+        }
+    }
+
+    /// Saves an event received from another node,
+    /// the event has to continue the locally stored history of the object
+    #[instrument(skip_all)]
+    pub async fn save_verified<T: ToGenericEvent>(&self, event: T) -> Result<ArtifactId> {
+        let event = event.to_generic();
+        if let Some(prev_id) = event.obj_id().prev_id()
+            && let Some(prev_event) = self.repo.find_one(prev_id).await?
+            && event_chain::verify_link(&prev_event, &event).is_err()
+        {
+            let obj_id = event.obj_id().id_str();
+            error!("Event {} diverges from the local history", obj_id);
+            bail!(EventChainError::Divergence { obj_id });
+        }
+
+        if let Some(time) = event.time() {
+            time.observe();
+        }
+        self.repo.save(event).await
+    }
+
     #[instrument(skip_all)]
     pub async fn find_tail_event<Desc: ToObjectDescriptor>(
         &self,
@@ -173,3 +243,140 @@ pub mod fixture {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
+    use crate::meta_tests::fixture_util::fixture::states::EmptyState;
+    use crate::node::common::model::user::common::UserDataMember;
+    use crate::node::db::descriptors::vault_descriptor::VaultDescriptor;
+    use crate::node::db::events::event_chain;
+    use crate::node::db::events::event_chain::EventChainError;
+    use crate::node::db::events::generic_log_event::GenericKvLogEvent;
+    use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
+    use crate::node::db::events::object_id::{ArtifactId, Next};
+    use crate::node::db::events::vault::vault_event::VaultObject;
+    use crate::node::db::repo::generic_db::{DeleteCommand, FindOneQuery, SaveCommand};
+    use anyhow::Result;
+
+    /// Appends three events of the vault of the client, returns the id of the first one
+    async fn append_vault_events(registry: &FixtureRegistry<EmptyState>) -> Result<ArtifactId> {
+        let p_obj = registry.state.p_obj.client.clone();
+        let user = registry.state.user_creds.client.user();
+
+        let sign_up = VaultObject::sign_up(user.vault_name(), UserDataMember::from(user.clone()));
+        let mut key = KvKey::from(VaultDescriptor::from(user.vault_name()));
+        for _ in 0..3 {
+            let event = VaultObject(KvLogEvent::new(key.clone(), sign_up.clone().to_data()));
+            p_obj.append(event).await?;
+            key = key.next();
+        }
+
+        Ok(ArtifactId::from(VaultDescriptor::from(user.vault_name())))
+    }
+
+    #[tokio::test]
+    async fn test_appended_events_are_linked() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let first_id = append_vault_events(&registry).await?;
+
+        let events: Vec<VaultObject> = registry
+            .state
+            .p_obj
+            .client
+            .find_object_events(first_id)
+            .await?;
+
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].0.key.prev_hash, Some(event_chain::genesis()));
+        assert!(
+            events[1..]
+                .iter()
+                .all(|event| event.0.key.prev_hash.is_some())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unlinked_second_event_is_detected_on_read() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let p_obj = registry.state.p_obj.client.clone();
+        let first_id = append_vault_events(&registry).await?;
+
+        // the history is cut after the second event, which is rewritten without its link
+        p_obj.repo.delete(first_id.clone().next().next()).await;
+        let second_id = first_id.clone().next();
+        let Some(GenericKvLogEvent::Vault(mut rewritten)) = p_obj.repo.find_one(second_id.clone()).await?
+        else {
+            panic!("Vault event expected");
+        };
+        rewritten.0.value.users.clear();
+        rewritten.0.key.prev_hash = None;
+        p_obj.repo.delete(second_id).await;
+        p_obj.repo.save(rewritten).await?;
+
+        let err = p_obj
+            .find_object_events::<VaultObject>(first_id)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<EventChainError>(),
+            Some(EventChainError::BrokenLink { .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rewritten_event_is_detected_on_read() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let p_obj = registry.state.p_obj.client.clone();
+        let first_id = append_vault_events(&registry).await?;
+
+        let middle_id = first_id.clone().next();
+        let Some(GenericKvLogEvent::Vault(mut rewritten)) = p_obj.repo.find_one(middle_id).await?
+        else {
+            panic!("Vault event expected");
+        };
+        rewritten.0.value.users.clear();
+        p_obj.repo.save(rewritten).await?;
+
+        let err = p_obj
+            .find_object_events::<VaultObject>(first_id)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<EventChainError>(),
+            Some(EventChainError::BrokenLink { .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_diverged_event_is_not_saved() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_p_obj = registry.state.p_obj.server.clone();
+        let client_p_obj = registry.state.p_obj.client.clone();
+        let first_id = append_vault_events(&registry).await?;
+
+        // the server sends the first two events with the second one rewritten
+        let mut events: Vec<VaultObject> = client_p_obj.find_object_events(first_id).await?;
+        events[1].0.value.users.clear();
+        for event in events.iter().take(2) {
+            server_p_obj.repo.save(event.clone()).await?;
+        }
+
+        // the client has the original history, the next event of the server is linked to the rewritten one
+        let next_event = VaultObject(KvLogEvent::new(
+            events[2].0.key.clone(),
+            events[2].clone().to_data(),
+        ));
+        let next_event = server_p_obj.append(next_event).await?;
+
+        let err = client_p_obj.save_verified(next_event).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<EventChainError>(),
+            Some(EventChainError::Divergence { .. })
+        ));
+        Ok(())
+    }
+}
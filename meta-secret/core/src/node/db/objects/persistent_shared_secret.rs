@@ -7,7 +7,7 @@ use crate::node::db::descriptors::object_descriptor::ToObjectDescriptor;
 use crate::node::db::descriptors::shared_secret_descriptor::{
     SsDeviceLogDescriptor, SsLogDescriptor, SsWorkflowDescriptor,
 };
-use crate::node::db::events::generic_log_event::ToGenericEvent;
+use crate::node::db::events::generic_log_event::KeyExtractor;
 use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
 use crate::node::db::events::object_id::ArtifactId;
 use crate::node::db::events::shared_secret_event::{
@@ -63,6 +63,28 @@ impl<Repo: KvLogEventRepo> PersistentSharedSecret<Repo> {
         Ok(events)
     }
 
+    pub async fn get_approvals(&self, ss_claim: SsClaim) -> Result<Vec<SsWorkflowObject>> {
+        let mut events = vec![];
+        for recovery_id in ss_claim.recovery_db_ids() {
+            let desc = SsWorkflowDescriptor::Approval(recovery_id);
+            let tail_event = self.p_obj.find_tail_event(desc).await?;
+            if let Some(event) = tail_event {
+                events.push(event);
+            }
+        }
+        Ok(events)
+    }
+
+    pub async fn get_cancellation(&self, ss_claim: SsClaim) -> Result<Option<SsWorkflowObject>> {
+        let desc = SsWorkflowDescriptor::CancelClaim(ss_claim.dist_claim_id);
+        self.p_obj.find_tail_event(desc).await
+    }
+
+    pub async fn get_veto(&self, ss_claim: SsClaim) -> Result<Option<SsWorkflowObject>> {
+        let desc = SsWorkflowDescriptor::Veto(ss_claim.dist_claim_id);
+        self.p_obj.find_tail_event(desc).await
+    }
+
     pub async fn get_distributions(&self, ss_claim: SsClaim) -> Result<Vec<SsWorkflowObject>> {
         let mut events = vec![];
         for distribution_id in ss_claim.distribution_ids() {
@@ -112,26 +134,56 @@ impl<Repo: KvLogEventRepo> PersistentSharedSecret<Repo> {
     pub async fn save_ss_log_event(&self, claim: SsClaim) -> Result<()> {
         info!("Saving ss_log event");
 
+        let new_ss_log_event = self.ss_log_event_with_claim(claim).await?;
+        self.p_obj.append(new_ss_log_event).await?;
+
+        Ok(())
+    }
+
+    /// The ss log is written by the server, a client keeps its own view of a claim
+    /// only until the next sync: the event is not linked to the chain of the server
+    /// and gets replaced by the events of the server (see [`Self::find_ss_log_sync_id`])
+    #[instrument(skip(self))]
+    pub async fn save_local_ss_log_event(&self, claim: SsClaim) -> Result<()> {
+        info!("Saving local ss_log event");
+
+        let new_ss_log_event = self.ss_log_event_with_claim(claim).await?;
+        self.p_obj.repo.save(new_ss_log_event).await?;
+
+        Ok(())
+    }
+
+    async fn ss_log_event_with_claim(&self, claim: SsClaim) -> Result<SsLogObject> {
         let vault_name = claim.vault_name.clone();
 
         let maybe_ss_log_event = self.find_ss_log_tail_event(vault_name.clone()).await?;
 
-        let new_ss_log_event = match maybe_ss_log_event {
-            None => {
-                let ss_log_data = SsLogData::new(claim);
-                self.create_new_ss_log_object(ss_log_data, vault_name)
-                    .await?
-            }
-            Some(ss_log_event) => {
-                let new_log_data = ss_log_event.0.value.insert(claim);
-                self.create_new_ss_log_object(new_log_data, vault_name)
-                    .await?
-            }
+        let ss_log_data = match maybe_ss_log_event {
+            None => SsLogData::new(claim),
+            Some(ss_log_event) => ss_log_event.0.value.insert(claim),
         };
 
-        self.p_obj.repo.save(new_ss_log_event).await?;
+        self.create_new_ss_log_object(ss_log_data, vault_name).await
+    }
 
-        Ok(())
+    /// The id the client syncs the ss log from: the first of the trailing unlinked events
+    /// (local events or the events that precede the chains), all of them are requested again
+    #[instrument(skip(self))]
+    pub async fn find_ss_log_sync_id(&self, vault_name: VaultName) -> Result<ArtifactId> {
+        let obj_desc = SsLogDescriptor::from(vault_name);
+        let mut sync_id = self.p_obj.find_free_id_by_obj_desc(obj_desc).await?;
+
+        while let Some(prev_id) = sync_id.prev_id() {
+            let Some(prev_event) = self.p_obj.repo.find_one(prev_id.clone()).await? else {
+                break;
+            };
+            if prev_event.key().prev_hash.is_some() {
+                break;
+            }
+            sync_id = prev_id;
+        }
+
+        Ok(sync_id)
     }
 
     #[instrument(skip(self))]
@@ -149,10 +201,10 @@ impl<Repo: KvLogEventRepo> PersistentSharedSecret<Repo> {
             .find_free_id_by_obj_desc(obj_desc.clone())
             .await?;
 
-        Ok(SsLogObject(KvLogEvent {
-            key: KvKey::artifact(obj_desc.to_obj_desc(), free_id),
-            value: ss_log_data,
-        }))
+        Ok(SsLogObject(KvLogEvent::new(
+            KvKey::artifact(obj_desc.to_obj_desc(), free_id),
+            ss_log_data,
+        )))
     }
 }
 
@@ -167,12 +219,12 @@ impl<Repo: KvLogEventRepo> PersistentSharedSecret<Repo> {
             .find_free_id_by_obj_desc(obj_desc.clone())
             .await?;
 
-        let obj = SsDeviceLogObject(KvLogEvent {
-            key: KvKey::artifact(obj_desc.to_obj_desc(), free_id),
-            value: claim,
-        });
+        let obj = SsDeviceLogObject(KvLogEvent::new(
+            KvKey::artifact(obj_desc.to_obj_desc(), free_id),
+            claim,
+        ));
 
-        self.p_obj.repo.save(obj.to_generic()).await?;
+        self.p_obj.append(obj).await?;
 
         Ok(())
     }
@@ -204,10 +256,58 @@ mod test {
     use crate::meta_tests::fixture_util::fixture::FixtureRegistry;
     use crate::node::common::model::meta_pass::MetaPasswordId;
     use crate::node::common::model::secret::SecretDistributionType;
-    use crate::node::db::descriptors::shared_secret_descriptor::SsDeviceLogDescriptor;
-    use crate::node::db::events::shared_secret_event::SsDeviceLogObject;
+    use crate::node::db::descriptors::shared_secret_descriptor::{
+        SsDeviceLogDescriptor, SsLogDescriptor,
+    };
+    use crate::node::db::events::object_id::{ArtifactId, Next};
+    use crate::node::db::events::shared_secret_event::{SsDeviceLogObject, SsLogObject};
     use anyhow::Result;
 
+    #[tokio::test]
+    async fn test_local_ss_log_events_are_synced_again() -> Result<()> {
+        let registry = FixtureRegistry::empty();
+        let server_ss = super::PersistentSharedSecret::from(registry.state.p_obj.server.clone());
+        let client_ss = super::PersistentSharedSecret::from(registry.state.p_obj.client.clone());
+        let vault_member = registry.state.vault_data.client_vault_member;
+        let vault_name = vault_member.vault.vault_name.clone();
+
+        for pass in ["pass_1", "pass_2"] {
+            let claim = vault_member.create_split_claim(MetaPasswordId::build_from_str(pass));
+            server_ss.save_ss_log_event(claim).await?;
+        }
+
+        let first_id = ArtifactId::from(SsLogDescriptor::from(vault_name.clone()));
+        let server_events: Vec<SsLogObject> =
+            server_ss.p_obj.find_object_events(first_id.clone()).await?;
+        for event in server_events {
+            client_ss.p_obj.save_verified(event).await?;
+        }
+
+        let synced_id = first_id.clone().next().next();
+        assert_eq!(
+            client_ss.find_ss_log_sync_id(vault_name.clone()).await?,
+            synced_id
+        );
+
+        // a local view of a claim gets requested again and replaced by the events of the server
+        let claim = vault_member.create_split_claim(MetaPasswordId::build_from_str("pass_3"));
+        client_ss.save_local_ss_log_event(claim.clone()).await?;
+        assert_eq!(
+            client_ss.find_ss_log_sync_id(vault_name.clone()).await?,
+            synced_id
+        );
+
+        server_ss.save_ss_log_event(claim).await?;
+        let server_tail = server_ss.find_ss_log_tail_event(vault_name.clone()).await?;
+        client_ss.p_obj.save_verified(server_tail.unwrap()).await?;
+        assert_eq!(
+            client_ss.find_ss_log_sync_id(vault_name).await?,
+            synced_id.next()
+        );
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_save_claim_in_ss_device_log() -> Result<()> {
         // Setup
@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
+use crate::node::common::clock::EventTime;
 use crate::node::common::model::user::common::UserData;
 use crate::node::common::model::vault::vault::{VaultName, VaultStatus};
+use crate::node::db::descriptors::audit_descriptor::AuditLogDescriptor;
 use crate::node::db::descriptors::vault_descriptor::{
     VaultDescriptor, VaultLogDescriptor, VaultStatusDescriptor,
 };
@@ -25,6 +27,9 @@ pub struct VaultTail {
     pub vault_log: ArtifactId,
     pub vault: ArtifactId,
     pub vault_status: ArtifactId,
+    /// Older devices don't sync the audit log
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<ArtifactId>,
 }
 
 #[derive(From)]
@@ -59,10 +64,16 @@ impl<Repo: KvLogEventRepo> PersistentVault<Repo> {
             self.p_obj.find_free_id_by_obj_desc(obj_desc).await?
         };
 
+        let audit_log_free_id = {
+            let obj_desc = AuditLogDescriptor::from(user.vault_name());
+            self.p_obj.find_free_id_by_obj_desc(obj_desc).await?
+        };
+
         Ok(VaultTail {
             vault_log: vault_log_free_id,
             vault: vault_free_id,
             vault_status: vault_status_free_id,
+            audit_log: Some(audit_log_free_id),
         })
     }
 
@@ -90,11 +101,9 @@ impl<Repo: KvLogEventRepo> PersistentVault<Repo> {
             (None, None) => {
                 let desc = VaultStatusDescriptor::from(user.user_id());
                 let curr_status = VaultStatus::NotExists(user);
-                let obj = VaultStatusObject(KvLogEvent {
-                    key: KvKey::from(desc),
-                    value: curr_status.clone(),
-                });
-                self.p_obj.repo.save(obj).await?;
+                let obj =
+                    VaultStatusObject(KvLogEvent::new(KvKey::from(desc), curr_status.clone()));
+                self.p_obj.append(obj).await?;
                 curr_status
             }
             // Just in case - verify that membership is VaultNotExists
@@ -108,11 +117,11 @@ impl<Repo: KvLogEventRepo> PersistentVault<Repo> {
             (Some(vault_obj), None) => {
                 let status = vault_obj.to_data().status(user.clone());
 
-                let obj = VaultStatusObject(KvLogEvent {
-                    key: KvKey::from(VaultStatusDescriptor::from(user.user_id())),
-                    value: status.clone(),
-                });
-                self.p_obj.repo.save(obj).await?;
+                let obj = VaultStatusObject(KvLogEvent::new(
+                    KvKey::from(VaultStatusDescriptor::from(user.user_id())),
+                    status.clone(),
+                ));
+                self.p_obj.append(obj).await?;
                 status
             }
             // Verify that vault membership status is up to date
@@ -121,11 +130,11 @@ impl<Repo: KvLogEventRepo> PersistentVault<Repo> {
                 let membership_info = membership.clone().status();
 
                 if vault_info != membership_info {
-                    let obj = VaultStatusObject(KvLogEvent {
-                        key: membership.key().next(),
-                        value: vault_info.clone(),
-                    });
-                    self.p_obj.repo.save(obj).await?;
+                    let obj = VaultStatusObject(KvLogEvent::new(
+                        membership.key().next(),
+                        vault_info.clone(),
+                    ));
+                    self.p_obj.append(obj).await?;
                 }
 
                 vault_info
@@ -187,31 +196,30 @@ impl<Repo: KvLogEventRepo> PersistentVault<Repo> {
         let kv = self.get_vault_log_artifact(vault_name).await?;
         let next_key = kv.key().next();
 
-        let vault_log_event = VaultLogObject(KvLogEvent {
-            key: next_key,
-            value: events,
-        });
+        let vault_log_event = VaultLogObject(KvLogEvent::new(next_key, events));
 
-        self.p_obj.repo.save(vault_log_event).await?;
+        self.p_obj.append(vault_log_event).await?;
 
         Ok(())
     }
 
+    /// Adds the request to the pending requests of the vault, `time` is the time of the request
     pub async fn save_vault_log_request_event(
         &self,
         action_event: VaultActionRequestEvent,
+        time: Option<EventTime>,
     ) -> Result<()> {
         let kv = self
             .get_vault_log_artifact(action_event.vault_name())
             .await?;
         let next_key = kv.key().next();
 
-        let vault_log_event = VaultLogObject(KvLogEvent {
-            key: next_key,
-            value: kv.0.value.request(action_event),
-        });
+        let vault_log_event = VaultLogObject(KvLogEvent::new(
+            next_key,
+            kv.0.value.timed_request(action_event, time),
+        ));
 
-        self.p_obj.repo.save(vault_log_event).await?;
+        self.p_obj.append(vault_log_event).await?;
 
         Ok(())
     }
@@ -518,10 +526,10 @@ mod tests {
 
         // Create initial vault log object with empty action events
         let desc = VaultLogDescriptor::from(user.vault_name());
-        let initial_log = VaultLogObject(KvLogEvent {
-            key: KvKey::from(desc.clone()),
-            value: VaultActionEvents::default(),
-        });
+        let initial_log = VaultLogObject(KvLogEvent::new(
+            KvKey::from(desc.clone()),
+            VaultActionEvents::default(),
+        ));
         p_obj.repo.save(initial_log).await?;
 
         // Test get_vault_log_artifact retrieves the log we just created
@@ -529,9 +537,7 @@ mod tests {
         assert_eq!(log_artifact.0.value, VaultActionEvents::default());
 
         // Test save_vault_log_events by creating a join request event
-        let join_request = JoinClusterEvent {
-            candidate: user.clone(),
-        };
+        let join_request = JoinClusterEvent::from(user.clone());
         let new_events = VaultActionEvents::default()
             .request(VaultActionRequestEvent::JoinCluster(join_request));
         p_vault
@@ -545,11 +551,11 @@ mod tests {
 
         // Test save_vault_log_request_event by adding another join request
         // Note: This creates an identical request to the one we already added
-        let join_request2 = JoinClusterEvent {
-            candidate: user.clone(),
-        };
+        let join_request2 = JoinClusterEvent::from(user.clone());
         let request_event = VaultActionRequestEvent::JoinCluster(join_request2);
-        p_vault.save_vault_log_request_event(request_event).await?;
+        p_vault
+            .save_vault_log_request_event(request_event, None)
+            .await?;
 
         // Verify that the request count doesn't change
         // VaultActionEvents.requests is a HashSet, so identical requests are deduplicated
@@ -572,10 +578,10 @@ mod tests {
 
         // Create vault log and test again
         let desc = VaultLogDescriptor::from(user.vault_name());
-        let log_obj = VaultLogObject(KvLogEvent {
-            key: KvKey::from(desc),
-            value: VaultActionEvents::default(),
-        });
+        let log_obj = VaultLogObject(KvLogEvent::new(
+            KvKey::from(desc),
+            VaultActionEvents::default(),
+        ));
         p_obj.repo.save(log_obj).await?;
 
         let result = p_vault.vault_log(user.vault_name()).await?;
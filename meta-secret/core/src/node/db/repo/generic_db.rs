@@ -43,6 +43,22 @@ pub trait DbCleanUpCommand {
     async fn db_clean_up(&self);
 }
 
+/// Enumerates the objects of the repo, for server maintenance tools that don't know object names
+/// in advance. It is not a part of [`KvLogEventRepo`], clients never need it.
+#[async_trait(? Send)]
+pub trait FindObjectsQuery {
+    /// Names of all the objects of the given type (i.e. vault names for the `Vault` type), sorted
+    async fn find_object_names(&self, obj_type: &str) -> Result<Vec<String>>;
+}
+
+/// Object name of a db key (`"{obj_type}:{obj_name}::{seq}"`), if the key belongs to the type
+pub fn object_name_of_key(obj_type: &str, key: &str) -> Option<String> {
+    let (fqdn, seq) = key.rsplit_once("::")?;
+    seq.parse::<usize>().ok()?;
+    let obj_name = fqdn.strip_prefix(obj_type)?.strip_prefix(':')?;
+    Some(obj_name.to_string())
+}
+
 #[async_trait(? Send)]
 pub trait KvLogEventRepo:
     FindOneQuery + SaveCommand + DeleteCommand + DbCleanUpCommand + 'static
@@ -53,3 +69,27 @@ pub trait CommitLogDbConfig {
     fn db_name(&self) -> String;
     fn store_name(&self) -> String;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::common::model::vault::vault::VaultName;
+    use crate::node::common::model::IdString;
+    use crate::node::db::descriptors::vault_descriptor::{VaultDescriptor, VaultLogDescriptor};
+    use crate::node::db::events::object_id::Next;
+
+    #[test]
+    fn test_object_name_of_key() {
+        let vault_id = ArtifactId::from(VaultDescriptor::from(VaultName::from("my:vault")));
+        let vault_key = vault_id.next().id_str();
+
+        assert_eq!(
+            object_name_of_key("Vault", &vault_key),
+            Some(String::from("my:vault"))
+        );
+
+        let vault_log_key =
+            ArtifactId::from(VaultLogDescriptor::from(VaultName::from("my:vault"))).id_str();
+        assert_eq!(object_name_of_key("Vault", &vault_log_key), None);
+    }
+}
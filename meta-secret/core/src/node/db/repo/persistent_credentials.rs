@@ -4,10 +4,13 @@ use crate::node::common::model::device::device_creds::{
     DeviceCreds, DeviceCredsBuilder, SecureDeviceCreds,
 };
 use crate::node::common::model::user::user_creds::{SecureUserCreds, UserCreds, UserCredsBuilder};
+use crate::node::common::model::vault::label::VaultLabel;
 use crate::node::common::model::vault::vault::VaultName;
 use crate::node::db::descriptors::creds::{DeviceCredsDescriptor, UserCredsDescriptor};
+use crate::node::db::events::generic_log_event::GenericKvLogEventConvertible;
+use crate::node::db::events::kv_log_event::{KvKey, KvLogEvent};
 use crate::node::db::events::local_event::{DeviceCredsObject, UserCredsObject};
-use crate::node::db::events::object_id::ArtifactId;
+use crate::node::db::events::object_id::{ArtifactId, Next};
 use crate::node::db::objects::persistent_object::PersistentObject;
 use crate::node::db::repo::generic_db::KvLogEventRepo;
 use anyhow::{Result, bail};
@@ -23,8 +26,9 @@ pub struct PersistentCredentials<Repo: KvLogEventRepo> {
 
 impl<Repo: KvLogEventRepo> PersistentCredentials<Repo> {
     pub async fn get_device_creds(&self) -> Result<Option<DeviceCreds>> {
-        let maybe_secure_device_creds_obj =
-            self.p_obj.find_tail_event(DeviceCredsDescriptor).await?;
+        let maybe_secure_device_creds_obj: Option<DeviceCredsObject> = self
+            .find_current_creds(ArtifactId::from(DeviceCredsDescriptor))
+            .await?;
 
         match maybe_secure_device_creds_obj {
             None => Ok(None),
@@ -85,27 +89,106 @@ impl<Repo: KvLogEventRepo> PersistentCredentials<Repo> {
         self.p_obj.repo.save(creds_obj).await
     }
 
+    /// Keeps the new credentials (e.g. of a key rotation) in a pending slot next to the current ones,
+    /// the current ones are still needed until the rotation is complete
+    #[instrument(skip_all)]
+    pub async fn save_pending_user_creds(&self, user_creds: UserCreds) -> Result<()> {
+        let master_pk = self.master_key.pk()?;
+        let secure_user_creds = SecureUserCreds::build(user_creds.clone(), master_pk.clone())?;
+        let secure_device_creds = SecureDeviceCreds::build(user_creds.device_creds, master_pk)?;
+
+        let device_creds_obj = DeviceCredsObject(KvLogEvent::new(
+            KvKey::from(DeviceCredsDescriptor).next(),
+            secure_device_creds,
+        ));
+        let user_creds_obj = UserCredsObject(KvLogEvent::new(
+            KvKey::from(UserCredsDescriptor).next(),
+            secure_user_creds,
+        ));
+
+        self.delete_pending_user_creds().await;
+        self.p_obj.repo.save(device_creds_obj).await?;
+        self.p_obj.repo.save(user_creds_obj).await?;
+        Ok(())
+    }
+
+    /// The credentials of a rotation that hasn't been completed yet
+    #[instrument(skip_all)]
+    pub async fn get_pending_user_creds(&self) -> Result<Option<UserCreds>> {
+        let pending_id = ArtifactId::from(UserCredsDescriptor).next();
+        let maybe_pending_obj: Option<UserCredsObject> =
+            self.p_obj.repo.find_one_obj(pending_id).await?;
+
+        match maybe_pending_obj {
+            None => Ok(None),
+            Some(pending_obj) => Ok(Some(pending_obj.value().decrypt(&self.master_key)?)),
+        }
+    }
+
+    /// Makes the pending credentials the current ones (e.g. at the end of a key rotation).
+    /// The pending slot is written first and it's cleared last: if the swap is interrupted,
+    /// the credentials are read from the pending slot, the device never ends up without keys
+    #[instrument(skip_all)]
+    pub async fn replace_user_creds(&self, user_creds: UserCreds) -> Result<()> {
+        let is_pending = self
+            .get_pending_user_creds()
+            .await?
+            .is_some_and(|pending| pending == user_creds);
+        if !is_pending {
+            self.save_pending_user_creds(user_creds.clone()).await?;
+        }
+
+        self.p_obj
+            .repo
+            .delete(ArtifactId::from(DeviceCredsDescriptor))
+            .await;
+        self.save_device_creds(user_creds.device_creds.clone())
+            .await?;
+
+        self.p_obj
+            .repo
+            .delete(ArtifactId::from(UserCredsDescriptor))
+            .await;
+        self.save_user_creds(user_creds).await?;
+
+        self.delete_pending_user_creds().await;
+        Ok(())
+    }
+
+    async fn delete_pending_user_creds(&self) {
+        self.p_obj
+            .repo
+            .delete(ArtifactId::from(DeviceCredsDescriptor).next())
+            .await;
+        self.p_obj
+            .repo
+            .delete(ArtifactId::from(UserCredsDescriptor).next())
+            .await;
+    }
+
+    /// The current credentials are the first event of the object, the pending slot comes next.
+    /// Only the pending credentials are left if the device stopped in the middle of a swap
+    async fn find_current_creds<T: GenericKvLogEventConvertible>(
+        &self,
+        obj_id: ArtifactId,
+    ) -> Result<Option<T>> {
+        let maybe_current = self.p_obj.repo.find_one_obj(obj_id.clone()).await?;
+        match maybe_current {
+            Some(current) => Ok(Some(current)),
+            None => self.p_obj.repo.find_one_obj(obj_id.next()).await,
+        }
+    }
+
     #[instrument(skip_all)]
     pub async fn get_user_creds(&self) -> Result<Option<UserCreds>> {
-        let maybe_secure_user_creds_obj = self
-            .p_obj
-            .find_tail_event(UserCredsDescriptor)
+        let maybe_secure_user_creds_obj: Option<UserCredsObject> = self
+            .find_current_creds(ArtifactId::from(UserCredsDescriptor))
             .await?;
 
         match maybe_secure_user_creds_obj {
             None => Ok(None),
             Some(secure_user_creds_obj) => {
-                let secure_user_creds = secure_user_creds_obj.value();
-                
-                // Decrypt device credentials
-                let device_creds = secure_user_creds.device_creds.decrypt(&self.master_key)?;
-                
-                // Create UserCreds with the decrypted device credentials
-                let user_creds = UserCreds {
-                    vault_name: secure_user_creds.vault_name,
-                    device_creds,
-                };
-                
+                let user_creds = secure_user_creds_obj.value().decrypt(&self.master_key)?;
                 Ok(Some(user_creds))
             }
         }
@@ -153,6 +236,36 @@ impl<Repo: KvLogEventRepo> PersistentCredentials<Repo> {
         device_name: DeviceName,
         device_type: DeviceType,
         vault_name: VaultName,
+    ) -> Result<UserCreds> {
+        self.get_or_build_user_creds(device_name, device_type, |device_creds| {
+            Ok(UserCredsBuilder::init(device_creds).build(vault_name).creds)
+        })
+        .await
+    }
+
+    /// The vault name the user has typed becomes the label of the vault: the server gets
+    /// the opaque name derived from it, and the name of the device is sealed with it
+    #[instrument(skip_all)]
+    pub async fn get_or_generate_labeled_user_creds(
+        &self,
+        device_name: DeviceName,
+        device_type: DeviceType,
+        vault_name: VaultName,
+    ) -> Result<UserCreds> {
+        self.get_or_build_user_creds(device_name, device_type, |device_creds| {
+            let label = VaultLabel::derive(&vault_name.0)?;
+            Ok(UserCredsBuilder::init(device_creds)
+                .build_with_label(label)?
+                .creds)
+        })
+        .await
+    }
+
+    async fn get_or_build_user_creds(
+        &self,
+        device_name: DeviceName,
+        device_type: DeviceType,
+        build: impl FnOnce(DeviceCreds) -> Result<UserCreds>,
     ) -> Result<UserCreds> {
         let device_creds = self
             .get_or_generate_device_creds_with_type(device_name.clone(), device_type.clone())
@@ -162,9 +275,7 @@ impl<Repo: KvLogEventRepo> PersistentCredentials<Repo> {
 
         let user_creds = match maybe_user_creds {
             None => {
-                let user_creds = UserCredsBuilder::init(device_creds.clone())
-                    .build(vault_name)
-                    .creds;
+                let user_creds = build(device_creds.clone())?;
                 self.save_user_creds(user_creds.clone()).await?;
                 user_creds
             }
@@ -341,4 +452,37 @@ mod test {
         
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_labeled_user_creds_keep_names_from_the_server() -> anyhow::Result<()> {
+        use crate::node::common::model::device::common::DeviceType;
+        use crate::node::common::model::vault::vault::VaultName;
+        use crate::node::db::repo::persistent_credentials::PersistentCredentials;
+
+        let repo = Arc::new(InMemKvLogEventRepo::default());
+        let p_creds = PersistentCredentials {
+            p_obj: Arc::new(PersistentObject::new(repo)),
+            master_key: TransportDsaKeyPair::generate().sk(),
+        };
+
+        let vault_name = VaultName::from("family-vault");
+        let user_creds = p_creds
+            .get_or_generate_labeled_user_creds(
+                DeviceName::from("laptop"),
+                DeviceType::other(),
+                vault_name.clone(),
+            )
+            .await?;
+
+        assert_ne!(user_creds.vault_name, vault_name);
+        assert_eq!(user_creds.vault_label_name(), vault_name);
+        assert!(user_creds.device_creds.device.device_name.is_empty());
+        assert_eq!(user_creds.open_device().device_name, DeviceName::from("laptop"));
+
+        let restored = p_creds.get_user_creds().await?.unwrap();
+        assert_eq!(restored.vault_label, user_creds.vault_label);
+        assert_eq!(restored.vault_name, user_creds.vault_name);
+
+        Ok(())
+    }
 }
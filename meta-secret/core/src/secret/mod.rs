@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use crate::crypto::keys::TransportPk;
 use crate::node::common::model::crypto::aead::EncryptedMessage;
 use crate::node::common::model::meta_pass::SecurePassInfo;
 use crate::node::common::model::secret::{SecretDistributionData, SsDistributionId};
@@ -16,10 +17,11 @@ use crate::node::db::repo::generic_db::KvLogEventRepo;
 use crate::secret::shared_secret::UserSecretDto;
 use crate::CoreResult;
 use crate::{PlainText, SharedSecretConfig, SharedSecretEncryption, UserShareDto};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use tracing_attributes::instrument;
 
 pub mod data_block;
+pub mod share_verification;
 pub mod shared_secret;
 
 pub fn split2(pass_info: SecurePassInfo, config: SharedSecretConfig) -> CoreResult<UserSecretDto> {
@@ -36,13 +38,7 @@ pub fn split(
         SharedSecretEncryption::new(config, plain_text)?
     };
 
-    let mut shares: Vec<UserShareDto> = vec![];
-    for share_index in 0..config.number_of_shares {
-        let share: UserShareDto = shared_secret.get_share(share_index);
-        shares.push(share);
-    }
-
-    Ok(shares)
+    shared_secret.get_shares(config.number_of_shares)
 }
 
 pub struct MetaEncryptor {
@@ -57,7 +53,9 @@ impl MetaEncryptor {
     ///  - encrypt each share with ECIES Encryption Scheme
     fn split_and_encrypt(self, pass_info: SecurePassInfo) -> Result<Vec<EncryptedMessage>> {
         // Safely get the password string
-        let secret = split2(pass_info, self.owner.vault.sss_cfg())?;
+        let mut secret = split2(pass_info, self.owner.vault.sss_cfg())?;
+        let key_manager = self.creds.device_creds.key_manager()?;
+        secret.sign_commitments(self.creds.device_id(), &key_manager.dsa)?;
 
         let mut encrypted_shares = vec![];
 
@@ -67,11 +65,7 @@ impl MetaEncryptor {
             let encrypted_share = {
                 let share_str = PlainText::from(share.as_json()?);
                 let receiver_pk = &receiver.user().device.keys.transport_pk();
-                self.creds
-                    .device_creds
-                    .key_manager()?
-                    .transport
-                    .encrypt_string(share_str, receiver_pk)?
+                key_manager.transport.encrypt_string(share_str, receiver_pk)?
             };
 
             let cipher_share = EncryptedMessage::CipherShare {
@@ -100,6 +94,8 @@ impl<Repo: KvLogEventRepo> MetaDistributor<Repo> {
     ) -> Result<()> {
         let vault_name = self.user_creds.vault_name.clone();
         let pass_id = pass_info.pass_id.clone();
+        let recovery_policy = pass_info.recovery_policy.clone();
+        let break_glass = pass_info.break_glass.clone();
 
         let encrypted_shares = {
             let encryptor = MetaEncryptor {
@@ -109,13 +105,27 @@ impl<Repo: KvLogEventRepo> MetaDistributor<Repo> {
             encryptor.split_and_encrypt(pass_info)?
         };
 
-        let claim = vault_member.create_split_claim(pass_id);
+        let claim = vault_member.create_split_claim(pass_id.clone());
 
-        //save meta password
+        //save meta password, the name is readable by the vault members only
         {
+            let sealed_pass_id = {
+                let receivers: Vec<TransportPk> = self
+                    .vault_member
+                    .vault
+                    .members()
+                    .iter()
+                    .map(|member| member.user().device.keys.transport_pk().clone())
+                    .collect();
+                let transport = self.user_creds.device_creds.key_manager()?.transport;
+                pass_id.seal(&transport, &receivers)?
+            };
+
             let add_meta_pass = AddMetaPassEvent {
                 sender: self.vault_member.member,
-                meta_pass_id: claim.dist_claim_id.pass_id.clone(),
+                meta_pass_id: sealed_pass_id,
+                recovery_policy,
+                break_glass,
             };
 
             let p_device_log = PersistentDeviceLog::from(self.p_obj.clone());
@@ -137,19 +147,22 @@ impl<Repo: KvLogEventRepo> MetaDistributor<Repo> {
             };
 
             let dist_id = {
-                let receiver = secret_share.cipher_text().channel.receiver().to_device_id();
+                let receiver_pk = secret_share.cipher_text().channel.receiver();
+                let Some(receiver) = self.vault_member.vault.find_member_by_key(receiver_pk) else {
+                    bail!("Receiver of the share is not a member of the vault");
+                };
                 SsDistributionId {
                     pass_id: claim.dist_claim_id.pass_id.clone(),
-                    receiver,
+                    receiver: receiver.user().device.device_id.clone(),
                 }
             };
 
             let split_key = KvKey::from(SsWorkflowDescriptor::Distribution(dist_id));
 
-            let ss_obj = SsWorkflowObject::Distribution(KvLogEvent {
-                key: split_key.clone(),
-                value: distribution_data,
-            });
+            let ss_obj = SsWorkflowObject::Distribution(KvLogEvent::new(
+                split_key.clone(),
+                distribution_data,
+            ));
 
             self.p_obj.repo.save(ss_obj).await?;
         }
@@ -296,7 +309,13 @@ mod tests {
             .get_object_events_from_beginning(device_log_desc)
             .await?;
 
-        // Check that the meta password was added to the device log
+        // Check that the meta password was added to the device log, with the name sealed
+        let transport_sk = creds_fixture
+            .client
+            .device_creds
+            .key_manager()?
+            .transport
+            .sk();
         let found_password = device_log_events.iter().any(|event| {
             let DeviceLogObject(log_event) = event;
 
@@ -308,7 +327,9 @@ mod tests {
                 return false;
             };
 
-            add_meta_pass.meta_pass_id.name == pass_id.name
+            let meta_pass_id = add_meta_pass.meta_pass_id.clone();
+            !meta_pass_id.has_clear_name()
+                && meta_pass_id.unseal(&transport_sk).name == pass_id.name
         });
 
         assert!(
@@ -321,6 +342,7 @@ mod tests {
 
         // Create a test SsClaim to check if it was stored
         let claim = vault_member.create_split_claim(pass_id);
+        assert!(!claim.dist_claim_id.pass_id.has_clear_name());
         let events = p_ss.get_ss_workflow_events(claim).await?;
 
         // There should be at least one event for the distribution
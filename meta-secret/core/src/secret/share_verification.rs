@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::keys::DsaPk;
+use crate::errors::RecoveryError;
+use crate::node::common::model::device::common::DeviceId;
+use crate::node::common::model::user::common::UserDataMember;
+use crate::secret::shared_secret::{PlainText, UserShareDto};
+use crate::{CoreResult, recover_from_shares};
+
+/// A share of the secret and the device that has sent it
+#[derive(Debug, Clone)]
+pub struct HeldShare {
+    pub holder: DeviceId,
+    pub share: UserShareDto,
+}
+
+/// Shares split into the ones that match the commitments of the split and the rest
+#[derive(Debug, Default)]
+pub struct VerifiedShares {
+    pub valid: Vec<UserShareDto>,
+    pub invalid: Vec<DeviceId>,
+}
+
+/// Secret recovered from the valid shares and the devices that have sent invalid shares
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiedRecovery {
+    pub secret: PlainText,
+    pub invalid: Vec<DeviceId>,
+}
+
+/// Verifies the shares of the secret on the device that recovers it.
+///
+/// Every holder gets the commitments to all shares of the split. A share is valid if it matches
+/// its own commitment and the commitments of the split:
+///  - the commitments of the local share, the device got it right from the device that has split the secret
+///  - without the local share, the commitments signed by a member of the vault
+///    that most of the holders agree on
+///
+/// Shares without commitments (made before the shares became verifiable) are accepted
+/// as is, unless the others are verifiable.
+pub struct ShareVerifier {
+    pub local: DeviceId,
+    pub dealers: HashMap<DeviceId, DsaPk>,
+}
+
+impl ShareVerifier {
+    pub fn new(local: DeviceId, members: &[UserDataMember]) -> Self {
+        let dealers = members
+            .iter()
+            .map(|member| {
+                let device = &member.user_data.device;
+                (device.device_id.clone(), device.keys.dsa_pk.clone())
+            })
+            .collect();
+        Self { local, dealers }
+    }
+
+    pub fn verify_shares(&self, shares: Vec<HeldShare>) -> CoreResult<VerifiedShares> {
+        if shares.iter().all(|held| !held.share.is_verifiable()) {
+            return Ok(VerifiedShares {
+                valid: shares.into_iter().map(|held| held.share).collect(),
+                invalid: vec![],
+            });
+        }
+
+        let commitments = self.split_commitments(&shares)?;
+
+        let mut verified = VerifiedShares::default();
+        for held in shares {
+            let is_valid = held.share.is_committed() && held.share.commitments == commitments;
+            if !is_valid {
+                verified.invalid.push(held.holder);
+                continue;
+            }
+
+            let is_duplicate = verified
+                .valid
+                .iter()
+                .any(|share| share.share_id == held.share.share_id);
+            if !is_duplicate {
+                verified.valid.push(held.share);
+            }
+        }
+
+        Ok(verified)
+    }
+
+    /// Recovers the secret from the valid shares only
+    pub fn recover(&self, shares: Vec<HeldShare>) -> CoreResult<VerifiedRecovery> {
+        let threshold = shares
+            .iter()
+            .filter_map(|held| held.share.threshold())
+            .max()
+            .unwrap_or_default();
+
+        let VerifiedShares { valid, invalid } = self.verify_shares(shares)?;
+        if valid.is_empty() || valid.len() < threshold {
+            let err = RecoveryError::NotEnoughValidShares {
+                valid: valid.len(),
+                threshold,
+                devices: invalid,
+            };
+            return Err(err.into());
+        }
+
+        let secret = recover_from_shares(valid)?;
+        Ok(VerifiedRecovery { secret, invalid })
+    }
+
+    fn split_commitments(&self, shares: &[HeldShare]) -> CoreResult<Vec<Base64Text>> {
+        let local_share = shares
+            .iter()
+            .find(|held| held.holder == self.local && held.share.is_committed());
+        if let Some(local_share) = local_share {
+            return Ok(local_share.share.commitments.clone());
+        }
+
+        let mut votes: HashMap<&Vec<Base64Text>, usize> = HashMap::new();
+        let authentic_shares = shares
+            .iter()
+            .filter(|held| held.share.is_committed() && self.is_dealt_by_member(&held.share));
+        for held in authentic_shares {
+            *votes.entry(&held.share.commitments).or_default() += 1;
+        }
+
+        let Some(max_votes) = votes.values().copied().max() else {
+            return Err(RecoveryError::UnauthenticatedShares.into());
+        };
+        let mut winners = votes
+            .into_iter()
+            .filter(|(_, count)| *count == max_votes)
+            .map(|(commitments, _)| commitments.clone());
+        let (Some(commitments), None) = (winners.next(), winners.next()) else {
+            return Err(RecoveryError::AmbiguousShares.into());
+        };
+
+        Ok(commitments)
+    }
+
+    fn is_dealt_by_member(&self, share: &UserShareDto) -> bool {
+        let Some(dealer) = &share.dealer else {
+            return false;
+        };
+        self.dealers
+            .get(&dealer.device_id)
+            .is_some_and(|dealer_pk| share.is_dealt_by(dealer_pk))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crypto::key_pair::KeyPair;
+    use crate::crypto::keys::KeyManager;
+    use crate::errors::CoreError;
+    use crate::secret::data_block::common::SharedSecretConfig;
+    use crate::secret::shared_secret::SharedSecretEncryption;
+
+    fn device_id() -> DeviceId {
+        KeyManager::generate().transport.pk().to_device_id()
+    }
+
+    /// Shares of the secret signed by a member of the vault, the first share is the local one
+    fn split(secret: &str) -> CoreResult<(ShareVerifier, Vec<HeldShare>)> {
+        let config = SharedSecretConfig {
+            number_of_shares: 4,
+            threshold: 2,
+        };
+        let dealer = KeyManager::generate();
+        let dealer_id = dealer.transport.pk().to_device_id();
+
+        let shared_secret = SharedSecretEncryption::new(config, PlainText::from(secret))?;
+        let mut shares = vec![];
+        for mut share in shared_secret.get_shares(config.number_of_shares)? {
+            share.sign_commitments(dealer_id.clone(), &dealer.dsa)?;
+            shares.push(HeldShare {
+                holder: device_id(),
+                share,
+            });
+        }
+
+        let verifier = ShareVerifier {
+            local: shares[0].holder.clone(),
+            dealers: HashMap::from([(dealer_id, dealer.dsa.pk())]),
+        };
+        Ok((verifier, shares))
+    }
+
+    fn corrupt(held: &mut HeldShare) {
+        let block = &mut held.share.share_blocks[0];
+        let mut data = Vec::try_from(&block.data).unwrap();
+        data[0] ^= 0xff;
+        block.data = Base64Text::from(data.as_slice());
+    }
+
+    #[test]
+    fn test_valid_shares() -> CoreResult<()> {
+        let (verifier, shares) = split("2bee|~")?;
+        let recovery = verifier.recover(shares)?;
+
+        assert_eq!("2bee|~", recovery.secret.text);
+        assert!(recovery.invalid.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_share_is_discarded() -> CoreResult<()> {
+        let (verifier, mut shares) = split("2bee|~")?;
+        corrupt(&mut shares[1]);
+        let bad_device = shares[1].holder.clone();
+
+        let recovery = verifier.recover(shares)?;
+
+        assert_eq!("2bee|~", recovery.secret.text);
+        assert_eq!(vec![bad_device], recovery.invalid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_share_with_forged_commitments_is_discarded() -> CoreResult<()> {
+        let (verifier, mut shares) = split("2bee|~")?;
+
+        // the share is consistent with its own commitments, but not with the split
+        corrupt(&mut shares[2]);
+        let forged_commitment = shares[2].share.commitment()?;
+        shares[2].share.commitments[2] = forged_commitment;
+        let bad_device = shares[2].holder.clone();
+
+        let verified = verifier.verify_shares(shares)?;
+
+        assert_eq!(3, verified.valid.len());
+        assert_eq!(vec![bad_device], verified.invalid);
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_share_outweighs_forged_majority() -> CoreResult<()> {
+        let (mut verifier, mut shares) = split("2bee|~")?;
+
+        // a member of the vault signs the commitments made up by the holders of the other shares
+        let forger = KeyManager::generate();
+        let forger_id = device_id();
+        verifier
+            .dealers
+            .insert(forger_id.clone(), forger.dsa.pk());
+
+        corrupt(&mut shares[1]);
+        corrupt(&mut shares[2]);
+        let mut forged_commitments = shares[0].share.commitments.clone();
+        forged_commitments[1] = shares[1].share.commitment()?;
+        forged_commitments[2] = shares[2].share.commitment()?;
+        for held in shares.iter_mut().skip(1) {
+            held.share.commitments = forged_commitments.clone();
+            held.share.sign_commitments(forger_id.clone(), &forger.dsa)?;
+        }
+
+        let verified = verifier.verify_shares(shares)?;
+
+        assert_eq!(1, verified.valid.len());
+        assert_eq!(3, verified.invalid.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_commitments_without_member_signature() -> CoreResult<()> {
+        let (mut verifier, mut shares) = split("2bee|~")?;
+        verifier.local = device_id();
+
+        // signed by a device that isn't a member of the vault
+        let outsider = KeyManager::generate();
+        for held in shares.iter_mut() {
+            held.share.sign_commitments(device_id(), &outsider.dsa)?;
+        }
+        let result = verifier.verify_shares(shares.clone());
+        assert!(matches!(
+            result,
+            Err(CoreError::RecoveryError {
+                source: RecoveryError::UnauthenticatedShares
+            })
+        ));
+
+        for held in shares.iter_mut() {
+            held.share.dealer = None;
+        }
+        let result = verifier.verify_shares(shares);
+        assert!(matches!(
+            result,
+            Err(CoreError::RecoveryError {
+                source: RecoveryError::UnauthenticatedShares
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_not_enough_valid_shares() -> CoreResult<()> {
+        let (verifier, mut shares) = split("2bee|~")?;
+        shares.truncate(3);
+        corrupt(&mut shares[0]);
+        corrupt(&mut shares[1]);
+
+        let result = verifier.recover(shares);
+        match result {
+            Err(CoreError::RecoveryError {
+                source: RecoveryError::NotEnoughValidShares { valid, devices, .. },
+            }) => {
+                assert_eq!(1, valid);
+                assert_eq!(2, devices.len());
+            }
+            _ => panic!("Expected NotEnoughValidShares error, got: {:?}", result),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_shares_of_different_splits_are_ambiguous() -> CoreResult<()> {
+        let (mut verifier, mut shares) = split("2bee|~")?;
+        let (other_verifier, other_shares) = split("2bee|~")?;
+        verifier.local = device_id();
+        verifier.dealers.extend(other_verifier.dealers);
+
+        shares.truncate(1);
+        shares.extend(other_shares.into_iter().skip(1).take(1));
+
+        let result = verifier.verify_shares(shares);
+        assert!(matches!(
+            result,
+            Err(CoreError::RecoveryError {
+                source: RecoveryError::AmbiguousShares
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_shares_without_commitments() -> CoreResult<()> {
+        let (verifier, mut shares) = split("2bee|~")?;
+        for held in shares.iter_mut() {
+            held.share.commitments.clear();
+            held.share.dealer = None;
+        }
+
+        let recovery = verifier.recover(shares)?;
+        assert_eq!("2bee|~", recovery.secret.text);
+        Ok(())
+    }
+}
@@ -3,10 +3,14 @@ use std::fmt::Display;
 use std::str;
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 use crate::crypto::encoding::base64::Base64Text;
+use crate::crypto::key_pair::DsaKeyPair;
+use crate::crypto::keys::DsaPk;
 use crate::errors::RecoveryError::InvalidShare;
 use crate::errors::{CoreError, RecoveryError};
+use crate::node::common::model::device::common::DeviceId;
 use crate::secret::data_block::common::{BlockMetaData, SharedSecretConfig};
 use crate::secret::data_block::encrypted_data_block::EncryptedDataBlock;
 use crate::secret::data_block::plain_data_block::{PlainDataBlock, PLAIN_DATA_BLOCK_SIZE};
@@ -70,17 +74,94 @@ pub struct UserSecretDto {
     pub shares: Vec<UserShareDto>,
 }
 
+impl UserSecretDto {
+    /// Signs the commitments of all the shares by the device that has split the secret
+    pub fn sign_commitments(&mut self, device_id: &DeviceId, dsa: &DsaKeyPair) -> CoreResult<()> {
+        for share in self.shares.iter_mut() {
+            share.sign_commitments(device_id.clone(), dsa)?;
+        }
+        Ok(())
+    }
+}
+
 // A share of the secret that user holds
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserShareDto {
     pub share_id: usize,
     pub share_blocks: Vec<SecretShareWithOrderingDto>,
+    /// Commitments to all shares of the split (indexed by `share_id - 1`),
+    /// every holder gets the same list, so a modified share doesn't match the list of the others.
+    /// Shares created before the commitments were introduced have an empty list
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commitments: Vec<Base64Text>,
+    /// The commitments signed by the device that has split the secret,
+    /// holders can't make up the commitments of their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dealer: Option<DealerSignature>,
+}
+
+/// Signature of the commitments by the device that has split the secret
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DealerSignature {
+    pub device_id: DeviceId,
+    pub signature: Base64Text,
 }
 
 impl UserShareDto {
     pub fn as_json(&self) -> serde_json::Result<String> {
         serde_json::to_string(self)
     }
+
+    /// Sha256 of the share data
+    pub fn commitment(&self) -> CoreResult<Base64Text> {
+        let share_json = serde_json::to_vec(&(self.share_id, &self.share_blocks))?;
+        let hash = Sha256::digest(share_json);
+        Ok(Base64Text::from(hash.as_slice()))
+    }
+
+    pub fn is_verifiable(&self) -> bool {
+        !self.commitments.is_empty()
+    }
+
+    /// The share matches its own commitment
+    pub fn is_committed(&self) -> bool {
+        let Some(index) = self.share_id.checked_sub(1) else {
+            return false;
+        };
+        match (self.commitments.get(index), self.commitment()) {
+            (Some(expected), Ok(actual)) => expected.eq(&actual),
+            _ => false,
+        }
+    }
+
+    /// Signs the commitments of the split by the device that has made it
+    pub fn sign_commitments(&mut self, device_id: DeviceId, dsa: &DsaKeyPair) -> CoreResult<()> {
+        let signature = dsa.sign(self.dealt_commitments(&device_id)?);
+        self.dealer = Some(DealerSignature {
+            device_id,
+            signature,
+        });
+        Ok(())
+    }
+
+    /// The commitments are signed by the dealer with the key
+    pub fn is_dealt_by(&self, dealer_pk: &DsaPk) -> bool {
+        let Some(dealer) = &self.dealer else {
+            return false;
+        };
+        match self.dealt_commitments(&dealer.device_id) {
+            Ok(text) => dealer_pk.verify(&text, &dealer.signature).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn dealt_commitments(&self, device_id: &DeviceId) -> CoreResult<String> {
+        Ok(serde_json::to_string(&(&self.commitments, device_id))?)
+    }
+
+    pub fn threshold(&self) -> Option<usize> {
+        self.share_blocks.first().map(|block| block.config.threshold)
+    }
 }
 
 impl UserShareDto {
@@ -188,8 +269,27 @@ impl SharedSecret {
         UserShareDto {
             share_id: share_index + 1,
             share_blocks,
+            commitments: vec![],
+            dealer: None,
         }
     }
+
+    /// All shares of the secret with the commitments to each other
+    pub fn get_shares(&self, number_of_shares: usize) -> CoreResult<Vec<UserShareDto>> {
+        let mut shares: Vec<UserShareDto> = (0..number_of_shares)
+            .map(|share_index| self.get_share(share_index))
+            .collect();
+
+        let commitments = shares
+            .iter()
+            .map(|share| share.commitment())
+            .collect::<CoreResult<Vec<_>>>()?;
+        for share in shares.iter_mut() {
+            share.commitments = commitments.clone();
+        }
+
+        Ok(shares)
+    }
 }
 
 #[cfg(test)]
@@ -6,9 +6,11 @@ use meta_secret_core::node::db::events::generic_log_event::{
 };
 use meta_secret_core::node::db::events::object_id::ArtifactId;
 use meta_secret_core::node::db::repo::generic_db::{
-    DbCleanUpCommand, DeleteCommand, FindOneQuery, KvLogEventRepo, SaveCommand,
+    object_name_of_key, DbCleanUpCommand, DeleteCommand, FindObjectsQuery, FindOneQuery,
+    KvLogEventRepo, SaveCommand,
 };
 use redb::{Database, ReadableDatabase, TableDefinition};
+use std::collections::BTreeSet;
 use std::path::Path;
 use tracing::{error, instrument};
 
@@ -103,6 +105,29 @@ impl FindOneQuery for ReDbRepo {
     }
 }
 
+#[async_trait(? Send)]
+impl FindObjectsQuery for ReDbRepo {
+    async fn find_object_names(&self, obj_type: &str) -> Result<Vec<String>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(LOG_EVENTS_TABLE)?;
+
+        let prefix = format!("{}:", obj_type);
+        let mut names = BTreeSet::new();
+        for entry in table.range(prefix.clone()..)? {
+            let (key, _) = entry?;
+            let key = key.value();
+            if !key.starts_with(&prefix) {
+                break;
+            }
+            if let Some(name) = object_name_of_key(obj_type, &key) {
+                names.insert(name);
+            }
+        }
+
+        Ok(names.into_iter().collect())
+    }
+}
+
 #[async_trait(? Send)]
 impl DeleteCommand for ReDbRepo {
     async fn delete(&self, key: ArtifactId) {
@@ -228,10 +253,10 @@ mod tests {
 
         // Insert multiple events
         for i in 1..=5 {
-            let kv_event = KvLogEvent {
-                key: KvKey::artifact(creds_desc.clone().to_obj_desc(), id.clone()),
-                value: secure_device_creds.clone(),
-            };
+            let kv_event = KvLogEvent::new(
+                KvKey::artifact(creds_desc.clone().to_obj_desc(), id.clone()),
+                secure_device_creds.clone(),
+            );
             
             let creds_obj = DeviceCredsObject(kv_event);
             let test_event = creds_obj.to_generic();
@@ -321,4 +346,24 @@ mod tests {
         
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_redb_repo_find_object_names() -> Result<()> {
+        let (repo, _temp_dir) = create_test_db();
+
+        let device_creds = DeviceCredsBuilder::generate()
+            .build(DeviceName::client())
+            .creds;
+        let master_pk = TransportDsaKeyPair::generate().sk().pk()?;
+        let secure_device_creds = SecureDeviceCreds::build(device_creds, master_pk)?;
+        repo.save(DeviceCredsObject::from(secure_device_creds)).await?;
+
+        let names = repo.find_object_names("DeviceCreds").await?;
+        assert_eq!(names, vec![String::from("index")]);
+
+        // the type name must match exactly, not just by prefix
+        assert!(repo.find_object_names("Device").await?.is_empty());
+
+        Ok(())
+    }
 }
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use diesel::{
     Connection, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SqliteConnection,
+    TextExpressionMethods,
 };
 use meta_secret_core::node::common::model::IdString;
 use meta_secret_core::node::db::events::generic_log_event::{
@@ -8,8 +9,10 @@ use meta_secret_core::node::db::events::generic_log_event::{
 };
 use meta_secret_core::node::db::events::object_id::ArtifactId;
 use meta_secret_core::node::db::repo::generic_db::{
-    DbCleanUpCommand, DeleteCommand, FindOneQuery, KvLogEventRepo, SaveCommand,
+    object_name_of_key, DbCleanUpCommand, DeleteCommand, FindObjectsQuery, FindOneQuery,
+    KvLogEventRepo, SaveCommand,
 };
+use std::collections::BTreeSet;
 use tracing::{error, instrument};
 
 use crate::models::DbLogEvent;
@@ -77,6 +80,24 @@ impl FindOneQuery for SqlIteRepo {
     }
 }
 
+#[async_trait(? Send)]
+impl FindObjectsQuery for SqlIteRepo {
+    async fn find_object_names(&self, obj_type: &str) -> anyhow::Result<Vec<String>> {
+        let mut conn = establish_connection(self.conn_url.as_str())?;
+
+        let keys = dsl::db_commit_log
+            .filter(dsl::key_id.like(format!("{}:%", obj_type)))
+            .select(dsl::key_id)
+            .load::<String>(&mut conn)?;
+
+        let names: BTreeSet<String> = keys
+            .iter()
+            .filter_map(|key| object_name_of_key(obj_type, key))
+            .collect();
+        Ok(names.into_iter().collect())
+    }
+}
+
 #[async_trait(? Send)]
 impl DeleteCommand for SqlIteRepo {
     async fn delete(&self, key: ArtifactId) {
@@ -194,6 +215,34 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_sqlite_repo_find_object_names() -> anyhow::Result<()> {
+        let temp_dir = tempdir()?;
+        let db_path = temp_dir.path().join("find_object_names_test.db");
+        let conn_url = format!("file:{}", db_path.to_string_lossy());
+
+        let migration_tool = EmbeddedMigrationsTool {
+            db_url: conn_url.clone(),
+        };
+        migration_tool.migrate();
+        let repo = SqlIteRepo { conn_url };
+
+        let device_creds = DeviceCredsBuilder::generate()
+            .build(DeviceName::client())
+            .creds;
+        let master_pk = TransportDsaKeyPair::generate().sk().pk()?;
+        let secure_device_creds = SecureDeviceCreds::build(device_creds, master_pk)?;
+        repo.save(DeviceCredsObject::from(secure_device_creds)).await?;
+
+        let names = repo.find_object_names("DeviceCreds").await?;
+        assert_eq!(names, vec![String::from("index")]);
+
+        // the type name must match exactly, not just by prefix
+        assert!(repo.find_object_names("Device").await?.is_empty());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_db_clean_up() -> anyhow::Result<()> {
         // Create a temporary directory for the database
@@ -223,10 +272,10 @@ mod tests {
         let mut id = initial_id.clone();
 
         for i in 1..=5 {
-            let kv_event = KvLogEvent {
-                key: KvKey::artifact(creds_desc.clone().to_obj_desc(), id.clone()),
-                value: secure_device_creds.clone(),
-            };
+            let kv_event = KvLogEvent::new(
+                KvKey::artifact(creds_desc.clone().to_obj_desc(), id.clone()),
+                secure_device_creds.clone(),
+            );
 
             let creds_obj = DeviceCredsObject(kv_event);
             let test_event = creds_obj.to_generic();
@@ -0,0 +1,228 @@
+use crate::base_command::BaseCommand;
+use crate::cli_format::CliOutputFormat;
+use crate::template_manager::TemplateManager;
+use anyhow::{anyhow, Result};
+use meta_secret_core::node::app::app_manager_shared::{find_audit_entries, AuditQuery};
+use meta_secret_core::node::common::model::device::common::DeviceId;
+use meta_secret_core::node::common::model::{ApplicationState, IdString, VaultFullInfo};
+use meta_secret_core::node::db::events::audit_event::{AuditAction, AuditEntry};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tera::Context;
+
+/// Start of the audit period: a duration back from now (30d, 12h, 15m, 45s)
+/// or the unix time in milliseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditSince {
+    Ago(Duration),
+    UnixMillis(u64),
+}
+
+impl AuditSince {
+    pub fn to_unix_millis(self) -> Result<u64> {
+        match self {
+            AuditSince::UnixMillis(time) => Ok(time),
+            AuditSince::Ago(duration) => {
+                let since = SystemTime::now()
+                    .checked_sub(duration)
+                    .ok_or_else(|| anyhow!("Invalid audit period: {:?}", duration))?;
+                let since = since.duration_since(UNIX_EPOCH)?.as_millis();
+                Ok(u64::try_from(since)?)
+            }
+        }
+    }
+}
+
+impl FromStr for AuditSince {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(time) = s.parse::<u64>() {
+            return Ok(AuditSince::UnixMillis(time));
+        }
+
+        let unit_secs = match s.chars().last() {
+            Some('d') => 24 * 60 * 60,
+            Some('h') => 60 * 60,
+            Some('m') => 60,
+            Some('s') => 1,
+            _ => return Err(format!("Unknown audit period: {}", s)),
+        };
+        let amount = s[..s.len() - 1]
+            .parse::<u64>()
+            .map_err(|_| format!("Unknown audit period: {}", s))?;
+
+        Ok(AuditSince::Ago(Duration::from_secs(amount * unit_secs)))
+    }
+}
+
+/// Shows the audit trail of the vault
+pub struct AuditListCommand {
+    base: BaseCommand,
+    output_format: CliOutputFormat,
+    since: Option<AuditSince>,
+    device: Option<String>,
+    secret: Option<String>,
+}
+
+impl AuditListCommand {
+    pub fn new(
+        db_name: String,
+        output_format: CliOutputFormat,
+        since: Option<AuditSince>,
+        device: Option<String>,
+        secret: Option<String>,
+    ) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+            output_format,
+            since,
+            device,
+            secret,
+        }
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        let db_context = self.base.open_existing_db().await?;
+        let client = self.base.create_client_service(&db_context).await?;
+        let app_state = client.get_app_state().await?;
+
+        let ApplicationState::Vault(VaultFullInfo::Member(member_info)) = &app_state else {
+            let mut error_context = Context::new();
+            error_context.insert("message", "Not a vault member or vault doesn't exist");
+            let output =
+                TemplateManager::instance().render("error", &error_context, self.output_format)?;
+            println!("{}", output);
+            return Ok(());
+        };
+
+        let query = AuditQuery {
+            since: self.since.map(AuditSince::to_unix_millis).transpose()?,
+            device: self.device.clone(),
+            secret: self.secret.clone(),
+        };
+
+        let device_names: HashMap<DeviceId, String> = member_info
+            .member
+            .vault
+            .users
+            .values()
+            .map(|membership| {
+                let device = membership.user_data().device;
+                (device.device_id, device.device_name.as_str())
+            })
+            .collect();
+
+        let entries = find_audit_entries(db_context.p_obj.clone(), &app_state, &query).await?;
+        let entries_vec: Vec<Value> = entries
+            .iter()
+            .map(|entry| entry_json(entry, &device_names))
+            .collect();
+
+        let mut context = Context::new();
+        context.insert("entries", &entries_vec);
+        let output = TemplateManager::instance().render("audit", &context, self.output_format)?;
+        println!("{}", output);
+
+        Ok(())
+    }
+}
+
+fn entry_json(entry: &AuditEntry, device_names: &HashMap<DeviceId, String>) -> Value {
+    let device_name = device_names.get(&entry.device).cloned().unwrap_or_default();
+    let mut entry_json = json!({
+        "time": entry.time,
+        "device": entry.device.clone().id_str(),
+        "device_name": device_name,
+    });
+
+    let action = match &entry.action {
+        AuditAction::VaultCreated => "VaultCreated",
+        AuditAction::JoinRequested => "JoinRequested",
+        AuditAction::JoinAccepted { candidate } => {
+            entry_json["candidate"] = json!(candidate.clone().id_str());
+            "JoinAccepted"
+        }
+        AuditAction::JoinDeclined { candidate } => {
+            entry_json["candidate"] = json!(candidate.clone().id_str());
+            "JoinDeclined"
+        }
+        AuditAction::SecretAdded { .. } => "SecretAdded",
+        AuditAction::KeysRotated => "KeysRotated",
+        AuditAction::InviteCreated => "InviteCreated",
+        AuditAction::SettingsUpdated => "SettingsUpdated",
+        AuditAction::RecoveryRequested { claim } => {
+            entry_json["claim"] = json!(claim.id.0.clone().id_str());
+            "RecoveryRequested"
+        }
+        AuditAction::RecoveryApproved { claim } => {
+            entry_json["claim"] = json!(claim.id.0.clone().id_str());
+            "RecoveryApproved"
+        }
+        AuditAction::RecoveryApprovalGranted { claim } => {
+            entry_json["claim"] = json!(claim.id.0.clone().id_str());
+            "RecoveryApprovalGranted"
+        }
+        AuditAction::RecoveryDeclined { claim } => {
+            entry_json["claim"] = json!(claim.id.0.clone().id_str());
+            "RecoveryDeclined"
+        }
+        AuditAction::RecoveryCancelled { claim } => {
+            entry_json["claim"] = json!(claim.id.0.clone().id_str());
+            "RecoveryCancelled"
+        }
+        AuditAction::RecoveryVetoed { claim } => {
+            entry_json["claim"] = json!(claim.id.0.clone().id_str());
+            "RecoveryVetoed"
+        }
+        AuditAction::ShareDelivered {
+            claim,
+            distribution_type,
+        } => {
+            entry_json["claim"] = json!(claim.id.0.clone().id_str());
+            entry_json["distribution_type"] = json!(format!("{:?}", distribution_type));
+            "ShareDelivered"
+        }
+    };
+    entry_json["action"] = json!(action);
+
+    if let Some(pass_id) = entry.action.pass_id() {
+        let secret = if pass_id.name.is_empty() {
+            pass_id.id.clone().id_str()
+        } else {
+            pass_id.name.clone()
+        };
+        entry_json["secret"] = json!(secret);
+    }
+
+    entry_json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_since_parsing() {
+        assert_eq!(
+            AuditSince::from_str("30d"),
+            Ok(AuditSince::Ago(Duration::from_secs(30 * 24 * 60 * 60)))
+        );
+        assert_eq!(
+            AuditSince::from_str("12h"),
+            Ok(AuditSince::Ago(Duration::from_secs(12 * 60 * 60)))
+        );
+        assert_eq!(
+            AuditSince::from_str("15m"),
+            Ok(AuditSince::Ago(Duration::from_secs(15 * 60)))
+        );
+        assert_eq!(
+            AuditSince::from_str("1700000000000"),
+            Ok(AuditSince::UnixMillis(1_700_000_000_000))
+        );
+        assert!(AuditSince::from_str("week").is_err());
+        assert!(AuditSince::from_str("d").is_err());
+    }
+}
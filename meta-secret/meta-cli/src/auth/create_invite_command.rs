@@ -0,0 +1,56 @@
+use crate::base_command::BaseCommand;
+use anyhow::Result;
+use meta_secret_core::generate_qr_code;
+use std::path::PathBuf;
+
+/// Makes a one-time invite to the vault and prints it, optionally as a QR code image
+pub struct CreateInviteCommand {
+    pub base: BaseCommand,
+    /// Hours the invite can be used
+    pub valid_hours: u64,
+    pub pre_approved: bool,
+    pub qr_out: Option<PathBuf>,
+}
+
+impl CreateInviteCommand {
+    pub fn new(
+        db_name: String,
+        valid_hours: u64,
+        pre_approved: bool,
+        qr_out: Option<PathBuf>,
+    ) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+            valid_hours,
+            pre_approved,
+            qr_out,
+        }
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        let db_context = self.base.open_existing_db().await?;
+
+        // Ensure user credentials exist
+        self.base.ensure_user_creds(&db_context).await?;
+
+        let client = self.base.create_client_service(&db_context).await?;
+        let ttl = self.valid_hours * 60 * 60 * 1000;
+        let invite = client.create_invite(ttl, self.pre_approved).await?;
+        let invite_text = invite.to_text()?;
+
+        println!("Invite to the vault {}:", invite.vault_name);
+        println!("{}", invite_text);
+        println!("Key fingerprint of this device: {}", invite.key_fingerprint);
+
+        if let Some(qr_out) = &self.qr_out {
+            generate_qr_code(&invite_text, &qr_out.to_string_lossy());
+            println!("QR code saved to {}", qr_out.display());
+        }
+
+        if self.pre_approved {
+            println!("The device that joins with the invite is accepted by this device");
+        }
+
+        Ok(())
+    }
+}
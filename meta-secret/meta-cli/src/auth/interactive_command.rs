@@ -1,9 +1,11 @@
 use crate::auth::accept_all_join_requests_command::AcceptAllJoinRequestsCommand;
 use crate::auth::accept_join_request_command::AcceptJoinRequestCommand;
+use crate::auth::create_invite_command::CreateInviteCommand;
+use crate::auth::join_with_invite_command::JoinWithInviteCommand;
 use crate::auth::sign_up_command::JoinVaultCommand;
 use crate::base_command::BaseCommand;
 use anyhow::Result;
-use dialoguer::{theme::ColorfulTheme, Input, Select};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 
@@ -15,6 +17,10 @@ pub enum AuthOption {
     AcceptJoinRequest,
     #[strum(to_string = "Accept All Join Requests")]
     AcceptAllJoinRequests,
+    #[strum(to_string = "Create Invite")]
+    CreateInvite,
+    #[strum(to_string = "Join with Invite")]
+    JoinWithInvite,
     #[strum(to_string = "Back to Main Menu")]
     Back,
 }
@@ -71,6 +77,33 @@ impl AuthInteractiveCommand {
                 let accept_all_cmd = AcceptAllJoinRequestsCommand::new(self.base.db_name.clone());
                 accept_all_cmd.execute().await?
             }
+            AuthOption::CreateInvite => {
+                let valid_hours = Input::<u64>::new()
+                    .with_prompt("Hours the invite can be used")
+                    .default(24 * 7)
+                    .interact()?;
+                let pre_approved = Confirm::new()
+                    .with_prompt("Accept the invited device without asking?")
+                    .default(false)
+                    .interact()?;
+
+                let invite_cmd = CreateInviteCommand::new(
+                    self.base.db_name.clone(),
+                    valid_hours,
+                    pre_approved,
+                    None,
+                );
+                invite_cmd.execute().await?
+            }
+            AuthOption::JoinWithInvite => {
+                let invite = Input::<String>::new()
+                    .with_prompt("Enter the invite")
+                    .interact()?;
+
+                let join_cmd =
+                    JoinWithInviteCommand::new(self.base.db_name.clone(), Some(invite), None);
+                join_cmd.execute().await?
+            }
             AuthOption::Back => {
                 // Back to main menu
                 println!("Returning to main menu");
@@ -91,11 +124,13 @@ mod tests {
         let options: Vec<AuthOption> = AuthOption::iter().collect();
 
         // Verify the order matches expected indices
-        assert_eq!(options.len(), 4);
+        assert_eq!(options.len(), 6);
         assert!(matches!(options[0], AuthOption::SignUp));
         assert!(matches!(options[1], AuthOption::AcceptJoinRequest));
         assert!(matches!(options[2], AuthOption::AcceptAllJoinRequests));
-        assert!(matches!(options[3], AuthOption::Back));
+        assert!(matches!(options[3], AuthOption::CreateInvite));
+        assert!(matches!(options[4], AuthOption::JoinWithInvite));
+        assert!(matches!(options[5], AuthOption::Back));
     }
 
     #[test]
@@ -113,6 +148,8 @@ mod tests {
             AuthOption::AcceptAllJoinRequests.to_string(),
             "Accept All Join Requests"
         );
+        assert_eq!(AuthOption::CreateInvite.to_string(), "Create Invite");
+        assert_eq!(AuthOption::JoinWithInvite.to_string(), "Join with Invite");
         assert_eq!(AuthOption::Back.to_string(), "Back to Main Menu");
     }
 }
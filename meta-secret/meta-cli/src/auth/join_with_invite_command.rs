@@ -0,0 +1,64 @@
+use crate::base_command::BaseCommand;
+use anyhow::{bail, Result};
+use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
+use meta_secret_core::node::common::model::device::common::DeviceType;
+use meta_secret_core::node::common::model::vault::invite::VaultInvite;
+use meta_secret_core::read_qr_code;
+use std::path::PathBuf;
+
+/// Joins the vault of an invite, given as text or as a QR code image
+pub struct JoinWithInviteCommand {
+    pub base: BaseCommand,
+    pub invite: Option<String>,
+    pub invite_qr: Option<PathBuf>,
+}
+
+impl JoinWithInviteCommand {
+    pub fn new(db_name: String, invite: Option<String>, invite_qr: Option<PathBuf>) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+            invite,
+            invite_qr,
+        }
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        let invite_text = match (&self.invite, &self.invite_qr) {
+            (Some(invite), _) => invite.clone(),
+            (None, Some(invite_qr)) => read_qr_code(invite_qr)?,
+            (None, None) => bail!("Either an invite or a QR code image of the invite is required"),
+        };
+        let invite = VaultInvite::from_text(&invite_text)?;
+
+        let db_context = self.base.open_existing_db().await?;
+
+        // Ensure user credentials exist
+        self.base.ensure_user_creds(&db_context).await?;
+
+        let user_creds = db_context.p_creds.get_user_creds().await?.unwrap();
+        let device_name = user_creds.device().device_name.clone();
+
+        // Force CLI device type for CLI client flow (also migrates old `Other` credentials).
+        let _ = db_context
+            .p_creds
+            .get_or_generate_user_creds_with_type(
+                device_name,
+                DeviceType::cli(),
+                user_creds.vault_name.clone(),
+            )
+            .await?;
+
+        println!(
+            "Joining the vault {}, the inviting device has the key fingerprint {}",
+            invite.vault_name, invite.key_fingerprint
+        );
+
+        let join_request = GenericAppStateRequest::JoinWithInvite(invite);
+        self.base
+            .handle_client_request(&db_context, join_request)
+            .await?;
+
+        println!("Join vault request submitted successfully");
+        Ok(())
+    }
+}
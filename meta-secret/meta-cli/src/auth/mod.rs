@@ -1,4 +1,7 @@
 pub mod accept_all_join_requests_command;
 pub mod accept_join_request_command;
+pub mod create_invite_command;
 pub mod interactive_command;
+pub mod join_with_invite_command;
 pub mod sign_up_command;
+pub mod vault_settings_command;
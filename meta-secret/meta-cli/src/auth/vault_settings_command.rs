@@ -0,0 +1,37 @@
+use crate::base_command::BaseCommand;
+use anyhow::Result;
+
+/// Changes the settings of the vault. Any member makes the vault stricter,
+/// loosening it takes as many members as the join quorum of the vault
+pub struct VaultSettingsCommand {
+    pub base: BaseCommand,
+    pub join_quorum: Option<usize>,
+    pub require_invite: Option<bool>,
+}
+
+impl VaultSettingsCommand {
+    pub fn new(db_name: String, join_quorum: Option<usize>, require_invite: Option<bool>) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+            join_quorum,
+            require_invite,
+        }
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        let db_context = self.base.open_existing_db().await?;
+
+        // Ensure user credentials exist
+        self.base.ensure_user_creds(&db_context).await?;
+
+        let client = self.base.create_client_service(&db_context).await?;
+        client
+            .update_vault_settings(self.join_quorum, self.require_invite)
+            .await?;
+
+        println!("The settings have been sent to the vault");
+        println!("Loosening the settings takes effect once enough members have sent the same settings");
+
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 use anyhow::{bail, Result};
+use dialoguer::Password;
 use meta_db_redb::ReDbRepo;
-use meta_secret_core::crypto::key_utils::load_or_create_master_key;
+use meta_secret_core::crypto::keys::TransportSk;
+use meta_secret_core::crypto::master_key::MasterKeySource;
+use meta_secret_core::node::api::{UpgradeComponent, UpgradeRequired};
 use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
 use meta_secret_core::node::app::meta_app::meta_client_service::{
     MetaClientDataTransfer, MetaClientService, MetaClientStateProvider,
@@ -12,9 +15,17 @@ use meta_secret_core::node::common::data_transfer::MpscDataTransfer;
 use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
 use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
 use meta_secret_core::node::db::repo::persistent_credentials::PersistentCredentials;
+use secrecy::SecretString;
+use std::io::{self, IsTerminal};
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::Arc;
 
+/// Selects where the master key comes from: file (default), passphrase-file, env:<VAR>, stdin or command:<CMD>
+pub const MASTER_KEY_SOURCE_ENV: &str = "META_SECRET_MASTER_KEY_SOURCE";
+/// Passphrase of the master key file for non-interactive runs
+pub const PASSPHRASE_ENV: &str = "META_SECRET_PASSPHRASE";
+
 /// Container for database-related components
 pub struct DbContext<Repo: KvLogEventRepo> {
     pub repo: Arc<Repo>,
@@ -50,7 +61,7 @@ impl BaseCommand {
         let p_obj = Arc::new(PersistentObject::new(repo.clone()));
         
         // Load or create master key
-        let master_key = load_or_create_master_key(&self.master_key_path)?;
+        let master_key = self.master_key()?;
         
         let p_creds = PersistentCredentials {
             p_obj: p_obj.clone(),
@@ -78,7 +89,7 @@ impl BaseCommand {
         let p_obj = Arc::new(PersistentObject::new(repo.clone()));
         
         // Load or create master key
-        let master_key = load_or_create_master_key(&self.master_key_path)?;
+        let master_key = self.master_key()?;
         
         let p_creds = PersistentCredentials {
             p_obj: p_obj.clone(),
@@ -92,6 +103,22 @@ impl BaseCommand {
         })
     }
 
+    /// Loads the master key from the source selected by `META_SECRET_MASTER_KEY_SOURCE`,
+    /// the passphrase is asked for only if the key file is encrypted
+    pub fn master_key(&self) -> Result<TransportSk> {
+        let source = match std::env::var(MASTER_KEY_SOURCE_ENV) {
+            Ok(source) => MasterKeySource::from_str(&source)?,
+            Err(_) => MasterKeySource::default(),
+        };
+
+        let key_path = Path::new(&self.master_key_path);
+        let is_new_key = !key_path.exists();
+        let provider = source.provider(key_path, || {
+            read_passphrase("Master key passphrase", is_new_key)
+        })?;
+        provider.master_key()
+    }
+
     /// Common error message for credentials that already exist
     pub fn already_exists_error(entity: &str) -> String {
         let err_msg = "credentials already exist. Cannot initialize again.";
@@ -99,6 +126,20 @@ impl BaseCommand {
         format!("{} {} {}", entity, err_msg, info_msg)
     }
 
+    /// What to do when the server and meta-cli speak different protocols
+    pub fn upgrade_required_hint(upgrade: &UpgradeRequired) -> String {
+        match upgrade.component {
+            UpgradeComponent::Client => format!(
+                "meta-cli {} is too old for the server {}. Please upgrade meta-cli.",
+                upgrade.client_version, upgrade.server_version
+            ),
+            UpgradeComponent::Server => format!(
+                "The server {} is too old for meta-cli {}. Please ask the server operator to upgrade it.",
+                upgrade.server_version, upgrade.client_version
+            ),
+        }
+    }
+
     /// Common error handling for missing device credentials
     pub async fn ensure_device_creds<Repo: KvLogEventRepo>(
         &self,
@@ -152,16 +193,14 @@ impl BaseCommand {
         // Get master key
         let master_key = db_context.p_creds.master_key.clone();
 
-        let sync_protocol = HttpSyncProtocol {
-            api_url: self.api_url,
-        };
+        let sync_protocol = HttpSyncProtocol::new(self.api_url);
 
-        let sync_gateway = Arc::new(SyncGateway {
-            id: "meta-cli".to_string(),
-            p_obj: db_context.p_obj.clone(),
-            sync: Arc::new(sync_protocol),
-            master_key: master_key.clone(),
-        });
+        let sync_gateway = Arc::new(SyncGateway::new(
+            "meta-cli".to_string(),
+            db_context.p_obj.clone(),
+            Arc::new(sync_protocol),
+            master_key.clone(),
+        ));
 
         let state_provider = Arc::new(MetaClientStateProvider::new());
 
@@ -177,3 +216,27 @@ impl BaseCommand {
         })
     }
 }
+
+/// Takes the passphrase from `META_SECRET_PASSPHRASE` or asks for it in the terminal
+pub fn read_passphrase(prompt: &str, confirm: bool) -> Result<SecretString> {
+    read_passphrase_from(PASSPHRASE_ENV, prompt, confirm)
+}
+
+pub fn read_passphrase_from(env_var: &str, prompt: &str, confirm: bool) -> Result<SecretString> {
+    if let Ok(passphrase) = std::env::var(env_var) {
+        return Ok(SecretString::from(passphrase));
+    }
+
+    if !io::stdin().is_terminal() {
+        bail!(
+            "No terminal detected for passphrase input. Set {} instead.",
+            env_var
+        );
+    }
+
+    let mut password = Password::new().with_prompt(prompt);
+    if confirm {
+        password = password.with_confirmation("Confirm passphrase", "Passphrases don't match");
+    }
+    Ok(SecretString::from(password.interact()?))
+}
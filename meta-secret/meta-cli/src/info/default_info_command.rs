@@ -59,20 +59,19 @@ impl InfoCommandTrait for DefaultInfoCommand {
 
         let maybe_user_creds = db_context.p_creds.get_user_creds().await?;
 
-        if let Some(user_creds) = maybe_user_creds {
-            context.insert(
-                "user",
-                &json!({
-                    "vault_name": user_creds.vault_name
-                }),
-            );
-        } else {
+        let Some(user_creds) = maybe_user_creds else {
             // Just render the template with the current context to show the "no user" message
             let output =
                 TemplateManager::instance().render("info", &context, self.base.output_format())?;
             print!("{}", output);
             return Ok(());
-        }
+        };
+        context.insert(
+            "user",
+            &json!({
+                "vault_name": user_creds.vault_label_name()
+            }),
+        );
 
         // Get app state using client service
         let client = self.base.base().create_client_service(&db_context).await?;
@@ -89,21 +88,21 @@ impl InfoCommandTrait for DefaultInfoCommand {
                 );
             }
             ApplicationState::Vault(vault_info) => match vault_info {
-                VaultFullInfo::NotExists(user_data) => {
+                VaultFullInfo::NotExists(_) => {
                     context.insert(
                         "app_state",
                         &json!({
                             "status": "Vault not exists",
-                            "vault_name": user_data.vault_name(),
+                            "vault_name": user_creds.vault_label_name(),
                         }),
                     );
                 }
-                VaultFullInfo::Outsider(outsider) => {
+                VaultFullInfo::Outsider(_) => {
                     context.insert(
                         "app_state",
                         &json!({
                             "status": "Outsider",
-                            "vault_name": outsider.user_data.vault_name(),
+                            "vault_name": user_creds.vault_label_name(),
                         }),
                     );
                 }
@@ -147,6 +146,8 @@ impl InfoCommandTrait for DefaultInfoCommand {
                                         SsDistributionStatus::Sent => "Sent",
                                         SsDistributionStatus::Delivered => "Delivered",
                                         SsDistributionStatus::Declined => "Declined",
+                                        SsDistributionStatus::Expired => "Expired",
+                                        SsDistributionStatus::Approved => "Approved",
                                     });
 
                             receivers.push(json!({
@@ -182,12 +183,32 @@ impl InfoCommandTrait for DefaultInfoCommand {
                                     "sender": format!("{:?}", meta_pass.sender.user_data.user_id()),
                                 }));
                             }
+                            VaultActionRequestEvent::RotateDeviceKeys(rotation) => {
+                                events.push(json!({
+                                    "type": "RotateDeviceKeys",
+                                    "user_id": format!("{:?}", rotation.member.user_data.user_id()),
+                                }));
+                            }
+                            VaultActionRequestEvent::AddInvite(add_invite) => {
+                                events.push(json!({
+                                    "type": "AddInvite",
+                                    "sender": format!("{:?}", add_invite.sender.user_data.user_id()),
+                                }));
+                            }
+                            VaultActionRequestEvent::UpdateSettings(settings) => {
+                                events.push(json!({
+                                    "type": "UpdateSettings",
+                                    "sender": format!("{:?}", settings.sender.user_data.user_id()),
+                                    "join_quorum": settings.join_quorum,
+                                    "require_invite": settings.require_invite,
+                                }));
+                            }
                         }
                     }
 
                     context.insert("app_state", &json!({
                         "status": "Member",
-                        "vault_name": member_info.member.vault.vault_name,
+                        "vault_name": user_creds.vault_label_name(),
                         "vault": {
                             "users": users,
                             "owner_id": member_info.member.member.user_data.user_id().device_id.id_str(),
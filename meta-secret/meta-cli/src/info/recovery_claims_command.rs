@@ -50,6 +50,8 @@ impl InfoCommandTrait for RecoveryClaimsInfoCommand {
                                 SsDistributionStatus::Sent => "Sent",
                                 SsDistributionStatus::Delivered => "Delivered",
                                 SsDistributionStatus::Declined => "Declined",
+                                SsDistributionStatus::Expired => "Expired",
+                                SsDistributionStatus::Approved => "Approved",
                             });
 
                         receivers.push(json!({
@@ -64,6 +66,20 @@ impl InfoCommandTrait for RecoveryClaimsInfoCommand {
                         "type": format!("{:?}", ss_claim.distribution_type),
                         "password": ss_claim.dist_claim_id.pass_id.name.clone(),
                         "status": format!("{:?}", ss_claim.status.status()),
+                        "created_at": ss_claim.time.as_ref().map(|time| time.created_at),
+                        "received_at": ss_claim.time.as_ref().and_then(|time| time.received_at),
+                        "expires_at": ss_claim.expires_at(),
+                        "fallback_at": ss_claim.fallback_at(),
+                        "break_glass_escrow": ss_claim
+                            .break_glass
+                            .as_ref()
+                            .map(|break_glass| break_glass.escrow.clone().id_str()),
+                        "break_glass_at": ss_claim.break_glass_at(),
+                        "vetoed_by": ss_claim
+                            .break_glass
+                            .as_ref()
+                            .and_then(|break_glass| break_glass.vetoed_by.clone())
+                            .map(|device_id| device_id.id_str()),
                         "receivers": receivers
                     }));
                 }
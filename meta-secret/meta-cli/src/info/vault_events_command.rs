@@ -40,22 +40,42 @@ impl InfoCommandTrait for VaultEventsInfoCommand {
 
                 let mut events_vec = Vec::new();
                 for request in &member_info.vault_events.requests {
-                    match request {
-                        VaultActionRequestEvent::JoinCluster(join_request) => {
-                            events_vec.push(json!({
-                                "type": "JoinCluster",
-                                "device_name": join_request.candidate.device.device_name.as_str().to_string(),
-                                "user_id": format!("{:?}", join_request.candidate.user_id())
-                            }));
-                        }
-                        VaultActionRequestEvent::AddMetaPass(meta_pass) => {
-                            events_vec.push(json!({
-                                "type": "AddMetaPass",
-                                "meta_pass_id": format!("{:?}", meta_pass.meta_pass_id),
-                                "sender": format!("{:?}", meta_pass.sender.user_data.user_id())
-                            }));
-                        }
-                    }
+                    let mut event_json = match request {
+                        VaultActionRequestEvent::JoinCluster(join_request) => json!({
+                            "type": "JoinCluster",
+                            "device_name": join_request.candidate.device.device_name.as_str().to_string(),
+                            "user_id": format!("{:?}", join_request.candidate.user_id()),
+                            "approvals": member_info.vault_events.join_approvers(join_request).len(),
+                            "required_approvals": member_info.member.vault.required_join_approvals(),
+                            "invited": join_request.invite.is_some()
+                        }),
+                        VaultActionRequestEvent::AddMetaPass(meta_pass) => json!({
+                            "type": "AddMetaPass",
+                            "meta_pass_id": format!("{:?}", meta_pass.meta_pass_id),
+                            "sender": format!("{:?}", meta_pass.sender.user_data.user_id())
+                        }),
+                        VaultActionRequestEvent::RotateDeviceKeys(rotation) => json!({
+                            "type": "RotateDeviceKeys",
+                            "user_id": format!("{:?}", rotation.member.user_data.user_id())
+                        }),
+                        VaultActionRequestEvent::AddInvite(add_invite) => json!({
+                            "type": "AddInvite",
+                            "sender": format!("{:?}", add_invite.sender.user_data.user_id()),
+                            "expires_at": add_invite.invite.expires_at
+                        }),
+                        VaultActionRequestEvent::UpdateSettings(settings) => json!({
+                            "type": "UpdateSettings",
+                            "sender": format!("{:?}", settings.sender.user_data.user_id()),
+                            "join_quorum": settings.join_quorum,
+                            // a text, so that the templates tell false from a setting left out
+                            "require_invite": settings.require_invite.map(|value| value.to_string())
+                        }),
+                    };
+
+                    let time = member_info.vault_events.request_time(request);
+                    event_json["created_at"] = json!(time.map(|time| time.created_at));
+                    event_json["received_at"] = json!(time.and_then(|time| time.received_at));
+                    events_vec.push(event_json);
                 }
 
                 context.insert("events", &events_vec);
@@ -45,7 +45,7 @@ impl InitUserCommand {
         // Generate user credentials
         let user_creds = db_context
             .p_creds
-            .get_or_generate_user_creds_with_type(
+            .get_or_generate_labeled_user_creds(
                 device_name.clone(),
                 device_type.clone(),
                 self.vault_name.clone(),
@@ -57,7 +57,7 @@ impl InitUserCommand {
         println!("Device ID: {}", device_creds.device.device_id);
         println!("Device Name: {:?}", device_name);
         println!("Device Type: {:?}", device_type.as_str());
-        println!("Vault Name: {}", user_creds.vault_name.0);
+        println!("Vault Name: {}", user_creds.vault_label_name());
 
         Ok(())
     }
@@ -124,12 +124,14 @@ mod tests {
             "User credentials should exist after user initialization"
         );
 
-        // Verify the vault name is correct
-        let user_creds = maybe_user_creds.unwrap().value();
+        // The vault name becomes the label, the server only gets the opaque name
+        let user_creds = db_context.p_creds.get_user_creds().await?.unwrap();
         assert_eq!(
-            user_creds.vault_name.0, vault_name.0,
+            user_creds.vault_label_name(),
+            vault_name,
             "Vault name should match the input value"
         );
+        assert_ne!(user_creds.vault_name, vault_name);
 
         // Try to run the command again - should fail because user credentials already exist
         let second_result = init_user_cmd.execute_with_context(&db_context).await;
@@ -0,0 +1,52 @@
+use crate::base_command::{read_passphrase, read_passphrase_from, BaseCommand};
+use anyhow::{bail, Result};
+use meta_secret_core::crypto::master_key::{
+    KeyFileKind, MasterKeyProvider, PassphraseKeyFile, PlainKeyFile,
+};
+use secrecy::ExposeSecret;
+use std::path::Path;
+
+/// New passphrase for non-interactive runs
+pub const NEW_PASSPHRASE_ENV: &str = "META_SECRET_NEW_PASSPHRASE";
+
+/// Sets or changes the passphrase of the master key file,
+/// a plain key file gets encrypted
+pub struct ChangePassphraseCommand {
+    pub base: BaseCommand,
+}
+
+impl ChangePassphraseCommand {
+    pub fn new(db_name: String) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+        }
+    }
+
+    pub fn execute(&self) -> Result<()> {
+        let key_path = Path::new(&self.base.master_key_path);
+
+        let master_key = match KeyFileKind::of(key_path)? {
+            None => {
+                bail!(
+                    "Master key file {} not found. Please run 'meta-secret init device' command first.",
+                    self.base.master_key_path
+                );
+            }
+            Some(KeyFileKind::Plain) => PlainKeyFile::new(key_path).master_key()?,
+            Some(KeyFileKind::Encrypted) => {
+                let passphrase = read_passphrase("Current passphrase", false)?;
+                PassphraseKeyFile::new(key_path, passphrase).master_key()?
+            }
+        };
+
+        let new_passphrase = read_passphrase_from(NEW_PASSPHRASE_ENV, "New passphrase", true)?;
+        if new_passphrase.expose_secret().is_empty() {
+            bail!("The passphrase must not be empty");
+        }
+
+        PassphraseKeyFile::new(key_path, new_passphrase).save(&master_key)?;
+
+        println!("Passphrase of the master key has been changed");
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+pub mod change_passphrase_command;
+pub mod rotate_device_keys_command;
@@ -0,0 +1,32 @@
+use crate::base_command::BaseCommand;
+use anyhow::Result;
+use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
+
+/// Replaces the transport and dsa keys of the device, the device id stays the same
+pub struct RotateDeviceKeysCommand {
+    pub base: BaseCommand,
+}
+
+impl RotateDeviceKeysCommand {
+    pub fn new(db_name: String) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+        }
+    }
+
+    pub async fn execute(self) -> Result<()> {
+        let db_context = self.base.open_existing_db().await?;
+
+        // Ensure user credentials exist
+        self.base.ensure_user_creds(&db_context).await?;
+
+        self.base
+            .handle_client_request(&db_context, GenericAppStateRequest::RotateDeviceKeys)
+            .await?;
+
+        println!("Device keys have been rotated");
+        println!("Vault members will use the new keys after their next sync");
+
+        Ok(())
+    }
+}
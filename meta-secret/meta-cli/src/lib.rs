@@ -1,8 +1,11 @@
+pub mod audit;
 pub mod auth;
 pub mod base_command;
 pub mod cli_format;
 pub mod info;
 pub mod init;
 pub mod interactive_command;
+pub mod key;
 pub mod secret;
+pub mod sync;
 pub mod template_manager;
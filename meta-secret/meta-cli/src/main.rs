@@ -1,17 +1,25 @@
 extern crate core;
+mod audit;
 mod auth;
 mod base_command;
 mod cli_format;
 mod info;
 mod init;
 mod interactive_command;
+mod key;
 mod secret;
+mod sync;
 mod template_manager;
 
+use crate::audit::list_command::{AuditListCommand, AuditSince};
 use crate::auth::accept_all_join_requests_command::AcceptAllJoinRequestsCommand;
 use crate::auth::accept_join_request_command::AcceptJoinRequestCommand;
+use crate::auth::create_invite_command::CreateInviteCommand;
 use crate::auth::interactive_command::AuthInteractiveCommand;
+use crate::auth::join_with_invite_command::JoinWithInviteCommand;
 use crate::auth::sign_up_command::JoinVaultCommand;
+use crate::auth::vault_settings_command::VaultSettingsCommand;
+use crate::base_command::BaseCommand;
 use crate::cli_format::CliOutputFormat;
 use crate::info::default_info_command::DefaultInfoCommand;
 use crate::info::info_command_base::InfoCommandTrait;
@@ -23,18 +31,27 @@ use crate::init::device_command::InitDeviceCommand;
 use crate::init::interactive_command::InitInteractiveCommand;
 use crate::init::user_command::InitUserCommand;
 use crate::interactive_command::InteractiveCommand;
+use crate::key::change_passphrase_command::ChangePassphraseCommand;
+use crate::key::rotate_device_keys_command::RotateDeviceKeysCommand;
 use crate::secret::accept_all_recovery_requests_command::AcceptAllRecoveryRequestsCommand;
 use crate::secret::accept_recovery_request_command::AcceptRecoveryRequestCommand;
+use crate::secret::cancel_recovery_command::CancelRecoveryCommand;
 use crate::secret::interactive_command::SecretInteractiveCommand;
 use crate::secret::recovery_request_command::RecoveryRequestCommand;
 use crate::secret::show_secret_command::ShowSecretCommand;
 use crate::secret::split_command::SplitCommand;
+use crate::secret::veto_recovery_command::VetoRecoveryCommand;
+use crate::sync::export_command::SyncExportCommand;
+use crate::sync::import_command::SyncImportCommand;
+use crate::sync::relay_command::SyncRelayCommand;
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use dialoguer::Password;
+use meta_secret_core::node::api::UpgradeRequired;
 use meta_secret_core::node::common::model::meta_pass::PlainPassInfo;
 use meta_secret_core::node::common::model::vault::vault::VaultName;
 use std::io::{self, IsTerminal, Read};
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[command(about = "Meta Secret CLI", long_about = None)]
@@ -63,6 +80,23 @@ enum Command {
         #[command(subcommand)]
         command: SecretCommand,
     },
+    /// Offline sync through bundle files for air-gapped devices
+    Sync {
+        #[command(subcommand)]
+        command: SyncCommand,
+    },
+    /// Manage the keys of the device and the master key that protects the local credentials.
+    /// The master key source is selected by META_SECRET_MASTER_KEY_SOURCE:
+    /// file (default), passphrase-file, env:<VAR>, stdin or command:<CMD>
+    Key {
+        #[command(subcommand)]
+        command: KeyCommand,
+    },
+    /// Signed history of the actions taken in the vault
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommand,
+    },
     /// Show information about the device and credentials
     Info {
         #[command(subcommand)]
@@ -98,6 +132,36 @@ enum AuthCommand {
     },
     /// Accept all pending join requests
     AcceptAllJoinRequests,
+    /// Make a one-time invite to the vault
+    Invite {
+        /// Hours the invite can be used
+        #[arg(long, default_value_t = 24 * 7)]
+        valid_hours: u64,
+        /// Accept the device that joins with the invite without asking
+        #[arg(long)]
+        pre_approved: bool,
+        /// Save the invite as a QR code image too
+        #[arg(long)]
+        qr_out: Option<PathBuf>,
+    },
+    /// Join a vault with an invite of a member
+    JoinWithInvite {
+        /// The invite text
+        #[arg(long, conflicts_with = "invite_qr")]
+        invite: Option<String>,
+        /// QR code image of the invite
+        #[arg(long)]
+        invite_qr: Option<PathBuf>,
+    },
+    /// Change the settings of the vault, loosening them takes the join quorum of the members
+    Settings {
+        /// Member approvals a device needs to join the vault
+        #[arg(long)]
+        join_quorum: Option<usize>,
+        /// Devices join the vault with an invite of a member only
+        #[arg(long)]
+        require_invite: Option<bool>,
+    },
     /// Interactive mode for authentication
     Interactive,
 }
@@ -113,10 +177,37 @@ enum SecretCommand {
         /// Read password from stdin (pipe) instead of prompting
         #[arg(long)]
         stdin: bool,
+
+        /// Number of distinct devices that have to approve a recovery of the secret
+        #[arg(long)]
+        approvals: Option<usize>,
+
+        /// Device type whose approvals count (repeatable), any type by default
+        #[arg(long)]
+        approver_type: Vec<String>,
+
+        /// Device id or name of a member whose approvals count (repeatable), any member by default
+        #[arg(long)]
+        approver: Vec<String>,
+
+        /// Device id or name of the escrow member that releases its share of a recovery
+        /// by itself if nobody vetoes the recovery in time
+        #[arg(long)]
+        break_glass_escrow: Option<String>,
+
+        /// Hours the members have to veto a break-glass recovery, 72 by default
+        #[arg(long)]
+        break_glass_delay: Option<u64>,
     },
     RecoveryRequest {
         #[arg(long)]
         pass_name: String,
+        /// Device id or name of a member to ask for its share (repeatable), all members by default
+        #[arg(long)]
+        from: Vec<String>,
+        /// Minutes to wait for the chosen devices before the rest of the members are asked
+        #[arg(long)]
+        fallback_after: Option<u64>,
     },
     Show {
         #[arg(long)]
@@ -126,12 +217,71 @@ enum SecretCommand {
         #[arg(long)]
         claim_id: String,
     },
+    /// Withdraw a recovery request made by this device
+    CancelRecovery {
+        #[arg(long)]
+        claim_id: String,
+    },
+    /// Stop the escrow device from releasing its share of a break-glass recovery
+    VetoRecovery {
+        #[arg(long)]
+        claim_id: String,
+    },
     /// Accept all pending recovery requests
     AcceptAllRecoveryRequests,
     /// Interactive mode for secret management
     Interactive,
 }
 
+#[derive(Subcommand, Debug)]
+enum SyncCommand {
+    /// Write pending sync requests into a bundle for the relay device
+    Export {
+        /// Device id of the vault member that relays the bundle to the server
+        #[arg(long)]
+        relay_device_id: String,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Send a bundle of an air-gapped device to the server and write the response bundle
+    Relay {
+        #[arg(long)]
+        input: PathBuf,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Apply a response bundle received from the relay device
+    Import {
+        #[arg(long)]
+        input: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum KeyCommand {
+    /// Set or change the passphrase of the master key file.
+    /// META_SECRET_PASSPHRASE and META_SECRET_NEW_PASSPHRASE replace the prompts
+    ChangePassphrase,
+    /// Replace the transport and signing keys of the device, the device id stays the same
+    RotateDeviceKeys,
+}
+
+#[derive(Subcommand, Debug)]
+enum AuditCommand {
+    /// List audit entries: joins, secrets, key rotations, recovery requests and share deliveries
+    List {
+        /// Period (30d, 12h, 15m) or unix time in milliseconds
+        #[arg(long)]
+        since: Option<AuditSince>,
+        /// Device id or device name
+        #[arg(long)]
+        device: Option<String>,
+        /// Secret name
+        #[arg(long)]
+        secret: Option<String>,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 enum InfoSubCommand {
     /// Show information about recovery claims
@@ -171,6 +321,16 @@ async fn main() -> Result<()> {
 
     let args = CmdLine::parse();
 
+    let result = run(args).await;
+    if let Err(err) = &result
+        && let Some(upgrade) = err.downcast_ref::<UpgradeRequired>()
+    {
+        eprintln!("{}", BaseCommand::upgrade_required_hint(upgrade));
+    }
+    result
+}
+
+async fn run(args: CmdLine) -> Result<()> {
     let db_name = String::from("meta-secret.redb");
 
     match args.command {
@@ -210,6 +370,17 @@ async fn main() -> Result<()> {
                 cmd.execute().await?
             }
         },
+        Command::Audit { command } => match command {
+            AuditCommand::List {
+                since,
+                device,
+                secret,
+            } => {
+                let cmd =
+                    AuditListCommand::new(db_name, args.output_format, since, device, secret);
+                cmd.execute().await?
+            }
+        },
         Command::Auth { command } => match command {
             AuthCommand::SignUp => {
                 let sign_up_cmd = JoinVaultCommand::new(db_name);
@@ -223,13 +394,40 @@ async fn main() -> Result<()> {
                 let accept_all_cmd = AcceptAllJoinRequestsCommand::new(db_name);
                 accept_all_cmd.execute().await?
             }
+            AuthCommand::Invite {
+                valid_hours,
+                pre_approved,
+                qr_out,
+            } => {
+                let invite_cmd = CreateInviteCommand::new(db_name, valid_hours, pre_approved, qr_out);
+                invite_cmd.execute().await?
+            }
+            AuthCommand::JoinWithInvite { invite, invite_qr } => {
+                let join_cmd = JoinWithInviteCommand::new(db_name, invite, invite_qr);
+                join_cmd.execute().await?
+            }
+            AuthCommand::Settings {
+                join_quorum,
+                require_invite,
+            } => {
+                let settings_cmd = VaultSettingsCommand::new(db_name, join_quorum, require_invite);
+                settings_cmd.execute().await?
+            }
             AuthCommand::Interactive => {
                 let auth_interactive_cmd = AuthInteractiveCommand::new(db_name);
                 auth_interactive_cmd.execute().await?
             }
         },
         Command::Secret { command } => match command {
-            SecretCommand::Split { pass_name, stdin } => {
+            SecretCommand::Split {
+                pass_name,
+                stdin,
+                approvals,
+                approver_type,
+                approver,
+                break_glass_escrow,
+                break_glass_delay,
+            } => {
                 let pass = if stdin {
                     // Read password from stdin (pipe)
                     read_password_from_stdin()?
@@ -248,11 +446,18 @@ async fn main() -> Result<()> {
                 };
 
                 let plain_pass = PlainPassInfo::new(pass_name, pass);
-                let split_cmd = SplitCommand::new(db_name);
+                let split_cmd = SplitCommand::new(db_name)
+                    .with_policy(approvals, approver_type, approver)
+                    .with_break_glass(break_glass_escrow, break_glass_delay);
                 split_cmd.execute(plain_pass).await?
             }
-            SecretCommand::RecoveryRequest { pass_name } => {
-                let recover_cmd = RecoveryRequestCommand::new(db_name, pass_name);
+            SecretCommand::RecoveryRequest {
+                pass_name,
+                from,
+                fallback_after,
+            } => {
+                let recover_cmd = RecoveryRequestCommand::new(db_name, pass_name)
+                    .with_targets(from, fallback_after);
                 recover_cmd.execute().await?
             }
             SecretCommand::Show { claim_id } => {
@@ -263,6 +468,14 @@ async fn main() -> Result<()> {
                 let accept_recover_cmd = AcceptRecoveryRequestCommand::new(db_name, claim_id);
                 accept_recover_cmd.execute().await?
             }
+            SecretCommand::CancelRecovery { claim_id } => {
+                let cancel_recovery_cmd = CancelRecoveryCommand::new(db_name, claim_id);
+                cancel_recovery_cmd.execute().await?
+            }
+            SecretCommand::VetoRecovery { claim_id } => {
+                let veto_recovery_cmd = VetoRecoveryCommand::new(db_name, claim_id);
+                veto_recovery_cmd.execute().await?
+            }
             SecretCommand::AcceptAllRecoveryRequests => {
                 let accept_all_recover_cmd = AcceptAllRecoveryRequestsCommand::new(db_name);
                 accept_all_recover_cmd.execute().await?
@@ -272,6 +485,33 @@ async fn main() -> Result<()> {
                 secret_interactive_cmd.execute().await?
             }
         },
+        Command::Sync { command } => match command {
+            SyncCommand::Export {
+                relay_device_id,
+                out,
+            } => {
+                let export_cmd = SyncExportCommand::new(db_name, relay_device_id, out);
+                export_cmd.execute().await?
+            }
+            SyncCommand::Relay { input, out } => {
+                let relay_cmd = SyncRelayCommand::new(db_name, input, out);
+                relay_cmd.execute().await?
+            }
+            SyncCommand::Import { input } => {
+                let import_cmd = SyncImportCommand::new(db_name, input);
+                import_cmd.execute().await?
+            }
+        },
+        Command::Key { command } => match command {
+            KeyCommand::ChangePassphrase => {
+                let change_passphrase_cmd = ChangePassphraseCommand::new(db_name);
+                change_passphrase_cmd.execute()?
+            }
+            KeyCommand::RotateDeviceKeys => {
+                let rotate_cmd = RotateDeviceKeysCommand::new(db_name);
+                rotate_cmd.execute().await?
+            }
+        },
         Command::Interactive => {
             let interactive_cmd = InteractiveCommand::new(db_name);
             interactive_cmd.execute().await?
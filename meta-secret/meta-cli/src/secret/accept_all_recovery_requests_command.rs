@@ -1,5 +1,6 @@
 use crate::base_command::BaseCommand;
 use anyhow::{bail, Result};
+use meta_secret_core::node::common::clock::unix_time_millis;
 use meta_secret_core::node::common::model::secret::SecretDistributionType;
 use meta_secret_core::node::common::model::{ApplicationState, VaultFullInfo};
 use tracing::info;
@@ -42,13 +43,15 @@ impl AcceptAllRecoveryRequestsCommand {
                 VaultFullInfo::Member(member_info) => {
                     info!("Finding all pending recovery requests");
 
-                    // Filter recovery requests from ss_claims
+                    // Filter recovery requests from ss_claims, expired ones can't be accepted
+                    let now = unix_time_millis();
                     let recovery_requests: Vec<_> = member_info
                         .ss_claims
                         .claims
                         .iter()
                         .filter(|(_, claim)| {
                             claim.distribution_type == SecretDistributionType::Recover
+                                && !claim.is_expired(now)
                         })
                         .collect();
                     let recovery_requests_num = recovery_requests.len();
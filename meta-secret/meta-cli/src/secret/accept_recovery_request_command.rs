@@ -72,6 +72,16 @@ impl AcceptRecoveryRequestCommand {
                                 "Recovery request for password '{}' accepted successfully",
                                 claim.dist_claim_id.pass_id.name
                             );
+
+                            let vault = &member_info.member.vault;
+                            if let Some(policy) =
+                                vault.recovery_policy(&claim.dist_claim_id.pass_id)
+                            {
+                                println!(
+                                    "The share is released once the claim has {} approvals",
+                                    policy.approvals
+                                );
+                            }
                             Ok(())
                         }
                         None => {
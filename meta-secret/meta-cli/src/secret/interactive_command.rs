@@ -2,9 +2,11 @@ use crate::base_command::BaseCommand;
 use crate::cli_format::CliOutputFormat;
 use crate::secret::accept_all_recovery_requests_command::AcceptAllRecoveryRequestsCommand;
 use crate::secret::accept_recovery_request_command::AcceptRecoveryRequestCommand;
+use crate::secret::cancel_recovery_command::CancelRecoveryCommand;
 use crate::secret::recovery_request_command::RecoveryRequestCommand;
 use crate::secret::show_secret_command::ShowSecretCommand;
 use crate::secret::split_command::SplitCommand;
+use crate::secret::veto_recovery_command::VetoRecoveryCommand;
 use anyhow::Result;
 use dialoguer::{theme::ColorfulTheme, Input, Password, Select};
 use meta_secret_core::node::common::model::meta_pass::PlainPassInfo;
@@ -23,6 +25,10 @@ pub enum SecretOption {
     AcceptRecoveryRequest,
     #[strum(to_string = "Accept All Recovery Requests")]
     AcceptAllRecoveryRequests,
+    #[strum(to_string = "Cancel Recovery Request")]
+    CancelRecovery,
+    #[strum(to_string = "Veto Break-Glass Recovery")]
+    VetoRecovery,
     #[strum(to_string = "Back to Main Menu")]
     Back,
 }
@@ -109,6 +115,26 @@ impl SecretInteractiveCommand {
                     AcceptAllRecoveryRequestsCommand::new(self.base.db_name.clone());
                 accept_all_recover_cmd.execute().await?
             }
+            SecretOption::CancelRecovery => {
+                // Cancel Recovery Request
+                let claim_id = Input::<String>::new()
+                    .with_prompt("Enter claim ID")
+                    .interact()?;
+
+                let cancel_recovery_cmd =
+                    CancelRecoveryCommand::new(self.base.db_name.clone(), claim_id);
+                cancel_recovery_cmd.execute().await?
+            }
+            SecretOption::VetoRecovery => {
+                // Veto Break-Glass Recovery
+                let claim_id = Input::<String>::new()
+                    .with_prompt("Enter claim ID")
+                    .interact()?;
+
+                let veto_recovery_cmd =
+                    VetoRecoveryCommand::new(self.base.db_name.clone(), claim_id);
+                veto_recovery_cmd.execute().await?
+            }
             SecretOption::Back => {
                 // Back to main menu
                 println!("Returning to main menu");
@@ -129,7 +155,7 @@ mod tests {
         let options: Vec<SecretOption> = SecretOption::iter().collect();
 
         // Verify the order matches expected indices
-        assert_eq!(options.len(), 6);
+        assert_eq!(options.len(), 8);
         assert!(matches!(options[0], SecretOption::SplitSecret));
         assert!(matches!(options[1], SecretOption::RequestRecovery));
         assert!(matches!(options[2], SecretOption::ShowSecret));
@@ -138,7 +164,9 @@ mod tests {
             options[4],
             SecretOption::AcceptAllRecoveryRequests
         ));
-        assert!(matches!(options[5], SecretOption::Back));
+        assert!(matches!(options[5], SecretOption::CancelRecovery));
+        assert!(matches!(options[6], SecretOption::VetoRecovery));
+        assert!(matches!(options[7], SecretOption::Back));
     }
 
     #[test]
@@ -158,6 +186,14 @@ mod tests {
             SecretOption::AcceptAllRecoveryRequests.to_string(),
             "Accept All Recovery Requests"
         );
+        assert_eq!(
+            SecretOption::CancelRecovery.to_string(),
+            "Cancel Recovery Request"
+        );
+        assert_eq!(
+            SecretOption::VetoRecovery.to_string(),
+            "Veto Break-Glass Recovery"
+        );
         assert_eq!(SecretOption::Back.to_string(), "Back to Main Menu");
     }
 }
@@ -1,6 +1,8 @@
 pub mod accept_all_recovery_requests_command;
 pub mod accept_recovery_request_command;
+pub mod cancel_recovery_command;
 pub mod interactive_command;
 pub mod recovery_request_command;
 pub mod show_secret_command;
 pub mod split_command;
+pub mod veto_recovery_command;
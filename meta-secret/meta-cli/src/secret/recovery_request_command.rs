@@ -1,39 +1,105 @@
 use crate::base_command::BaseCommand;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
-use meta_secret_core::node::common::model::meta_pass::MetaPasswordId;
+use meta_secret_core::node::common::model::device::common::DeviceId;
+use meta_secret_core::node::common::model::secret::RecoveryTargets;
+use meta_secret_core::node::common::model::vault::vault_data::VaultData;
+use meta_secret_core::node::common::model::{ApplicationState, VaultFullInfo};
 
 pub struct RecoveryRequestCommand {
     pub base: BaseCommand,
-    pub pass_id: MetaPasswordId,
+    /// Name or id of the password, the name is resolved against the vault on the device
+    pub pass_name: String,
+    /// Device ids or names of the members to ask, all members if empty
+    pub from: Vec<String>,
+    /// Minutes to wait for the chosen devices before the rest of the members are asked
+    pub fallback_after: Option<u64>,
 }
 
 impl RecoveryRequestCommand {
     pub fn new(db_name: String, pass_name: String) -> Self {
         Self {
             base: BaseCommand::new(db_name),
-            pass_id: MetaPasswordId::build(pass_name),
+            pass_name,
+            from: vec![],
+            fallback_after: None,
         }
     }
 
+    pub fn with_targets(mut self, from: Vec<String>, fallback_after: Option<u64>) -> Self {
+        self.from = from;
+        self.fallback_after = fallback_after;
+        self
+    }
+
     pub async fn execute(self) -> Result<()> {
         let db_context = self.base.open_existing_db().await?;
 
         // Ensure user credentials exist
         self.base.ensure_user_creds(&db_context).await?;
 
+        let client = self.base.create_client_service(&db_context).await?;
+        let app_state = client.get_app_state().await?;
+        let ApplicationState::Vault(VaultFullInfo::Member(member_info)) = app_state else {
+            bail!("Invalid state. Only vault members can request a recovery");
+        };
+
+        let vault = &member_info.member.vault;
+        let pass_id = vault.find_secret_by_name(&self.pass_name)?;
+
+        let targets = if self.from.is_empty() {
+            if self.fallback_after.is_some() {
+                bail!("--fallback-after requires the devices to ask first (--from)");
+            }
+            RecoveryTargets::All
+        } else {
+            let devices = self
+                .from
+                .iter()
+                .map(|device| find_device(vault, device))
+                .collect::<Result<Vec<DeviceId>>>()?;
+
+            match self.fallback_after {
+                None => RecoveryTargets::Devices(devices),
+                Some(minutes) => RecoveryTargets::Preferred {
+                    devices,
+                    fallback_after: minutes * 60 * 1000,
+                },
+            }
+        };
+
         // Create recovery request with password ID and handle it
-        let recovery_request = GenericAppStateRequest::Recover(self.pass_id.clone());
+        let recovery_request = GenericAppStateRequest::Recover(pass_id, targets);
         self.base
             .handle_client_request(&db_context, recovery_request)
             .await?;
 
         println!(
-            "Recovery request for '{:?}' submitted successfully",
-            self.pass_id
+            "Recovery request for '{}' submitted successfully",
+            self.pass_name
         );
         println!("The secret will be recovered when enough shares are available");
 
         Ok(())
     }
 }
+
+/// Finds a vault member by device id or device name
+pub(crate) fn find_device(vault: &VaultData, device: &str) -> Result<DeviceId> {
+    let found: Vec<DeviceId> = vault
+        .members()
+        .into_iter()
+        .map(|member| member.user_data.device)
+        .filter(|device_data| {
+            device_data.device_id.to_string() == device
+                || device_data.device_name.as_str() == device
+        })
+        .map(|device_data| device_data.device_id)
+        .collect();
+
+    match found.as_slice() {
+        [device_id] => Ok(device_id.clone()),
+        [] => bail!("Device '{}' is not a member of the vault", device),
+        _ => bail!("Device name '{}' is ambiguous, use the device id", device),
+    }
+}
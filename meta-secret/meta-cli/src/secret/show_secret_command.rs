@@ -58,7 +58,7 @@ impl ShowSecretCommand {
 
                                 // Clone claim_id to avoid ownership issues
                                 let claim_id_for_recovery = claim_id.clone();
-                                let secret = handler
+                                let recovery = handler
                                     .recover(
                                         user_creds,
                                         claim_id_for_recovery,
@@ -69,16 +69,23 @@ impl ShowSecretCommand {
                                 match self.output_format {
                                     CliOutputFormat::Json => {
                                         let result = json!({
-                                            "secret": secret.text,
+                                            "secret": recovery.secret.text,
                                             "status": "success",
+                                            "invalid_shares": recovery.invalid,
                                             "claim_id": claim_id.0.id_str(),
                                             "password_name": claim.dist_claim_id.pass_id.name
                                         });
                                         println!("{}", serde_json::to_string_pretty(&result)?);
                                     }
                                     CliOutputFormat::Yaml => {
-                                        println!("secret: {}", secret.text);
+                                        println!("secret: {}", recovery.secret.text);
                                         println!("status: success");
+                                        if !recovery.invalid.is_empty() {
+                                            println!("invalid_shares:");
+                                            for device_id in &recovery.invalid {
+                                                println!("  - {}", device_id);
+                                            }
+                                        }
                                         println!("claim_id: {}", claim_id.0.id_str());
                                         println!(
                                             "password_name: {}",
@@ -1,25 +1,117 @@
 use crate::base_command::BaseCommand;
-use anyhow::Result;
+use crate::secret::recovery_request_command::find_device;
+use anyhow::{bail, Result};
 use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
+use meta_secret_core::node::common::model::device::common::{DeviceId, DeviceType};
 use meta_secret_core::node::common::model::meta_pass::PlainPassInfo;
+use meta_secret_core::node::common::model::secret::{
+    BreakGlassPolicy, RecoveryPolicy, DEFAULT_BREAK_GLASS_DELAY,
+};
+use meta_secret_core::node::common::model::{ApplicationState, VaultFullInfo};
 
 pub struct SplitCommand {
     pub base: BaseCommand,
+    /// Distinct approvals a recovery of the secret requires, no recovery policy if not set
+    pub approvals: Option<usize>,
+    /// Device types whose approvals count
+    pub approver_types: Vec<String>,
+    /// Device ids or names of the members whose approvals count
+    pub approvers: Vec<String>,
+    /// Device id or name of the escrow member, no break-glass recovery if not set
+    pub break_glass_escrow: Option<String>,
+    /// Hours the members have to veto a break-glass recovery
+    pub break_glass_delay: Option<u64>,
 }
 
 impl SplitCommand {
     pub fn new(db_name: String) -> Self {
         Self {
             base: BaseCommand::new(db_name),
+            approvals: None,
+            approver_types: vec![],
+            approvers: vec![],
+            break_glass_escrow: None,
+            break_glass_delay: None,
         }
     }
 
+    pub fn with_policy(
+        mut self,
+        approvals: Option<usize>,
+        approver_types: Vec<String>,
+        approvers: Vec<String>,
+    ) -> Self {
+        self.approvals = approvals;
+        self.approver_types = approver_types;
+        self.approvers = approvers;
+        self
+    }
+
+    pub fn with_break_glass(mut self, escrow: Option<String>, delay_hours: Option<u64>) -> Self {
+        self.break_glass_escrow = escrow;
+        self.break_glass_delay = delay_hours;
+        self
+    }
+
     pub async fn execute(self, pass: PlainPassInfo) -> Result<()> {
         let db_context = self.base.open_existing_db().await?;
 
         // Ensure user credentials exist
         self.base.ensure_user_creds(&db_context).await?;
 
+        if self.approvals.is_none()
+            && (!self.approver_types.is_empty() || !self.approvers.is_empty())
+        {
+            bail!("--approver-type and --approver require the number of approvals (--approvals)");
+        }
+        if self.approvals == Some(0) {
+            bail!("A recovery policy requires at least one approval");
+        }
+        if self.break_glass_escrow.is_none() && self.break_glass_delay.is_some() {
+            bail!("--break-glass-delay requires the escrow device (--break-glass-escrow)");
+        }
+
+        let mut pass = pass;
+        if self.approvals.is_some() || self.break_glass_escrow.is_some() {
+            let client = self.base.create_client_service(&db_context).await?;
+            let app_state = client.get_app_state().await?;
+            let ApplicationState::Vault(VaultFullInfo::Member(member_info)) = app_state else {
+                bail!("Invalid state. Only vault members can split a secret");
+            };
+            let vault = &member_info.member.vault;
+
+            if let Some(approvals) = self.approvals {
+                let approvers = self
+                    .approvers
+                    .iter()
+                    .map(|device| find_device(vault, device))
+                    .collect::<Result<Vec<DeviceId>>>()?;
+
+                let policy = RecoveryPolicy {
+                    approvals,
+                    device_types: self
+                        .approver_types
+                        .iter()
+                        .map(|device_type| DeviceType::from(device_type.as_str()))
+                        .collect(),
+                    approvers,
+                };
+                pass = pass.with_recovery_policy(policy);
+            }
+
+            if let Some(escrow) = &self.break_glass_escrow {
+                let delay = self
+                    .break_glass_delay
+                    .map(|hours| hours.saturating_mul(60 * 60 * 1000))
+                    .unwrap_or(DEFAULT_BREAK_GLASS_DELAY);
+                let policy = BreakGlassPolicy {
+                    escrow: find_device(vault, escrow)?,
+                    delay,
+                };
+                pass = pass.with_break_glass(policy);
+            }
+        }
+
         // Handle cluster distribution request
         let request = GenericAppStateRequest::ClusterDistribution(pass.clone());
         self.base
@@ -27,6 +119,19 @@ impl SplitCommand {
             .await?;
 
         println!("Secret '{}' has been split successfully", pass.pass_id.name);
+        if let Some(policy) = &pass.recovery_policy {
+            println!(
+                "A recovery of the secret requires {} approvals",
+                policy.approvals
+            );
+        }
+        if let Some(break_glass) = &pass.break_glass {
+            println!(
+                "Device {} releases its share of a recovery after {} hours unless a member vetoes it",
+                break_glass.escrow,
+                break_glass.delay / (60 * 60 * 1000)
+            );
+        }
         Ok(())
     }
 }
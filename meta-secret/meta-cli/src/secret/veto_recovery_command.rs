@@ -0,0 +1,36 @@
+use crate::base_command::BaseCommand;
+use anyhow::Result;
+use meta_secret_core::crypto::utils::Id48bit;
+use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
+use meta_secret_core::node::common::model::secret::ClaimId;
+
+pub struct VetoRecoveryCommand {
+    pub base: BaseCommand,
+    pub claim_id: String,
+}
+
+impl VetoRecoveryCommand {
+    pub fn new(db_name: String, claim_id: String) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+            claim_id,
+        }
+    }
+
+    pub async fn execute(self) -> Result<()> {
+        let db_context = self.base.open_existing_db().await?;
+
+        // Ensure user credentials exist
+        self.base.ensure_user_creds(&db_context).await?;
+
+        let claim_id = ClaimId::from(Id48bit::from(self.claim_id.clone()));
+        let veto_request = GenericAppStateRequest::VetoRecovery(claim_id);
+        self.base
+            .handle_client_request(&db_context, veto_request)
+            .await?;
+
+        println!("Break-glass recovery of request {} vetoed", self.claim_id);
+
+        Ok(())
+    }
+}
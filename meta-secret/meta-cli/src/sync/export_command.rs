@@ -0,0 +1,68 @@
+use crate::base_command::BaseCommand;
+use crate::sync::offline_gateway::OfflineSyncGateway;
+use anyhow::{bail, Result};
+use meta_secret_core::node::common::model::IdString;
+use meta_secret_core::node::db::objects::persistent_vault::PersistentVault;
+use std::path::PathBuf;
+
+pub struct SyncExportCommand {
+    pub base: BaseCommand,
+    pub relay_device_id: String,
+    pub out: PathBuf,
+}
+
+impl SyncExportCommand {
+    pub fn new(db_name: String, relay_device_id: String, out: PathBuf) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+            relay_device_id,
+            out,
+        }
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        let db_context = self.base.open_existing_db().await?;
+        self.base.ensure_user_creds(&db_context).await?;
+
+        let Some(user_creds) = db_context.p_creds.get_user_creds().await? else {
+            bail!("User credentials not found. Please run `meta-secret init-user` first.");
+        };
+
+        // The relay device has to be a known vault member, the bundle is encrypted to its key
+        let relay_device = {
+            let p_vault = PersistentVault::from(db_context.p_obj.clone());
+            let vault = p_vault
+                .get_vault(user_creds.vault_name.clone())
+                .await?
+                .to_data();
+
+            let maybe_relay = vault.members().into_iter().find(|member| {
+                member.user().device.device_id.clone().id_str() == self.relay_device_id
+            });
+
+            let Some(relay) = maybe_relay else {
+                bail!(
+                    "Relay device {} is not a member of the vault",
+                    self.relay_device_id
+                );
+            };
+
+            relay.user().device.clone()
+        };
+
+        let offline_gateway = OfflineSyncGateway::new(&self.base, &db_context);
+        offline_gateway.sync(user_creds.user()).await?;
+
+        offline_gateway
+            .protocol()
+            .export(&user_creds.device_creds, &relay_device, &self.out)?;
+
+        println!("Sync bundle written to {}", self.out.display());
+        println!(
+            "Relay it with `meta-cli sync relay` on device {}",
+            self.relay_device_id
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use crate::base_command::BaseCommand;
+use crate::sync::offline_gateway::OfflineSyncGateway;
+use anyhow::{bail, Result};
+use meta_secret_core::node::app::sync::file_sync_protocol::SyncBundle;
+use std::fs;
+use std::path::PathBuf;
+
+pub struct SyncImportCommand {
+    pub base: BaseCommand,
+    pub input: PathBuf,
+}
+
+impl SyncImportCommand {
+    pub fn new(db_name: String, input: PathBuf) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+            input,
+        }
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        let db_context = self.base.open_existing_db().await?;
+        self.base.ensure_user_creds(&db_context).await?;
+
+        let Some(user_creds) = db_context.p_creds.get_user_creds().await? else {
+            bail!("User credentials not found. Please run `meta-secret init-user` first.");
+        };
+
+        let response_bundle: SyncBundle = serde_json::from_str(&fs::read_to_string(&self.input)?)?;
+
+        let offline_gateway = OfflineSyncGateway::new(&self.base, &db_context);
+        offline_gateway
+            .protocol()
+            .import(&user_creds.device_creds, &response_bundle)?;
+        offline_gateway.sync(user_creds.user()).await?;
+
+        let pending = offline_gateway.protocol().load_state()?.outbox.len();
+        println!(
+            "Sync bundle imported, {} requests are waiting for the next export",
+            pending
+        );
+
+        Ok(())
+    }
+}
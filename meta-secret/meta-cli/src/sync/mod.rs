@@ -0,0 +1,4 @@
+pub mod export_command;
+pub mod import_command;
+pub mod offline_gateway;
+pub mod relay_command;
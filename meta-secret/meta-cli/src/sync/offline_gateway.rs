@@ -0,0 +1,47 @@
+use crate::base_command::{BaseCommand, DbContext};
+use anyhow::Result;
+use meta_db_redb::ReDbRepo;
+use meta_secret_core::node::app::sync::file_sync_protocol::{FileSyncProtocol, SyncBundleError};
+use meta_secret_core::node::app::sync::sync_gateway::SyncGateway;
+use meta_secret_core::node::common::model::user::common::UserData;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::info;
+
+/// Sync gateway of an air-gapped device, all server communication goes through bundle files
+pub struct OfflineSyncGateway {
+    pub gateway: SyncGateway<ReDbRepo, FileSyncProtocol>,
+}
+
+impl OfflineSyncGateway {
+    pub fn new(base: &BaseCommand, db_context: &DbContext<ReDbRepo>) -> Self {
+        let sync_protocol = FileSyncProtocol {
+            state_path: PathBuf::from(format!("{}.sync.json", base.db_name)),
+        };
+
+        let gateway = SyncGateway::new(
+            "meta-cli-offline".to_string(),
+            db_context.p_obj.clone(),
+            Arc::new(sync_protocol),
+            db_context.p_creds.master_key.clone(),
+        );
+
+        Self { gateway }
+    }
+
+    pub fn protocol(&self) -> &FileSyncProtocol {
+        self.gateway.sync.as_ref()
+    }
+
+    /// Run a sync round as far as the imported responses allow
+    pub async fn sync(&self, user: UserData) -> Result<()> {
+        match self.gateway.sync(user).await {
+            Ok(()) => Ok(()),
+            Err(err) if SyncBundleError::is_awaiting_relay(&err) => {
+                info!("Pending reads collected, waiting for the server responses");
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
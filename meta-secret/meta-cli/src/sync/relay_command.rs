@@ -0,0 +1,67 @@
+use crate::base_command::BaseCommand;
+use anyhow::{bail, Result};
+use meta_secret_core::node::app::sync::file_sync_protocol::{SyncBundle, SyncBundleRelay};
+use meta_secret_core::node::app::sync::sync_protocol::HttpSyncProtocol;
+use meta_secret_core::node::db::objects::persistent_vault::PersistentVault;
+use std::fs;
+use std::path::PathBuf;
+
+pub struct SyncRelayCommand {
+    pub base: BaseCommand,
+    pub input: PathBuf,
+    pub out: PathBuf,
+}
+
+impl SyncRelayCommand {
+    pub fn new(db_name: String, input: PathBuf, out: PathBuf) -> Self {
+        Self {
+            base: BaseCommand::new(db_name),
+            input,
+            out,
+        }
+    }
+
+    pub async fn execute(&self) -> Result<()> {
+        let db_context = self.base.open_existing_db().await?;
+        self.base.ensure_user_creds(&db_context).await?;
+
+        let Some(user_creds) = db_context.p_creds.get_user_creds().await? else {
+            bail!("User credentials not found. Please run `meta-secret init-user` first.");
+        };
+
+        let request_bundle: SyncBundle = serde_json::from_str(&fs::read_to_string(&self.input)?)?;
+
+        // only the members of the vault get their requests relayed
+        let members = {
+            let p_vault = PersistentVault::from(db_context.p_obj.clone());
+            let vault = p_vault
+                .get_vault(user_creds.vault_name.clone())
+                .await?
+                .to_data();
+            vault
+                .members()
+                .into_iter()
+                .map(|member| member.user().device.clone())
+                .collect()
+        };
+
+        let relay = SyncBundleRelay {
+            sync: HttpSyncProtocol::new(self.base.api_url),
+            members,
+        };
+        let response_bundle = relay
+            .relay(&user_creds.device_creds, &request_bundle)
+            .await?;
+        fs::write(&self.out, serde_json::to_string_pretty(&response_bundle)?)?;
+
+        println!(
+            "Requests of device {:?} relayed, response bundle written to {}",
+            user_creds
+                .open_device_name(request_bundle.sender.clone())
+                .device_name,
+            self.out.display()
+        );
+
+        Ok(())
+    }
+}
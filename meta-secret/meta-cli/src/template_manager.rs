@@ -35,6 +35,7 @@ impl TemplateManager {
             "vault_events.json",
             include_str!("templates/vault_events.json.tera"),
         )?;
+        tera.add_raw_template("audit.json", include_str!("templates/audit.json.tera"))?;
         tera.add_raw_template("error.json", include_str!("templates/error.json.tera"))?;
 
         // Add YAML templates
@@ -48,6 +49,7 @@ impl TemplateManager {
             "vault_events.yaml",
             include_str!("templates/vault_events.yaml.tera"),
         )?;
+        tera.add_raw_template("audit.yaml", include_str!("templates/audit.yaml.tera"))?;
         tera.add_raw_template("error.yaml", include_str!("templates/error.yaml.tera"))?;
 
         Ok(Self { tera })
@@ -0,0 +1,337 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use meta_secret_core::node::common::model::device::common::DeviceId;
+use meta_secret_core::node::common::model::secret::{
+    SecretDistributionType, SsDistributionStatus, SsLogData,
+};
+use meta_secret_core::node::common::model::user::common::{UserId, UserMembership};
+use meta_secret_core::node::common::model::vault::vault::VaultName;
+use meta_secret_core::node::common::model::vault::vault_data::VaultData;
+use meta_secret_core::node::common::model::IdString;
+use meta_secret_core::node::db::descriptors::object_descriptor::{ObjectType, ToObjectDescriptor};
+use meta_secret_core::node::db::descriptors::shared_secret_descriptor::{
+    SsDeviceLogDescriptor, SsLogDescriptor,
+};
+use meta_secret_core::node::db::descriptors::vault_descriptor::{
+    DeviceLogDescriptor, VaultDescriptor, VaultLogDescriptor, VaultStatusDescriptor,
+};
+use meta_secret_core::node::db::events::generic_log_event::{GenericKvLogEvent, ObjIdExtractor};
+use meta_secret_core::node::db::events::object_id::ArtifactId;
+use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
+use meta_secret_core::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
+use meta_secret_core::node::db::repo::generic_db::{FindObjectsQuery, KvLogEventRepo};
+use serde_derive::Serialize;
+
+use crate::server::retention::{claims_history, stale_workflow_events, workflow_descriptors};
+use serde_json::Value;
+use tracing::info;
+
+/// Replaces share ciphertext in exported events
+pub const REDACTED: &str = "redacted";
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VaultSummary {
+    pub vault_name: VaultName,
+    pub members: usize,
+    pub outsiders: usize,
+    pub secrets: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemberInfo {
+    pub device_id: String,
+    pub device_name: String,
+    /// `Member`, or the outsider status: `Pending`, `Declined`, `NonMember`
+    pub status: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClaimInfo {
+    pub claim_id: String,
+    pub distribution_type: SecretDistributionType,
+    /// Password names are sealed to the vault members, the server knows the id only
+    pub password_id: String,
+    pub sender: String,
+    pub status: SsDistributionStatus,
+    pub receivers: Vec<ReceiverStatus>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiverStatus {
+    pub device_id: String,
+    /// No status means the receiver is missing in the claim status map
+    pub status: Option<SsDistributionStatus>,
+}
+
+/// Read and maintenance operations over the server repo for operators.
+/// None of the reports contain share ciphertext, an export has it only on explicit request.
+pub struct ServerAdmin<Repo: KvLogEventRepo> {
+    p_obj: Arc<PersistentObject<Repo>>,
+}
+
+impl<Repo: KvLogEventRepo + FindObjectsQuery> ServerAdmin<Repo> {
+    pub async fn list_vaults(&self) -> Result<Vec<VaultSummary>> {
+        let vault_type = VaultDescriptor::from(VaultName::from("")).object_type();
+        let vault_names = self.p_obj.repo.find_object_names(&vault_type).await?;
+
+        let mut vaults = Vec::with_capacity(vault_names.len());
+        for vault_name in vault_names {
+            let Some(vault) = self.find_vault(VaultName::from(vault_name)).await? else {
+                continue;
+            };
+
+            vaults.push(VaultSummary {
+                members: vault.members().len(),
+                outsiders: vault.outsiders().len(),
+                secrets: vault.secrets.len(),
+                vault_name: vault.vault_name,
+            });
+        }
+
+        Ok(vaults)
+    }
+}
+
+impl<Repo: KvLogEventRepo> ServerAdmin<Repo> {
+    pub fn new(repo: Arc<Repo>) -> Self {
+        Self {
+            p_obj: Arc::new(PersistentObject::new(repo)),
+        }
+    }
+
+    pub async fn vault_members(&self, vault_name: VaultName) -> Result<Vec<MemberInfo>> {
+        let vault = self.get_vault(vault_name).await?;
+
+        let mut members: Vec<MemberInfo> = vault
+            .users
+            .values()
+            .map(|membership| {
+                let (user, status) = match membership {
+                    UserMembership::Member(member) => (&member.user_data, String::from("Member")),
+                    UserMembership::Outsider(outsider) => {
+                        (&outsider.user_data, format!("{:?}", outsider.status))
+                    }
+                };
+
+                MemberInfo {
+                    device_id: user.device.device_id.to_string(),
+                    device_name: user.device.device_name.as_str(),
+                    status,
+                }
+            })
+            .collect();
+        // the device names of the newer vaults are sealed, the server only has the ids
+        members.sort_by(|a, b| {
+            (&a.device_name, &a.device_id).cmp(&(&b.device_name, &b.device_id))
+        });
+
+        Ok(members)
+    }
+
+    /// Open claims, a claim is removed from the ss log once every share is delivered
+    pub async fn ss_claims(&self, vault_name: VaultName) -> Result<Vec<ClaimInfo>> {
+        let ss_log = self.ss_log(vault_name).await?;
+
+        let mut claims: Vec<ClaimInfo> = ss_log
+            .claims
+            .values()
+            .map(|claim| {
+                let receivers = claim
+                    .receivers
+                    .iter()
+                    .map(|receiver| ReceiverStatus {
+                        device_id: receiver.to_string(),
+                        status: claim.status.get(receiver).cloned(),
+                    })
+                    .collect();
+
+                ClaimInfo {
+                    claim_id: claim.id.0.clone().id_str(),
+                    distribution_type: claim.distribution_type,
+                    password_id: claim.dist_claim_id.pass_id.id_str(),
+                    sender: claim.sender.to_string(),
+                    status: claim.status.status(),
+                    receivers,
+                }
+            })
+            .collect();
+        claims.sort_by(|a, b| a.claim_id.cmp(&b.claim_id));
+
+        Ok(claims)
+    }
+
+    /// Deletes workflow events (shares and declines) of the receivers that are done with a claim:
+    /// the share has been delivered or the receiver has declined.
+    /// Returns ids of the deleted events, nothing is deleted on a dry run.
+    pub async fn purge_stale_workflow_events(
+        &self,
+        vault_name: VaultName,
+        dry_run: bool,
+    ) -> Result<Vec<ArtifactId>> {
+        let stale_ids = stale_workflow_events(self.p_obj.clone(), vault_name.clone()).await?;
+
+        if !dry_run {
+            for id in stale_ids.iter() {
+                self.p_obj.repo.delete(id.clone()).await;
+            }
+            info!(
+                "Purged {} stale workflow events of vault: {}",
+                stale_ids.len(),
+                vault_name
+            );
+        }
+
+        Ok(stale_ids)
+    }
+
+    /// All the events the server has for the vault, in the order of objects and their ids.
+    /// Share ciphertext is replaced with [`REDACTED`] unless `include_ciphertext` is set.
+    pub async fn export_vault(
+        &self,
+        vault_name: VaultName,
+        include_ciphertext: bool,
+    ) -> Result<Vec<Value>> {
+        let vault = self.get_vault(vault_name.clone()).await?;
+
+        let mut events = vec![];
+        events.extend(
+            self.object_events(VaultLogDescriptor::from(vault_name.clone()))
+                .await?,
+        );
+        events.extend(
+            self.object_events(VaultDescriptor::from(vault_name.clone()))
+                .await?,
+        );
+
+        let mut device_ids: Vec<&DeviceId> = vault.users.keys().collect();
+        device_ids.sort_by_key(|device_id| device_id.to_string());
+        for device_id in device_ids {
+            let user_id = UserId {
+                vault_name: vault_name.clone(),
+                device_id: device_id.clone(),
+            };
+            events.extend(
+                self.object_events(DeviceLogDescriptor::from(user_id.clone()))
+                    .await?,
+            );
+            events.extend(
+                self.object_events(VaultStatusDescriptor::from(user_id))
+                    .await?,
+            );
+            events.extend(
+                self.object_events(SsDeviceLogDescriptor::from(device_id.clone()))
+                    .await?,
+            );
+        }
+
+        events.extend(
+            self.object_events(SsLogDescriptor::from(vault_name.clone()))
+                .await?,
+        );
+
+        let mut workflow_ids = HashSet::new();
+        for claim in claims_history(&self.p_obj, vault_name).await? {
+            for recovery_id in claim.recovery_db_ids() {
+                for desc in workflow_descriptors(recovery_id) {
+                    for event in self.object_events(desc).await? {
+                        // claims of the same password share split distributions
+                        if workflow_ids.insert(event.obj_id().id_str()) {
+                            events.push(event);
+                        }
+                    }
+                }
+            }
+        }
+
+        events
+            .into_iter()
+            .map(|event| {
+                let mut json = serde_json::to_value(event)?;
+                if !include_ciphertext {
+                    redact_ciphertext(&mut json);
+                }
+                Ok(json)
+            })
+            .collect()
+    }
+
+    async fn find_vault(&self, vault_name: VaultName) -> Result<Option<VaultData>> {
+        let vault = self
+            .p_obj
+            .find_tail_event(VaultDescriptor::from(vault_name))
+            .await?;
+        Ok(vault.map(|vault| vault.to_data()))
+    }
+
+    async fn get_vault(&self, vault_name: VaultName) -> Result<VaultData> {
+        let Some(vault) = self.find_vault(vault_name.clone()).await? else {
+            bail!("Vault not found: {}", vault_name);
+        };
+        Ok(vault)
+    }
+
+    async fn ss_log(&self, vault_name: VaultName) -> Result<SsLogData> {
+        PersistentSharedSecret::from(self.p_obj.clone())
+            .get_ss_log_obj(vault_name)
+            .await
+    }
+
+    async fn object_events<Desc: ToObjectDescriptor>(
+        &self,
+        obj_desc: Desc,
+    ) -> Result<Vec<GenericKvLogEvent>> {
+        self.p_obj
+            .find_object_events(ArtifactId::from(obj_desc))
+            .await
+    }
+}
+
+/// Replaces every encrypted secret message in the json with [`REDACTED`]
+pub fn redact_ciphertext(json: &mut Value) {
+    match json {
+        Value::Object(fields) => {
+            for (name, value) in fields.iter_mut() {
+                if name == "secretMessage" {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact_ciphertext(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact_ciphertext),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_ciphertext_keeps_metadata() {
+        let mut event = json!({
+            "ssWorkflow": {
+                "distribution": {
+                    "key": { "objId": "SsDistribution:pass|device::1" },
+                    "value": {
+                        "vaultName": "vault",
+                        "secretMessage": { "cipherShare": { "share": { "msg": "c2hhcmU=" } } }
+                    }
+                }
+            }
+        });
+
+        redact_ciphertext(&mut event);
+
+        let value = &event["ssWorkflow"]["distribution"]["value"];
+        assert_eq!(value["secretMessage"], json!(REDACTED));
+        assert_eq!(value["vaultName"], json!("vault"));
+    }
+}
@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use meta_secret_core::crypto::key_pair::DsaKeyPair;
+use meta_secret_core::node::common::model::device::common::DeviceId;
+use meta_secret_core::node::common::model::secret::{SecretDistributionType, SsClaimId};
+use meta_secret_core::node::common::model::user::common::{UserDataOutsiderStatus, UserMembership};
+use meta_secret_core::node::common::model::vault::vault::VaultName;
+use meta_secret_core::node::db::events::audit_event::{AuditAction, AuditEntry};
+use meta_secret_core::node::db::events::generic_log_event::GenericKvLogEvent;
+use meta_secret_core::node::db::events::shared_secret_event::SsWorkflowObject;
+use meta_secret_core::node::db::events::vault::vault_log_event::{
+    VaultActionEvent, VaultActionInitEvent, VaultActionRequestEvent, VaultActionUpdateEvent,
+};
+use meta_secret_core::node::db::objects::persistent_audit::PersistentAudit;
+use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
+use meta_secret_core::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
+use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
+
+use crate::server::server_data_sync::{share_device_id, share_receiver_id};
+
+/// An action found in a client event, not yet timestamped
+struct AuditRecord {
+    vault_name: VaultName,
+    device: DeviceId,
+    action: AuditAction,
+}
+
+/// Derives audit entries from the events handled by the server and records them
+/// into the audit logs of the vaults, signed with the server key
+pub struct ServerAudit<Repo: KvLogEventRepo> {
+    p_obj: Arc<PersistentObject<Repo>>,
+    dsa: DsaKeyPair,
+}
+
+impl<Repo: KvLogEventRepo> ServerAudit<Repo> {
+    pub fn new(p_obj: Arc<PersistentObject<Repo>>, dsa: DsaKeyPair) -> Self {
+        Self { p_obj, dsa }
+    }
+
+    /// Records the action of an event the server has saved, `now` is the unix time in milliseconds
+    pub async fn record_write(&self, event: &GenericKvLogEvent, now: u64) -> Result<()> {
+        if let Some(record) = self.write_record(event).await? {
+            self.record(record, now).await?;
+        }
+        Ok(())
+    }
+
+    /// Records the shares the server has handed over. The receiving device is taken from
+    /// the id of the share rather than from the (unauthenticated) sender of the request
+    pub async fn record_delivery(&self, events: &[GenericKvLogEvent], now: u64) -> Result<()> {
+        for event in events {
+            let GenericKvLogEvent::SsWorkflow(ss_object) = event else {
+                continue;
+            };
+
+            let (distribution_type, data) = match ss_object {
+                SsWorkflowObject::Distribution(event) => {
+                    (SecretDistributionType::Split, &event.value)
+                }
+                SsWorkflowObject::Recovery(event) => {
+                    (SecretDistributionType::Recover, &event.value)
+                }
+                SsWorkflowObject::Decline(_)
+                | SsWorkflowObject::Approval(_)
+                | SsWorkflowObject::CancelClaim(_)
+                | SsWorkflowObject::Veto(_) => continue,
+            };
+
+            let record = AuditRecord {
+                vault_name: data.vault_name.clone(),
+                device: share_receiver_id(ss_object)?,
+                action: AuditAction::ShareDelivered {
+                    claim: opaque_claim(&data.claim_id),
+                    distribution_type,
+                },
+            };
+            self.record(record, now).await?;
+        }
+        Ok(())
+    }
+
+    async fn record(&self, record: AuditRecord, now: u64) -> Result<()> {
+        let entry = AuditEntry {
+            time: now,
+            device: record.device,
+            action: record.action,
+        };
+
+        let p_audit = PersistentAudit::from(self.p_obj.clone());
+        p_audit.record(record.vault_name, entry, &self.dsa).await
+    }
+
+    async fn write_record(&self, event: &GenericKvLogEvent) -> Result<Option<AuditRecord>> {
+        let record = match event {
+            GenericKvLogEvent::DeviceLog(device_log) => vault_action_record(&device_log.0.value),
+            GenericKvLogEvent::SsDeviceLog(ss_device_log) => {
+                let claim = &ss_device_log.0.value;
+                match claim.distribution_type {
+                    SecretDistributionType::Recover => Some(AuditRecord {
+                        vault_name: claim.vault_name.clone(),
+                        device: claim.sender.clone(),
+                        action: AuditAction::RecoveryRequested {
+                            claim: opaque_claim(&claim.dist_claim_id),
+                        },
+                    }),
+                    SecretDistributionType::Split => None,
+                }
+            }
+            GenericKvLogEvent::SsWorkflow(ss_object) => match ss_object {
+                SsWorkflowObject::Recovery(recovery) => Some(AuditRecord {
+                    vault_name: recovery.value.vault_name.clone(),
+                    device: share_device_id(ss_object)?,
+                    action: AuditAction::RecoveryApproved {
+                        claim: opaque_claim(&recovery.value.claim_id),
+                    },
+                }),
+                SsWorkflowObject::Decline(decline) => {
+                    let decline_data = &decline.value;
+                    let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+                    let ss_log_data = p_ss.get_ss_log_obj(decline_data.vault_name.clone()).await?;
+
+                    ss_log_data
+                        .claims
+                        .get(&decline_data.claim_id)
+                        .map(|claim| AuditRecord {
+                            vault_name: decline_data.vault_name.clone(),
+                            device: decline_data.receiver_id.clone(),
+                            action: AuditAction::RecoveryDeclined {
+                                claim: opaque_claim(&claim.dist_claim_id),
+                            },
+                        })
+                }
+                SsWorkflowObject::Approval(approval) => {
+                    let approval_data = &approval.value;
+                    let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+                    let ss_log_data = p_ss
+                        .get_ss_log_obj(approval_data.vault_name.clone())
+                        .await?;
+
+                    ss_log_data
+                        .claims
+                        .get(&approval_data.claim_id)
+                        .map(|claim| AuditRecord {
+                            vault_name: approval_data.vault_name.clone(),
+                            device: approval_data.receiver_id.clone(),
+                            action: AuditAction::RecoveryApprovalGranted {
+                                claim: opaque_claim(&claim.dist_claim_id),
+                            },
+                        })
+                }
+                SsWorkflowObject::CancelClaim(cancel) => Some(AuditRecord {
+                    vault_name: cancel.value.vault_name.clone(),
+                    device: cancel.value.sender.clone(),
+                    action: AuditAction::RecoveryCancelled {
+                        claim: opaque_claim(&cancel.value.claim_id),
+                    },
+                }),
+                SsWorkflowObject::Veto(veto) => Some(AuditRecord {
+                    vault_name: veto.value.vault_name.clone(),
+                    device: veto.value.device_id.clone(),
+                    action: AuditAction::RecoveryVetoed {
+                        claim: opaque_claim(&veto.value.claim_id),
+                    },
+                }),
+                SsWorkflowObject::Distribution(_) => None,
+            },
+            _ => None,
+        };
+
+        Ok(record)
+    }
+}
+
+fn vault_action_record(vault_action: &VaultActionEvent) -> Option<AuditRecord> {
+    let vault_name = vault_action.vault_name();
+
+    let (device, action) = match vault_action {
+        VaultActionEvent::Init(VaultActionInitEvent::CreateVault(create)) => (
+            create.owner.user_data.device.device_id.clone(),
+            AuditAction::VaultCreated,
+        ),
+        VaultActionEvent::Request(request) => match request {
+            VaultActionRequestEvent::JoinCluster(join) => (
+                join.candidate.device.device_id.clone(),
+                AuditAction::JoinRequested,
+            ),
+            VaultActionRequestEvent::AddMetaPass(add_pass) => (
+                add_pass.sender.user_data.device.device_id.clone(),
+                AuditAction::SecretAdded {
+                    pass_id: add_pass.meta_pass_id.opaque(),
+                },
+            ),
+            VaultActionRequestEvent::RotateDeviceKeys(rotation) => (
+                rotation.member.user_data.device.device_id.clone(),
+                AuditAction::KeysRotated,
+            ),
+            VaultActionRequestEvent::AddInvite(add_invite) => (
+                add_invite.sender.user_data.device.device_id.clone(),
+                AuditAction::InviteCreated,
+            ),
+            VaultActionRequestEvent::UpdateSettings(settings) => (
+                settings.sender_id().clone(),
+                AuditAction::SettingsUpdated,
+            ),
+        },
+        VaultActionEvent::Update(VaultActionUpdateEvent::UpdateMembership(update)) => {
+            let sender = update.sender.user_data.device.device_id.clone();
+            let candidate = update.request.candidate.device.device_id.clone();
+            match &update.update {
+                UserMembership::Member(_) => (sender, AuditAction::JoinAccepted { candidate }),
+                UserMembership::Outsider(outsider) => match outsider.status {
+                    UserDataOutsiderStatus::Declined => {
+                        (sender, AuditAction::JoinDeclined { candidate })
+                    }
+                    UserDataOutsiderStatus::NonMember | UserDataOutsiderStatus::Pending => {
+                        return None;
+                    }
+                },
+            }
+        }
+        VaultActionEvent::Update(_) => return None,
+    };
+
+    Some(AuditRecord {
+        vault_name,
+        device,
+        action,
+    })
+}
+
+/// Audit entries reference passwords by their ids only
+fn opaque_claim(claim_id: &SsClaimId) -> SsClaimId {
+    SsClaimId {
+        id: claim_id.id.clone(),
+        pass_id: claim_id.pass_id.opaque(),
+    }
+}
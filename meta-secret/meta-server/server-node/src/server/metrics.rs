@@ -0,0 +1,409 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use meta_secret_core::node::api::{ReadSyncRequest, SyncRequest, WriteSyncRequest};
+use meta_secret_core::node::common::model::secret::{SsClaim, SsDistributionStatus, SsLogData};
+use meta_secret_core::node::common::model::vault::vault::VaultName;
+use meta_secret_core::node::db::events::generic_log_event::{GenericKvLogEvent, ToGenericEvent};
+use meta_secret_core::node::db::events::object_id::ArtifactId;
+use meta_secret_core::node::db::repo::generic_db::{
+    DbCleanUpCommand, DeleteCommand, FindObjectsQuery, FindOneQuery, KvLogEventRepo, SaveCommand,
+};
+use prometheus::{
+    HistogramOpts, HistogramTimer, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+
+const METRICS_PREFIX: &str = "meta_server";
+
+/// The kind of a client request, used as the `request` label of the request metrics
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestKind {
+    Vault,
+    SsRequest,
+    SsRecoveryCompletion,
+    ServerTail,
+    Handshake,
+    WriteEvent,
+}
+
+impl RequestKind {
+    pub fn of(request: &SyncRequest) -> Self {
+        match request {
+            SyncRequest::Read(read_request) => match read_request.as_ref() {
+                ReadSyncRequest::Vault(_) => RequestKind::Vault,
+                ReadSyncRequest::SsRequest(_) => RequestKind::SsRequest,
+                ReadSyncRequest::SsRecoveryCompletion(_) => RequestKind::SsRecoveryCompletion,
+                ReadSyncRequest::ServerTail(_) => RequestKind::ServerTail,
+                ReadSyncRequest::Handshake(_) => RequestKind::Handshake,
+            },
+            SyncRequest::Write(write_request) => match write_request.as_ref() {
+                WriteSyncRequest::Event(_) => RequestKind::WriteEvent,
+            },
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RequestKind::Vault => "vault",
+            RequestKind::SsRequest => "ss_request",
+            RequestKind::SsRecoveryCompletion => "ss_recovery_completion",
+            RequestKind::ServerTail => "server_tail",
+            RequestKind::Handshake => "handshake",
+            RequestKind::WriteEvent => "write_event",
+        }
+    }
+
+    /// Requests of this kind can change the ss claims of the vault
+    pub fn changes_claims(&self) -> bool {
+        matches!(
+            self,
+            RequestKind::WriteEvent | RequestKind::SsRecoveryCompletion
+        )
+    }
+}
+
+/// Prometheus metrics of the server, rendered by the `/metrics` endpoint
+pub struct ServerMetrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_errors: IntCounterVec,
+    request_duration: HistogramVec,
+    active_vaults: IntGauge,
+    ss_claims: IntGaugeVec,
+    repo_op_duration: HistogramVec,
+    vault_claims: Mutex<HashMap<VaultName, BTreeMap<&'static str, i64>>>,
+}
+
+impl ServerMetrics {
+    pub fn new() -> Result<Self> {
+        let registry = Registry::new_custom(Some(METRICS_PREFIX.to_string()), None)?;
+
+        let requests = IntCounterVec::new(
+            Opts::new("requests_total", "Client requests received by the server"),
+            &["request"],
+        )?;
+        let request_errors = IntCounterVec::new(
+            Opts::new("request_errors_total", "Client requests that failed"),
+            &["request"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "request_duration_seconds",
+                "Time spent processing a client request",
+            ),
+            &["request"],
+        )?;
+        let active_vaults =
+            IntGauge::new("active_vaults", "Vaults that have a running request worker")?;
+        let ss_claims = IntGaugeVec::new(
+            Opts::new(
+                "ss_claims",
+                "Not yet delivered ss claims of the vaults the server has seen since the start",
+            ),
+            &["status"],
+        )?;
+        let repo_op_duration = HistogramVec::new(
+            HistogramOpts::new("repo_op_duration_seconds", "Time spent in a db operation").buckets(
+                vec![
+                    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0,
+                ],
+            ),
+            &["op"],
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_errors.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(active_vaults.clone()))?;
+        registry.register(Box::new(ss_claims.clone()))?;
+        registry.register(Box::new(repo_op_duration.clone()))?;
+
+        Ok(Self {
+            registry,
+            requests,
+            request_errors,
+            request_duration,
+            active_vaults,
+            ss_claims,
+            repo_op_duration,
+            vault_claims: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Counts the request, the duration is recorded when the returned timer is dropped
+    pub fn request_started(&self, kind: RequestKind) -> HistogramTimer {
+        self.requests.with_label_values(&[kind.label()]).inc();
+        self.request_duration
+            .with_label_values(&[kind.label()])
+            .start_timer()
+    }
+
+    pub fn request_failed(&self, kind: RequestKind) {
+        self.request_errors.with_label_values(&[kind.label()]).inc();
+    }
+
+    pub fn set_active_vaults(&self, active_vaults: usize) {
+        self.active_vaults.set(active_vaults as i64);
+    }
+
+    /// Replaces the claim counters of the vault with the current state of its ss log
+    pub fn update_vault_claims(&self, vault_name: VaultName, ss_log: &SsLogData) {
+        let mut counts = BTreeMap::new();
+        for claim in ss_log.claims.values() {
+            let status = claim_status(claim);
+            if status == SsDistributionStatus::Delivered {
+                continue;
+            }
+            *counts.entry(status_label(&status)).or_insert(0) += 1;
+        }
+
+        let mut vault_claims = self.vault_claims.lock().unwrap();
+        vault_claims.insert(vault_name, counts);
+
+        for status in [
+            SsDistributionStatus::Pending,
+            SsDistributionStatus::Sent,
+            SsDistributionStatus::Declined,
+            SsDistributionStatus::Expired,
+            SsDistributionStatus::Approved,
+        ] {
+            let label = status_label(&status);
+            let total = vault_claims
+                .values()
+                .filter_map(|counts| counts.get(label))
+                .sum();
+            self.ss_claims.with_label_values(&[label]).set(total);
+        }
+    }
+
+    /// Metrics in the prometheus text format
+    pub fn encode(&self) -> Result<String> {
+        let text = TextEncoder::new().encode_to_string(&self.registry.gather())?;
+        Ok(text)
+    }
+}
+
+/// A pending claim that some receivers have approved already waits for the other approvals
+fn claim_status(claim: &SsClaim) -> SsDistributionStatus {
+    let status = claim.status.status();
+    let is_approved = claim
+        .status
+        .statuses
+        .values()
+        .any(|dist_status| matches!(dist_status, SsDistributionStatus::Approved));
+
+    if status == SsDistributionStatus::Pending && is_approved {
+        SsDistributionStatus::Approved
+    } else {
+        status
+    }
+}
+
+fn status_label(status: &SsDistributionStatus) -> &'static str {
+    match status {
+        SsDistributionStatus::Pending => "pending",
+        SsDistributionStatus::Sent => "sent",
+        SsDistributionStatus::Delivered => "delivered",
+        SsDistributionStatus::Declined => "declined",
+        SsDistributionStatus::Expired => "expired",
+        SsDistributionStatus::Approved => "approved",
+    }
+}
+
+/// Records the duration of every db operation of the wrapped repo
+pub struct MeteredRepo<Repo: KvLogEventRepo> {
+    repo: Arc<Repo>,
+    op_duration: HistogramVec,
+}
+
+impl<Repo: KvLogEventRepo> MeteredRepo<Repo> {
+    pub fn new(repo: Arc<Repo>, metrics: &ServerMetrics) -> Self {
+        Self {
+            repo,
+            op_duration: metrics.repo_op_duration.clone(),
+        }
+    }
+
+    fn timer(&self, op: &str) -> HistogramTimer {
+        self.op_duration.with_label_values(&[op]).start_timer()
+    }
+}
+
+#[async_trait(? Send)]
+impl<Repo: KvLogEventRepo> SaveCommand for MeteredRepo<Repo> {
+    async fn save<T: ToGenericEvent>(&self, value: T) -> Result<ArtifactId> {
+        let _timer = self.timer("save");
+        self.repo.save(value).await
+    }
+}
+
+#[async_trait(? Send)]
+impl<Repo: KvLogEventRepo> FindOneQuery for MeteredRepo<Repo> {
+    async fn find_one(&self, key: ArtifactId) -> Result<Option<GenericKvLogEvent>> {
+        let _timer = self.timer("find_one");
+        self.repo.find_one(key).await
+    }
+
+    async fn get_key(&self, key: ArtifactId) -> Result<Option<ArtifactId>> {
+        let _timer = self.timer("get_key");
+        self.repo.get_key(key).await
+    }
+}
+
+#[async_trait(? Send)]
+impl<Repo: KvLogEventRepo> DeleteCommand for MeteredRepo<Repo> {
+    async fn delete(&self, key: ArtifactId) {
+        let _timer = self.timer("delete");
+        self.repo.delete(key).await
+    }
+}
+
+#[async_trait(? Send)]
+impl<Repo: KvLogEventRepo> DbCleanUpCommand for MeteredRepo<Repo> {
+    async fn db_clean_up(&self) {
+        let _timer = self.timer("db_clean_up");
+        self.repo.db_clean_up().await
+    }
+}
+
+#[async_trait(? Send)]
+impl<Repo: KvLogEventRepo + FindObjectsQuery> FindObjectsQuery for MeteredRepo<Repo> {
+    async fn find_object_names(&self, obj_type: &str) -> Result<Vec<String>> {
+        let _timer = self.timer("find_object_names");
+        self.repo.find_object_names(obj_type).await
+    }
+}
+
+impl<Repo: KvLogEventRepo> KvLogEventRepo for MeteredRepo<Repo> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meta_secret_core::crypto::keys::fixture::KeyManagerFixture;
+    use meta_secret_core::crypto::utils::Id48bit;
+    use meta_secret_core::node::api::{HandshakeRequest, ProtocolInfo};
+    use meta_secret_core::node::common::model::device::device_creds::fixture::DeviceCredentialsFixture;
+    use meta_secret_core::node::common::model::meta_pass::MetaPasswordId;
+    use meta_secret_core::node::common::model::secret::{
+        ClaimId, SecretDistributionType, SsClaim, SsClaimId, SsDistributionCompositeStatus,
+    };
+    use meta_secret_core::node::db::descriptors::shared_secret_descriptor::SsLogDescriptor;
+    use meta_secret_core::node::db::in_mem_db::InMemKvLogEventRepo;
+
+    fn claim(vault_name: &str, status: SsDistributionStatus) -> SsClaim {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let device_id = creds.client.device.device_id.clone();
+        let mut statuses = HashMap::new();
+        statuses.insert(device_id.clone(), status);
+
+        let id = ClaimId(Id48bit::generate());
+        SsClaim {
+            id: id.clone(),
+            dist_claim_id: SsClaimId {
+                id,
+                pass_id: MetaPasswordId::build_from_str("pass"),
+            },
+            vault_name: VaultName::from(vault_name),
+            sender: device_id.clone(),
+            distribution_type: SecretDistributionType::Recover,
+            receivers: vec![device_id],
+            status: SsDistributionCompositeStatus { statuses },
+            created_at: None,
+            time: None,
+            ttl: None,
+            fallback: None,
+            break_glass: None,
+            approvals: vec![],
+        }
+    }
+
+    fn ss_log(claims: Vec<SsClaim>) -> SsLogData {
+        SsLogData {
+            claims: claims
+                .into_iter()
+                .map(|claim| (claim.id.clone(), claim))
+                .collect(),
+        }
+    }
+
+    fn gauge(metrics: &ServerMetrics, status: &str) -> i64 {
+        metrics.ss_claims.with_label_values(&[status]).get()
+    }
+
+    #[test]
+    fn test_request_kind_of_handshake() {
+        let request = SyncRequest::Read(Box::new(ReadSyncRequest::Handshake(HandshakeRequest {
+            client: ProtocolInfo::current(),
+        })));
+        let kind = RequestKind::of(&request);
+        assert_eq!(kind, RequestKind::Handshake);
+        assert!(!kind.changes_claims());
+    }
+
+    #[test]
+    fn test_claims_are_summed_across_vaults_without_delivered() {
+        let metrics = ServerMetrics::new().unwrap();
+
+        let vault_a = ss_log(vec![
+            claim("vault_a", SsDistributionStatus::Pending),
+            claim("vault_a", SsDistributionStatus::Delivered),
+        ]);
+        metrics.update_vault_claims(VaultName::from("vault_a"), &vault_a);
+
+        let vault_b = ss_log(vec![
+            claim("vault_b", SsDistributionStatus::Pending),
+            claim("vault_b", SsDistributionStatus::Sent),
+        ]);
+        metrics.update_vault_claims(VaultName::from("vault_b"), &vault_b);
+
+        assert_eq!(gauge(&metrics, "pending"), 2);
+        assert_eq!(gauge(&metrics, "sent"), 1);
+        assert_eq!(gauge(&metrics, "declined"), 0);
+
+        // the claim of vault_b is delivered now
+        let vault_b = ss_log(vec![claim("vault_b", SsDistributionStatus::Delivered)]);
+        metrics.update_vault_claims(VaultName::from("vault_b"), &vault_b);
+
+        assert_eq!(gauge(&metrics, "pending"), 1);
+        assert_eq!(gauge(&metrics, "sent"), 0);
+    }
+
+    #[test]
+    fn test_approved_claims_are_counted() {
+        let metrics = ServerMetrics::new().unwrap();
+
+        let vault_a = ss_log(vec![
+            claim("vault_a", SsDistributionStatus::Approved),
+            claim("vault_a", SsDistributionStatus::Pending),
+        ]);
+        metrics.update_vault_claims(VaultName::from("vault_a"), &vault_a);
+        assert_eq!(gauge(&metrics, "approved"), 1);
+
+        // the approved claim has released its share
+        let vault_a = ss_log(vec![claim("vault_a", SsDistributionStatus::Delivered)]);
+        metrics.update_vault_claims(VaultName::from("vault_a"), &vault_a);
+        assert_eq!(gauge(&metrics, "approved"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_metered_repo_records_op_durations() {
+        let metrics = ServerMetrics::new().unwrap();
+        let repo = MeteredRepo::new(Arc::new(InMemKvLogEventRepo::default()), &metrics);
+
+        let key = ArtifactId::from(SsLogDescriptor::from(VaultName::from("vault_a")));
+        repo.find_one(key).await.unwrap();
+
+        metrics
+            .request_started(RequestKind::Vault)
+            .observe_duration();
+        metrics.request_failed(RequestKind::Vault);
+
+        let text = metrics.encode().unwrap();
+        assert!(text.contains("meta_server_repo_op_duration_seconds_count{op=\"find_one\"} 1"));
+        assert!(text.contains("meta_server_requests_total{request=\"vault\"} 1"));
+        assert!(text.contains("meta_server_request_errors_total{request=\"vault\"} 1"));
+    }
+}
@@ -1,3 +1,9 @@
+pub mod admin;
+pub mod audit;
+pub mod metrics;
+pub mod retention;
 pub mod server_app;
 pub mod server_data_sync;
 pub mod server_sync_protocol;
+pub mod validation;
+pub mod vault_router;
@@ -0,0 +1,575 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use meta_secret_core::node::common::model::secret::{
+    ClaimId, SsClaim, SsDistributionStatus, SsRecoveryId,
+};
+use meta_secret_core::node::common::model::vault::vault::VaultName;
+use meta_secret_core::node::common::model::IdString;
+use meta_secret_core::node::db::descriptors::object_descriptor::ToObjectDescriptor;
+use meta_secret_core::node::db::descriptors::shared_secret_descriptor::{
+    SsLogDescriptor, SsWorkflowDescriptor,
+};
+use meta_secret_core::node::db::events::generic_log_event::{GenericKvLogEvent, ObjIdExtractor};
+use meta_secret_core::node::db::events::object_id::ArtifactId;
+use meta_secret_core::node::db::events::shared_secret_event::SsLogObject;
+use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
+use meta_secret_core::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
+use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
+use tracing::info;
+
+pub const DEFAULT_GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+pub const DEFAULT_CLAIM_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// How long the server keeps secret sharing data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// How often the garbage collector runs
+    pub gc_interval: Duration,
+    /// Claims older than this are removed from the ss log, whatever their status is.
+    /// The age is counted from the time the server received the claim, or
+    /// from the first garbage collection that saw a claim without a time.
+    pub claim_max_age: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            gc_interval: DEFAULT_GC_INTERVAL,
+            claim_max_age: DEFAULT_CLAIM_MAX_AGE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GcReport {
+    pub expired_claims: Vec<ClaimId>,
+    pub deleted_events: Vec<ArtifactId>,
+}
+
+impl GcReport {
+    pub fn is_empty(&self) -> bool {
+        self.expired_claims.is_empty() && self.deleted_events.is_empty()
+    }
+
+    pub fn merge(&mut self, other: GcReport) {
+        self.expired_claims.extend(other.expired_claims);
+        self.deleted_events.extend(other.deleted_events);
+    }
+}
+
+/// Removes expired claims from the ss log of a vault and deletes the workflow events
+/// (shares and declines) nobody needs anymore
+pub struct WorkflowGc<Repo: KvLogEventRepo> {
+    p_obj: Arc<PersistentObject<Repo>>,
+    policy: RetentionPolicy,
+}
+
+impl<Repo: KvLogEventRepo> WorkflowGc<Repo> {
+    pub fn new(p_obj: Arc<PersistentObject<Repo>>, policy: RetentionPolicy) -> Self {
+        Self { p_obj, policy }
+    }
+
+    /// `now` is the unix time in milliseconds
+    pub async fn collect_vault(&self, vault_name: VaultName, now: u64) -> Result<GcReport> {
+        let expired_claims = self.expire_claims(vault_name.clone(), now).await?;
+
+        let deleted_events = stale_workflow_events(self.p_obj.clone(), vault_name.clone()).await?;
+        for id in deleted_events.iter() {
+            self.p_obj.repo.delete(id.clone()).await;
+        }
+
+        let report = GcReport {
+            expired_claims,
+            deleted_events,
+        };
+        if !report.is_empty() {
+            info!(
+                "Vault {}: expired {} claims, deleted {} workflow events",
+                vault_name,
+                report.expired_claims.len(),
+                report.deleted_events.len()
+            );
+        }
+
+        Ok(report)
+    }
+
+    /// Stamps new claims without a time, removes the expired ones and closes
+    /// the recovery claims that are past their ttl.
+    /// A new ss log event is written only if anything has changed
+    async fn expire_claims(&self, vault_name: VaultName, now: u64) -> Result<Vec<ClaimId>> {
+        let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+        let Some(ss_log_event) = p_ss.find_ss_log_tail_event(vault_name.clone()).await? else {
+            return Ok(vec![]);
+        };
+
+        let max_age = u64::try_from(self.policy.claim_max_age.as_millis()).unwrap_or(u64::MAX);
+        let mut ss_log_data = ss_log_event.to_data();
+        let mut changed = false;
+        let mut expired = vec![];
+
+        ss_log_data
+            .claims
+            .retain(|claim_id, claim| match claim.started_at().or(claim.created_at) {
+                None => {
+                    claim.created_at = Some(now);
+                    changed = true;
+                    true
+                }
+                Some(started_at) if now.saturating_sub(started_at) >= max_age => {
+                    expired.push(claim_id.clone());
+                    changed = true;
+                    false
+                }
+                Some(_) => true,
+            });
+
+        for claim in ss_log_data.claims.values_mut() {
+            if claim.expire(now) {
+                changed = true;
+            }
+        }
+
+        if changed {
+            let new_ss_log_event = p_ss
+                .create_new_ss_log_object(ss_log_data, vault_name)
+                .await?;
+            self.p_obj.append(new_ss_log_event).await?;
+        }
+
+        Ok(expired)
+    }
+}
+
+/// Workflow events of the receivers that are done with a claim: the share has been delivered,
+/// the receiver has declined or hasn't answered in time. Claims that are gone from the ss log
+/// (fully delivered, cancelled or expired) are done for every receiver.
+pub async fn stale_workflow_events<Repo: KvLogEventRepo>(
+    p_obj: Arc<PersistentObject<Repo>>,
+    vault_name: VaultName,
+) -> Result<Vec<ArtifactId>> {
+    let ss_log = PersistentSharedSecret::from(p_obj.clone())
+        .get_ss_log_obj(vault_name.clone())
+        .await?;
+
+    let mut stale = vec![];
+    // split distributions are identified by password and receiver, not by claim,
+    // a newer claim may still need the same distribution object
+    let mut in_use = HashSet::new();
+    for claim in claims_history(&p_obj, vault_name).await? {
+        let active_claim = ss_log.claims.get(&claim.id);
+        if active_claim.is_none() {
+            stale.push(SsWorkflowDescriptor::CancelClaim(
+                claim.dist_claim_id.clone(),
+            ));
+            stale.push(SsWorkflowDescriptor::Veto(claim.dist_claim_id.clone()));
+        }
+        for recovery_id in claim.recovery_db_ids() {
+            let is_done = active_claim.is_none_or(|active| {
+                matches!(
+                    active.status.get(&recovery_id.distribution_id.receiver),
+                    Some(
+                        SsDistributionStatus::Delivered
+                            | SsDistributionStatus::Declined
+                            | SsDistributionStatus::Expired
+                    )
+                )
+            });
+            for desc in workflow_descriptors(recovery_id) {
+                if is_done {
+                    stale.push(desc);
+                } else {
+                    in_use.insert(desc_key(desc));
+                }
+            }
+        }
+    }
+
+    let mut stale_ids = vec![];
+    let mut seen = HashSet::new();
+    for desc in stale {
+        let desc_id = desc_key(desc.clone());
+        if in_use.contains(&desc_id) || !seen.insert(desc_id) {
+            continue;
+        }
+        let events: Vec<GenericKvLogEvent> =
+            p_obj.find_object_events(ArtifactId::from(desc)).await?;
+        stale_ids.extend(events.iter().map(|event| event.obj_id()));
+    }
+
+    Ok(stale_ids)
+}
+
+/// Every claim that has ever been in the ss log, in its latest known state
+pub async fn claims_history<Repo: KvLogEventRepo>(
+    p_obj: &PersistentObject<Repo>,
+    vault_name: VaultName,
+) -> Result<Vec<SsClaim>> {
+    let ss_log_events: Vec<SsLogObject> = p_obj
+        .find_object_events(ArtifactId::from(SsLogDescriptor::from(vault_name)))
+        .await?;
+
+    let mut claims = HashMap::new();
+    for ss_log_event in ss_log_events {
+        claims.extend(ss_log_event.to_data().claims);
+    }
+
+    let mut claims: Vec<SsClaim> = claims.into_values().collect();
+    claims.sort_by_key(|claim| claim.id.0.clone().id_str());
+    Ok(claims)
+}
+
+/// Workflow objects of a receiver of a claim
+/// Recovery, decline and approval objects of the same receiver share the id, the object type tells them apart
+fn desc_key(desc: SsWorkflowDescriptor) -> String {
+    desc.to_obj_desc().fqdn().id_str()
+}
+
+pub fn workflow_descriptors(recovery_id: SsRecoveryId) -> [SsWorkflowDescriptor; 4] {
+    [
+        SsWorkflowDescriptor::Distribution(recovery_id.distribution_id.clone()),
+        SsWorkflowDescriptor::Recovery(recovery_id.clone()),
+        SsWorkflowDescriptor::Decline(recovery_id.clone()),
+        SsWorkflowDescriptor::Approval(recovery_id),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meta_secret_core::crypto::key_pair::KeyPair;
+    use meta_secret_core::crypto::keys::fixture::KeyManagerFixture;
+    use meta_secret_core::crypto::keys::KeyManager;
+    use meta_secret_core::crypto::utils::Id48bit;
+    use meta_secret_core::node::common::clock::{EventTime, HlcTimestamp};
+    use meta_secret_core::node::common::model::crypto::aead::EncryptedMessage;
+    use meta_secret_core::node::common::model::device::common::DeviceId;
+    use meta_secret_core::node::common::model::device::device_creds::fixture::DeviceCredentialsFixture;
+    use meta_secret_core::node::common::model::meta_pass::MetaPasswordId;
+    use meta_secret_core::node::common::model::secret::{
+        SecretDistributionData, SecretDistributionType, SsCancelClaimData, SsClaimId,
+        SsDeclineData, SsDistributionCompositeStatus, SsDistributionId, SsLogData,
+    };
+    use meta_secret_core::node::db::events::kv_log_event::{KvKey, KvLogEvent};
+    use meta_secret_core::node::db::events::shared_secret_event::SsWorkflowObject;
+    use meta_secret_core::node::db::in_mem_db::InMemKvLogEventRepo;
+    use meta_secret_core::node::db::repo::generic_db::{FindOneQuery, SaveCommand};
+    use meta_secret_core::secret::shared_secret::PlainText;
+
+    const VAULT: &str = "vault";
+    const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+    struct GcFixture {
+        p_obj: Arc<PersistentObject<InMemKvLogEventRepo>>,
+        gc: WorkflowGc<InMemKvLogEventRepo>,
+        creds: DeviceCredentialsFixture,
+        key_manager: KeyManager,
+    }
+
+    impl GcFixture {
+        fn new() -> Self {
+            let p_obj = Arc::new(PersistentObject::in_mem());
+            let policy = RetentionPolicy {
+                gc_interval: DEFAULT_GC_INTERVAL,
+                claim_max_age: Duration::from_millis(DAY_MILLIS),
+            };
+            Self {
+                gc: WorkflowGc::new(p_obj.clone(), policy),
+                p_obj,
+                creds: DeviceCredentialsFixture::from_km(KeyManagerFixture::generate()),
+                key_manager: KeyManager::generate(),
+            }
+        }
+
+        /// A split of the client device to the given receivers
+        fn split_claim(&self, receivers: Vec<(DeviceId, SsDistributionStatus)>) -> SsClaim {
+            let id = ClaimId(Id48bit::generate());
+            SsClaim {
+                id: id.clone(),
+                dist_claim_id: SsClaimId {
+                    id,
+                    pass_id: MetaPasswordId::build_from_str("pass"),
+                },
+                vault_name: VaultName::from(VAULT),
+                sender: self.creds.client.device.device_id.clone(),
+                distribution_type: SecretDistributionType::Split,
+                receivers: receivers
+                    .iter()
+                    .map(|(receiver, _)| receiver.clone())
+                    .collect(),
+                status: SsDistributionCompositeStatus {
+                    statuses: receivers.into_iter().collect(),
+                },
+                created_at: None,
+                time: None,
+                ttl: None,
+                fallback: None,
+                break_glass: None,
+                approvals: vec![],
+            }
+        }
+
+        async fn save_ss_log(&self, claims: Vec<SsClaim>) -> Result<()> {
+            let ss_log_data = SsLogData {
+                claims: claims
+                    .into_iter()
+                    .map(|claim| (claim.id.clone(), claim))
+                    .collect(),
+            };
+            let ss_log_event = PersistentSharedSecret::from(self.p_obj.clone())
+                .create_new_ss_log_object(ss_log_data, VaultName::from(VAULT))
+                .await?;
+            self.p_obj.repo.save(ss_log_event).await?;
+            Ok(())
+        }
+
+        async fn save_share(&self, claim: &SsClaim, receiver: &DeviceId) -> Result<ArtifactId> {
+            let share = self
+                .key_manager
+                .transport
+                .encrypt_string(PlainText::from("share"), &self.key_manager.transport.pk())?;
+            let desc = SsWorkflowDescriptor::Distribution(SsDistributionId {
+                pass_id: claim.dist_claim_id.pass_id.clone(),
+                receiver: receiver.clone(),
+            });
+            let share_event = SsWorkflowObject::Distribution(KvLogEvent::new(
+                KvKey::from(desc),
+                SecretDistributionData {
+                    vault_name: claim.vault_name.clone(),
+                    claim_id: claim.dist_claim_id.clone(),
+                    secret_message: EncryptedMessage::CipherShare { share },
+                },
+            ));
+            self.p_obj.repo.save(share_event).await
+        }
+
+        async fn save_decline(&self, claim: &SsClaim, receiver: &DeviceId) -> Result<ArtifactId> {
+            let recovery_id = claim
+                .recovery_db_ids()
+                .into_iter()
+                .find(|recovery_id| recovery_id.distribution_id.receiver == *receiver)
+                .expect("receiver of the claim");
+            let decline_event = SsWorkflowObject::Decline(KvLogEvent::new(
+                KvKey::from(SsWorkflowDescriptor::Decline(recovery_id)),
+                SsDeclineData {
+                    vault_name: claim.vault_name.clone(),
+                    claim_id: claim.id.clone(),
+                    receiver_id: receiver.clone(),
+                },
+            ));
+            self.p_obj.repo.save(decline_event).await
+        }
+
+        async fn ss_log(&self) -> Result<SsLogData> {
+            PersistentSharedSecret::from(self.p_obj.clone())
+                .get_ss_log_obj(VaultName::from(VAULT))
+                .await
+        }
+
+        async fn exists(&self, id: &ArtifactId) -> Result<bool> {
+            Ok(self.p_obj.repo.find_one(id.clone()).await?.is_some())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delivered_shares_are_deleted() -> Result<()> {
+        let fixture = GcFixture::new();
+        let client_b = fixture.creds.client_b.device.device_id.clone();
+        let vd = fixture.creds.vd.device.device_id.clone();
+
+        let claim = fixture.split_claim(vec![
+            (client_b.clone(), SsDistributionStatus::Delivered),
+            (vd.clone(), SsDistributionStatus::Sent),
+        ]);
+        fixture.save_ss_log(vec![claim.clone()]).await?;
+        let delivered_share = fixture.save_share(&claim, &client_b).await?;
+        let pending_share = fixture.save_share(&claim, &vd).await?;
+
+        let report = fixture.gc.collect_vault(VaultName::from(VAULT), 0).await?;
+
+        assert!(report.expired_claims.is_empty());
+        assert_eq!(report.deleted_events, vec![delivered_share.clone()]);
+        assert!(!fixture.exists(&delivered_share).await?);
+        assert!(fixture.exists(&pending_share).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_old_claims_expire_with_their_shares() -> Result<()> {
+        let fixture = GcFixture::new();
+        let client_b = fixture.creds.client_b.device.device_id.clone();
+
+        let claim = fixture.split_claim(vec![(client_b.clone(), SsDistributionStatus::Sent)]);
+        fixture.save_ss_log(vec![claim.clone()]).await?;
+        let share = fixture.save_share(&claim, &client_b).await?;
+
+        // the first run only starts the clock of the claim
+        let start = 10 * DAY_MILLIS;
+        let report = fixture
+            .gc
+            .collect_vault(VaultName::from(VAULT), start)
+            .await?;
+        assert!(report.is_empty());
+        let ss_log = fixture.ss_log().await?;
+        assert_eq!(ss_log.claims[&claim.id].created_at, Some(start));
+
+        let report = fixture
+            .gc
+            .collect_vault(VaultName::from(VAULT), start + DAY_MILLIS - 1)
+            .await?;
+        assert!(report.is_empty());
+
+        let report = fixture
+            .gc
+            .collect_vault(VaultName::from(VAULT), start + DAY_MILLIS)
+            .await?;
+        assert_eq!(report.expired_claims, vec![claim.id.clone()]);
+        assert_eq!(report.deleted_events, vec![share.clone()]);
+        assert!(fixture.ss_log().await?.claims.is_empty());
+        assert!(!fixture.exists(&share).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_claims_age_from_the_time_they_were_received() -> Result<()> {
+        let fixture = GcFixture::new();
+        let client_b = fixture.creds.client_b.device.device_id.clone();
+
+        let mut claim = fixture.split_claim(vec![(client_b.clone(), SsDistributionStatus::Sent)]);
+        claim.time = Some(EventTime {
+            created_at: 0,
+            hlc: HlcTimestamp::default(),
+            received_at: Some(DAY_MILLIS),
+        });
+        fixture.save_ss_log(vec![claim.clone()]).await?;
+
+        // the claim is not stamped, its age is known already
+        let report = fixture
+            .gc
+            .collect_vault(VaultName::from(VAULT), 2 * DAY_MILLIS - 1)
+            .await?;
+        assert!(report.is_empty());
+        assert_eq!(fixture.ss_log().await?.claims[&claim.id].created_at, None);
+
+        let report = fixture
+            .gc
+            .collect_vault(VaultName::from(VAULT), 2 * DAY_MILLIS)
+            .await?;
+        assert_eq!(report.expired_claims, vec![claim.id.clone()]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_split_share_of_a_newer_claim_is_kept() -> Result<()> {
+        let fixture = GcFixture::new();
+        let client_b = fixture.creds.client_b.device.device_id.clone();
+
+        // both claims split the same password, the distribution object is shared
+        let old_claim =
+            fixture.split_claim(vec![(client_b.clone(), SsDistributionStatus::Delivered)]);
+        fixture.save_ss_log(vec![old_claim]).await?;
+        let new_claim = fixture.split_claim(vec![(client_b.clone(), SsDistributionStatus::Sent)]);
+        fixture.save_ss_log(vec![new_claim.clone()]).await?;
+        let share = fixture.save_share(&new_claim, &client_b).await?;
+
+        let report = fixture.gc.collect_vault(VaultName::from(VAULT), 0).await?;
+
+        assert!(report.deleted_events.is_empty());
+        assert!(fixture.exists(&share).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recovery_claims_expire_after_ttl() -> Result<()> {
+        let fixture = GcFixture::new();
+        let client_b = fixture.creds.client_b.device.device_id.clone();
+        let vd = fixture.creds.vd.device.device_id.clone();
+
+        let mut claim = fixture.split_claim(vec![
+            (client_b.clone(), SsDistributionStatus::Pending),
+            (vd.clone(), SsDistributionStatus::Declined),
+        ]);
+        claim.distribution_type = SecretDistributionType::Recover;
+        claim.time = Some(EventTime {
+            created_at: 0,
+            hlc: HlcTimestamp::default(),
+            received_at: Some(DAY_MILLIS),
+        });
+        claim.ttl = Some(DAY_MILLIS / 2);
+        fixture.save_ss_log(vec![claim.clone()]).await?;
+        let decline = fixture.save_decline(&claim, &vd).await?;
+
+        fixture
+            .gc
+            .collect_vault(VaultName::from(VAULT), DAY_MILLIS + DAY_MILLIS / 2 - 1)
+            .await?;
+        let ss_log = fixture.ss_log().await?;
+        assert_eq!(
+            ss_log.claims[&claim.id].status.status(),
+            SsDistributionStatus::Pending
+        );
+
+        fixture
+            .gc
+            .collect_vault(VaultName::from(VAULT), DAY_MILLIS + DAY_MILLIS / 2)
+            .await?;
+        let ss_log = fixture.ss_log().await?;
+        let expired_claim = &ss_log.claims[&claim.id];
+        assert_eq!(
+            expired_claim.status.get(&client_b),
+            Some(&SsDistributionStatus::Expired)
+        );
+        assert_eq!(
+            expired_claim.status.get(&vd),
+            Some(&SsDistributionStatus::Declined)
+        );
+        assert!(!fixture.exists(&decline).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_claims_are_cleaned_up() -> Result<()> {
+        let fixture = GcFixture::new();
+        let client_b = fixture.creds.client_b.device.device_id.clone();
+
+        let mut claim = fixture.split_claim(vec![(client_b.clone(), SsDistributionStatus::Sent)]);
+        claim.distribution_type = SecretDistributionType::Recover;
+        fixture.save_ss_log(vec![claim.clone()]).await?;
+
+        let cancel_event = SsWorkflowObject::CancelClaim(KvLogEvent::new(
+            KvKey::from(SsWorkflowDescriptor::CancelClaim(
+                claim.dist_claim_id.clone(),
+            )),
+            SsCancelClaimData {
+                vault_name: claim.vault_name.clone(),
+                claim_id: claim.dist_claim_id.clone(),
+                sender: claim.sender.clone(),
+            },
+        ));
+        let cancellation = fixture.p_obj.repo.save(cancel_event).await?;
+
+        let report = fixture.gc.collect_vault(VaultName::from(VAULT), 0).await?;
+        assert!(report.deleted_events.is_empty());
+
+        // the server has processed the cancellation
+        let ss_log = fixture.ss_log().await?.cancel(&claim.id);
+        let ss_log_event = PersistentSharedSecret::from(fixture.p_obj.clone())
+            .create_new_ss_log_object(ss_log, VaultName::from(VAULT))
+            .await?;
+        fixture.p_obj.append(ss_log_event).await?;
+
+        let report = fixture.gc.collect_vault(VaultName::from(VAULT), 0).await?;
+        assert_eq!(report.deleted_events, vec![cancellation.clone()]);
+        assert!(!fixture.exists(&cancellation).await?);
+
+        Ok(())
+    }
+}
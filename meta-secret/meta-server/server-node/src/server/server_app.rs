@@ -1,55 +1,109 @@
 use std::sync::Arc;
 
+use crate::server::audit::ServerAudit;
+use crate::server::metrics::{MeteredRepo, RequestKind, ServerMetrics};
+use crate::server::retention::{GcReport, RetentionPolicy, WorkflowGc};
 use crate::server::server_data_sync::ServerSyncGateway;
+use crate::server::vault_router::{
+    request_vault, Dispatch, ServerEnvelope, VaultLocks, VaultRouter,
+};
 use anyhow::{bail, Result};
 use meta_secret_core::node::api::{
-    DataEventsResponse, DataSyncResponse, ReadSyncRequest, ServerTailRequest, ServerTailResponse,
-    SyncRequest, WriteSyncRequest,
+    DataEventsResponse, DataSyncResponse, HandshakeRequest, HandshakeResponse, ProtocolInfo,
+    ReadSyncRequest, ServerTailRequest, ServerTailResponse, SyncRequest, WriteSyncRequest,
 };
-use meta_secret_core::node::common::data_transfer::MpscDataTransfer;
 use meta_secret_core::node::common::model::device::common::DeviceName;
 use meta_secret_core::node::common::model::device::device_creds::DeviceCreds;
+use meta_secret_core::node::db::descriptors::object_descriptor::ObjectType;
 use meta_secret_core::node::db::descriptors::shared_secret_descriptor::SsLogDescriptor;
-use meta_secret_core::node::db::events::generic_log_event::ToGenericEvent;
+use meta_secret_core::node::db::descriptors::vault_descriptor::VaultDescriptor;
 use meta_secret_core::node::db::events::object_id::Next;
 use meta_secret_core::node::db::objects::persistent_device_log::PersistentDeviceLog;
 use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
 use meta_secret_core::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
-use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
+use meta_secret_core::node::db::repo::generic_db::{FindObjectsQuery, KvLogEventRepo};
 use meta_secret_core::node::db::repo::persistent_credentials::PersistentCredentials;
-use tracing::{error, info, instrument};
+use flume::Receiver;
+use meta_secret_core::node::common::data_transfer::MpscDataTransfer;
+use std::num::NonZeroUsize;
+use std::thread::available_parallelism;
+use meta_secret_core::node::common::clock::unix_time_millis;
+use std::time::Duration;
+use meta_secret_core::node::common::model::vault::vault::VaultName;
+use tokio::time::{interval, MissedTickBehavior};
+use tokio_util::task::LocalPoolHandle;
+use tracing::{debug, error, info, instrument, warn};
 use meta_secret_core::crypto::keys::TransportSk;
 
+/// Pending client requests the server hasn't dispatched yet
+const REQUEST_QUEUE_CAPACITY: usize = 256;
+/// A client gets an error if the server hasn't responded in time, the request is cancelled
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Answers whether the server can serve requests: the db is reachable
+/// and the master key decrypts the server credentials
+pub type ReadinessProbe = MpscDataTransfer<(), Result<(), String>>;
+
 pub struct MetaServerDataTransfer {
     pub dt: MpscDataTransfer<SyncRequest, DataSyncResponse>,
+    request_timeout: Duration,
 }
 
 impl Default for MetaServerDataTransfer {
     fn default() -> Self {
-        Self {
-            dt: MpscDataTransfer::new(),
-        }
+        Self::new(DEFAULT_REQUEST_TIMEOUT)
     }
 }
 
 impl MetaServerDataTransfer {
+    pub fn new(request_timeout: Duration) -> Self {
+        Self {
+            dt: MpscDataTransfer::with_capacity(REQUEST_QUEUE_CAPACITY),
+            request_timeout,
+        }
+    }
+
     pub async fn send_request(&self, request: SyncRequest) -> Result<DataSyncResponse> {
-        self.dt
-            .send_to_service_and_get(request)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to get response: {:?}", e))
+        let response = self
+            .dt
+            .send_to_service_and_get_timeout(request, self.request_timeout)
+            .await?;
+        Ok(response)
     }
 }
 
 pub struct ServerApp<Repo: KvLogEventRepo> {
-    data_sync: Arc<ServerSyncGateway<Repo>>,
-    p_obj: Arc<PersistentObject<Repo>>,
-    creds_repo: Arc<PersistentCredentials<Repo>>,
+    data_sync: Arc<ServerSyncGateway<MeteredRepo<Repo>>>,
+    p_obj: Arc<PersistentObject<MeteredRepo<Repo>>>,
+    creds_repo: Arc<PersistentCredentials<MeteredRepo<Repo>>>,
     data_transfer: Arc<MetaServerDataTransfer>,
+    readiness: Arc<ReadinessProbe>,
+    metrics: Arc<ServerMetrics>,
+    vault_locks: Arc<VaultLocks>,
+    retention: RetentionPolicy,
+    worker_threads: usize,
+}
+
+impl<Repo: KvLogEventRepo> Clone for ServerApp<Repo> {
+    fn clone(&self) -> Self {
+        Self {
+            data_sync: self.data_sync.clone(),
+            p_obj: self.p_obj.clone(),
+            creds_repo: self.creds_repo.clone(),
+            data_transfer: self.data_transfer.clone(),
+            readiness: self.readiness.clone(),
+            metrics: self.metrics.clone(),
+            vault_locks: self.vault_locks.clone(),
+            retention: self.retention,
+            worker_threads: self.worker_threads,
+        }
+    }
 }
 
 impl<Repo: KvLogEventRepo> ServerApp<Repo> {
     pub fn new(repo: Arc<Repo>, master_key: TransportSk) -> Result<Self> {
+        let metrics = Arc::new(ServerMetrics::new()?);
+        let repo = Arc::new(MeteredRepo::new(repo, &metrics));
         let p_obj = Arc::new(PersistentObject::new(repo));
         let data_sync = Arc::new(ServerSyncGateway::from(p_obj.clone()));
         let creds_repo = Arc::new(PersistentCredentials {
@@ -62,48 +116,172 @@ impl<Repo: KvLogEventRepo> ServerApp<Repo> {
             data_sync,
             p_obj,
             creds_repo,
-            data_transfer
+            data_transfer,
+            readiness: Arc::new(ReadinessProbe::new()),
+            metrics,
+            vault_locks: Arc::new(VaultLocks::default()),
+            retention: RetentionPolicy::default(),
+            worker_threads: available_parallelism().map(NonZeroUsize::get).unwrap_or(1),
         })
     }
 
+    pub fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.data_transfer = Arc::new(MetaServerDataTransfer::new(request_timeout));
+        self
+    }
+
+    pub fn with_retention(mut self, retention: RetentionPolicy) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// Number of threads the vault workers are spread over, one per cpu by default
+    pub fn with_worker_threads(mut self, worker_threads: usize) -> Self {
+        self.worker_threads = worker_threads.max(1);
+        self
+    }
+
+    /// The join quorum new vaults start with, the members of a vault change it later
+    pub fn with_join_quorum(mut self, join_quorum: usize) -> Self {
+        self.data_sync = Arc::new(ServerSyncGateway {
+            p_obj: self.p_obj.clone(),
+            join_quorum,
+            require_invite: self.data_sync.require_invite,
+        });
+        self
+    }
+
+    /// Whether new vaults accept invited devices only, the members of a vault change it later
+    pub fn with_require_invite(mut self, require_invite: bool) -> Self {
+        self.data_sync = Arc::new(ServerSyncGateway {
+            p_obj: self.p_obj.clone(),
+            join_quorum: self.data_sync.join_quorum,
+            require_invite,
+        });
+        self
+    }
+
     pub fn get_data_transfer(&self) -> Arc<MetaServerDataTransfer> {
         self.data_transfer.clone()
     }
 
-    pub async fn run(&self) -> Result<()> {
-        info!("Run server_app service");
+    pub fn get_readiness_probe(&self) -> Arc<ReadinessProbe> {
+        self.readiness.clone()
+    }
 
-        let device_creds = self.get_creds().await?;
-        info!("Server initialized with device: {:?}", &device_creds.device);
+    pub fn get_metrics(&self) -> Arc<ServerMetrics> {
+        self.metrics.clone()
+    }
+
+    async fn readiness_checks(&self) -> Result<()> {
+        while let Ok(envelope) = self.readiness.service_receive().await {
+            let readiness = self
+                .get_creds()
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("{:?}", e));
+            let _ = envelope.reply(readiness).await;
+        }
+        Ok(())
+    }
+
+    async fn dispatch(&self, workers: &LocalPoolHandle) -> Result<()>
+    where
+        Repo: Send + Sync,
+    {
+        let mut router = VaultRouter::default();
 
         loop {
-            match self.data_transfer.dt.service_receive().await {
-                Ok(request) => {
-                    let response = self.handle_client_request(request).await;
-                    match response {
-                        Ok(resp) => {
-                            self.data_transfer.dt.send_to_client(resp).await;
-                        }
-                        Err(e) => {
-                            let resp = DataSyncResponse::Error {
-                                msg: format!("Error processing client request: {:?}", e),
-                            };
-                            error!("Error processing request: {:?}", e);
-                            self.data_transfer.dt.send_to_client(resp).await;
-                        }
-                    }
+            let envelope = match self.data_transfer.dt.service_receive().await {
+                Ok(envelope) => envelope,
+                Err(e) => bail!("Server app request channel is closed: {:?}", e),
+            };
+
+            let dispatch = router.dispatch(envelope);
+            self.metrics.set_active_vaults(router.active_vaults());
+
+            match dispatch {
+                Dispatch::Queued => {}
+                Dispatch::NewWorker { vault_name, queue } => {
+                    let server_app = self.clone();
+                    workers.spawn_pinned(move || server_app.vault_worker(vault_name, queue));
+                }
+                Dispatch::Unrouted(envelope) => {
+                    let server_app = self.clone();
+                    workers.spawn_pinned(move || async move {
+                        server_app.process(envelope).await;
+                    });
                 }
-                Err(e) => {
-                    error!("Error receiving message: {:?}", e);
+                Dispatch::Busy {
+                    vault_name,
+                    envelope,
+                } => {
+                    warn!("Too many pending requests for vault: {:?}", vault_name);
+                    self.metrics
+                        .request_failed(RequestKind::of(&envelope.request));
                     let resp = DataSyncResponse::Error {
-                        msg: format!("Error receiving message: {:?}", e),
+                        msg: format!("Vault is busy, try again later: {:?}", vault_name),
                     };
-                    self.data_transfer.dt.send_to_client(resp).await;
-                    // Continue the loop even if there's an error
+                    let _ = envelope.reply(resp).await;
                 }
             }
+        }
+    }
 
-            async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+    #[instrument(skip(self, queue))]
+    async fn vault_worker(self, vault_name: VaultName, queue: Receiver<ServerEnvelope>) {
+        debug!("Vault worker started");
+        while let Ok(envelope) = queue.recv_async().await {
+            self.process(envelope).await;
+        }
+        debug!("Vault worker stopped");
+    }
+
+    async fn process(&self, envelope: ServerEnvelope) {
+        if envelope.is_cancelled() {
+            warn!("Skip request cancelled by the client: {}", envelope.id);
+            return;
+        }
+
+        let kind = RequestKind::of(&envelope.request);
+        let vault_name = request_vault(&envelope.request);
+        let _vault_guard = match &vault_name {
+            Some(vault_name) => Some(self.vault_locks.lock(vault_name.clone()).await),
+            None => None,
+        };
+        let timer = self.metrics.request_started(kind);
+
+        let resp = match self.handle_client_request(envelope.request.clone()).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                error!("Error processing request: {:?}", e);
+                self.metrics.request_failed(kind);
+                DataSyncResponse::Error {
+                    msg: format!("Error processing client request: {:?}", e),
+                }
+            }
+        };
+
+        timer.observe_duration();
+
+        if let Err(e) = envelope.reply(resp).await {
+            warn!("Response is not delivered: {}", e);
+        }
+
+        if let Some(vault_name) = vault_name.filter(|_| kind.changes_claims()) {
+            self.update_claims_metrics(vault_name).await;
+        }
+    }
+
+    async fn update_claims_metrics(&self, vault_name: VaultName) {
+        let ss_log_desc = SsLogDescriptor::from(vault_name.clone());
+        match self.p_obj.find_tail_event(ss_log_desc).await {
+            Ok(Some(ss_log_event)) => {
+                let ss_log_data = ss_log_event.to_data();
+                self.metrics.update_vault_claims(vault_name, &ss_log_data);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read ss log for metrics: {:?}", e),
         }
     }
 
@@ -128,14 +306,23 @@ impl<Repo: KvLogEventRepo> ServerApp<Repo> {
             SyncRequest::Read(read_request) => match *read_request {
                 ReadSyncRequest::Vault(request) => {
                     let new_events = self.data_sync.vault_replication(request).await?;
-                    Ok(DataSyncResponse::Data(DataEventsResponse(new_events)))
+                    Ok(DataSyncResponse::Data(new_events))
                 }
                 ReadSyncRequest::SsRequest(request) => {
                     let new_events = self
                         .data_sync
                         .ss_replication(request, server_creds.device.device_id.clone())
                         .await?;
-                    Ok(DataSyncResponse::Data(DataEventsResponse(new_events)))
+
+                    let audit = self.audit(&server_creds)?;
+                    if let Err(e) = audit
+                        .record_delivery(&new_events.events, unix_time_millis())
+                        .await
+                    {
+                        error!("Failed to record share delivery audit: {:?}", e);
+                    }
+
+                    Ok(DataSyncResponse::Data(new_events))
                 }
                 ReadSyncRequest::SsRecoveryCompletion(recovery_completion) => {
                     let vault_name = recovery_completion.vault_name;
@@ -161,12 +348,9 @@ impl<Repo: KvLogEventRepo> ServerApp<Repo> {
                             let new_ss_log_obj = p_ss
                                 .create_new_ss_log_object(updated_ss_log_data, vault_name)
                                 .await?;
-                            self.p_obj
-                                .repo
-                                .save(new_ss_log_obj.clone().to_generic())
-                                .await?;
-                            let commit_log = vec![new_ss_log_obj.to_generic()];
-                            Ok(DataSyncResponse::Data(DataEventsResponse(commit_log)))
+                            let new_ss_log_event = self.p_obj.append(new_ss_log_obj).await?;
+                            let commit_log = vec![new_ss_log_event];
+                            Ok(DataSyncResponse::Data(DataEventsResponse::from(commit_log)))
                         }
                     }
                 }
@@ -196,13 +380,35 @@ impl<Repo: KvLogEventRepo> ServerApp<Repo> {
                     let data_sync_response = DataSyncResponse::ServerTailResponse(response);
                     Ok(data_sync_response)
                 }
+                ReadSyncRequest::Handshake(HandshakeRequest { client }) => {
+                    let server = ProtocolInfo::current();
+                    match HandshakeResponse::negotiate(&client, &server) {
+                        Ok(mut handshake) => {
+                            // clients pin the key and verify the audit logs against it
+                            handshake.audit_key = Some(server_creds.device.keys.dsa_pk.clone());
+                            Ok(DataSyncResponse::Handshake(handshake))
+                        }
+                        Err(upgrade) => {
+                            warn!("Incompatible client protocol: {}", upgrade);
+                            Ok(DataSyncResponse::UpgradeRequired(upgrade))
+                        }
+                    }
+                }
             },
             SyncRequest::Write(write_request) => match *write_request {
-                WriteSyncRequest::Event(event) => {
-                    info!("Received new event: {:?}", event);
-                    self.data_sync
-                        .handle_write(server_creds.device, event)
+                WriteSyncRequest::Event(signed_event) => {
+                    info!("Received new event: {:?}", signed_event);
+                    let now = unix_time_millis();
+
+                    let audit = self.audit(&server_creds)?;
+                    let event = self
+                        .data_sync
+                        .handle_write(server_creds.device, &signed_event, now)
                         .await?;
+
+                    if let Err(e) = audit.record_write(&event, now).await {
+                        error!("Failed to record audit entry: {:?}", e);
+                    }
                     Ok(DataSyncResponse::Empty)
                 }
             },
@@ -214,4 +420,238 @@ impl<Repo: KvLogEventRepo> ServerApp<Repo> {
             .get_or_generate_device_creds(DeviceName::server())
             .await
     }
+
+    fn audit(&self, server_creds: &DeviceCreds) -> Result<ServerAudit<MeteredRepo<Repo>>> {
+        let dsa = server_creds.key_manager()?.dsa;
+        Ok(ServerAudit::new(self.p_obj.clone(), dsa))
+    }
+}
+
+impl<Repo: KvLogEventRepo + FindObjectsQuery> ServerApp<Repo> {
+    /// Processes requests of different vaults in parallel, each vault has its own worker
+    /// that keeps the requests of the vault in order. Repo futures are not `Send`
+    /// (the browser repo can't be), so a worker is pinned to one thread of a pool
+    /// and a slow db call only holds up the vaults of that thread.
+    pub async fn run(&self) -> Result<()>
+    where
+        Repo: Send + Sync,
+    {
+        info!("Run server_app service");
+
+        let device_creds = self.get_creds().await?;
+        info!("Server initialized with device: {:?}", &device_creds.device);
+
+        let workers = LocalPoolHandle::new(self.worker_threads);
+        tokio::try_join!(
+            self.dispatch(&workers),
+            self.readiness_checks(),
+            self.garbage_collection()
+        )?;
+        Ok(())
+    }
+
+    /// Runs the garbage collection of every vault once per gc interval
+    async fn garbage_collection(&self) -> Result<()> {
+        let mut ticks = interval(self.retention.gc_interval);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            ticks.tick().await;
+
+            if let Err(e) = self.collect_garbage(unix_time_millis()).await {
+                error!("Garbage collection failed: {:?}", e);
+            }
+        }
+    }
+
+    /// Expires old claims and deletes delivered workflow events of all the vaults,
+    /// `now` is the unix time in milliseconds
+    pub async fn collect_garbage(&self, now: u64) -> Result<GcReport> {
+        let vault_type = VaultDescriptor::from(VaultName::from("")).object_type();
+        let vault_names = self.p_obj.repo.find_object_names(&vault_type).await?;
+
+        let gc = WorkflowGc::new(self.p_obj.clone(), self.retention);
+        let mut report = GcReport::default();
+        for vault_name in vault_names {
+            let vault_name = VaultName::from(vault_name);
+            let _vault_guard = self.vault_locks.lock(vault_name.clone()).await;
+
+            let vault_report = gc.collect_vault(vault_name.clone(), now).await?;
+            if !vault_report.expired_claims.is_empty() {
+                self.update_claims_metrics(vault_name).await;
+            }
+            report.merge(vault_report);
+        }
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use meta_secret_core::crypto::keys::fixture::KeyManagerFixture;
+    use meta_secret_core::node::api::{
+        ProtocolFeature, UpgradeComponent, VaultRequest, DEFAULT_PAGE_SIZE,
+    };
+    use meta_secret_core::node::common::model::device::device_creds::fixture::DeviceCredentialsFixture;
+    use meta_secret_core::node::common::model::user::common::UserData;
+    use meta_secret_core::node::db::events::generic_log_event::{
+        GenericKvLogEvent, ToGenericEvent,
+    };
+    use meta_secret_core::node::db::events::object_id::ArtifactId;
+    use meta_secret_core::node::db::in_mem_db::InMemKvLogEventRepo;
+    use meta_secret_core::node::db::objects::persistent_vault::PersistentVault;
+    use meta_secret_core::node::db::repo::generic_db::{
+        DbCleanUpCommand, DeleteCommand, FindOneQuery, SaveCommand,
+    };
+    use std::time::Instant;
+
+    const SLOW_VAULT: &str = "slow_vault";
+    const SLOW_READ: Duration = Duration::from_secs(2);
+
+    /// Blocks its thread on every read of the slow vault, like a synchronous db call does
+    #[derive(Default)]
+    struct SlowVaultRepo {
+        repo: InMemKvLogEventRepo,
+    }
+
+    #[async_trait(? Send)]
+    impl SaveCommand for SlowVaultRepo {
+        async fn save<T: ToGenericEvent>(&self, value: T) -> Result<ArtifactId> {
+            self.repo.save(value).await
+        }
+    }
+
+    #[async_trait(? Send)]
+    impl FindOneQuery for SlowVaultRepo {
+        async fn find_one(&self, key: ArtifactId) -> Result<Option<GenericKvLogEvent>> {
+            if key.fqdn.obj_instance == SLOW_VAULT {
+                std::thread::sleep(SLOW_READ);
+            }
+            self.repo.find_one(key).await
+        }
+
+        async fn get_key(&self, key: ArtifactId) -> Result<Option<ArtifactId>> {
+            self.repo.get_key(key).await
+        }
+    }
+
+    #[async_trait(? Send)]
+    impl DeleteCommand for SlowVaultRepo {
+        async fn delete(&self, key: ArtifactId) {
+            self.repo.delete(key).await
+        }
+    }
+
+    #[async_trait(? Send)]
+    impl DbCleanUpCommand for SlowVaultRepo {
+        async fn db_clean_up(&self) {
+            self.repo.db_clean_up().await
+        }
+    }
+
+    #[async_trait(? Send)]
+    impl FindObjectsQuery for SlowVaultRepo {
+        async fn find_object_names(&self, obj_type: &str) -> Result<Vec<String>> {
+            self.repo.find_object_names(obj_type).await
+        }
+    }
+
+    impl KvLogEventRepo for SlowVaultRepo {}
+
+    async fn vault_request(user: UserData) -> Result<SyncRequest> {
+        let tail = PersistentVault::from(Arc::new(PersistentObject::in_mem()))
+            .vault_tail(user.clone())
+            .await?;
+        Ok(SyncRequest::Read(Box::new(ReadSyncRequest::Vault(
+            VaultRequest {
+                sender: user,
+                tail,
+                page_size: DEFAULT_PAGE_SIZE,
+            },
+        ))))
+    }
+
+    #[tokio::test]
+    async fn test_slow_vault_doesnt_hold_up_other_vaults() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let server_app = ServerApp::new(
+            Arc::new(SlowVaultRepo::default()),
+            creds.client_master_key.clone(),
+        )?
+        .with_worker_threads(2);
+        let data_transfer = server_app.get_data_transfer();
+
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            let _ = rt.block_on(server_app.run());
+        });
+
+        let slow_request = vault_request(UserData {
+            vault_name: VaultName::from(SLOW_VAULT),
+            device: creds.client.device.clone(),
+        })
+        .await?;
+        let fast_request = vault_request(UserData {
+            vault_name: VaultName::from("fast_vault"),
+            device: creds.client_b.device.clone(),
+        })
+        .await?;
+
+        let slow_response = {
+            let data_transfer = data_transfer.clone();
+            tokio::spawn(async move { data_transfer.send_request(slow_request).await })
+        };
+        // let the worker of the slow vault get stuck in the db first
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let started = Instant::now();
+        data_transfer.send_request(fast_request).await?;
+        assert!(started.elapsed() < SLOW_READ / 2);
+        assert!(!slow_response.is_finished());
+
+        slow_response.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_requires_signed_requests() -> Result<()> {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let server_app = ServerApp::new(
+            Arc::new(InMemKvLogEventRepo::default()),
+            creds.client_master_key.clone(),
+        )?;
+
+        let handshake = |features: Vec<ProtocolFeature>| {
+            SyncRequest::Read(Box::new(ReadSyncRequest::Handshake(HandshakeRequest {
+                client: ProtocolInfo {
+                    features,
+                    ..ProtocolInfo::current()
+                },
+            })))
+        };
+
+        let unsigned_client = handshake(vec![ProtocolFeature::JsonCodec]);
+        let DataSyncResponse::UpgradeRequired(upgrade) =
+            server_app.handle_client_request(unsigned_client).await?
+        else {
+            panic!("A client without signed requests must be asked to upgrade");
+        };
+        assert_eq!(upgrade.component, UpgradeComponent::Client);
+        assert_eq!(upgrade.missing_features, vec![ProtocolFeature::SignedRequests]);
+
+        let client = handshake(ProtocolInfo::current().features);
+        let negotiated = server_app
+            .handle_client_request(client)
+            .await?
+            .to_handshake()?;
+        assert!(negotiated.features.contains(&ProtocolFeature::PagedSync));
+        assert!(negotiated.features.contains(&ProtocolFeature::SignedRequests));
+        Ok(())
+    }
 }
@@ -3,33 +3,54 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use anyhow::{bail, Ok};
-use derive_more::From;
-use meta_secret_core::node::api::{SsRequest, VaultRequest};
+use meta_secret_core::node::api::{
+    DataEventsResponse, PageCursor, SignedEvent, SsRequest, VaultRequest, MAX_PAGE_SIZE,
+};
 use meta_secret_core::node::common::model::device::common::{DeviceData, DeviceId};
 use meta_secret_core::node::common::model::secret::SecretDistributionType;
 use meta_secret_core::node::common::model::vault::vault::VaultStatus;
+use meta_secret_core::node::common::model::vault::vault_data::DEFAULT_JOIN_QUORUM;
 use meta_secret_core::node::db::actions::vault::vault_action::ServerVaultAction;
 use meta_secret_core::node::db::descriptors::shared_secret_descriptor::SsWorkflowDescriptor;
+use meta_secret_core::node::db::descriptors::object_descriptor::ObjectDescriptor;
 use meta_secret_core::node::db::events::generic_log_event::{
-    GenericKvLogEvent, ObjIdExtractor, ToGenericEvent,
+    GenericKvLogEvent, KeyExtractor, ObjIdExtractor, ToGenericEvent,
 };
+use meta_secret_core::node::db::events::object_id::{ArtifactId, Next};
 use meta_secret_core::node::db::events::shared_secret_event::{SsLogObject, SsWorkflowObject};
 use meta_secret_core::node::db::events::vault::device_log_event::DeviceLogObject;
 use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
 use meta_secret_core::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
 use meta_secret_core::node::db::objects::persistent_vault::PersistentVault;
 use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
-use tracing::{debug, info, instrument};
+use tracing::{debug, info, instrument, warn};
+
+use meta_secret_core::node::common::clock::unix_time_millis;
+use crate::server::validation::EventValidator;
 
-#[derive(From)]
 pub struct ServerSyncGateway<Repo: KvLogEventRepo> {
     pub p_obj: Arc<PersistentObject<Repo>>,
+    /// Member approvals a device needs to join a new vault, the members change it later
+    pub join_quorum: usize,
+    /// Whether a new vault accepts invited devices only, the members change it later
+    pub require_invite: bool,
+}
+
+impl<Repo: KvLogEventRepo> From<Arc<PersistentObject<Repo>>> for ServerSyncGateway<Repo> {
+    fn from(p_obj: Arc<PersistentObject<Repo>>) -> Self {
+        Self {
+            p_obj,
+            join_quorum: DEFAULT_JOIN_QUORUM,
+            require_invite: false,
+        }
+    }
 }
 
 impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
     #[instrument(skip(self))]
-    pub async fn vault_replication(&self, request: VaultRequest) -> Result<Vec<GenericKvLogEvent>> {
-        let mut commit_log = vec![];
+    pub async fn vault_replication(&self, request: VaultRequest) -> Result<DataEventsResponse> {
+        let mut page = EventsPage::new(request.page_size);
+        let mut next_tail = request.tail.clone();
 
         let p_vault = PersistentVault::from(self.p_obj.clone());
 
@@ -38,14 +59,7 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
             .await?;
 
         //sync vault status (available to any user - just by definition)
-        {
-            let vault_status_events = self
-                .p_obj
-                .find_object_events::<GenericKvLogEvent>(request.tail.vault_status.clone())
-                .await?;
-
-            commit_log.extend(vault_status_events);
-        }
+        page.fetch(&self.p_obj, &mut next_tail.vault_status).await?;
 
         // guarding vault from sending event to outsiders
         match vault_status {
@@ -57,37 +71,42 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
             }
             VaultStatus::Member(_) => {
                 //sync VaultLog
-                {
-                    let vault_log_events = self
-                        .p_obj
-                        .find_object_events::<GenericKvLogEvent>(request.tail.vault_log.clone())
-                        .await?;
-                    commit_log.extend(vault_log_events);
-                }
-
+                page.fetch(&self.p_obj, &mut next_tail.vault_log).await?;
                 //sync Vault
-                {
-                    let vault_events = self
-                        .p_obj
-                        .find_object_events::<GenericKvLogEvent>(request.tail.vault.clone())
-                        .await?;
-                    commit_log.extend(vault_events);
+                page.fetch(&self.p_obj, &mut next_tail.vault).await?;
+                //sync AuditLog
+                if let Some(audit_log) = next_tail.audit_log.as_mut() {
+                    page.fetch(&self.p_obj, audit_log).await?;
                 }
             }
         }
 
-        Ok(commit_log)
+        Ok(page.into_response(PageCursor::Vault(next_tail)))
     }
 
     /// Handle request: all types of requests will be handled
-    /// and the actions will be executed accordingly
+    /// and the actions will be executed accordingly.
+    /// Events are validated first, an invalid event fails with an [`EventRejection`](crate::server::validation::EventRejection)
+    /// and nothing gets saved. Returns the saved event, stamped with the time it has been received at.
     pub async fn handle_write(
         &self,
         server_device: DeviceData,
-        generic_event: GenericKvLogEvent,
-    ) -> Result<()> {
-        self.server_write_processing(server_device, generic_event)
+        signed_event: &SignedEvent,
+        received_at: u64,
+    ) -> Result<GenericKvLogEvent> {
+        let validator = EventValidator::new(self.p_obj.clone());
+        let mut generic_event = validator
+            .validate(signed_event)
             .await
+            .inspect_err(|err| warn!("Event rejected: {}", err))?;
+
+        if let Some(time) = generic_event.time_mut() {
+            time.received_at = Some(received_at);
+        }
+
+        self.server_write_processing(server_device, generic_event.clone())
+            .await?;
+        Ok(generic_event)
     }
 }
 
@@ -109,9 +128,20 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
                     &ss_device_log_obj
                 );
 
-                self.p_obj.repo.save(ss_device_log_obj.clone()).await?;
+                self.p_obj.save_verified(ss_device_log_obj.clone()).await?;
 
-                let ss_claim = ss_device_log_obj.to_distribution_request();
+                let received_at = ss_device_log_obj
+                    .0
+                    .time
+                    .as_ref()
+                    .and_then(|time| time.received_at);
+
+                let mut ss_claim = ss_device_log_obj.to_distribution_request();
+                // the age of a claim is counted by the server clock only
+                ss_claim.created_at = None;
+                if let Some(time) = ss_claim.time.as_mut() {
+                    time.received_at = received_at;
+                }
 
                 let p_ss_log = PersistentSharedSecret::from(self.p_obj.clone());
                 p_ss_log.save_ss_log_event(ss_claim).await?;
@@ -137,8 +167,59 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
                             decline_data.vault_name,
                         )
                         .await?;
-                    self.p_obj.repo.save(new_ss_log_event).await?;
+                    self.p_obj.append(new_ss_log_event).await?;
+                } else if let SsWorkflowObject::Approval(approval_event) = &ss_object {
+                    let approval_data = approval_event.value.clone();
+                    let p_ss_log = PersistentSharedSecret::from(self.p_obj.clone());
+                    let maybe_ss_log_event = p_ss_log
+                        .find_ss_log_tail_event(approval_data.vault_name.clone())
+                        .await?;
+                    let Some(ss_event) = maybe_ss_log_event else {
+                        bail!("No claim found for approval: {:?}", approval_data)
+                    };
+                    let vault_name = approval_data.vault_name.clone();
+                    let new_ss_log_data = ss_event.to_data().approve(approval_data);
+                    let new_ss_log_event = p_ss_log
+                        .create_new_ss_log_object(new_ss_log_data, vault_name)
+                        .await?;
+                    self.p_obj.append(new_ss_log_event).await?;
+                } else if let SsWorkflowObject::CancelClaim(cancel_event) = &ss_object {
+                    let cancel_data = cancel_event.value.clone();
+                    let p_ss_log = PersistentSharedSecret::from(self.p_obj.clone());
+                    let maybe_ss_log_event = p_ss_log
+                        .find_ss_log_tail_event(cancel_data.vault_name.clone())
+                        .await?;
+                    let Some(ss_event) = maybe_ss_log_event else {
+                        bail!("No claim found for cancellation: {:?}", cancel_data)
+                    };
+                    // the shares released for the claim are never delivered,
+                    // the garbage collector removes them
+                    let new_ss_log_data = ss_event.to_data().cancel(&cancel_data.claim_id.id);
+                    let new_ss_log_event = p_ss_log
+                        .create_new_ss_log_object(new_ss_log_data, cancel_data.vault_name)
+                        .await?;
+                    self.p_obj.append(new_ss_log_event).await?;
+                } else if let SsWorkflowObject::Veto(veto_event) = &ss_object {
+                    let veto_data = veto_event.value.clone();
+                    let p_ss_log = PersistentSharedSecret::from(self.p_obj.clone());
+                    let maybe_ss_log_event = p_ss_log
+                        .find_ss_log_tail_event(veto_data.vault_name.clone())
+                        .await?;
+                    let Some(ss_event) = maybe_ss_log_event else {
+                        bail!("No claim found for veto: {:?}", veto_data)
+                    };
+                    // the claim stays open, the escrow just won't release its share by itself
+                    let new_ss_log_data = ss_event
+                        .to_data()
+                        .veto(&veto_data.claim_id.id, veto_data.device_id);
+                    let new_ss_log_event = p_ss_log
+                        .create_new_ss_log_object(new_ss_log_data, veto_data.vault_name)
+                        .await?;
+                    self.p_obj.append(new_ss_log_event).await?;
                 } else {
+                    // device ids don't change when the keys are rotated,
+                    // so the device is taken from the validated key of the share
+                    let device_id = share_device_id(&ss_object)?;
                     let wf = ss_object.to_distribution_data()?;
                         let p_ss_log = PersistentSharedSecret::from(self.p_obj.clone());
                         let maybe_ss_log_event = p_ss_log
@@ -158,20 +239,6 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
                                     }
                                     Some(claim) => {
                                         let distribution_type = claim.distribution_type;
-                                        let device_id = match distribution_type {
-                                            SecretDistributionType::Split => wf
-                                                .secret_message
-                                                .cipher_text()
-                                                .channel
-                                                .receiver()
-                                                .to_device_id(),
-                                            SecretDistributionType::Recover => wf
-                                                .secret_message
-                                                .cipher_text()
-                                                .channel
-                                                .sender()
-                                                .to_device_id(),
-                                        };
 
                                         let claim_id = wf.claim_id.id.clone();
                                         let new_ss_log_data =
@@ -186,7 +253,7 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
                                         let new_ss_log_event = p_ss_log
                                             .create_new_ss_log_object(new_ss_log_data, wf.vault_name)
                                             .await?;
-                                        self.p_obj.repo.save(new_ss_log_event).await?;
+                                        self.p_obj.append(new_ss_log_event).await?;
                                     }
                                 }
                             }
@@ -211,6 +278,15 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
             GenericKvLogEvent::SsLog(_) => {
                 bail!("Invalid event type: {:?}", generic_event);
             }
+            GenericKvLogEvent::AuditLog(_) => {
+                bail!("Invalid event type: {:?}", generic_event);
+            }
+            GenericKvLogEvent::AuditKey(_) => {
+                bail!("Invalid event type: {:?}", generic_event);
+            }
+            GenericKvLogEvent::JoinInvite(_) => {
+                bail!("Invalid event type: {:?}", generic_event);
+            }
             GenericKvLogEvent::DbError(_) => {
                 bail!("Invalid event type: {:?}", generic_event);
             }
@@ -225,7 +301,7 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
         server_device: DeviceData,
         device_log_obj: DeviceLogObject,
     ) -> Result<()> {
-        self.p_obj.repo.save(device_log_obj.clone()).await?;
+        self.p_obj.save_verified(device_log_obj.clone()).await?;
 
         let vault_action_event = device_log_obj.0;
         let vault_action = vault_action_event.value;
@@ -233,9 +309,13 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
         let action = ServerVaultAction {
             p_obj: self.p_obj.clone(),
             server_device,
+            join_quorum: self.join_quorum,
+            require_invite: self.require_invite,
         };
 
-        action.do_processing(vault_action).await?;
+        action
+            .do_processing(vault_action, vault_action_event.time)
+            .await?;
         Ok(())
     }
 
@@ -243,20 +323,35 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
         &self,
         request: SsRequest,
         server_device: DeviceId,
-    ) -> Result<Vec<GenericKvLogEvent>> {
+    ) -> Result<DataEventsResponse> {
         //sync SsLog
-        let ss_log_events = self
+        let page_size = request.page_size.clamp(1, MAX_PAGE_SIZE);
+        let mut ss_log_events = self
             .p_obj
-            .find_object_events::<SsLogObject>(request.ss_log.clone())
+            .find_object_events_page::<SsLogObject>(request.ss_log.clone(), page_size + 1)
             .await?;
 
         debug!(
             ss_log_events_count = ss_log_events.len(),
             "ss_replication: events from find_object_events for request.ss_log"
         );
+
+        // The client is behind: send the ss log page by page,
+        // shares get delivered only when the client has the latest ss log state
+        if ss_log_events.len() > page_size {
+            ss_log_events.truncate(page_size);
+            let next_ss_log = ss_log_events[page_size - 1].obj_id().next();
+            let events = ss_log_events.into_iter().map(|event| event.to_generic()).collect();
+
+            return Ok(DataEventsResponse {
+                events,
+                next_page: Some(PageCursor::SsLog(next_ss_log)),
+            });
+        }
+
         let maybe_latest_ss_log_state = ss_log_events.last();
         let Some(latest_ss_log_state) = maybe_latest_ss_log_state else {
-            return Ok(vec![]);
+            return Ok(DataEventsResponse::from(vec![]));
         };
 
         let mut commit_log = vec![];
@@ -268,6 +363,14 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
         let mut updated_ss_log_data = ss_log_data.clone();
         let mut updated_state = false;
 
+        // recovery claims nobody has answered in time are passed to the fallback receivers
+        let now = unix_time_millis();
+        for claim in updated_ss_log_data.claims.values_mut() {
+            if claim.fall_back(now) {
+                updated_state = true;
+            }
+        }
+
         for (_, claim) in ss_log_data.claims.iter() {
             // Distribute shares
             for dist_id in claim.recovery_db_ids() {
@@ -331,17 +434,93 @@ impl<Repo: KvLogEventRepo> ServerSyncGateway<Repo> {
             let new_ss_log_obj = p_ss
                 .create_new_ss_log_object(updated_ss_log_data, request.sender.vault_name.clone())
                 .await?;
-            self.p_obj
-                .repo
-                .save(new_ss_log_obj.clone().to_generic())
-                .await?;
-            commit_log.push(new_ss_log_obj.to_generic());
+            let new_ss_log_event = self.p_obj.append(new_ss_log_obj).await?;
+            commit_log.push(new_ss_log_event);
         }
 
         debug!(
             commit_log_len = commit_log.len(),
             "ss_replication: returning commit_log to client"
         );
-        Ok(commit_log)
+        Ok(DataEventsResponse::from(commit_log))
+    }
+}
+
+/// The device that holds a share: the receiver of a split share, or the sender of a recovery share
+pub(crate) fn share_device_id(ss_object: &SsWorkflowObject) -> Result<DeviceId> {
+    match ss_object.key().obj_desc {
+        ObjectDescriptor::SharedSecret(SsWorkflowDescriptor::Distribution(dist_id)) => {
+            Ok(dist_id.receiver)
+        }
+        ObjectDescriptor::SharedSecret(SsWorkflowDescriptor::Recovery(recovery_id)) => {
+            Ok(recovery_id.distribution_id.receiver)
+        }
+        _ => bail!("Not a share: {:?}", ss_object.key()),
+    }
+}
+
+/// The device a share is delivered to: the receiver of a split share, or the claimant of a recovery share
+pub(crate) fn share_receiver_id(ss_object: &SsWorkflowObject) -> Result<DeviceId> {
+    match ss_object.key().obj_desc {
+        ObjectDescriptor::SharedSecret(SsWorkflowDescriptor::Distribution(dist_id)) => {
+            Ok(dist_id.receiver)
+        }
+        ObjectDescriptor::SharedSecret(SsWorkflowDescriptor::Recovery(recovery_id)) => {
+            Ok(recovery_id.sender)
+        }
+        _ => bail!("Not a share: {:?}", ss_object.key()),
+    }
+}
+
+/// Collects events of several objects into a single replication page of a bounded size
+struct EventsPage {
+    events: Vec<GenericKvLogEvent>,
+    page_size: usize,
+    has_more: bool,
+}
+
+impl EventsPage {
+    fn new(page_size: usize) -> Self {
+        Self {
+            events: vec![],
+            page_size: page_size.clamp(1, MAX_PAGE_SIZE),
+            has_more: false,
+        }
+    }
+
+    /// Append object events starting from the tail and move the tail after the last taken event
+    async fn fetch<Repo: KvLogEventRepo>(
+        &mut self,
+        p_obj: &PersistentObject<Repo>,
+        tail: &mut ArtifactId,
+    ) -> Result<()> {
+        if self.has_more {
+            return Ok(());
+        }
+
+        let limit = self.page_size - self.events.len();
+        // one extra event tells if there is anything left for the next page
+        let mut events = p_obj
+            .find_object_events_page::<GenericKvLogEvent>(tail.clone(), limit + 1)
+            .await?;
+
+        if events.len() > limit {
+            events.truncate(limit);
+            self.has_more = true;
+        }
+
+        if let Some(last_event) = events.last() {
+            *tail = last_event.obj_id().next();
+        }
+
+        self.events.extend(events);
+        Ok(())
+    }
+
+    fn into_response(self, cursor: PageCursor) -> DataEventsResponse {
+        DataEventsResponse {
+            events: self.events,
+            next_page: self.has_more.then_some(cursor),
+        }
     }
 }
@@ -0,0 +1,747 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use meta_secret_core::crypto::keys::DsaPk;
+use meta_secret_core::node::api::SignedEvent;
+use meta_secret_core::node::common::model::device::common::DeviceId;
+use meta_secret_core::node::common::model::meta_pass::MetaPasswordId;
+use meta_secret_core::node::common::model::secret::{
+    ClaimId, SecretDistributionData, SecretDistributionType, SsApprovalData, SsClaim,
+};
+use meta_secret_core::node::common::model::user::common::{UserData, UserMembership};
+use meta_secret_core::node::common::model::vault::vault::VaultName;
+use meta_secret_core::node::common::model::vault::vault_data::VaultData;
+use meta_secret_core::node::common::model::IdString;
+use meta_secret_core::node::db::descriptors::object_descriptor::{
+    ObjectDescriptor, ObjectType, ToObjectDescriptor,
+};
+use meta_secret_core::node::db::descriptors::shared_secret_descriptor::{
+    SsDeviceLogDescriptor, SsWorkflowDescriptor,
+};
+use meta_secret_core::node::db::descriptors::vault_descriptor::{
+    DeviceLogDescriptor, VaultDescriptor,
+};
+use meta_secret_core::node::db::events::generic_log_event::{GenericKvLogEvent, KeyExtractor};
+use meta_secret_core::node::db::events::kv_log_event::KvKey;
+use meta_secret_core::node::db::events::shared_secret_event::{
+    SsDeviceLogObject, SsWorkflowObject,
+};
+use meta_secret_core::node::db::events::vault::device_log_event::DeviceLogObject;
+use meta_secret_core::node::db::events::vault::vault_log_event::{
+    AddInviteEvent, JoinClusterEvent, RotateDeviceKeysEvent, VaultActionEvent,
+    VaultActionInitEvent, VaultActionRequestEvent, VaultActionUpdateEvent,
+};
+use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
+use meta_secret_core::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
+use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
+use thiserror::Error;
+
+use meta_secret_core::node::common::clock::unix_time_millis;
+
+/// Max size of a serialized event a client can write
+pub const MAX_EVENT_SIZE: usize = 64 * 1024;
+
+/// Reasons the server refuses to save an event sent by a client
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum EventRejection {
+    #[error("Event is too large: {size} bytes, the limit is {limit}")]
+    TooLarge { size: usize, limit: usize },
+    #[error("Event is not signed by its author {device_id}")]
+    InvalidSignature { device_id: DeviceId },
+    #[error("Clients can't write {0} events")]
+    ForbiddenObject(String),
+    #[error("Only the server creates {0} updates")]
+    ServerOnlyAction(String),
+    #[error("Event is keyed to a log of another device: expected {expected}, actual {actual}")]
+    ForeignLog { expected: String, actual: String },
+    #[error("Event id is not the next free id of the log: expected {expected}, actual {actual}")]
+    UnexpectedId { expected: String, actual: String },
+    #[error("Event belongs to vault {event_vault}, but its author is from vault {author_vault}")]
+    VaultMismatch {
+        event_vault: VaultName,
+        author_vault: VaultName,
+    },
+    #[error("Vault not found: {0}")]
+    UnknownVault(VaultName),
+    #[error("Device {device_id} is not a member of vault {vault_name}")]
+    NotVaultMember {
+        vault_name: VaultName,
+        device_id: DeviceId,
+    },
+    #[error("No open claim {claim_id} in vault {vault_name}")]
+    UnknownClaim {
+        vault_name: VaultName,
+        claim_id: String,
+    },
+    #[error("Claim {claim_id} in vault {vault_name} has expired")]
+    ExpiredClaim {
+        vault_name: VaultName,
+        claim_id: String,
+    },
+    #[error("Invalid claim: {0}")]
+    InvalidClaim(String),
+    #[error("Workflow event key doesn't match its claim: {0}")]
+    ForeignWorkflowKey(String),
+    #[error("Name of the password {0} must be sealed to the vault members")]
+    ClearTextName(String),
+    #[error("Invalid key rotation: {0}")]
+    InvalidKeyRotation(String),
+    #[error("Vault {0} accepts invited devices only")]
+    InviteRequired(VaultName),
+    #[error("Invalid invite: {0}")]
+    InvalidInvite(String),
+    #[error("Invalid vault settings: {0}")]
+    InvalidSettings(String),
+    #[error("Claim {claim_id} has {actual} of {required} approvals its recovery policy requires")]
+    MissingApprovals {
+        claim_id: String,
+        required: usize,
+        actual: usize,
+    },
+}
+
+/// Checks events written by clients before the server saves them.
+/// The author of an event is the device the event names (the sender of a claim, the approver...),
+/// the event has to be signed with the key the vault holds for that device, a device that is
+/// not known to the vault yet signs with the keys its device id is derived from.
+/// The validator also makes sure that an event is consistent with its author: it can only be
+/// appended to the author's own log, and it can only reference the vault and the claims
+/// the author takes part in.
+pub struct EventValidator<Repo: KvLogEventRepo> {
+    p_obj: Arc<PersistentObject<Repo>>,
+}
+
+impl<Repo: KvLogEventRepo> EventValidator<Repo> {
+    pub fn new(p_obj: Arc<PersistentObject<Repo>>) -> Self {
+        Self { p_obj }
+    }
+
+    /// Returns the signed event, fails with an [`EventRejection`] if the event must not be saved
+    pub async fn validate(&self, signed_event: &SignedEvent) -> Result<GenericKvLogEvent> {
+        let size = signed_event.size();
+        if size > MAX_EVENT_SIZE {
+            bail!(EventRejection::TooLarge {
+                size,
+                limit: MAX_EVENT_SIZE,
+            });
+        }
+
+        let event = signed_event.event()?;
+        match &event {
+            GenericKvLogEvent::DeviceLog(device_log) => {
+                self.validate_device_log(device_log, signed_event).await?
+            }
+            GenericKvLogEvent::SsDeviceLog(ss_device_log) => {
+                self.validate_ss_device_log(ss_device_log, signed_event)
+                    .await?
+            }
+            GenericKvLogEvent::SsWorkflow(ss_workflow) => {
+                self.validate_ss_workflow(ss_workflow, signed_event).await?
+            }
+            _ => bail!(EventRejection::ForbiddenObject(
+                event.key().obj_desc.object_type()
+            )),
+        }
+
+        Ok(event)
+    }
+
+    async fn validate_device_log(
+        &self,
+        device_log: &DeviceLogObject,
+        signed_event: &SignedEvent,
+    ) -> Result<()> {
+        let action = &device_log.0.value;
+
+        let author = match action {
+            VaultActionEvent::Init(VaultActionInitEvent::CreateVault(create)) => {
+                &create.owner.user_data
+            }
+            VaultActionEvent::Request(VaultActionRequestEvent::JoinCluster(join)) => {
+                &join.candidate
+            }
+            VaultActionEvent::Request(VaultActionRequestEvent::AddMetaPass(add_pass)) => {
+                check_opaque(&add_pass.meta_pass_id)?;
+                &add_pass.sender.user_data
+            }
+            VaultActionEvent::Request(VaultActionRequestEvent::RotateDeviceKeys(rotation)) => {
+                &rotation.member.user_data
+            }
+            VaultActionEvent::Request(VaultActionRequestEvent::AddInvite(add_invite)) => {
+                &add_invite.sender.user_data
+            }
+            VaultActionEvent::Request(VaultActionRequestEvent::UpdateSettings(settings)) => {
+                if settings.join_quorum == Some(0) {
+                    bail!(EventRejection::InvalidSettings(String::from(
+                        "the join quorum must be positive"
+                    )));
+                }
+                &settings.sender.user_data
+            }
+            VaultActionEvent::Update(VaultActionUpdateEvent::UpdateMembership(update)) => {
+                &update.sender.user_data
+            }
+            VaultActionEvent::Update(update) => {
+                bail!(EventRejection::ServerOnlyAction(update.name()))
+            }
+        };
+
+        let event_vault = action.vault_name();
+        if event_vault != author.vault_name {
+            bail!(EventRejection::VaultMismatch {
+                event_vault,
+                author_vault: author.vault_name(),
+            });
+        }
+
+        let log_desc = DeviceLogDescriptor::from(author.user_id());
+        self.check_log_key(device_log.key(), log_desc).await?;
+
+        match action {
+            VaultActionEvent::Init(_) => {
+                let maybe_vault = self.find_vault(&event_vault).await?;
+                check_author_signature(signed_event, maybe_vault.as_ref(), author)?;
+            }
+            VaultActionEvent::Request(VaultActionRequestEvent::JoinCluster(join)) => {
+                let vault = self.get_vault(&event_vault).await?;
+                check_author_signature(signed_event, Some(&vault), author)?;
+                check_join_invite(&vault, join)?;
+            }
+            VaultActionEvent::Request(VaultActionRequestEvent::RotateDeviceKeys(rotation)) => {
+                let vault = self.get_vault(&event_vault).await?;
+                check_member(&vault, author)?;
+                // the device may have switched to the new keys before the rotation is sent
+                let is_new_key = signed_event.verify(&rotation.new_keys.dsa_pk).is_ok();
+                if !is_new_key {
+                    check_signature(signed_event, &vault, &author.device.device_id)?;
+                }
+                check_rotation(&vault, rotation)?;
+            }
+            VaultActionEvent::Request(VaultActionRequestEvent::AddInvite(add_invite)) => {
+                let vault = self.get_vault(&event_vault).await?;
+                check_member(&vault, author)?;
+                check_signature(signed_event, &vault, &author.device.device_id)?;
+                check_add_invite(&vault, add_invite)?;
+            }
+            VaultActionEvent::Request(_) | VaultActionEvent::Update(_) => {
+                let vault = self.get_vault(&event_vault).await?;
+                check_member(&vault, author)?;
+                check_signature(signed_event, &vault, &author.device.device_id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn validate_ss_device_log(
+        &self,
+        ss_device_log: &SsDeviceLogObject,
+        signed_event: &SignedEvent,
+    ) -> Result<()> {
+        let claim = &ss_device_log.0.value;
+        check_opaque(&claim.dist_claim_id.pass_id)?;
+
+        let log_desc = SsDeviceLogDescriptor::from(claim.sender.clone());
+        self.check_log_key(ss_device_log.key(), log_desc).await?;
+
+        if claim.id != claim.dist_claim_id.id {
+            bail!(EventRejection::InvalidClaim(format!(
+                "claim id {} differs from the distribution claim id {}",
+                claim.id.0.clone().id_str(),
+                claim.dist_claim_id.id.0.clone().id_str()
+            )));
+        }
+
+        let fallback_receivers = claim
+            .fallback
+            .as_ref()
+            .map(|fallback| fallback.receivers.as_slice())
+            .unwrap_or_default();
+
+        if claim.receivers.contains(&claim.sender) || fallback_receivers.contains(&claim.sender) {
+            bail!(EventRejection::InvalidClaim(String::from(
+                "the sender can't be a receiver of its own claim"
+            )));
+        }
+
+        if !claim.approvals.is_empty() {
+            bail!(EventRejection::InvalidClaim(String::from(
+                "a new claim can't have approvals"
+            )));
+        }
+
+        if claim.fallback.is_some() && claim.distribution_type != SecretDistributionType::Recover {
+            bail!(EventRejection::InvalidClaim(String::from(
+                "only recovery claims can have fallback receivers"
+            )));
+        }
+
+        let vault = self.get_vault(&claim.vault_name).await?;
+        let devices = claim.receivers.iter().chain(fallback_receivers);
+        for device_id in std::iter::once(&claim.sender).chain(devices) {
+            if !vault.is_member(device_id) {
+                bail!(EventRejection::NotVaultMember {
+                    vault_name: claim.vault_name.clone(),
+                    device_id: device_id.clone(),
+                });
+            }
+        }
+
+        check_signature(signed_event, &vault, &claim.sender)?;
+        check_break_glass(&vault, claim)?;
+
+        Ok(())
+    }
+
+    async fn validate_ss_workflow(
+        &self,
+        ss_workflow: &SsWorkflowObject,
+        signed_event: &SignedEvent,
+    ) -> Result<()> {
+        let key = ss_workflow.key();
+
+        if let ObjectDescriptor::SharedSecret(desc) = &key.obj_desc {
+            for pass_id in workflow_pass_ids(desc) {
+                check_opaque(pass_id)?;
+            }
+        }
+
+        match ss_workflow {
+            SsWorkflowObject::Distribution(event) => {
+                check_opaque(&event.value.claim_id.pass_id)?;
+                let claim = self.find_claim(&event.value).await?;
+                let ObjectDescriptor::SharedSecret(SsWorkflowDescriptor::Distribution(dist_id)) =
+                    key.obj_desc.clone()
+                else {
+                    bail!(EventRejection::ForeignWorkflowKey(key.obj_id.id_str()));
+                };
+
+                // the sender redistributes shares when a new device joins,
+                // so a receiver of a split share can be any member of the vault
+                let vault = self.get_vault(&claim.vault_name).await?;
+                let receiver = share_device(&vault, &event.value, claim.distribution_type)?;
+                let is_valid = claim.distribution_type == SecretDistributionType::Split
+                    && dist_id.pass_id == claim.dist_claim_id.pass_id
+                    && dist_id.receiver == receiver
+                    && vault.is_member(&receiver)
+                    && key == KvKey::from(SsWorkflowDescriptor::Distribution(dist_id));
+                if !is_valid {
+                    bail!(EventRejection::ForeignWorkflowKey(key.obj_id.id_str()));
+                }
+
+                // split shares are sent by the sender of the claim
+                check_signature(signed_event, &vault, &claim.sender)?;
+            }
+            SsWorkflowObject::Recovery(event) => {
+                check_opaque(&event.value.claim_id.pass_id)?;
+                let claim = self.find_claim(&event.value).await?;
+                if claim.is_expired(unix_time_millis()) {
+                    bail!(EventRejection::ExpiredClaim {
+                        vault_name: claim.vault_name.clone(),
+                        claim_id: claim.id.0.clone().id_str(),
+                    });
+                }
+
+                let vault = self.get_vault(&claim.vault_name).await?;
+                let sender = share_device(&vault, &event.value, claim.distribution_type)?;
+
+                let is_valid = claim.distribution_type == SecretDistributionType::Recover
+                    && claim.recovery_db_ids().into_iter().any(|recovery_id| {
+                        recovery_id.distribution_id.receiver == sender
+                            && key == KvKey::from(SsWorkflowDescriptor::Recovery(recovery_id))
+                    });
+                if !is_valid {
+                    bail!(EventRejection::ForeignWorkflowKey(key.obj_id.id_str()));
+                }
+                check_signature(signed_event, &vault, &sender)?;
+
+                // nobody has stopped the escrow in time, it releases its share without approvals
+                let is_break_glass = claim
+                    .break_glass
+                    .as_ref()
+                    .is_some_and(|break_glass| break_glass.escrow == sender)
+                    && claim.is_break_glass_due(unix_time_millis());
+
+                // the share holders enforce the policy, the server doesn't trust them to
+                if let Some(policy) = vault
+                    .recovery_policy(&claim.dist_claim_id.pass_id)
+                    .filter(|_| !is_break_glass)
+                {
+                    let actual = policy.approvals_of(&claim, &vault);
+                    if actual < policy.approvals {
+                        bail!(EventRejection::MissingApprovals {
+                            claim_id: claim.id.0.clone().id_str(),
+                            required: policy.approvals,
+                            actual,
+                        });
+                    }
+                }
+            }
+            SsWorkflowObject::Decline(event) => {
+                let decline = &event.value;
+                let claim = self
+                    .find_open_claim(&decline.vault_name, &decline.claim_id)
+                    .await?;
+
+                let is_valid = claim.recovery_db_ids().into_iter().any(|recovery_id| {
+                    recovery_id.distribution_id.receiver == decline.receiver_id
+                        && key == KvKey::from(SsWorkflowDescriptor::Decline(recovery_id))
+                });
+                if !is_valid {
+                    bail!(EventRejection::ForeignWorkflowKey(key.obj_id.id_str()));
+                }
+
+                let vault = self.get_vault(&claim.vault_name).await?;
+                check_signature(signed_event, &vault, &decline.receiver_id)?;
+            }
+            SsWorkflowObject::Approval(event) => {
+                let approval = &event.value;
+                let claim = self
+                    .find_open_claim(&approval.vault_name, &approval.claim_id)
+                    .await?;
+                if claim.is_expired(unix_time_millis()) {
+                    bail!(EventRejection::ExpiredClaim {
+                        vault_name: claim.vault_name.clone(),
+                        claim_id: claim.id.0.clone().id_str(),
+                    });
+                }
+
+                let is_valid = claim.distribution_type == SecretDistributionType::Recover
+                    && claim.recovery_db_ids().into_iter().any(|recovery_id| {
+                        recovery_id.distribution_id.receiver == approval.receiver_id
+                            && key == KvKey::from(SsWorkflowDescriptor::Approval(recovery_id))
+                    });
+                if !is_valid {
+                    bail!(EventRejection::ForeignWorkflowKey(key.obj_id.id_str()));
+                }
+
+                let vault = self.get_vault(&claim.vault_name).await?;
+                check_signature(signed_event, &vault, &approval.receiver_id)?;
+                check_approval(&vault, &claim, approval)?;
+            }
+            SsWorkflowObject::CancelClaim(event) => {
+                let cancel = &event.value;
+                check_opaque(&cancel.claim_id.pass_id)?;
+                let claim = self
+                    .find_open_claim(&cancel.vault_name, &cancel.claim_id.id)
+                    .await?;
+
+                // only the sender can withdraw its own recovery claim
+                let is_valid = claim.distribution_type == SecretDistributionType::Recover
+                    && claim.sender == cancel.sender
+                    && claim.dist_claim_id == cancel.claim_id
+                    && key == KvKey::from(SsWorkflowDescriptor::CancelClaim(claim.dist_claim_id));
+                if !is_valid {
+                    bail!(EventRejection::ForeignWorkflowKey(key.obj_id.id_str()));
+                }
+
+                let vault = self.get_vault(&claim.vault_name).await?;
+                check_signature(signed_event, &vault, &cancel.sender)?;
+            }
+            SsWorkflowObject::Veto(event) => {
+                let veto = &event.value;
+                check_opaque(&veto.claim_id.pass_id)?;
+                let claim = self
+                    .find_open_claim(&veto.vault_name, &veto.claim_id.id)
+                    .await?;
+
+                let vault = self.get_vault(&claim.vault_name).await?;
+                if !vault.is_member(&veto.device_id) {
+                    bail!(EventRejection::NotVaultMember {
+                        vault_name: claim.vault_name.clone(),
+                        device_id: veto.device_id.clone(),
+                    });
+                }
+
+                // any member but the sender can stop a break-glass recovery
+                let is_valid = claim.distribution_type == SecretDistributionType::Recover
+                    && claim.break_glass.is_some()
+                    && claim.sender != veto.device_id
+                    && claim.dist_claim_id == veto.claim_id
+                    && key == KvKey::from(SsWorkflowDescriptor::Veto(claim.dist_claim_id));
+                if !is_valid {
+                    bail!(EventRejection::ForeignWorkflowKey(key.obj_id.id_str()));
+                }
+                check_signature(signed_event, &vault, &veto.device_id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// An event has to be appended to the given log right after its tail
+    async fn check_log_key<Desc: ToObjectDescriptor>(
+        &self,
+        key: KvKey,
+        log_desc: Desc,
+    ) -> Result<()> {
+        let expected_desc = log_desc.clone().to_obj_desc();
+        if key.obj_desc != expected_desc {
+            bail!(EventRejection::ForeignLog {
+                expected: expected_desc.fqdn().id_str(),
+                actual: key.obj_desc.fqdn().id_str(),
+            });
+        }
+
+        let free_id = self.p_obj.find_free_id_by_obj_desc(log_desc).await?;
+        if key.obj_id != free_id {
+            bail!(EventRejection::UnexpectedId {
+                expected: free_id.id_str(),
+                actual: key.obj_id.id_str(),
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn get_vault(&self, vault_name: &VaultName) -> Result<VaultData> {
+        let Some(vault) = self.find_vault(vault_name).await? else {
+            bail!(EventRejection::UnknownVault(vault_name.clone()));
+        };
+        Ok(vault)
+    }
+
+    async fn find_vault(&self, vault_name: &VaultName) -> Result<Option<VaultData>> {
+        let maybe_vault = self
+            .p_obj
+            .find_tail_event(VaultDescriptor::from(vault_name.clone()))
+            .await?;
+        Ok(maybe_vault.map(|vault| vault.to_data()))
+    }
+
+    async fn find_claim(&self, distribution: &SecretDistributionData) -> Result<SsClaim> {
+        let claim = self
+            .find_open_claim(&distribution.vault_name, &distribution.claim_id.id)
+            .await?;
+
+        if claim.dist_claim_id != distribution.claim_id {
+            bail!(EventRejection::InvalidClaim(format!(
+                "password of the share doesn't match the claim {}",
+                claim.id.0.clone().id_str()
+            )));
+        }
+        Ok(claim)
+    }
+
+    async fn find_open_claim(&self, vault_name: &VaultName, claim_id: &ClaimId) -> Result<SsClaim> {
+        let p_ss = PersistentSharedSecret::from(self.p_obj.clone());
+        let maybe_ss_log = p_ss.find_ss_log_tail_event(vault_name.clone()).await?;
+
+        let maybe_claim = maybe_ss_log.and_then(|ss_log| ss_log.to_data().claims.remove(claim_id));
+        let Some(claim) = maybe_claim else {
+            bail!(EventRejection::UnknownClaim {
+                vault_name: vault_name.clone(),
+                claim_id: claim_id.0.clone().id_str(),
+            });
+        };
+        Ok(claim)
+    }
+}
+
+fn check_member(vault: &VaultData, user: &UserData) -> Result<()> {
+    if !vault.is_member(&user.device.device_id) {
+        bail!(EventRejection::NotVaultMember {
+            vault_name: vault.vault_name.clone(),
+            device_id: user.device.device_id.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// The event has to be signed with the key the vault holds for its author
+fn check_signature(signed_event: &SignedEvent, vault: &VaultData, author: &DeviceId) -> Result<()> {
+    let Some(membership) = vault.find_user(author) else {
+        bail!(EventRejection::NotVaultMember {
+            vault_name: vault.vault_name.clone(),
+            device_id: author.clone(),
+        });
+    };
+
+    verify_author(signed_event, &membership.user_data().device.keys.dsa_pk, author)
+}
+
+/// A device known to the vault signs with the key the vault holds for it. A new device
+/// (the owner of a new vault, a candidate) signs with its own key, the device id is derived
+/// from the keys, so another device can't present itself as that device.
+fn check_author_signature(
+    signed_event: &SignedEvent,
+    maybe_vault: Option<&VaultData>,
+    author: &UserData,
+) -> Result<()> {
+    let device_id = &author.device.device_id;
+    if let Some(vault) = maybe_vault.filter(|vault| vault.find_user(device_id).is_some()) {
+        return check_signature(signed_event, vault, device_id);
+    }
+
+    let keys = &author.device.keys;
+    if keys.transport_pk().to_device_id() != *device_id {
+        bail!(EventRejection::InvalidSignature {
+            device_id: device_id.clone(),
+        });
+    }
+    verify_author(signed_event, &keys.dsa_pk, device_id)
+}
+
+fn verify_author(signed_event: &SignedEvent, dsa_pk: &DsaPk, author: &DeviceId) -> Result<()> {
+    if signed_event.verify(dsa_pk).is_err() {
+        bail!(EventRejection::InvalidSignature {
+            device_id: author.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// New keys must be signed with the keys the vault currently knows for the device
+fn check_rotation(vault: &VaultData, rotation: &RotateDeviceKeysEvent) -> Result<()> {
+    let device_id = &rotation.member.user().device.device_id;
+    let is_current_member = matches!(
+        vault.find_user(device_id),
+        Some(UserMembership::Member(member)) if *member == rotation.member
+    );
+    if !is_current_member {
+        bail!(EventRejection::InvalidKeyRotation(format!(
+            "the keys of device {} have already been changed",
+            device_id
+        )));
+    }
+
+    if let Err(err) = rotation.verify() {
+        bail!(EventRejection::InvalidKeyRotation(err.to_string()));
+    }
+    Ok(())
+}
+
+/// The approval itself is signed by the approver, share holders check the signature too
+fn check_approval(vault: &VaultData, claim: &SsClaim, approval: &SsApprovalData) -> Result<()> {
+    let Some(membership) = vault.find_user(&approval.receiver_id) else {
+        bail!(EventRejection::NotVaultMember {
+            vault_name: vault.vault_name.clone(),
+            device_id: approval.receiver_id.clone(),
+        });
+    };
+
+    let dsa_pk = &membership.user_data().device.keys.dsa_pk;
+    if approval.verify(claim, dsa_pk).is_err() {
+        bail!(EventRejection::InvalidSignature {
+            device_id: approval.receiver_id.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// A join request has to present a valid invite when the vault requires one,
+/// an invite presented anyway is checked too
+fn check_join_invite(vault: &VaultData, join: &JoinClusterEvent) -> Result<()> {
+    let Some(ticket) = &join.invite else {
+        if vault.require_invite {
+            bail!(EventRejection::InviteRequired(vault.vault_name.clone()));
+        }
+        return Ok(());
+    };
+
+    let candidate = &join.candidate.device.device_id;
+    if let Err(err) = vault.check_invite(ticket, candidate, unix_time_millis()) {
+        bail!(EventRejection::InvalidInvite(err.to_string()));
+    }
+    Ok(())
+}
+
+/// A member invites on behalf of its own device with its current keys
+fn check_add_invite(vault: &VaultData, add_invite: &AddInviteEvent) -> Result<()> {
+    let sender = add_invite.sender.user();
+    let invite = &add_invite.invite;
+
+    let is_current_member = matches!(
+        vault.find_user(&sender.device.device_id),
+        Some(UserMembership::Member(member)) if *member == add_invite.sender
+    );
+    let is_valid = is_current_member
+        && invite.created_by == sender.device.device_id
+        && invite.key_fingerprint == sender.device.keys.fingerprint()
+        && invite.used_by.is_none()
+        && !invite.is_expired(unix_time_millis());
+    if !is_valid {
+        bail!(EventRejection::InvalidInvite(String::from(
+            "the invite doesn't match the device of the sender"
+        )));
+    }
+    Ok(())
+}
+
+/// A break-glass claim has to follow the policy of its secret: the escrow and the delay
+/// are taken from the vault, a client can't shorten the delay or pick another escrow
+fn check_break_glass(vault: &VaultData, claim: &SsClaim) -> Result<()> {
+    let Some(break_glass) = &claim.break_glass else {
+        return Ok(());
+    };
+
+    if claim.distribution_type != SecretDistributionType::Recover {
+        bail!(EventRejection::InvalidClaim(String::from(
+            "only recovery claims can be break-glass claims"
+        )));
+    }
+
+    let Some(policy) = vault.break_glass_policy(&claim.dist_claim_id.pass_id) else {
+        bail!(EventRejection::InvalidClaim(String::from(
+            "the secret has no break-glass policy"
+        )));
+    };
+
+    let is_valid = break_glass.vetoed_by.is_none()
+        && break_glass.follows(policy)
+        && claim.receivers.contains(&break_glass.escrow);
+    if !is_valid {
+        bail!(EventRejection::InvalidClaim(String::from(
+            "the break-glass claim doesn't match the policy of the secret"
+        )));
+    }
+    Ok(())
+}
+
+/// Events stored on the server reference passwords by id, the names are sealed
+fn check_opaque(pass_id: &MetaPasswordId) -> Result<()> {
+    if pass_id.has_clear_name() {
+        bail!(EventRejection::ClearTextName(pass_id.id_str()));
+    }
+    Ok(())
+}
+
+fn workflow_pass_ids(desc: &SsWorkflowDescriptor) -> Vec<&MetaPasswordId> {
+    match desc {
+        SsWorkflowDescriptor::Distribution(dist_id) => vec![&dist_id.pass_id],
+        SsWorkflowDescriptor::Recovery(recovery_id)
+        | SsWorkflowDescriptor::Decline(recovery_id)
+        | SsWorkflowDescriptor::Approval(recovery_id) => {
+            vec![
+                &recovery_id.claim_id.pass_id,
+                &recovery_id.distribution_id.pass_id,
+            ]
+        }
+        SsWorkflowDescriptor::CancelClaim(claim_id) | SsWorkflowDescriptor::Veto(claim_id) => {
+            vec![&claim_id.pass_id]
+        }
+    }
+}
+
+/// The device that holds the share of a workflow event besides the claim sender:
+/// the receiver of a split share, or the sender of a recovery share.
+/// Devices are looked up by the current keys of the vault members, the keys can be rotated
+fn share_device(
+    vault: &VaultData,
+    share: &SecretDistributionData,
+    distribution_type: SecretDistributionType,
+) -> Result<DeviceId> {
+    let channel = &share.secret_message.cipher_text().channel;
+    let share_pk = match distribution_type {
+        SecretDistributionType::Split => channel.receiver(),
+        SecretDistributionType::Recover => channel.sender(),
+    };
+
+    let Some(member) = vault.find_member_by_key(share_pk) else {
+        bail!(EventRejection::NotVaultMember {
+            vault_name: vault.vault_name.clone(),
+            device_id: share_pk.to_device_id(),
+        });
+    };
+    Ok(member.user().device.device_id.clone())
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use flume::{Receiver, Sender, TrySendError};
+use meta_secret_core::node::api::{
+    DataSyncResponse, ReadSyncRequest, SyncRequest, WriteSyncRequest,
+};
+use meta_secret_core::node::common::data_transfer::MpscEnvelope;
+use meta_secret_core::node::common::model::vault::vault::VaultName;
+use meta_secret_core::node::db::events::generic_log_event::GenericKvLogEvent;
+use meta_secret_core::node::db::events::shared_secret_event::SsWorkflowObject;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// Pending requests of a single vault, the vault is rejected as busy when the queue is full
+pub const VAULT_QUEUE_CAPACITY: usize = 64;
+/// A vault worker is stopped when it has nothing to do for this long
+pub const VAULT_WORKER_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A client request together with the channel its response must be delivered to
+pub type ServerEnvelope = MpscEnvelope<SyncRequest, DataSyncResponse>;
+
+/// Where the server has to process a request
+pub enum Dispatch {
+    /// The request is queued to a running vault worker
+    Queued,
+    /// A worker for the vault has to be started, the request is already in its queue
+    NewWorker {
+        vault_name: VaultName,
+        queue: Receiver<ServerEnvelope>,
+    },
+    /// The request doesn't belong to any vault and can be processed right away
+    Unrouted(ServerEnvelope),
+    /// The vault has too many pending requests
+    Busy {
+        vault_name: VaultName,
+        envelope: ServerEnvelope,
+    },
+}
+
+/// Keeps one request queue per vault. Requests of the same vault are processed in order
+/// by a single worker, requests of different vaults are processed concurrently.
+pub struct VaultRouter {
+    queues: HashMap<VaultName, VaultQueue>,
+    idle_timeout: Duration,
+}
+
+struct VaultQueue {
+    sender: Sender<ServerEnvelope>,
+    last_used: Instant,
+}
+
+impl Default for VaultRouter {
+    fn default() -> Self {
+        Self::new(VAULT_WORKER_IDLE_TIMEOUT)
+    }
+}
+
+impl VaultRouter {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            queues: HashMap::new(),
+            idle_timeout,
+        }
+    }
+
+    pub fn dispatch(&mut self, envelope: ServerEnvelope) -> Dispatch {
+        self.release_idle_workers();
+
+        let Some(vault_name) = request_vault(&envelope.request) else {
+            return Dispatch::Unrouted(envelope);
+        };
+
+        if let Some(queue) = self.queues.get_mut(&vault_name) {
+            queue.last_used = Instant::now();
+            return match queue.sender.try_send(envelope) {
+                Ok(()) => Dispatch::Queued,
+                Err(TrySendError::Full(envelope)) => Dispatch::Busy {
+                    vault_name,
+                    envelope,
+                },
+                Err(TrySendError::Disconnected(envelope)) => {
+                    // the worker is gone, start a new one
+                    self.queues.remove(&vault_name);
+                    self.start_worker(vault_name, envelope)
+                }
+            };
+        }
+
+        self.start_worker(vault_name, envelope)
+    }
+
+    pub fn active_vaults(&self) -> usize {
+        self.queues.len()
+    }
+
+    fn start_worker(&mut self, vault_name: VaultName, envelope: ServerEnvelope) -> Dispatch {
+        let (sender, queue) = flume::bounded(VAULT_QUEUE_CAPACITY);
+        // the queue is empty and we hold the receiver, so sending can't fail
+        let _ = sender.try_send(envelope);
+
+        let vault_queue = VaultQueue {
+            sender,
+            last_used: Instant::now(),
+        };
+        self.queues.insert(vault_name.clone(), vault_queue);
+
+        Dispatch::NewWorker { vault_name, queue }
+    }
+
+    /// Dropping the sender of an empty queue lets the worker finish its current request and stop.
+    /// The router is the only sender, so no request can be lost in between.
+    fn release_idle_workers(&mut self) {
+        let idle_timeout = self.idle_timeout;
+        self.queues.retain(|_, queue| {
+            !queue.sender.is_empty() || queue.last_used.elapsed() < idle_timeout
+        });
+    }
+}
+
+/// Keeps server maintenance of a vault (like garbage collection) from interleaving
+/// with the requests of the vault, both read and update the same log objects
+#[derive(Default)]
+pub struct VaultLocks {
+    locks: Mutex<HashMap<VaultName, Arc<AsyncMutex<()>>>>,
+}
+
+impl VaultLocks {
+    pub async fn lock(&self, vault_name: VaultName) -> OwnedMutexGuard<()> {
+        let vault_lock = {
+            let mut locks = self.locks.lock().unwrap();
+            Self::release_unused_locks(&mut locks);
+            locks.entry(vault_name).or_default().clone()
+        };
+        vault_lock.lock_owned().await
+    }
+
+    /// A lock referenced by the map only is neither held nor awaited by anyone.
+    /// New references are taken under the map mutex, so it can't be picked up in between.
+    fn release_unused_locks(locks: &mut HashMap<VaultName, Arc<AsyncMutex<()>>>) {
+        locks.retain(|_, vault_lock| Arc::strong_count(vault_lock) > 1);
+    }
+}
+
+/// The vault a request reads or writes, if any
+pub fn request_vault(request: &SyncRequest) -> Option<VaultName> {
+    match request {
+        SyncRequest::Read(read_request) => match read_request.as_ref() {
+            ReadSyncRequest::Vault(request) => Some(request.sender.vault_name()),
+            ReadSyncRequest::SsRequest(request) => Some(request.sender.vault_name()),
+            ReadSyncRequest::SsRecoveryCompletion(completion) => {
+                Some(completion.vault_name.clone())
+            }
+            ReadSyncRequest::ServerTail(request) => Some(request.sender.vault_name()),
+            ReadSyncRequest::Handshake(_) => None,
+        },
+        SyncRequest::Write(write_request) => match write_request.as_ref() {
+            WriteSyncRequest::Event(signed_event) => {
+                signed_event.event().ok().and_then(|event| event_vault(&event))
+            }
+        },
+    }
+}
+
+fn event_vault(event: &GenericKvLogEvent) -> Option<VaultName> {
+    match event {
+        GenericKvLogEvent::DeviceLog(device_log) => Some(device_log.0.value.vault_name()),
+        GenericKvLogEvent::SsDeviceLog(ss_device_log) => {
+            Some(ss_device_log.0.value.vault_name.clone())
+        }
+        GenericKvLogEvent::SsWorkflow(ss_workflow) => match ss_workflow {
+            SsWorkflowObject::Recovery(event) | SsWorkflowObject::Distribution(event) => {
+                Some(event.value.vault_name.clone())
+            }
+            SsWorkflowObject::Decline(event) => Some(event.value.vault_name.clone()),
+            SsWorkflowObject::Approval(event) => Some(event.value.vault_name.clone()),
+            SsWorkflowObject::CancelClaim(event) => Some(event.value.vault_name.clone()),
+            SsWorkflowObject::Veto(event) => Some(event.value.vault_name.clone()),
+        },
+        GenericKvLogEvent::DeviceCreds(_)
+        | GenericKvLogEvent::UserCreds(_)
+        | GenericKvLogEvent::VaultLog(_)
+        | GenericKvLogEvent::Vault(_)
+        | GenericKvLogEvent::VaultStatus(_)
+        | GenericKvLogEvent::SsLog(_)
+        | GenericKvLogEvent::AuditLog(_)
+        | GenericKvLogEvent::AuditKey(_)
+        | GenericKvLogEvent::JoinInvite(_)
+        | GenericKvLogEvent::DbError(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meta_secret_core::crypto::keys::fixture::KeyManagerFixture;
+    use meta_secret_core::node::api::{HandshakeRequest, ProtocolInfo, ServerTailRequest};
+    use meta_secret_core::node::common::data_transfer::CorrelationId;
+    use meta_secret_core::node::common::model::device::device_creds::fixture::DeviceCredentialsFixture;
+    use meta_secret_core::node::common::model::user::common::UserData;
+
+    fn server_tail_request(vault_name: &str) -> SyncRequest {
+        let creds = DeviceCredentialsFixture::from_km(KeyManagerFixture::generate());
+        let sender = UserData {
+            vault_name: VaultName::from(vault_name),
+            device: creds.client.device,
+        };
+        SyncRequest::Read(Box::new(ReadSyncRequest::ServerTail(ServerTailRequest {
+            sender,
+        })))
+    }
+
+    fn handshake_request() -> SyncRequest {
+        SyncRequest::Read(Box::new(ReadSyncRequest::Handshake(HandshakeRequest {
+            client: ProtocolInfo::current(),
+        })))
+    }
+
+    fn envelope(request: SyncRequest) -> (ServerEnvelope, Receiver<DataSyncResponse>) {
+        ServerEnvelope::new(CorrelationId::from(0), request)
+    }
+
+    #[test]
+    fn test_same_vault_requests_share_worker() {
+        let mut router = VaultRouter::default();
+
+        let (first, _) = envelope(server_tail_request("vault_a"));
+        let Dispatch::NewWorker { queue, .. } = router.dispatch(first) else {
+            panic!("A worker must be started for a new vault");
+        };
+
+        let (second, _) = envelope(server_tail_request("vault_a"));
+        assert!(matches!(router.dispatch(second), Dispatch::Queued));
+        assert_eq!(queue.len(), 2);
+
+        let (other_vault, _) = envelope(server_tail_request("vault_b"));
+        assert!(matches!(
+            router.dispatch(other_vault),
+            Dispatch::NewWorker { .. }
+        ));
+        assert_eq!(router.active_vaults(), 2);
+    }
+
+    #[test]
+    fn test_handshake_is_not_routed() {
+        let mut router = VaultRouter::default();
+        let (request, _) = envelope(handshake_request());
+        assert!(matches!(router.dispatch(request), Dispatch::Unrouted(_)));
+        assert_eq!(router.active_vaults(), 0);
+    }
+
+    #[test]
+    fn test_full_vault_queue_is_busy() {
+        let mut router = VaultRouter::default();
+
+        let mut queues = vec![];
+        for _ in 0..VAULT_QUEUE_CAPACITY {
+            let (request, _) = envelope(server_tail_request("vault_a"));
+            if let Dispatch::NewWorker { queue, .. } = router.dispatch(request) {
+                queues.push(queue);
+            }
+        }
+
+        let (request, _) = envelope(server_tail_request("vault_a"));
+        assert!(matches!(router.dispatch(request), Dispatch::Busy { .. }));
+    }
+
+    #[test]
+    fn test_idle_worker_is_released() {
+        let mut router = VaultRouter::new(Duration::ZERO);
+
+        let (request, _) = envelope(server_tail_request("vault_a"));
+        let Dispatch::NewWorker { queue, .. } = router.dispatch(request) else {
+            panic!("A worker must be started for a new vault");
+        };
+        let _ = queue.try_recv();
+
+        let (handshake, _) = envelope(handshake_request());
+        router.dispatch(handshake);
+
+        assert_eq!(router.active_vaults(), 0);
+        assert!(queue.recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_vault_lock_is_exclusive_per_vault() {
+        let locks = VaultLocks::default();
+
+        let _guard = locks.lock(VaultName::from("vault_a")).await;
+        // another vault is not blocked
+        let _other = locks.lock(VaultName::from("vault_b")).await;
+
+        let same_vault = tokio::time::timeout(
+            Duration::from_millis(50),
+            locks.lock(VaultName::from("vault_a")),
+        );
+        assert!(same_vault.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unused_vault_locks_are_released() {
+        let locks = VaultLocks::default();
+
+        let held = locks.lock(VaultName::from("vault_a")).await;
+        drop(locks.lock(VaultName::from("vault_b")).await);
+        let _other = locks.lock(VaultName::from("vault_c")).await;
+
+        let vaults = locks.locks.lock().unwrap().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(vaults.len(), 2);
+        assert!(vaults.contains(&VaultName::from("vault_a")));
+        assert!(!vaults.contains(&VaultName::from("vault_b")));
+        drop(held);
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Result, bail};
+use clap::{Args, Subcommand, ValueEnum};
+use meta_db_redb::ReDbRepo;
+use meta_db_sqlite::db::sqlite_store::SqlIteRepo;
+use meta_secret_core::node::common::model::IdString;
+use meta_secret_core::node::common::model::vault::vault::VaultName;
+use meta_secret_core::node::db::repo::generic_db::{FindObjectsQuery, KvLogEventRepo};
+use meta_server_node::server::admin::ServerAdmin;
+use serde::Serialize;
+
+use crate::config::{DbBackend, ServerConfig};
+
+/// Inspect and maintain the server database, the master key is not needed.
+/// A redb database can't be opened while the server is running.
+#[derive(Args, Debug)]
+pub struct AdminArgs {
+    /// Output format of the reports
+    #[arg(long, value_enum, default_value_t = AdminOutputFormat::Yaml, global = true)]
+    pub format: AdminOutputFormat,
+
+    #[command(subcommand)]
+    pub command: AdminCommand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AdminOutputFormat {
+    Yaml,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AdminCommand {
+    /// List the vaults stored on the server
+    Vaults,
+    /// Show the devices of a vault and their membership statuses
+    Members { vault_name: String },
+    /// Show the open secret sharing claims of a vault and the status of every receiver
+    Claims { vault_name: String },
+    /// Delete workflow events of the shares that are delivered or declined
+    Purge {
+        vault_name: String,
+        /// Only report the events that would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export the full event history of a vault as json lines, share ciphertext is redacted
+    Export {
+        vault_name: String,
+        /// Keep share ciphertext in the export
+        #[arg(long)]
+        include_ciphertext: bool,
+        /// Output file, stdout if not set
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PurgeReport {
+    dry_run: bool,
+    events: Vec<String>,
+}
+
+pub async fn run(config: &ServerConfig, args: AdminArgs) -> Result<()> {
+    let db_path = &config.database.path;
+    if !db_path.exists() {
+        bail!("Database not found: {:?}", db_path);
+    }
+
+    match config.database.backend {
+        DbBackend::Sqlite => {
+            let repo = SqlIteRepo {
+                conn_url: format!("file:{}", db_path.display()),
+            };
+            run_with_repo(Arc::new(repo), args).await
+        }
+        DbBackend::Redb => {
+            let repo = ReDbRepo::open(db_path)?;
+            run_with_repo(Arc::new(repo), args).await
+        }
+    }
+}
+
+async fn run_with_repo<Repo: KvLogEventRepo + FindObjectsQuery>(
+    repo: Arc<Repo>,
+    args: AdminArgs,
+) -> Result<()> {
+    let admin = ServerAdmin::new(repo);
+    let format = args.format;
+
+    match args.command {
+        AdminCommand::Vaults => print(&admin.list_vaults().await?, format),
+        AdminCommand::Members { vault_name } => {
+            let members = admin.vault_members(VaultName::from(vault_name)).await?;
+            print(&members, format)
+        }
+        AdminCommand::Claims { vault_name } => {
+            let claims = admin.ss_claims(VaultName::from(vault_name)).await?;
+            print(&claims, format)
+        }
+        AdminCommand::Purge {
+            vault_name,
+            dry_run,
+        } => {
+            let ids = admin
+                .purge_stale_workflow_events(VaultName::from(vault_name), dry_run)
+                .await?;
+            let report = PurgeReport {
+                dry_run,
+                events: ids.into_iter().map(|id| id.id_str()).collect(),
+            };
+            print(&report, format)
+        }
+        AdminCommand::Export {
+            vault_name,
+            include_ciphertext,
+            output,
+        } => {
+            let events = admin
+                .export_vault(VaultName::from(vault_name), include_ciphertext)
+                .await?;
+
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout().lock()),
+            };
+            for event in events {
+                serde_json::to_writer(&mut writer, &event)?;
+                writeln!(writer)?;
+            }
+            writer.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn print<T: Serialize>(report: &T, format: AdminOutputFormat) -> Result<()> {
+    let text = match format {
+        AdminOutputFormat::Yaml => serde_yaml::to_string(report)?,
+        AdminOutputFormat::Json => serde_json::to_string_pretty(report)?,
+    };
+    println!("{}", text);
+    Ok(())
+}
@@ -0,0 +1,680 @@
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{Args, ValueEnum};
+use http::HeaderValue;
+use meta_secret_core::crypto::master_key::{KeyFileKind, MasterKeySource};
+use meta_secret_core::node::common::model::vault::vault_data::DEFAULT_JOIN_QUORUM;
+use meta_server_node::server::retention::{
+    DEFAULT_CLAIM_MAX_AGE, DEFAULT_GC_INTERVAL, RetentionPolicy,
+};
+use meta_server_node::server::server_app::DEFAULT_REQUEST_TIMEOUT;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Failed to read config file {path:?}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Unsupported config file format: {0:?}, expected .toml, .yaml or .yml")]
+    UnsupportedFormat(PathBuf),
+    #[error("Invalid TOML config: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid YAML config: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("Invalid configuration:\n  - {}", .0.join("\n  - "))]
+    Invalid(Vec<String>),
+}
+
+/// Server settings. Precedence: defaults < config file < environment < command line flags
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listen_addr: String,
+    /// Key file for the `file` and `passphrase-file` sources
+    pub master_key_path: PathBuf,
+    pub master_key_source: MasterKeySource,
+    pub database: DatabaseConfig,
+    pub cors: CorsConfig,
+    pub log: LogConfig,
+    pub limits: LimitsConfig,
+    pub retention: RetentionConfig,
+    pub vaults: VaultsConfig,
+    /// Plain HTTP if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub backend: DbBackend,
+    pub path: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DbBackend {
+    Sqlite,
+    Redb,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    /// `*` allows any origin
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Tracing filter directive, `RUST_LOG` takes precedence if set
+    pub level: String,
+    pub format: LogFormat,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Compact,
+    Full,
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub max_request_body_bytes: usize,
+    pub request_timeout_secs: u64,
+}
+
+/// Garbage collection of secret sharing data: delivered shares are deleted on every run,
+/// claims are removed once they are older than `claim_max_age_secs`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetentionConfig {
+    pub gc_interval_secs: u64,
+    pub claim_max_age_secs: u64,
+}
+
+/// Settings a new vault starts with, the members of the vault change them later
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VaultsConfig {
+    /// Member approvals a device needs to join a new vault, all members of a smaller vault
+    /// have to approve. The members of a vault change it later
+    pub join_quorum: usize,
+    /// Devices join a new vault with an invite of a member only
+    pub require_invite: bool,
+}
+
+/// PEM encoded certificate chain and private key, reloaded on SIGHUP
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            listen_addr: String::from("0.0.0.0:3000"),
+            master_key_path: PathBuf::from("master_key.json"),
+            master_key_source: MasterKeySource::default(),
+            database: DatabaseConfig::default(),
+            cors: CorsConfig::default(),
+            log: LogConfig::default(),
+            limits: LimitsConfig::default(),
+            retention: RetentionConfig::default(),
+            vaults: VaultsConfig::default(),
+            tls: None,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            backend: DbBackend::Sqlite,
+            path: PathBuf::from("meta-secret.db"),
+        }
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![String::from("*")],
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: String::from("debug"),
+            format: LogFormat::Compact,
+        }
+    }
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            // sync requests carry a single event, anything bigger than that is rejected before parsing
+            max_request_body_bytes: 1024 * 1024,
+            request_timeout_secs: DEFAULT_REQUEST_TIMEOUT.as_secs(),
+        }
+    }
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            gc_interval_secs: DEFAULT_GC_INTERVAL.as_secs(),
+            claim_max_age_secs: DEFAULT_CLAIM_MAX_AGE.as_secs(),
+        }
+    }
+}
+
+impl Default for VaultsConfig {
+    fn default() -> Self {
+        Self {
+            join_quorum: DEFAULT_JOIN_QUORUM,
+            require_invite: false,
+        }
+    }
+}
+
+/// Settings that can be given on the command line or through `META_SERVER_*` environment variables
+#[derive(Args, Debug, Default)]
+pub struct ConfigOverrides {
+    /// Address to listen on, e.g. 0.0.0.0:3000
+    #[arg(long, env = "META_SERVER_LISTEN_ADDR")]
+    pub listen_addr: Option<String>,
+
+    /// Path to the master key file, created if missing
+    #[arg(long, env = "META_SERVER_MASTER_KEY_PATH")]
+    pub master_key_path: Option<PathBuf>,
+
+    /// Where the master key comes from: file, passphrase-file, env:<VAR>, stdin or command:<CMD>
+    #[arg(long, env = "META_SERVER_MASTER_KEY_SOURCE")]
+    pub master_key_source: Option<MasterKeySource>,
+
+    #[arg(long, env = "META_SERVER_DB_BACKEND")]
+    pub db_backend: Option<DbBackend>,
+
+    /// Path to the database file
+    #[arg(long, env = "META_SERVER_DB_PATH")]
+    pub db_path: Option<PathBuf>,
+
+    /// Allowed CORS origins, comma separated
+    #[arg(long, env = "META_SERVER_CORS_ORIGINS", value_delimiter = ',')]
+    pub cors_origins: Option<Vec<String>>,
+
+    #[arg(long, env = "META_SERVER_LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    #[arg(long, env = "META_SERVER_LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+
+    #[arg(long, env = "META_SERVER_MAX_REQUEST_BODY_BYTES")]
+    pub max_request_body_bytes: Option<usize>,
+
+    #[arg(long, env = "META_SERVER_REQUEST_TIMEOUT_SECS")]
+    pub request_timeout_secs: Option<u64>,
+
+    #[arg(long, env = "META_SERVER_GC_INTERVAL_SECS")]
+    pub gc_interval_secs: Option<u64>,
+
+    /// Claims older than this are removed together with their shares
+    #[arg(long, env = "META_SERVER_CLAIM_MAX_AGE_SECS")]
+    pub claim_max_age_secs: Option<u64>,
+
+    /// Member approvals a device needs to join a new vault
+    #[arg(long, env = "META_SERVER_JOIN_QUORUM")]
+    pub join_quorum: Option<usize>,
+
+    /// New vaults reject join requests without a valid invite
+    #[arg(long, env = "META_SERVER_REQUIRE_INVITE")]
+    pub require_invite: Option<bool>,
+
+    /// PEM certificate chain, enables https together with --tls-key-path
+    #[arg(long, env = "META_SERVER_TLS_CERT_PATH")]
+    pub tls_cert_path: Option<PathBuf>,
+
+    /// PEM private key
+    #[arg(long, env = "META_SERVER_TLS_KEY_PATH")]
+    pub tls_key_path: Option<PathBuf>,
+}
+
+impl ServerConfig {
+    /// Reads the config file, the format is chosen by the file extension
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        match extension {
+            Some("toml") => Ok(toml::from_str(&content)?),
+            Some("yaml") | Some("yml") => Ok(serde_yaml::from_str(&content)?),
+            _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    pub fn load(path: Option<&Path>, overrides: ConfigOverrides) -> Result<Self, ConfigError> {
+        let config = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+        Ok(config.with_overrides(overrides))
+    }
+
+    pub fn with_overrides(mut self, overrides: ConfigOverrides) -> Self {
+        if let Some(listen_addr) = overrides.listen_addr {
+            self.listen_addr = listen_addr;
+        }
+        if let Some(master_key_path) = overrides.master_key_path {
+            self.master_key_path = master_key_path;
+        }
+        if let Some(master_key_source) = overrides.master_key_source {
+            self.master_key_source = master_key_source;
+        }
+        if let Some(backend) = overrides.db_backend {
+            self.database.backend = backend;
+        }
+        if let Some(path) = overrides.db_path {
+            self.database.path = path;
+        }
+        if let Some(origins) = overrides.cors_origins {
+            self.cors.allowed_origins = origins;
+        }
+        if let Some(level) = overrides.log_level {
+            self.log.level = level;
+        }
+        if let Some(format) = overrides.log_format {
+            self.log.format = format;
+        }
+        if let Some(max_request_body_bytes) = overrides.max_request_body_bytes {
+            self.limits.max_request_body_bytes = max_request_body_bytes;
+        }
+        if let Some(request_timeout_secs) = overrides.request_timeout_secs {
+            self.limits.request_timeout_secs = request_timeout_secs;
+        }
+        if let Some(gc_interval_secs) = overrides.gc_interval_secs {
+            self.retention.gc_interval_secs = gc_interval_secs;
+        }
+        if let Some(claim_max_age_secs) = overrides.claim_max_age_secs {
+            self.retention.claim_max_age_secs = claim_max_age_secs;
+        }
+        if let Some(join_quorum) = overrides.join_quorum {
+            self.vaults.join_quorum = join_quorum;
+        }
+        if let Some(require_invite) = overrides.require_invite {
+            self.vaults.require_invite = require_invite;
+        }
+        if overrides.tls_cert_path.is_some() || overrides.tls_key_path.is_some() {
+            let tls = self.tls.get_or_insert_with(TlsConfig::default);
+            if let Some(cert_path) = overrides.tls_cert_path {
+                tls.cert_path = cert_path;
+            }
+            if let Some(key_path) = overrides.tls_key_path {
+                tls.key_path = key_path;
+            }
+        }
+        self
+    }
+
+    /// Checks everything the server needs to start, without touching the database
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = vec![];
+
+        if let Err(e) = self.socket_addr() {
+            problems.push(format!("listen_addr {:?}: {}", self.listen_addr, e));
+        }
+
+        if self.database.path.as_os_str().is_empty() {
+            problems.push(String::from("database.path is empty"));
+        } else if !parent_exists(&self.database.path) {
+            problems.push(format!(
+                "database.path {:?}: directory does not exist",
+                self.database.path
+            ));
+        }
+
+        if let Err(e) = self.validate_master_key() {
+            problems.push(e);
+        }
+
+        if let Err(e) = self.cors_origins() {
+            problems.push(e);
+        }
+
+        if let Err(e) = EnvFilter::try_new(&self.log.level) {
+            problems.push(format!("log.level {:?}: {}", self.log.level, e));
+        }
+
+        if self.limits.max_request_body_bytes == 0 {
+            problems.push(String::from(
+                "limits.max_request_body_bytes must be positive",
+            ));
+        }
+        if self.limits.request_timeout_secs == 0 {
+            problems.push(String::from("limits.request_timeout_secs must be positive"));
+        }
+        if self.retention.gc_interval_secs == 0 {
+            problems.push(String::from("retention.gc_interval_secs must be positive"));
+        }
+        if self.retention.claim_max_age_secs == 0 {
+            problems.push(String::from(
+                "retention.claim_max_age_secs must be positive",
+            ));
+        }
+        if self.vaults.join_quorum == 0 {
+            problems.push(String::from("vaults.join_quorum must be positive"));
+        }
+
+        if let Some(tls) = &self.tls {
+            if let Err(e) = read_certs(&tls.cert_path) {
+                problems.push(format!("tls.cert_path {:?}: {}", tls.cert_path, e));
+            }
+            if let Err(e) = PrivateKeyDer::from_pem_file(&tls.key_path) {
+                problems.push(format!("tls.key_path {:?}: {}", tls.key_path, e));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    pub fn socket_addr(&self) -> Result<SocketAddr, std::net::AddrParseError> {
+        self.listen_addr.parse()
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.limits.request_timeout_secs)
+    }
+
+    pub fn retention_policy(&self) -> RetentionPolicy {
+        RetentionPolicy {
+            gc_interval: Duration::from_secs(self.retention.gc_interval_secs),
+            claim_max_age: Duration::from_secs(self.retention.claim_max_age_secs),
+        }
+    }
+
+    /// `None` means any origin is allowed
+    pub fn cors_origins(&self) -> Result<Option<Vec<HeaderValue>>, String> {
+        let origins = &self.cors.allowed_origins;
+        if origins.iter().any(|origin| origin == "*") {
+            if origins.len() > 1 {
+                return Err(String::from(
+                    "cors.allowed_origins: `*` can't be combined with other origins",
+                ));
+            }
+            return Ok(None);
+        }
+
+        origins
+            .iter()
+            .map(|origin| {
+                let is_http = origin.starts_with("http://") || origin.starts_with("https://");
+                match HeaderValue::from_str(origin) {
+                    Ok(value) if is_http => Ok(value),
+                    _ => Err(format!("cors.allowed_origins: invalid origin {:?}", origin)),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Some)
+    }
+
+    /// The key itself is not loaded, an encrypted file would need the passphrase
+    fn validate_master_key(&self) -> Result<(), String> {
+        let key_path = &self.master_key_path;
+        match &self.master_key_source {
+            MasterKeySource::File | MasterKeySource::PassphraseFile => {
+                match KeyFileKind::of(key_path) {
+                    Err(e) => Err(format!("master_key_path {:?}: {}", key_path, e)),
+                    Ok(Some(KeyFileKind::Plain))
+                        if self.master_key_source == MasterKeySource::PassphraseFile =>
+                    {
+                        Err(format!(
+                            "master_key_path {:?}: the key file is not encrypted",
+                            key_path
+                        ))
+                    }
+                    Ok(Some(_)) => Ok(()),
+                    Ok(None) if parent_exists(key_path) => Ok(()),
+                    Ok(None) => Err(format!(
+                        "master_key_path {:?}: directory does not exist",
+                        key_path
+                    )),
+                }
+            }
+            MasterKeySource::Env { var } => match std::env::var(var) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(format!(
+                    "master_key_source: environment variable {} is not set",
+                    var
+                )),
+            },
+            MasterKeySource::Command { command } if command.trim().is_empty() => {
+                Err(String::from("master_key_source: the command is empty"))
+            }
+            MasterKeySource::Command { .. } | MasterKeySource::Stdin => Ok(()),
+        }
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).unwrap_or_else(|e| format!("# {}", e))
+    }
+}
+
+fn parent_exists(path: &Path) -> bool {
+    match path.parent() {
+        None => true,
+        Some(parent) => parent.as_os_str().is_empty() || parent.is_dir(),
+    }
+}
+
+fn read_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)?.collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        anyhow::bail!("no certificates found");
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meta_secret_core::crypto::master_key::{MasterKeyProvider, PlainKeyFile};
+
+    #[test]
+    fn test_toml_and_yaml_configs_are_equal() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        let toml_path = dir.path().join("meta-server.toml");
+        fs::write(
+            &toml_path,
+            r#"
+listen_addr = "127.0.0.1:8080"
+
+[database]
+backend = "redb"
+path = "/var/lib/meta-secret/meta-secret.redb"
+
+[cors]
+allowed_origins = ["https://meta-secret.org"]
+
+[log]
+format = "json"
+"#,
+        )?;
+
+        let yaml_path = dir.path().join("meta-server.yaml");
+        fs::write(
+            &yaml_path,
+            r#"
+listen_addr: 127.0.0.1:8080
+database:
+  backend: redb
+  path: /var/lib/meta-secret/meta-secret.redb
+cors:
+  allowed_origins: ["https://meta-secret.org"]
+log:
+  format: json
+"#,
+        )?;
+
+        let toml_config = ServerConfig::from_file(&toml_path)?;
+        let yaml_config = ServerConfig::from_file(&yaml_path)?;
+        assert_eq!(toml_config, yaml_config);
+
+        assert_eq!(toml_config.database.backend, DbBackend::Redb);
+        assert_eq!(toml_config.log.format, LogFormat::Json);
+        // not mentioned in the file
+        assert_eq!(toml_config.log.level, LogConfig::default().level);
+        assert_eq!(toml_config.limits, LimitsConfig::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_field_is_rejected() {
+        let result: Result<ServerConfig, _> = toml::from_str("listen_address = \"0.0.0.0:80\"");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let overrides = ConfigOverrides {
+            listen_addr: Some(String::from("127.0.0.1:4000")),
+            db_backend: Some(DbBackend::Redb),
+            cors_origins: Some(vec![String::from("https://a.org")]),
+            gc_interval_secs: Some(60),
+            join_quorum: Some(2),
+            require_invite: Some(true),
+            ..ConfigOverrides::default()
+        };
+
+        let config = ServerConfig::default().with_overrides(overrides);
+        assert_eq!(config.listen_addr, "127.0.0.1:4000");
+        assert_eq!(config.database.backend, DbBackend::Redb);
+        assert_eq!(config.database.path, DatabaseConfig::default().path);
+        assert_eq!(config.cors.allowed_origins, vec!["https://a.org"]);
+        assert_eq!(config.vaults.join_quorum, 2);
+        assert!(config.vaults.require_invite);
+
+        let retention = config.retention_policy();
+        assert_eq!(retention.gc_interval, Duration::from_secs(60));
+        assert_eq!(
+            retention.claim_max_age,
+            RetentionPolicy::default().claim_max_age
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let mut config = ServerConfig::default();
+        assert!(config.validate().is_ok());
+
+        config.listen_addr = String::from("localhost");
+        config.database.path = PathBuf::from("/no/such/dir/meta-secret.db");
+        config.cors.allowed_origins = vec![String::from("*"), String::from("https://a.org")];
+        config.limits.request_timeout_secs = 0;
+        config.vaults.join_quorum = 0;
+
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("Config must be invalid");
+        };
+        assert_eq!(problems.len(), 5);
+    }
+
+    #[test]
+    fn test_tls_paths_from_overrides() {
+        let overrides = ConfigOverrides {
+            tls_cert_path: Some(PathBuf::from("/no/such/cert.pem")),
+            ..ConfigOverrides::default()
+        };
+
+        let config = ServerConfig::default().with_overrides(overrides);
+        let tls = config.tls.clone().expect("tls must be enabled");
+        assert_eq!(tls.cert_path, PathBuf::from("/no/such/cert.pem"));
+
+        // neither the certificate nor the (empty) key path can be read
+        let Err(ConfigError::Invalid(problems)) = config.validate() else {
+            panic!("Config must be invalid");
+        };
+        assert_eq!(problems.len(), 2);
+    }
+
+    #[test]
+    fn test_broken_master_key_is_invalid() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let master_key_path = dir.path().join("master_key.json");
+        fs::write(&master_key_path, "not a key")?;
+
+        let config = ServerConfig {
+            master_key_path,
+            ..ServerConfig::default()
+        };
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_master_key_source() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let toml_path = dir.path().join("meta-server.toml");
+        fs::write(
+            &toml_path,
+            r#"
+[master_key_source]
+type = "command"
+command = "pass show meta-server"
+"#,
+        )?;
+
+        let config = ServerConfig::from_file(&toml_path)?;
+        assert_eq!(
+            config.master_key_source,
+            MasterKeySource::Command {
+                command: String::from("pass show meta-server"),
+            }
+        );
+
+        let overrides = ConfigOverrides {
+            master_key_source: Some("env:META_SERVER_TEST_UNSET_MASTER_KEY".parse()?),
+            ..ConfigOverrides::default()
+        };
+        let config = config.with_overrides(overrides);
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        // a plain key file can't be used as an encrypted one
+        let master_key_path = dir.path().join("master_key.json");
+        PlainKeyFile::new(&master_key_path).master_key()?;
+        let config = ServerConfig {
+            master_key_path,
+            master_key_source: MasterKeySource::PassphraseFile,
+            ..ServerConfig::default()
+        };
+        assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+
+        Ok(())
+    }
+}
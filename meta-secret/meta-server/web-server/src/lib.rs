@@ -0,0 +1,4 @@
+pub mod admin;
+pub mod config;
+pub mod server;
+pub mod tls;
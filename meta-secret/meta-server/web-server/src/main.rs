@@ -1,124 +1,112 @@
-use axum::extract::State;
-use axum::{Json, Router, routing::post};
-use http::{StatusCode, Uri};
-use serde_derive::Serialize;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 use anyhow::Result;
-use axum::response::Html;
-use axum::routing::get;
-use meta_db_sqlite::db::sqlite_store::SqlIteRepo;
-use meta_secret_core::crypto::key_utils;
-use meta_secret_core::node::api::{DataSyncResponse, SyncRequest};
-use meta_server_node::server::server_app::{MetaServerDataTransfer, ServerApp};
-use tokio::net::TcpListener;
-use tower_http::cors::CorsLayer;
-use tower_http::trace::TraceLayer;
-use tracing::{Level, info};
+use clap::{Parser, Subcommand};
+use meta_server::admin::{self, AdminArgs};
+use meta_server::config::{ConfigOverrides, LogConfig, LogFormat, ServerConfig};
+use meta_server::server;
+use tracing::Level;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
-#[derive(Clone)]
-pub struct MetaServerAppState {
-    data_transfer: Arc<MetaServerDataTransfer>,
+#[derive(Debug, Parser)]
+#[command(name = "meta-server", about = "Meta Secret server", long_about = None)]
+struct ServerArgs {
+    /// Config file (.toml, .yaml or .yml)
+    #[arg(long, short, env = "META_SERVER_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Validate the configuration, print it and exit without starting the server
+    #[arg(long)]
+    check_config: bool,
+
+    #[command(flatten)]
+    overrides: ConfigOverrides,
+
+    #[command(subcommand)]
+    command: Option<ServerCommand>,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("debug"))
-        .add_directive("hyper=info".parse()?)
-        .add_directive("h2=info".parse()?)
-        .add_directive("tower=info".parse()?)
-        .add_directive("sqlx=info".parse()?);
+#[derive(Debug, Subcommand)]
+enum ServerCommand {
+    Admin(AdminArgs),
+}
 
-    // a builder for `FmtSubscriber`.
-    let subscriber = FmtSubscriber::builder()
-        // Use a more compact, abbreviated log format
-        .compact()
-        // all spans/events with a level higher than TRACE (e.g, debug, info, warn, etc.)
-        // will be written to stdout.
-        .with_max_level(Level::DEBUG)
-        .with_env_filter(filter)
-        // completes the builder.
-        .finish();
-    tracing::subscriber::set_global_default(subscriber)?;
-
-    info!("Starting Server...");
-
-    // Load or create a master key from a file
-    let master_key_path = "master_key.json";
-    let master_key = key_utils::load_or_create_master_key(master_key_path)?;
-    info!("Master key loaded successfully");
-
-    info!("Creating router...");
-    let cors = CorsLayer::permissive();
-
-    let server_app = {
-        let repo = Arc::new(SqlIteRepo {
-            conn_url: String::from("file:meta-secret.db"),
-        });
-        Arc::new(ServerApp::new(repo.clone(), master_key)?)
+fn main() -> ExitCode {
+    let args = ServerArgs::parse();
+
+    let config = match ServerConfig::load(args.config.as_deref(), args.overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
     };
 
-    let data_transfer = server_app.get_data_transfer();
-    let server_app_clone = server_app.clone();
+    if let Err(e) = config.validate() {
+        eprintln!("{}", e);
+        return ExitCode::FAILURE;
+    }
+
+    if args.check_config {
+        println!("Configuration is valid\n\n{}", config.to_toml());
+        return ExitCode::SUCCESS;
+    }
 
-    // Create a separate runtime for the server app
-    std::thread::spawn(move || {
-        let rt = tokio::runtime::Builder::new_current_thread()
+    if let Some(ServerCommand::Admin(admin_args)) = args.command {
+        let result = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
-            .unwrap();
-
-        rt.block_on(async move {
-            if let Err(e) = server_app_clone.run().await {
-                panic!("Server app background task failed: {:?}", e);
+            .map_err(anyhow::Error::from)
+            .and_then(|rt| rt.block_on(admin::run(&config, admin_args)));
+
+        return match result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Admin command failed: {:?}", e);
+                ExitCode::FAILURE
             }
-        });
-    });
-
-    let app_state = Arc::new(MetaServerAppState { data_transfer });
-
-    info!("Creating router...");
-    let app = Router::new()
-        .route("/meta_request", post(meta_request))
-        .route("/hi", get(hi))
-        .with_state(app_state)
-        .layer(cors)
-        .layer(TraceLayer::new_for_http())
-        .fallback(not_found_handler);
+        };
+    }
 
-    let port = 3000;
-    info!("Run axum server, on port: {}", port);
-    let listener = TcpListener::bind(format!("0.0.0.0:{:?}", port)).await?;
-    axum::serve(listener, app).await?;
-
-    Ok(())
-}
+    let result = init_tracing(&config.log).and_then(|()| {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?
+            .block_on(server::start(config))
+    });
 
-async fn hi() -> Html<&'static str> {
-    Html("<h1>Hello, World!</h1>")
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Server failed: {:?}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-#[derive(Serialize)]
-struct ErrorResponse {
-    message: String,
-}
-async fn not_found_handler(uri: Uri) -> (StatusCode, Json<ErrorResponse>) {
-    let error_response = ErrorResponse {
-        message: format!("404. MetaServer has no route: {uri}"),
-    };
-    let response = Json(error_response);
-    (StatusCode::NOT_FOUND, response)
-}
+fn init_tracing(log: &LogConfig) -> Result<()> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&log.level))?
+        .add_directive("hyper=info".parse()?)
+        .add_directive("h2=info".parse()?)
+        .add_directive("tower=info".parse()?)
+        .add_directive("sqlx=info".parse()?);
 
-pub async fn meta_request(
-    State(state): State<Arc<MetaServerAppState>>,
-    Json(msg_request): Json<SyncRequest>,
-) -> Json<DataSyncResponse> {
-    info!("Event processing");
+    // a builder for `FmtSubscriber`.
+    let subscriber = FmtSubscriber::builder()
+        // all spans/events with a level higher than TRACE (e.g, debug, info, warn, etc.)
+        // will be written to stdout.
+        .with_max_level(Level::TRACE)
+        .with_env_filter(filter);
 
-    let response = state.data_transfer.send_request(msg_request).await.unwrap();
+    match log.format {
+        LogFormat::Compact => {
+            tracing::subscriber::set_global_default(subscriber.compact().finish())?
+        }
+        LogFormat::Full => tracing::subscriber::set_global_default(subscriber.finish())?,
+        LogFormat::Json => tracing::subscriber::set_global_default(subscriber.json().finish())?,
+    }
 
-    Json(response)
+    Ok(())
 }
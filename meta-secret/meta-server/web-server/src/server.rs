@@ -0,0 +1,288 @@
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{DefaultBodyLimit, State};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use axum_server::tls_rustls::RustlsConfig;
+use http::header::CONTENT_TYPE;
+use http::{HeaderMap, StatusCode, Uri};
+use meta_db_redb::ReDbRepo;
+use meta_db_sqlite::db::sqlite_store::SqlIteRepo;
+use meta_secret_core::crypto::keys::TransportSk;
+use meta_secret_core::node::api::{
+    DataSyncResponse, SyncRequest, UpgradeRequired, PKG_VERSION_HEADER, PROTOCOL_VERSION_HEADER,
+};
+use meta_secret_core::node::db::repo::generic_db::{FindObjectsQuery, KvLogEventRepo};
+use meta_secret_core::CRATE_PKG_VERSION;
+use meta_server_node::server::metrics::ServerMetrics;
+use meta_server_node::server::server_app::{MetaServerDataTransfer, ReadinessProbe, ServerApp};
+use secrecy::SecretString;
+use serde_derive::Serialize;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tower_http::trace::TraceLayer;
+use tracing::{error, info, warn};
+
+use crate::config::{DbBackend, ServerConfig};
+use crate::tls;
+
+/// The readiness check fails if the server app doesn't answer in time
+const READINESS_TIMEOUT: Duration = Duration::from_secs(5);
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+const MASTER_KEY_PASSPHRASE_ENV: &str = "META_SERVER_MASTER_KEY_PASSPHRASE";
+
+#[derive(Clone)]
+pub struct MetaServerAppState {
+    data_transfer: Arc<MetaServerDataTransfer>,
+    readiness: Arc<ReadinessProbe>,
+    metrics: Arc<ServerMetrics>,
+}
+
+pub async fn start(config: ServerConfig) -> Result<()> {
+    info!("Starting Server...");
+
+    let master_key = {
+        let provider = config
+            .master_key_source
+            .provider(&config.master_key_path, master_key_passphrase)?;
+        provider.master_key()?
+    };
+    info!("Master key loaded from: {}", config.master_key_source);
+
+    let db_path = &config.database.path;
+    info!("Open {:?} database: {:?}", config.database.backend, db_path);
+    match config.database.backend {
+        DbBackend::Sqlite => {
+            let repo = Arc::new(SqlIteRepo {
+                conn_url: format!("file:{}", db_path.display()),
+            });
+            serve(config, repo, master_key).await
+        }
+        DbBackend::Redb => {
+            let repo = Arc::new(ReDbRepo::new(db_path)?);
+            serve(config, repo, master_key).await
+        }
+    }
+}
+
+async fn serve<Repo: KvLogEventRepo + FindObjectsQuery + Send + Sync>(
+    config: ServerConfig,
+    repo: Arc<Repo>,
+    master_key: TransportSk,
+) -> Result<()> {
+    let server_app = ServerApp::new(repo, master_key)?
+        .with_request_timeout(config.request_timeout())
+        .with_retention(config.retention_policy())
+        .with_join_quorum(config.vaults.join_quorum)
+        .with_require_invite(config.vaults.require_invite);
+    let server_app = Arc::new(server_app);
+    let app_state = spawn_server_app(server_app);
+
+    let app = router(app_state, &config)?;
+
+    let addr = config.socket_addr()?;
+    let listener = TcpListener::bind(addr)?;
+
+    match &config.tls {
+        None => {
+            info!("Run axum server, on: http://{}", addr);
+            serve_http(listener, app).await
+        }
+        Some(tls_config) => {
+            let rustls = tls::rustls_config(tls_config).await?;
+            #[cfg(unix)]
+            tls::reload_on_sighup(rustls.clone(), tls_config.clone())?;
+
+            info!("Run axum server, on: https://{}", addr);
+            serve_https(listener, app, rustls).await
+        }
+    }
+}
+
+/// Passphrase of an encrypted master key file, taken from
+/// `META_SERVER_MASTER_KEY_PASSPHRASE` or read as the first line of stdin
+fn master_key_passphrase() -> Result<SecretString> {
+    if let Ok(passphrase) = std::env::var(MASTER_KEY_PASSPHRASE_ENV) {
+        return Ok(SecretString::from(passphrase));
+    }
+
+    eprint!("Master key passphrase: ");
+    let mut passphrase = String::new();
+    std::io::stdin().read_line(&mut passphrase)?;
+    let passphrase = passphrase.trim_end_matches(['\r', '\n']).to_string();
+    Ok(SecretString::from(passphrase))
+}
+
+/// Runs the server app on its own thread, the vault workers
+/// are spread over the worker threads of the server app
+pub fn spawn_server_app<Repo: KvLogEventRepo + FindObjectsQuery + Send + Sync>(
+    server_app: Arc<ServerApp<Repo>>,
+) -> MetaServerAppState {
+    let app_state = MetaServerAppState {
+        data_transfer: server_app.get_data_transfer(),
+        readiness: server_app.get_readiness_probe(),
+        metrics: server_app.get_metrics(),
+    };
+
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        rt.block_on(async move {
+            if let Err(e) = server_app.run().await {
+                panic!("Server app background task failed: {:?}", e);
+            }
+        });
+    });
+
+    app_state
+}
+
+pub fn router(app_state: MetaServerAppState, config: &ServerConfig) -> Result<Router> {
+    let app_state = Arc::new(app_state);
+
+    info!("Creating router...");
+    let app = Router::new()
+        .route("/meta_request", post(meta_request))
+        .route("/hi", get(hi))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .with_state(app_state)
+        .layer(DefaultBodyLimit::max(config.limits.max_request_body_bytes))
+        .layer(cors_layer(config)?)
+        .layer(TraceLayer::new_for_http())
+        .fallback(not_found_handler);
+
+    Ok(app)
+}
+
+pub async fn serve_http(listener: TcpListener, app: Router) -> Result<()> {
+    listener.set_nonblocking(true)?;
+    let listener = tokio::net::TcpListener::from_std(listener)?;
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+pub async fn serve_https(listener: TcpListener, app: Router, rustls: RustlsConfig) -> Result<()> {
+    listener.set_nonblocking(true)?;
+    axum_server::from_tcp_rustls(listener, rustls)?
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+fn cors_layer(config: &ServerConfig) -> Result<CorsLayer> {
+    let origins = config.cors_origins().map_err(anyhow::Error::msg)?;
+    let cors = match origins {
+        None => CorsLayer::permissive(),
+        Some(origins) => CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods(Any)
+            .allow_headers(Any),
+    };
+    Ok(cors)
+}
+
+async fn hi() -> Html<&'static str> {
+    Html("<h1>Hello, World!</h1>")
+}
+
+/// The process is up and serves http
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// The server app is running, the db is reachable and the master key is loaded
+async fn readyz(State(state): State<Arc<MetaServerAppState>>) -> (StatusCode, String) {
+    let readiness = state
+        .readiness
+        .send_to_service_and_get_timeout((), READINESS_TIMEOUT)
+        .await
+        .unwrap_or_else(|e| Err(e.to_string()));
+
+    match readiness {
+        Ok(()) => (StatusCode::OK, String::from("ready")),
+        Err(e) => {
+            error!("Server is not ready: {}", e);
+            (StatusCode::SERVICE_UNAVAILABLE, format!("not ready: {}", e))
+        }
+    }
+}
+
+async fn metrics(State(state): State<Arc<MetaServerAppState>>) -> Response {
+    match state.metrics.encode() {
+        Ok(text) => ([(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], text).into_response(),
+        Err(e) => {
+            error!("Failed to encode metrics: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    message: String,
+}
+async fn not_found_handler(uri: Uri) -> (StatusCode, Json<ErrorResponse>) {
+    let error_response = ErrorResponse {
+        message: format!("404. MetaServer has no route: {uri}"),
+    };
+    let response = Json(error_response);
+    (StatusCode::NOT_FOUND, response)
+}
+
+/// A request that can't be read comes from a client of another protocol version,
+/// it's answered with a typed [`UpgradeRequired`] instead of a plain rejection
+pub async fn meta_request(
+    State(state): State<Arc<MetaServerAppState>>,
+    headers: HeaderMap,
+    payload: Result<Json<SyncRequest>, JsonRejection>,
+) -> Response {
+    let msg_request = match payload {
+        Ok(Json(msg_request)) => msg_request,
+        Err(JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_)) => {
+            let upgrade = unreadable_request(&headers);
+            warn!("Unreadable client request: {}", upgrade);
+            let response = DataSyncResponse::UpgradeRequired(upgrade);
+            return meta_response(StatusCode::UPGRADE_REQUIRED, response);
+        }
+        Err(rejection) => return rejection.into_response(),
+    };
+
+    info!("Event processing");
+
+    let response = state
+        .data_transfer
+        .send_request(msg_request)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Request failed: {}", e);
+            DataSyncResponse::Error { msg: e.to_string() }
+        });
+
+    meta_response(StatusCode::OK, response)
+}
+
+fn unreadable_request(headers: &HeaderMap) -> UpgradeRequired {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    };
+
+    let client_protocol = header(PROTOCOL_VERSION_HEADER).and_then(|version| version.parse().ok());
+    UpgradeRequired::unreadable_request(client_protocol, header(PKG_VERSION_HEADER))
+}
+
+fn meta_response(status: StatusCode, response: DataSyncResponse) -> Response {
+    let version_header = [(PKG_VERSION_HEADER, CRATE_PKG_VERSION)];
+    (status, version_header, Json(response)).into_response()
+}
@@ -0,0 +1,38 @@
+use anyhow::{Context, Result};
+use axum_server::tls_rustls::RustlsConfig;
+
+use crate::config::TlsConfig;
+
+pub async fn rustls_config(tls: &TlsConfig) -> Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .with_context(|| format!("Failed to load TLS certificate {:?}", tls.cert_path))
+}
+
+/// Connections accepted after the reload use the new certificate, established ones are not affected
+pub async fn reload(rustls: &RustlsConfig, tls: &TlsConfig) -> Result<()> {
+    rustls
+        .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .with_context(|| format!("Failed to reload TLS certificate {:?}", tls.cert_path))
+}
+
+/// Re-reads the certificate and the key on every SIGHUP.
+/// The current certificate stays in use if the new files are broken.
+#[cfg(unix)]
+pub fn reload_on_sighup(rustls: RustlsConfig, tls: TlsConfig) -> Result<()> {
+    use tokio::signal::unix::{SignalKind, signal};
+    use tracing::{error, info};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match reload(&rustls, &tls).await {
+                Ok(()) => info!("TLS certificate reloaded: {:?}", tls.cert_path),
+                Err(e) => error!("{:?}", e),
+            }
+        }
+    });
+
+    Ok(())
+}
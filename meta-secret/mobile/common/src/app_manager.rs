@@ -2,9 +2,12 @@ use crate::log_timestamp;
 use anyhow::bail;
 use anyhow::Result;
 use meta_secret_core::crypto::keys::TransportSk;
-use meta_secret_core::node::api::{ReadSyncRequest, SsRecoveryCompletion, SyncRequest};
+use meta_secret_core::node::api::{
+    HandshakeResponse, ReadSyncRequest, SsRecoveryCompletion, SyncRequest,
+};
 use meta_secret_core::node::app::app_manager_shared::{
-    build_client_components, recover_plain_text, resolve_signup_vault_name,
+    build_client_components, find_audit_entries, recover_secret, resolve_signup_vault_name,
+    AuditQuery,
 };
 use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
 use meta_secret_core::node::app::meta_app::meta_client_service::MetaClientService;
@@ -15,15 +18,18 @@ use meta_secret_core::node::common::meta_tracing::client_span;
 use meta_secret_core::node::common::model::device::common::{DeviceName, DeviceType};
 use meta_secret_core::node::common::model::meta_pass::{MetaPasswordId, PlainPassInfo};
 use meta_secret_core::node::common::model::secret::{
-    ClaimId, SecretDistributionType, SsClaim, SsDistributionId, SsDistributionStatus, SsRecoveryId,
+    ClaimId, RecoveryTargets, SecretDistributionType, SsClaim, SsClaimId, SsDistributionId,
+    SsDistributionStatus, SsRecoveryId,
 };
 use meta_secret_core::node::common::model::user::common::UserData;
 use meta_secret_core::node::common::model::user::user_creds::UserCreds;
 use meta_secret_core::node::common::model::vault::vault::VaultName;
 use meta_secret_core::node::common::model::{ApplicationState, VaultFullInfo};
 use meta_secret_core::node::db::actions::sign_up::join::JoinActionUpdate;
+use meta_secret_core::node::db::events::audit_event::AuditEntry;
 use meta_secret_core::node::db::events::vault::vault_log_event::JoinClusterEvent;
 use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
+use meta_secret_core::secret::share_verification::VerifiedRecovery;
 use meta_secret_core::secret::shared_secret::PlainText;
 use std::sync::Arc;
 use std::thread;
@@ -100,9 +106,7 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
     ) -> Result<ApplicationManager<Repo, HttpSyncProtocol>> {
         println!("🦀Mobile App Manager: Initialize application state manager");
 
-        let sync_protocol = Arc::new(HttpSyncProtocol {
-            api_url: ApiUrl::prod(),
-        });
+        let sync_protocol = Arc::new(HttpSyncProtocol::new(ApiUrl::prod()));
 
         let app_manager = Self::client_setup(
             client_repo,
@@ -185,13 +189,8 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
             return Ok(None);
         };
 
-        let found_secret = member
-            .member
-            .vault
-            .secrets
-            .iter()
-            .find(|secret| secret.id.text.base64_str() == secret_id)
-            .cloned();
+        // the mobile apps address the secrets by name, the ids are random
+        let found_secret = member.member.vault.find_secret_by_name(secret_id).ok();
 
         println!(
             "🦀 Mobile App Manager: Looking for secret with id: {}, found: {:?}",
@@ -203,7 +202,7 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
 
     pub async fn recover_js(&self, meta_pass_id: MetaPasswordId) {
         println!("🦀 Mobile App Manager: recover");
-        let request = GenericAppStateRequest::Recover(meta_pass_id);
+        let request = GenericAppStateRequest::Recover(meta_pass_id, RecoveryTargets::All);
         self.meta_client_service
             .send_request(request)
             .await
@@ -288,6 +287,22 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
         Ok(())
     }
 
+    /// Withdraws a recovery request of this device
+    pub async fn cancel_recovery(&self, claim_id: ClaimId) -> Result<()> {
+        println!("🦀 Mobile App Manager: Cancel recovery");
+        let request = GenericAppStateRequest::CancelRecovery(claim_id);
+        self.meta_client_service.send_request(request).await?;
+        Ok(())
+    }
+
+    /// Stops the escrow device from releasing its share of a break-glass recovery
+    pub async fn veto_recovery(&self, claim_id: ClaimId) -> Result<()> {
+        println!("🦀 Mobile App Manager: Veto recovery");
+        let request = GenericAppStateRequest::VetoRecovery(claim_id);
+        self.meta_client_service.send_request(request).await?;
+        Ok(())
+    }
+
     pub async fn send_decline_completion(&self, claim_id: ClaimId) -> Result<()> {
         println!("🦀 Mobile App Manager: Send decline completion");
         let user_creds = self.meta_client_service.find_user_creds().await?;
@@ -316,9 +331,13 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
                 anyhow::anyhow!("No declined receiver found for claim: {:?}", claim_id)
             })?;
         let vault_name = user_creds.vault_name.clone();
-        let pass_id = claim.dist_claim_id.pass_id.clone();
+        // The server only gets the password id, the name stays on the device
+        let pass_id = claim.dist_claim_id.pass_id.opaque();
         let recovery_id = SsRecoveryId {
-            claim_id: claim.dist_claim_id.clone(),
+            claim_id: SsClaimId {
+                id: claim.dist_claim_id.id.clone(),
+                pass_id: pass_id.clone(),
+            },
             sender: claim.sender.clone(),
             distribution_id: SsDistributionId {
                 pass_id,
@@ -352,13 +371,13 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
         candidate: UserData,
         upd: JoinActionUpdate,
     ) -> Result<()> {
-        let join_request = JoinClusterEvent { candidate };
+        let join_request = JoinClusterEvent::from(candidate);
         self.meta_client_service
             .update_membership(join_request, upd)
             .await
     }
 
-    pub async fn show_recovered(&self, pass_id: MetaPasswordId) -> Result<PlainText> {
+    pub async fn show_recovered(&self, pass_id: MetaPasswordId) -> Result<VerifiedRecovery> {
         let user_creds = self.meta_client_service.find_user_creds().await?;
         let state = self.get_state().await?;
 
@@ -378,7 +397,11 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
 
                     if vault_members_count == 1 {
                         println!("🦀 Mobile App Manager: Single device mode, showing local secret");
-                        return self.show_local_secret(user_creds, pass_id).await;
+                        let secret = self.show_local_secret(user_creds, pass_id).await?;
+                        return Ok(VerifiedRecovery {
+                            secret,
+                            invalid: vec![],
+                        });
                     }
 
                     let claim_id = self.find_claim_id_by_pass_id(&pass_id).await;
@@ -388,7 +411,7 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
                             bail!("Claim id not found");
                         }
                         Some(claim_id) => {
-                            let pass = recover_plain_text(
+                            let recovery = recover_secret(
                                 self.sync_gateway.as_ref(),
                                 user_creds.clone(),
                                 claim_id.clone(),
@@ -404,10 +427,13 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
                                 let device_id = user_creds.device_id();
 
                                 let recovery_id = SsRecoveryId {
-                                    claim_id: claim.dist_claim_id.clone(),
+                                    claim_id: SsClaimId {
+                                        id: claim.dist_claim_id.id.clone(),
+                                        pass_id: pass_id.opaque(),
+                                    },
                                     sender: claim.sender.clone(),
                                     distribution_id: SsDistributionId {
-                                        pass_id: pass_id.clone(),
+                                        pass_id: pass_id.opaque(),
                                         receiver: device_id.clone(),
                                     },
                                 };
@@ -429,7 +455,7 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
                                 }
                             }
 
-                            Ok(pass)
+                            Ok(recovery)
                         }
                     }
                 }
@@ -469,6 +495,15 @@ impl<Repo: KvLogEventRepo + Send + Sync + 'static, SyncP: SyncProtocol + Send +
         Ok(plain_text)
     }
 
+    pub async fn audit_list(&self, query: AuditQuery) -> Result<Vec<AuditEntry>> {
+        let state = self.get_state().await?;
+        find_audit_entries(self.sync_gateway.p_obj.clone(), &state, &query).await
+    }
+
+    pub async fn handshake(&self) -> Result<HandshakeResponse> {
+        self.sync_gateway.handshake().await
+    }
+
     pub async fn clean_up_database(&self) {
         self.sync_gateway.p_obj.repo.db_clean_up().await
     }
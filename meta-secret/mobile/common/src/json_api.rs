@@ -2,6 +2,8 @@
 
 use meta_secret_core::crypto::key_pair::MasterKeyManager;
 use meta_secret_core::crypto::utils::Id48bit;
+use meta_secret_core::node::api::UpgradeRequired;
+use meta_secret_core::node::app::app_manager_shared::AuditQuery;
 use meta_secret_core::node::common::model::meta_pass::{MetaPasswordId, PlainPassInfo};
 use meta_secret_core::node::common::model::secret::ClaimId;
 use meta_secret_core::node::common::model::device::common::{DeviceName, DeviceType};
@@ -184,7 +186,12 @@ async fn async_split_secret(secret_id: String, secret: String) -> String {
     match MobileApplicationManager::get_global_instance() {
         Some(app_manager) => {
             let meta_pass_id = MetaPasswordId::build_from_str(&secret_id);
-            let plan_pass_info = PlainPassInfo { pass_id: meta_pass_id, pass: secret };
+            let plan_pass_info = PlainPassInfo {
+                pass_id: meta_pass_id,
+                pass: secret,
+                recovery_policy: None,
+                break_glass: None,
+            };
             app_manager.cluster_distribution(&plan_pass_info).await;
             json!({"success": true}).to_string()
         }
@@ -199,7 +206,10 @@ pub fn find_claim_by(secret_id: String) -> String {
 async fn async_find_claim_by(secret_id: String) -> String {
     match MobileApplicationManager::get_global_instance() {
         Some(app_manager) => {
-            let meta_password_id = MetaPasswordId::build_from_str(&secret_id);
+            let meta_password_id = match app_manager.find_pass_id(&secret_id).await {
+                Ok(pass_id) => pass_id,
+                Err(e) => return json!({"success": false, "error": format!("{}", e)}).to_string(),
+            };
             match app_manager.find_claim_by_pass_id(&meta_password_id).await {
                 Some(claim) => json!({"success": true, "message": {"claim": claim}}).to_string(),
                 None => json!({"success": false, "error": "Claim has not been found"}).to_string(),
@@ -216,7 +226,10 @@ pub fn find_claim_id_by(secret_id: String) -> String {
 async fn async_find_claim_id_by(secret_id: String) -> String {
     match MobileApplicationManager::get_global_instance() {
         Some(app_manager) => {
-            let meta_password_id = MetaPasswordId::build_from_str(&secret_id);
+            let meta_password_id = match app_manager.find_pass_id(&secret_id).await {
+                Ok(pass_id) => pass_id,
+                Err(e) => return json!({"success": false, "error": format!("{}", e)}).to_string(),
+            };
             match app_manager.find_claim_id_by_pass_id(&meta_password_id).await {
                 Some(claim) => json!({"success": true, "message": {"claim": claim}}).to_string(),
                 None => json!({"success": false, "error": "Claim has not been found"}).to_string(),
@@ -233,7 +246,10 @@ pub fn recover(secret_id: String) -> String {
 async fn async_recover(secret_id: String) -> String {
     match MobileApplicationManager::get_global_instance() {
         Some(app_manager) => {
-            let meta_password_id = MetaPasswordId::build_from_str(&secret_id);
+            let meta_password_id = match app_manager.find_pass_id(&secret_id).await {
+                Ok(pass_id) => pass_id,
+                Err(e) => return json!({"success": false, "error": format!("{}", e)}).to_string(),
+            };
             app_manager.recover(&meta_password_id).await;
             json!({"success": true}).to_string()
         }
@@ -292,6 +308,57 @@ async fn async_send_decline_completion(claim_id: String) -> String {
     }
 }
 
+pub fn cancel_recovery(claim_id: String) -> String {
+    MobileApplicationManager::sync_wrapper(async_cancel_recovery(claim_id))
+}
+
+async fn async_cancel_recovery(claim_id: String) -> String {
+    match MobileApplicationManager::get_global_instance() {
+        Some(app_manager) => {
+            let meta_claim_id = ClaimId::from(Id48bit::from(claim_id));
+            match app_manager.cancel_recovery(meta_claim_id).await {
+                Ok(_) => json!({"success": true}).to_string(),
+                Err(e) => json!({"success": false, "error": format!("Cancel recovery failed: {}", e)}).to_string(),
+            }
+        }
+        None => json!({"success": false, "error": "Cancel recovery request is failed"}).to_string(),
+    }
+}
+
+pub fn veto_recovery(claim_id: String) -> String {
+    MobileApplicationManager::sync_wrapper(async_veto_recovery(claim_id))
+}
+
+async fn async_veto_recovery(claim_id: String) -> String {
+    match MobileApplicationManager::get_global_instance() {
+        Some(app_manager) => {
+            let meta_claim_id = ClaimId::from(Id48bit::from(claim_id));
+            match app_manager.veto_recovery(meta_claim_id).await {
+                Ok(_) => json!({"success": true}).to_string(),
+                Err(e) => json!({"success": false, "error": format!("Veto recovery failed: {}", e)}).to_string(),
+            }
+        }
+        None => json!({"success": false, "error": "Veto recovery request is failed"}).to_string(),
+    }
+}
+
+pub fn check_protocol() -> String {
+    MobileApplicationManager::sync_wrapper(async_check_protocol())
+}
+
+async fn async_check_protocol() -> String {
+    match MobileApplicationManager::get_global_instance() {
+        Some(app_manager) => match app_manager.handshake().await {
+            Ok(handshake) => json!({"success": true, "message": {"handshake": handshake}}).to_string(),
+            Err(e) => match e.downcast_ref::<UpgradeRequired>() {
+                Some(upgrade) => json!({"success": false, "error": format!("{}", e), "upgradeRequired": upgrade}).to_string(),
+                None => json!({"success": false, "error": format!("{}", e)}).to_string(),
+            },
+        },
+        None => json!({"success": false, "error": "App manager is not initialized"}).to_string(),
+    }
+}
+
 pub fn show_recovered(secret_id: String) -> String {
     MobileApplicationManager::sync_wrapper(async_show_recovered(secret_id))
 }
@@ -299,12 +366,37 @@ pub fn show_recovered(secret_id: String) -> String {
 async fn async_show_recovered(secret_id: String) -> String {
     match MobileApplicationManager::get_global_instance() {
         Some(app_manager) => {
-            let meta_password_id = MetaPasswordId::build_from_str(&secret_id);
+            let meta_password_id = match app_manager.find_pass_id(&secret_id).await {
+                Ok(pass_id) => pass_id,
+                Err(e) => return json!({"success": false, "error": format!("{}", e)}).to_string(),
+            };
             match app_manager.show_recovered(&meta_password_id).await {
-                Ok(secret) => json!({"success": true, "message": {"secret": secret}}).to_string(),
+                Ok(recovery) => json!({
+                    "success": true,
+                    "message": {"secret": recovery.secret.text, "invalidShares": recovery.invalid}
+                })
+                .to_string(),
                 Err(e) => json!({"success": false, "error": format!("{}", e)}).to_string(),
             }
         }
         None => json!({"success": false, "error": "Show recovered request is failed"}).to_string(),
     }
 }
+
+pub fn audit_list(filter: String) -> String {
+    MobileApplicationManager::sync_wrapper(async_audit_list(filter))
+}
+
+async fn async_audit_list(filter: String) -> String {
+    let query: AuditQuery = match serde_json::from_str(&filter) {
+        Ok(query) => query,
+        Err(e) => return json!({"success": false, "error": format!("Failed to parse an audit filter: {}", e)}).to_string(),
+    };
+    match MobileApplicationManager::get_global_instance() {
+        Some(app_manager) => match app_manager.audit_list(query).await {
+            Ok(entries) => json!({"success": true, "message": {"entries": entries}}).to_string(),
+            Err(e) => json!({"success": false, "error": format!("{}", e)}).to_string(),
+        },
+        None => json!({"success": false, "error": "App manager is not initialized"}).to_string(),
+    }
+}
@@ -3,6 +3,8 @@ use anyhow::{bail, Result};
 use meta_db_sqlite::db::sqlite_migration::EmbeddedMigrationsTool;
 use meta_db_sqlite::db::sqlite_store::SqlIteRepo;
 use meta_secret_core::crypto::keys::TransportSk;
+use meta_secret_core::node::api::HandshakeResponse;
+use meta_secret_core::node::app::app_manager_shared::AuditQuery;
 use meta_secret_core::node::app::sync::sync_protocol::HttpSyncProtocol;
 use meta_secret_core::node::common::model::device::common::{DeviceName, DeviceType};
 use meta_secret_core::node::common::model::meta_pass::{MetaPasswordId, PlainPassInfo};
@@ -11,6 +13,8 @@ use meta_secret_core::node::common::model::user::common::UserData;
 use meta_secret_core::node::common::model::vault::vault::VaultName;
 use meta_secret_core::node::common::model::ApplicationState;
 use meta_secret_core::node::db::actions::sign_up::join::JoinActionUpdate;
+use meta_secret_core::node::db::events::audit_event::AuditEntry;
+use meta_secret_core::secret::share_verification::VerifiedRecovery;
 use once_cell::sync::Lazy;
 use std::fs;
 use std::future::Future;
@@ -190,6 +194,18 @@ impl MobileApplicationManager {
             .await;
     }
 
+    /// The password of the vault with the name (or id) the mobile app has given
+    pub async fn find_pass_id(&self, secret_id: &str) -> Result<MetaPasswordId> {
+        match self
+            .app_manager
+            .find_meta_password_id_by_secret_id(secret_id)
+            .await?
+        {
+            Some(pass_id) => Ok(pass_id),
+            None => bail!("Secret '{}' is not found in the vault", secret_id),
+        }
+    }
+
     pub async fn recover(&self, meta_pass_id: &MetaPasswordId) {
         self.app_manager.recover_js(meta_pass_id.clone()).await;
     }
@@ -208,6 +224,14 @@ impl MobileApplicationManager {
         self.app_manager.send_decline_completion(claim_id).await
     }
 
+    pub async fn cancel_recovery(&self, claim_id: ClaimId) -> Result<()> {
+        self.app_manager.cancel_recovery(claim_id).await
+    }
+
+    pub async fn veto_recovery(&self, claim_id: ClaimId) -> Result<()> {
+        self.app_manager.veto_recovery(claim_id).await
+    }
+
     pub async fn accept_recover(&self, claim_id: ClaimId) {
         match self.app_manager.accept_recover(claim_id).await {
             Ok(res) => res,
@@ -217,9 +241,16 @@ impl MobileApplicationManager {
         };
     }
 
-    pub async fn show_recovered(&self, pass_id: &MetaPasswordId) -> Result<String> {
-        let plain_text = self.app_manager.show_recovered(pass_id.clone()).await?;
-        Ok(plain_text.text)
+    pub async fn show_recovered(&self, pass_id: &MetaPasswordId) -> Result<VerifiedRecovery> {
+        self.app_manager.show_recovered(pass_id.clone()).await
+    }
+
+    pub async fn audit_list(&self, query: AuditQuery) -> Result<Vec<AuditEntry>> {
+        self.app_manager.audit_list(query).await
+    }
+
+    pub async fn handshake(&self) -> Result<HandshakeResponse> {
+        self.app_manager.handshake().await
     }
 
     pub async fn clean_up_database(&self) {
@@ -288,9 +319,9 @@ impl MobileApplicationManager {
 
         let app_manager = ApplicationManager::<SqlIteRepo, HttpSyncProtocol>::client_setup(
             client_repo,
-            Arc::new(HttpSyncProtocol {
-                api_url: meta_secret_core::node::app::sync::api_url::ApiUrl::prod(),
-            }),
+            Arc::new(HttpSyncProtocol::new(
+                meta_secret_core::node::app::sync::api_url::ApiUrl::prod(),
+            )),
             master_key,
             device_name,
             device_type,
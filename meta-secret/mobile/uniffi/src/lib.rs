@@ -66,6 +66,10 @@ pub fn update_membership(candidate: String, action_update: String) -> String {
     json_api::update_membership(candidate, action_update)
 }
 
+pub fn check_protocol() -> String {
+    json_api::check_protocol()
+}
+
 pub fn clean_up_database() -> String {
     json_api::clean_up_database()
 }
@@ -98,10 +102,22 @@ pub fn send_decline_completion(claim_id: String) -> String {
     json_api::send_decline_completion(claim_id)
 }
 
+pub fn cancel_recovery(claim_id: String) -> String {
+    json_api::cancel_recovery(claim_id)
+}
+
+pub fn veto_recovery(claim_id: String) -> String {
+    json_api::veto_recovery(claim_id)
+}
+
 pub fn show_recovered(secret_id: String) -> String {
     json_api::show_recovered(secret_id)
 }
 
+pub fn audit_list(filter: String) -> String {
+    json_api::audit_list(filter)
+}
+
 #[cfg(test)]
 mod device_ui_category_ffi_tests {
     use super::device_ui_category_discriminant;
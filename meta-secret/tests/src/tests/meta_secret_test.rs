@@ -56,12 +56,19 @@ mod test {
     use meta_secret_core::meta_tests::fixture_util::fixture::FixtureRegistry;
     use meta_secret_core::meta_tests::fixture_util::fixture::states::EmptyState;
     use meta_secret_core::meta_tests::spec::test_spec::TestSpec;
+    use meta_secret_core::node::api::{
+        HandshakeRequest, PageCursor, ProtocolInfo, ReadSyncRequest, ServerTailRequest,
+        SignedEvent, SyncRequest, VaultRequest, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+    };
+    use meta_secret_core::node::app::app_manager_shared::{AuditQuery, find_audit_entries};
     use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
+    use meta_secret_core::node::app::auto_approval::{AutoApprovalPolicy, AutoApprover, JoinRule};
     use meta_secret_core::node::app::orchestrator::MetaOrchestrator;
     use meta_secret_core::node::app::sync::sync_gateway::SyncGateway;
+    use meta_secret_core::node::common::clock::{unix_time_millis, EventTime, HlcTimestamp};
     use meta_secret_core::node::common::meta_tracing::{client_span, server_span, vd_span};
     use meta_secret_core::node::common::model::crypto::aead::EncryptedMessage;
-    use meta_secret_core::node::common::model::device::common::DeviceName;
+    use meta_secret_core::node::common::model::device::common::{DeviceId, DeviceName};
     use meta_secret_core::node::common::model::device::device_creds::{
         DeviceCreds, DeviceCredsBuilder,
     };
@@ -69,12 +76,17 @@ mod test {
         MetaPasswordId, PlainPassInfo, SecurePassInfo,
     };
     use meta_secret_core::node::common::model::secret::{
-        ClaimId, SecretDistributionType, SsClaim, SsDistributionId, SsDistributionStatus,
+        BreakGlass, BreakGlassPolicy, ClaimId, RecoveryFallback, RecoveryPolicy, RecoveryTargets,
+        SecretDistributionData, SecretDistributionType, SsApprovalData, SsCancelClaimData, SsClaim,
+        SsClaimId, SsDistributionCompositeStatus, SsDistributionId, SsDistributionStatus,
+        SsVetoData,
     };
     use meta_secret_core::node::common::model::user::common::{
         UserData, UserDataMember, UserMembership,
     };
+    use meta_secret_core::node::common::model::user::user_creds::UserCreds;
     use meta_secret_core::node::common::model::user::user_creds::fixture::UserCredentialsFixture;
+    use meta_secret_core::node::common::model::vault::invite::{VaultInvite, DEFAULT_INVITE_TTL};
     use meta_secret_core::node::common::model::vault::vault::{
         VaultMember, VaultName, VaultStatus,
     };
@@ -85,19 +97,38 @@ mod test {
     use meta_secret_core::node::db::actions::sign_up::claim::test_action::SignUpClaimTestAction;
     use meta_secret_core::node::db::actions::sign_up::join::JoinActionUpdate;
     use meta_secret_core::node::db::descriptors::shared_secret_descriptor::{
-        SsDeviceLogDescriptor, SsWorkflowDescriptor,
+        SsDeviceLogDescriptor, SsLogDescriptor, SsWorkflowDescriptor,
+    };
+    use meta_secret_core::node::db::descriptors::object_descriptor::ToObjectDescriptor;
+    use meta_secret_core::node::db::descriptors::vault_descriptor::DeviceLogDescriptor;
+    use meta_secret_core::node::db::events::generic_log_event::{GenericKvLogEvent, ToGenericEvent};
+    use meta_secret_core::node::db::events::kv_log_event::{KvKey, KvLogEvent};
+    use meta_secret_core::node::db::events::audit_event::AuditAction;
+    use meta_secret_core::node::db::events::object_id::Next;
+    use meta_secret_core::node::db::events::shared_secret_event::{
+        SsDeviceLogObject, SsLogObject, SsWorkflowObject,
     };
-    use meta_secret_core::node::db::events::generic_log_event::GenericKvLogEvent;
-    use meta_secret_core::node::db::events::shared_secret_event::SsWorkflowObject;
+    use meta_secret_core::node::db::events::vault::device_log_event::DeviceLogObject;
     use meta_secret_core::node::db::events::vault::vault_log_event::{
-        JoinClusterEvent, VaultActionRequestEvent,
+        AddInviteEvent, AddMetaPassEvent, JoinClusterEvent, RotateDeviceKeysEvent,
+        UpdateVaultSettingsEvent, VaultActionEvent, VaultActionRequestEvent, VaultActionUpdateEvent,
     };
     use meta_secret_core::node::db::in_mem_db::InMemKvLogEventRepo;
+    use meta_secret_core::node::db::objects::persistent_audit::{AuditFilter, PersistentAudit};
     use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
+    use meta_secret_core::node::db::repo::persistent_credentials::PersistentCredentials;
     use meta_secret_core::node::db::objects::persistent_shared_secret::PersistentSharedSecret;
+    use meta_secret_core::node::db::objects::persistent_vault::{PersistentVault, VaultTail};
     use meta_secret_core::recover_from_shares;
     use meta_secret_core::secret::MetaDistributor;
-    use meta_secret_core::secret::shared_secret::UserShareDto;
+    use meta_secret_core::secret::shared_secret::{PlainText, UserShareDto};
+    use meta_secret_core::node::db::repo::generic_db::SaveCommand;
+    use meta_server_node::server::admin::{ServerAdmin, REDACTED};
+    use meta_server_node::server::validation::{EventRejection, EventValidator, MAX_EVENT_SIZE};
+    use meta_secret_core::crypto::key_pair::KeyPair;
+    use meta_secret_core::crypto::keys::KeyManager;
+    use meta_secret_core::crypto::utils::Id48bit;
+    use std::sync::Arc;
     use tracing::{Instrument, info};
 
     struct ServerAppSignUpSpec {
@@ -119,6 +150,25 @@ mod test {
         }
 
         async fn sign_up_and_second_devices_joins(&self) -> Result<()> {
+            let join_request = self.sign_up_and_request_join().await?;
+
+            self.registry
+                .state
+                .vd
+                .orchestrator
+                .update_membership(join_request, JoinActionUpdate::Accept)
+                .await?;
+            self.vd_gw_sync().await?;
+            self.client_gw_sync().await?;
+
+            //accept join request by vd
+            assert_eq!(2, self.vd_vault_members().await?);
+
+            Ok(())
+        }
+
+        /// Signs up the vault on vd and returns the join request of the client
+        async fn sign_up_and_request_join(&self) -> Result<JoinClusterEvent> {
             //setup_tracing()?;
 
             self.init_server().await?;
@@ -144,8 +194,9 @@ mod test {
                 .instrument(client_span())
                 .await?;
 
+            // including the audit entry of the vault creation and the pinned audit key
             let vd_db = self.registry.state.vd.p_obj.repo.get_db().await;
-            assert_eq!(7, vd_db.len());
+            assert_eq!(9, vd_db.len());
 
             self.registry
                 .state
@@ -184,16 +235,10 @@ mod test {
                 bail!("Join request is not found");
             };
 
-            self.registry
-                .state
-                .vd
-                .orchestrator
-                .update_membership(join_request.clone(), JoinActionUpdate::Accept)
-                .await?;
-            self.vd_gw_sync().await?;
-            self.client_gw_sync().await?;
+            Ok(join_request.clone())
+        }
 
-            //accept join request by vd
+        async fn vd_vault_members(&self) -> Result<usize> {
             let vault_status = self
                 .registry
                 .state
@@ -214,9 +259,7 @@ mod test {
                 .get_vault(member.user_data.vault_name())
                 .await?;
 
-            assert_eq!(2, vd_vault_obj.to_data().users.len());
-
-            Ok(())
+            Ok(vd_vault_obj.to_data().members().len())
         }
 
         async fn client_gw_sync(&self) -> Result<()> {
@@ -264,7 +307,8 @@ mod test {
 
             let server_db = server_p_obj.repo.get_db().await;
 
-            assert_eq!(6, server_db.len());
+            // including the audit entry of the vault creation
+            assert_eq!(7, server_db.len());
 
             let server_claim_spec = SignUpClaimSpec {
                 p_obj: server_p_obj.clone(),
@@ -287,7 +331,7 @@ mod test {
     }
 
     impl SplitSpec {
-        async fn split(&self) -> Result<()> {
+        async fn split(&self) -> Result<MetaPasswordId> {
             let client_client_service = self.spec.registry.state.client.client_service.clone();
             let app_state = client_client_service.build_service_state().await?.app_state;
 
@@ -295,6 +339,8 @@ mod test {
             let plain_pass = PlainPassInfo {
                 pass_id: pass_id.clone(),
                 pass: "2bee|~".to_string(),
+                recovery_policy: None,
+                break_glass: None,
             };
             let dist_request = GenericAppStateRequest::ClusterDistribution(plain_pass);
 
@@ -308,9 +354,23 @@ mod test {
             };
 
             assert_eq!(1, member.member.vault.secrets.len());
+            assert_eq!(
+                "test_pass",
+                member.member.vault.find_secret(&pass_id).name,
+                "The device must open the sealed name of its password"
+            );
 
             self.vd_gw_sync().await?;
 
+            // the server knows the password by its id only
+            let server_vault = PersistentVault::from(self.spec.empty_state().p_obj.server.clone())
+                .get_vault(self.spec.user_creds().client.vault_name.clone())
+                .await?
+                .to_data();
+            let server_pass_id = server_vault.find_secret(&pass_id);
+            assert!(!server_pass_id.has_clear_name());
+            assert!(server_pass_id.sealed_name.is_some());
+
             // let client_db: HashMap<ArtifactId, GenericKvLogEvent> =
             //     self.sign_up.vd.p_obj.repo.get_db().await;
             // for (id, event) in client_db {
@@ -386,7 +446,7 @@ mod test {
             //let new_app_state_json = serde_json::to_string_pretty(&new_app_state)?;
             //println!("{}", new_app_state_json);
 
-            Ok(())
+            Ok(pass_id)
         }
 
         async fn vd_gw_sync(&self) -> Result<()> {
@@ -480,6 +540,48 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_vd_auto_approval_policy() -> Result<()> {
+        let spec = ServerAppSignUpSpec::build().await?;
+        spec.sign_up_and_request_join().await?;
+
+        let vd_orchestrator = &spec.registry.state.vd.orchestrator;
+
+        // the client is neither a hardware key nor a device of the list
+        let restrictive = AutoApprover::new(AutoApprovalPolicy::from_json(
+            r#"{"join": {"deviceTypes": ["HardwareKey"], "deviceNames": ["backup"]}}"#,
+        )?);
+        vd_orchestrator.orchestrate_with(&restrictive).await?;
+        spec.vd_gw_sync().await?;
+        assert_eq!(1, spec.vd_vault_members().await?);
+
+        let vd_app_state = spec
+            .registry
+            .state
+            .vd
+            .client_service
+            .get_app_state()
+            .await?;
+        let ApplicationState::Vault(VaultFullInfo::Member(vd_member_info)) = vd_app_state else {
+            bail!("Vd is not a vault member");
+        };
+        assert_eq!(1, vd_member_info.vault_events.requests.len());
+
+        let client_name = spec.user_creds().client.device().device_name;
+        let allow_client = AutoApprover::new(AutoApprovalPolicy {
+            join: JoinRule {
+                device_names: vec![client_name],
+                ..JoinRule::default()
+            },
+            ..AutoApprovalPolicy::default()
+        });
+        vd_orchestrator.orchestrate_with(&allow_client).await?;
+        spec.vd_gw_sync().await?;
+        assert_eq!(2, spec.vd_vault_members().await?);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_secret_split() -> Result<()> {
         let spec = ServerAppSignUpSpec::build().await?;
@@ -491,6 +593,1347 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_audit_trail_of_vault_actions() -> Result<()> {
+        let spec = ServerAppSignUpSpec::build().await?;
+        let split = SplitSpec { spec };
+
+        split.spec.sign_up_and_second_devices_joins().await?;
+        split.split().await?;
+        split.spec.client_gw_sync().await?;
+
+        let client = &split.spec.registry.state.client;
+        let client_device = client.device_id();
+        let vd_device = split.spec.registry.state.vd.device_id();
+
+        let app_state = client.client_service.get_app_state().await?;
+        let entries =
+            find_audit_entries(client.p_obj.clone(), &app_state, &AuditQuery::default()).await?;
+        let actions: Vec<(DeviceId, AuditAction)> = entries
+            .into_iter()
+            .map(|entry| (entry.device, entry.action))
+            .collect();
+
+        assert_eq!(actions[0], (vd_device.clone(), AuditAction::VaultCreated));
+        assert!(actions.contains(&(client_device.clone(), AuditAction::JoinRequested)));
+        assert!(actions.contains(&(
+            vd_device.clone(),
+            AuditAction::JoinAccepted {
+                candidate: client_device.clone()
+            }
+        )));
+
+        let secret_added = actions
+            .iter()
+            .find_map(|(device, action)| match action {
+                AuditAction::SecretAdded { pass_id } => Some((device, pass_id)),
+                _ => None,
+            })
+            .expect("Secret addition is not audited");
+        assert_eq!(client_device, *secret_added.0);
+        assert_eq!("test_pass", secret_added.1.name);
+
+        assert!(actions.iter().any(|(device, action)| {
+            device.eq(&vd_device)
+                && matches!(
+                    action,
+                    AuditAction::ShareDelivered {
+                        distribution_type: SecretDistributionType::Split,
+                        ..
+                    }
+                )
+        }));
+
+        // the filters of the audit query
+        let client_query = AuditQuery {
+            device: Some(client_device.to_string()),
+            ..AuditQuery::default()
+        };
+        let client_entries =
+            find_audit_entries(client.p_obj.clone(), &app_state, &client_query).await?;
+        assert!(!client_entries.is_empty());
+        assert!(client_entries.iter().all(|entry| entry.device.eq(&client_device)));
+
+        let secret_query = AuditQuery {
+            secret: Some("test_pass".to_string()),
+            ..AuditQuery::default()
+        };
+        let secret_entries =
+            find_audit_entries(client.p_obj.clone(), &app_state, &secret_query).await?;
+        assert!(!secret_entries.is_empty());
+        assert!(secret_entries.iter().all(|entry| entry.action.pass_id().is_some()));
+
+        // the client has pinned the audit key of the server on the handshake
+        let server_app = split.spec.registry.state.server_app.server_app.clone();
+        let server_audit_key = server_app.get_creds().await?.device.keys.dsa_pk;
+        let client_audit = PersistentAudit::from(client.p_obj.clone());
+        assert_eq!(
+            client_audit.find_pinned_key().await?,
+            Some(server_audit_key.clone())
+        );
+
+        // the server records password ids only
+        let server_audit = PersistentAudit::from(split.spec.empty_state().p_obj.server.clone());
+        server_audit.pin_key(server_audit_key).await?;
+        let server_entries = server_audit
+            .find(client.user.vault_name(), &AuditFilter::default())
+            .await?;
+        assert!(server_entries
+            .iter()
+            .filter_map(|entry| entry.action.pass_id())
+            .all(|pass_id| !pass_id.has_clear_name() && pass_id.sealed_name.is_none()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_events_are_timestamped() -> Result<()> {
+        let spec = ServerAppSignUpSpec::build().await?;
+        let split = SplitSpec { spec };
+
+        split.spec.sign_up_and_second_devices_joins().await?;
+        split.split().await?;
+        split.spec.client_gw_sync().await?;
+
+        let client = &split.spec.registry.state.client;
+        let server_p_obj = split.spec.empty_state().p_obj.server.clone();
+
+        // the events of the client keep the time of the client and get the receive time of the server
+        let device_log: Vec<DeviceLogObject> = server_p_obj
+            .get_object_events_from_beginning(DeviceLogDescriptor::from(client.user.user_id()))
+            .await?;
+        assert!(!device_log.is_empty());
+        for event in &device_log {
+            let time = event.0.time.as_ref().expect("Device log event has no time");
+            let received_at = time.received_at.expect("Device log event has no receive time");
+            assert!(time.created_at <= received_at);
+        }
+        let hlc: Vec<HlcTimestamp> = device_log
+            .iter()
+            .filter_map(|event| event.0.time.as_ref().map(|time| time.hlc))
+            .collect();
+        assert!(hlc.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // the claims are timestamped by the sender and the server
+        let ss_log: Vec<SsLogObject> = server_p_obj
+            .get_object_events_from_beginning(SsLogDescriptor::from(client.user.vault_name()))
+            .await?;
+        let claim = ss_log
+            .into_iter()
+            .find_map(|event| event.to_data().claims.into_values().next())
+            .expect("No split claim");
+        let claim_time = claim.time.as_ref().expect("Claim has no time");
+        assert!(claim_time.received_at.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_admin_inspects_and_purges_vault() -> Result<()> {
+        let spec = ServerAppSignUpSpec::build().await?;
+        let split = SplitSpec { spec };
+        split.spec.sign_up_and_second_devices_joins().await?;
+        let pass_id = split.split().await?;
+
+        let state = &split.spec.registry.state;
+        let vault_name = state.client.user.vault_name.clone();
+        let server_repo = state.base.empty.p_obj.server.repo.clone();
+        let admin = ServerAdmin::new(server_repo.clone());
+
+        let vaults = admin.list_vaults().await?;
+        assert_eq!(vaults.len(), 1);
+        assert_eq!(vaults[0].vault_name, vault_name);
+        assert_eq!(vaults[0].secrets, 1);
+
+        let members = admin.vault_members(vault_name.clone()).await?;
+        assert_eq!(members.len(), vaults[0].members + vaults[0].outsiders);
+        assert!(members.iter().any(|member| member.status == "Member"));
+
+        // the split is delivered to every device, so the claim is completed and no longer open
+        assert!(admin.ss_claims(vault_name.clone()).await?.is_empty());
+
+        // a leftover copy of the delivered vd share on the server is stale
+        let vd_receiver = state.vd.device_id();
+        let vd_dist_desc = SsWorkflowDescriptor::Distribution(SsDistributionId {
+            pass_id,
+            receiver: vd_receiver,
+        });
+        let stale_share = state.vd.p_obj.find_tail_event(vd_dist_desc.clone()).await?.unwrap();
+        server_repo.save(stale_share.clone()).await?;
+
+        let redacted = serde_json::to_string(&admin.export_vault(vault_name.clone(), false).await?)?;
+        assert!(redacted.contains(REDACTED));
+        let SsWorkflowObject::Distribution(share_event) = stale_share else {
+            bail!("Expected split distribution event");
+        };
+        let EncryptedMessage::CipherShare { share } = share_event.value.secret_message;
+        let cipher_text = serde_json::to_string(&share.msg)?;
+        assert!(!redacted.contains(&cipher_text));
+
+        let full = serde_json::to_string(&admin.export_vault(vault_name.clone(), true).await?)?;
+        assert!(full.contains(&cipher_text));
+
+        let dry_run = admin.purge_stale_workflow_events(vault_name.clone(), true).await?;
+        assert_eq!(dry_run.len(), 1);
+        let server_p_obj = PersistentObject::new(server_repo);
+        assert!(server_p_obj.find_tail_event(vd_dist_desc.clone()).await?.is_some());
+
+        let purged = admin.purge_stale_workflow_events(vault_name, false).await?;
+        assert_eq!(purged, dry_run);
+        assert!(server_p_obj.find_tail_event(vd_dist_desc).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_gc_deletes_delivered_shares() -> Result<()> {
+        let spec = ServerAppSignUpSpec::build().await?;
+        let split = SplitSpec { spec };
+        split.spec.sign_up_and_second_devices_joins().await?;
+        let pass_id = split.split().await?;
+
+        let state = &split.spec.registry.state;
+        let server_app = state.server_app.server_app.clone();
+        let server_p_obj = PersistentObject::new(state.base.empty.p_obj.server.repo.clone());
+
+        // a delivered share that no client has removed from the server
+        let vd_dist_desc = SsWorkflowDescriptor::Distribution(SsDistributionId {
+            pass_id,
+            receiver: state.vd.device_id(),
+        });
+        let stale_share = state.vd.p_obj.find_tail_event(vd_dist_desc.clone()).await?.unwrap();
+        server_p_obj.repo.save(stale_share).await?;
+
+        let report = server_app.collect_garbage(0).await?;
+        assert!(report.expired_claims.is_empty());
+        assert_eq!(report.deleted_events.len(), 1);
+        assert!(server_p_obj.find_tail_event(vd_dist_desc).await?.is_none());
+
+        // nothing is left to collect
+        assert!(server_app.collect_garbage(0).await?.is_empty());
+
+        Ok(())
+    }
+
+    /// Writes events to the server the way a malicious client would, bypassing the client logic
+    struct MaliciousWriteSpec {
+        spec: ServerAppSignUpSpec,
+    }
+
+    impl MaliciousWriteSpec {
+        async fn build() -> Result<Self> {
+            let spec = ServerAppSignUpSpec::build().await?;
+            spec.sign_up_and_second_devices_joins().await?;
+            Ok(Self { spec })
+        }
+
+        fn server_p_obj(&self) -> PersistentObject<InMemKvLogEventRepo> {
+            PersistentObject::new(self.spec.empty_state().p_obj.server.repo.clone())
+        }
+
+        /// Write request with the event signed by the signer device
+        fn write(event: GenericKvLogEvent, signer: &UserCreds) -> Result<SyncRequest> {
+            SyncRequest::signed_write(event, &signer.device_creds.key_manager()?.dsa)
+        }
+
+        /// Sends the event signed by the signer and returns the reason the server rejected it
+        async fn rejection(
+            &self,
+            event: GenericKvLogEvent,
+            signer: &UserCreds,
+        ) -> Result<EventRejection> {
+            let server_app = self.spec.registry.state.server_app.server_app.clone();
+            let request = Self::write(event, signer)?;
+
+            let Err(err) = server_app.handle_client_request(request).await else {
+                bail!("The server accepted a malicious event");
+            };
+            let Some(rejection) = err.downcast_ref::<EventRejection>() else {
+                bail!("Unexpected error: {:?}", err);
+            };
+            Ok(rejection.clone())
+        }
+
+        async fn device_log_event(
+            &self,
+            log_owner: UserData,
+            action: VaultActionEvent,
+        ) -> Result<GenericKvLogEvent> {
+            let log_desc = DeviceLogDescriptor::from(log_owner.user_id());
+            let obj_id = self
+                .server_p_obj()
+                .find_free_id_by_obj_desc(log_desc.clone())
+                .await?;
+            let event = DeviceLogObject(KvLogEvent::new(
+                KvKey::artifact(log_desc.to_obj_desc(), obj_id),
+                action,
+            ));
+            self.server_p_obj().link(event).await
+        }
+
+        async fn ss_device_log_event(&self, claim: SsClaim) -> Result<GenericKvLogEvent> {
+            let log_desc = SsDeviceLogDescriptor::from(claim.sender.clone());
+            let obj_id = self
+                .server_p_obj()
+                .find_free_id_by_obj_desc(log_desc.clone())
+                .await?;
+            let event = SsDeviceLogObject(KvLogEvent::new(
+                KvKey::artifact(log_desc.to_obj_desc(), obj_id),
+                claim,
+            ));
+            self.server_p_obj().link(event).await
+        }
+
+        fn split_claim(
+            &self,
+            vault_name: VaultName,
+            sender: &UserCreds,
+            receiver: &UserCreds,
+        ) -> SsClaim {
+            let claim_id = ClaimId::from(Id48bit::generate());
+            let receivers = vec![receiver.device_id().clone()];
+            SsClaim {
+                id: claim_id.clone(),
+                dist_claim_id: SsClaimId {
+                    id: claim_id,
+                    pass_id: MetaPasswordId::build_from_str("forged_pass").opaque(),
+                },
+                vault_name,
+                sender: sender.device_id().clone(),
+                distribution_type: SecretDistributionType::Split,
+                receivers: receivers.clone(),
+                status: SsDistributionCompositeStatus::from(receivers),
+                created_at: None,
+                time: None,
+                ttl: None,
+                fallback: None,
+                break_glass: None,
+                approvals: vec![],
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_writes_into_foreign_device_log() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let victim = user_creds.client.user();
+        let intruder = UserData {
+            vault_name: victim.vault_name(),
+            device: user_creds.client_b.device_creds.device.clone(),
+        };
+        let join = VaultActionEvent::Request(VaultActionRequestEvent::JoinCluster(
+            JoinClusterEvent::from(intruder.clone()),
+        ));
+
+        let victim_log = DeviceLogDescriptor::from(victim.user_id());
+        let victim_tail = malicious
+            .server_p_obj()
+            .find_free_id_by_obj_desc(victim_log.clone())
+            .await?;
+
+        let event = malicious
+            .device_log_event(victim.clone(), join.clone())
+            .await?;
+        let rejection = malicious.rejection(event, &user_creds.client_b).await?;
+        assert!(matches!(rejection, EventRejection::ForeignLog { .. }));
+
+        // nothing has been written into the log of the victim
+        let free_id = malicious
+            .server_p_obj()
+            .find_free_id_by_obj_desc(victim_log)
+            .await?;
+        assert_eq!(victim_tail, free_id);
+
+        // own log, but the event skips an id
+        let mut event = malicious
+            .device_log_event(intruder.clone(), join.clone())
+            .await?;
+        if let GenericKvLogEvent::DeviceLog(device_log) = &mut event {
+            device_log.0.key.obj_id = device_log.0.key.obj_id.clone().next();
+        }
+        let rejection = malicious.rejection(event, &user_creds.client_b).await?;
+        assert!(matches!(rejection, EventRejection::UnexpectedId { .. }));
+
+        // the server alone puts candidates on the pending list
+        let add_to_pending = VaultActionEvent::Update(VaultActionUpdateEvent::AddToPending {
+            candidate: intruder.clone(),
+            invite: None,
+        });
+        let event = malicious
+            .device_log_event(intruder.clone(), add_to_pending)
+            .await?;
+        let rejection = malicious.rejection(event, &user_creds.client_b).await?;
+        assert_eq!(
+            rejection,
+            EventRejection::ServerOnlyAction(String::from("AddToPending"))
+        );
+
+        // only members add passwords into the vault
+        let add_pass =
+            VaultActionEvent::Request(VaultActionRequestEvent::AddMetaPass(AddMetaPassEvent {
+                sender: UserDataMember {
+                    user_data: intruder.clone(),
+                },
+                meta_pass_id: MetaPasswordId::build_from_str("forged_pass").opaque(),
+                recovery_policy: None,
+                break_glass: None,
+            }));
+        let event = malicious
+            .device_log_event(intruder.clone(), add_pass)
+            .await?;
+        let rejection = malicious.rejection(event, &user_creds.client_b).await?;
+        assert_eq!(
+            rejection,
+            EventRejection::NotVaultMember {
+                vault_name: intruder.vault_name(),
+                device_id: intruder.device.device_id.clone(),
+            }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_oversized_event() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let member = user_creds.client.user();
+
+        let add_pass =
+            VaultActionEvent::Request(VaultActionRequestEvent::AddMetaPass(AddMetaPassEvent {
+                sender: UserDataMember {
+                    user_data: member.clone(),
+                },
+                meta_pass_id: MetaPasswordId::build("x".repeat(MAX_EVENT_SIZE)),
+                recovery_policy: None,
+                break_glass: None,
+            }));
+        let event = malicious.device_log_event(member, add_pass).await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert!(matches!(
+            rejection,
+            EventRejection::TooLarge {
+                limit: MAX_EVENT_SIZE,
+                ..
+            }
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_clear_text_password_names() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let member = user_creds.client.user();
+        let pass_id = MetaPasswordId::build_from_str("prod-db-root");
+
+        let add_pass =
+            VaultActionEvent::Request(VaultActionRequestEvent::AddMetaPass(AddMetaPassEvent {
+                sender: UserDataMember {
+                    user_data: member.clone(),
+                },
+                meta_pass_id: pass_id.clone(),
+                recovery_policy: None,
+                break_glass: None,
+            }));
+        let event = malicious.device_log_event(member, add_pass).await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert_eq!(rejection, EventRejection::ClearTextName(pass_id.id_str()));
+
+        let mut claim = malicious.split_claim(
+            user_creds.client.vault_name.clone(),
+            &user_creds.client,
+            &user_creds.vd,
+        );
+        claim.dist_claim_id.pass_id = pass_id.clone();
+        let event = malicious.ss_device_log_event(claim).await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert_eq!(rejection, EventRejection::ClearTextName(pass_id.id_str()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_forged_ss_claims() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let vault_name = user_creds.client.vault_name.clone();
+
+        // an outsider claims a split in the vault
+        let claim =
+            malicious.split_claim(vault_name.clone(), &user_creds.client_b, &user_creds.client);
+        let event = malicious.ss_device_log_event(claim).await?;
+        let rejection = malicious.rejection(event, &user_creds.client_b).await?;
+        assert_eq!(
+            rejection,
+            EventRejection::NotVaultMember {
+                vault_name: vault_name.clone(),
+                device_id: user_creds.client_b.device_id().clone(),
+            }
+        );
+
+        // a member sends a share to an outsider
+        let claim =
+            malicious.split_claim(vault_name.clone(), &user_creds.client, &user_creds.client_b);
+        let event = malicious.ss_device_log_event(claim).await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert!(matches!(rejection, EventRejection::NotVaultMember { .. }));
+
+        // a claim for a foreign vault
+        let foreign_vault = VaultName::from("foreign_vault");
+        let claim =
+            malicious.split_claim(foreign_vault.clone(), &user_creds.client, &user_creds.vd);
+        let event = malicious.ss_device_log_event(claim).await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert_eq!(rejection, EventRejection::UnknownVault(foreign_vault));
+
+        // a claim written into the ss log of another device
+        let claim = malicious.split_claim(vault_name.clone(), &user_creds.client, &user_creds.vd);
+        let mut event = malicious.ss_device_log_event(claim).await?;
+        if let GenericKvLogEvent::SsDeviceLog(ss_device_log) = &mut event {
+            ss_device_log.0.value.sender = user_creds.vd.device_id().clone();
+            ss_device_log.0.value.receivers = vec![user_creds.client.device_id().clone()];
+        }
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert!(matches!(rejection, EventRejection::ForeignLog { .. }));
+
+        // a recovery claim that falls back to an outsider
+        let mut claim =
+            malicious.split_claim(vault_name.clone(), &user_creds.client, &user_creds.vd);
+        claim.distribution_type = SecretDistributionType::Recover;
+        claim.fallback = Some(RecoveryFallback {
+            receivers: vec![user_creds.client_b.device_id().clone()],
+            after: 0,
+        });
+        let event = malicious.ss_device_log_event(claim).await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert_eq!(
+            rejection,
+            EventRejection::NotVaultMember {
+                vault_name: vault_name.clone(),
+                device_id: user_creds.client_b.device_id().clone(),
+            }
+        );
+
+        let ss_log = PersistentSharedSecret::from(Arc::new(malicious.server_p_obj()))
+            .get_ss_log_obj(vault_name)
+            .await?;
+        assert!(ss_log.claims.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_shares_of_unknown_claims() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let claim = malicious.split_claim(
+            user_creds.client.vault_name.clone(),
+            &user_creds.client,
+            &user_creds.vd,
+        );
+
+        let key_manager = KeyManager::generate();
+        let share = key_manager
+            .transport
+            .encrypt_string(PlainText::from("share"), &key_manager.transport.pk())?;
+        let dist_id = claim.distribution_ids().remove(0);
+        let share_event = SsWorkflowObject::Distribution(KvLogEvent::new(
+            KvKey::from(SsWorkflowDescriptor::Distribution(dist_id.clone())),
+            SecretDistributionData {
+                vault_name: claim.vault_name.clone(),
+                claim_id: claim.dist_claim_id.clone(),
+                secret_message: EncryptedMessage::CipherShare { share },
+            },
+        ));
+
+        let rejection = malicious
+            .rejection(share_event.to_generic(), &user_creds.client)
+            .await?;
+        assert!(matches!(rejection, EventRejection::UnknownClaim { .. }));
+
+        let dist_desc = SsWorkflowDescriptor::Distribution(dist_id);
+        assert!(
+            malicious
+                .server_p_obj()
+                .find_tail_event(dist_desc)
+                .await?
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_only_sender_cancels_recovery_claim() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let server_app = malicious.spec.registry.state.server_app.server_app.clone();
+
+        let mut claim = malicious.split_claim(
+            user_creds.client.vault_name.clone(),
+            &user_creds.client,
+            &user_creds.vd,
+        );
+        claim.distribution_type = SecretDistributionType::Recover;
+        let claim_event = malicious.ss_device_log_event(claim.clone()).await?;
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(claim_event, &user_creds.client)?)
+            .await?;
+
+        let cancel_event = |sender: &UserCreds| {
+            SsWorkflowObject::CancelClaim(KvLogEvent::new(
+                KvKey::from(SsWorkflowDescriptor::CancelClaim(claim.dist_claim_id.clone())),
+                SsCancelClaimData {
+                    vault_name: claim.vault_name.clone(),
+                    claim_id: claim.dist_claim_id.clone(),
+                    sender: sender.device_id().clone(),
+                },
+            ))
+            .to_generic()
+        };
+
+        // a receiver can't withdraw the claim of another device
+        let rejection = malicious
+            .rejection(cancel_event(&user_creds.vd), &user_creds.vd)
+            .await?;
+        assert!(matches!(rejection, EventRejection::ForeignWorkflowKey(_)));
+
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(
+                cancel_event(&user_creds.client),
+                &user_creds.client,
+            )?)
+            .await?;
+
+        let ss_log = PersistentSharedSecret::from(Arc::new(malicious.server_p_obj()))
+            .get_ss_log_obj(claim.vault_name.clone())
+            .await?;
+        assert!(!ss_log.claims.contains_key(&claim.id));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_enforces_recovery_policy() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let server_app = malicious.spec.registry.state.server_app.server_app.clone();
+        let client = user_creds.client.user();
+        let vd_device_id = user_creds.vd.device_id().clone();
+
+        // the secret can be recovered only with the approval of the virtual device
+        let pass_id = MetaPasswordId::build_from_str("two_person_pass").opaque();
+        let add_pass =
+            VaultActionEvent::Request(VaultActionRequestEvent::AddMetaPass(AddMetaPassEvent {
+                sender: UserDataMember {
+                    user_data: client.clone(),
+                },
+                meta_pass_id: pass_id.clone(),
+                recovery_policy: Some(RecoveryPolicy {
+                    approvals: 1,
+                    device_types: vec![],
+                    approvers: vec![vd_device_id.clone()],
+                }),
+                break_glass: None,
+            }));
+        let event = malicious.device_log_event(client, add_pass).await?;
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(event, &user_creds.client)?)
+            .await?;
+
+        let mut claim = malicious.split_claim(
+            user_creds.client.vault_name.clone(),
+            &user_creds.client,
+            &user_creds.vd,
+        );
+        claim.distribution_type = SecretDistributionType::Recover;
+        claim.dist_claim_id.pass_id = pass_id;
+        let claim_event = malicious.ss_device_log_event(claim.clone()).await?;
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(claim_event, &user_creds.client)?)
+            .await?;
+
+        let recovery_id = claim.recovery_db_ids().remove(0);
+        let vd_km = user_creds.vd.device_creds.key_manager()?;
+        let share = vd_km.transport.encrypt_string(
+            PlainText::from("share"),
+            &user_creds.client.device_creds.device.keys.transport_pk,
+        )?;
+        let share_event = SsWorkflowObject::Recovery(KvLogEvent::new(
+            KvKey::from(SsWorkflowDescriptor::Recovery(recovery_id.clone())),
+            SecretDistributionData {
+                vault_name: claim.vault_name.clone(),
+                claim_id: claim.dist_claim_id.clone(),
+                secret_message: EncryptedMessage::CipherShare { share },
+            },
+        ))
+        .to_generic();
+
+        // the share can't be released before the claim is approved
+        let rejection = malicious
+            .rejection(share_event.clone(), &user_creds.vd)
+            .await?;
+        assert!(matches!(
+            rejection,
+            EventRejection::MissingApprovals {
+                required: 1,
+                actual: 0,
+                ..
+            }
+        ));
+
+        let approval_event = |receiver_id: &DeviceId, approver: &UserCreds| -> Result<_> {
+            let dsa = approver.device_creds.key_manager()?.dsa;
+            let event = SsWorkflowObject::Approval(KvLogEvent::new(
+                KvKey::from(SsWorkflowDescriptor::Approval(recovery_id.clone())),
+                SsApprovalData::sign(&claim, receiver_id.clone(), &dsa)?,
+            ));
+            Ok(event.to_generic())
+        };
+
+        // the sender can't approve its own claim
+        let client_device_id = user_creds.client.device_id();
+        let rejection = malicious
+            .rejection(
+                approval_event(client_device_id, &user_creds.client)?,
+                &user_creds.client,
+            )
+            .await?;
+        assert!(matches!(rejection, EventRejection::ForeignWorkflowKey(_)));
+
+        // the approval has to be signed by the approver, not just sent on its behalf
+        let rejection = malicious
+            .rejection(approval_event(&vd_device_id, &user_creds.client)?, &user_creds.vd)
+            .await?;
+        assert_eq!(
+            rejection,
+            EventRejection::InvalidSignature {
+                device_id: vd_device_id.clone(),
+            }
+        );
+
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(
+                approval_event(&vd_device_id, &user_creds.vd)?,
+                &user_creds.vd,
+            )?)
+            .await?;
+        let ss_log = PersistentSharedSecret::from(Arc::new(malicious.server_p_obj()))
+            .get_ss_log_obj(claim.vault_name.clone())
+            .await?;
+        assert_eq!(
+            ss_log.claims[&claim.id].status.get(&vd_device_id),
+            Some(&SsDistributionStatus::Approved)
+        );
+
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(share_event, &user_creds.vd)?)
+            .await?;
+        let ss_log = PersistentSharedSecret::from(Arc::new(malicious.server_p_obj()))
+            .get_ss_log_obj(claim.vault_name.clone())
+            .await?;
+        assert_eq!(
+            ss_log.claims[&claim.id].status.get(&vd_device_id),
+            Some(&SsDistributionStatus::Sent)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_enforces_break_glass_policy() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let server_app = malicious.spec.registry.state.server_app.server_app.clone();
+        let server_p_ss = PersistentSharedSecret::from(Arc::new(malicious.server_p_obj()));
+        let client = user_creds.client.user();
+        let client_device_id = user_creds.client.device_id().clone();
+        let vd_device_id = user_creds.vd.device_id().clone();
+
+        // the virtual device approves the recovery, or releases its share on its own
+        // once nobody has vetoed the claim (the delay is over right away)
+        let pass_id = MetaPasswordId::build_from_str("escrowed_pass").opaque();
+        let policy = BreakGlassPolicy {
+            escrow: vd_device_id.clone(),
+            delay: 0,
+        };
+        let add_pass =
+            VaultActionEvent::Request(VaultActionRequestEvent::AddMetaPass(AddMetaPassEvent {
+                sender: UserDataMember {
+                    user_data: client.clone(),
+                },
+                meta_pass_id: pass_id.clone(),
+                recovery_policy: Some(RecoveryPolicy {
+                    approvals: 1,
+                    device_types: vec![],
+                    approvers: vec![vd_device_id.clone()],
+                }),
+                break_glass: Some(policy.clone()),
+            }));
+        let event = malicious.device_log_event(client, add_pass).await?;
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(event, &user_creds.client)?)
+            .await?;
+
+        let break_glass_claim = || {
+            let mut claim = malicious.split_claim(
+                user_creds.client.vault_name.clone(),
+                &user_creds.client,
+                &user_creds.vd,
+            );
+            claim.distribution_type = SecretDistributionType::Recover;
+            claim.dist_claim_id.pass_id = pass_id.clone();
+            claim.time = Some(EventTime {
+                created_at: 0,
+                hlc: HlcTimestamp::default(),
+                received_at: None,
+            });
+            claim.break_glass = Some(BreakGlass::from(policy.clone()));
+            claim
+        };
+
+        // the sender can't pick the delay
+        let mut hasty_claim = break_glass_claim();
+        hasty_claim.break_glass = Some(BreakGlass::from(BreakGlassPolicy {
+            delay: 1,
+            ..policy.clone()
+        }));
+        let claim_event = malicious.ss_device_log_event(hasty_claim).await?;
+        let rejection = malicious
+            .rejection(claim_event, &user_creds.client)
+            .await?;
+        assert!(matches!(rejection, EventRejection::InvalidClaim(_)));
+
+        let send_claim = |claim: SsClaim| async {
+            let claim_event = malicious.ss_device_log_event(claim).await?;
+            server_app
+                .handle_client_request(MaliciousWriteSpec::write(claim_event, &user_creds.client)?)
+                .await
+        };
+        let share_event = |claim: &SsClaim| -> Result<GenericKvLogEvent> {
+            let recovery_id = claim.recovery_db_ids().remove(0);
+            let vd_km = user_creds.vd.device_creds.key_manager()?;
+            let share = vd_km.transport.encrypt_string(
+                PlainText::from("share"),
+                &user_creds.client.device_creds.device.keys.transport_pk,
+            )?;
+            let event = SsWorkflowObject::Recovery(KvLogEvent::new(
+                KvKey::from(SsWorkflowDescriptor::Recovery(recovery_id)),
+                SecretDistributionData {
+                    vault_name: claim.vault_name.clone(),
+                    claim_id: claim.dist_claim_id.clone(),
+                    secret_message: EncryptedMessage::CipherShare { share },
+                },
+            ));
+            Ok(event.to_generic())
+        };
+        let veto_event = |claim: &SsClaim, device_id: &DeviceId| {
+            SsWorkflowObject::Veto(KvLogEvent::new(
+                KvKey::from(SsWorkflowDescriptor::Veto(claim.dist_claim_id.clone())),
+                SsVetoData {
+                    vault_name: claim.vault_name.clone(),
+                    claim_id: claim.dist_claim_id.clone(),
+                    device_id: device_id.clone(),
+                },
+            ))
+            .to_generic()
+        };
+
+        let vetoed_claim = break_glass_claim();
+        send_claim(vetoed_claim.clone()).await?;
+
+        // the sender can't veto its own claim
+        let rejection = malicious
+            .rejection(
+                veto_event(&vetoed_claim, &client_device_id),
+                &user_creds.client,
+            )
+            .await?;
+        assert!(matches!(rejection, EventRejection::ForeignWorkflowKey(_)));
+
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(
+                veto_event(&vetoed_claim, &vd_device_id),
+                &user_creds.vd,
+            )?)
+            .await?;
+        let ss_log = server_p_ss
+            .get_ss_log_obj(vetoed_claim.vault_name.clone())
+            .await?;
+        let server_claim = &ss_log.claims[&vetoed_claim.id];
+        assert_eq!(
+            server_claim
+                .break_glass
+                .as_ref()
+                .and_then(|break_glass| break_glass.vetoed_by.clone()),
+            Some(vd_device_id.clone())
+        );
+
+        // after the veto the escrow needs the approvals like any other receiver
+        let rejection = malicious
+            .rejection(share_event(&vetoed_claim)?, &user_creds.vd)
+            .await?;
+        assert!(matches!(rejection, EventRejection::MissingApprovals { .. }));
+
+        let due_claim = break_glass_claim();
+        send_claim(due_claim.clone()).await?;
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(
+                share_event(&due_claim)?,
+                &user_creds.vd,
+            )?)
+            .await?;
+        let ss_log = server_p_ss
+            .get_ss_log_obj(due_claim.vault_name.clone())
+            .await?;
+        assert_eq!(
+            ss_log.claims[&due_claim.id].status.get(&vd_device_id),
+            Some(&SsDistributionStatus::Sent)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_escrow_releases_only_claims_of_the_break_glass_policy() -> Result<()> {
+        let spec = ServerAppSignUpSpec::build().await?;
+        spec.sign_up_and_second_devices_joins().await?;
+
+        let client = &spec.registry.state.client;
+        let client_device_id = client.device_id();
+        let policy = BreakGlassPolicy {
+            escrow: client_device_id.clone(),
+            delay: 0,
+        };
+
+        // the client holds the escrow share of the password
+        let app_state = client.client_service.build_service_state().await?.app_state;
+        let plain_pass = PlainPassInfo {
+            pass_id: MetaPasswordId::build_from_str("escrowed_pass"),
+            pass: "2bee|~".to_string(),
+            recovery_policy: None,
+            break_glass: Some(policy.clone()),
+        };
+        let app_state = client
+            .client_service
+            .handle_client_request(app_state, GenericAppStateRequest::ClusterDistribution(plain_pass))
+            .await?;
+        let ApplicationState::Vault(VaultFullInfo::Member(member)) = &app_state else {
+            bail!("Has to be Vault");
+        };
+        let pass_id = member
+            .member
+            .vault
+            .secrets
+            .iter()
+            .find(|pass_id| member.member.vault.break_glass_policy(pass_id).is_some())
+            .unwrap()
+            .clone();
+
+        // the virtual device breaks the glass
+        let vd_member = VaultMember {
+            member: spec.registry.state.vd.user.clone().into(),
+            vault: member.member.vault.clone(),
+        };
+        let break_glass_claim = |break_glass: BreakGlassPolicy| {
+            let mut claim = vd_member.create_recovery_claim(pass_id.clone());
+            // the claim has been made a minute ago
+            claim.time = Some(EventTime {
+                created_at: unix_time_millis() - 60_000,
+                hlc: HlcTimestamp::default(),
+                received_at: None,
+            });
+            claim.break_glass = Some(BreakGlass::from(break_glass));
+            claim
+        };
+        let is_released = |claim: &SsClaim| {
+            let recovery_desc = SsWorkflowDescriptor::Recovery(claim.recovery_db_ids().remove(0));
+            let p_obj = client.p_obj.clone();
+            async move { Ok::<bool, anyhow::Error>(p_obj.find_tail_event(recovery_desc).await?.is_some()) }
+        };
+
+        // a claim with a delay of its own doesn't follow the policy of the secret
+        let hasty_claim = break_glass_claim(BreakGlassPolicy {
+            delay: 1,
+            ..policy.clone()
+        });
+        client.p_ss.save_local_ss_log_event(hasty_claim.clone()).await?;
+        client.orchestrator.release_break_glass_shares().await?;
+        assert!(!is_released(&hasty_claim).await?);
+
+        let due_claim = break_glass_claim(policy);
+        client.p_ss.save_local_ss_log_event(due_claim.clone()).await?;
+        client.orchestrator.release_break_glass_shares().await?;
+        assert!(is_released(&due_claim).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_checks_join_invites() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let member = UserDataMember::from(user_creds.client.user());
+        let outsider = UserData {
+            vault_name: member.user_data.vault_name(),
+            device: user_creds.client_b.device_creds.device.clone(),
+        };
+        let outsider_dsa = user_creds.client_b.device_creds.key_manager()?.dsa;
+        let validator = EventValidator::new(Arc::new(malicious.server_p_obj()));
+        let join_with = |invite: Option<&VaultInvite>| {
+            VaultActionEvent::Request(VaultActionRequestEvent::JoinCluster(JoinClusterEvent {
+                candidate: outsider.clone(),
+                invite: invite.map(VaultInvite::ticket),
+            }))
+        };
+
+        // the vault takes devices without an invite until a member requires one
+        let event = malicious
+            .device_log_event(outsider.clone(), join_with(None))
+            .await?;
+        validator
+            .validate(&SignedEvent::sign(&event, &outsider_dsa)?)
+            .await?;
+
+        let client_service = &malicious.spec.registry.state.client.client_service;
+        client_service.update_vault_settings(None, Some(true)).await?;
+
+        // the vault accepts invited devices only
+        let event = malicious
+            .device_log_event(outsider.clone(), join_with(None))
+            .await?;
+        let err = validator
+            .validate(&SignedEvent::sign(&event, &outsider_dsa)?)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EventRejection>(),
+            Some(&EventRejection::InviteRequired(outsider.vault_name()))
+        );
+
+        // the invite has never been added to the vault
+        let invite = VaultInvite::generate(&member, DEFAULT_INVITE_TTL, false, unix_time_millis());
+        let event = malicious
+            .device_log_event(outsider.clone(), join_with(Some(&invite)))
+            .await?;
+        let rejection = malicious.rejection(event, &user_creds.client_b).await?;
+        assert!(matches!(rejection, EventRejection::InvalidInvite(_)));
+
+        // a member can't add invites on behalf of another device
+        let forged = AddInviteEvent {
+            sender: member.clone(),
+            invite: invite.record(outsider.device.device_id.clone()),
+        };
+        let add_forged = VaultActionEvent::Request(VaultActionRequestEvent::AddInvite(forged));
+        let event = malicious
+            .device_log_event(member.user_data.clone(), add_forged)
+            .await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert!(matches!(rejection, EventRejection::InvalidInvite(_)));
+
+        // once the member has added the invite, the candidate can present it
+        let add_invite =
+            VaultActionEvent::Request(VaultActionRequestEvent::AddInvite(AddInviteEvent {
+                sender: member.clone(),
+                invite: invite.record(member.user().device.device_id.clone()),
+            }));
+        let event = malicious
+            .device_log_event(member.user_data.clone(), add_invite)
+            .await?;
+        let server_app = malicious.spec.registry.state.server_app.server_app.clone();
+        let request = MaliciousWriteSpec::write(event, &user_creds.client)?;
+        server_app.handle_client_request(request).await?;
+
+        let event = malicious
+            .device_log_event(outsider.clone(), join_with(Some(&invite)))
+            .await?;
+        validator
+            .validate(&SignedEvent::sign(&event, &outsider_dsa)?)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_members_set_join_quorum_of_their_vault() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let client_service = &malicious.spec.registry.state.client.client_service;
+        let vd_service = &malicious.spec.registry.state.vd.client_service;
+        let vault_name = malicious.spec.user_creds().client.vault_name.clone();
+        let server_vault = || async {
+            let p_vault = PersistentVault::from(Arc::new(malicious.server_p_obj()));
+            anyhow::Ok(p_vault.get_vault(vault_name.clone()).await?.to_data())
+        };
+
+        // a single member raises the quorum, the server keeps it on the next updates
+        client_service.update_vault_settings(Some(2), None).await?;
+        assert_eq!(2, server_vault().await?.join_quorum);
+        client_service.create_invite(DEFAULT_INVITE_TTL, false).await?;
+        assert_eq!(2, server_vault().await?.join_quorum);
+
+        // lowering it takes two members now
+        client_service.update_vault_settings(Some(1), None).await?;
+        assert_eq!(2, server_vault().await?.join_quorum);
+        vd_service.update_vault_settings(Some(1), None).await?;
+        assert_eq!(1, server_vault().await?.join_quorum);
+
+        // the server refuses a quorum nobody can reach
+        let user_creds = malicious.spec.user_creds();
+        let zero_quorum = UpdateVaultSettingsEvent {
+            sender: UserDataMember::from(user_creds.client.user()),
+            join_quorum: Some(0),
+            require_invite: None,
+        };
+        let update_settings =
+            VaultActionEvent::Request(VaultActionRequestEvent::UpdateSettings(zero_quorum));
+        let event = malicious
+            .device_log_event(user_creds.client.user(), update_settings)
+            .await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert!(matches!(rejection, EventRejection::InvalidSettings(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_forged_key_rotation() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let member = UserDataMember::from(user_creds.client.user());
+        let new_keys = user_creds.client.device_creds.rotate_keys().device.keys;
+
+        // the new keys are signed by a key that doesn't belong to the member
+        let intruder_km = user_creds.client_b.device_creds.key_manager()?;
+        let forged = RotateDeviceKeysEvent::sign(member.clone(), new_keys.clone(), &intruder_km.dsa)?;
+        let event = malicious
+            .device_log_event(
+                member.user_data.clone(),
+                VaultActionEvent::Request(VaultActionRequestEvent::RotateDeviceKeys(forged)),
+            )
+            .await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert!(matches!(rejection, EventRejection::InvalidKeyRotation(_)));
+
+        // a correct signature of keys the vault doesn't know for the device
+        let stale_member = UserDataMember::from(UserData {
+            vault_name: member.user_data.vault_name(),
+            device: member
+                .user()
+                .device
+                .with_keys(user_creds.client_b.device_creds.device.keys.clone()),
+        });
+        let stale = RotateDeviceKeysEvent::sign(stale_member, new_keys, &intruder_km.dsa)?;
+        let event = malicious
+            .device_log_event(
+                member.user_data.clone(),
+                VaultActionEvent::Request(VaultActionRequestEvent::RotateDeviceKeys(stale)),
+            )
+            .await?;
+        let rejection = malicious.rejection(event, &user_creds.client).await?;
+        assert!(matches!(rejection, EventRejection::InvalidKeyRotation(_)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_events_of_forged_authors() -> Result<()> {
+        let malicious = MaliciousWriteSpec::build().await?;
+        let user_creds = malicious.spec.user_creds();
+        let server_app = malicious.spec.registry.state.server_app.server_app.clone();
+        let victim = user_creds.client.user();
+        let forged_by = |device_id: &DeviceId| EventRejection::InvalidSignature {
+            device_id: device_id.clone(),
+        };
+
+        // an outsider adds a password on behalf of a member
+        let add_pass =
+            VaultActionEvent::Request(VaultActionRequestEvent::AddMetaPass(AddMetaPassEvent {
+                sender: UserDataMember {
+                    user_data: victim.clone(),
+                },
+                meta_pass_id: MetaPasswordId::build_from_str("forged_pass").opaque(),
+                recovery_policy: None,
+                break_glass: None,
+            }));
+        let event = malicious.device_log_event(victim.clone(), add_pass).await?;
+        let rejection = malicious.rejection(event, &user_creds.client_b).await?;
+        assert_eq!(rejection, forged_by(user_creds.client.device_id()));
+
+        // a member asks to join on behalf of a device that is not in the vault
+        let candidate = UserData {
+            vault_name: victim.vault_name(),
+            device: user_creds.client_b.device_creds.device.clone(),
+        };
+        let join = VaultActionEvent::Request(VaultActionRequestEvent::JoinCluster(
+            JoinClusterEvent::from(candidate.clone()),
+        ));
+        let event = malicious.device_log_event(candidate, join).await?;
+        let rejection = malicious.rejection(event, &user_creds.vd).await?;
+        assert_eq!(rejection, forged_by(user_creds.client_b.device_id()));
+
+        // a member sends a claim on behalf of another member
+        let mut claim = malicious.split_claim(
+            user_creds.client.vault_name.clone(),
+            &user_creds.client,
+            &user_creds.vd,
+        );
+        claim.distribution_type = SecretDistributionType::Recover;
+        let event = malicious.ss_device_log_event(claim.clone()).await?;
+        let rejection = malicious.rejection(event.clone(), &user_creds.vd).await?;
+        assert_eq!(rejection, forged_by(user_creds.client.device_id()));
+
+        // the sender of a claim approves it on behalf of the share holder
+        server_app
+            .handle_client_request(MaliciousWriteSpec::write(event, &user_creds.client)?)
+            .await?;
+        let vd_device_id = user_creds.vd.device_id().clone();
+        let recovery_id = claim.recovery_db_ids().remove(0);
+        let client_dsa = user_creds.client.device_creds.key_manager()?.dsa;
+        let approval_event = SsWorkflowObject::Approval(KvLogEvent::new(
+            KvKey::from(SsWorkflowDescriptor::Approval(recovery_id)),
+            SsApprovalData::sign(&claim, vd_device_id.clone(), &client_dsa)?,
+        ))
+        .to_generic();
+        let rejection = malicious
+            .rejection(approval_event, &user_creds.client)
+            .await?;
+        assert_eq!(rejection, forged_by(&vd_device_id));
+
+        let ss_log = PersistentSharedSecret::from(Arc::new(malicious.server_p_obj()))
+            .get_ss_log_obj(claim.vault_name.clone())
+            .await?;
+        assert_ne!(
+            ss_log.claims[&claim.id].status.get(&vd_device_id),
+            Some(&SsDistributionStatus::Approved)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_vault_replication_pages() -> Result<()> {
+        let spec = ServerAppSignUpSpec::build().await?;
+        spec.sign_up_and_second_devices_joins().await?;
+
+        let server_app = spec.registry.state.server_app.server_app.clone();
+        let sender = spec.registry.state.client.user.clone();
+        let vault_request = |tail: VaultTail, page_size: usize| {
+            SyncRequest::Read(Box::new(ReadSyncRequest::Vault(VaultRequest {
+                sender: sender.clone(),
+                tail,
+                page_size,
+            })))
+        };
+
+        // a device that has never synced the vault
+        let initial_tail = PersistentVault::from(Arc::new(PersistentObject::in_mem()))
+            .vault_tail(sender.clone())
+            .await?;
+
+        let all_events = server_app
+            .handle_client_request(vault_request(initial_tail.clone(), MAX_PAGE_SIZE))
+            .await?
+            .to_data()?;
+        assert!(all_events.next_page.is_none());
+
+        let mut paged_events = vec![];
+        let mut tail = initial_tail;
+        loop {
+            let page = server_app
+                .handle_client_request(vault_request(tail.clone(), 2))
+                .await?
+                .to_data()?;
+            assert!(page.events.len() <= 2);
+            paged_events.extend(page.events);
+
+            match page.next_page {
+                None => break,
+                Some(PageCursor::Vault(next_tail)) => tail = next_tail,
+                Some(cursor) => bail!("Unexpected cursor: {:?}", cursor),
+            }
+        }
+
+        assert!(all_events.events.len() > 2);
+        assert_eq!(all_events.events, paged_events);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_get_own_responses() -> Result<()> {
+        let spec = ServerAppSignUpSpec::build().await?;
+        spec.sign_up_and_second_devices_joins().await?;
+
+        let server_app = spec.registry.state.server_app.server_app.clone();
+        let user_creds = spec.user_creds();
+        let another_vault_user = UserData {
+            vault_name: VaultName::from("another_vault"),
+            device: user_creds.client_b.device_creds.device.clone(),
+        };
+
+        let mut requests = vec![SyncRequest::Read(Box::new(ReadSyncRequest::Handshake(
+            HandshakeRequest {
+                client: ProtocolInfo::current(),
+            },
+        )))];
+        for user in [
+            user_creds.client.user(),
+            user_creds.vd.user(),
+            another_vault_user,
+        ] {
+            let tail = PersistentVault::from(Arc::new(PersistentObject::in_mem()))
+                .vault_tail(user.clone())
+                .await?;
+            requests.push(SyncRequest::Read(Box::new(ReadSyncRequest::Vault(
+                VaultRequest {
+                    sender: user.clone(),
+                    tail,
+                    page_size: DEFAULT_PAGE_SIZE,
+                },
+            ))));
+            requests.push(SyncRequest::Read(Box::new(ReadSyncRequest::ServerTail(
+                ServerTailRequest { sender: user },
+            ))));
+        }
+
+        let mut expected = vec![];
+        for request in requests.iter().cloned() {
+            expected.push(server_app.handle_client_request(request).await?);
+        }
+
+        let local = tokio::task::LocalSet::new();
+        let server = server_app.clone();
+        local.spawn_local(async move { server.run().await });
+
+        let responses = local
+            .run_until(async move {
+                let data_transfer = server_app.get_data_transfer();
+                let callers: Vec<_> = requests
+                    .into_iter()
+                    .map(|request| {
+                        let data_transfer = data_transfer.clone();
+                        tokio::task::spawn_local(async move {
+                            data_transfer.send_request(request).await
+                        })
+                    })
+                    .collect();
+
+                let mut responses = vec![];
+                for caller in callers {
+                    responses.push(caller.await??);
+                }
+                anyhow::Ok(responses)
+            })
+            .await?;
+
+        assert_eq!(expected, responses);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_e2e_redistribution_recalculates_shares_after_late_joins() -> Result<()> {
         let spec = ServerAppSignUpSpec::build().await?;
@@ -502,19 +1945,25 @@ mod test {
         let vd_user = spec.user_creds().vd.user();
         let client_b_user = spec.user_creds().client_b.user();
 
-        let client_b_gw = std::sync::Arc::new(SyncGateway {
-            id: "client_b_gw".to_string(),
-            p_obj: spec.registry.state.base.empty.p_obj.client_b.clone(),
-            sync: spec.registry.state.sync.sync_protocol.clone(),
-            master_key: spec
-                .registry
+        let client_b_gw = std::sync::Arc::new(SyncGateway::new(
+            "client_b_gw".to_string(),
+            spec.registry.state.base.empty.p_obj.client_b.clone(),
+            spec.registry.state.sync.sync_protocol.clone(),
+            spec.registry
                 .state
                 .base
                 .empty
                 .device_creds
                 .client_b_master_key
                 .clone(),
-        });
+        ));
+        // D3 signs its writes with its own keys
+        PersistentCredentials {
+            p_obj: spec.registry.state.base.empty.p_obj.client_b.clone(),
+            master_key: spec.registry.state.base.empty.device_creds.client_b_master_key.clone(),
+        }
+        .save_device_creds(spec.user_creds().client_b.device_creds.clone())
+        .await?;
 
         // D1 creates vault and secret before any joins.
         SignUpClaimTestAction::sign_up(
@@ -529,6 +1978,8 @@ mod test {
         let dist_request = GenericAppStateRequest::ClusterDistribution(PlainPassInfo {
             pass_id: pass_id.clone(),
             pass: "2bee|~".to_string(),
+            recovery_policy: None,
+            break_glass: None,
         });
         let app_state = spec
             .registry
@@ -754,9 +2205,7 @@ mod test {
         orchestrator
             .redistribute_existing_secrets_for_test(
                 &vault_d2,
-                &JoinClusterEvent {
-                    candidate: d2.user().clone(),
-                },
+                &JoinClusterEvent::from(d2.user().clone()),
             )
             .await?;
 
@@ -767,9 +2216,7 @@ mod test {
         orchestrator
             .redistribute_existing_secrets_for_test(
                 &vault_d3,
-                &JoinClusterEvent {
-                    candidate: d3.user().clone(),
-                },
+                &JoinClusterEvent::from(d3.user().clone()),
             )
             .await?;
 
@@ -780,9 +2227,7 @@ mod test {
         orchestrator
             .redistribute_existing_secrets_for_test(
                 &vault_d4,
-                &JoinClusterEvent {
-                    candidate: d4.user().clone(),
-                },
+                &JoinClusterEvent::from(d4.user().clone()),
             )
             .await?;
 
@@ -793,9 +2238,7 @@ mod test {
         orchestrator
             .redistribute_existing_secrets_for_test(
                 &vault_d5,
-                &JoinClusterEvent {
-                    candidate: d5.user().clone(),
-                },
+                &JoinClusterEvent::from(d5.user().clone()),
             )
             .await?;
 
@@ -876,7 +2319,7 @@ mod test {
                 .next()
                 .unwrap()
                 .clone();
-            GenericAppStateRequest::Recover(pass_id)
+            GenericAppStateRequest::Recover(pass_id, RecoveryTargets::All)
         };
 
         let _app_state = split
@@ -1060,7 +2503,7 @@ mod test {
             p_obj: split.spec.registry.state.vd.p_obj.clone(),
         };
 
-        let pass = recovery_handler
+        let recovery = recovery_handler
             .recover(
                 split.spec.user_creds().vd.clone(),
                 recovery_id.claim_id.id,
@@ -1068,7 +2511,8 @@ mod test {
             )
             .await?;
 
-        assert_eq!("2bee|~", pass.text);
+        assert_eq!("2bee|~", recovery.secret.text);
+        assert!(recovery.invalid.is_empty());
 
         //let app_state_after_recover_json = serde_json::to_string_pretty(&app_state_after_recover)?;
         //println!("{}", app_state_after_recover_json);
@@ -1 +1,3 @@
 pub mod meta_secret_test;
+pub mod server_endpoints_test;
+pub mod tls_test;
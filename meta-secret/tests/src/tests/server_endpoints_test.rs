@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use meta_secret_core::crypto::key_pair::{KeyPair, TransportDsaKeyPair};
+    use meta_secret_core::node::api::{
+        DataSyncResponse, HandshakeRequest, ProtocolInfo, ReadSyncRequest, SyncRequest,
+        UpgradeComponent, PROTOCOL_VERSION, PROTOCOL_VERSION_HEADER,
+    };
+    use meta_secret_core::node::app::sync::api_url::ApiUrl;
+    use meta_secret_core::node::app::sync::sync_protocol::{HttpSyncProtocol, SyncProtocol};
+    use meta_secret_core::node::db::in_mem_db::InMemKvLogEventRepo;
+    use meta_server::config::ServerConfig;
+    use meta_server::server::{router, serve_http, spawn_server_app};
+    use meta_server_node::server::server_app::ServerApp;
+    use reqwest::StatusCode;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    async fn start_server() -> Result<u16> {
+        let repo = Arc::new(InMemKvLogEventRepo::default());
+        let master_key = TransportDsaKeyPair::generate().sk();
+        let server_app = Arc::new(ServerApp::new(repo, master_key)?);
+        let app = router(spawn_server_app(server_app), &ServerConfig::default())?;
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        tokio::spawn(serve_http(listener, app));
+        Ok(port)
+    }
+
+    async fn get(port: u16, path: &str) -> Result<(StatusCode, String)> {
+        let response = reqwest::get(format!("http://127.0.0.1:{}{}", port, path)).await?;
+        let status = response.status();
+        Ok((status, response.text().await?))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_health_and_readiness() -> Result<()> {
+        let port = start_server().await?;
+
+        assert_eq!(get(port, "/healthz").await?, (StatusCode::OK, "ok".into()));
+        assert_eq!(
+            get(port, "/readyz").await?,
+            (StatusCode::OK, "ready".into())
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_metrics_count_requests_by_kind() -> Result<()> {
+        let port = start_server().await?;
+
+        let client = HttpSyncProtocol::new(ApiUrl::custom_dev("http://127.0.0.1", u32::from(port)));
+        let handshake = SyncRequest::Read(Box::new(ReadSyncRequest::Handshake(HandshakeRequest {
+            client: ProtocolInfo::current(),
+        })));
+        let response = client.send(handshake).await?;
+        assert!(matches!(response, DataSyncResponse::Handshake(_)));
+
+        let (status, metrics) = get(port, "/metrics").await?;
+        assert_eq!(status, StatusCode::OK);
+        assert!(metrics.contains("meta_server_requests_total{request=\"handshake\"} 1"));
+        assert!(
+            metrics.contains("meta_server_request_duration_seconds_count{request=\"handshake\"} 1")
+        );
+        // the server reads its credentials on start
+        assert!(metrics.contains("meta_server_repo_op_duration_seconds_count{op=\"find_one\"}"));
+        assert!(metrics.contains("meta_server_active_vaults 0"));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_unreadable_request_requires_upgrade() -> Result<()> {
+        let port = start_server().await?;
+
+        let newer_request = r#"{"read":{"quantumSync":{}}}"#;
+        let response = reqwest::Client::new()
+            .post(format!("http://127.0.0.1:{}/meta_request", port))
+            .header("Content-Type", "application/json")
+            .header(PROTOCOL_VERSION_HEADER, (PROTOCOL_VERSION + 1).to_string())
+            .body(newer_request)
+            .send()
+            .await?;
+        assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+
+        let DataSyncResponse::UpgradeRequired(upgrade) = response.json().await? else {
+            panic!("Expected a typed upgrade response");
+        };
+        assert_eq!(upgrade.component, UpgradeComponent::Server);
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod test {
+    use anyhow::Result;
+    use meta_secret_core::crypto::key_pair::{KeyPair, TransportDsaKeyPair};
+    use meta_secret_core::node::api::{
+        DataSyncResponse, HandshakeRequest, ProtocolInfo, ReadSyncRequest, SyncRequest,
+    };
+    use meta_secret_core::node::app::sync::api_url::ApiUrl;
+    use meta_secret_core::node::app::sync::sync_protocol::{HttpSyncProtocol, SyncProtocol};
+    use meta_secret_core::node::db::in_mem_db::InMemKvLogEventRepo;
+    use meta_server::config::{ServerConfig, TlsConfig};
+    use meta_server::server::{router, serve_https, spawn_server_app};
+    use meta_server::tls;
+    use meta_server_node::server::server_app::ServerApp;
+    use std::fs;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    struct SelfSignedCert {
+        cert_pem: String,
+        key_pem: String,
+    }
+
+    impl SelfSignedCert {
+        fn generate() -> Result<Self> {
+            let certified = rcgen::generate_simple_self_signed(vec![String::from("localhost")])?;
+            Ok(Self {
+                cert_pem: certified.cert.pem(),
+                key_pem: certified.signing_key.serialize_pem(),
+            })
+        }
+
+        fn write(&self, tls: &TlsConfig) -> Result<()> {
+            fs::write(&tls.cert_path, &self.cert_pem)?;
+            fs::write(&tls.key_path, &self.key_pem)?;
+            Ok(())
+        }
+
+        fn trusting_client(&self, port: u16) -> Result<HttpSyncProtocol> {
+            let api_url = ApiUrl::custom_dev("https://localhost", u32::from(port));
+            HttpSyncProtocol::with_trusted_cert(api_url, self.cert_pem.as_bytes())
+        }
+    }
+
+    fn handshake() -> SyncRequest {
+        SyncRequest::Read(Box::new(ReadSyncRequest::Handshake(HandshakeRequest {
+            client: ProtocolInfo::current(),
+        })))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_https_sync_with_self_signed_cert() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let tls_config = TlsConfig {
+            cert_path: dir.path().join("cert.pem"),
+            key_path: dir.path().join("key.pem"),
+        };
+        let first_cert = SelfSignedCert::generate()?;
+        first_cert.write(&tls_config)?;
+
+        let server_app = {
+            let repo = Arc::new(InMemKvLogEventRepo::default());
+            let master_key = TransportDsaKeyPair::generate().sk();
+            Arc::new(ServerApp::new(repo, master_key)?)
+        };
+        let app_state = spawn_server_app(server_app);
+        let app = router(app_state, &ServerConfig::default())?;
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let rustls = tls::rustls_config(&tls_config).await?;
+        tokio::spawn(serve_https(listener, app, rustls.clone()));
+
+        let response = first_cert.trusting_client(port)?.send(handshake()).await?;
+        assert!(matches!(response, DataSyncResponse::Handshake(_)));
+
+        let untrusted =
+            HttpSyncProtocol::new(ApiUrl::custom_dev("https://localhost", u32::from(port)));
+        assert!(untrusted.send(handshake()).await.is_err());
+
+        // certificate rotation, the same as on SIGHUP
+        let second_cert = SelfSignedCert::generate()?;
+        second_cert.write(&tls_config)?;
+        tls::reload(&rustls, &tls_config).await?;
+
+        let response = second_cert.trusting_client(port)?.send(handshake()).await?;
+        assert!(matches!(response, DataSyncResponse::Handshake(_)));
+        assert!(first_cert
+            .trusting_client(port)?
+            .send(handshake())
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
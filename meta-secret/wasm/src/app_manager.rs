@@ -4,9 +4,10 @@ use tracing::{Instrument, error, info, instrument};
 use wasm_bindgen_futures::spawn_local;
 
 use meta_secret_core::crypto::keys::TransportSk;
+use meta_secret_core::node::api::HandshakeResponse;
 use meta_secret_core::node::app::app_manager_shared::{
-    build_client_components, find_recovery_claim_id_from_state, recover_plain_text,
-    resolve_signup_vault_name,
+    AuditQuery, build_client_components, find_audit_entries, find_recovery_claim_id_from_state,
+    recover_secret, resolve_signup_vault_name,
 };
 use meta_secret_core::node::app::meta_app::messaging::GenericAppStateRequest;
 use meta_secret_core::node::app::meta_app::meta_client_service::MetaClientService;
@@ -16,14 +17,15 @@ use meta_secret_core::node::app::sync::sync_protocol::{HttpSyncProtocol, SyncPro
 use meta_secret_core::node::common::meta_tracing::client_span;
 use meta_secret_core::node::common::model::device::common::{DeviceName, DeviceType};
 use meta_secret_core::node::common::model::meta_pass::{MetaPasswordId, PlainPassInfo};
-use meta_secret_core::node::common::model::secret::ClaimId;
+use meta_secret_core::node::common::model::secret::{ClaimId, RecoveryTargets};
 use meta_secret_core::node::common::model::user::common::UserData;
 use meta_secret_core::node::common::model::vault::vault::VaultName;
 use meta_secret_core::node::common::model::{ApplicationState, VaultFullInfo};
 use meta_secret_core::node::db::actions::sign_up::join::JoinActionUpdate;
+use meta_secret_core::node::db::events::audit_event::AuditEntry;
 use meta_secret_core::node::db::events::vault::vault_log_event::JoinClusterEvent;
 use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
-use meta_secret_core::secret::shared_secret::PlainText;
+use meta_secret_core::secret::share_verification::VerifiedRecovery;
 
 pub struct ApplicationManager<Repo: KvLogEventRepo, Sync: SyncProtocol> {
     pub meta_client_service: Arc<MetaClientService<Repo, Sync>>,
@@ -70,9 +72,7 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> ApplicationManager<Repo, Sync> {
     ) -> Result<ApplicationManager<Repo, HttpSyncProtocol>> {
         info!("Initialize application state manager");
 
-        let sync_protocol = Arc::new(HttpSyncProtocol {
-            api_url: ApiUrl::prod(),
-        });
+        let sync_protocol = Arc::new(HttpSyncProtocol::new(ApiUrl::prod()));
 
         Self::client_setup(
             client_repo,
@@ -110,13 +110,25 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> ApplicationManager<Repo, Sync> {
     }
 
     pub async fn recover_js(&self, meta_pass_id: MetaPasswordId) {
-        let request = GenericAppStateRequest::Recover(meta_pass_id);
+        let request = GenericAppStateRequest::Recover(meta_pass_id, RecoveryTargets::All);
         self.meta_client_service
             .send_request(request)
             .await
             .unwrap();
     }
 
+    pub async fn cancel_recovery(&self, claim_id: ClaimId) -> Result<()> {
+        let request = GenericAppStateRequest::CancelRecovery(claim_id);
+        self.meta_client_service.send_request(request).await?;
+        Ok(())
+    }
+
+    pub async fn veto_recovery(&self, claim_id: ClaimId) -> Result<()> {
+        let request = GenericAppStateRequest::VetoRecovery(claim_id);
+        self.meta_client_service.send_request(request).await?;
+        Ok(())
+    }
+
     pub async fn get_state(&self) -> ApplicationState {
         let request = GenericAppStateRequest::GetState;
         self.meta_client_service
@@ -134,13 +146,13 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> ApplicationManager<Repo, Sync> {
         candidate: UserData,
         upd: JoinActionUpdate,
     ) -> Result<()> {
-        let join_request = JoinClusterEvent { candidate };
+        let join_request = JoinClusterEvent::from(candidate);
         self.meta_client_service
             .update_membership(join_request, upd)
             .await
     }
 
-    pub async fn show_recovered(&self, pass_id: MetaPasswordId) -> Result<PlainText> {
+    pub async fn show_recovered(&self, pass_id: MetaPasswordId) -> Result<VerifiedRecovery> {
         let user_creds = self.meta_client_service.find_user_creds().await?;
         match &self.get_state().await {
             ApplicationState::Local(_) => {
@@ -158,7 +170,7 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> ApplicationManager<Repo, Sync> {
                     match claim_id {
                         None => bail!("Claim id not found"),
                         Some(claim_id) => {
-                            recover_plain_text(
+                            recover_secret(
                                 self.sync_gateway.as_ref(),
                                 user_creds,
                                 claim_id,
@@ -172,6 +184,15 @@ impl<Repo: KvLogEventRepo, Sync: SyncProtocol> ApplicationManager<Repo, Sync> {
         }
     }
 
+    pub async fn audit_list(&self, query: AuditQuery) -> Result<Vec<AuditEntry>> {
+        let state = self.get_state().await;
+        find_audit_entries(self.sync_gateway.p_obj.clone(), &state, &query).await
+    }
+
+    pub async fn handshake(&self) -> Result<HandshakeResponse> {
+        self.sync_gateway.handshake().await
+    }
+
     pub async fn clean_up_database(&self) {
         self.sync_gateway.p_obj.repo.db_clean_up().await
     }
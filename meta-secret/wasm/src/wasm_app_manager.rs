@@ -4,6 +4,8 @@ use crate::app_manager::ApplicationManager;
 use crate::configure;
 use crate::wasm_repo::WasmRepo;
 use meta_secret_core::crypto::keys::TransportSk;
+use meta_secret_core::node::api::UpgradeRequired;
+use meta_secret_core::node::app::app_manager_shared::AuditQuery;
 use meta_secret_core::node::app::sync::sync_protocol::HttpSyncProtocol;
 use meta_secret_core::node::common::model::device::common::{DeviceName, DeviceType};
 use meta_secret_core::node::common::model::WasmApplicationState;
@@ -82,10 +84,33 @@ impl WasmApplicationManager {
         self.app_manager.recover_js(meta_pass_id.clone()).await;
     }
 
-    pub async fn show_recovered(&self, pass_id: &MetaPasswordId) -> Result<String, JsValue> {
+    /// Withdraw a recovery request made by this device
+    pub async fn cancel_recovery(&self, claim_id: &ClaimId) -> Result<(), JsValue> {
+        match self.app_manager.cancel_recovery(claim_id.clone()).await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                error!(error = %e, "cancel_recovery failed");
+                Err(JsError::new(&e.to_string()).into())
+            }
+        }
+    }
+
+    /// Stop the escrow device from releasing its share of a break-glass recovery
+    pub async fn veto_recovery(&self, claim_id: &ClaimId) -> Result<(), JsValue> {
+        match self.app_manager.veto_recovery(claim_id.clone()).await {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                error!(error = %e, "veto_recovery failed");
+                Err(JsError::new(&e.to_string()).into())
+            }
+        }
+    }
+
+    /// The recovered secret and the devices that have sent invalid shares
+    pub async fn show_recovered(&self, pass_id: &MetaPasswordId) -> Result<JsValue, JsValue> {
         info!("Show recovered pass id: {:?}", pass_id);
         match self.app_manager.show_recovered(pass_id.clone()).await {
-            Ok(plain) => Ok(plain.text),
+            Ok(recovery) => Ok(serde_wasm_bindgen::to_value(&recovery)?),
             Err(e) => {
                 error!(error = %e, "show_recovered failed");
                 Err(JsError::new(&e.to_string()).into())
@@ -93,6 +118,44 @@ impl WasmApplicationManager {
         }
     }
 
+    /// Audit entries of the vault, `since` is the unix time in milliseconds,
+    /// `device` is a device id or name and `secret` is a password name
+    pub async fn audit_list(
+        &self,
+        since: Option<f64>,
+        device: Option<String>,
+        secret: Option<String>,
+    ) -> Result<JsValue, JsValue> {
+        let query = AuditQuery {
+            since: since.map(|since| since as u64),
+            device,
+            secret,
+        };
+
+        match self.app_manager.audit_list(query).await {
+            Ok(entries) => Ok(serde_wasm_bindgen::to_value(&entries)?),
+            Err(e) => {
+                error!(error = %e, "audit_list failed");
+                Err(JsError::new(&e.to_string()).into())
+            }
+        }
+    }
+
+    /// Check protocol compatibility with the server.
+    /// Rejects with the `UpgradeRequired` details if the app or the server is outdated.
+    pub async fn check_protocol(&self) -> Result<JsValue, JsValue> {
+        match self.app_manager.handshake().await {
+            Ok(handshake) => Ok(serde_wasm_bindgen::to_value(&handshake)?),
+            Err(e) => match e.downcast_ref::<UpgradeRequired>() {
+                Some(upgrade) => Err(serde_wasm_bindgen::to_value(upgrade)?),
+                None => {
+                    error!(error = %e, "check_protocol failed");
+                    Err(JsError::new(&e.to_string()).into())
+                }
+            },
+        }
+    }
+
     pub async fn clean_up_database(&self) {
         self.app_manager.clean_up_database().await
     }
@@ -1,5 +1,6 @@
 use crate::wasm_repo::WasmSyncProtocol;
 use meta_secret_core::crypto::keys::TransportSk;
+use meta_secret_core::node::app::auto_approval::AutoApprovalSource;
 use meta_secret_core::node::app::meta_app::meta_client_service::{
     MetaClientAccessProxy, MetaClientDataTransfer, MetaClientService, MetaClientStateProvider,
 };
@@ -7,7 +8,7 @@ use meta_secret_core::node::app::sync::sync_gateway::SyncGateway;
 use meta_secret_core::node::app::virtual_device::VirtualDevice;
 use meta_secret_core::node::common::data_transfer::MpscDataTransfer;
 use meta_secret_core::node::common::meta_tracing::vd_span;
-use meta_secret_core::node::common::model::device::common::DeviceName;
+use meta_secret_core::node::common::model::device::common::{DeviceName, DeviceType};
 use meta_secret_core::node::common::model::vault::vault::VaultName;
 use meta_secret_core::node::db::objects::persistent_object::PersistentObject;
 use meta_secret_core::node::db::repo::generic_db::KvLogEventRepo;
@@ -21,6 +22,7 @@ pub async fn virtual_device_setup<Repo: KvLogEventRepo>(
     device_repo: Arc<Repo>,
     sync_protocol: Arc<WasmSyncProtocol<Repo>>,
     master_key: TransportSk,
+    policy_source: AutoApprovalSource,
 ) -> anyhow::Result<()> {
     info!("virtual device initialization");
 
@@ -32,7 +34,11 @@ pub async fn virtual_device_setup<Repo: KvLogEventRepo>(
     };
 
     let user_creds = creds_repo
-        .get_or_generate_user_creds(DeviceName::virtual_device(), VaultName::test())
+        .get_or_generate_labeled_user_creds(
+            DeviceName::virtual_device(),
+            DeviceType::other(),
+            VaultName::test(),
+        )
         .await?;
     let device_creds = Arc::new(user_creds.device_creds.clone());
 
@@ -40,12 +46,12 @@ pub async fn virtual_device_setup<Repo: KvLogEventRepo>(
         dt: MpscDataTransfer::new(),
     });
 
-    let gateway = Arc::new(SyncGateway {
-        id: String::from("vd-gateway"),
-        p_obj: persistent_object.clone(),
-        sync: sync_protocol.clone(),
-        master_key: master_key.clone(),
-    });
+    let gateway = Arc::new(SyncGateway::new(
+        String::from("vd-gateway"),
+        persistent_object.clone(),
+        sync_protocol.clone(),
+        master_key.clone(),
+    ));
 
     let state_provider = Arc::new(MetaClientStateProvider::new());
     let meta_client_service = MetaClientService {
@@ -71,6 +77,7 @@ pub async fn virtual_device_setup<Repo: KvLogEventRepo>(
         meta_client_access_proxy,
         gateway,
         master_key,
+        policy_source,
     )
     .await?;
     
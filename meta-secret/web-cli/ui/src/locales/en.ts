@@ -41,6 +41,7 @@ export const vaultSecrets = {
   addSecretValidationSeedRequired: 'All seed words must be filled.',
   addSecretSubmitError: 'Could not add secret. Please try again.',
   errorShowRecovered: 'Could not display the recovered secret. Shares may be incomplete or corrupted.',
+  warningInvalidShares: 'The secret was recovered, but these devices have sent invalid shares:',
   errorCopySecret: 'Could not copy the secret.',
   errorRecoveryTimeout: 'Recovery took too long. Please try again.',
 } as const;